@@ -0,0 +1,76 @@
+//! Spawns a burst of 1000 blueprints as soon as the game starts and prints the worst frame time
+//! until they are all spawned. Run with `cargo run --release --example spawn_stress`.
+
+use bevy::{app::AppExit, prelude::*};
+use foxtrot::{GamePlugin, GameState, SpawnQueueDrained, SpawnRequest};
+use std::time::Duration;
+
+const GRID_SIZE: usize = 32;
+const OBJECT_COUNT: usize = 1000;
+const SPACING: f32 = 3.;
+const BLUEPRINTS: [&str; 2] = ["Orb", "House"];
+/// Blueprints finish spawning a few frames after their request was handled.
+const SETTLE_FRAMES: u32 = 30;
+
+fn main() {
+    App::new()
+        .add_plugins(GamePlugin)
+        .init_resource::<Measurement>()
+        .add_systems(OnEnter(GameState::Menu), skip_menu)
+        .add_systems(OnEnter(GameState::Playing), request_spawns)
+        .add_systems(
+            Last,
+            measure_frame_times.run_if(in_state(GameState::Playing)),
+        )
+        .run();
+}
+
+#[derive(Debug, Default, Resource)]
+struct Measurement {
+    frames: u32,
+    worst_frame_time: Duration,
+    frames_after_drain: Option<u32>,
+}
+
+fn skip_menu(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::Playing);
+}
+
+fn request_spawns(mut spawn_requests: EventWriter<SpawnRequest>) {
+    spawn_requests.send_batch((0..OBJECT_COUNT).map(|index| {
+        let (x, z) = (index % GRID_SIZE, index / GRID_SIZE);
+        let translation = Vec3::new(x as f32, 0., z as f32) * SPACING;
+        SpawnRequest::new(
+            BLUEPRINTS[index % BLUEPRINTS.len()],
+            Transform::from_translation(translation),
+        )
+    }));
+}
+
+fn measure_frame_times(
+    time: Res<Time<Real>>,
+    mut measurement: ResMut<Measurement>,
+    mut drained_events: EventReader<SpawnQueueDrained>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    measurement.frames += 1;
+    // The first frame includes the level transition
+    if measurement.frames > 1 {
+        measurement.worst_frame_time = measurement.worst_frame_time.max(time.delta());
+    }
+    if drained_events.read().next().is_some() {
+        measurement.frames_after_drain.get_or_insert(0);
+    }
+    let Some(frames_after_drain) = measurement.frames_after_drain.as_mut() else {
+        return;
+    };
+    *frames_after_drain += 1;
+    if *frames_after_drain >= SETTLE_FRAMES {
+        println!(
+            "Spawned {OBJECT_COUNT} objects over {} frames, worst frame time: {:.2} ms",
+            measurement.frames,
+            measurement.worst_frame_time.as_secs_f64() * 1000.
+        );
+        app_exit_events.send(AppExit);
+    }
+}
@@ -0,0 +1,163 @@
+//! Aggregates the sessions recorded by builds with the `telemetry` feature into counts and a death heatmap.
+//! Run with `cargo run --example telemetry_summary -- <folder> [heatmap.csv]`.
+//! The heatmap counts deaths per cell of the XZ plane and is written to `deaths.csv` in the folder by default.
+
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env, fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+/// The size of the heatmap's cells on the ground, in meters.
+const CELL_SIZE: f32 = 5.;
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let Some(folder) = args.next().map(PathBuf::from) else {
+        eprintln!("Usage: telemetry_summary <folder> [heatmap.csv]");
+        return ExitCode::FAILURE;
+    };
+    let heatmap_path = args
+        .next()
+        .map_or_else(|| folder.join("deaths.csv"), PathBuf::from);
+    match summarize(&folder, &heatmap_path) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Summary {
+    sessions: usize,
+    builds: BTreeSet<String>,
+    play_time: f64,
+    events: BTreeMap<String, usize>,
+    interactions: BTreeMap<String, usize>,
+    dialog_nodes: BTreeMap<String, (usize, usize)>,
+    death_causes: BTreeMap<String, usize>,
+    deaths: BTreeMap<(i32, i32), usize>,
+    levels: BTreeMap<String, usize>,
+    /// The sum of the durations and how often the flag changed.
+    flags: BTreeMap<String, (f64, usize)>,
+    skipped_lines: usize,
+}
+
+fn summarize(folder: &Path, heatmap_path: &Path) -> Result<(), String> {
+    let entries = fs::read_dir(folder)
+        .map_err(|error| format!("Failed to read {}: {error}", folder.display()))?;
+    let mut summary = Summary::default();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "jsonl")
+        {
+            match fs::read_to_string(&path) {
+                Ok(session) => summary.add_session(&session),
+                Err(error) => eprintln!("Skipping {}: {error}", path.display()),
+            }
+        }
+    }
+    summary.print();
+    fs::write(heatmap_path, summary.heatmap_csv())
+        .map_err(|error| format!("Failed to write {}: {error}", heatmap_path.display()))?;
+    println!("Wrote the death heatmap to {}", heatmap_path.display());
+    Ok(())
+}
+
+impl Summary {
+    fn add_session(&mut self, session: &str) {
+        self.sessions += 1;
+        let mut session_end: f64 = 0.;
+        for line in session.lines().filter(|line| !line.trim().is_empty()) {
+            // The last line of a session that crashed may be cut off
+            let Ok(event) = serde_json::from_str::<Value>(line) else {
+                self.skipped_lines += 1;
+                continue;
+            };
+            let text = |key: &str| event[key].as_str().unwrap_or("<unknown>").to_string();
+            session_end = session_end.max(event["time"].as_f64().unwrap_or_default());
+            let kind = text("kind");
+            *self.events.entry(kind.clone()).or_default() += 1;
+            match kind.as_str() {
+                "session_started" => {
+                    self.builds.insert(text("build"));
+                }
+                "interaction" => *self.interactions.entry(text("target")).or_default() += 1,
+                "dialog_node_started" => self.dialog_nodes.entry(text("node")).or_default().0 += 1,
+                "dialog_node_finished" => self.dialog_nodes.entry(text("node")).or_default().1 += 1,
+                "death" => {
+                    *self.death_causes.entry(text("cause")).or_default() += 1;
+                    let coordinate = |index: usize| {
+                        let value = event["position"][index].as_f64().unwrap_or_default() as f32;
+                        (value / CELL_SIZE).floor() as i32
+                    };
+                    *self
+                        .deaths
+                        .entry((coordinate(0), coordinate(2)))
+                        .or_default() += 1;
+                }
+                "level_reached" => *self.levels.entry(text("level")).or_default() += 1,
+                "flag_changed" => {
+                    let flag = self.flags.entry(text("flag")).or_default();
+                    flag.0 += event["duration"].as_f64().unwrap_or_default();
+                    flag.1 += 1;
+                }
+                _ => {}
+            }
+        }
+        self.play_time += session_end;
+    }
+
+    fn print(&self) {
+        let builds: Vec<_> = self.builds.iter().map(String::as_str).collect();
+        println!(
+            "{} sessions of builds {}, {:.1} minutes in total",
+            self.sessions,
+            builds.join(", "),
+            self.play_time / 60.
+        );
+        if self.skipped_lines > 0 {
+            println!(
+                "Skipped {} lines that were not valid JSON",
+                self.skipped_lines
+            );
+        }
+        print_counts("Events", &self.events);
+        print_counts("Interactions", &self.interactions);
+        println!("\nDialog nodes (started / finished):");
+        for (node, (started, finished)) in &self.dialog_nodes {
+            println!("  {node}: {started} / {finished}");
+        }
+        print_counts("Deaths", &self.death_causes);
+        print_counts("Levels reached", &self.levels);
+        println!("\nFlags (changes, average seconds since the previous flag):");
+        for (flag, (duration, count)) in &self.flags {
+            println!("  {flag}: {count}, {:.1}", duration / *count as f64);
+        }
+    }
+
+    /// One row per cell with at least one death. `x` and `z` are the cell's center.
+    fn heatmap_csv(&self) -> String {
+        let mut csv = String::from("x,z,deaths\n");
+        for ((x, z), count) in &self.deaths {
+            let center = |cell: i32| (cell as f32 + 0.5) * CELL_SIZE;
+            csv.push_str(&format!("{},{},{count}\n", center(*x), center(*z)));
+        }
+        csv
+    }
+}
+
+fn print_counts(title: &str, counts: &BTreeMap<String, usize>) {
+    println!("\n{title}:");
+    let mut counts: Vec<_> = counts.iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(a.1));
+    for (name, count) in counts {
+        println!("  {name}: {count}");
+    }
+}
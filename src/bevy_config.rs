@@ -1,3 +1,4 @@
+use crate::level_instantiation::static_geometry::transform_propagation_plugin;
 use anyhow::Context;
 use bevy::render::settings::{WgpuFeatures, WgpuSettings};
 use bevy::render::RenderPlugin;
@@ -7,9 +8,12 @@ use std::io::Cursor;
 use winit::window::Icon;
 
 /// Overrides the default Bevy plugins and configures things like the screen settings.
+/// The [`TransformPlugin`] is replaced by [`transform_propagation_plugin`], which skips static geometry.
 pub(super) fn plugin(app: &mut App) {
-    app.add_plugins(
+    app.add_plugins((
         DefaultPlugins
+            .build()
+            .disable::<TransformPlugin>()
             .set(WindowPlugin {
                 primary_window: Some(Window {
                     title: "Foxtrot".to_string(),
@@ -21,7 +25,8 @@ pub(super) fn plugin(app: &mut App) {
                 render_creation: create_wgpu_settings().into(),
                 synchronous_pipeline_compilation: false,
             }),
-    )
+        transform_propagation_plugin,
+    ))
     .insert_resource(Msaa::Sample4)
     .insert_resource(ClearColor(Color::rgb(0.4, 0.4, 0.4)))
     .add_systems(Startup, set_window_icon);
@@ -0,0 +1,63 @@
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use std::{path::PathBuf, time::Duration};
+
+mod replay;
+pub(crate) mod rng;
+
+pub(crate) use rng::{fnv1a, GameRng, RngStream};
+
+/// Frame time used by every frame while [`LaunchOptions::deterministic`] is set.
+const DETERMINISTIC_FRAME_TIME: f64 = 1. / 60.;
+
+/// Makes runs reproducible. All randomness goes through [`GameRng`], whose seed is logged on startup.
+/// Passing `--deterministic` advances time by a fixed step every frame, so that the same seed and the
+/// same input produce the same run. The input can be recorded with `--record-input <path>` and played back
+/// with `--replay-input <path>`, optionally with `--exit-after-replay` for headless regression runs.
+pub(super) fn plugin(app: &mut App) {
+    let options = LaunchOptions::from_args(std::env::args().skip(1));
+    if options.deterministic {
+        info!("Running in deterministic mode");
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            DETERMINISTIC_FRAME_TIME,
+        )));
+    }
+    app.register_type::<GameRng>()
+        .insert_resource(options)
+        // The replay needs to run first, since a recording overrides the seed
+        .add_plugins((replay::plugin, rng::plugin));
+}
+
+/// Options passed on the command line.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Resource)]
+pub(crate) struct LaunchOptions {
+    pub(crate) seed: Option<u64>,
+    pub(crate) deterministic: bool,
+    pub(crate) record_input: Option<PathBuf>,
+    pub(crate) replay_input: Option<PathBuf>,
+    pub(crate) exit_after_replay: bool,
+}
+
+impl LaunchOptions {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Self {
+        let mut options = Self::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => match args.next().map(|seed| seed.parse()) {
+                    Some(Ok(seed)) => options.seed = Some(seed),
+                    _ => warn!("--seed expects an unsigned integer"),
+                },
+                "--deterministic" => options.deterministic = true,
+                "--record-input" => options.record_input = args.next().map(PathBuf::from),
+                "--replay-input" => options.replay_input = args.next().map(PathBuf::from),
+                "--exit-after-replay" => options.exit_after_replay = true,
+                _ => {}
+            }
+        }
+        if options.seed.is_none() {
+            options.seed = std::env::var("FOXTROT_SEED")
+                .ok()
+                .and_then(|seed| seed.parse().ok());
+        }
+        options
+    }
+}
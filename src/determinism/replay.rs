@@ -0,0 +1,195 @@
+use crate::{
+    determinism::{GameRng, LaunchOptions},
+    player_control::actions::{CameraAction, PlayerAction, UiAction},
+    GameState,
+};
+use anyhow::Context;
+use bevy::{app::AppExit, prelude::*};
+use bevy_mod_sysfail::prelude::*;
+use leafwing_input_manager::{
+    buttonlike::ButtonState, plugin::InputManagerSystem, prelude::ActionState, Actionlike,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Records the input of every frame spent playing to a file, or plays such a file back instead of the real input.
+/// A recording remembers its seed, so playing it back with `--deterministic` reproduces the recorded run.
+pub(super) fn plugin(app: &mut App) {
+    let options = app.world.resource::<LaunchOptions>().clone();
+    if let Some(path) = options.replay_input {
+        match load_recording(&path) {
+            Ok(recording) => {
+                info!("Replaying input from {}", path.display());
+                app.world.resource_mut::<LaunchOptions>().seed = Some(recording.seed);
+                app.insert_resource(InputPlayback {
+                    recording,
+                    frame: 0,
+                    exit_when_done: options.exit_after_replay,
+                })
+                .add_systems(
+                    Update,
+                    play_back_input
+                        .before(InputManagerSystem::ManualControl)
+                        .run_if(in_state(GameState::Playing)),
+                );
+            }
+            Err(error) => error!("Failed to load input recording: {error:?}"),
+        }
+    } else if let Some(path) = options.record_input {
+        info!("Recording input to {}", path.display());
+        app.insert_resource(InputRecorder {
+            path,
+            recording: default(),
+        })
+        .add_systems(
+            Update,
+            record_input
+                .before(InputManagerSystem::ManualControl)
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(Last, save_recording_on_exit);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+struct InputRecording {
+    seed: u64,
+    frames: Vec<InputFrame>,
+}
+
+/// Only holds actions that are not in their default state.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+struct InputFrame {
+    player: Vec<(PlayerAction, RecordedAction)>,
+    camera: Vec<(CameraAction, RecordedAction)>,
+    ui: Vec<(UiAction, RecordedAction)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct RecordedAction {
+    state: ButtonState,
+    value: f32,
+    axis_pair: Option<Vec2>,
+}
+
+#[derive(Debug, Resource)]
+struct InputRecorder {
+    path: PathBuf,
+    recording: InputRecording,
+}
+
+#[derive(Debug, Resource)]
+struct InputPlayback {
+    recording: InputRecording,
+    frame: usize,
+    exit_when_done: bool,
+}
+
+fn load_recording(path: &Path) -> anyhow::Result<InputRecording> {
+    let serialized =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    ron::from_str(&serialized).context("Failed to deserialize input recording")
+}
+
+fn record_input(
+    mut recorder: ResMut<InputRecorder>,
+    game_rng: Res<GameRng>,
+    player_actions: Query<&ActionState<PlayerAction>>,
+    camera_actions: Query<&ActionState<CameraAction>>,
+    ui_actions: Query<&ActionState<UiAction>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("record_input").entered();
+    recorder.recording.seed = game_rng.seed();
+    let frame = InputFrame {
+        player: snapshot(player_actions.iter().next()),
+        camera: snapshot(camera_actions.iter().next()),
+        ui: snapshot(ui_actions.iter().next()),
+    };
+    recorder.recording.frames.push(frame);
+}
+
+fn snapshot<A: Actionlike>(actions: Option<&ActionState<A>>) -> Vec<(A, RecordedAction)> {
+    let Some(actions) = actions else {
+        return default();
+    };
+    actions
+        .all_action_data()
+        .iter()
+        .filter(|(_, data)| {
+            data.state != ButtonState::Released || data.value != 0. || data.axis_pair.is_some()
+        })
+        .map(|(action, data)| {
+            let recorded = RecordedAction {
+                state: data.state,
+                value: data.value,
+                axis_pair: data.axis_pair.map(|axis| axis.xy()),
+            };
+            (action.clone(), recorded)
+        })
+        .collect()
+}
+
+#[sysfail(Log<anyhow::Error, Error>)]
+fn save_recording_on_exit(mut exit_events: EventReader<AppExit>, recorder: Res<InputRecorder>) {
+    if exit_events.read().next().is_none() {
+        return Ok(());
+    }
+    let serialized = ron::ser::to_string_pretty(&recorder.recording, default())
+        .context("Failed to serialize input recording")?;
+    fs::write(&recorder.path, serialized)
+        .with_context(|| format!("Failed to write {}", recorder.path.display()))?;
+    info!(
+        "Saved {} frames of input to {}",
+        recorder.recording.frames.len(),
+        recorder.path.display()
+    );
+}
+
+fn play_back_input(
+    mut playback: ResMut<InputPlayback>,
+    mut player_actions: Query<&mut ActionState<PlayerAction>>,
+    mut camera_actions: Query<&mut ActionState<CameraAction>>,
+    mut ui_actions: Query<&mut ActionState<UiAction>>,
+    mut exit_events: EventWriter<AppExit>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("play_back_input").entered();
+    let Some(frame) = playback.recording.frames.get(playback.frame).cloned() else {
+        if playback.frame == playback.recording.frames.len() {
+            info!("Finished replaying {} frames", playback.frame);
+            playback.frame += 1;
+            if playback.exit_when_done {
+                exit_events.send(AppExit);
+            }
+        }
+        return;
+    };
+    playback.frame += 1;
+    if let Some(mut actions) = player_actions.iter_mut().next() {
+        apply(&mut actions, &frame.player);
+    }
+    if let Some(mut actions) = camera_actions.iter_mut().next() {
+        apply(&mut actions, &frame.camera);
+    }
+    if let Some(mut actions) = ui_actions.iter_mut().next() {
+        apply(&mut actions, &frame.ui);
+    }
+}
+
+/// Overwrites the state written by the real input devices.
+fn apply<A: Actionlike>(actions: &mut ActionState<A>, recorded: &[(A, RecordedAction)]) {
+    let all_actions: Vec<A> = actions.all_action_data().keys().cloned().collect();
+    for action in all_actions {
+        *actions.action_data_mut_or_default(&action) = default();
+    }
+    for (action, recorded) in recorded {
+        let data = actions.action_data_mut_or_default(action);
+        data.state = recorded.state;
+        data.value = recorded.value;
+        data.axis_pair = recorded.axis_pair.map(Into::into);
+    }
+}
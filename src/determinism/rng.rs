@@ -0,0 +1,124 @@
+use crate::determinism::LaunchOptions;
+use bevy::prelude::*;
+use std::ops::Range;
+
+pub(super) fn plugin(app: &mut App) {
+    let seed = app
+        .world
+        .resource::<LaunchOptions>()
+        .seed
+        .unwrap_or_else(seed_from_time);
+    info!("Random seed: {seed}. Pass `--seed {seed}` to reproduce this run.");
+    app.insert_resource(GameRng::new(seed));
+}
+
+/// The source of all randomness in the game. Systems do not draw from it directly,
+/// but [`fork`](GameRng::fork) their own [`RngStream`], usually stored in a [`Local`]:
+/// ```ignore
+/// fn scatter(game_rng: Res<GameRng>, mut rng: Local<Option<RngStream>>) {
+///     let rng = rng.get_or_insert_with(|| game_rng.fork("scatter"));
+///     let offset = rng.range_f32(-1.0..1.0);
+/// }
+/// ```
+/// That way, the numbers a system gets do not depend on which other systems ran before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource, Reflect)]
+#[reflect(Resource)]
+pub(crate) struct GameRng {
+    seed: u64,
+}
+
+impl GameRng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    pub(crate) fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The same seed and stream name always produce the same sequence.
+    pub(crate) fn fork(&self, stream: &str) -> RngStream {
+        RngStream::new(self.seed ^ fnv1a(stream.as_bytes()))
+    }
+
+    /// Like [`GameRng::fork`], but additionally distinguishes between entities of the same stream.
+    /// Uses the entity's index, so it is only stable when entities are spawned in the same order.
+    pub(crate) fn fork_for(&self, stream: &str, entity: Entity) -> RngStream {
+        RngStream::new(
+            self.seed ^ fnv1a(stream.as_bytes()) ^ u64::from(entity.index()).rotate_left(32),
+        )
+    }
+}
+
+/// A SplitMix64 generator. Fast and good enough for gameplay, not for cryptography.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RngStream {
+    state: u64,
+}
+
+impl RngStream {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0.0..1.0`.
+    pub(crate) fn f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub(crate) fn range_f32(&mut self, range: Range<f32>) -> f32 {
+        range.start + self.f32() * (range.end - range.start)
+    }
+
+    /// Returns `range.start` for empty ranges.
+    pub(crate) fn range_usize(&mut self, range: Range<usize>) -> usize {
+        let len = range.len() as u64;
+        if len == 0 {
+            return range.start;
+        }
+        range.start + (self.next_u64() % len) as usize
+    }
+
+    pub(crate) fn chance(&mut self, probability: f32) -> bool {
+        self.f32() < probability
+    }
+
+    pub(crate) fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        items.get(self.range_usize(0..items.len()))
+    }
+
+    /// A random direction on the horizontal plane.
+    pub(crate) fn horizontal_direction(&mut self) -> Vec3 {
+        let angle = self.range_f32(0.0..std::f32::consts::TAU);
+        Vec3::new(angle.cos(), 0., angle.sin())
+    }
+}
+
+/// Unlike the hashers in `std`, this is guaranteed to stay the same across platforms and Rust versions.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01B3)
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn seed_from_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos() as u64)
+}
+
+// `SystemTime` panics on the web, so the hasher's random keys are used instead
+#[cfg(target_arch = "wasm32")]
+fn seed_from_time() -> u64 {
+    use std::hash::BuildHasher;
+    bevy::utils::RandomState::new().hash_one(0_u8)
+}
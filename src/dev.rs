@@ -6,9 +6,20 @@ use bevy_editor_pls::prelude::*;
 use bevy_xpbd_3d::prelude::*;
 
 pub(crate) mod dev_editor;
+pub(crate) mod dev_tools;
+mod environment;
+mod frame_rate;
+mod gameplay_log;
+mod level_bounds;
+mod npc_memory_console;
+mod particle_emitters;
+mod quick_pick;
+mod spectator;
+mod tag_console;
 
 /// Plugin with debugging utility intended for use during development only.
 /// Don't include this in a release build.
+/// Debug features register themselves with [`dev_tools::plugin`], which binds them to hotkeys and lists them in the dev menu.
 pub(super) fn plugin(app: &mut App) {
     {
         app.add_plugins(EditorPlugin::new())
@@ -16,8 +27,18 @@ pub(super) fn plugin(app: &mut App) {
             .add_plugins((
                 FrameTimeDiagnosticsPlugin,
                 dev_editor::plugin,
+                dev_tools::plugin,
+                environment::plugin,
+                frame_rate::plugin,
+                gameplay_log::plugin,
+                level_bounds::plugin,
                 LogDiagnosticsPlugin::filtered(vec![]),
+                npc_memory_console::plugin,
+                particle_emitters::plugin,
                 PhysicsDebugPlugin::default(),
+                quick_pick::plugin,
+                spectator::plugin,
+                tag_console::plugin,
             ))
             .insert_gizmo_group(
                 PhysicsGizmos {
@@ -1,13 +1,12 @@
-use crate::{player_control::camera::ForceCursorGrabMode, GameState};
-use anyhow::Context;
-use bevy::{prelude::*, window::CursorGrabMode};
-use bevy_editor_pls::{
-    editor::{Editor, EditorEvent},
-    editor_window::EditorWindow,
-    AddEditorWindow,
+use crate::{
+    dev::dev_tools::{DevTools, RegisterDevToolExt},
+    level_instantiation::spawn_queue::BlueprintVariants,
+    player_control::camera::CursorGrabRequests,
+    GameState,
 };
+use bevy::prelude::*;
+use bevy_editor_pls::{editor::EditorEvent, editor_window::EditorWindow, AddEditorWindow};
 use bevy_egui::egui;
-use bevy_mod_sysfail::prelude::*;
 use bevy_xpbd_3d::prelude::PhysicsGizmos;
 use serde::{Deserialize, Serialize};
 
@@ -16,8 +15,9 @@ pub(super) fn plugin(app: &mut App) {
         .add_editor_window::<DevEditorWindow>()
         .add_systems(
             Update,
-            (handle_debug_render, set_cursor_grab_mode).run_if(in_state(GameState::Playing)),
-        );
+            set_cursor_grab_mode.run_if(in_state(GameState::Playing)),
+        )
+        .register_dev_tool("Colliders", Some(KeyCode::F3), toggle_collider_render);
 }
 
 pub(crate) struct DevEditorWindow;
@@ -27,7 +27,7 @@ impl EditorWindow for DevEditorWindow {
     const NAME: &'static str = "Foxtrot Dev";
     const DEFAULT_SIZE: (f32, f32) = (200., 150.);
     fn ui(
-        _world: &mut World,
+        world: &mut World,
         mut cx: bevy_editor_pls::editor_window::EditorWindowContext,
         ui: &mut egui::Ui,
     ) {
@@ -36,9 +36,29 @@ impl EditorWindow for DevEditorWindow {
             .expect("Failed to get dev window state");
 
         state.open = true;
-        ui.heading("Debug Rendering");
-        ui.checkbox(&mut state.collider_render_enabled, "Colliders");
-        ui.checkbox(&mut state.navmesh_render_enabled, "Navmeshes");
+        ui.heading("Dev Tools");
+        let mut tools = world.resource_mut::<DevTools>();
+        let mut toggled = Vec::new();
+        for (index, name, mut active) in tools.iter() {
+            if ui.checkbox(&mut active, name).changed() {
+                toggled.push(index);
+            }
+        }
+        for index in toggled {
+            tools.request_toggle(index);
+        }
+
+        let variant_tables = world.resource::<Assets<BlueprintVariants>>();
+        if let Some((_, table)) = variant_tables.iter().next() {
+            ui.collapsing("Blueprint Variants", |ui| {
+                let mut blueprints: Vec<_> = table.variants.iter().collect();
+                blueprints.sort_by_key(|(blueprint, _)| *blueprint);
+                for (blueprint, variants) in blueprints {
+                    ui.label(format!("{blueprint}: {} variants", variants.len()))
+                        .on_hover_text(variants.join(", "));
+                }
+            });
+        }
     }
 }
 
@@ -47,38 +67,23 @@ impl EditorWindow for DevEditorWindow {
 #[derive(Default)]
 pub(crate) struct DevEditorState {
     pub(crate) open: bool,
-    pub(crate) collider_render_enabled: bool,
-    pub(crate) navmesh_render_enabled: bool,
 }
 
-#[sysfail(Log<anyhow::Error, Error>)]
-fn handle_debug_render(
-    state: Res<Editor>,
-    mut last_enabled: Local<bool>,
-    mut config_store: ResMut<GizmoConfigStore>,
-) {
-    let current_enabled = state
-        .window_state::<DevEditorWindow>()
-        .context("Failed to read dev window state")?
-        .collider_render_enabled;
-    if current_enabled == *last_enabled {
-        return Ok(());
-    }
-    *last_enabled = current_enabled;
+fn toggle_collider_render(In(active): In<bool>, mut config_store: ResMut<GizmoConfigStore>) {
     let config = config_store.config_mut::<PhysicsGizmos>().0;
-    config.enabled = current_enabled;
+    config.enabled = active;
 }
 
 fn set_cursor_grab_mode(
     mut events: EventReader<EditorEvent>,
-    mut force_cursor_grab: ResMut<ForceCursorGrabMode>,
+    mut cursor_grab: ResMut<CursorGrabRequests>,
 ) {
     for event in events.read() {
         if let EditorEvent::Toggle { now_active } = event {
             if *now_active {
-                force_cursor_grab.0 = Some(CursorGrabMode::None);
+                cursor_grab.request_free();
             } else {
-                force_cursor_grab.0 = None;
+                cursor_grab.release();
             }
         }
     }
@@ -0,0 +1,243 @@
+use crate::{
+    player_control::{camera::CursorGrabRequests, ui_layer::UiLayer},
+    GameState,
+};
+use bevy::{ecs::system::SystemId, prelude::*};
+use bevy_egui::{egui, EguiContexts};
+use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Collects all debug features in one place. Each tool registers itself with [`RegisterDevToolExt::register_dev_tool`],
+/// which gives it a rebindable [`DevAction`] and an entry in the dev menu opened with F1.
+/// All tools are turned off when leaving [`GameState::Playing`].
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<DevAction>()
+        .add_plugins(InputManagerPlugin::<DevAction>::default())
+        .init_resource::<DevTools>()
+        .init_resource::<ActionState<DevAction>>();
+    app.world.get_resource_or_insert_with(default_input_map);
+    app.add_systems(
+        Update,
+        (
+            toggle_tools_from_input,
+            rebind_tool,
+            display_dev_menu,
+            apply_toggles,
+        )
+            .chain()
+            .run_if(in_state(GameState::Playing)),
+    )
+    .add_systems(OnExit(GameState::Playing), close_all_tools);
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Actionlike, Reflect, Serialize, Deserialize)]
+pub(crate) enum DevAction {
+    ToggleMenu,
+    /// Toggles the tool at this index in [`DevTools`].
+    Tool(usize),
+}
+
+pub(crate) trait RegisterDevToolExt {
+    /// Adds a debug tool to the dev menu. `toggle_system` runs with the new state whenever the tool is turned on or off.
+    fn register_dev_tool<M>(
+        &mut self,
+        name: &'static str,
+        default_key: Option<KeyCode>,
+        toggle_system: impl IntoSystem<bool, (), M> + 'static,
+    ) -> &mut Self;
+}
+
+impl RegisterDevToolExt for App {
+    fn register_dev_tool<M>(
+        &mut self,
+        name: &'static str,
+        default_key: Option<KeyCode>,
+        toggle_system: impl IntoSystem<bool, (), M> + 'static,
+    ) -> &mut Self {
+        let system = self.world.register_system(toggle_system);
+        let mut tools = self.world.get_resource_or_insert_with(DevTools::default);
+        if tools.tools.iter().any(|tool| tool.name == name) {
+            warn!("Dev tool \"{name}\" is registered twice");
+        }
+        let index = tools.tools.len();
+        tools.tools.push(DevTool {
+            name,
+            active: false,
+            system,
+        });
+        if let Some(key) = default_key {
+            self.world
+                .get_resource_or_insert_with(default_input_map)
+                .insert(DevAction::Tool(index), key);
+        }
+        self
+    }
+}
+
+/// All registered dev tools and the state of the dev menu.
+#[derive(Debug, Default, Resource)]
+pub(crate) struct DevTools {
+    tools: Vec<DevTool>,
+    menu_open: bool,
+    /// The tool waiting for a key to be bound to it.
+    rebinding: Option<usize>,
+    /// Tools to toggle at the end of this frame.
+    pending_toggles: Vec<usize>,
+}
+
+#[derive(Debug)]
+struct DevTool {
+    name: &'static str,
+    active: bool,
+    system: SystemId<bool>,
+}
+
+impl DevTools {
+    pub(crate) fn is_active(&self, name: &str) -> bool {
+        self.tools
+            .iter()
+            .any(|tool| tool.name == name && tool.active)
+    }
+
+    /// The index, name and state of every tool.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (usize, &'static str, bool)> + '_ {
+        self.tools
+            .iter()
+            .enumerate()
+            .map(|(index, tool)| (index, tool.name, tool.active))
+    }
+
+    /// The tool is toggled at the end of the frame.
+    pub(crate) fn request_toggle(&mut self, index: usize) {
+        if !self.pending_toggles.contains(&index) {
+            self.pending_toggles.push(index);
+        }
+    }
+}
+
+fn default_input_map() -> InputMap<DevAction> {
+    InputMap::new([(DevAction::ToggleMenu, KeyCode::F1)])
+}
+
+fn toggle_tools_from_input(
+    actions: Res<ActionState<DevAction>>,
+    mut tools: ResMut<DevTools>,
+    mut cursor_grab: ResMut<CursorGrabRequests>,
+) {
+    // Keys pressed while rebinding belong to the new binding
+    if tools.rebinding.is_some() {
+        return;
+    }
+    if actions.just_pressed(&DevAction::ToggleMenu) {
+        tools.menu_open = !tools.menu_open;
+        if tools.menu_open {
+            cursor_grab.request_free();
+        } else {
+            cursor_grab.release();
+        }
+    }
+    for index in 0..tools.tools.len() {
+        if actions.just_pressed(&DevAction::Tool(index)) {
+            tools.request_toggle(index);
+        }
+    }
+}
+
+fn rebind_tool(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut tools: ResMut<DevTools>,
+    mut input_map: ResMut<InputMap<DevAction>>,
+) {
+    let Some(index) = tools.rebinding else {
+        return;
+    };
+    let Some(&key) = keys.get_just_pressed().next() else {
+        return;
+    };
+    tools.rebinding = None;
+    let action = DevAction::Tool(index);
+    input_map.clear_action(&action);
+    // Escape only cancels
+    if key != KeyCode::Escape {
+        input_map.insert(action, key);
+    }
+}
+
+fn display_dev_menu(
+    mut tools: ResMut<DevTools>,
+    input_map: Res<InputMap<DevAction>>,
+    mut egui_contexts: EguiContexts,
+) {
+    if !tools.menu_open {
+        return;
+    }
+    let mut toggled = Vec::new();
+    let mut rebinding = tools.rebinding;
+    egui::Window::new("Dev Tools")
+        .order(UiLayer::Dev.order())
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::LEFT_TOP, egui::vec2(10., 10.))
+        .show(egui_contexts.ctx_mut(), |ui| {
+            egui::Grid::new("Dev Tools Grid")
+                .num_columns(3)
+                .striped(true)
+                .show(ui, |ui| {
+                    for (index, tool) in tools.tools.iter().enumerate() {
+                        let mut active = tool.active;
+                        if ui.checkbox(&mut active, tool.name).changed() {
+                            toggled.push(index);
+                        }
+                        let binding = input_map
+                            .get(&DevAction::Tool(index))
+                            .filter(|inputs| !inputs.is_empty())
+                            .map_or_else(|| "Unbound".to_string(), |inputs| format!("{inputs:?}"));
+                        ui.label(binding);
+                        let label = if rebinding == Some(index) {
+                            "Press a key..."
+                        } else {
+                            "Rebind"
+                        };
+                        if ui.button(label).clicked() {
+                            rebinding = Some(index);
+                        }
+                        ui.end_row();
+                    }
+                });
+            if tools.tools.is_empty() {
+                ui.label("No dev tools registered");
+            }
+        });
+    tools.rebinding = rebinding;
+    for index in toggled {
+        tools.request_toggle(index);
+    }
+}
+
+fn apply_toggles(mut commands: Commands, mut tools: ResMut<DevTools>) {
+    let pending = std::mem::take(&mut tools.pending_toggles);
+    for index in pending {
+        let Some(tool) = tools.tools.get_mut(index) else {
+            continue;
+        };
+        tool.active = !tool.active;
+        commands.run_system_with_input(tool.system, tool.active);
+    }
+}
+
+fn close_all_tools(
+    mut commands: Commands,
+    mut tools: ResMut<DevTools>,
+    mut cursor_grab: ResMut<CursorGrabRequests>,
+) {
+    if tools.menu_open {
+        tools.menu_open = false;
+        cursor_grab.release();
+    }
+    tools.rebinding = None;
+    tools.pending_toggles.clear();
+    for tool in tools.tools.iter_mut().filter(|tool| tool.active) {
+        tool.active = false;
+        commands.run_system_with_input(tool.system, false);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::{
+    dev::dev_tools::{DevTools, RegisterDevToolExt},
+    level_instantiation::{
+        environment::EnvironmentSettings,
+        level_overrides::LevelOverrideTable,
+        map::{CurrentLevel, LevelRoot},
+    },
+    player_control::ui_layer::UiLayer,
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+/// Lets the [`EnvironmentSettings`] of the current level be edited live.
+/// Since levels are authored in Blender, the edited settings are saved to the [`LevelOverrideTable`] instead of the level file.
+pub(super) fn plugin(app: &mut App) {
+    app.register_dev_tool("Environment", None, |_: In<bool>| {})
+        .add_systems(
+            Update,
+            edit_environment.run_if(
+                in_state(GameState::Playing)
+                    .and_then(|dev_tools: Res<DevTools>| dev_tools.is_active("Environment")),
+            ),
+        );
+}
+
+fn edit_environment(
+    mut commands: Commands,
+    current_level: Res<CurrentLevel>,
+    mut settings: Query<(Entity, &mut EnvironmentSettings)>,
+    parents: Query<&Parent>,
+    level_roots: Query<(Entity, &LevelRoot)>,
+    override_tables: Res<Assets<LevelOverrideTable>>,
+    mut egui_contexts: EguiContexts,
+) {
+    let level_of = |entity: Entity| {
+        std::iter::once(entity)
+            .chain(parents.iter_ancestors(entity))
+            .find_map(|entity| level_roots.get(entity).ok())
+            .map(|(_, root)| root.0.clone())
+    };
+    let current = settings
+        .iter_mut()
+        .find(|(entity, ..)| level_of(*entity).as_ref() == Some(&current_level.0));
+    egui::Window::new("Environment")
+        .order(UiLayer::Dev.order())
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-10., 10.))
+        .show(egui_contexts.ctx_mut(), |ui| {
+            let Some((_, mut settings)) = current else {
+                ui.label("The current level has no environment settings.");
+                let root = level_roots
+                    .iter()
+                    .find(|(_, root)| root.0 == current_level.0);
+                if let Some((root, _)) = root {
+                    if ui.button("Add").clicked() {
+                        commands.entity(root).insert(EnvironmentSettings::default());
+                    }
+                }
+                return;
+            };
+            let mut edited = settings.clone();
+            egui::Grid::new("Environment Grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    optional_row(
+                        ui,
+                        "Ambient color",
+                        &mut edited.ambient_color,
+                        Color::WHITE,
+                        color_edit,
+                    );
+                    optional_row(
+                        ui,
+                        "Ambient brightness",
+                        &mut edited.ambient_brightness,
+                        80.,
+                        |ui, value| {
+                            ui.add(
+                                egui::DragValue::new(value)
+                                    .speed(1.)
+                                    .clamp_range(0.0..=f32::MAX),
+                            );
+                        },
+                    );
+                    optional_row(
+                        ui,
+                        "Sun direction",
+                        &mut edited.sun_direction,
+                        Vec3::NEG_Y,
+                        |ui, value| {
+                            ui.horizontal(|ui| {
+                                for component in [&mut value.x, &mut value.y, &mut value.z] {
+                                    ui.add(
+                                        egui::DragValue::new(component)
+                                            .speed(0.01)
+                                            .clamp_range(-1.0..=1.0),
+                                    );
+                                }
+                            });
+                        },
+                    );
+                    optional_row(
+                        ui,
+                        "Fog color",
+                        &mut edited.fog_color,
+                        Color::WHITE,
+                        color_edit,
+                    );
+                    optional_row(ui, "Fog start", &mut edited.fog_start, 0., distance_edit);
+                    optional_row(ui, "Fog end", &mut edited.fog_end, 100., distance_edit);
+                    optional_row(
+                        ui,
+                        "Clear color",
+                        &mut edited.clear_color,
+                        Color::GRAY,
+                        color_edit,
+                    );
+                });
+            if ui
+                .button("Save")
+                .on_hover_text("Save the settings to assets/config/config.levels.ron")
+                .clicked()
+            {
+                if let Err(error) =
+                    LevelOverrideTable::save(&override_tables, &current_level.0, |overrides| {
+                        overrides.environment = Some(edited.clone());
+                    })
+                {
+                    error!("Failed to save the environment settings: {error:?}");
+                }
+            }
+            if *settings != edited {
+                *settings = edited;
+            }
+        });
+}
+
+/// A row with a checkbox that decides whether the value is set, and an editor for the value if it is.
+/// `default` is what a newly set value starts with.
+fn optional_row<T: Copy>(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut Option<T>,
+    default: T,
+    edit: impl FnOnce(&mut egui::Ui, &mut T),
+) {
+    let mut is_set = value.is_some();
+    ui.checkbox(&mut is_set, label);
+    match (is_set, value.as_mut()) {
+        (true, Some(inner)) => edit(ui, inner),
+        (true, None) => *value = Some(default),
+        (false, _) => *value = None,
+    }
+    ui.end_row();
+}
+
+fn color_edit(ui: &mut egui::Ui, color: &mut Color) {
+    let mut rgb = [color.r(), color.g(), color.b()];
+    if ui.color_edit_button_rgb(&mut rgb).changed() {
+        *color = Color::rgb(rgb[0], rgb[1], rgb[2]);
+    }
+}
+
+fn distance_edit(ui: &mut egui::Ui, value: &mut f32) {
+    ui.add(
+        egui::DragValue::new(value)
+            .speed(0.5)
+            .clamp_range(0.0..=f32::MAX),
+    );
+}
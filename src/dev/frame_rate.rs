@@ -0,0 +1,127 @@
+use crate::{
+    dev::dev_tools::{DevTools, RegisterDevToolExt},
+    file_system_interaction::config::{GameConfig, Graphics, GraphicsPreset},
+    level_instantiation::static_geometry::StaticFrozen,
+    player_control::ui_layer::UiLayer,
+    GameState,
+};
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+use bevy_egui::{egui, EguiContexts};
+
+const PRESETS: [GraphicsPreset; 4] = [
+    GraphicsPreset::Low,
+    GraphicsPreset::Medium,
+    GraphicsPreset::High,
+    GraphicsPreset::Custom,
+];
+
+/// Shows the frame rate next to the average frame rate under the previous graphics settings,
+/// to check what switching the [`GraphicsPreset`] costs. The preset can be switched from the overlay.
+/// Also counts how many entities have their transforms propagated every frame and how many are [`StaticFrozen`].
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<FrameRateHistory>()
+        .register_dev_tool("Frame Rate", Some(KeyCode::F7), |_: In<bool>| {})
+        .add_systems(
+            Update,
+            (
+                record_frame_rate,
+                display_frame_rate
+                    .run_if(|dev_tools: Res<DevTools>| dev_tools.is_active("Frame Rate")),
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing).and_then(resource_exists::<GameConfig>)),
+        );
+}
+
+#[derive(Debug, Default, Resource)]
+struct FrameRateHistory {
+    /// The graphics settings the frames since the last change were rendered with.
+    settings: Option<Graphics>,
+    /// The preset and average frame rate before the last change of the settings.
+    previous: Option<(GraphicsPreset, f64)>,
+    frames: u32,
+    /// In seconds.
+    elapsed: f64,
+}
+
+impl FrameRateHistory {
+    fn average(&self) -> Option<f64> {
+        (self.elapsed > 0.).then(|| f64::from(self.frames) / self.elapsed)
+    }
+}
+
+fn record_frame_rate(
+    time: Res<Time<Real>>,
+    config: Res<GameConfig>,
+    mut history: ResMut<FrameRateHistory>,
+) {
+    let settings = config.graphics.resolve();
+    if history.settings != Some(settings) {
+        let previous_preset = history.settings.map(|settings| settings.preset);
+        history.previous = previous_preset.zip(history.average()).or(history.previous);
+        history.settings = Some(settings);
+        history.frames = 0;
+        history.elapsed = 0.;
+        // The frame the settings changed in is usually a slow one
+        return;
+    }
+    history.frames += 1;
+    history.elapsed += time.delta_seconds_f64();
+}
+
+fn display_frame_rate(
+    mut config: ResMut<GameConfig>,
+    history: Res<FrameRateHistory>,
+    diagnostics: Res<DiagnosticsStore>,
+    transforms: Query<Has<StaticFrozen>, With<GlobalTransform>>,
+    mut egui_contexts: EguiContexts,
+) {
+    let fps = diagnostics
+        .get(&FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed());
+    let frozen = transforms.iter().filter(|&is_frozen| is_frozen).count();
+    let dynamic = transforms.iter().len() - frozen;
+    let mut preset = config.graphics.preset;
+    egui::Window::new("Frame Rate")
+        .order(UiLayer::Dev.order())
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-10., 10.))
+        .show(egui_contexts.ctx_mut(), |ui| {
+            egui::Grid::new("Frame Rate Grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Current");
+                    ui.label(fps.map_or_else(|| "-".to_string(), |fps| format!("{fps:.1} FPS")));
+                    ui.end_row();
+                    ui.label(format!("Average with {preset:?}"));
+                    ui.label(
+                        history
+                            .average()
+                            .map_or_else(|| "-".to_string(), |average| format!("{average:.1} FPS")),
+                    );
+                    ui.end_row();
+                    if let Some((previous_preset, average)) = history.previous {
+                        ui.label(format!("Average with {previous_preset:?} before"));
+                        ui.label(format!("{average:.1} FPS"));
+                        ui.end_row();
+                    }
+                    ui.label("Transforms");
+                    ui.label(format!("{dynamic} dynamic, {frozen} frozen"));
+                    ui.end_row();
+                });
+            egui::ComboBox::from_label("Preset")
+                .selected_text(format!("{preset:?}"))
+                .show_ui(ui, |ui| {
+                    for option in PRESETS {
+                        ui.selectable_value(&mut preset, option, format!("{option:?}"));
+                    }
+                });
+        });
+    if preset != config.graphics.preset {
+        config.graphics.preset = preset;
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{
+    dev::{
+        dev_tools::{DevTools, RegisterDevToolExt},
+        quick_pick::Inspected,
+    },
+    gameplay_log::{GameplayLog, LogCategory, LogEntry},
+    player_control::ui_layer::UiLayer,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::fs;
+
+/// Where the log is written to by the export button.
+const EXPORT_PATH: &str = "gameplay_log.txt";
+
+/// Shows the [`GameplayLog`] with filters for categories and entities.
+/// While "Filter by inspected" is checked, only the entries of the entity picked with the inspector are shown.
+/// Clicking the entity of an entry filters by it instead.
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<LogViewer>()
+        .register_dev_tool("Gameplay Log", Some(KeyCode::F2), |_: In<bool>| {})
+        .add_systems(
+            Update,
+            display_gameplay_log
+                .run_if(|dev_tools: Res<DevTools>| dev_tools.is_active("Gameplay Log")),
+        );
+}
+
+#[derive(Debug, Resource)]
+struct LogViewer {
+    hidden_categories: Vec<LogCategory>,
+    entity_filter: Option<Entity>,
+    filter_by_inspected: bool,
+    /// Only entries up to this sequence number are shown while paused.
+    paused_at: Option<u64>,
+    /// Keeps the newest entry in view.
+    follow: bool,
+}
+
+impl Default for LogViewer {
+    fn default() -> Self {
+        Self {
+            hidden_categories: Vec::new(),
+            entity_filter: None,
+            filter_by_inspected: false,
+            paused_at: None,
+            follow: true,
+        }
+    }
+}
+
+impl LogViewer {
+    fn shows(&self, entry: &LogEntry, entity_filter: Option<Entity>) -> bool {
+        !self.hidden_categories.contains(&entry.category)
+            && entity_filter.map_or(true, |entity| entry.entity == Some(entity))
+            && self
+                .paused_at
+                .map_or(true, |paused_at| entry.sequence <= paused_at)
+    }
+}
+
+fn display_gameplay_log(
+    mut log: ResMut<GameplayLog>,
+    mut viewer: ResMut<LogViewer>,
+    inspected: Res<Inspected>,
+    names: Query<&Name>,
+    mut egui_contexts: EguiContexts,
+) {
+    let entity_filter = if viewer.filter_by_inspected {
+        inspected.entity
+    } else {
+        viewer.entity_filter
+    };
+    let label = |entity: Entity| {
+        names
+            .get(entity)
+            .map_or_else(|_| format!("{entity:?}"), |name| name.to_string())
+    };
+    let mut clear = false;
+    egui::Window::new("Gameplay Log")
+        .order(UiLayer::Dev.order())
+        .default_width(600.)
+        .default_height(300.)
+        .default_pos(egui::pos2(380., 10.))
+        .show(egui_contexts.ctx_mut(), |ui| {
+            if !log.is_enabled() {
+                ui.label("The gameplay log is disabled in this build.");
+                return;
+            }
+            ui.horizontal_wrapped(|ui| {
+                for category in LogCategory::ALL {
+                    let mut shown = !viewer.hidden_categories.contains(&category);
+                    if ui.checkbox(&mut shown, category.name()).changed() {
+                        if shown {
+                            viewer
+                                .hidden_categories
+                                .retain(|hidden| *hidden != category);
+                        } else {
+                            viewer.hidden_categories.push(category);
+                        }
+                    }
+                }
+            });
+            ui.horizontal_wrapped(|ui| {
+                ui.checkbox(&mut viewer.filter_by_inspected, "Filter by inspected");
+                if let Some(entity) = entity_filter {
+                    ui.label(format!("Showing {}", label(entity)));
+                    if !viewer.filter_by_inspected && ui.button("Show all").clicked() {
+                        viewer.entity_filter = None;
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                let mut paused = viewer.paused_at.is_some();
+                if ui.checkbox(&mut paused, "Pause").changed() {
+                    viewer.paused_at =
+                        paused.then(|| log.entries().last().map_or(0, |entry| entry.sequence));
+                }
+                ui.checkbox(&mut viewer.follow, "Follow");
+                if ui.button("Clear").clicked() {
+                    clear = true;
+                }
+                if ui
+                    .button("Export")
+                    .on_hover_text(format!("Write the whole log to {EXPORT_PATH}"))
+                    .clicked()
+                {
+                    export(&log);
+                }
+            });
+            ui.separator();
+
+            let rows: Vec<_> = log
+                .entries()
+                .filter(|entry| viewer.shows(entry, entity_filter))
+                .collect();
+            let row_height = ui.spacing().interact_size.y;
+            let mut clicked_entity = None;
+            egui::ScrollArea::vertical()
+                .auto_shrink(false)
+                .stick_to_bottom(viewer.follow)
+                .show_rows(ui, row_height, rows.len(), |ui, range| {
+                    for entry in &rows[range] {
+                        ui.horizontal(|ui| {
+                            ui.monospace(format!("{:>9.3}", entry.time));
+                            let category = entry.category;
+                            ui.colored_label(category_color(category), category.name());
+                            if let Some(entity) = entry.entity {
+                                if ui.small_button(label(entity)).clicked() {
+                                    clicked_entity = Some(entity);
+                                }
+                            }
+                            ui.label(entry.message.as_ref());
+                            for (name, value) in entry.fields() {
+                                ui.weak(format!("{name}={value}"));
+                            }
+                        });
+                    }
+                });
+            if let Some(entity) = clicked_entity {
+                viewer.entity_filter = Some(entity);
+                viewer.filter_by_inspected = false;
+            }
+        });
+    if clear {
+        log.clear();
+    }
+}
+
+fn category_color(category: LogCategory) -> egui::Color32 {
+    match category {
+        LogCategory::Movement => egui::Color32::LIGHT_BLUE,
+        LogCategory::Interaction => egui::Color32::YELLOW,
+        LogCategory::Dialog => egui::Color32::LIGHT_GREEN,
+        LogCategory::Spawn => egui::Color32::GRAY,
+        LogCategory::Ai => egui::Color32::LIGHT_RED,
+    }
+}
+
+fn export(log: &GameplayLog) {
+    let text: String = log.entries().map(|entry| format!("{entry}\n")).collect();
+    match fs::write(EXPORT_PATH, text) {
+        Ok(()) => info!("Exported the gameplay log to {EXPORT_PATH}"),
+        Err(error) => error!("Failed to export the gameplay log to {EXPORT_PATH}: {error}"),
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{
+    dev::dev_tools::{DevTools, RegisterDevToolExt},
+    level_instantiation::level_bounds::LevelBounds,
+    player_control::ui_layer::UiLayer,
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::f32::consts::FRAC_PI_2;
+
+/// Shows the [`LevelBounds`], their warning zone and the kill plane, and lets them be edited live.
+/// Since levels are authored in Blender, the edited bounds are copied as a component value to paste back into the level file.
+pub(super) fn plugin(app: &mut App) {
+    app.register_dev_tool("Level Bounds", Some(KeyCode::F9), |_: In<bool>| {})
+        .add_systems(
+            Update,
+            (draw_level_bounds, edit_level_bounds).run_if(
+                in_state(GameState::Playing)
+                    .and_then(|dev_tools: Res<DevTools>| dev_tools.is_active("Level Bounds")),
+            ),
+        );
+}
+
+fn draw_level_bounds(bounds: Query<(&LevelBounds, &GlobalTransform)>, mut gizmos: Gizmos) {
+    for (bounds, transform) in bounds.iter() {
+        let (_, rotation, translation) = transform.to_scale_rotation_translation();
+        let box_transform = |half_extents: Vec3| {
+            Transform::from_translation(translation)
+                .with_rotation(rotation)
+                .with_scale(half_extents * 2.)
+        };
+        gizmos.cuboid(box_transform(bounds.half_extents), Color::ORANGE);
+        let warning =
+            bounds.half_extents - Vec3::new(bounds.warning_distance, 0., bounds.warning_distance);
+        if warning.cmpgt(Vec3::ZERO).all() {
+            gizmos.cuboid(box_transform(warning), Color::YELLOW);
+        }
+        let kill_plane = Vec3::new(translation.x, bounds.kill_height(transform), translation.z);
+        gizmos.rect(
+            kill_plane,
+            rotation * Quat::from_rotation_x(FRAC_PI_2),
+            Vec2::new(bounds.half_extents.x, bounds.half_extents.z) * 2.,
+            Color::RED,
+        );
+        // Marks the faces that the half extents move
+        for axis in [Vec3::X, Vec3::NEG_X, Vec3::Y, Vec3::Z, Vec3::NEG_Z] {
+            let handle = translation + rotation * (axis * bounds.half_extents);
+            gizmos.sphere(handle, Quat::IDENTITY, 0.5, Color::ORANGE);
+        }
+    }
+}
+
+fn edit_level_bounds(
+    mut bounds: Query<(&mut LevelBounds, &mut Transform, Option<&Name>)>,
+    mut egui_contexts: EguiContexts,
+) {
+    let Some((mut bounds, mut transform, name)) = bounds.iter_mut().next() else {
+        return;
+    };
+    let mut edited = *bounds;
+    let mut translation = transform.translation;
+    egui::Window::new("Level Bounds")
+        .order(UiLayer::Dev.order())
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10., -10.))
+        .show(egui_contexts.ctx_mut(), |ui| {
+            egui::Grid::new("Level Bounds Grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Center");
+                    vec3_drag_values(ui, &mut translation);
+                    ui.end_row();
+                    ui.label("Half extents");
+                    vec3_drag_values(ui, &mut edited.half_extents);
+                    ui.end_row();
+                    ui.label("Warning distance");
+                    ui.add(
+                        egui::DragValue::new(&mut edited.warning_distance)
+                            .speed(0.1)
+                            .clamp_range(0.0..=f32::MAX),
+                    );
+                    ui.end_row();
+                    ui.label("Kill depth");
+                    ui.add(
+                        egui::DragValue::new(&mut edited.kill_depth)
+                            .speed(0.1)
+                            .clamp_range(0.0..=f32::MAX),
+                    );
+                    ui.end_row();
+                    ui.label("Ceiling");
+                    ui.checkbox(&mut edited.ceiling, "");
+                    ui.end_row();
+                });
+            let object = name.map_or_else(
+                || "the bounds object".to_string(),
+                |name| format!("\"{name}\""),
+            );
+            if ui
+                .button("Copy component")
+                .on_hover_text(format!(
+                    "Paste this into the LevelBounds of {object} in Blender \
+                    and move it to the center shown above"
+                ))
+                .clicked()
+            {
+                match ron::to_string(&edited) {
+                    Ok(text) => ui.output_mut(|output| output.copied_text = text),
+                    Err(error) => error!("Failed to serialize the level bounds: {error}"),
+                }
+            }
+        });
+    edited.half_extents = edited.half_extents.max(Vec3::splat(0.5));
+    if *bounds != edited {
+        *bounds = edited;
+    }
+    if transform.translation != translation {
+        transform.translation = translation;
+    }
+}
+
+fn vec3_drag_values(ui: &mut egui::Ui, value: &mut Vec3) {
+    ui.horizontal(|ui| {
+        for component in [&mut value.x, &mut value.y, &mut value.z] {
+            ui.add(egui::DragValue::new(component).speed(0.1));
+        }
+    });
+}
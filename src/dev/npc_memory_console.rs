@@ -0,0 +1,220 @@
+use crate::{
+    determinism::GameRng,
+    dev::dev_tools::{DevTools, RegisterDevToolExt},
+    level_instantiation::stable_id::{StableId, StableIdRegistry},
+    player_control::ui_layer::UiLayer,
+    world_interaction::{
+        dialog::{
+            small_talk::{preview_small_talk, SmallTalk},
+            YarnNode,
+        },
+        npc_memory::{NpcMemory, NpcRecord},
+        time_of_day::TimeOfDay,
+    },
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+/// How many lines of output the console keeps.
+const HISTORY_LENGTH: usize = 50;
+
+/// A console for inspecting and editing what NPCs remember about the player while testing dialog branches,
+/// e.g. `set blacksmith hours_since 30` to check the greeting after a long absence.
+/// `next blacksmith` tells which yarn node talking to an NPC in the level starts next, see [`SmallTalk`].
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<NpcMemoryConsole>()
+        .register_dev_tool("NPC Memory", None, |_: In<bool>| {})
+        .add_systems(
+            Update,
+            display_npc_memory_console
+                .run_if(|dev_tools: Res<DevTools>| dev_tools.is_active("NPC Memory")),
+        );
+}
+
+#[derive(Debug, Default, Resource)]
+struct NpcMemoryConsole {
+    input: String,
+    history: Vec<String>,
+}
+
+fn display_npc_memory_console(
+    mut console: ResMut<NpcMemoryConsole>,
+    mut memory: ResMut<NpcMemory>,
+    time_of_day: Res<TimeOfDay>,
+    dialog_targets: Query<(Entity, &Name, &YarnNode, Option<&SmallTalk>)>,
+    registry: Res<StableIdRegistry>,
+    game_rng: Res<GameRng>,
+    mut egui_contexts: EguiContexts,
+) {
+    let console = &mut *console;
+    egui::Window::new("NPC Memory")
+        .order(UiLayer::Dev.order())
+        .default_width(350.)
+        .default_pos(egui::pos2(380., 60.))
+        .show(egui_contexts.ctx_mut(), |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(150.)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for line in &console.history {
+                        ui.monospace(line);
+                    }
+                });
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut console.input)
+                    .hint_text("show npc, set npc field value, forget npc, next npc")
+                    .desired_width(f32::INFINITY),
+            );
+            if response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+                let command = std::mem::take(&mut console.input);
+                let words: Vec<_> = command.split_whitespace().collect();
+                let output = match words.as_slice() {
+                    ["next", npc] => {
+                        preview_next_node(npc, &dialog_targets, &registry, &memory, &game_rng)
+                    }
+                    _ => run_command(&command, &mut memory, &time_of_day),
+                };
+                console.history.push(format!("> {command}"));
+                console.history.push(output);
+                let excess = console.history.len().saturating_sub(HISTORY_LENGTH);
+                console.history.drain(..excess);
+                response.request_focus();
+            }
+            ui.separator();
+
+            ui.collapsing("All NPCs", |ui| {
+                for (id, record) in memory.records() {
+                    ui.label(format!(
+                        "{}: talked {} times ({:?})",
+                        record.name, record.times_talked, id.0
+                    ));
+                }
+            });
+        });
+}
+
+fn run_command(command: &str, memory: &mut NpcMemory, time_of_day: &TimeOfDay) -> String {
+    let mut words = command.split_whitespace();
+    let (Some(verb), Some(npc)) = (words.next(), words.next()) else {
+        return unknown(command);
+    };
+    let Some(id) = memory.find(npc).map(|(id, _)| id) else {
+        return format!("No NPC named \"{npc}\" is remembered. Talk to it first or use its id");
+    };
+    let arguments: Vec<_> = words.collect();
+    match (verb, arguments.as_slice()) {
+        ("show", []) => describe(id, memory.entry(id), time_of_day),
+        ("forget", []) => {
+            memory.forget(id);
+            format!("Forgot everything about {npc}")
+        }
+        ("set", [field, value]) => match set_field(memory.entry(id), field, value, time_of_day) {
+            Ok(()) => describe(id, memory.entry(id), time_of_day),
+            Err(error) => error,
+        },
+        _ => unknown(command),
+    }
+}
+
+fn preview_next_node(
+    npc: &str,
+    dialog_targets: &Query<(Entity, &Name, &YarnNode, Option<&SmallTalk>)>,
+    registry: &StableIdRegistry,
+    memory: &NpcMemory,
+    game_rng: &GameRng,
+) -> String {
+    let Some((entity, name, yarn_node, small_talk)) = dialog_targets
+        .iter()
+        .find(|(_, name, ..)| name.as_str().eq_ignore_ascii_case(npc))
+    else {
+        return format!("No dialog target named \"{npc}\" is in the level");
+    };
+    let Some(small_talk) = small_talk else {
+        return format!("{name} has no small talk and always starts {}", yarn_node.0);
+    };
+    let Some(id) = registry.id_of(entity) else {
+        return format!("{name} has no stable id, so its small talk is not remembered");
+    };
+    let history = memory
+        .get(id)
+        .map(|record| record.small_talk.clone())
+        .unwrap_or_default();
+    let used = small_talk
+        .0
+        .iter()
+        .filter(|line| history.used.contains(&line.node))
+        .count();
+    let node = preview_small_talk(small_talk, &history, game_rng, id).unwrap_or(&yarn_node.0);
+    format!(
+        "{name} starts {node} next ({used} of {} small talk nodes used)",
+        small_talk.0.len()
+    )
+}
+
+fn set_field(
+    record: &mut NpcRecord,
+    field: &str,
+    value: &str,
+    time_of_day: &TimeOfDay,
+) -> Result<(), String> {
+    let number = || {
+        value
+            .parse::<f32>()
+            .map_err(|_| format!("Expected a number, but got \"{value}\""))
+    };
+    match field {
+        "times_talked" => record.times_talked = number()?.max(0.) as u32,
+        "hours_since" => record.last_talked = Some(time_of_day.total_hours() - number()?),
+        "never_talked" => record.last_talked = None,
+        "small_talk_unused" => record.small_talk = default(),
+        "completed" => {
+            record.completed_nodes.insert(value.to_string());
+        }
+        "uncompleted" => {
+            record.completed_nodes.remove(value);
+        }
+        _ => {
+            let Some(item) = field.strip_prefix("gifts.") else {
+                return Err(format!(
+                    "Unknown field \"{field}\". Try times_talked, hours_since, never_talked, \
+                    completed, uncompleted, small_talk_unused or gifts.item_id"
+                ));
+            };
+            record
+                .gifts
+                .insert(item.to_string(), number()?.max(0.) as u32);
+        }
+    }
+    Ok(())
+}
+
+fn describe(id: StableId, record: &NpcRecord, time_of_day: &TimeOfDay) -> String {
+    let hours_since = record
+        .hours_since_talked(time_of_day)
+        .map_or("never".to_string(), |hours| format!("{hours:.1}h ago"));
+    let mut nodes: Vec<_> = record.completed_nodes.iter().map(String::as_str).collect();
+    nodes.sort_unstable();
+    let mut gifts: Vec<_> = record
+        .gifts
+        .iter()
+        .map(|(item, count)| format!("{count} {item}"))
+        .collect();
+    gifts.sort_unstable();
+    let mut small_talk: Vec<_> = record.small_talk.used.iter().map(String::as_str).collect();
+    small_talk.sort_unstable();
+    format!(
+        "{} ({:?}): talked {} times, last {hours_since}\nnodes: {}\ngifts: {}\nsmall talk used: {}",
+        record.name,
+        id.0,
+        record.times_talked,
+        nodes.join(", "),
+        gifts.join(", "),
+        small_talk.join(", ")
+    )
+}
+
+fn unknown(command: &str) -> String {
+    format!(
+        "Unknown command \"{command}\". Try show npc, set npc field value, forget npc or next npc"
+    )
+}
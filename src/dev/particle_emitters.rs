@@ -0,0 +1,214 @@
+use crate::{
+    dev::dev_tools::{DevTools, RegisterDevToolExt},
+    level_instantiation::{
+        level_overrides::LevelOverrideTable,
+        map::{CurrentLevel, LevelRoot},
+    },
+    particles::{
+        attached::EffectTable,
+        emitter::{EmitterShape, ParticleEmitter},
+    },
+    player_control::ui_layer::UiLayer,
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+/// Draws the shapes of all [`ParticleEmitter`]s and lets one of them be edited live, which the emitter picks up right away.
+/// Since levels are authored in Blender, the edited emitter is saved to the [`LevelOverrideTable`] by its name instead of to the level file.
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<SelectedEmitter>()
+        .register_dev_tool("Particle Emitters", None, |_: In<bool>| {})
+        .add_systems(
+            Update,
+            (draw_emitter_shapes, edit_particle_emitters).run_if(
+                in_state(GameState::Playing)
+                    .and_then(|dev_tools: Res<DevTools>| dev_tools.is_active("Particle Emitters")),
+            ),
+        );
+}
+
+#[derive(Debug, Default, Resource)]
+struct SelectedEmitter(Option<Entity>);
+
+fn draw_emitter_shapes(
+    emitters: Query<(Entity, &ParticleEmitter, &GlobalTransform)>,
+    selected: Res<SelectedEmitter>,
+    mut gizmos: Gizmos,
+) {
+    for (entity, emitter, transform) in emitters.iter() {
+        let color = if selected.0 == Some(entity) {
+            Color::YELLOW
+        } else {
+            Color::CYAN
+        };
+        let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+        match emitter.shape {
+            EmitterShape::Point => {
+                gizmos.sphere(translation, rotation, 0.1, color);
+            }
+            EmitterShape::Box { half_extents } => {
+                let transform = Transform::from_translation(translation)
+                    .with_rotation(rotation)
+                    .with_scale(scale * half_extents * 2.);
+                gizmos.cuboid(transform, color);
+            }
+            EmitterShape::SphereSurface { radius } => {
+                gizmos.sphere(translation, rotation, radius * scale.max_element(), color);
+            }
+        }
+    }
+}
+
+fn edit_particle_emitters(
+    mut emitters: Query<(Entity, &mut ParticleEmitter, Option<&Name>)>,
+    mut selected: ResMut<SelectedEmitter>,
+    tables: Res<Assets<EffectTable>>,
+    override_tables: Res<Assets<LevelOverrideTable>>,
+    parents: Query<&Parent>,
+    level_roots: Query<&LevelRoot>,
+    current_level: Res<CurrentLevel>,
+    mut egui_contexts: EguiContexts,
+) {
+    let label = |entity: Entity, name: Option<&Name>| {
+        name.map_or_else(|| format!("{entity:?}"), ToString::to_string)
+    };
+    let mut effects: Vec<_> = tables
+        .iter()
+        .flat_map(|(_, table)| table.effects.keys().cloned())
+        .collect();
+    effects.sort_unstable();
+    let emitter_count = emitters.iter().count();
+    if selected.0.is_some_and(|entity| !emitters.contains(entity)) {
+        selected.0 = None;
+    }
+
+    egui::Window::new("Particle Emitters")
+        .order(UiLayer::Dev.order())
+        .default_width(280.)
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-10., 60.))
+        .show(egui_contexts.ctx_mut(), |ui| {
+            let selected_label = selected
+                .0
+                .and_then(|entity| emitters.get(entity).ok())
+                .map_or("None".to_string(), |(entity, _, name)| label(entity, name));
+            egui::ComboBox::from_label("Emitter")
+                .selected_text(selected_label)
+                .show_ui(ui, |ui| {
+                    for (entity, _, name) in emitters.iter() {
+                        ui.selectable_value(&mut selected.0, Some(entity), label(entity, name));
+                    }
+                });
+            let Some(Ok((entity, mut emitter, name))) =
+                selected.0.map(|entity| emitters.get_mut(entity))
+            else {
+                ui.label(format!("{emitter_count} emitters in the level"));
+                return;
+            };
+            let mut edited = emitter.clone();
+            egui::Grid::new("Particle Emitter Grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Effect");
+                    egui::ComboBox::from_id_source("Particle Emitter Effect")
+                        .selected_text(edited.effect.clone())
+                        .show_ui(ui, |ui| {
+                            for effect in &effects {
+                                ui.selectable_value(&mut edited.effect, effect.clone(), effect);
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Rate");
+                    ui.horizontal(|ui| {
+                        let mut has_rate = edited.rate.is_some();
+                        ui.checkbox(&mut has_rate, "")
+                            .on_hover_text("Unchecked uses the rate of the effect");
+                        if has_rate != edited.rate.is_some() {
+                            edited.rate = has_rate.then_some(10.);
+                        }
+                        if let Some(rate) = &mut edited.rate {
+                            ui.add(
+                                egui::DragValue::new(rate)
+                                    .speed(0.1)
+                                    .clamp_range(0.0..=f32::MAX),
+                            );
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Shape");
+                    ui.horizontal(|ui| {
+                        let size = match edited.shape {
+                            EmitterShape::Point => 1.,
+                            EmitterShape::Box { half_extents } => half_extents.max_element(),
+                            EmitterShape::SphereSurface { radius } => radius,
+                        };
+                        ui.radio_value(&mut edited.shape, EmitterShape::Point, "Point");
+                        if ui
+                            .radio(matches!(edited.shape, EmitterShape::Box { .. }), "Box")
+                            .clicked()
+                        {
+                            edited.shape = EmitterShape::Box {
+                                half_extents: Vec3::splat(size),
+                            };
+                        }
+                        if ui
+                            .radio(
+                                matches!(edited.shape, EmitterShape::SphereSurface { .. }),
+                                "Sphere",
+                            )
+                            .clicked()
+                        {
+                            edited.shape = EmitterShape::SphereSurface { radius: size };
+                        }
+                    });
+                    ui.end_row();
+                    match &mut edited.shape {
+                        EmitterShape::Point => {}
+                        EmitterShape::Box { half_extents } => {
+                            ui.label("Half extents");
+                            ui.horizontal(|ui| {
+                                for component in [
+                                    &mut half_extents.x,
+                                    &mut half_extents.y,
+                                    &mut half_extents.z,
+                                ] {
+                                    ui.add(egui::DragValue::new(component).speed(0.05));
+                                }
+                            });
+                            ui.end_row();
+                        }
+                        EmitterShape::SphereSurface { radius } => {
+                            ui.label("Radius");
+                            ui.add(
+                                egui::DragValue::new(radius)
+                                    .speed(0.05)
+                                    .clamp_range(0.0..=f32::MAX),
+                            );
+                            ui.end_row();
+                        }
+                    }
+                });
+            let level = std::iter::once(entity)
+                .chain(parents.iter_ancestors(entity))
+                .find_map(|entity| level_roots.get(entity).ok())
+                .map_or(current_level.0.as_str(), |root| root.0.as_str());
+            let save_button = ui.add_enabled(name.is_some(), egui::Button::new("Save"));
+            let save_button = if name.is_some() {
+                save_button.on_hover_text("Save the emitter to assets/config/config.levels.ron")
+            } else {
+                save_button.on_disabled_hover_text("Only named emitters can be saved")
+            };
+            if let Some(name) = name.filter(|_| save_button.clicked()) {
+                if let Err(error) = LevelOverrideTable::save(&override_tables, level, |overrides| {
+                    overrides.emitters.insert(name.to_string(), edited.clone());
+                }) {
+                    error!("Failed to save the particle emitter: {error:?}");
+                }
+            }
+            if *emitter != edited {
+                *emitter = edited;
+            }
+        });
+}
@@ -0,0 +1,223 @@
+use crate::{
+    dev::dev_tools::{DevTools, RegisterDevToolExt},
+    level_instantiation::{map::LevelRoot, stable_id::StableId},
+    player_control::{
+        camera::{CursorGrabRequests, IngameCamera},
+        ui_layer::UiLayer,
+    },
+    GameState,
+};
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContext, EguiContexts};
+use bevy_gltf_blueprints::{AnimationPlayerLink, BlueprintName};
+use bevy_xpbd_3d::prelude::*;
+
+/// Clicks further away than this pick nothing.
+const MAX_PICK_DISTANCE: f32 = 500.;
+
+/// Inspects whatever is clicked: while the tool is active, the cursor is free and clicking into the world
+/// raycasts through the [`IngameCamera`]. The hit collider is resolved to the closest ancestor that was spawned as its own
+/// object, i.e. a blueprint, an animated character or an entity with a [`StableId`], falling back to the closest named one.
+/// Its components are shown in a window with live editing, along with a breadcrumb to walk the hierarchy
+/// and buttons to copy the name and stable id for use in signals and yarn commands.
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<Inspected>()
+        .register_dev_tool("Inspector", Some(KeyCode::F8), toggle_inspector)
+        .add_systems(
+            Update,
+            (pick_entity, display_inspector).chain().run_if(
+                in_state(GameState::Playing)
+                    .and_then(|dev_tools: Res<DevTools>| dev_tools.is_active("Inspector")),
+            ),
+        );
+}
+
+#[derive(Debug, Default, Resource)]
+pub(super) struct Inspected {
+    pub(super) entity: Option<Entity>,
+    /// Explains a pick that did not go as expected, e.g. a hit without a meaningful ancestor.
+    note: Option<String>,
+}
+
+fn toggle_inspector(
+    In(active): In<bool>,
+    mut cursor_grab: ResMut<CursorGrabRequests>,
+    mut inspected: ResMut<Inspected>,
+) {
+    if active {
+        cursor_grab.request_free();
+    } else {
+        cursor_grab.release();
+        *inspected = default();
+    }
+}
+
+fn pick_entity(
+    mouse: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<IngameCamera>>,
+    mut egui_contexts: EguiContexts,
+    spatial_query: SpatialQuery,
+    parents: Query<&Parent>,
+    spawned: Query<
+        (),
+        Or<(
+            With<BlueprintName>,
+            With<AnimationPlayerLink>,
+            With<StableId>,
+        )>,
+    >,
+    names: Query<(), With<Name>>,
+    level_roots: Query<(), With<LevelRoot>>,
+    mut inspected: ResMut<Inspected>,
+) {
+    if !mouse.just_pressed(MouseButton::Left) || egui_contexts.ctx_mut().wants_pointer_input() {
+        return;
+    }
+    let Some(cursor) = windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+    else {
+        return;
+    };
+    let Some(ray) = cameras
+        .iter()
+        .find_map(|(camera, transform)| camera.viewport_to_world(transform, cursor))
+    else {
+        return;
+    };
+    let Some(hit) = spatial_query.cast_ray(
+        ray.origin,
+        ray.direction,
+        MAX_PICK_DISTANCE,
+        true,
+        SpatialQueryFilter::default(),
+    ) else {
+        inspected.note = Some("Nothing with a collider under the cursor".to_string());
+        return;
+    };
+
+    // The level root is an ancestor of everything, so it never counts
+    let lineage: Vec<_> = std::iter::once(hit.entity)
+        .chain(parents.iter_ancestors(hit.entity))
+        .take_while(|entity| !level_roots.contains(*entity))
+        .collect();
+    if let Some(&entity) = lineage.iter().find(|entity| spawned.contains(**entity)) {
+        inspected.entity = Some(entity);
+        inspected.note = None;
+    } else if let Some(&entity) = lineage.iter().find(|entity| names.contains(**entity)) {
+        inspected.entity = Some(entity);
+        inspected.note =
+            Some("Not part of a spawned object, showing the closest named entity".to_string());
+    } else {
+        inspected.entity = Some(hit.entity);
+        inspected.note =
+            Some("Not part of a spawned or named object, showing the hit collider".to_string());
+    }
+}
+
+fn display_inspector(world: &mut World) {
+    let Ok(egui_context) = world
+        .query_filtered::<&EguiContext, With<PrimaryWindow>>()
+        .get_single(world)
+    else {
+        return;
+    };
+    let mut egui_context = egui_context.clone();
+    let inspected = world.resource::<Inspected>();
+    let mut entity = inspected.entity;
+    let note = inspected.note.clone();
+    if entity.is_some_and(|entity| world.get_entity(entity).is_none()) {
+        entity = None;
+    }
+
+    let label = |world: &World, entity: Entity| {
+        world
+            .get::<Name>(entity)
+            .map_or_else(|| format!("{entity:?}"), |name| name.to_string())
+    };
+    let lineage: Vec<_> = entity
+        .map(|entity| {
+            let mut lineage: Vec<_> = std::iter::successors(Some(entity), |entity| {
+                world.get::<Parent>(*entity).map(|parent| parent.get())
+            })
+            .map(|entity| (entity, label(world, entity)))
+            .collect();
+            lineage.reverse();
+            lineage
+        })
+        .unwrap_or_default();
+    let children: Vec<_> = entity
+        .and_then(|entity| world.get::<Children>(entity))
+        .map(|children| {
+            children
+                .iter()
+                .map(|&child| (child, label(world, child)))
+                .collect()
+        })
+        .unwrap_or_default();
+    let name = entity.and_then(|entity| world.get::<Name>(entity).map(|name| name.to_string()));
+    let stable_id =
+        entity.and_then(|entity| world.get::<StableId>(entity).map(|id| id.0.to_string()));
+
+    let mut selected = None;
+    egui::Window::new("Inspector")
+        .order(UiLayer::Dev.order())
+        .collapsible(false)
+        .default_width(350.)
+        .default_pos(egui::pos2(10., 220.))
+        .show(egui_context.get_mut(), |ui| {
+            if let Some(note) = &note {
+                ui.colored_label(egui::Color32::YELLOW, note);
+            }
+            let Some(entity) = entity else {
+                ui.label("Click an object to inspect it");
+                return;
+            };
+            ui.horizontal_wrapped(|ui| {
+                for (index, (ancestor, label)) in lineage.iter().enumerate() {
+                    if index > 0 {
+                        ui.label(">");
+                    }
+                    if ui.selectable_label(*ancestor == entity, label).clicked() {
+                        selected = Some(*ancestor);
+                    }
+                }
+            });
+            if !children.is_empty() {
+                ui.collapsing(format!("Children ({})", children.len()), |ui| {
+                    for (child, label) in &children {
+                        if ui.button(label).clicked() {
+                            selected = Some(*child);
+                        }
+                    }
+                });
+            }
+            ui.horizontal(|ui| {
+                if let Some(name) = &name {
+                    if ui.button("Copy name").clicked() {
+                        ui.output_mut(|output| output.copied_text = name.clone());
+                    }
+                }
+                if let Some(stable_id) = &stable_id {
+                    if ui.button("Copy stable id").clicked() {
+                        ui.output_mut(|output| output.copied_text = stable_id.clone());
+                    }
+                }
+            });
+            ui.separator();
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                bevy_inspector_egui::bevy_inspector::ui_for_entity(world, entity, ui);
+            });
+        });
+
+    let mut inspected = world.resource_mut::<Inspected>();
+    if let Some(selected) = selected {
+        inspected.entity = Some(selected);
+        inspected.note = None;
+    } else if entity.is_none() && inspected.entity.is_some() {
+        inspected.entity = None;
+        inspected.note = Some("The inspected entity was despawned".to_string());
+    }
+}
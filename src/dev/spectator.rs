@@ -0,0 +1,306 @@
+use crate::{
+    dev::{
+        dev_tools::{DevTools, RegisterDevToolExt},
+        quick_pick::Inspected,
+    },
+    level_instantiation::on_spawn::Player,
+    movement::{
+        character_controller::{AnimationState, MovementStats, Walk},
+        navigation::{CompanionState, NavigationDestination, NavigationPath, WanderState},
+    },
+    player_control::{
+        actions::ActionsFrozen,
+        camera::{CursorGrabRequests, IngameCamera},
+        ui_layer::UiLayer,
+    },
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_atmosphere::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_gltf_blueprints::{AnimationPlayerLink, Animations};
+use bevy_tnua::TnuaAnimatingState;
+
+/// The spectator camera looks at this point above the target's origin, in meters.
+const FOCUS_HEIGHT: f32 = 1.;
+
+/// Lets a camera orbit an NPC to watch how it moves. While active, the [`IngameCamera`] is switched off
+/// and a separate camera follows the target, so switching back leaves the game camera exactly as it was.
+/// The target is either picked with the inspector or cycled through everything that walks.
+/// The player keeps being simulated, and its input can be suspended so that it does not wander off.
+/// A window shows the target's [`MovementStats`], behavior and current animation.
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<Spectator>()
+        .register_dev_tool("Spectator", None, toggle_spectator)
+        .add_systems(
+            Update,
+            (display_spectator, orbit_target).chain().run_if(
+                in_state(GameState::Playing)
+                    .and_then(|dev_tools: Res<DevTools>| dev_tools.is_active("Spectator")),
+            ),
+        );
+}
+
+#[derive(Debug, Clone, Copy, Resource)]
+struct Spectator {
+    target: Option<Entity>,
+    camera: Option<Entity>,
+    /// In radians.
+    yaw: f32,
+    /// In radians per second.
+    orbit_speed: f32,
+    /// In meters.
+    distance: f32,
+    /// How far above the target the camera is, in meters.
+    height: f32,
+    suspend_input: bool,
+    /// Whether this tool currently holds a freeze on the [`ActionsFrozen`].
+    input_suspended: bool,
+}
+
+impl Default for Spectator {
+    fn default() -> Self {
+        Self {
+            target: None,
+            camera: None,
+            yaw: 0.,
+            orbit_speed: 0.3,
+            distance: 5.,
+            height: 2.,
+            suspend_input: true,
+            input_suspended: false,
+        }
+    }
+}
+
+fn toggle_spectator(
+    In(active): In<bool>,
+    mut commands: Commands,
+    mut spectator: ResMut<Spectator>,
+    mut cursor_grab: ResMut<CursorGrabRequests>,
+    mut actions_frozen: ResMut<ActionsFrozen>,
+    mut ingame_cameras: Query<(&mut Camera, &GlobalTransform), With<IngameCamera>>,
+    inspected: Res<Inspected>,
+    walkers: Query<Entity, (With<Walk>, Without<Player>)>,
+) {
+    if active {
+        cursor_grab.request_free();
+        let mut transform = Transform::default();
+        for (mut camera, global_transform) in ingame_cameras.iter_mut() {
+            camera.is_active = false;
+            transform = global_transform.compute_transform();
+        }
+        let camera = commands
+            .spawn((
+                Name::new("Spectator Camera"),
+                Camera3dBundle {
+                    transform,
+                    ..default()
+                },
+                AtmosphereCamera::default(),
+            ))
+            .id();
+        spectator.camera = Some(camera);
+        if spectator.target.is_none() {
+            spectator.target = inspected
+                .entity
+                .or_else(|| sorted(&walkers).first().copied());
+        }
+    } else {
+        cursor_grab.release();
+        for (mut camera, _) in ingame_cameras.iter_mut() {
+            camera.is_active = true;
+        }
+        if let Some(camera) = spectator.camera.take() {
+            commands.entity(camera).despawn_recursive();
+        }
+        if spectator.input_suspended {
+            actions_frozen.unfreeze();
+            spectator.input_suspended = false;
+        }
+    }
+}
+
+/// Walkers in a stable order for cycling through them.
+fn sorted(walkers: &Query<Entity, (With<Walk>, Without<Player>)>) -> Vec<Entity> {
+    let mut walkers: Vec<_> = walkers.iter().collect();
+    walkers.sort();
+    walkers
+}
+
+fn display_spectator(
+    mut spectator: ResMut<Spectator>,
+    mut actions_frozen: ResMut<ActionsFrozen>,
+    inspected: Res<Inspected>,
+    walkers: Query<Entity, (With<Walk>, Without<Player>)>,
+    targets: Query<(
+        Option<&Name>,
+        Option<&MovementStats>,
+        Option<&TnuaAnimatingState<AnimationState>>,
+        Option<&WanderState>,
+        Option<&CompanionState>,
+        Option<&NavigationDestination>,
+        Option<&NavigationPath>,
+        Option<(&AnimationPlayerLink, &Animations)>,
+    )>,
+    animation_players: Query<&AnimationPlayer>,
+    mut egui_contexts: EguiContexts,
+) {
+    if spectator
+        .target
+        .is_some_and(|target| !targets.contains(target))
+    {
+        spectator.target = None;
+    }
+    let walkers = sorted(&walkers);
+    let cycle = |target: Option<Entity>, step: isize| {
+        let index = target
+            .and_then(|target| walkers.iter().position(|&walker| walker == target))
+            .map_or(0, |index| index as isize + step);
+        let length = walkers.len() as isize;
+        (length > 0).then(|| walkers[index.rem_euclid(length) as usize])
+    };
+
+    let mut edited = *spectator;
+    egui::Window::new("Spectator")
+        .order(UiLayer::Dev.order())
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(10., -10.))
+        .show(egui_contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("<").clicked() {
+                    edited.target = cycle(edited.target, -1);
+                }
+                if ui.button(">").clicked() {
+                    edited.target = cycle(edited.target, 1);
+                }
+                if ui
+                    .add_enabled(
+                        inspected.entity.is_some(),
+                        egui::Button::new("Use inspected"),
+                    )
+                    .on_hover_text("Orbit the entity selected in the inspector")
+                    .clicked()
+                {
+                    edited.target = inspected.entity;
+                }
+            });
+            egui::Grid::new("Spectator Grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Distance");
+                    ui.add(egui::Slider::new(&mut edited.distance, 1.0..=30.0));
+                    ui.end_row();
+                    ui.label("Height");
+                    ui.add(egui::Slider::new(&mut edited.height, -5.0..=20.0));
+                    ui.end_row();
+                    ui.label("Orbit speed");
+                    ui.add(egui::Slider::new(&mut edited.orbit_speed, -2.0..=2.0));
+                    ui.end_row();
+                    ui.label("Suspend player input");
+                    ui.checkbox(&mut edited.suspend_input, "");
+                    ui.end_row();
+                });
+            ui.separator();
+
+            let Some((target, components)) = edited
+                .target
+                .and_then(|target| Some((target, targets.get(target).ok()?)))
+            else {
+                ui.label("Nothing to orbit. Pick an NPC with the inspector or cycle through them.");
+                return;
+            };
+            let (name, stats, animating_state, wander, companion, destination, path, animations) =
+                components;
+            ui.heading(name.map_or_else(|| format!("{target:?}"), |name| name.to_string()));
+            let behavior = wander
+                .map(WanderState::describe)
+                .or_else(|| companion.map(CompanionState::describe))
+                .unwrap_or_else(|| "None".to_string());
+            let animation_state = animating_state
+                .and_then(TnuaAnimatingState::get)
+                .map_or_else(|| "None".to_string(), |state| format!("{state:?}"));
+            let clip = animations
+                .and_then(|(link, animations)| {
+                    let player = animation_players.get(link.0).ok()?;
+                    let clip = player.animation_clip().id();
+                    animations
+                        .named_animations
+                        .iter()
+                        .find(|(_, handle)| handle.id() == clip)
+                        .map(|(name, _)| name.clone())
+                })
+                .unwrap_or_else(|| "None".to_string());
+            egui::Grid::new("Spectator Readout Grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Behavior");
+                    ui.label(behavior);
+                    ui.end_row();
+                    if let Some(destination) = destination {
+                        ui.label("Destination");
+                        ui.label(format!("{:.1}", destination.position));
+                        ui.end_row();
+                    }
+                    if let Some(path) = path {
+                        ui.label("Path");
+                        ui.label(format!("{} corners left", path.corners.len()));
+                        ui.end_row();
+                    }
+                    ui.label("Animation state");
+                    ui.label(animation_state);
+                    ui.end_row();
+                    ui.label("Animation clip");
+                    ui.label(clip);
+                    ui.end_row();
+                });
+            if let Some(stats) = stats {
+                ui.collapsing("Movement stats", |ui| {
+                    ui.monospace(format!("{stats:#?}"));
+                });
+            }
+        });
+
+    if edited.suspend_input != edited.input_suspended {
+        if edited.suspend_input {
+            actions_frozen.freeze();
+        } else {
+            actions_frozen.unfreeze();
+        }
+        edited.input_suspended = edited.suspend_input;
+    }
+    *spectator = edited;
+}
+
+fn orbit_target(
+    time: Res<Time>,
+    mut spectator: ResMut<Spectator>,
+    targets: Query<&GlobalTransform>,
+    mut cameras: Query<&mut Transform, Without<IngameCamera>>,
+    mut ingame_cameras: Query<&mut Camera, With<IngameCamera>>,
+) {
+    // The level may have spawned a new game camera in the meantime
+    for mut camera in ingame_cameras.iter_mut() {
+        if camera.is_active {
+            camera.is_active = false;
+        }
+    }
+    spectator.yaw += spectator.orbit_speed * time.delta_seconds();
+    let Some(target) = spectator
+        .target
+        .and_then(|target| targets.get(target).ok())
+        .map(GlobalTransform::translation)
+    else {
+        return;
+    };
+    let Some(mut transform) = spectator
+        .camera
+        .and_then(|camera| cameras.get_mut(camera).ok())
+    else {
+        return;
+    };
+    let offset = Quat::from_rotation_y(spectator.yaw) * Vec3::Z * spectator.distance;
+    transform.translation = target + offset + Vec3::Y * spectator.height;
+    transform.look_at(target + Vec3::Y * FOCUS_HEIGHT, Vec3::Y);
+}
@@ -0,0 +1,114 @@
+use crate::{
+    dev::{
+        dev_tools::{DevTools, RegisterDevToolExt},
+        quick_pick::Inspected,
+    },
+    level_instantiation::tags::{normalize, DespawnTaggedEvent, TagRegistry},
+    player_control::ui_layer::UiLayer,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+/// How many lines of output the console keeps.
+const HISTORY_LENGTH: usize = 50;
+
+/// A console for acting on groups of entities by their tags, e.g. `despawn #props` or `count #enemies`.
+/// Also lists all tags in use and the tags of the entity picked with the inspector, which edits them.
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<TagConsole>()
+        .register_dev_tool("Tag Console", None, |_: In<bool>| {})
+        .add_systems(
+            Update,
+            display_tag_console
+                .run_if(|dev_tools: Res<DevTools>| dev_tools.is_active("Tag Console")),
+        );
+}
+
+#[derive(Debug, Default, Resource)]
+struct TagConsole {
+    input: String,
+    history: Vec<String>,
+}
+
+fn display_tag_console(
+    mut console: ResMut<TagConsole>,
+    registry: Res<TagRegistry>,
+    inspected: Res<Inspected>,
+    mut despawn_events: EventWriter<DespawnTaggedEvent>,
+    mut egui_contexts: EguiContexts,
+) {
+    let console = &mut *console;
+    egui::Window::new("Tag Console")
+        .order(UiLayer::Dev.order())
+        .default_width(350.)
+        .default_pos(egui::pos2(380., 320.))
+        .show(egui_contexts.ctx_mut(), |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(150.)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for line in &console.history {
+                        ui.monospace(line);
+                    }
+                });
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut console.input)
+                    .hint_text("despawn #tag, count #tag")
+                    .desired_width(f32::INFINITY),
+            );
+            if response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+                let command = std::mem::take(&mut console.input);
+                let output = run_command(&command, &registry, &mut despawn_events);
+                console.history.push(format!("> {command}"));
+                console.history.push(output);
+                let excess = console.history.len().saturating_sub(HISTORY_LENGTH);
+                console.history.drain(..excess);
+                response.request_focus();
+            }
+            ui.separator();
+
+            if let Some(entity) = inspected.entity {
+                let tags = registry.tags_of(entity);
+                if tags.is_empty() {
+                    ui.label("The inspected entity has no tags");
+                } else {
+                    ui.label(format!("Inspected: #{}", tags.join(" #")));
+                }
+            }
+            ui.collapsing("All tags", |ui| {
+                let mut tags: Vec<_> = registry.all().collect();
+                tags.sort_unstable();
+                for (tag, count) in tags {
+                    ui.label(format!("#{tag}: {count}"));
+                }
+            });
+        });
+}
+
+fn run_command(
+    command: &str,
+    registry: &TagRegistry,
+    despawn_events: &mut EventWriter<DespawnTaggedEvent>,
+) -> String {
+    let mut words = command.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some("count"), Some(tag), None) => {
+            format!(
+                "{} entities tagged #{}",
+                registry.count(tag),
+                normalize(tag)
+            )
+        }
+        (Some("despawn"), Some(tag), None) => {
+            despawn_events.send(DespawnTaggedEvent {
+                tag: tag.to_string(),
+            });
+            format!(
+                "Despawning {} entities tagged #{}",
+                registry.count(tag),
+                normalize(tag)
+            )
+        }
+        _ => format!("Unknown command \"{command}\". Try despawn #tag or count #tag"),
+    }
+}
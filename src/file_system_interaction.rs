@@ -2,12 +2,30 @@ use bevy::prelude::*;
 
 pub(crate) mod asset_loading;
 pub(crate) mod audio;
+pub(crate) mod autosave;
 pub(crate) mod config;
+pub(crate) mod localization;
+pub(crate) mod music;
+pub(crate) mod save;
+pub(crate) mod spatial_audio;
 
 /// Handles loading and saving of levels and save states to disk.
 /// Split into the following sub-plugins:
 /// - [`asset_loading::plugin`] handles loading of assets.els.
 /// - [`audio::plugin`]: Handles audio initialization
+/// - [`autosave::plugin`]: Saves the game on its own at checkpoints, portals and regular intervals
+/// - [`localization::plugin`]: Handles translations of player-facing text
+/// - [`music::plugin`]: Handles background music
+/// - [`save::plugin`]: Handles writing and reading save files
+/// - [`spatial_audio::plugin`]: Handles sounds placed in the world, their occlusion and reverb
 pub(super) fn plugin(app: &mut App) {
-    app.add_plugins((asset_loading::plugin, audio::plugin));
+    app.add_plugins((
+        asset_loading::plugin,
+        audio::plugin,
+        autosave::plugin,
+        localization::plugin,
+        music::plugin,
+        save::plugin,
+        spatial_audio::plugin,
+    ));
 }
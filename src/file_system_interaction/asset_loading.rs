@@ -1,4 +1,14 @@
-use crate::{file_system_interaction::config::GameConfig, GameState};
+use crate::{
+    file_system_interaction::{config::GameConfig, localization::StringTable, music::MusicTable},
+    level_instantiation::{level_overrides::LevelOverrideTable, spawn_queue::BlueprintVariants},
+    movement::character_controller::MovementConfig,
+    particles::attached::EffectTable,
+    player_control::{actions::glyphs::GlyphAtlas, emote_wheel::EmoteTable},
+    world_interaction::{
+        dialog::stage_directions::StageDirectionTable, shop::ShopTable, tutorial::TutorialTable,
+    },
+    GameState,
+};
 use bevy::{gltf::Gltf, prelude::*, utils::HashMap};
 use bevy_asset_loader::prelude::*;
 use bevy_common_assets::toml::TomlAssetPlugin;
@@ -59,6 +69,28 @@ pub(crate) struct GrassAssets {
 pub(crate) struct ConfigAssets {
     #[asset(key = "game_config")]
     pub(crate) _game: Handle<GameConfig>,
+    #[asset(key = "music_table")]
+    pub(crate) _music: Handle<MusicTable>,
+    #[asset(key = "glyph_atlas")]
+    pub(crate) _glyphs: Handle<GlyphAtlas>,
+    #[asset(key = "emote_table")]
+    pub(crate) _emotes: Handle<EmoteTable>,
+    #[asset(key = "tutorial_table")]
+    pub(crate) _tutorials: Handle<TutorialTable>,
+    #[asset(key = "blueprint_variants")]
+    pub(crate) _variants: Handle<BlueprintVariants>,
+    #[asset(key = "movement_config")]
+    pub(crate) _movement: Handle<MovementConfig>,
+    #[asset(key = "stage_directions")]
+    pub(crate) _stage_directions: Handle<StageDirectionTable>,
+    #[asset(key = "shop_table")]
+    pub(crate) _shops: Handle<ShopTable>,
+    #[asset(key = "effect_table")]
+    pub(crate) _effects: Handle<EffectTable>,
+    #[asset(key = "level_overrides")]
+    pub(crate) _level_overrides: Handle<LevelOverrideTable>,
+    #[asset(key = "string_tables", collection(typed))]
+    pub(crate) _strings: Vec<Handle<StringTable>>,
 }
 
 fn show_progress(
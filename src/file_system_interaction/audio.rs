@@ -1,11 +1,18 @@
-use crate::{file_system_interaction::asset_loading::AudioAssets, GameState};
+use crate::{
+    file_system_interaction::{
+        asset_loading::AudioAssets, config::GameConfig, spatial_audio::SpatialAudioChannel,
+    },
+    movement::slow_motion::TimeScale,
+    GameState,
+};
 use bevy::prelude::*;
 use bevy_kira_audio::prelude::{Audio, *};
 
-/// Handles initialization of all sounds.
+/// Handles initialization of all sounds and pitches sound effects along with the [`TimeScale`].
 pub(super) fn plugin(app: &mut App) {
     app.add_plugins(AudioPlugin)
-        .add_systems(OnExit(GameState::Loading), init_audio);
+        .add_systems(OnExit(GameState::Loading), init_audio)
+        .add_systems(Update, pitch_with_time_scale);
 }
 
 #[derive(Debug, Clone, Resource)]
@@ -22,3 +29,23 @@ fn init_audio(mut commands: Commands, audio_assets: Res<AudioAssets>, audio: Res
         .handle();
     commands.insert_resource(AudioHandles { walking: handle });
 }
+
+fn pitch_with_time_scale(
+    time_scale: Res<TimeScale>,
+    config: Res<GameConfig>,
+    audio: Res<Audio>,
+    spatial_audio: Res<AudioChannel<SpatialAudioChannel>>,
+    mut playback_rate: Local<Option<f64>>,
+) {
+    let rate = if config.audio.slow_motion_pitch {
+        f64::from(time_scale.current)
+    } else {
+        1.
+    };
+    if *playback_rate == Some(rate) {
+        return;
+    }
+    *playback_rate = Some(rate);
+    audio.set_playback_rate(rate);
+    spatial_audio.set_playback_rate(rate);
+}
@@ -0,0 +1,335 @@
+use crate::{
+    file_system_interaction::{
+        config::GameConfig,
+        localization::{t, Strings},
+        save::{list_save_slots, GameSavedEvent, SaveGameEvent, SaveSlot, ThumbnailCapture},
+    },
+    level_instantiation::{
+        on_spawn::Player,
+        portal::{Portal, Travel},
+    },
+    movement::{character_controller::GroundedState, slow_motion::UnscaledTime},
+    player_control::{screen_effects::ScreenEffects, ui_layer::UiLayer},
+    util::math_trait_ext::F32Ext,
+    world_interaction::{nameplate::Health, world_flags::WorldFlagChanged},
+    GameState,
+};
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashSet};
+use bevy_egui::{egui, EguiContexts};
+use bevy_yarnspinner::prelude::DialogueRunner;
+use serde::{Deserialize, Serialize};
+
+/// Auto-save slots are named this followed by their number, e.g. `autosave-2`.
+pub(crate) const AUTOSAVE_SLOT_PREFIX: &str = "autosave-";
+/// Approaching a portal this much closer than its entry radius counts as about to enter it, in meters.
+const PORTAL_APPROACH_MARGIN: f32 = 2.;
+/// How long the saving indicator shows after an auto-save, in seconds.
+const INDICATOR_DURATION: f32 = 1.5;
+/// How long a failed save is reported, in seconds.
+const FAILURE_DURATION: f32 = 6.;
+
+/// Saves the game on its own into a rotating set of auto-save slots, overwriting the oldest one.
+/// The triggers are configured in the `autosave` section of the game config: reaching a [`Checkpoint`], every few minutes of play,
+/// setting a world flag, which is how quest objectives are tracked, and approaching a [`Portal`].
+/// An auto-save waits for a safe moment: not during dialog or cutscenes, not in mid-air, and not right after taking damage.
+/// A small indicator shows while auto-saving, and any save that fails is reported to players instead of being lost silently.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Checkpoint>()
+        .init_resource::<AutoSaveState>()
+        .add_systems(
+            Update,
+            (
+                track_damage,
+                trigger_autosaves,
+                run_pending_autosave,
+                display_save_status,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Auto-saves when the player comes within `radius` of it, e.g. at the entrance of a dungeon.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct Checkpoint {
+    /// In meters.
+    pub(crate) radius: f32,
+}
+
+impl Default for Checkpoint {
+    fn default() -> Self {
+        Self { radius: 3. }
+    }
+}
+
+/// Whether the slot is one of the auto-save slots, which the load UI lists separately.
+pub(crate) fn is_autosave_slot(slot: &str) -> bool {
+    slot.starts_with(AUTOSAVE_SLOT_PREFIX)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AutoSaveTrigger {
+    Checkpoint,
+    Interval,
+    Objective,
+    Portal,
+}
+
+#[derive(Debug, Default, Resource)]
+struct AutoSaveState {
+    /// The trigger waiting for a safe moment to save.
+    pending: Option<AutoSaveTrigger>,
+    /// Seconds of play since the last auto-save.
+    since_save: f32,
+    /// Seconds of play since the player last took damage, `None` if they have not yet.
+    since_damage: Option<f32>,
+    /// The checkpoints and portals the player was close to last frame, so that only coming close triggers.
+    /// `None` right after arriving in a level, so that arriving at a checkpoint, e.g. by loading a save made there, does not.
+    nearby: Option<HashSet<Entity>>,
+    /// Seconds left to show the saving indicator.
+    indicator: f32,
+    /// The last failed save and the seconds left to show it.
+    failure: Option<(String, f32)>,
+}
+
+/// What prevents saving right now. Deferred auto-saves wait for all of these to clear.
+#[derive(SystemParam)]
+struct SaveSafety<'w, 's> {
+    travel: Res<'w, Travel>,
+    virtual_time: Res<'w, Time<Virtual>>,
+    screen_effects: Res<'w, ScreenEffects>,
+    dialogue_runners: Query<'w, 's, &'static DialogueRunner>,
+    players: Query<'w, 's, &'static GroundedState, With<Player>>,
+}
+
+impl SaveSafety<'_, '_> {
+    fn is_safe(&self) -> bool {
+        !self.travel.is_traveling()
+            && !self.virtual_time.is_paused()
+            && !self.screen_effects.is_busy()
+            && !self.screen_effects.is_letterboxed()
+            && !self.dialogue_runners.iter().any(DialogueRunner::is_running)
+            && self
+                .players
+                .get_single()
+                .is_ok_and(|grounded| !grounded.airborne)
+    }
+}
+
+fn track_damage(
+    time: Res<Time>,
+    mut state: ResMut<AutoSaveState>,
+    players: Query<&Health, With<Player>>,
+    mut last_health: Local<Option<f32>>,
+) {
+    state.since_save += time.delta_seconds();
+    if let Some(since_damage) = state.since_damage.as_mut() {
+        *since_damage += time.delta_seconds();
+    }
+    let health = players.get_single().ok().map(|health| health.current);
+    if let (Some(health), Some(last)) = (health, *last_health) {
+        if health < last {
+            state.since_damage = Some(0.);
+        }
+    }
+    *last_health = health;
+}
+
+fn trigger_autosaves(
+    mut state: ResMut<AutoSaveState>,
+    config: Res<GameConfig>,
+    travel: Res<Travel>,
+    mut flag_changes: EventReader<WorldFlagChanged>,
+    players: Query<&GlobalTransform, With<Player>>,
+    checkpoints: Query<(Entity, &Checkpoint, &GlobalTransform)>,
+    portals: Query<(Entity, &Portal, &GlobalTransform)>,
+) {
+    let config = &config.autosave;
+    let objective_completed = flag_changes.read().any(|change| change.value.is_some());
+    if !config.enabled {
+        return;
+    }
+    if config.on_objective && objective_completed {
+        state.pending = Some(AutoSaveTrigger::Objective);
+    }
+    if config.interval_minutes > 0. && state.since_save >= config.interval_minutes * 60. {
+        state.pending.get_or_insert(AutoSaveTrigger::Interval);
+    }
+
+    if travel.is_traveling() {
+        state.nearby = None;
+        return;
+    }
+    let Ok(player) = players.get_single() else {
+        return;
+    };
+    let is_near = |transform: &GlobalTransform, radius: f32| {
+        transform
+            .translation()
+            .distance_squared(player.translation())
+            < radius.squared()
+    };
+    let nearby_checkpoints = checkpoints
+        .iter()
+        .filter(|(_, checkpoint, transform)| is_near(transform, checkpoint.radius))
+        .map(|(entity, ..)| (entity, AutoSaveTrigger::Checkpoint));
+    let nearby_portals = portals
+        .iter()
+        .filter(|(_, portal, transform)| {
+            is_near(transform, portal.entry_radius + PORTAL_APPROACH_MARGIN)
+        })
+        .map(|(entity, ..)| (entity, AutoSaveTrigger::Portal));
+    let nearby: Vec<_> = nearby_checkpoints.chain(nearby_portals).collect();
+    if let Some(previous) = &state.nearby {
+        let approached = nearby
+            .iter()
+            .filter(|(entity, _)| !previous.contains(entity))
+            .find(|(_, trigger)| match trigger {
+                AutoSaveTrigger::Checkpoint => config.on_checkpoint,
+                AutoSaveTrigger::Portal => config.before_portal,
+                _ => false,
+            });
+        if let Some(&(_, trigger)) = approached {
+            state.pending = Some(trigger);
+        }
+    }
+    state.nearby = Some(nearby.into_iter().map(|(entity, _)| entity).collect());
+}
+
+fn run_pending_autosave(
+    mut state: ResMut<AutoSaveState>,
+    config: Res<GameConfig>,
+    safety: SaveSafety,
+    mut save_events: EventWriter<SaveGameEvent>,
+) {
+    let config = &config.autosave;
+    let Some(trigger) = state.pending else {
+        return;
+    };
+    let recently_damaged = state
+        .since_damage
+        .is_some_and(|since_damage| since_damage < config.damage_grace);
+    if recently_damaged || state.since_save < config.min_interval || !safety.is_safe() {
+        return;
+    }
+    let slot = next_autosave_slot(&list_save_slots(), config.slots);
+    info!("Auto-saving to {slot} because of {trigger:?}");
+    save_events.send(SaveGameEvent { slot });
+    state.pending = None;
+    state.since_save = 0.;
+    state.indicator = INDICATOR_DURATION;
+}
+
+/// The auto-save slot to write next: an unused one if there is any, otherwise the one saved the longest time ago.
+fn next_autosave_slot(slots: &[SaveSlot], count: usize) -> String {
+    let saved_at = |name: &str| {
+        slots.iter().find(|slot| slot.name == name).map(|slot| {
+            slot.metadata
+                .as_ref()
+                .map_or(0, |metadata| metadata.saved_at)
+        })
+    };
+    (1..=count.max(1))
+        .map(|index| format!("{AUTOSAVE_SLOT_PREFIX}{index}"))
+        .min_by_key(|name| saved_at(name))
+        .unwrap_or_default()
+}
+
+fn display_save_status(
+    time: UnscaledTime,
+    mut state: ResMut<AutoSaveState>,
+    mut saved_events: EventReader<GameSavedEvent>,
+    thumbnail_capture: Res<ThumbnailCapture>,
+    strings: Strings,
+    mut egui_contexts: EguiContexts,
+) {
+    for event in saved_events.read() {
+        if let Some(error) = &event.error {
+            state.failure = Some((error.clone(), FAILURE_DURATION));
+        }
+    }
+    let dt = time.delta_seconds();
+    state.indicator = (state.indicator - dt).max(0.);
+    if let Some((_, remaining)) = state.failure.as_mut() {
+        *remaining -= dt;
+    }
+    if state
+        .failure
+        .as_ref()
+        .is_some_and(|(_, remaining)| *remaining <= 0.)
+    {
+        state.failure = None;
+    }
+    // Keeps the indicator out of the thumbnail of the save it announces
+    if thumbnail_capture.is_capturing() {
+        return;
+    }
+
+    let ctx = egui_contexts.ctx_mut();
+    if state.indicator > 0. {
+        egui::Area::new("Auto-save Indicator")
+            .order(UiLayer::Hud.order())
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-20., -20.))
+            .interactable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(egui::Spinner::new().size(14.));
+                    ui.label(
+                        egui::RichText::new(t!(strings, "autosave.saving"))
+                            .color(egui::Color32::from_white_alpha(200)),
+                    );
+                });
+            });
+    }
+    if let Some((error, _)) = &state.failure {
+        // Above the pause menu, so that failed saves started from there are reported too
+        egui::Area::new("Save Failure")
+            .order(UiLayer::SystemModal.order())
+            .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-20., 20.))
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::none()
+                    .fill(egui::Color32::from_rgba_unmultiplied(120, 20, 20, 220))
+                    .rounding(6.)
+                    .inner_margin(egui::Margin::same(10.))
+                    .show(ui, |ui| {
+                        ui.set_max_width(320.);
+                        ui.visuals_mut().override_text_color = Some(egui::Color32::WHITE);
+                        ui.label(t!(strings, "save.failed", error = error));
+                    });
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_system_interaction::save::SaveMetadata;
+    use std::path::PathBuf;
+
+    #[test]
+    fn autosaves_fill_free_slots_before_overwriting_the_oldest() {
+        let slot = |name: &str, saved_at: Option<u64>| SaveSlot {
+            name: name.to_string(),
+            metadata: saved_at.map(|saved_at| SaveMetadata {
+                saved_at,
+                ..default()
+            }),
+            thumbnail: PathBuf::new(),
+        };
+        let mut slots = vec![slot("slot-1", Some(50)), slot("autosave-1", Some(100))];
+        assert_eq!(next_autosave_slot(&slots, 3), "autosave-2");
+
+        slots.push(slot("autosave-2", Some(300)));
+        slots.push(slot("autosave-3", Some(200)));
+        assert_eq!(next_autosave_slot(&slots, 3), "autosave-1");
+        // Slots beyond the configured count are left alone
+        assert_eq!(next_autosave_slot(&slots, 2), "autosave-1");
+
+        // Saves without metadata are treated as the oldest
+        slots.push(slot("autosave-4", None));
+        assert_eq!(next_autosave_slot(&slots, 4), "autosave-4");
+    }
+}
@@ -7,6 +7,18 @@ use serde::{Deserialize, Serialize};
 pub(crate) struct GameConfig {
     pub(crate) camera: Camera,
     pub(crate) player: PlayerEffects,
+    pub(crate) audio: Audio,
+    pub(crate) localization: Localization,
+    pub(crate) accessibility: Accessibility,
+    pub(crate) footprints: Footprints,
+    pub(crate) tutorials: Tutorials,
+    pub(crate) graphics: Graphics,
+    pub(crate) waypoints: Waypoints,
+    pub(crate) rumble: Rumble,
+    pub(crate) telemetry: Telemetry,
+    pub(crate) autosave: AutoSave,
+    pub(crate) carry: Carry,
+    pub(crate) respawn_grace: RespawnGrace,
 }
 
 #[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
@@ -56,10 +68,235 @@ pub(crate) struct ThirdPerson {
     pub(crate) tracking_smoothing: f32,
     pub(crate) zoom_in_smoothing: f32,
     pub(crate) zoom_out_smoothing: f32,
+    /// The player model starts fading out when the camera is closer to it than this.
+    pub(crate) fade_distance: f32,
+    /// The player model is invisible when the camera is closer to it than this. Its shadow remains.
+    pub(crate) hide_distance: f32,
 }
 
 #[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
 #[reflect(Serialize, Deserialize)]
 pub(crate) struct PlayerEffects {
     pub(crate) sprint_effect_speed_threshold: f32,
+    /// Show an icon on the HUD while zones or the level change how the player moves.
+    pub(crate) movement_modifier_icon: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) struct Audio {
+    pub(crate) music_volume: f32,
+    /// Whether sound effects drop in pitch along with the game's [`TimeScale`](crate::movement::slow_motion::TimeScale).
+    /// Music always plays at its normal pitch.
+    pub(crate) slow_motion_pitch: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) struct Localization {
+    /// E.g. `"en-US"`. See `assets/localization` for the available locales.
+    pub(crate) locale: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) struct Accessibility {
+    pub(crate) sprint_mode: ActionMode,
+    /// Applies to interactions that need the button to be held, see [`HoldToInteract`](crate::world_interaction::interaction_ui::HoldToInteract).
+    pub(crate) interact_mode: ActionMode,
+    pub(crate) emote_wheel_mode: ActionMode,
+    /// Multiplies how long buttons need to be held.
+    pub(crate) hold_duration_multiplier: f32,
+    /// Continue dialog lines on their own after the time it takes to read them.
+    pub(crate) auto_advance_dialog: bool,
+    /// Characters per second, used to time auto-advancing and subtitles.
+    pub(crate) reading_speed: f32,
+    pub(crate) subtitles: bool,
+    pub(crate) subtitle_size: f32,
+    /// Between 0 and 1.
+    pub(crate) subtitle_background_opacity: f32,
+    /// Plays a soft chime from the direction of something the player could interact with if it turned towards it.
+    pub(crate) interaction_cue: bool,
+    /// Path of the chime. No cue plays when unset.
+    pub(crate) interaction_cue_sound: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) struct Footprints {
+    pub(crate) enabled: bool,
+    /// When more footprints than this are on the ground, the oldest are reused.
+    pub(crate) max_count: usize,
+    /// In seconds.
+    pub(crate) lifetime: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) struct Tutorials {
+    pub(crate) enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) struct Waypoints {
+    /// The waypoint is cleared when the player gets this close to it, in meters.
+    pub(crate) reach_radius: f32,
+    pub(crate) beacon_height: f32,
+    /// Path of the sound played when reaching the waypoint. No sound plays when unset.
+    pub(crate) reached_sound: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) struct Rumble {
+    pub(crate) enabled: bool,
+    /// Scales the strength of all gamepad rumble, between 0 and 1.
+    pub(crate) intensity: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) struct Telemetry {
+    /// Records playtesting sessions into the `telemetry` directory. Only has an effect in builds with the `telemetry` feature.
+    pub(crate) enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) struct AutoSave {
+    pub(crate) enabled: bool,
+    /// How many auto-save slots to rotate through. The oldest one is overwritten.
+    pub(crate) slots: usize,
+    /// Auto-save after this many minutes of play without one. 0 turns this off.
+    pub(crate) interval_minutes: f32,
+    pub(crate) on_checkpoint: bool,
+    /// Auto-save when a world flag is set, which is how quest objectives are tracked.
+    pub(crate) on_objective: bool,
+    pub(crate) before_portal: bool,
+    /// Auto-saves wait this many seconds after the player took damage.
+    pub(crate) damage_grace: f32,
+    /// Auto-saves wait until this many seconds of play passed since the last one, so that triggers in quick succession save once.
+    pub(crate) min_interval: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) struct Carry {
+    /// A dragged prop is let go on ground steeper than this, in degrees.
+    pub(crate) max_drag_slope: f32,
+    /// Played when trying to pick up something too heavy, e.g. a headshake. No animation plays when unset.
+    pub(crate) refuse_animation: Option<String>,
+}
+
+/// Protects the player right after arriving in a level, see [`GracePeriod`](crate::world_interaction::respawn_grace::GracePeriod).
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) struct RespawnGrace {
+    /// How long damage against the player is ignored, in seconds.
+    pub(crate) duration: f32,
+    /// How long NPCs wait before they start following the player, in seconds.
+    pub(crate) aggro_delay: f32,
+    /// How long the player model is shown and hidden in turn while damage is ignored, in seconds.
+    pub(crate) blink_interval: f32,
+}
+
+/// The settings below [`Graphics::preset`] are only used with [`GraphicsPreset::Custom`].
+/// Systems read them through [`Graphics::resolve`].
+#[derive(Debug, Clone, Copy, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) struct Graphics {
+    pub(crate) preset: GraphicsPreset,
+    pub(crate) shadow_quality: ShadowQuality,
+    /// How far from the camera directional lights cast shadows, in meters.
+    pub(crate) shadow_distance: f32,
+    /// How many shadow maps the shadow distance is split into. More cascades give sharper shadows up close.
+    pub(crate) shadow_cascades: usize,
+    /// The fraction of grass blades that are drawn, between 0 and 1.
+    pub(crate) grass_density: f32,
+    /// Scales how many particles and footprints may exist at the same time.
+    pub(crate) particle_budget: f32,
+    /// Scales how far from the camera particle effects are still spawned.
+    pub(crate) effect_distance: f32,
+    /// Off-screen characters further than this from the camera animate at half the rate, at twice the distance
+    /// at a quarter of the rate, and beyond four times the distance not at all. In meters.
+    pub(crate) animation_lod_distance: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) enum GraphicsPreset {
+    Low,
+    Medium,
+    #[default]
+    High,
+    /// Uses the settings of [`Graphics`] as configured.
+    Custom,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) enum ShadowQuality {
+    /// Characters only get blob shadows.
+    Low,
+    /// Characters cast real shadows within the shadow distance and get blob shadows beyond it.
+    #[default]
+    High,
+}
+
+impl Graphics {
+    /// The settings of the [`Graphics::preset`].
+    pub(crate) fn resolve(&self) -> Graphics {
+        let preset = self.preset;
+        match preset {
+            GraphicsPreset::Low => Graphics {
+                preset,
+                shadow_quality: ShadowQuality::Low,
+                shadow_distance: 20.,
+                shadow_cascades: 1,
+                grass_density: 0.3,
+                particle_budget: 0.25,
+                effect_distance: 0.5,
+                animation_lod_distance: 8.,
+            },
+            GraphicsPreset::Medium => Graphics {
+                preset,
+                shadow_quality: ShadowQuality::High,
+                shadow_distance: 30.,
+                shadow_cascades: 2,
+                grass_density: 0.6,
+                particle_budget: 0.5,
+                effect_distance: 0.75,
+                animation_lod_distance: 15.,
+            },
+            GraphicsPreset::High => Graphics {
+                preset,
+                shadow_quality: ShadowQuality::High,
+                shadow_distance: 40.,
+                shadow_cascades: 4,
+                grass_density: 1.,
+                particle_budget: 1.,
+                effect_distance: 1.,
+                animation_lod_distance: 25.,
+            },
+            GraphicsPreset::Custom => *self,
+        }
+    }
+}
+
+impl Accessibility {
+    /// How long a text stays on screen for the player to read it, in seconds.
+    pub(crate) fn reading_time(&self, text: &str) -> f32 {
+        const MIN_READING_TIME: f32 = 1.5;
+        MIN_READING_TIME + text.chars().count() as f32 / self.reading_speed.max(1.)
+    }
+}
+
+/// Whether an action is active while its button is held, or toggled on and off by pressing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) enum ActionMode {
+    #[default]
+    Hold,
+    Toggle,
 }
@@ -0,0 +1,210 @@
+use crate::file_system_interaction::config::GameConfig;
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap, utils::HashSet};
+use bevy_common_assets::ron::RonAssetPlugin;
+use bevy_yarnspinner::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// The locale every other locale falls back to. Its [`StringTable`] is expected to contain every key.
+pub(crate) const DEFAULT_LOCALE: &str = "en-US";
+/// Locales the yarn files are translated to. Yarn Spinner generates the missing
+/// `assets/dialogue/<locale>.strings.csv` files in dev builds.
+const DIALOGUE_TRANSLATIONS: &[&str] = &[];
+
+/// Translates player-facing strings. UI code looks them up through [`Strings`], usually via [`t!`].
+/// The tables live in `assets/localization/<locale>.strings.ron`. The locale is set through
+/// `localization.locale` in the game config and can be changed at runtime by replacing [`CurrentLocale`].
+pub(super) fn plugin(app: &mut App) {
+    app.add_plugins(RonAssetPlugin::<StringTable>::new(&["strings.ron"]))
+        .register_type::<CurrentLocale>()
+        .init_resource::<CurrentLocale>()
+        .add_systems(
+            Update,
+            (
+                apply_locale_from_config.run_if(resource_exists_and_changed::<GameConfig>),
+                warn_about_missing_translations,
+                set_dialogue_language.run_if(resource_exists::<YarnProject>),
+            )
+                .chain(),
+        );
+}
+
+/// The [`Localizations`] the yarn project is compiled with.
+pub(crate) fn dialogue_localizations() -> Localizations {
+    Localizations {
+        base_localization: DEFAULT_LOCALE.into(),
+        translations: DIALOGUE_TRANSLATIONS
+            .iter()
+            .map(|&locale| locale.into())
+            .collect(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Asset, Reflect, Serialize, Deserialize, Default)]
+pub(crate) struct StringTable {
+    /// E.g. `"en-US"`.
+    pub(crate) locale: String,
+    /// The name of the language in that language, shown when choosing a language.
+    pub(crate) name: String,
+    /// Values may contain `{placeholders}`, see [`Strings::t_with`].
+    pub(crate) strings: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Resource, Reflect, Serialize, Deserialize)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct CurrentLocale(pub(crate) String);
+
+impl Default for CurrentLocale {
+    fn default() -> Self {
+        Self(DEFAULT_LOCALE.to_string())
+    }
+}
+
+/// Looks up translations in the [`CurrentLocale`], falling back to the [`DEFAULT_LOCALE`] and then to the key itself.
+/// Because the key is the last fallback, text authored in levels and data files, like the title of a
+/// [`Readable`](crate::world_interaction::readable::Readable), can be passed in as well and is only translated
+/// when a table contains it.
+#[derive(SystemParam)]
+pub(crate) struct Strings<'w> {
+    locale: Res<'w, CurrentLocale>,
+    tables: Res<'w, Assets<StringTable>>,
+}
+
+impl Strings<'_> {
+    pub(crate) fn get(&self, key: &str) -> Option<&str> {
+        [self.locale.0.as_str(), DEFAULT_LOCALE]
+            .into_iter()
+            .filter_map(|locale| self.table(locale))
+            .find_map(|table| table.strings.get(key))
+            .map(String::as_str)
+    }
+
+    pub(crate) fn t(&self, key: &str) -> String {
+        self.get(key).unwrap_or(key).to_string()
+    }
+
+    /// Like [`Strings::t`], but replaces every `{name}` with the matching argument.
+    pub(crate) fn t_with(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        args.iter().fold(self.t(key), |text, (name, value)| {
+            text.replace(&format!("{{{name}}}"), &value.to_string())
+        })
+    }
+
+    /// The locales that have a table, together with their display names.
+    pub(crate) fn available_locales(&self) -> Vec<(&str, &str)> {
+        let mut locales: Vec<_> = self
+            .tables
+            .iter()
+            .map(|(_, table)| (table.locale.as_str(), table.name.as_str()))
+            .collect();
+        locales.sort_unstable();
+        locales
+    }
+
+    pub(crate) fn current_locale(&self) -> &str {
+        &self.locale.0
+    }
+
+    fn table(&self, locale: &str) -> Option<&StringTable> {
+        self.tables
+            .iter()
+            .map(|(_, table)| table)
+            .find(|table| table.locale == locale)
+    }
+}
+
+/// Translates a key through [`Strings`], e.g. `t!(strings, "interaction.talk")`
+/// or `t!(strings, "pause.hint", key = "ESC")`.
+macro_rules! t {
+    ($strings:expr, $key:expr) => {
+        $strings.t($key)
+    };
+    ($strings:expr, $key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $strings.t_with(
+            $key,
+            &[$((stringify!($name), &$value as &dyn ::std::fmt::Display)),+],
+        )
+    };
+}
+pub(crate) use t;
+
+fn apply_locale_from_config(
+    config: Res<GameConfig>,
+    mut locale: ResMut<CurrentLocale>,
+    mut configured_locale: Local<Option<String>>,
+) {
+    let configured = &config.localization.locale;
+    // Only react to changes of the config itself, so that hot reloading does not undo a runtime switch
+    if configured_locale.as_ref() != Some(configured) {
+        *configured_locale = Some(configured.clone());
+        locale.0 = configured.clone();
+    }
+}
+
+fn warn_about_missing_translations(
+    locale: Res<CurrentLocale>,
+    tables: Res<Assets<StringTable>>,
+    mut table_events: EventReader<AssetEvent<StringTable>>,
+    mut warned_locales: Local<HashSet<String>>,
+) {
+    if table_events.read().count() > 0 {
+        warned_locales.clear();
+    }
+    if warned_locales.contains(&locale.0) {
+        return;
+    }
+    let find_table = |locale: &str| {
+        tables
+            .iter()
+            .map(|(_, table)| table)
+            .find(|table| table.locale == locale)
+    };
+    let Some(default_table) = find_table(DEFAULT_LOCALE) else {
+        return;
+    };
+    warned_locales.insert(locale.0.clone());
+    let Some(table) = find_table(&locale.0) else {
+        warn!(
+            "No string table for locale {}, falling back to {DEFAULT_LOCALE}",
+            locale.0
+        );
+        return;
+    };
+    let mut missing: Vec<_> = default_table
+        .strings
+        .keys()
+        .filter(|key| !table.strings.contains_key(*key))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        missing.sort_unstable();
+        warn!(
+            "Locale {} is missing the following strings, falling back to {DEFAULT_LOCALE}: {}",
+            locale.0,
+            missing.join(", ")
+        );
+    }
+}
+
+fn set_dialogue_language(
+    locale: Res<CurrentLocale>,
+    project: Res<YarnProject>,
+    mut dialogue_runners: Query<&mut DialogueRunner>,
+    added_runners: Query<(), Added<DialogueRunner>>,
+) {
+    if !locale.is_changed() && added_runners.is_empty() {
+        return;
+    }
+    let language = Language::from(locale.0.as_str());
+    let is_supported = project
+        .localizations()
+        .is_some_and(|localizations| localizations.supports_language(&language));
+    let language = if is_supported {
+        language
+    } else {
+        Language::from(DEFAULT_LOCALE)
+    };
+    for mut dialogue_runner in dialogue_runners.iter_mut() {
+        dialogue_runner.set_language(language.clone());
+    }
+}
@@ -0,0 +1,219 @@
+use crate::{
+    file_system_interaction::config::GameConfig,
+    level_instantiation::on_spawn::{MusicRegion, Player},
+    world_interaction::dialog::CurrentDialogTarget,
+    GameState,
+};
+use bevy::{prelude::*, utils::HashMap};
+use bevy_common_assets::ron::RonAssetPlugin;
+use bevy_kira_audio::prelude::{AudioSource, *};
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How long it takes for a track or layer to fade in or out.
+const CROSSFADE: Duration = Duration::from_millis(1500);
+/// Pausing should silence the music quicker than a normal crossfade.
+const PAUSE_FADE: Duration = Duration::from_millis(300);
+/// How loud the base layer is while the dialog layer plays.
+const DIALOG_BASE_VOLUME: f64 = 0.5;
+
+/// Plays background music depending on which [`MusicRegion`] the player is in.
+/// Each track in the [`MusicTable`] has a base layer and optional layers that fade in
+/// during dialog and when [`MusicMood::tension`] is set. Tracks that are faded out are paused instead of stopped,
+/// so returning to a region resumes its track where it left off.
+pub(super) fn plugin(app: &mut App) {
+    app.add_plugins(RonAssetPlugin::<MusicTable>::new(&["music.ron"]))
+        .add_audio_channel::<MusicChannel>()
+        .register_type::<MusicMood>()
+        .init_resource::<MusicMood>()
+        .init_resource::<MusicPlayback>()
+        .add_systems(
+            Update,
+            (update_active_track, update_music_layers)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Maps track names used by [`MusicRegion`]s to the audio files of their layers.
+/// See `assets/config/config.music.ron`.
+#[derive(Debug, Clone, PartialEq, Asset, Reflect, Serialize, Deserialize, Default)]
+pub(crate) struct MusicTable {
+    pub(crate) tracks: HashMap<String, MusicTrack>,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+pub(crate) struct MusicTrack {
+    /// Path to the layer that is always playing while the track is active.
+    pub(crate) base: String,
+    /// Path to the layer that plays while [`MusicMood::tension`] is set.
+    #[serde(default)]
+    pub(crate) tension: Option<String>,
+    /// Path to the layer that plays while the player is in a dialog.
+    #[serde(default)]
+    pub(crate) dialog: Option<String>,
+}
+
+/// Set by gameplay systems to layer stems on top of the current track.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct MusicMood {
+    /// Something is chasing the player.
+    pub(crate) tension: bool,
+}
+
+#[derive(Resource)]
+struct MusicChannel;
+
+#[derive(Debug, Default, Resource)]
+struct MusicPlayback {
+    active_track: Option<String>,
+    tracks: HashMap<String, TrackPlayback>,
+}
+
+#[derive(Debug)]
+struct TrackPlayback {
+    layers: Vec<LayerPlayback>,
+    paused: bool,
+}
+
+#[derive(Debug)]
+struct LayerPlayback {
+    kind: LayerKind,
+    instance: Handle<AudioInstance>,
+    volume: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LayerKind {
+    Base,
+    Tension,
+    Dialog,
+}
+
+fn update_active_track(
+    regions: Query<(&MusicRegion, &CollidingEntities)>,
+    player_query: Query<Entity, With<Player>>,
+    mut playback: ResMut<MusicPlayback>,
+) {
+    let Some(player) = player_query.iter().next() else {
+        return;
+    };
+    let active_region = regions
+        .iter()
+        .filter(|(_, colliding)| colliding.contains(&player))
+        .map(|(region, _)| region)
+        .max_by_key(|region| region.priority);
+    // Leaving all regions keeps the last track playing
+    if let Some(region) = active_region {
+        if playback.active_track.as_ref() != Some(&region.track) {
+            playback.active_track = Some(region.track.clone());
+        }
+    }
+}
+
+fn update_music_layers(
+    time: Res<Time<Virtual>>,
+    config: Res<GameConfig>,
+    mood: Res<MusicMood>,
+    dialog_target: Res<CurrentDialogTarget>,
+    music_table: Res<Assets<MusicTable>>,
+    asset_server: Res<AssetServer>,
+    channel: Res<AudioChannel<MusicChannel>>,
+    mut playback: ResMut<MusicPlayback>,
+    mut audio_instances: ResMut<Assets<AudioInstance>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_music_layers").entered();
+    let Some(table) = music_table.iter().next().map(|(_, table)| table) else {
+        return;
+    };
+    let playback = &mut *playback;
+    if let Some(active_track) = playback.active_track.clone() {
+        if !playback.tracks.contains_key(&active_track) {
+            if let Some(track) = table.tracks.get(&active_track) {
+                let layers = start_track(track, &asset_server, &channel);
+                playback.tracks.insert(
+                    active_track,
+                    TrackPlayback {
+                        layers,
+                        paused: false,
+                    },
+                );
+            } else {
+                warn!("Music track \"{active_track}\" is not defined in the music table");
+                playback.active_track = None;
+            }
+        }
+    }
+
+    let volume = f64::from(config.audio.music_volume);
+    let in_dialog = dialog_target.0.is_some();
+    let paused = time.is_paused();
+    for (name, track) in playback.tracks.iter_mut() {
+        let is_active = playback.active_track.as_ref() == Some(name);
+        if is_active == track.paused {
+            track.paused = !is_active;
+            for layer in &track.layers {
+                if let Some(instance) = audio_instances.get_mut(&layer.instance) {
+                    if is_active {
+                        instance.resume(AudioTween::linear(CROSSFADE));
+                    } else {
+                        instance.pause(AudioTween::linear(CROSSFADE));
+                    }
+                }
+            }
+        }
+        if !is_active {
+            continue;
+        }
+        for layer in track.layers.iter_mut() {
+            let target = if paused {
+                0.
+            } else {
+                match layer.kind {
+                    LayerKind::Base if in_dialog => volume * DIALOG_BASE_VOLUME,
+                    LayerKind::Base => volume,
+                    LayerKind::Tension if mood.tension => volume,
+                    LayerKind::Dialog if in_dialog => volume,
+                    LayerKind::Tension | LayerKind::Dialog => 0.,
+                }
+            };
+            if (layer.volume - target).abs() < 1e-3 {
+                continue;
+            }
+            let Some(instance) = audio_instances.get_mut(&layer.instance) else {
+                continue;
+            };
+            let fade = if paused { PAUSE_FADE } else { CROSSFADE };
+            instance.set_volume(target, AudioTween::linear(fade));
+            layer.volume = target;
+        }
+    }
+}
+
+/// Starts all layers of a track at the same time so that they stay in sync.
+/// Their volume starts at zero and is faded in by [`update_music_layers`].
+fn start_track(
+    track: &MusicTrack,
+    asset_server: &AssetServer,
+    channel: &AudioChannel<MusicChannel>,
+) -> Vec<LayerPlayback> {
+    [
+        (LayerKind::Base, Some(&track.base)),
+        (LayerKind::Tension, track.tension.as_ref()),
+        (LayerKind::Dialog, track.dialog.as_ref()),
+    ]
+    .into_iter()
+    .filter_map(|(kind, path)| {
+        let source: Handle<AudioSource> = asset_server.load(path?.clone());
+        let instance = channel.play(source).looped().with_volume(0.).handle();
+        Some(LayerPlayback {
+            kind,
+            instance,
+            volume: 0.,
+        })
+    })
+    .collect()
+}
@@ -0,0 +1,588 @@
+use crate::{
+    level_instantiation::{
+        map::CurrentLevel,
+        on_spawn::Player,
+        portal::{Arrival, LevelStateCache, Travel, TravelEvent},
+    },
+    movement::character_controller::{
+        ActiveMovementModifiers, MovementModifiers, RestoredMovementModifiers,
+    },
+    world_interaction::{
+        inventory::Inventory,
+        npc_memory::NpcMemory,
+        party::Party,
+        race::RaceRecords,
+        readable::CollectedNotes,
+        shop::ShopStates,
+        time_of_day::TimeOfDay,
+        tutorial::CompletedTutorials,
+        waypoint::CustomWaypoint,
+        weather::{SnowCover, Weather, WeatherTransition},
+        world_flags::WorldFlags,
+    },
+    GameState,
+};
+use anyhow::{anyhow, bail, ensure, Context};
+use bevy::{
+    ecs::system::SystemParam, prelude::*, render::view::screenshot::ScreenshotManager,
+    window::PrimaryWindow,
+};
+use bevy_mod_sysfail::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The version of the save format written by this build. Older saves are migrated by [`MIGRATIONS`] when loading.
+const SAVE_VERSION: u32 = 2;
+const SAVE_DIRECTORY: &str = "saves";
+/// The slot used by the quick save and quick load buttons of the pause menu.
+pub(crate) const QUICK_SAVE_SLOT: &str = "quicksave";
+/// Thumbnails are downscaled to fit into this size, in pixels.
+const THUMBNAIL_SIZE: UVec2 = UVec2::new(256, 144);
+
+/// Upgrades saves of older versions to [`SAVE_VERSION`]. The migration at index `i` turns a save of version `i + 1`
+/// into one of version `i + 2`, so they run in order. Migrations work on the RON of the save instead of on our types,
+/// because the types of older versions do not exist anymore.
+///
+/// Every change to the saved types that makes older saves fail to deserialize needs a migration here and a new [`SAVE_VERSION`].
+/// Adding a field with `#[serde(default)]` does not.
+const MIGRATIONS: &[(&str, Migration)] = &[("rename level to current_level", rename_level)];
+
+type Migration = fn(&mut ron::Value) -> anyhow::Result<()>;
+
+const _: () = assert!(
+    MIGRATIONS.len() + 1 == SAVE_VERSION as usize,
+    "Every save version except the first needs a migration from the one before it"
+);
+
+/// Writes and reads save files in the `saves` directory. Each save slot is a RON file with a `version` at the top.
+/// Loading a save travels to its level, even when it is the current one, so that the level's state is restored as saved.
+///
+/// Next to the save, every slot has a [`SaveMetadata`] file and a screenshot thumbnail for the save slot picker,
+/// so that listing the slots does not need to read the saves themselves. The screenshot is taken the frame after saving
+/// and read back from the GPU asynchronously, while [`ThumbnailCapture::is_capturing`] tells menus to hide.
+pub(super) fn plugin(app: &mut App) {
+    app.add_event::<SaveGameEvent>()
+        .add_event::<GameSavedEvent>()
+        .add_event::<LoadGameEvent>()
+        .add_event::<DeleteSaveEvent>()
+        .register_type::<PlayTime>()
+        .init_resource::<PlayTime>()
+        .init_resource::<ThumbnailCapture>()
+        .add_systems(
+            Update,
+            (count_play_time, (save_game, load_game, delete_save).chain())
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            PostUpdate,
+            capture_thumbnail.run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Saves the game to a slot, overwriting any save in it.
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub(crate) struct SaveGameEvent {
+    pub(crate) slot: String,
+}
+
+/// Sent for every [`SaveGameEvent`] once the save was written or failed, so that players learn whether their progress is safe.
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub(crate) struct GameSavedEvent {
+    pub(crate) slot: String,
+    /// Why the save failed, for players. `None` if it succeeded.
+    pub(crate) error: Option<String>,
+}
+
+/// Loads the save in a slot.
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub(crate) struct LoadGameEvent {
+    pub(crate) slot: String,
+}
+
+/// Deletes the save in a slot together with its metadata and thumbnail.
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub(crate) struct DeleteSaveEvent {
+    pub(crate) slot: String,
+}
+
+/// Seconds spent playing since the game was started fresh, not counting pauses. Saved and restored with the rest of the game.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct PlayTime(pub(crate) f32);
+
+/// What the save slot picker shows about a slot. Written next to the save, see [`plugin`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct SaveMetadata {
+    /// The name of the level the save is in, for players.
+    pub(crate) level: String,
+    /// See [`TimeOfDay::hour`].
+    pub(crate) hour: f32,
+    /// See [`PlayTime`].
+    pub(crate) play_time: f32,
+    /// Seconds since the Unix epoch.
+    pub(crate) saved_at: u64,
+}
+
+/// A save slot found in the `saves` directory.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SaveSlot {
+    pub(crate) name: String,
+    /// `None` if the metadata is missing or corrupt, e.g. for saves from before it was written.
+    pub(crate) metadata: Option<SaveMetadata>,
+    /// The thumbnail may not exist.
+    pub(crate) thumbnail: PathBuf,
+}
+
+/// Holds the slot whose thumbnail will be captured.
+#[derive(Debug, Clone, PartialEq, Eq, Resource, Default)]
+pub(crate) struct ThumbnailCapture {
+    pending: Option<String>,
+    /// The capture waits for one frame, so that menus drawn in the frame of saving are hidden in the screenshot.
+    waited: bool,
+}
+
+impl ThumbnailCapture {
+    /// Menus should not draw themselves while this is true, so that they do not end up in the thumbnail.
+    pub(crate) fn is_capturing(&self) -> bool {
+        self.pending.is_some()
+    }
+}
+
+/// Everything that is stored in a save. See [`MIGRATIONS`] before changing it or anything in it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SaveFile {
+    /// Comes first so that it is at the top of the file.
+    version: u32,
+    current_level: String,
+    player: PlayerState,
+    level_states: LevelStateCache,
+    party: Party,
+    completed_tutorials: CompletedTutorials,
+    custom_waypoint: CustomWaypoint,
+    #[serde(default)]
+    time_of_day: TimeOfDay,
+    #[serde(default)]
+    inventory: Inventory,
+    #[serde(default)]
+    shop_states: ShopStates,
+    #[serde(default)]
+    weather: Weather,
+    #[serde(default)]
+    weather_transition: WeatherTransition,
+    #[serde(default)]
+    snow_cover: SnowCover,
+    #[serde(default)]
+    world_flags: WorldFlags,
+    #[serde(default)]
+    play_time: PlayTime,
+    #[serde(default)]
+    npc_memory: NpcMemory,
+    #[serde(default)]
+    race_records: RaceRecords,
+    #[serde(default)]
+    collected_notes: CollectedNotes,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct PlayerState {
+    translation: Vec3,
+    rotation: Quat,
+    /// So that the player does not arrive with the wrong rules before the zone it was saved in has spawned.
+    #[serde(default)]
+    movement_modifiers: MovementModifiers,
+}
+
+/// Read before the rest of the save to know which migrations it needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+/// Whether there is a save in the slot.
+pub(crate) fn save_exists(slot: &str) -> bool {
+    save_path(slot).is_file()
+}
+
+/// All slots that hold a save, the most recently saved first.
+pub(crate) fn list_save_slots() -> Vec<SaveSlot> {
+    let Ok(entries) = fs::read_dir(SAVE_DIRECTORY) else {
+        return Vec::new();
+    };
+    let mut slots: Vec<_> = entries
+        .filter_map(|entry| {
+            let file_name = entry.ok()?.file_name();
+            let name = file_name.to_str()?.strip_suffix(".save.ron")?;
+            Some(SaveSlot {
+                name: name.to_string(),
+                metadata: read_metadata(name),
+                thumbnail: thumbnail_path(name),
+            })
+        })
+        .collect();
+    slots.sort_by(|a, b| {
+        let saved_at = |slot: &SaveSlot| slot.metadata.as_ref().map(|metadata| metadata.saved_at);
+        saved_at(b)
+            .cmp(&saved_at(a))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    slots
+}
+
+fn read_metadata(slot: &str) -> Option<SaveMetadata> {
+    let serialized = fs::read_to_string(metadata_path(slot)).ok()?;
+    ron::from_str(&serialized)
+        .map_err(|error| warn!("Ignoring the corrupt metadata of save slot {slot}: {error}"))
+        .ok()
+}
+
+fn save_path(slot: &str) -> PathBuf {
+    PathBuf::from(SAVE_DIRECTORY).join(format!("{slot}.save.ron"))
+}
+
+fn metadata_path(slot: &str) -> PathBuf {
+    PathBuf::from(SAVE_DIRECTORY).join(format!("{slot}.meta.ron"))
+}
+
+fn thumbnail_path(slot: &str) -> PathBuf {
+    PathBuf::from(SAVE_DIRECTORY).join(format!("{slot}.thumbnail.png"))
+}
+
+/// The file name of the level without its extension, e.g. "World" for `levels/World.glb`.
+fn level_display_name(level: &str) -> String {
+    Path::new(level)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(level)
+        .to_string()
+}
+
+/// The resources that are saved as they are.
+#[derive(SystemParam)]
+struct SavedResources<'w> {
+    level_states: Res<'w, LevelStateCache>,
+    party: Res<'w, Party>,
+    completed_tutorials: Res<'w, CompletedTutorials>,
+    custom_waypoint: Res<'w, CustomWaypoint>,
+    time_of_day: Res<'w, TimeOfDay>,
+    inventory: Res<'w, Inventory>,
+    shop_states: Res<'w, ShopStates>,
+    weather: Res<'w, Weather>,
+    weather_transition: Res<'w, WeatherTransition>,
+    snow_cover: Res<'w, SnowCover>,
+    world_flags: Res<'w, WorldFlags>,
+    play_time: Res<'w, PlayTime>,
+    npc_memory: Res<'w, NpcMemory>,
+    race_records: Res<'w, RaceRecords>,
+    collected_notes: Res<'w, CollectedNotes>,
+}
+
+fn count_play_time(time: Res<Time>, mut play_time: ResMut<PlayTime>) {
+    play_time.0 += time.delta_seconds();
+}
+
+fn save_game(
+    mut save_events: EventReader<SaveGameEvent>,
+    mut saved_events: EventWriter<GameSavedEvent>,
+    travel: Res<Travel>,
+    current_level: Res<CurrentLevel>,
+    players: Query<(&GlobalTransform, Option<&ActiveMovementModifiers>), With<Player>>,
+    saved: SavedResources,
+    mut thumbnail_capture: ResMut<ThumbnailCapture>,
+) {
+    for event in save_events.read() {
+        let result = write_save(&event.slot, &travel, &current_level, &players, &saved);
+        let error = match result {
+            Ok(()) => {
+                *thumbnail_capture = ThumbnailCapture {
+                    pending: Some(event.slot.clone()),
+                    waited: false,
+                };
+                None
+            }
+            Err(error) => {
+                error!("Failed to save to slot {}: {error:?}", event.slot);
+                Some(format!("{error:#}"))
+            }
+        };
+        saved_events.send(GameSavedEvent {
+            slot: event.slot.clone(),
+            error,
+        });
+    }
+}
+
+fn write_save(
+    slot: &str,
+    travel: &Travel,
+    current_level: &CurrentLevel,
+    players: &Query<(&GlobalTransform, Option<&ActiveMovementModifiers>), With<Player>>,
+    saved: &SavedResources,
+) -> anyhow::Result<()> {
+    ensure!(
+        !travel.is_traveling(),
+        "Cannot save while travelling to another level"
+    );
+    let (player, modifiers) = players
+        .get_single()
+        .context("Failed to find the player to save")?;
+    let (_, rotation, translation) = player.to_scale_rotation_translation();
+    let save = SaveFile {
+        version: SAVE_VERSION,
+        current_level: current_level.0.clone(),
+        player: PlayerState {
+            translation,
+            rotation,
+            movement_modifiers: modifiers.map(|modifiers| modifiers.0).unwrap_or_default(),
+        },
+        level_states: saved.level_states.clone(),
+        party: saved.party.clone(),
+        completed_tutorials: saved.completed_tutorials.clone(),
+        custom_waypoint: saved.custom_waypoint.clone(),
+        time_of_day: *saved.time_of_day,
+        inventory: saved.inventory.clone(),
+        shop_states: saved.shop_states.clone(),
+        weather: *saved.weather,
+        weather_transition: *saved.weather_transition,
+        snow_cover: *saved.snow_cover,
+        world_flags: saved.world_flags.clone(),
+        play_time: *saved.play_time,
+        npc_memory: saved.npc_memory.clone(),
+        race_records: saved.race_records.clone(),
+        collected_notes: saved.collected_notes.clone(),
+    };
+    let serialized =
+        ron::ser::to_string_pretty(&save, default()).context("Failed to serialize save")?;
+    fs::create_dir_all(SAVE_DIRECTORY)
+        .with_context(|| format!("Failed to create the {SAVE_DIRECTORY} directory"))?;
+    let path = save_path(slot);
+    fs::write(&path, serialized).with_context(|| format!("Failed to write {}", path.display()))?;
+    info!("Saved the game to {}", path.display());
+
+    let metadata = SaveMetadata {
+        level: level_display_name(&current_level.0),
+        hour: saved.time_of_day.hour,
+        play_time: saved.play_time.0,
+        saved_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs()),
+    };
+    let serialized = ron::ser::to_string_pretty(&metadata, default())
+        .context("Failed to serialize save metadata")?;
+    let path = metadata_path(slot);
+    fs::write(&path, serialized).with_context(|| format!("Failed to write {}", path.display()))?;
+    // The old thumbnail would show the wrong place if capturing the new one fails
+    remove_if_exists(&thumbnail_path(slot))
+}
+
+#[sysfail(Log<anyhow::Error, Error>)]
+fn load_game(
+    mut commands: Commands,
+    mut load_events: EventReader<LoadGameEvent>,
+    mut travel_events: EventWriter<TravelEvent>,
+) {
+    let Some(event) = load_events.read().last() else {
+        return Ok(());
+    };
+    let path = save_path(&event.slot);
+    let serialized =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let save = deserialize_save(&serialized)
+        .with_context(|| format!("Failed to load save {}", path.display()))?;
+    commands.insert_resource(save.level_states);
+    commands.insert_resource(save.party);
+    commands.insert_resource(save.completed_tutorials);
+    commands.insert_resource(save.custom_waypoint);
+    commands.insert_resource(save.time_of_day);
+    commands.insert_resource(save.inventory);
+    commands.insert_resource(save.shop_states);
+    commands.insert_resource(save.weather);
+    commands.insert_resource(save.weather_transition);
+    commands.insert_resource(save.snow_cover);
+    commands.insert_resource(save.world_flags);
+    commands.insert_resource(save.play_time);
+    commands.insert_resource(save.npc_memory);
+    commands.insert_resource(save.race_records);
+    commands.insert_resource(save.collected_notes);
+    commands.insert_resource(RestoredMovementModifiers::new(
+        save.player.movement_modifiers,
+    ));
+    // The party is restored from the save once the level spawns its members
+    travel_events.send(TravelEvent {
+        level: save.current_level,
+        arrival: Arrival::Transform(
+            Transform::from_translation(save.player.translation)
+                .with_rotation(save.player.rotation),
+        ),
+        bring_companions: false,
+    });
+    info!("Loaded the game from {}", path.display());
+}
+
+#[sysfail(Log<anyhow::Error, Error>)]
+fn delete_save(mut delete_events: EventReader<DeleteSaveEvent>) {
+    for event in delete_events.read() {
+        for path in [
+            save_path(&event.slot),
+            metadata_path(&event.slot),
+            thumbnail_path(&event.slot),
+        ] {
+            remove_if_exists(&path)?;
+        }
+        info!("Deleted save slot {}", event.slot);
+    }
+}
+
+fn remove_if_exists(path: &Path) -> anyhow::Result<()> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => {
+            Err(error).with_context(|| format!("Failed to delete {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[sysfail(Log<anyhow::Error, Error>)]
+fn capture_thumbnail(
+    mut thumbnail_capture: ResMut<ThumbnailCapture>,
+    mut screenshot_manager: ResMut<ScreenshotManager>,
+    windows: Query<Entity, With<PrimaryWindow>>,
+) {
+    if !thumbnail_capture.is_capturing() {
+        return Ok(());
+    }
+    if !thumbnail_capture.waited {
+        thumbnail_capture.waited = true;
+        return Ok(());
+    }
+    let Some(slot) = thumbnail_capture.pending.take() else {
+        return Ok(());
+    };
+    let window = windows
+        .get_single()
+        .context("Failed to find the window to take a thumbnail of")?;
+    let path = thumbnail_path(&slot);
+    // The callback runs on a background task once the GPU has copied the frame, so the downscaling does not hitch either
+    screenshot_manager
+        .take_screenshot(window, move |image| {
+            if let Err(error) = write_thumbnail(image, &path) {
+                error!("Failed to write the thumbnail of save slot {slot}: {error:?}");
+            }
+        })
+        .map_err(|error| anyhow!("Failed to take a thumbnail: {error:?}"))?;
+}
+
+fn write_thumbnail(image: Image, path: &Path) -> anyhow::Result<()> {
+    let image = image
+        .try_into_dynamic()
+        .map_err(|error| anyhow!("Failed to convert the screenshot: {error:?}"))?;
+    image
+        .thumbnail(THUMBNAIL_SIZE.x, THUMBNAIL_SIZE.y)
+        .to_rgb8()
+        .save(path)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Version 2 names the level after [`CurrentLevel`], like the other fields are named after the resources they restore.
+fn rename_level(save: &mut ron::Value) -> anyhow::Result<()> {
+    let ron::Value::Map(fields) = save else {
+        bail!("The save is not a struct");
+    };
+    let level = fields
+        .remove(&ron::Value::String("level".to_string()))
+        .context("The save has no level")?;
+    fields.insert(ron::Value::String("current_level".to_string()), level);
+    Ok(())
+}
+
+/// Reads a save of any version, migrating it to the current one first.
+fn deserialize_save(serialized: &str) -> anyhow::Result<SaveFile> {
+    let header: SaveHeader =
+        ron::from_str(serialized).context("Failed to read the version of the save")?;
+    let version = header.version;
+    ensure!(
+        version > 0,
+        "Save versions start at 1, but the save has version 0"
+    );
+    match version.cmp(&SAVE_VERSION) {
+        Ordering::Equal => ron::from_str(serialized).context("Failed to deserialize save"),
+        Ordering::Greater => bail!(
+            "The save has version {version}, but this build of the game only knows versions up to {SAVE_VERSION}"
+        ),
+        Ordering::Less => {
+            let mut value: ron::Value =
+                ron::from_str(serialized).context("Failed to parse save")?;
+            let pending = MIGRATIONS.iter().zip(1_u32..).skip(version as usize - 1);
+            for ((name, migrate), from) in pending {
+                migrate(&mut value).with_context(|| {
+                    format!(
+                        "Failed to migrate save from version {from} to {}: migration \"{name}\" failed",
+                        from + 1
+                    )
+                })?;
+            }
+            let mut save: SaveFile = value.into_rust().with_context(|| {
+                format!("Failed to deserialize save after migrating it from version {version}")
+            })?;
+            save.version = SAVE_VERSION;
+            Ok(save)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A save as written by version 1, before the level was renamed.
+    const VERSION_1_SAVE: &str = r#"(
+    version: 1,
+    level: "levels/old_town.glb",
+    player: (
+        translation: (1.0, 2.0, 3.0),
+        rotation: (0.0, 0.0, 0.0, 1.0),
+    ),
+    level_states: ({}),
+    party: ([]),
+    completed_tutorials: (["jump"]),
+    custom_waypoint: (None),
+)"#;
+
+    #[test]
+    fn version_1_saves_are_migrated_to_the_current_version() {
+        let save = deserialize_save(VERSION_1_SAVE).unwrap();
+        assert_eq!(save.version, SAVE_VERSION);
+        assert_eq!(save.current_level, "levels/old_town.glb");
+        assert_eq!(save.player.translation, Vec3::new(1., 2., 3.));
+        assert!(save.completed_tutorials.0.contains("jump"));
+    }
+
+    #[test]
+    fn failing_migrations_are_named() {
+        let broken = VERSION_1_SAVE.replace("level:", "levle:");
+        let error = format!("{:#}", deserialize_save(&broken).unwrap_err());
+        assert!(
+            error.contains("\"rename level to current_level\"")
+                && error.contains("from version 1 to 2"),
+            "Unexpected error: {error}"
+        );
+    }
+
+    #[test]
+    fn current_saves_round_trip() {
+        let save = deserialize_save(VERSION_1_SAVE).unwrap();
+        let serialized = ron::ser::to_string_pretty(&save, default()).unwrap();
+        assert_eq!(deserialize_save(&serialized).unwrap(), save);
+    }
+
+    #[test]
+    fn saves_from_newer_builds_are_rejected() {
+        let newer = VERSION_1_SAVE.replace("version: 1", &format!("version: {}", SAVE_VERSION + 1));
+        assert!(deserialize_save(&newer).is_err());
+    }
+}
@@ -0,0 +1,374 @@
+use crate::{movement::physics::CollisionLayer, player_control::camera::IngameCamera, GameState};
+use bevy::{prelude::*, utils::HashMap};
+use bevy_kira_audio::prelude::{AudioSource, *};
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// At most this many emitters are checked for occlusion per frame. The others keep their last result.
+const MAX_OCCLUSION_RAYS: usize = 8;
+/// More pieces of geometry than this between the listener and an emitter make no difference.
+const MAX_BLOCKERS: u32 = 4;
+/// How much each piece of geometry between the listener and an emitter occludes it.
+const OCCLUSION_PER_BLOCKER: f32 = 0.35;
+/// How much each meter of geometry occludes an emitter on top of [`OCCLUSION_PER_BLOCKER`].
+const OCCLUSION_PER_METER: f32 = 0.1;
+/// The volume of a fully occluded emitter as a fraction of its unoccluded volume.
+const OCCLUDED_VOLUME: f64 = 0.25;
+/// How quickly occlusion and reverb follow their targets, per second.
+/// Keeps the volume from jumping around when the ray flickers across the edge of a wall.
+const SMOOTHING_RATE: f32 = 6.;
+/// Volume changes are spread over this duration so that they don't click.
+const VOLUME_TWEEN: Duration = Duration::from_millis(50);
+const STOP_FADE: Duration = Duration::from_millis(300);
+
+/// Plays [`SoundEmitter`]s relative to the camera, which is the listener.
+/// Emitters behind geometry are occluded and get quieter, depending on how many walls are in the way and how thick they are.
+/// Only a few emitters per frame are checked for occlusion, preferring the loudest and closest ones.
+///
+/// A [`PlaySpatialSoundEvent`] plays a sound once, occluded and panned the same way.
+///
+/// While the listener is in a [`ReverbZone`], its preset sets the global [`ReverbSend`].
+///
+/// Out of scope for now:
+/// - Occlusion only lowers the volume. bevy_kira_audio exposes no filters on instances or channels,
+///   so muffling occluded sounds with a low-pass needs kira's tracks directly.
+/// - For the same reason, nothing consumes the [`ReverbSend`] yet.
+/// - Footsteps are the player's walking loop and barks are only subtitles, so neither has a position to play at.
+///   New positional sounds should be [`SoundEmitter`]s or [`PlaySpatialSoundEvent`]s.
+pub(super) fn plugin(app: &mut App) {
+    app.add_audio_channel::<SpatialAudioChannel>()
+        .add_event::<PlaySpatialSoundEvent>()
+        .register_type::<SoundEmitter>()
+        .register_type::<ReverbZone>()
+        .register_type::<ReverbSend>()
+        .init_resource::<ReverbSend>()
+        .add_systems(
+            Update,
+            (
+                (start_emitters, stop_removed_emitters),
+                update_occlusion,
+                update_emitter_playback,
+                play_spatial_sounds,
+                update_reverb_send,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// A looping sound that plays at the entity's position, e.g. a waterfall or a campfire.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct SoundEmitter {
+    /// Path to the sound.
+    pub(crate) sound: String,
+    pub(crate) volume: f32,
+    /// In meters. Beyond this distance, the emitter is silent.
+    pub(crate) range: f32,
+}
+
+impl Default for SoundEmitter {
+    fn default() -> Self {
+        Self {
+            sound: default(),
+            volume: 1.,
+            range: 20.,
+        }
+    }
+}
+
+/// Plays a sound once at a position, e.g. a chime coming from a door.
+#[derive(Debug, Clone, PartialEq, Event)]
+pub(crate) struct PlaySpatialSoundEvent {
+    pub(crate) emitter: SoundEmitter,
+    pub(crate) position: Vec3,
+    /// The entity making the sound, whose collider does not occlude it.
+    pub(crate) source: Option<Entity>,
+    /// Drops the sound instead of muffling it when there is geometry between the listener and the position.
+    pub(crate) needs_line_of_sight: bool,
+}
+
+/// A box around the entity's origin that makes sounds reverberate while the listener is inside.
+/// Zones may be rotated around the Y axis, but not tilted.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct ReverbZone {
+    pub(crate) half_extents: Vec3,
+    pub(crate) preset: ReverbPreset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) enum ReverbPreset {
+    #[default]
+    Cave,
+    Hall,
+}
+
+impl ReverbPreset {
+    /// How much of every sound is sent to the reverb.
+    pub(crate) fn send(self) -> f32 {
+        match self {
+            Self::Cave => 0.6,
+            Self::Hall => 0.35,
+        }
+    }
+}
+
+/// The global reverb send, smoothed as the listener enters and leaves [`ReverbZone`]s.
+/// bevy_kira_audio does not expose kira's track effects, so for now this is only the input for a reverb, not the reverb itself.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct ReverbSend {
+    /// The preset of the zone the listener is in or was in last.
+    pub(crate) preset: Option<ReverbPreset>,
+    /// Between 0 and [`ReverbPreset::send`].
+    pub(crate) level: f32,
+}
+
+#[derive(Resource)]
+pub(super) struct SpatialAudioChannel;
+
+#[derive(Debug, Component)]
+struct EmitterPlayback {
+    instance: Handle<AudioInstance>,
+    /// Between 0 for a clear line of sight and 1 for fully occluded.
+    target_occlusion: f32,
+    /// [`EmitterPlayback::target_occlusion`], smoothed over time.
+    occlusion: f32,
+    /// How many frames ago the occlusion was last checked.
+    staleness: u32,
+    volume: f64,
+    panning: f64,
+}
+
+fn start_emitters(
+    mut commands: Commands,
+    emitters: Query<(Entity, &SoundEmitter), Without<EmitterPlayback>>,
+    asset_server: Res<AssetServer>,
+    channel: Res<AudioChannel<SpatialAudioChannel>>,
+) {
+    for (entity, emitter) in emitters.iter() {
+        let source: Handle<AudioSource> = asset_server.load(emitter.sound.clone());
+        // Faded in by `update_emitter_playback`
+        let instance = channel.play(source).looped().with_volume(0.).handle();
+        commands.entity(entity).insert(EmitterPlayback {
+            instance,
+            target_occlusion: 0.,
+            occlusion: 0.,
+            staleness: u32::MAX,
+            volume: 0.,
+            panning: 0.5,
+        });
+    }
+}
+
+/// Stops the sounds of emitters that were removed or despawned. Despawned emitters have no components left to read
+/// the handle from, so the handles are remembered here.
+fn stop_removed_emitters(
+    mut commands: Commands,
+    started: Query<(Entity, &EmitterPlayback), Added<EmitterPlayback>>,
+    mut removed: RemovedComponents<SoundEmitter>,
+    mut audio_instances: ResMut<Assets<AudioInstance>>,
+    mut instances: Local<HashMap<Entity, Handle<AudioInstance>>>,
+) {
+    for (entity, playback) in started.iter() {
+        instances.insert(entity, playback.instance.clone());
+    }
+    for entity in removed.read() {
+        let Some(instance) = instances.remove(&entity) else {
+            continue;
+        };
+        if let Some(instance) = audio_instances.get_mut(&instance) {
+            instance.stop(AudioTween::linear(STOP_FADE));
+        }
+        if let Some(mut entity_commands) = commands.get_entity(entity) {
+            entity_commands.remove::<EmitterPlayback>();
+        }
+    }
+}
+
+fn update_occlusion(
+    cameras: Query<&GlobalTransform, With<IngameCamera>>,
+    mut emitters: Query<(
+        Entity,
+        &SoundEmitter,
+        &GlobalTransform,
+        &mut EmitterPlayback,
+    )>,
+    spatial_query: SpatialQuery,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_occlusion").entered();
+    let Some(listener) = cameras.iter().next().map(GlobalTransform::translation) else {
+        return;
+    };
+    let mut candidates = Vec::new();
+    for (entity, emitter, transform, mut playback) in emitters.iter_mut() {
+        playback.staleness = playback.staleness.saturating_add(1);
+        let position = transform.translation();
+        let audible_volume = audible_volume(emitter, listener.distance(position));
+        if audible_volume > 0. {
+            // Emitters that were not checked for a while move up the queue, so that quiet ones are not left out forever
+            let priority = audible_volume * playback.staleness as f32;
+            candidates.push((entity, position, priority));
+        }
+    }
+    candidates.sort_unstable_by(|(_, _, a), (_, _, b)| b.total_cmp(a));
+    for (entity, position, _) in candidates.into_iter().take(MAX_OCCLUSION_RAYS) {
+        let occlusion = compute_occlusion(&spatial_query, listener, position, [entity]);
+        if let Ok((.., mut playback)) = emitters.get_mut(entity) {
+            playback.target_occlusion = occlusion;
+            playback.staleness = 0;
+        }
+    }
+}
+
+/// Counts the geometry between the listener and the emitter. How thick it is, is estimated by how far apart
+/// the first hit coming from the listener and the first hit coming from the emitter are.
+fn compute_occlusion(
+    spatial_query: &SpatialQuery,
+    listener: Vec3,
+    emitter_position: Vec3,
+    emitter: impl IntoIterator<Item = Entity>,
+) -> f32 {
+    let offset = emitter_position - listener;
+    let distance = offset.length();
+    let Ok(direction) = Direction3d::new(offset) else {
+        return 0.;
+    };
+    // The emitter's own collider does not occlude it
+    let filter = SpatialQueryFilter::from_mask(CollisionLayer::Terrain.to_bits())
+        .with_excluded_entities(emitter);
+    let hits = spatial_query.ray_hits(
+        listener,
+        direction,
+        distance,
+        MAX_BLOCKERS,
+        true,
+        filter.clone(),
+    );
+    let Some(first_entry) = hits.iter().map(|hit| hit.time_of_impact).reduce(f32::min) else {
+        return 0.;
+    };
+    let last_exit = spatial_query
+        .cast_ray(emitter_position, -direction, distance, true, filter)
+        .map_or(distance, |hit| distance - hit.time_of_impact);
+    let thickness = (last_exit - first_entry).max(0.);
+    (hits.len() as f32 * OCCLUSION_PER_BLOCKER + thickness * OCCLUSION_PER_METER).min(1.)
+}
+
+fn update_emitter_playback(
+    time: Res<Time<Virtual>>,
+    cameras: Query<&GlobalTransform, With<IngameCamera>>,
+    mut emitters: Query<(&SoundEmitter, &GlobalTransform, &mut EmitterPlayback)>,
+    mut audio_instances: ResMut<Assets<AudioInstance>>,
+) {
+    let Some(listener) = cameras.iter().next() else {
+        return;
+    };
+    let smoothing = 1. - (-SMOOTHING_RATE * time.delta_seconds()).exp();
+    for (emitter, transform, mut playback) in emitters.iter_mut() {
+        let occlusion =
+            playback.occlusion + (playback.target_occlusion - playback.occlusion) * smoothing;
+        playback.occlusion = occlusion;
+        let offset = transform.translation() - listener.translation();
+        let volume = if time.is_paused() {
+            0.
+        } else {
+            let occlusion_factor = 1. - f64::from(occlusion) * (1. - OCCLUDED_VOLUME);
+            f64::from(audible_volume(emitter, offset.length())) * occlusion_factor
+        };
+        let panning = panning(listener, offset);
+        let Some(instance) = audio_instances.get_mut(&playback.instance) else {
+            continue;
+        };
+        if (playback.volume - volume).abs() > 1e-3 {
+            instance.set_volume(volume, AudioTween::linear(VOLUME_TWEEN));
+            playback.volume = volume;
+        }
+        if (playback.panning - panning).abs() > 1e-2 {
+            instance.set_panning(panning, AudioTween::linear(VOLUME_TWEEN));
+            playback.panning = panning;
+        }
+    }
+}
+
+fn play_spatial_sounds(
+    mut sound_events: EventReader<PlaySpatialSoundEvent>,
+    time: Res<Time<Virtual>>,
+    cameras: Query<&GlobalTransform, With<IngameCamera>>,
+    spatial_query: SpatialQuery,
+    asset_server: Res<AssetServer>,
+    channel: Res<AudioChannel<SpatialAudioChannel>>,
+) {
+    let listener = cameras.iter().next();
+    for event in sound_events.read() {
+        let Some(listener) = listener.filter(|_| !time.is_paused()) else {
+            continue;
+        };
+        let offset = event.position - listener.translation();
+        let volume = audible_volume(&event.emitter, offset.length());
+        if volume <= 0. {
+            continue;
+        }
+        let occlusion = compute_occlusion(
+            &spatial_query,
+            listener.translation(),
+            event.position,
+            event.source,
+        );
+        if event.needs_line_of_sight && occlusion > 0. {
+            continue;
+        }
+        let occlusion_factor = 1. - f64::from(occlusion) * (1. - OCCLUDED_VOLUME);
+        let source: Handle<AudioSource> = asset_server.load(event.emitter.sound.clone());
+        channel
+            .play(source)
+            .with_volume(f64::from(volume) * occlusion_factor)
+            .with_panning(panning(listener, offset));
+    }
+}
+
+/// 0 is fully left, 1 is fully right.
+fn panning(listener: &GlobalTransform, offset: Vec3) -> f64 {
+    0.5 + 0.5 * f64::from(offset.normalize_or_zero().dot(listener.right()))
+}
+
+/// The volume of an unoccluded emitter at a distance from the listener.
+fn audible_volume(emitter: &SoundEmitter, distance: f32) -> f32 {
+    let falloff = (1. - distance / emitter.range.max(1e-3)).clamp(0., 1.);
+    emitter.volume * falloff * falloff
+}
+
+fn update_reverb_send(
+    time: Res<Time>,
+    cameras: Query<&GlobalTransform, With<IngameCamera>>,
+    zones: Query<(&ReverbZone, &GlobalTransform)>,
+    mut send: ResMut<ReverbSend>,
+) {
+    let Some(listener) = cameras.iter().next().map(GlobalTransform::translation) else {
+        return;
+    };
+    let active_preset = zones
+        .iter()
+        .find(|(zone, transform)| {
+            let local = transform.affine().inverse().transform_point3(listener);
+            local.abs().cmple(zone.half_extents).all()
+        })
+        .map(|(zone, _)| zone.preset);
+    if active_preset.is_some() && send.preset != active_preset {
+        send.preset = active_preset;
+    }
+    let target = active_preset.map_or(0., ReverbPreset::send);
+    let smoothing = 1. - (-SMOOTHING_RATE * time.delta_seconds()).exp();
+    let level = send.level + (target - send.level) * smoothing;
+    if (level - send.level).abs() > 1e-4 {
+        send.level = level;
+    } else if send.level != target {
+        send.level = target;
+    }
+}
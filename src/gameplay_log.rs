@@ -0,0 +1,343 @@
+use crate::{
+    movement::character_controller::{CharacterStuckEvent, LandedEvent, LeftGroundEvent},
+    world_interaction::dialog::CurrentDialogTarget,
+};
+use bevy::prelude::*;
+use bevy_gltf_blueprints::BlueprintName;
+use bevy_yarnspinner::events::{DialogueCompleteEvent, NodeStartEvent};
+use std::{borrow::Cow, collections::VecDeque, fmt};
+
+/// How many entries the [`GameplayLog`] keeps before dropping the oldest ones.
+const CAPACITY: usize = 4096;
+/// How many fields an entry can hold. Further fields are dropped.
+const MAX_FIELDS: usize = 4;
+
+/// Records what happens in the game into the [`GameplayLog`], so that emergent behavior can be traced back afterwards.
+/// Gameplay code logs directly with [`log_event!`], while the events of other plugins are collected here.
+/// Only enabled in dev builds.
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<GameplayLog>()
+        .add_systems(First, update_log_time)
+        .add_systems(
+            Update,
+            (log_movement_events, log_dialogs, log_spawns)
+                .run_if(|log: Res<GameplayLog>| log.is_enabled()),
+        );
+}
+
+/// Adds an entry to the [`GameplayLog`], e.g.
+/// `log_event!(log, Ai, Some(entity), "Started following", distance = 4.2)`.
+/// The message can be a `&'static str` or a `String`. Nothing is evaluated while the log is disabled.
+macro_rules! log_event {
+    ($log:expr, $category:ident, $entity:expr, $message:expr $(, $name:ident = $value:expr)* $(,)?) => {
+        if $log.is_enabled() {
+            $log.push(
+                $crate::gameplay_log::LogCategory::$category,
+                $entity,
+                $message,
+                [$((stringify!($name), $crate::gameplay_log::FieldValue::from($value))),*],
+            );
+        }
+    };
+}
+pub(crate) use log_event;
+
+/// A bounded history of [`LogEntry`]s. The oldest entries are dropped once it is full.
+#[derive(Debug, Clone, Resource)]
+pub(crate) struct GameplayLog {
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+    enabled: bool,
+    /// Seconds since startup, updated at the start of every frame so that pushing does not need [`Time`].
+    now: f32,
+    /// How many entries were ever pushed. Gives entries a sequence number that survives dropping old ones.
+    pushed: u64,
+}
+
+impl Default for GameplayLog {
+    fn default() -> Self {
+        Self::with_capacity(CAPACITY)
+    }
+}
+
+impl GameplayLog {
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+            enabled: cfg!(feature = "dev"),
+            now: 0.,
+            pushed: 0,
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Prefer [`log_event!`], which skips building the message while the log is disabled.
+    pub(crate) fn push<const N: usize>(
+        &mut self,
+        category: LogCategory,
+        entity: Option<Entity>,
+        message: impl Into<Cow<'static, str>>,
+        fields: [(&'static str, FieldValue); N],
+    ) {
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        let mut entry_fields = [None; MAX_FIELDS];
+        for (slot, field) in entry_fields.iter_mut().zip(fields) {
+            *slot = Some(field);
+        }
+        self.entries.push_back(LogEntry {
+            sequence: self.pushed,
+            time: self.now,
+            category,
+            entity,
+            message: message.into(),
+            fields: entry_fields,
+        });
+        self.pushed += 1;
+    }
+
+    /// From oldest to newest.
+    pub(crate) fn entries(&self) -> impl DoubleEndedIterator<Item = &LogEntry> + '_ {
+        self.entries.iter()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LogEntry {
+    pub(crate) sequence: u64,
+    /// Seconds since startup.
+    pub(crate) time: f32,
+    pub(crate) category: LogCategory,
+    pub(crate) entity: Option<Entity>,
+    pub(crate) message: Cow<'static, str>,
+    pub(crate) fields: [Option<(&'static str, FieldValue)>; MAX_FIELDS],
+}
+
+impl LogEntry {
+    pub(crate) fn fields(&self) -> impl Iterator<Item = &(&'static str, FieldValue)> + '_ {
+        self.fields.iter().flatten()
+    }
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{:>9.3}] {:<11} ", self.time, self.category.name())?;
+        if let Some(entity) = self.entity {
+            write!(f, "{entity:?} ")?;
+        }
+        write!(f, "{}", self.message)?;
+        for (name, value) in self.fields() {
+            write!(f, " {name}={value}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum LogCategory {
+    Movement,
+    Interaction,
+    Dialog,
+    Spawn,
+    Ai,
+}
+
+impl LogCategory {
+    pub(crate) const ALL: [Self; 5] = [
+        Self::Movement,
+        Self::Interaction,
+        Self::Dialog,
+        Self::Spawn,
+        Self::Ai,
+    ];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Movement => "Movement",
+            Self::Interaction => "Interaction",
+            Self::Dialog => "Dialog",
+            Self::Spawn => "Spawn",
+            Self::Ai => "AI",
+        }
+    }
+}
+
+/// The value of a structured field. Kept `Copy` so that logging does not allocate for it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum FieldValue {
+    Bool(bool),
+    Int(i64),
+    Float(f32),
+    Vec3(Vec3),
+    Entity(Entity),
+    Text(&'static str),
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool(value) => write!(f, "{value}"),
+            Self::Int(value) => write!(f, "{value}"),
+            Self::Float(value) => write!(f, "{value:.2}"),
+            Self::Vec3(value) => write!(f, "({:.2}, {:.2}, {:.2})", value.x, value.y, value.z),
+            Self::Entity(value) => write!(f, "{value:?}"),
+            Self::Text(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+impl From<bool> for FieldValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i32> for FieldValue {
+    fn from(value: i32) -> Self {
+        Self::Int(value.into())
+    }
+}
+
+impl From<u32> for FieldValue {
+    fn from(value: u32) -> Self {
+        Self::Int(value.into())
+    }
+}
+
+impl From<usize> for FieldValue {
+    fn from(value: usize) -> Self {
+        Self::Int(value as i64)
+    }
+}
+
+impl From<f32> for FieldValue {
+    fn from(value: f32) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<Vec3> for FieldValue {
+    fn from(value: Vec3) -> Self {
+        Self::Vec3(value)
+    }
+}
+
+impl From<Entity> for FieldValue {
+    fn from(value: Entity) -> Self {
+        Self::Entity(value)
+    }
+}
+
+impl From<&'static str> for FieldValue {
+    fn from(value: &'static str) -> Self {
+        Self::Text(value)
+    }
+}
+
+fn update_log_time(time: Res<Time>, mut log: ResMut<GameplayLog>) {
+    log.now = time.elapsed_seconds();
+}
+
+fn log_movement_events(
+    mut landed_events: EventReader<LandedEvent>,
+    mut left_ground_events: EventReader<LeftGroundEvent>,
+    mut stuck_events: EventReader<CharacterStuckEvent>,
+    mut log: ResMut<GameplayLog>,
+) {
+    for event in left_ground_events.read() {
+        log_event!(log, Movement, Some(event.entity), "Left the ground");
+    }
+    for event in landed_events.read() {
+        log_event!(
+            log,
+            Movement,
+            Some(event.entity),
+            "Landed",
+            impact_speed = event.impact_speed
+        );
+    }
+    for event in stuck_events.read() {
+        log_event!(log, Movement, Some(event.entity), "Stuck in geometry");
+    }
+}
+
+/// Dialog starts are logged where they are dispatched, see [`crate::world_interaction::interaction_ui`].
+fn log_dialogs(
+    mut node_start_events: EventReader<NodeStartEvent>,
+    mut dialogue_complete_events: EventReader<DialogueCompleteEvent>,
+    dialog_target: Res<CurrentDialogTarget>,
+    mut log: ResMut<GameplayLog>,
+    mut last_target: Local<Option<Entity>>,
+) {
+    // The target is already cleared when the dialog completes
+    if dialog_target.0.is_some() {
+        *last_target = dialog_target.0;
+    }
+    for event in node_start_events.read() {
+        log_event!(
+            log,
+            Dialog,
+            dialog_target.0,
+            format!("Entered node {}", event.node_name)
+        );
+    }
+    for _event in dialogue_complete_events.read() {
+        log_event!(log, Dialog, last_target.take(), "Dialog ended");
+    }
+}
+
+fn log_spawns(
+    spawned: Query<(Entity, &BlueprintName), Added<BlueprintName>>,
+    mut despawned: RemovedComponents<BlueprintName>,
+    mut log: ResMut<GameplayLog>,
+) {
+    for (entity, blueprint) in spawned.iter() {
+        log_event!(
+            log,
+            Spawn,
+            Some(entity),
+            format!("Spawned blueprint {}", blueprint.0)
+        );
+    }
+    for entity in despawned.read() {
+        log_event!(log, Spawn, Some(entity), "Despawned");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_log_drops_oldest_entries() {
+        let mut log = GameplayLog::with_capacity(2);
+        log.enabled = true;
+        for index in 0..3 {
+            log_event!(log, Ai, None, "Thought", index = index);
+        }
+        let entries: Vec<_> = log.entries().collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].sequence, 1);
+        assert_eq!(
+            entries[1].fields().next(),
+            Some(&("index", FieldValue::Int(2)))
+        );
+    }
+
+    #[test]
+    fn disabled_log_records_nothing() {
+        let mut log = GameplayLog::with_capacity(2);
+        log.enabled = false;
+        log_event!(log, Movement, None, "Landed");
+        assert_eq!(log.entries().count(), 0);
+    }
+}
@@ -0,0 +1,42 @@
+use crate::{file_system_interaction::config::GameConfig, GameState};
+use bevy::{pbr::CascadeShadowConfigBuilder, prelude::*};
+
+mod blob_shadows;
+pub(crate) mod decals;
+
+/// Applies the graphics settings of [`GameConfig`] to the scene.
+/// Directional lights get their shadow distance and cascades from the [`GraphicsPreset`](crate::file_system_interaction::config::GraphicsPreset),
+/// and characters get cheap blob shadows from [`blob_shadows::plugin`] where real shadows are too expensive.
+/// Textures projected onto the level, e.g. target markers, are handled by [`decals::plugin`].
+pub(super) fn plugin(app: &mut App) {
+    app.add_plugins((blob_shadows::plugin, decals::plugin))
+        .add_systems(
+            Update,
+            apply_shadow_settings
+                .run_if(in_state(GameState::Playing).and_then(resource_exists::<GameConfig>)),
+        );
+}
+
+fn apply_shadow_settings(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    lights: Query<Entity, With<DirectionalLight>>,
+    added_lights: Query<(), Added<DirectionalLight>>,
+) {
+    // Changing the preset at runtime applies right away
+    if !config.is_changed() && added_lights.is_empty() {
+        return;
+    }
+    let settings = config.graphics.resolve();
+    let maximum_distance = settings.shadow_distance.max(1.);
+    let cascades = CascadeShadowConfigBuilder {
+        num_cascades: settings.shadow_cascades.max(1),
+        maximum_distance,
+        first_cascade_far_bound: (maximum_distance / 8.).min(5.),
+        ..default()
+    }
+    .build();
+    for entity in lights.iter() {
+        commands.entity(entity).insert(cascades.clone());
+    }
+}
@@ -0,0 +1,261 @@
+use crate::{
+    file_system_interaction::config::{GameConfig, ShadowQuality},
+    movement::{character_controller::FloatHeight, physics::CollisionLayer},
+    player_control::camera::IngameCamera,
+    GameState,
+};
+use bevy::{
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+    transform::TransformSystem,
+};
+use bevy_tnua::controller::TnuaController;
+use bevy_xpbd_3d::prelude::*;
+
+/// How far below a character's feet the ground is searched for. Over deeper drops, the blob disappears.
+const MAX_HEIGHT: f32 = 4.;
+/// The blob is a bit larger than the capsule, like a real shadow under soft light.
+const SIZE_PER_RADIUS: f32 = 2.6;
+const MAX_OPACITY: f32 = 0.6;
+/// Real directional shadows already fade out before the shadow distance, so the blob takes over slightly earlier.
+const SHADOW_DISTANCE_FACTOR: f32 = 0.85;
+/// Lifts blobs off the ground. Together with [`DEPTH_BIAS`], this keeps them from z-fighting with it.
+const GROUND_OFFSET: f32 = 0.01;
+const DEPTH_BIAS: f32 = 6.;
+const TEXTURE_SIZE: u32 = 64;
+
+/// Gives every character a soft dark blob under their feet. It is used instead of a real shadow
+/// when [`ShadowQuality::Low`] is configured, or when the character is beyond the shadow distance of directional lights.
+/// The blob lies flat on the ground below the character, also on slopes, and fades as the character rises above it.
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<BlobShadowTexture>().add_systems(
+        Update,
+        (
+            spawn_blob_shadows,
+            update_blob_shadows,
+            hide_shadows_of_new_meshes,
+            despawn_orphaned_blob_shadows,
+        )
+            .chain()
+            .after(PhysicsSet::Sync)
+            .before(TransformSystem::TransformPropagate)
+            .run_if(in_state(GameState::Playing).and_then(resource_exists::<GameConfig>)),
+    );
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct BlobShadow {
+    /// The character casting this shadow.
+    owner: Entity,
+    radius: f32,
+    /// Whether the blob is used instead of the owner's real shadow.
+    active: bool,
+}
+
+/// On characters whose real shadow is replaced by their blob shadow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+struct UsesBlobShadow;
+
+#[derive(Debug, Default, Resource)]
+struct BlobShadowTexture {
+    mesh: Option<Handle<Mesh>>,
+    image: Option<Handle<Image>>,
+}
+
+fn spawn_blob_shadows(
+    mut commands: Commands,
+    characters: Query<(Entity, &Collider), (Added<TnuaController>, With<FloatHeight>)>,
+    mut texture: ResMut<BlobShadowTexture>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, collider) in characters.iter() {
+        let radius = collider
+            .shape_scaled()
+            .compute_local_aabb()
+            .half_extents()
+            .x;
+        let mesh = texture
+            .mesh
+            .get_or_insert_with(|| meshes.add(Plane3d::default().mesh().size(1., 1.)))
+            .clone();
+        let image = texture
+            .image
+            .get_or_insert_with(|| images.add(create_blob_image()))
+            .clone();
+        let material = materials.add(StandardMaterial {
+            base_color: Color::rgba(0., 0., 0., 0.),
+            base_color_texture: Some(image),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            depth_bias: DEPTH_BIAS,
+            ..default()
+        });
+        commands.spawn((
+            Name::new("Blob Shadow"),
+            PbrBundle {
+                mesh,
+                material,
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            NotShadowCaster,
+            BlobShadow {
+                owner: entity,
+                radius,
+                active: false,
+            },
+        ));
+    }
+}
+
+fn update_blob_shadows(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    mut blobs: Query<(
+        &mut BlobShadow,
+        &mut Transform,
+        &mut Visibility,
+        &Handle<StandardMaterial>,
+    )>,
+    characters: Query<(&Transform, &FloatHeight), Without<BlobShadow>>,
+    cameras: Query<&Transform, (With<IngameCamera>, Without<BlobShadow>)>,
+    children: Query<&Children>,
+    model_meshes: Query<(), With<Handle<Mesh>>>,
+    spatial_query: SpatialQuery,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_blob_shadows").entered();
+    let settings = config.graphics.resolve();
+    let camera = cameras.iter().next().map(|transform| transform.translation);
+    let blob_distance = settings.shadow_distance * SHADOW_DISTANCE_FACTOR;
+    let filter = SpatialQueryFilter::from_mask(CollisionLayer::Terrain.to_bits());
+    for (mut blob, mut transform, mut visibility, material) in blobs.iter_mut() {
+        let Ok((character, float_height)) = characters.get(blob.owner) else {
+            continue;
+        };
+        let active = match settings.shadow_quality {
+            ShadowQuality::Low => true,
+            ShadowQuality::High => camera.is_some_and(|camera| {
+                camera.distance_squared(character.translation) > blob_distance * blob_distance
+            }),
+        };
+        if blob.active != active {
+            blob.active = active;
+            if active {
+                commands.entity(blob.owner).insert(UsesBlobShadow);
+            } else {
+                commands.entity(blob.owner).remove::<UsesBlobShadow>();
+            }
+            set_casts_shadows(&mut commands, blob.owner, !active, &children, &model_meshes);
+        }
+        if !active {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+
+        let Some(hit) = spatial_query.cast_ray(
+            character.translation,
+            Direction3d::NEG_Y,
+            float_height.0 + MAX_HEIGHT,
+            true,
+            filter.clone(),
+        ) else {
+            // Over a void
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        let height = (hit.time_of_impact - float_height.0).max(0.);
+        let fade = 1. - height / MAX_HEIGHT;
+        let ground = character.translation - Vec3::Y * hit.time_of_impact;
+        *transform = Transform::from_translation(ground + hit.normal * GROUND_OFFSET)
+            .looking_to(hit.normal.any_orthonormal_vector(), hit.normal)
+            .with_scale(Vec3::splat(
+                blob.radius * SIZE_PER_RADIUS * (0.5 + 0.5 * fade),
+            ));
+        *visibility = Visibility::Visible;
+        if let Some(material) = materials.get_mut(material) {
+            material.base_color.set_a(MAX_OPACITY * fade);
+        }
+    }
+}
+
+/// Models are often spawned a few frames after their character, so they need to be caught separately.
+fn hide_shadows_of_new_meshes(
+    mut commands: Commands,
+    meshes: Query<Entity, Added<Handle<Mesh>>>,
+    parents: Query<&Parent>,
+    blob_users: Query<(), With<UsesBlobShadow>>,
+) {
+    for entity in meshes.iter() {
+        if parents
+            .iter_ancestors(entity)
+            .any(|ancestor| blob_users.contains(ancestor))
+        {
+            commands.entity(entity).insert(NotShadowCaster);
+        }
+    }
+}
+
+fn despawn_orphaned_blob_shadows(
+    mut commands: Commands,
+    blobs: Query<(Entity, &BlobShadow)>,
+    characters: Query<(), With<TnuaController>>,
+) {
+    for (entity, blob) in blobs.iter() {
+        if !characters.contains(blob.owner) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn set_casts_shadows(
+    commands: &mut Commands,
+    character: Entity,
+    casts_shadows: bool,
+    children: &Query<&Children>,
+    model_meshes: &Query<(), With<Handle<Mesh>>>,
+) {
+    for entity in children.iter_descendants(character) {
+        if !model_meshes.contains(entity) {
+            continue;
+        }
+        if casts_shadows {
+            commands.entity(entity).remove::<NotShadowCaster>();
+        } else {
+            commands.entity(entity).insert(NotShadowCaster);
+        }
+    }
+}
+
+/// A white disc whose alpha falls off smoothly towards the rim. The material tints it, e.g. black for blob shadows.
+pub(super) fn create_blob_image() -> Image {
+    let size = TEXTURE_SIZE as usize;
+    let center = (size as f32 - 1.) / 2.;
+    let mut data = Vec::with_capacity(size * size * 4);
+    for y in 0..size {
+        for x in 0..size {
+            let distance = Vec2::new(x as f32 - center, y as f32 - center).length() / center;
+            let t = (1. - distance).clamp(0., 1.);
+            let alpha = t * t * (3. - 2. * t);
+            data.extend_from_slice(&[255, 255, 255, (alpha * 255.) as u8]);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: TEXTURE_SIZE,
+            height: TEXTURE_SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
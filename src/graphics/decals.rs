@@ -0,0 +1,264 @@
+use crate::{
+    file_system_interaction::config::GameConfig, graphics::blob_shadows::create_blob_image,
+    movement::physics::CollisionLayer, GameState,
+};
+use bevy::{pbr::NotShadowCaster, prelude::*};
+use bevy_xpbd_3d::prelude::*;
+use std::collections::VecDeque;
+
+/// No more than this many decals exist at the same time, scaled by the [`Graphics::particle_budget`](crate::file_system_interaction::config::Graphics::particle_budget).
+const MAX_DECALS: usize = 64;
+/// How far in front of and behind its position a decal looks for the surface, in meters.
+const SEARCH_DISTANCE: f32 = 1.;
+/// Lifts decals off the surface. Together with [`DEPTH_BIAS`], this keeps them from z-fighting with it.
+const SURFACE_OFFSET: f32 = 0.008;
+const DEPTH_BIAS: f32 = 7.;
+
+/// Projects [`Decal`]s onto the level geometry, e.g. target markers or splashes. For now, a decal is a flat quad lying on the surface
+/// it was projected onto, so on edges and sharp bends it sticks out instead of wrapping around them.
+/// Decals become children of the surface, so that they move with it and are despawned along with it.
+/// Once more decals exist than the graphics preset's particle budget allows, the oldest are despawned first.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Decal>()
+        .init_resource::<DecalPool>()
+        .add_systems(
+            Update,
+            (project_decals, fade_decals)
+                .chain()
+                .run_if(in_state(GameState::Playing).and_then(resource_exists::<GameConfig>)),
+        );
+}
+
+/// A texture projected onto the closest surface around `position`. See [`spawn_decal`].
+#[derive(Debug, Clone, PartialEq, Component, Reflect)]
+#[reflect(Component, Default)]
+pub(crate) struct Decal {
+    pub(crate) position: Vec3,
+    /// Roughly the direction the surface faces, e.g. up for the ground. The surface is searched for along it,
+    /// while the decal is aligned with the surface's actual normal.
+    pub(crate) normal: Vec3,
+    /// Where the top of the texture points, projected onto the surface.
+    pub(crate) facing: Vec3,
+    /// Width and length, in meters.
+    pub(crate) size: Vec2,
+    /// `None` is a soft round spot.
+    pub(crate) texture: Option<Handle<Image>>,
+    /// Tints the texture. The alpha is the opacity before fading out.
+    pub(crate) color: Color,
+    pub(crate) unlit: bool,
+    /// In seconds.
+    pub(crate) lifetime: f32,
+    /// The decal fades out during the last this many seconds of its lifetime.
+    pub(crate) fade_duration: f32,
+}
+
+impl Default for Decal {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            normal: Vec3::Y,
+            facing: Vec3::NEG_Z,
+            size: Vec2::ONE,
+            texture: None,
+            color: Color::WHITE,
+            unlit: false,
+            lifetime: 10.,
+            fade_duration: 1.,
+        }
+    }
+}
+
+/// Spawns a decal that is projected onto the surface in the next update.
+/// A decal that finds no surface within a meter of its position is dropped.
+pub(crate) fn spawn_decal(commands: &mut Commands, decal: Decal) -> Entity {
+    commands.spawn((Name::new("Decal"), decal)).id()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct ProjectedDecal {
+    age: f32,
+}
+
+/// All projected decals from oldest to newest.
+#[derive(Debug, Default, Resource)]
+struct DecalPool {
+    decals: VecDeque<Entity>,
+    mesh: Option<Handle<Mesh>>,
+    default_texture: Option<Handle<Image>>,
+}
+
+fn max_decals(config: &GameConfig) -> usize {
+    let budget = config.graphics.resolve().particle_budget.max(0.);
+    (MAX_DECALS as f32 * budget).round() as usize
+}
+
+fn project_decals(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    new_decals: Query<(Entity, &Decal), Without<ProjectedDecal>>,
+    projected: Query<(), With<ProjectedDecal>>,
+    surfaces: Query<&GlobalTransform>,
+    spatial_query: SpatialQuery,
+    mut pool: ResMut<DecalPool>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("project_decals").entered();
+    // Decals on despawned surfaces were despawned with them
+    pool.decals.retain(|&entity| projected.contains(entity));
+    let filter = SpatialQueryFilter::from_mask(CollisionLayer::Terrain.to_bits());
+    for (entity, decal) in new_decals.iter() {
+        let direction = Direction3d::new(-decal.normal).unwrap_or(Direction3d::NEG_Y);
+        let origin = decal.position - *direction * SEARCH_DISTANCE;
+        let surface = spatial_query
+            .cast_ray(
+                origin,
+                direction,
+                SEARCH_DISTANCE * 2.,
+                true,
+                filter.clone(),
+            )
+            .and_then(|hit| Some((hit, surfaces.get(hit.entity).ok()?)));
+        let Some((hit, surface_transform)) = surface else {
+            debug!(
+                "Dropped the decal {entity:?}, since there is no surface at {}",
+                decal.position
+            );
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        let point = origin + *direction * hit.time_of_impact;
+        let forward = decal.facing - hit.normal * decal.facing.dot(hit.normal);
+        let forward = forward
+            .try_normalize()
+            .unwrap_or_else(|| hit.normal.any_orthonormal_vector());
+        let transform = Transform::from_translation(point + hit.normal * SURFACE_OFFSET)
+            .looking_to(forward, hit.normal)
+            .with_scale(Vec3::new(decal.size.x, 1., decal.size.y));
+
+        let mesh = pool
+            .mesh
+            .get_or_insert_with(|| meshes.add(Plane3d::default().mesh().size(1., 1.)))
+            .clone();
+        let texture = decal.texture.clone().unwrap_or_else(|| {
+            pool.default_texture
+                .get_or_insert_with(|| images.add(create_blob_image()))
+                .clone()
+        });
+        let material = materials.add(StandardMaterial {
+            base_color: decal.color,
+            base_color_texture: Some(texture),
+            alpha_mode: AlphaMode::Blend,
+            perceptual_roughness: 1.,
+            unlit: decal.unlit,
+            depth_bias: DEPTH_BIAS,
+            ..default()
+        });
+        commands
+            .entity(entity)
+            .insert((
+                PbrBundle {
+                    mesh,
+                    material,
+                    transform: GlobalTransform::from(transform).reparented_to(surface_transform),
+                    ..default()
+                },
+                NotShadowCaster,
+                ProjectedDecal { age: 0. },
+            ))
+            .set_parent(hit.entity);
+        pool.decals.push_back(entity);
+    }
+    let max_count = max_decals(&config);
+    while pool.decals.len() > max_count {
+        if let Some(entity) = pool.decals.pop_front() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn fade_decals(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut decals: Query<(
+        Entity,
+        &Decal,
+        &mut ProjectedDecal,
+        &Handle<StandardMaterial>,
+    )>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, decal, mut projected, material) in decals.iter_mut() {
+        projected.age += time.delta_seconds();
+        let remaining = decal.lifetime - projected.age;
+        if remaining <= 0. {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        if remaining < decal.fade_duration {
+            if let Some(material) = materials.get_mut(material) {
+                let fade = remaining / decal.fade_duration;
+                material.base_color.set_a(decal.color.a() * fade);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{assert_near, TestApp};
+
+    #[test]
+    fn decals_stick_to_their_surface_and_the_oldest_make_way() {
+        let mut app = TestApp::new();
+        app.add_plugins(plugin)
+            .init_asset::<Image>()
+            .init_asset::<StandardMaterial>();
+        app.spawn_ground();
+        // Its top is at the height 1
+        let block = app.spawn_block(Vec3::new(4., 0.5, 0.), Vec3::splat(1.));
+        // Lets the spatial query pick up the colliders
+        app.step(1);
+
+        let marker = app
+            .world_mut()
+            .spawn(Decal {
+                position: Vec3::new(4., 1.5, 0.),
+                lifetime: 0.5,
+                ..default()
+            })
+            .id();
+        app.step(1);
+        assert_eq!(
+            app.world().get::<Parent>(marker).map(Parent::get),
+            Some(block)
+        );
+        assert_near(
+            app.translation(marker),
+            Vec3::new(4., 1. + SURFACE_OFFSET, 0.),
+            1e-3,
+        );
+        app.step(30);
+        assert!(app.world().get_entity(marker).is_none());
+
+        let max_count = max_decals(app.resource::<GameConfig>());
+        let decals: Vec<_> = (0..=max_count)
+            .map(|index| {
+                app.world_mut()
+                    .spawn(Decal {
+                        position: Vec3::new(index as f32 * 0.1 - 10., 0., 5.),
+                        ..default()
+                    })
+                    .id()
+            })
+            .collect();
+        app.step(1);
+        assert!(app.world().get_entity(decals[0]).is_none());
+        assert!(decals[1..]
+            .iter()
+            .all(|&decal| app.world().get_entity(decal).is_some()));
+    }
+}
@@ -1,64 +1,526 @@
 use crate::{
-    player_control::actions::{ActionsFrozen, UiAction},
+    file_system_interaction::{
+        autosave::is_autosave_slot,
+        localization::{t, CurrentLocale, Strings},
+        save::{
+            list_save_slots, save_exists, DeleteSaveEvent, LoadGameEvent, SaveGameEvent, SaveSlot,
+            ThumbnailCapture, QUICK_SAVE_SLOT,
+        },
+    },
+    player_control::{
+        actions::{ActionsFrozen, UiAction, UiActions},
+        camera::CursorGrabRequests,
+        ui_layer::{UiLayer, UiLayers},
+        virtual_cursor::CursorTargetExt,
+    },
+    world_interaction::readable::CollectedNotes,
     GameState,
 };
-use bevy::{app::AppExit, prelude::*};
+use bevy::{app::AppExit, prelude::*, utils::HashMap};
 use bevy_egui::{egui, EguiContexts};
 use bevy_xpbd_3d::prelude::*;
-use leafwing_input_manager::prelude::ActionState;
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// How often the save slots are read from disk again while the menu is open.
+const SLOT_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+/// The size thumbnails are shown at, in points.
+const THUMBNAIL_SIZE: egui::Vec2 = egui::vec2(128., 72.);
 
 /// Handles the pause menu accessed while playing the game via ESC.
+/// It is a [`UiLayer::SystemModal`], so it takes the input from any dialog or note below it until it closes.
 pub(super) fn plugin(app: &mut App) {
-    app.add_systems(Update, handle_pause.run_if(in_state(GameState::Playing)));
+    app.init_resource::<Paused>().add_systems(
+        Update,
+        (toggle_pause, display_pause_menu)
+            .chain()
+            .run_if(in_state(GameState::Playing)),
+    );
 }
 
-fn handle_pause(
+/// Whether the pause menu is open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource, Default)]
+struct Paused(bool);
+
+fn toggle_pause(
     mut time: ResMut<Time<Virtual>>,
     mut physics_time: ResMut<Time<Physics>>,
-    actions: Query<&ActionState<UiAction>>,
-    mut app_exit_events: EventWriter<AppExit>,
+    mut ui_actions: UiActions,
     mut actions_frozen: ResMut<ActionsFrozen>,
-    mut egui_contexts: EguiContexts,
-    mut paused: Local<bool>,
+    mut cursor_grab: ResMut<CursorGrabRequests>,
+    mut ui_layers: ResMut<UiLayers>,
+    mut paused: ResMut<Paused>,
 ) {
-    for action in actions.iter() {
-        let toggled = action.just_pressed(&UiAction::TogglePause);
-        if toggled {
-            if *paused {
-                *paused = false;
-                time.unpause();
-                physics_time.unpause();
-                actions_frozen.unfreeze();
-            } else {
-                *paused = true;
-                time.pause();
-                physics_time.pause();
-                actions_frozen.freeze();
-            }
+    let layer = UiLayer::SystemModal;
+    let resumed = paused.0 && ui_actions.consume(layer, UiAction::Cancel);
+    if ui_actions.consume(layer, UiAction::TogglePause) || resumed {
+        if paused.0 {
+            paused.0 = false;
+            time.unpause();
+            physics_time.unpause();
+            actions_frozen.unfreeze();
+            cursor_grab.release();
+        } else {
+            paused.0 = true;
+            time.pause();
+            physics_time.pause();
+            actions_frozen.freeze();
+            cursor_grab.request_free();
         }
     }
-    if !*paused {
+    if paused.0 {
+        ui_layers.show(layer);
+    }
+}
+
+fn display_pause_menu(
+    paused: Res<Paused>,
+    mut app_exit_events: EventWriter<AppExit>,
+    mut save_events: EventWriter<SaveGameEvent>,
+    mut load_events: EventWriter<LoadGameEvent>,
+    mut egui_contexts: EguiContexts,
+    strings: Strings,
+    mut commands: Commands,
+    thumbnail_capture: Res<ThumbnailCapture>,
+    mut delete_events: EventWriter<DeleteSaveEvent>,
+    mut slot_picker: Local<SlotPicker>,
+    collected_notes: Res<CollectedNotes>,
+) {
+    let layer = UiLayer::SystemModal;
+    if !paused.0 {
+        return;
+    }
+    if paused.is_changed() {
+        // Just opened
+        slot_picker.refreshed_at = None;
+    }
+    if thumbnail_capture.is_capturing() {
+        // Keeps the menu out of the thumbnail of the save that was just made
         return;
     }
+    slot_picker.refresh();
+
+    // An area instead of a panel, because panels are always drawn below windows
+    let ctx = egui_contexts.ctx_mut();
+    let screen = ctx.screen_rect();
+    egui::Area::new(egui::Id::new("Pause Menu"))
+        .order(layer.order())
+        .fixed_pos(screen.min)
+        .show(ctx, |ui| {
+            egui::Frame::none()
+                .fill(egui::Color32::from_black_alpha(240))
+                .show(ui, |ui| {
+                    ui.set_min_size(screen.size());
+                    ui.vertical_centered_justified(|ui| {
+                        ui.visuals_mut().override_text_color = Some(egui::Color32::from_gray(240));
+                        ui.add_space(100.0);
+                        ui.heading(t!(strings, "pause.title"));
+                        ui.separator();
+                        ui.label(t!(strings, "pause.hint", key = "ESC"));
 
-    egui::CentralPanel::default()
-        .frame(egui::Frame {
-            fill: egui::Color32::from_black_alpha(240),
-            ..default()
-        })
-        .show(egui_contexts.ctx_mut(), |ui| {
-            ui.vertical_centered_justified(|ui| {
-                ui.visuals_mut().override_text_color = Some(egui::Color32::from_gray(240));
-                ui.add_space(100.0);
-                ui.heading("Game Paused");
-                ui.separator();
-                ui.label("Press ESC to resume");
-
-                ui.add_space(100.0);
-
-                if ui.button("Quit Game").clicked() {
-                    app_exit_events.send(AppExit);
-                }
-            });
+                        ui.add_space(100.0);
+
+                        ui.label(t!(strings, "pause.language"));
+                        ui.horizontal_wrapped(|ui| {
+                            for (locale, name) in strings.available_locales() {
+                                let is_current = locale == strings.current_locale();
+                                if ui
+                                    .selectable_label(is_current, name)
+                                    .cursor_target()
+                                    .clicked()
+                                    && !is_current
+                                {
+                                    commands.insert_resource(CurrentLocale(locale.to_string()));
+                                }
+                            }
+                        });
+
+                        ui.add_space(50.0);
+
+                        if ui
+                            .button(t!(strings, "pause.quick_save"))
+                            .cursor_target()
+                            .clicked()
+                        {
+                            save_events.send(SaveGameEvent {
+                                slot: QUICK_SAVE_SLOT.to_string(),
+                            });
+                        }
+                        let can_load = save_exists(QUICK_SAVE_SLOT);
+                        if ui
+                            .add_enabled(
+                                can_load,
+                                egui::Button::new(t!(strings, "pause.quick_load")),
+                            )
+                            .cursor_target()
+                            .clicked()
+                        {
+                            load_events.send(LoadGameEvent {
+                                slot: QUICK_SAVE_SLOT.to_string(),
+                            });
+                        }
+
+                        ui.add_space(50.0);
+
+                        ui.label(t!(strings, "pause.saves"));
+                        if ui
+                            .button(t!(strings, "pause.new_save"))
+                            .cursor_target()
+                            .clicked()
+                        {
+                            save_events.send(SaveGameEvent {
+                                slot: slot_picker.free_slot(),
+                            });
+                        }
+                        save_slots(
+                            ui,
+                            &strings,
+                            &mut slot_picker,
+                            false,
+                            &mut load_events,
+                            &mut delete_events,
+                        );
+                        if slot_picker
+                            .slots
+                            .iter()
+                            .any(|slot| is_autosave_slot(&slot.name))
+                        {
+                            ui.add_space(20.0);
+                            ui.label(t!(strings, "pause.autosaves"));
+                            save_slots(
+                                ui,
+                                &strings,
+                                &mut slot_picker,
+                                true,
+                                &mut load_events,
+                                &mut delete_events,
+                            );
+                        }
+                        if !collected_notes.0.is_empty() {
+                            ui.add_space(50.0);
+                            ui.label(t!(strings, "pause.notes"));
+                            egui::ScrollArea::vertical()
+                                .id_source("notes")
+                                .max_height(160.0)
+                                .show(ui, |ui| {
+                                    for title in &collected_notes.0 {
+                                        ui.strong(title);
+                                    }
+                                });
+                        }
+                        ui.add_space(50.0);
+                        if ui
+                            .button(t!(strings, "pause.quit"))
+                            .cursor_target()
+                            .clicked()
+                        {
+                            app_exit_events.send(AppExit);
+                        }
+                    });
+                });
         });
 }
+
+/// The save slots listed in the pause menu, read from disk when the menu opens and every [`SLOT_REFRESH_INTERVAL`] after that.
+#[derive(Default)]
+struct SlotPicker {
+    slots: Vec<SaveSlot>,
+    refreshed_at: Option<Instant>,
+    thumbnails: HashMap<String, Thumbnail>,
+    /// The slot whose delete button was clicked, waiting for confirmation.
+    confirm_delete: Option<String>,
+}
+
+struct Thumbnail {
+    modified: Option<SystemTime>,
+    /// `None` if the thumbnail is missing or could not be decoded, in which case a placeholder is shown.
+    texture: Option<egui::TextureHandle>,
+}
+
+impl SlotPicker {
+    fn refresh(&mut self) {
+        if self
+            .refreshed_at
+            .is_some_and(|refreshed_at| refreshed_at.elapsed() < SLOT_REFRESH_INTERVAL)
+        {
+            return;
+        }
+        self.refreshed_at = Some(Instant::now());
+        self.slots = list_save_slots();
+        let slots = &self.slots;
+        self.thumbnails
+            .retain(|name, _| slots.iter().any(|slot| slot.name == *name));
+    }
+
+    /// A slot name that no save uses yet.
+    fn free_slot(&self) -> String {
+        (1..)
+            .map(|index| format!("slot-{index}"))
+            .find(|name| !self.slots.iter().any(|slot| slot.name == *name) && !save_exists(name))
+            .unwrap_or_default()
+    }
+
+    /// Loads the thumbnail of a slot into egui, again whenever the file changed.
+    fn thumbnail(&mut self, ctx: &egui::Context, slot: &SaveSlot) -> Option<egui::TextureId> {
+        let modified = fs::metadata(&slot.thumbnail)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let is_current = self
+            .thumbnails
+            .get(&slot.name)
+            .is_some_and(|thumbnail| thumbnail.modified == modified);
+        if !is_current {
+            let texture = modified.and_then(|_| load_thumbnail(ctx, &slot.name, &slot.thumbnail));
+            self.thumbnails
+                .insert(slot.name.clone(), Thumbnail { modified, texture });
+        }
+        self.thumbnails[&slot.name]
+            .texture
+            .as_ref()
+            .map(egui::TextureHandle::id)
+    }
+}
+
+fn load_thumbnail(ctx: &egui::Context, slot: &str, path: &Path) -> Option<egui::TextureHandle> {
+    let image = image::open(path)
+        .map_err(|error| warn!("Failed to read the thumbnail {}: {error}", path.display()))
+        .ok()?
+        .to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+    let image = egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw());
+    Some(ctx.load_texture(
+        format!("Save Thumbnail {slot}"),
+        image,
+        egui::TextureOptions::LINEAR,
+    ))
+}
+
+/// Lists either the manual saves or the auto-saves, the most recent first.
+fn save_slots(
+    ui: &mut egui::Ui,
+    strings: &Strings,
+    slot_picker: &mut SlotPicker,
+    autosaves: bool,
+    load_events: &mut EventWriter<LoadGameEvent>,
+    delete_events: &mut EventWriter<DeleteSaveEvent>,
+) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let slots: Vec<_> = slot_picker
+        .slots
+        .iter()
+        .filter(|slot| is_autosave_slot(&slot.name) == autosaves)
+        .cloned()
+        .collect();
+    egui::ScrollArea::vertical()
+        .id_source(autosaves)
+        .max_height(if autosaves { 160.0 } else { 320.0 })
+        .show(ui, |ui| {
+            for slot in &slots {
+                ui.horizontal(|ui| {
+                    match slot_picker.thumbnail(ui.ctx(), slot) {
+                        Some(texture) => {
+                            ui.image((texture, THUMBNAIL_SIZE));
+                        }
+                        None => thumbnail_placeholder(ui, strings),
+                    }
+                    ui.vertical(|ui| {
+                        ui.strong(&slot.name);
+                        match &slot.metadata {
+                            Some(metadata) => {
+                                ui.label(t!(
+                                    strings,
+                                    "pause.slot_details",
+                                    level = metadata.level,
+                                    time = format_hour(metadata.hour),
+                                    play_time = format_play_time(metadata.play_time),
+                                ));
+                                ui.label(t!(
+                                    strings,
+                                    "pause.saved_ago",
+                                    age = format_age(now.saturating_sub(metadata.saved_at)),
+                                ));
+                            }
+                            None => {
+                                ui.label(t!(strings, "pause.no_details"));
+                            }
+                        }
+                        ui.horizontal(|ui| {
+                            if slot_picker.confirm_delete.as_ref() == Some(&slot.name) {
+                                ui.label(t!(strings, "pause.confirm_delete"));
+                                if ui
+                                    .button(t!(strings, "pause.delete"))
+                                    .cursor_target()
+                                    .clicked()
+                                {
+                                    delete_events.send(DeleteSaveEvent {
+                                        slot: slot.name.clone(),
+                                    });
+                                    slot_picker.confirm_delete = None;
+                                    slot_picker.refreshed_at = None;
+                                }
+                                if ui
+                                    .button(t!(strings, "pause.keep"))
+                                    .cursor_target()
+                                    .clicked()
+                                {
+                                    slot_picker.confirm_delete = None;
+                                }
+                            } else {
+                                if ui
+                                    .button(t!(strings, "pause.load"))
+                                    .cursor_target()
+                                    .clicked()
+                                {
+                                    load_events.send(LoadGameEvent {
+                                        slot: slot.name.clone(),
+                                    });
+                                }
+                                if ui
+                                    .button(t!(strings, "pause.delete"))
+                                    .cursor_target()
+                                    .clicked()
+                                {
+                                    slot_picker.confirm_delete = Some(slot.name.clone());
+                                }
+                            }
+                        });
+                    });
+                });
+            }
+        });
+}
+
+fn thumbnail_placeholder(ui: &mut egui::Ui, strings: &Strings) {
+    let (rect, _) = ui.allocate_exact_size(THUMBNAIL_SIZE, egui::Sense::hover());
+    let painter = ui.painter();
+    painter.rect_filled(rect, 4.0, egui::Color32::from_gray(60));
+    painter.text(
+        rect.center(),
+        egui::Align2::CENTER_CENTER,
+        t!(strings, "pause.no_preview"),
+        egui::FontId::proportional(12.0),
+        egui::Color32::from_gray(160),
+    );
+}
+
+/// E.g. "07:30".
+fn format_hour(hour: f32) -> String {
+    let minutes = (hour.rem_euclid(24.) * 60.) as u32;
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// E.g. "2:05 h".
+fn format_play_time(seconds: f32) -> String {
+    let minutes = (seconds.max(0.) / 60.) as u32;
+    format!("{}:{:02} h", minutes / 60, minutes % 60)
+}
+
+/// E.g. "5 min" or "3 d".
+fn format_age(seconds: u64) -> String {
+    let minutes = seconds / 60;
+    let hours = minutes / 60;
+    let days = hours / 24;
+    if days > 0 {
+        format!("{days} d")
+    } else if hours > 0 {
+        format!("{hours} h")
+    } else {
+        format!("{minutes} min")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        player_control::{
+            actions::ui_queue,
+            ui_layer::{self, receives_input},
+        },
+        testing::TestApp,
+    };
+    use leafwing_input_manager::prelude::ActionState;
+
+    /// Stands in for the dialogue view, which advances on [`UiAction::Confirm`] and is gated like the real one.
+    #[derive(Debug, Default, Resource)]
+    struct Dialog {
+        lines_advanced: usize,
+    }
+
+    fn advance_dialog(mut dialog: ResMut<Dialog>, mut ui_actions: UiActions) {
+        if ui_actions.consume(UiLayer::GameplayModal, UiAction::Confirm) {
+            dialog.lines_advanced += 1;
+        }
+    }
+
+    fn show_dialog(mut ui_layers: ResMut<UiLayers>) {
+        ui_layers.show(UiLayer::GameplayModal);
+    }
+
+    #[test]
+    fn pausing_over_a_dialog_takes_its_input_until_the_pause_menu_closes() {
+        let mut app = TestApp::new();
+        app.add_plugins((ui_queue::plugin, ui_layer::plugin))
+            .init_resource::<Paused>()
+            .init_resource::<ActionsFrozen>()
+            .init_resource::<CursorGrabRequests>()
+            .init_resource::<Dialog>()
+            .add_systems(
+                Update,
+                (
+                    toggle_pause,
+                    advance_dialog.run_if(receives_input(UiLayer::GameplayModal)),
+                    show_dialog,
+                )
+                    .chain(),
+            );
+        let input = app
+            .world_mut()
+            .spawn(ActionState::<UiAction>::default())
+            .id();
+        let press = |app: &mut TestApp, action: UiAction| {
+            app.world_mut()
+                .get_mut::<ActionState<UiAction>>(input)
+                .unwrap()
+                .press(&action);
+            app.step(1);
+            app.world_mut()
+                .get_mut::<ActionState<UiAction>>(input)
+                .unwrap()
+                .release(&action);
+            app.step(1);
+        };
+        let lines_advanced = |app: &TestApp| app.resource::<Dialog>().lines_advanced;
+        app.step(1);
+        press(&mut app, UiAction::Confirm);
+        assert_eq!(lines_advanced(&app), 1);
+
+        press(&mut app, UiAction::TogglePause);
+        assert!(app.resource::<Paused>().0);
+        assert!(app.resource::<Time<Virtual>>().is_paused());
+        assert_eq!(
+            app.resource::<UiLayers>().top_modal(),
+            Some(UiLayer::SystemModal)
+        );
+        // The dialog below the pause menu does not advance
+        press(&mut app, UiAction::Confirm);
+        assert_eq!(lines_advanced(&app), 1);
+
+        press(&mut app, UiAction::Cancel);
+        assert!(!app.resource::<Paused>().0);
+        assert!(!app.resource::<Time<Virtual>>().is_paused());
+        assert!(!app.resource::<ActionsFrozen>().is_frozen());
+        assert_eq!(
+            app.resource::<UiLayers>().top_modal(),
+            Some(UiLayer::GameplayModal)
+        );
+        // Once the pause menu is closed, the dialog goes on
+        press(&mut app, UiAction::Confirm);
+        assert_eq!(lines_advanced(&app), 2);
+    }
+}
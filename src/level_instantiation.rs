@@ -1,13 +1,53 @@
 use bevy::prelude::*;
 
 mod blender_workflow;
-mod map;
+pub(crate) mod environment;
+pub(crate) mod level_bounds;
+pub(crate) mod level_overrides;
+pub(crate) mod map;
+pub(crate) mod named_entities;
 pub(crate) mod on_spawn;
+pub(crate) mod portal;
+pub(crate) mod prop_persistence;
+pub(crate) mod spawn_manifest;
+pub(crate) mod spawn_queue;
+pub(crate) mod stable_id;
+pub(crate) mod static_geometry;
+pub(crate) mod tags;
+pub(crate) mod validation;
 
 /// Handles creation of levels and objects. Split into the following sub-plugins:
 /// - [`map::plugin`] handles loading of level files and orchestrates the spawning of the objects therein.
 /// - [`on_spawn::plugin`] handles the spawning of objects in general.
 /// - [`blender_workflow::plugin`] handles the integration with [kaosat's Blender workflow](https://github.com/kaosat-dev/Blender_bevy_components_workflow)
+/// - [`environment::plugin`] applies the lighting, fog and sky of the current level.
+/// - [`level_bounds::plugin`] fences in the playable area and catches whatever falls out of the level.
+/// - [`level_overrides::plugin`] applies the edits the dev tools saved on top of the level files.
+/// - [`named_entities::plugin`] keeps track of entities by their name.
+/// - [`portal::plugin`] streams levels in and out through portals.
+/// - [`prop_persistence::plugin`] remembers where props were left in a level.
+/// - [`spawn_manifest::plugin`] spawns the blueprints listed in `.spawns.ron` files and applies edits to them in place.
+/// - [`spawn_queue::plugin`] spawns requested blueprints within a per-frame budget.
+/// - [`static_geometry::plugin`] stops propagating the transforms of spawned objects that never move.
+/// - [`stable_id::plugin`] keeps track of entities by an id that survives reloads.
+/// - [`tags::plugin`] keeps track of groups of entities by their tags.
+/// - [`validation::plugin`] reports mistakes in the components of level files.
 pub(super) fn plugin(app: &mut App) {
-    app.add_plugins((map::plugin, on_spawn::plugin, blender_workflow::plugin));
+    app.add_plugins((
+        map::plugin,
+        on_spawn::plugin,
+        blender_workflow::plugin,
+        environment::plugin,
+        level_bounds::plugin,
+        level_overrides::plugin,
+        named_entities::plugin,
+        portal::plugin,
+        prop_persistence::plugin,
+        spawn_manifest::plugin,
+        spawn_queue::plugin,
+        stable_id::plugin,
+        static_geometry::plugin,
+        tags::plugin,
+        validation::plugin,
+    ));
 }
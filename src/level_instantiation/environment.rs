@@ -0,0 +1,273 @@
+use crate::{
+    level_instantiation::map::{CurrentLevel, LevelRoot},
+    player_control::camera::IngameCamera,
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_atmosphere::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Applies the [`EnvironmentSettings`] of the current level to the lighting, the fog and the sky.
+/// The resolved values live in the [`LevelEnvironment`]. When the player leaves for a level without settings,
+/// everything goes back to the defaults the game started with.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<EnvironmentSettings>()
+        .register_type::<LevelEnvironment>()
+        .add_systems(Startup, store_default_environment)
+        .add_systems(
+            Update,
+            (resolve_environment, apply_environment)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// How a level is lit, described in the level file. Levels have at most one.
+/// Omitted values keep the defaults the game starts with.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct EnvironmentSettings {
+    pub(crate) ambient_color: Option<Color>,
+    pub(crate) ambient_brightness: Option<f32>,
+    /// The direction the level's directional lights shine in. They keep their rotation from the level file when `None`.
+    pub(crate) sun_direction: Option<Vec3>,
+    pub(crate) fog_color: Option<Color>,
+    /// How far from the camera the fog starts, in meters. Levels have fog when any of the fog values is set.
+    pub(crate) fog_start: Option<f32>,
+    /// From this many meters on, the fog hides everything.
+    pub(crate) fog_end: Option<f32>,
+    /// Replaces the sky with a plain color.
+    pub(crate) clear_color: Option<Color>,
+}
+
+impl EnvironmentSettings {
+    fn has_fog(&self) -> bool {
+        self.fog_color.is_some() || self.fog_start.is_some() || self.fog_end.is_some()
+    }
+
+    /// Fills in the omitted values from `defaults`.
+    fn resolve(&self, defaults: &LevelEnvironment) -> LevelEnvironment {
+        let default_fog = defaults.fog.unwrap_or_default();
+        LevelEnvironment {
+            ambient_color: self.ambient_color.unwrap_or(defaults.ambient_color),
+            ambient_brightness: self
+                .ambient_brightness
+                .unwrap_or(defaults.ambient_brightness),
+            sun_direction: self
+                .sun_direction
+                .and_then(|direction| direction.try_normalize())
+                .or(defaults.sun_direction),
+            fog: if self.has_fog() {
+                Some(Fog {
+                    color: self.fog_color.unwrap_or(default_fog.color),
+                    start: self.fog_start.unwrap_or(default_fog.start),
+                    end: self.fog_end.unwrap_or(default_fog.end),
+                })
+            } else {
+                defaults.fog
+            },
+            clear_color: self.clear_color.or(defaults.clear_color),
+        }
+    }
+}
+
+/// The environment of the current level with all values filled in.
+/// Systems that change the lighting over time, e.g. with the time of day, should blend from these values instead of replacing them.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect)]
+#[reflect(Resource)]
+pub(crate) struct LevelEnvironment {
+    pub(crate) ambient_color: Color,
+    pub(crate) ambient_brightness: f32,
+    pub(crate) sun_direction: Option<Vec3>,
+    pub(crate) fog: Option<Fog>,
+    /// Shown instead of the sky.
+    pub(crate) clear_color: Option<Color>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub(crate) struct Fog {
+    pub(crate) color: Color,
+    pub(crate) start: f32,
+    pub(crate) end: f32,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        let settings = FogSettings::default();
+        let (start, end) = match settings.falloff {
+            FogFalloff::Linear { start, end } => (start, end),
+            _ => (0., 100.),
+        };
+        Self {
+            color: settings.color,
+            start,
+            end,
+        }
+    }
+}
+
+/// The environment the game starts with, used for levels without [`EnvironmentSettings`] and for omitted values.
+#[derive(Debug, Clone, Copy, PartialEq, Resource)]
+struct DefaultEnvironment {
+    environment: LevelEnvironment,
+    clear_color: Color,
+}
+
+/// The rotation a directional light had in the level file before the [`LevelEnvironment::sun_direction`] turned it.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct OriginalSunRotation(Quat);
+
+fn store_default_environment(
+    mut commands: Commands,
+    ambient_light: Option<Res<AmbientLight>>,
+    clear_color: Option<Res<ClearColor>>,
+) {
+    let ambient_light = ambient_light.map_or_else(AmbientLight::default, |light| light.clone());
+    let environment = LevelEnvironment {
+        ambient_color: ambient_light.color,
+        ambient_brightness: ambient_light.brightness,
+        sun_direction: None,
+        fog: None,
+        clear_color: None,
+    };
+    commands.insert_resource(DefaultEnvironment {
+        environment,
+        clear_color: clear_color.map_or_else(|| ClearColor::default().0, |color| color.0),
+    });
+    commands.insert_resource(environment);
+}
+
+fn resolve_environment(
+    current_level: Res<CurrentLevel>,
+    defaults: Res<DefaultEnvironment>,
+    settings: Query<(Entity, &EnvironmentSettings)>,
+    parents: Query<&Parent>,
+    level_roots: Query<&LevelRoot>,
+    mut environment: ResMut<LevelEnvironment>,
+) {
+    let in_current_level = |entity: Entity| {
+        std::iter::once(entity)
+            .chain(parents.iter_ancestors(entity))
+            .find_map(|entity| level_roots.get(entity).ok())
+            .is_some_and(|root| root.0 == current_level.0)
+    };
+    let resolved = settings
+        .iter()
+        .find(|(entity, _)| in_current_level(*entity))
+        .map_or(defaults.environment, |(_, settings)| {
+            settings.resolve(&defaults.environment)
+        });
+    if *environment != resolved {
+        *environment = resolved;
+    }
+}
+
+fn apply_environment(
+    mut commands: Commands,
+    environment: Res<LevelEnvironment>,
+    defaults: Res<DefaultEnvironment>,
+    mut ambient_light: ResMut<AmbientLight>,
+    mut clear_color: ResMut<ClearColor>,
+    cameras: Query<(Entity, Has<AtmosphereCamera>), With<IngameCamera>>,
+    added_cameras: Query<(), Added<IngameCamera>>,
+    mut lights: Query<(
+        Entity,
+        &mut Transform,
+        Option<&OriginalSunRotation>,
+        Ref<DirectionalLight>,
+    )>,
+) {
+    let lights_added = lights.iter().any(|(.., light)| light.is_added());
+    if !environment.is_changed() && added_cameras.is_empty() && !lights_added {
+        return;
+    }
+    ambient_light.color = environment.ambient_color;
+    ambient_light.brightness = environment.ambient_brightness;
+    clear_color.0 = environment.clear_color.unwrap_or(defaults.clear_color);
+
+    for (camera, has_atmosphere) in cameras.iter() {
+        let mut camera = commands.entity(camera);
+        match environment.fog {
+            Some(fog) => {
+                camera.insert(FogSettings {
+                    color: fog.color,
+                    falloff: FogFalloff::Linear {
+                        start: fog.start,
+                        end: fog.end.max(fog.start),
+                    },
+                    ..default()
+                });
+            }
+            None => {
+                camera.remove::<FogSettings>();
+            }
+        }
+        // The atmosphere would draw over the clear color
+        match (environment.clear_color.is_some(), has_atmosphere) {
+            (true, true) => {
+                camera.remove::<AtmosphereCamera>();
+            }
+            (false, false) => {
+                camera.insert(AtmosphereCamera::default());
+            }
+            _ => {}
+        }
+    }
+
+    for (entity, mut transform, original, _) in lights.iter_mut() {
+        match (environment.sun_direction, original) {
+            (Some(direction), original) => {
+                if original.is_none() {
+                    commands
+                        .entity(entity)
+                        .insert(OriginalSunRotation(transform.rotation));
+                }
+                transform.look_to(direction, Vec3::Y);
+            }
+            (None, Some(original)) => {
+                transform.rotation = original.0;
+                commands.entity(entity).remove::<OriginalSunRotation>();
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> LevelEnvironment {
+        LevelEnvironment {
+            ambient_color: Color::WHITE,
+            ambient_brightness: 80.,
+            sun_direction: None,
+            fog: None,
+            clear_color: None,
+        }
+    }
+
+    #[test]
+    fn omitted_values_keep_defaults() {
+        let settings: EnvironmentSettings =
+            ron::from_str("(ambient_brightness: Some(200.), fog_end: Some(60.))").unwrap();
+        let environment = settings.resolve(&defaults());
+        assert_eq!(environment.ambient_color, Color::WHITE);
+        assert_eq!(environment.ambient_brightness, 200.);
+        assert_eq!(environment.sun_direction, None);
+        assert_eq!(environment.clear_color, None);
+        let fog = environment.fog.unwrap();
+        assert_eq!(fog.end, 60.);
+        assert_eq!(fog.start, Fog::default().start);
+        assert_eq!(fog.color, Fog::default().color);
+    }
+
+    #[test]
+    fn empty_settings_change_nothing() {
+        assert_eq!(
+            EnvironmentSettings::default().resolve(&defaults()),
+            defaults()
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use crate::{
+    file_system_interaction::localization::Strings,
+    level_instantiation::on_spawn::Player,
+    movement::{
+        character_controller::{Depenetrate, GroundedState},
+        physics::CollisionLayer,
+    },
+    player_control::{
+        camera::IngameCamera,
+        ui_layer::{UiLayer, UiLayers},
+    },
+    util::math_trait_ext::Vec3Ext,
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const WALL_THICKNESS: f32 = 1.;
+/// Without a ceiling, the walls reach this far above the bounds, so that nothing can jump over them.
+const WALL_OVERHANG: f32 = 20.;
+/// How much turning the camera towards the edge is slowed down right at the edge.
+const MAX_CAMERA_RESISTANCE: f32 = 0.6;
+/// How much the hint's opacity changes per second.
+const HINT_FADE_SPEED: f32 = 2.;
+
+/// Fences in the playable area of a level, described by a [`LevelBounds`] in the level file.
+/// The bounds get invisible walls and optionally a ceiling. Close to the walls, a hint asks the player to turn back
+/// and turning the camera towards the edge is slowed down. Whatever falls below the kill plane is caught:
+/// characters are put back where they last stood inside the bounds and send a [`FellOutOfBoundsEvent`], other bodies are despawned.
+/// The walls are rebuilt whenever the bounds change, e.g. while editing them with the dev tools.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<LevelBounds>()
+        .add_event::<FellOutOfBoundsEvent>()
+        .init_resource::<BoundsWarning>()
+        .add_systems(
+            Update,
+            (
+                build_bounds_walls,
+                warn_near_bounds,
+                display_bounds_hint,
+                catch_fallen_bodies,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// The playable area of a level, a box around the entity's origin. Levels have at most one.
+/// It may be rotated around the Y axis, but not tilted or scaled.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct LevelBounds {
+    pub(crate) half_extents: Vec3,
+    /// Also closes the bounds at the top.
+    pub(crate) ceiling: bool,
+    /// How far inside the walls the player is asked to turn back, in meters.
+    pub(crate) warning_distance: f32,
+    /// How far below the bounds the kill plane is, in meters.
+    pub(crate) kill_depth: f32,
+}
+
+impl Default for LevelBounds {
+    fn default() -> Self {
+        Self {
+            half_extents: Vec3::new(100., 50., 100.),
+            ceiling: false,
+            warning_distance: 5.,
+            kill_depth: 2.,
+        }
+    }
+}
+
+impl LevelBounds {
+    /// Bodies below this height are caught.
+    pub(crate) fn kill_height(&self, transform: &GlobalTransform) -> f32 {
+        transform.translation().y - self.half_extents.y - self.kill_depth
+    }
+
+    /// How far `position` is horizontally from the closest wall, negative when outside,
+    /// and the horizontal direction towards that wall.
+    pub(crate) fn distance_to_edge(
+        &self,
+        transform: &GlobalTransform,
+        position: Vec3,
+    ) -> (f32, Vec3) {
+        let local = transform.affine().inverse().transform_point3(position);
+        let distance_x = self.half_extents.x - local.x.abs();
+        let distance_z = self.half_extents.z - local.z.abs();
+        let (distance, local_outward) = if distance_x < distance_z {
+            (distance_x, Vec3::X * local.x.signum())
+        } else {
+            (distance_z, Vec3::Z * local.z.signum())
+        };
+        let outward = transform
+            .affine()
+            .transform_vector3(local_outward)
+            .horizontal();
+        (distance, outward.normalize_or_zero())
+    }
+
+    /// Whether `position` is inside the bounds, ignoring their height.
+    pub(crate) fn contains_horizontally(
+        &self,
+        transform: &GlobalTransform,
+        position: Vec3,
+    ) -> bool {
+        self.distance_to_edge(transform, position).0 >= 0.
+    }
+}
+
+/// The wall colliders spawned for a [`LevelBounds`].
+#[derive(Debug, Clone, PartialEq, Component)]
+struct BoundsWalls(Vec<Entity>);
+
+/// The opacity of the "Turn back" hint.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Default)]
+struct BoundsWarning(f32);
+
+/// Sent when a character fell below the kill plane and was put back.
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub(crate) struct FellOutOfBoundsEvent {
+    pub(crate) entity: Entity,
+    /// Where the character last stood before falling, which is also where it was put back.
+    pub(crate) position: Vec3,
+}
+
+/// Where a character last stood inside the level bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct LastSafePosition(Vec3);
+
+fn build_bounds_walls(
+    mut commands: Commands,
+    bounds: Query<(Entity, &LevelBounds, Option<&BoundsWalls>), Changed<LevelBounds>>,
+) {
+    for (entity, bounds, previous) in bounds.iter() {
+        for &wall in previous.iter().flat_map(|previous| &previous.0) {
+            if let Some(wall) = commands.get_entity(wall) {
+                wall.despawn_recursive();
+            }
+        }
+        let half = bounds.half_extents;
+        let bottom = -half.y - bounds.kill_depth;
+        let top = if bounds.ceiling {
+            half.y
+        } else {
+            half.y + WALL_OVERHANG
+        };
+        let height = top - bottom;
+        let center_y = (top + bottom) / 2.;
+        let offset = half + Vec3::splat(WALL_THICKNESS / 2.);
+        let mut walls = vec![
+            (
+                Vec3::new(offset.x, center_y, 0.),
+                Vec3::new(WALL_THICKNESS, height, half.z * 2.),
+            ),
+            (
+                Vec3::new(-offset.x, center_y, 0.),
+                Vec3::new(WALL_THICKNESS, height, half.z * 2.),
+            ),
+            (
+                Vec3::new(0., center_y, offset.z),
+                Vec3::new(half.x * 2., height, WALL_THICKNESS),
+            ),
+            (
+                Vec3::new(0., center_y, -offset.z),
+                Vec3::new(half.x * 2., height, WALL_THICKNESS),
+            ),
+        ];
+        if bounds.ceiling {
+            walls.push((
+                Vec3::new(0., offset.y, 0.),
+                Vec3::new(half.x * 2., WALL_THICKNESS, half.z * 2.),
+            ));
+        }
+        let walls = walls
+            .into_iter()
+            .map(|(translation, size)| {
+                commands
+                    .spawn((
+                        Name::new("Level Bounds Wall"),
+                        TransformBundle::from_transform(Transform::from_translation(translation)),
+                        RigidBody::Static,
+                        Collider::cuboid(size.x, size.y, size.z),
+                        CollisionLayers::new(
+                            [CollisionLayer::Terrain],
+                            [CollisionLayer::Player, CollisionLayer::Character],
+                        ),
+                    ))
+                    .set_parent(entity)
+                    .id()
+            })
+            .collect();
+        commands.entity(entity).insert(BoundsWalls(walls));
+    }
+}
+
+fn warn_near_bounds(
+    time: Res<Time>,
+    bounds: Query<(&LevelBounds, &GlobalTransform)>,
+    players: Query<&GlobalTransform, With<Player>>,
+    mut cameras: Query<&mut IngameCamera>,
+    mut warning: ResMut<BoundsWarning>,
+) {
+    let closeness = bounds
+        .iter()
+        .next()
+        .zip(players.iter().next())
+        .map(|((bounds, transform), player)| {
+            let (distance, outward) = bounds.distance_to_edge(transform, player.translation());
+            let closeness = 1. - distance / bounds.warning_distance.max(1e-3);
+            (closeness.clamp(0., 1.), outward)
+        })
+        .filter(|(closeness, _)| *closeness > 0.);
+
+    for mut camera in cameras.iter_mut() {
+        let resisted_direction =
+            closeness.map(|(closeness, outward)| (outward, closeness * MAX_CAMERA_RESISTANCE));
+        if camera.resisted_direction != resisted_direction {
+            camera.resisted_direction = resisted_direction;
+        }
+    }
+    let target = if closeness.is_some() { 1. } else { 0. };
+    let max_step = HINT_FADE_SPEED * time.delta_seconds();
+    let opacity = warning.0 + (target - warning.0).clamp(-max_step, max_step);
+    if warning.0 != opacity {
+        warning.0 = opacity;
+    }
+}
+
+fn display_bounds_hint(
+    warning: Res<BoundsWarning>,
+    ui_layers: Res<UiLayers>,
+    strings: Strings,
+    mut egui_contexts: EguiContexts,
+) {
+    if warning.0 <= 0. || !ui_layers.is_visible(UiLayer::Hud) {
+        return;
+    }
+    let alpha = (warning.0.clamp(0., 1.) * 255.) as u8;
+    egui::Area::new("Level Bounds Hint")
+        .order(UiLayer::Hud.order())
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0., 80.))
+        .interactable(false)
+        .show(egui_contexts.ctx_mut(), |ui| {
+            ui.label(
+                egui::RichText::new(strings.t("bounds.turn_back"))
+                    .size(24.)
+                    .color(egui::Color32::from_white_alpha(alpha)),
+            );
+        });
+}
+
+fn catch_fallen_bodies(
+    mut commands: Commands,
+    bounds: Query<(&LevelBounds, &GlobalTransform)>,
+    mut bodies: Query<(
+        Entity,
+        &RigidBody,
+        &GlobalTransform,
+        &mut Transform,
+        Option<&mut LinearVelocity>,
+        Option<&GroundedState>,
+        Option<&mut LastSafePosition>,
+    )>,
+    mut fall_events: EventWriter<FellOutOfBoundsEvent>,
+) {
+    let Some((bounds, bounds_transform)) = bounds.iter().next() else {
+        return;
+    };
+    let kill_height = bounds.kill_height(bounds_transform);
+    for (entity, rigid_body, global_transform, mut transform, velocity, grounded, safe_position) in
+        bodies.iter_mut()
+    {
+        if !rigid_body.is_dynamic() {
+            continue;
+        }
+        let position = global_transform.translation();
+        if position.y >= kill_height {
+            let is_safe = grounded.is_some_and(|grounded| !grounded.airborne)
+                && bounds.contains_horizontally(bounds_transform, position);
+            if is_safe {
+                match safe_position {
+                    Some(mut safe_position) => safe_position.0 = position,
+                    None => {
+                        commands.entity(entity).insert(LastSafePosition(position));
+                    }
+                }
+            }
+            continue;
+        }
+        if grounded.is_none() {
+            info!("Despawning {entity:?}, which fell out of the level");
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        // Characters that never stood anywhere are put back at the center of the bounds
+        let target = safe_position.map_or(bounds_transform.translation(), |safe_position| {
+            safe_position.0
+        });
+        transform.translation = target;
+        fall_events.send(FellOutOfBoundsEvent {
+            entity,
+            position: target,
+        });
+        if let Some(mut velocity) = velocity {
+            velocity.0 = Vec3::ZERO;
+        }
+        commands.entity(entity).insert(Depenetrate::default());
+    }
+}
@@ -0,0 +1,191 @@
+use crate::{
+    level_instantiation::{environment::EnvironmentSettings, map::LevelRoot},
+    particles::emitter::ParticleEmitter,
+    GameState,
+};
+#[cfg(feature = "dev")]
+use anyhow::Context;
+use bevy::prelude::*;
+use bevy_common_assets::ron::RonAssetPlugin;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, iter};
+
+/// Where the [`LevelOverrideTable`] is written to by the dev tools, relative to the working directory.
+#[cfg(feature = "dev")]
+const OVERRIDES_PATH: &str = "assets/config/config.levels.ron";
+
+/// Applies the overrides in `assets/config/config.levels.ron` on top of the level files, see [`LevelOverrides`].
+/// Levels are authored in Blender, so the dev tools save their edits there instead of into the level files.
+/// The overrides are applied again whenever the file changes, e.g. when a dev tool saved it.
+pub(super) fn plugin(app: &mut App) {
+    app.add_plugins(RonAssetPlugin::<LevelOverrideTable>::new(&["levels.ron"]))
+        .add_systems(
+            Update,
+            apply_level_overrides.run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// The [`LevelOverrides`] of every level, by the level's id, i.e. the path of its file.
+#[derive(Debug, Clone, PartialEq, Asset, TypePath, Serialize, Deserialize, Default)]
+pub(crate) struct LevelOverrideTable(pub(crate) BTreeMap<String, LevelOverrides>);
+
+/// Components that replace the ones of the same type in a level file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct LevelOverrides {
+    /// Replaces the level's [`EnvironmentSettings`], or gives the level some if it has none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) environment: Option<EnvironmentSettings>,
+    /// Replace the [`ParticleEmitter`]s of the entities with these names in the level.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) emitters: BTreeMap<String, ParticleEmitter>,
+}
+
+impl LevelOverrideTable {
+    /// Changes the overrides of `level` in the loaded table and writes the whole table to `assets/config/config.levels.ron`.
+    /// The table is applied once it is reloaded from there.
+    #[cfg(feature = "dev")]
+    pub(crate) fn save(
+        tables: &Assets<Self>,
+        level: &str,
+        edit: impl FnOnce(&mut LevelOverrides),
+    ) -> anyhow::Result<()> {
+        let mut table = tables
+            .iter()
+            .next()
+            .map(|(_, table)| table.clone())
+            .unwrap_or_default();
+        edit(table.0.entry(level.to_string()).or_default());
+        let serialized = ron::ser::to_string_pretty(&table, default())
+            .context("Failed to serialize the level overrides")?;
+        std::fs::write(OVERRIDES_PATH, serialized)
+            .with_context(|| format!("Failed to write {OVERRIDES_PATH}"))?;
+        info!("Saved the overrides of {level} to {OVERRIDES_PATH}");
+        Ok(())
+    }
+}
+
+fn apply_level_overrides(
+    mut commands: Commands,
+    mut table_events: EventReader<AssetEvent<LevelOverrideTable>>,
+    tables: Res<Assets<LevelOverrideTable>>,
+    level_roots: Query<(Entity, Ref<LevelRoot>)>,
+    environments: Query<(Entity, Ref<EnvironmentSettings>)>,
+    emitters: Query<(Entity, &Name, Ref<ParticleEmitter>)>,
+    parents: Query<&Parent>,
+) {
+    let table_changed = table_events.read().count() > 0;
+    let Some((_, table)) = tables.iter().next() else {
+        return;
+    };
+    let overrides_of = |entity: Entity| {
+        iter::once(entity)
+            .chain(parents.iter_ancestors(entity))
+            .find_map(|entity| level_roots.get(entity).ok())
+            .and_then(|(_, root)| table.0.get(&root.0))
+    };
+
+    for (entity, settings) in environments.iter() {
+        if !table_changed && !settings.is_added() {
+            continue;
+        }
+        let Some(environment) =
+            overrides_of(entity).and_then(|overrides| overrides.environment.as_ref())
+        else {
+            continue;
+        };
+        if *settings != *environment {
+            commands.entity(entity).insert(environment.clone());
+        }
+    }
+    for (root, level) in level_roots.iter() {
+        if !table_changed && !level.is_added() {
+            continue;
+        }
+        let Some(environment) = table
+            .0
+            .get(&level.0)
+            .and_then(|overrides| overrides.environment.as_ref())
+        else {
+            continue;
+        };
+        let has_settings = environments.iter().any(|(entity, _)| {
+            iter::once(entity)
+                .chain(parents.iter_ancestors(entity))
+                .any(|ancestor| ancestor == root)
+        });
+        if !has_settings {
+            commands.entity(root).insert(environment.clone());
+        }
+    }
+    for (entity, name, emitter) in emitters.iter() {
+        if !table_changed && !emitter.is_added() {
+            continue;
+        }
+        let Some(edited) =
+            overrides_of(entity).and_then(|overrides| overrides.emitters.get(name.as_str()))
+        else {
+            continue;
+        };
+        if *emitter != *edited {
+            commands.entity(entity).insert(edited.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{particles::emitter::EmitterShape, testing::TestApp};
+
+    #[test]
+    fn overrides_replace_the_environment_and_emitters_of_their_level_only() {
+        let mut app = TestApp::new();
+        app.add_plugins(plugin);
+        let environment = EnvironmentSettings {
+            ambient_brightness: Some(20.),
+            ..default()
+        };
+        let smoke = ParticleEmitter {
+            effect: "chimney_smoke".to_string(),
+            shape: EmitterShape::SphereSurface { radius: 0.5 },
+            rate: Some(4.),
+        };
+        let overrides = LevelOverrides {
+            environment: Some(environment.clone()),
+            emitters: BTreeMap::from([("Chimney".to_string(), smoke.clone())]),
+        };
+        app.world_mut()
+            .resource_mut::<Assets<LevelOverrideTable>>()
+            .add(LevelOverrideTable(BTreeMap::from([(
+                "scenes/Village.glb".to_string(),
+                overrides,
+            )])));
+        let spawn_level = |app: &mut TestApp, id: &str| {
+            let world = app.world_mut();
+            let root = world
+                .spawn((LevelRoot(id.to_string()), SpatialBundle::default()))
+                .id();
+            let chimney = world
+                .spawn((Name::new("Chimney"), ParticleEmitter::default()))
+                .set_parent(root)
+                .id();
+            (root, chimney)
+        };
+        let (village, village_chimney) = spawn_level(&mut app, "scenes/Village.glb");
+        let (town, town_chimney) = spawn_level(&mut app, "scenes/Town.glb");
+        app.step(2);
+
+        let world = app.world();
+        assert_eq!(
+            world.get::<EnvironmentSettings>(village),
+            Some(&environment)
+        );
+        assert_eq!(world.get::<ParticleEmitter>(village_chimney), Some(&smoke));
+        assert!(world.get::<EnvironmentSettings>(town).is_none());
+        assert_eq!(
+            world.get::<ParticleEmitter>(town_chimney),
+            Some(&ParticleEmitter::default())
+        );
+    }
+}
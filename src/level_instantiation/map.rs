@@ -6,20 +6,52 @@ use crate::{
 use bevy::{gltf::Gltf, prelude::*};
 use bevy_atmosphere::prelude::*;
 use bevy_dolly::prelude::*;
+use serde::{Deserialize, Serialize};
 
 pub(super) fn plugin(app: &mut App) {
-    app.add_systems(OnEnter(GameState::Playing), spawn_level);
+    app.register_type::<CurrentLevel>()
+        .register_type::<LevelRoot>()
+        .init_resource::<CurrentLevel>()
+        .add_systems(OnEnter(GameState::Playing), spawn_level);
 }
 
-fn spawn_level(mut commands: Commands, models: Res<Assets<Gltf>>, gltf_assets: Res<GltfAssets>) {
+/// The level the player is in, identified by the path of its GLTF file.
+#[derive(Debug, Clone, PartialEq, Eq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct CurrentLevel(pub(crate) String);
+
+/// The root of a level's scene. Despawning it despawns everything the level spawned.
+#[derive(Debug, Clone, PartialEq, Eq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct LevelRoot(pub(crate) String);
+
+pub(crate) fn spawn_level_scene(commands: &mut Commands, level: &Gltf, id: &str) -> Entity {
+    commands
+        .spawn((
+            SceneBundle {
+                scene: level.scenes[0].clone(),
+                ..default()
+            },
+            Name::new("Level"),
+            LevelRoot(id.to_string()),
+        ))
+        .id()
+}
+
+fn spawn_level(
+    mut commands: Commands,
+    models: Res<Assets<Gltf>>,
+    gltf_assets: Res<GltfAssets>,
+    asset_server: Res<AssetServer>,
+    mut current_level: ResMut<CurrentLevel>,
+) {
     let gltf = models.get(&gltf_assets.level).unwrap();
-    commands.spawn((
-        SceneBundle {
-            scene: gltf.scenes[0].clone(),
-            ..default()
-        },
-        Name::new("Level"),
-    ));
+    let id = asset_server
+        .get_path(&gltf_assets.level)
+        .map(|path| path.to_string())
+        .unwrap_or_default();
+    spawn_level_scene(&mut commands, gltf, &id);
+    current_level.0 = id;
 
     commands.spawn((
         Name::new("Camera"),
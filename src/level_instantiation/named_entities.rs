@@ -0,0 +1,116 @@
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use std::fmt::Display;
+
+/// Keeps the [`NamedEntities`] resource in sync with all entities that have a [`Name`],
+/// so that dialog commands and other scripting can refer to entities by name.
+pub(crate) fn plugin(app: &mut App) {
+    app.init_resource::<NamedEntities>()
+        .add_systems(PostUpdate, update_named_entities);
+}
+
+/// Finds entities by their [`Name`] in constant time.
+/// When multiple entities share a name, a warning is logged once and the entity that got the name first is returned.
+/// [`Entity`] indices are recycled, so they say nothing about which entity is older.
+#[derive(Debug, Default, Resource)]
+pub(crate) struct NamedEntities {
+    by_name: HashMap<String, Vec<Entity>>,
+    names: HashMap<Entity, String>,
+    reported_duplicates: HashSet<String>,
+}
+
+impl NamedEntities {
+    pub(crate) fn get(&self, name: &str) -> Option<Entity> {
+        self.by_name
+            .get(name)
+            .and_then(|entities| entities.first().copied())
+    }
+
+    pub(crate) fn name_of(&self, entity: Entity) -> Option<&str> {
+        self.names.get(&entity).map(String::as_str)
+    }
+
+    fn insert(&mut self, entity: Entity, name: &str) {
+        // Setting the same name again keeps the entity's place among the others with that name
+        if self.name_of(entity) == Some(name) {
+            return;
+        }
+        self.remove(entity);
+        let entities = self.by_name.entry(name.to_string()).or_default();
+        entities.push(entity);
+        if entities.len() > 1 && self.reported_duplicates.insert(name.to_string()) {
+            warn!(
+                "Multiple entities are named \"{name}\". Looking them up by name will return {:?}",
+                entities[0]
+            );
+        }
+        self.names.insert(entity, name.to_string());
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        let Some(name) = self.names.remove(&entity) else {
+            return;
+        };
+        if let Some(entities) = self.by_name.get_mut(&name) {
+            entities.retain(|&other| other != entity);
+            if entities.is_empty() {
+                self.by_name.remove(&name);
+            }
+        }
+    }
+}
+
+/// Convenience wrapper around [`NamedEntities`] for systems that log when a name cannot be resolved.
+#[derive(SystemParam)]
+pub(crate) struct EntityNames<'w> {
+    named_entities: Res<'w, NamedEntities>,
+}
+
+impl EntityNames<'_> {
+    pub(crate) fn get(&self, name: &str) -> Option<Entity> {
+        self.named_entities.get(name)
+    }
+
+    /// Like [`EntityNames::get`], but logs an error that mentions `context` when no entity is found.
+    pub(crate) fn get_or_report(&self, name: &str, context: impl Display) -> Option<Entity> {
+        let entity = self.get(name);
+        if entity.is_none() {
+            error!("{context}: no entity named \"{name}\"");
+        }
+        entity
+    }
+}
+
+fn update_named_entities(
+    mut named_entities: ResMut<NamedEntities>,
+    names: Query<(Entity, &Name), Changed<Name>>,
+    mut removed_names: RemovedComponents<Name>,
+) {
+    for entity in removed_names.read() {
+        named_entities.remove(entity);
+    }
+    for (entity, name) in &names {
+        named_entities.insert(entity, name.as_str());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_names_resolve_to_the_entity_named_first() {
+        let [first, second] = [Entity::from_raw(7), Entity::from_raw(3)];
+        let mut named_entities = NamedEntities::default();
+        named_entities.insert(first, "Guard");
+        named_entities.insert(second, "Guard");
+        named_entities.insert(first, "Guard");
+        assert_eq!(named_entities.get("Guard"), Some(first));
+
+        named_entities.remove(first);
+        assert_eq!(named_entities.get("Guard"), Some(second));
+    }
+}
@@ -1,11 +1,20 @@
 use bevy::prelude::*;
 
-pub(crate) use self::{ground::Ground, npc::Npc, player::Player};
+pub(crate) use self::{
+    blender_properties::{parse_property, PropertyError},
+    collider::{AwaitingColliders, Collider as ColliderMarker},
+    ground::{Ground, GroundSurface, SurfaceGrip},
+    music_region::MusicRegion,
+    npc::Npc,
+    player::Player,
+};
 
+mod blender_properties;
 mod collider;
 mod grass;
 mod ground;
 mod hidden;
+mod music_region;
 mod npc;
 mod orb;
 pub(crate) mod player;
@@ -16,6 +25,7 @@ mod util;
 /// These marker components are then used to spawn the rest of the components or modify other existing components in Bevy through code.
 pub(super) fn plugin(app: &mut App) {
     app.add_plugins((
+        blender_properties::plugin,
         grass::plugin,
         ground::plugin,
         orb::plugin,
@@ -23,5 +33,6 @@ pub(super) fn plugin(app: &mut App) {
         npc::plugin,
         hidden::plugin,
         collider::plugin,
+        music_region::plugin,
     ));
 }
@@ -0,0 +1,248 @@
+use crate::{
+    level_instantiation::on_spawn::{
+        collider::{Collider, ColliderShape},
+        player,
+    },
+    world_interaction::{
+        dialog::{DialogContextVariable, YarnNode},
+        interaction_sensor::{InteractionSensor, SensorShape},
+    },
+    GameState,
+};
+use bevy::{gltf::GltfExtras, prelude::*};
+use std::fmt;
+
+/// Prefix of the custom properties in [`BlenderProperty`].
+pub(crate) const PREFIX: &str = "foxtrot:";
+
+/// Turns custom properties set on objects in Blender into components, so that level artists can mark up levels
+/// without writing component payloads. Unlike components set through the Bevy Components Addon,
+/// these are plain Blender custom properties whose names start with `foxtrot:`. See [`BlenderProperty`] for the vocabulary.
+/// Unknown and malformed properties are skipped here and reported once per file by the level validation.
+/// `assets/scenes/tests/blender_properties.gltf` uses every property.
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        apply_blender_properties.run_if(in_state(GameState::Playing)),
+    );
+}
+
+/// The custom properties understood by [`plugin`]:
+/// - `foxtrot:dialog_node = "node_name"`: talking to the object starts this yarn node, like a [`YarnNode`].
+/// - `foxtrot:dialog_context = "subject"`: the object's name is available in its dialog as the yarn variable `$subject`,
+///   like a [`DialogContextVariable`].
+/// - `foxtrot:collider = "convex"` or `"trimesh"`: generates static colliders from the meshes of the object.
+///   Convex hulls are cheaper, trimeshes follow concave shapes.
+/// - `foxtrot:sensor_radius = 1.5`: adds a sensor of this radius in meters, within which the player can interact with the object.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum BlenderProperty {
+    DialogNode(String),
+    DialogContext(String),
+    Collider(ColliderShape),
+    SensorRadius(f32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum PropertyError {
+    UnknownKey(String),
+    InvalidValue {
+        key: String,
+        expected: &'static str,
+        value: String,
+    },
+}
+
+impl fmt::Display for PropertyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PropertyError::UnknownKey(key) => write!(f, "unknown custom property \"{key}\""),
+            PropertyError::InvalidValue {
+                key,
+                expected,
+                value,
+            } => write!(
+                f,
+                "custom property \"{key}\" should be {expected}, but is {value}"
+            ),
+        }
+    }
+}
+
+/// Returns `None` for keys without the `foxtrot:` prefix, since those are components.
+pub(crate) fn parse_property(
+    key: &str,
+    value: &serde_json::Value,
+) -> Option<Result<BlenderProperty, PropertyError>> {
+    let name = key.strip_prefix(PREFIX)?;
+    let invalid = |expected| PropertyError::InvalidValue {
+        key: key.to_string(),
+        expected,
+        value: value.to_string(),
+    };
+    let property = match name {
+        "dialog_node" => value
+            .as_str()
+            .filter(|node| !node.is_empty())
+            .map(|node| BlenderProperty::DialogNode(node.to_string()))
+            .ok_or_else(|| invalid("the name of a yarn node")),
+        "dialog_context" => value
+            .as_str()
+            .filter(|variable| !variable.trim_start_matches('$').is_empty())
+            .map(|variable| BlenderProperty::DialogContext(variable.to_string()))
+            .ok_or_else(|| invalid("the name of a yarn variable")),
+        "collider" => match value.as_str() {
+            Some("convex") => Ok(BlenderProperty::Collider(ColliderShape::ConvexHull)),
+            Some("trimesh") => Ok(BlenderProperty::Collider(ColliderShape::Trimesh)),
+            _ => Err(invalid("\"convex\" or \"trimesh\"")),
+        },
+        "sensor_radius" => value
+            .as_f64()
+            .map(|radius| radius as f32)
+            .filter(|radius| *radius > 0.)
+            .map(BlenderProperty::SensorRadius)
+            .ok_or_else(|| invalid("a positive number")),
+        _ => Err(PropertyError::UnknownKey(key.to_string())),
+    };
+    Some(property)
+}
+
+fn apply_blender_properties(
+    mut commands: Commands,
+    extras: Query<(Entity, &GltfExtras), Added<GltfExtras>>,
+) {
+    for (entity, extras) in extras.iter() {
+        let Ok(properties) =
+            serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&extras.value)
+        else {
+            continue;
+        };
+        for (key, value) in &properties {
+            let Some(Ok(property)) = parse_property(key, value) else {
+                continue;
+            };
+            match property {
+                BlenderProperty::DialogNode(node) => {
+                    commands.entity(entity).insert(YarnNode(node));
+                }
+                BlenderProperty::DialogContext(variable) => {
+                    commands
+                        .entity(entity)
+                        .insert(DialogContextVariable(variable));
+                }
+                BlenderProperty::Collider(shape) => {
+                    commands.entity(entity).insert((Collider, shape));
+                }
+                BlenderProperty::SensorRadius(radius) => {
+                    commands
+                        .entity(entity)
+                        .insert(InteractionSensor::new(SensorShape::Cylinder {
+                            radius,
+                            height: player::HEIGHT,
+                        }));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+    use bevy::utils::HashMap;
+
+    const FIXTURE: &str = include_str!("../../../assets/scenes/tests/blender_properties.gltf");
+
+    /// Spawns the nodes of the fixture with their custom properties, the way the glTF loader hands them over.
+    fn spawn_fixture(app: &mut TestApp) -> HashMap<String, Entity> {
+        let gltf: serde_json::Value = serde_json::from_str(FIXTURE).unwrap();
+        gltf["nodes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|node| {
+                let name = node["name"].as_str().unwrap().to_string();
+                let extras = GltfExtras {
+                    value: node["extras"].to_string(),
+                };
+                let entity = app
+                    .world_mut()
+                    .spawn((Name::new(name.clone()), extras))
+                    .id();
+                (name, entity)
+            })
+            .collect()
+    }
+
+    fn sensor(radius: f32) -> InteractionSensor {
+        InteractionSensor::new(SensorShape::Cylinder {
+            radius,
+            height: player::HEIGHT,
+        })
+    }
+
+    #[test]
+    fn fixture_properties_become_components() {
+        let mut app = TestApp::new();
+        app.add_plugins(plugin);
+        let nodes = spawn_fixture(&mut app);
+        app.step(1);
+
+        let entity = |name: &str| app.world().entity(nodes[name]);
+        let blacksmith = entity("Blacksmith");
+        assert_eq!(
+            blacksmith.get::<YarnNode>(),
+            Some(&YarnNode("Follower".to_string()))
+        );
+        assert_eq!(blacksmith.get::<InteractionSensor>(), Some(&sensor(1.5)));
+        assert!(blacksmith.get::<DialogContextVariable>().is_none());
+
+        let hammer = entity("Hammer");
+        assert_eq!(
+            hammer.get::<YarnNode>(),
+            Some(&YarnNode("Follower".to_string()))
+        );
+        assert_eq!(
+            hammer.get::<DialogContextVariable>(),
+            Some(&DialogContextVariable("subject".to_string()))
+        );
+        assert_eq!(hammer.get::<InteractionSensor>(), Some(&sensor(1.)));
+
+        for (name, shape) in [
+            ("Floor", ColliderShape::Trimesh),
+            ("Crate", ColliderShape::ConvexHull),
+        ] {
+            let node = entity(name);
+            assert!(node.contains::<Collider>(), "{name} has no collider");
+            assert_eq!(node.get::<ColliderShape>(), Some(&shape));
+        }
+    }
+
+    #[test]
+    fn unknown_and_malformed_fixture_properties_are_skipped() {
+        let mut app = TestApp::new();
+        app.add_plugins(plugin);
+        let nodes = spawn_fixture(&mut app);
+        app.step(1);
+
+        for name in ["Misspelled", "Malformed"] {
+            let node = app.world().entity(nodes[name]);
+            assert!(!node.contains::<YarnNode>(), "{name} got a dialog");
+            assert!(!node.contains::<InteractionSensor>(), "{name} got a sensor");
+        }
+        assert_eq!(
+            parse_property("foxtrot:dialog_nod", &"Follower".into()),
+            Some(Err(PropertyError::UnknownKey(
+                "foxtrot:dialog_nod".to_string()
+            )))
+        );
+        assert_eq!(
+            parse_property("foxtrot:sensor_radius", &"wide".into()),
+            Some(Err(PropertyError::InvalidValue {
+                key: "foxtrot:sensor_radius".to_string(),
+                expected: "a positive number",
+                value: "\"wide\"".to_string(),
+            }))
+        );
+    }
+}
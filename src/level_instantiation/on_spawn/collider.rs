@@ -1,52 +1,256 @@
 use crate::{movement::physics::CollisionLayer, GameState};
-use anyhow::Context;
-use bevy::prelude::*;
-use bevy_mod_sysfail::prelude::*;
+use bevy::{prelude::*, tasks::AsyncComputeTaskPool, utils::HashMap};
 use bevy_xpbd_3d::prelude::{Collider as XpbdCollider, *};
 use oxidized_navigation::NavMeshAffector;
 use serde::{Deserialize, Serialize};
-use std::iter;
+use std::{
+    collections::VecDeque,
+    iter,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+};
 
 #[derive(Debug, Clone, Eq, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
 #[reflect(Component, Serialize, Deserialize)]
-struct Collider;
+pub(crate) struct Collider;
 
+/// The shape generated for the meshes of a [`Collider`]. Defaults to [`ColliderShape::ConvexHull`].
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Hash, Component, Reflect, Serialize, Deserialize, Default,
+)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) enum ColliderShape {
+    #[default]
+    ConvexHull,
+    /// Follows concave shapes, but is more expensive.
+    Trimesh,
+}
+
+/// Generating colliders from large meshes takes long, so it happens on the [`AsyncComputeTaskPool`] instead of stalling a frame.
+/// Once a scene with [`Collider`]s has spawned, every distinct mesh below them is baked once on a background task,
+/// and the results come back through a channel. At most [`ColliderBakeBudget::max_per_frame`] colliders are attached per frame.
+/// Until all of its colliders are attached, an entity is [`AwaitingColliders`].
 pub(super) fn plugin(app: &mut App) {
     app.register_type::<Collider>()
-        .add_systems(Update, spawn.run_if(in_state(GameState::Playing)));
+        .register_type::<ColliderShape>()
+        .init_resource::<ColliderBakeBudget>()
+        .init_resource::<ColliderBakes>()
+        .add_systems(
+            Update,
+            (queue_collider_bakes, attach_baked_colliders)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Present while some of the colliders of an entity with a [`Collider`] are still being baked.
+/// Nothing collides with the entity's meshes until then, so gameplay that relies on them, e.g. placing the player, should wait.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub(crate) struct AwaitingColliders {
+    /// How many meshes have no collider yet.
+    pub(crate) pending: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource)]
+pub(crate) struct ColliderBakeBudget {
+    pub(crate) max_per_frame: usize,
+}
+
+impl Default for ColliderBakeBudget {
+    fn default() -> Self {
+        Self { max_per_frame: 16 }
+    }
 }
 
-#[sysfail(Log<anyhow::Error, Error>)]
-fn spawn(
-    collider_marker: Query<Entity, With<Collider>>,
+/// A collider baked on a background task, with the meshes that use it and the entities awaiting them.
+struct BakedCollider {
+    collider: Option<XpbdCollider>,
+    targets: Vec<(Entity, Entity)>,
+}
+
+#[derive(Resource)]
+struct ColliderBakes {
+    sender: Sender<BakedCollider>,
+    /// Only read on the main thread, but resources need to be shareable between threads.
+    receiver: Mutex<Receiver<BakedCollider>>,
+    /// Received colliders that did not fit into the budget yet, with the mesh and the entity awaiting it.
+    ready: VecDeque<(Entity, Entity, Option<XpbdCollider>)>,
+}
+
+impl Default for ColliderBakes {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender,
+            receiver: Mutex::new(receiver),
+            ready: default(),
+        }
+    }
+}
+
+fn queue_collider_bakes(
+    collider_marker: Query<(Entity, Option<&ColliderShape>), With<Collider>>,
     mut commands: Commands,
     children: Query<&Children>,
     meshes: Res<Assets<Mesh>>,
     mesh_handles: Query<&Handle<Mesh>>,
+    bakes: Res<ColliderBakes>,
 ) {
     #[cfg(feature = "tracing")]
-    let _span = info_span!("read_colliders").entered();
-    for parent in collider_marker.iter() {
+    let _span = info_span!("queue_collider_bakes").entered();
+    // Scenes often use the same mesh many times, e.g. for a row of pillars
+    let mut targets_by_mesh: HashMap<(AssetId<Mesh>, ColliderShape), Vec<(Entity, Entity)>> =
+        default();
+    for (parent, shape) in collider_marker.iter() {
+        let shape = shape.copied().unwrap_or_default();
+        let mut pending = 0;
         for child in iter::once(parent).chain(children.iter_descendants(parent)) {
             let Ok(mesh_handle) = mesh_handles.get(child) else {
                 continue;
             };
-            // Unwrap cannot fail: we already load all the meshes at startup.
-            let mesh = meshes.get(mesh_handle).unwrap();
-            let collider = XpbdCollider::convex_hull_from_mesh(mesh)
-                .context("Failed to create collider from mesh")?;
-            commands.entity(child).insert((
-                collider,
-                CollisionLayers::new(
-                    [CollisionLayer::Terrain, CollisionLayer::CameraObstacle],
-                    [CollisionLayer::Character],
-                ),
-                NavMeshAffector,
-            ));
+            targets_by_mesh
+                .entry((mesh_handle.id(), shape))
+                .or_default()
+                .push((child, parent));
+            pending += 1;
         }
-        commands
-            .entity(parent)
-            .remove::<Collider>()
+        let mut entity_commands = commands.entity(parent);
+        entity_commands
+            .remove::<(Collider, ColliderShape)>()
             .insert(RigidBody::Static);
+        if pending > 0 {
+            entity_commands.insert(AwaitingColliders { pending });
+        }
+    }
+
+    let task_pool = AsyncComputeTaskPool::get();
+    for ((mesh, shape), targets) in targets_by_mesh {
+        // All meshes are loaded at startup
+        let mesh = meshes.get(mesh).cloned();
+        let sender = bakes.sender.clone();
+        task_pool
+            .spawn(async move {
+                let collider = mesh.and_then(|mesh| match shape {
+                    ColliderShape::ConvexHull => XpbdCollider::convex_hull_from_mesh(&mesh),
+                    ColliderShape::Trimesh => XpbdCollider::trimesh_from_mesh(&mesh),
+                });
+                // Sending only fails when the app is gone
+                let _ = sender.send(BakedCollider { collider, targets });
+            })
+            .detach();
+    }
+}
+
+fn attach_baked_colliders(
+    mut commands: Commands,
+    mut bakes: ResMut<ColliderBakes>,
+    budget: Res<ColliderBakeBudget>,
+    mut awaiting: Query<&mut AwaitingColliders>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("attach_baked_colliders").entered();
+    let bakes = bakes.as_mut();
+    if let Ok(receiver) = bakes.receiver.lock() {
+        for baked in receiver.try_iter() {
+            bakes.ready.extend(
+                baked
+                    .targets
+                    .into_iter()
+                    .map(|(mesh, owner)| (mesh, owner, baked.collider.clone())),
+            );
+        }
+    }
+
+    let count = budget.max_per_frame.min(bakes.ready.len());
+    for (mesh, owner, collider) in bakes.ready.drain(..count) {
+        // The level may have been left while baking
+        if let Some(mut mesh_commands) = commands.get_entity(mesh) {
+            match collider {
+                Some(collider) => {
+                    mesh_commands.insert((
+                        collider,
+                        CollisionLayers::new(
+                            [CollisionLayer::Terrain, CollisionLayer::CameraObstacle],
+                            [CollisionLayer::Character],
+                        ),
+                        NavMeshAffector,
+                    ));
+                }
+                None => error!("Failed to create collider from the mesh of {mesh:?}"),
+            }
+        }
+        let Ok(mut awaiting_colliders) = awaiting.get_mut(owner) else {
+            continue;
+        };
+        awaiting_colliders.pending = awaiting_colliders.pending.saturating_sub(1);
+        if awaiting_colliders.pending == 0 {
+            commands.entity(owner).remove::<AwaitingColliders>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+    use bevy::utils::{Duration, Instant};
+
+    #[test]
+    fn baking_many_trimesh_colliders_stays_within_the_budget() {
+        let mut app = TestApp::new();
+        app.add_plugins(plugin);
+        let max_per_frame = app.resource::<ColliderBakeBudget>().max_per_frame;
+        // Every object gets its own mesh, so that nothing is shared between bakes
+        let objects: Vec<_> = (0..50)
+            .map(|index| {
+                let mesh = app
+                    .world_mut()
+                    .resource_mut::<Assets<Mesh>>()
+                    .add(Sphere::new(1. + index as f32 * 0.01).mesh().ico(4).unwrap());
+                app.world_mut()
+                    .spawn((
+                        Collider,
+                        ColliderShape::Trimesh,
+                        SpatialBundle::from_transform(Transform::from_xyz(
+                            index as f32 * 3.,
+                            0.,
+                            0.,
+                        )),
+                        mesh,
+                    ))
+                    .id()
+            })
+            .collect();
+
+        let attached = |app: &TestApp| {
+            objects
+                .iter()
+                .filter(|&&object| app.world().get::<XpbdCollider>(object).is_some())
+                .count()
+        };
+        // Only guards against a hang, the frames themselves are not timed
+        let timeout = Instant::now() + Duration::from_secs(60);
+        let mut frames = 0;
+        let mut previously_attached = 0;
+        while attached(&app) < objects.len() {
+            assert!(Instant::now() < timeout, "Baking the colliders timed out");
+            app.step(1);
+            frames += 1;
+            let now_attached = attached(&app);
+            assert!(
+                now_attached - previously_attached <= max_per_frame,
+                "{} colliders were attached in one frame",
+                now_attached - previously_attached
+            );
+            let awaiting = objects
+                .iter()
+                .filter(|&&object| app.world().get::<AwaitingColliders>(object).is_some())
+                .count();
+            assert_eq!(awaiting, objects.len() - now_attached);
+            previously_attached = now_attached;
+        }
+        assert!(frames >= objects.len().div_ceil(max_per_frame));
     }
 }
@@ -1,5 +1,6 @@
 use crate::{
-    file_system_interaction::asset_loading::GrassAssets, level_instantiation::on_spawn::Ground,
+    file_system_interaction::{asset_loading::GrassAssets, config::GameConfig},
+    level_instantiation::on_spawn::Ground,
     GameState,
 };
 use bevy::{app::App, prelude::*, render::primitives::Aabb};
@@ -9,11 +10,31 @@ use warbler_grass::{
     prelude::*,
 };
 
+/// The density of a grass field at [`GraphicsPreset::High`](crate::file_system_interaction::config::GraphicsPreset::High).
+const FULL_DENSITY: f32 = 5.;
+/// How many layers of grass make up a field at full density. Lower densities despawn the upper layers.
+const LAYERS: usize = 10;
+
 pub(super) fn plugin(app: &mut App) {
-    app.add_plugins(WarblersPlugin)
-        .add_systems(Update, spawn.run_if(in_state(GameState::Playing)));
+    app.add_plugins(WarblersPlugin).add_systems(
+        Update,
+        (spawn, thin_grass)
+            .chain()
+            .run_if(in_state(GameState::Playing).and_then(resource_exists::<GameConfig>)),
+    );
+}
+
+/// A grass field spawned on the ground, made up of [`GrassLayer`]s.
+#[derive(Debug, Clone, PartialEq, Component)]
+struct GrassField {
+    density_map: Handle<Image>,
+    aabb: Aabb,
 }
 
+/// One of the [`LAYERS`] of a [`GrassField`], each with its own blades.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+struct GrassLayer(usize);
+
 /// Spawns the grass using the ground as a base
 fn spawn(
     mut commands: Commands,
@@ -21,21 +42,76 @@ fn spawn(
     grass_assets: Res<GrassAssets>,
 ) {
     for transform in ground.iter() {
-        let density_map = DensityMap::new(grass_assets.density_map.clone(), 5.);
         let offset = Vec3::new(transform.scale.x, 0., transform.scale.z);
         let aabb = Aabb::from_min_max(-offset, offset);
         let grass_transform =
             Transform::from_translation(-offset + transform.translation + Vec3::X);
-        commands.spawn(WarblersBundle {
-            density_map,
+        // The layers are spawned by `thin_grass`
+        commands.spawn((
+            Name::new("Grass"),
+            GrassField {
+                density_map: grass_assets.density_map.clone(),
+                aabb,
+            },
+            SpatialBundle::from_transform(grass_transform),
+        ));
+    }
+}
+
+/// Keeps as many [`GrassLayer`]s of every field as the configured [`Graphics::grass_density`](crate::file_system_interaction::config::Graphics::grass_density)
+/// asks for, instead of respawning the fields. Lowering the density despawns the upper layers and raising it
+/// spawns them again, so the blades that stay are always the same.
+fn thin_grass(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    fields: Query<(Entity, &GrassField, Option<&Children>)>,
+    added_fields: Query<(), Added<GrassField>>,
+    layers: Query<&GrassLayer>,
+) {
+    if !config.is_changed() && added_fields.is_empty() {
+        return;
+    }
+    let density = config.graphics.resolve().grass_density.clamp(0., 1.);
+    let layer_count = (LAYERS as f32 * density).round() as usize;
+    for (entity, field, children) in fields.iter() {
+        let mut present = [false; LAYERS];
+        for &child in children.into_iter().flatten() {
+            let Ok(&GrassLayer(index)) = layers.get(child) else {
+                continue;
+            };
+            if index < layer_count {
+                present[index] = true;
+            } else {
+                commands.entity(child).despawn_recursive();
+            }
+        }
+        commands.entity(entity).with_children(|parent| {
+            for index in (0..layer_count).filter(|&index| !present[index]) {
+                parent.spawn(layer_bundle(field, index));
+            }
+        });
+    }
+}
+
+fn layer_bundle(field: &GrassField, index: usize) -> impl Bundle {
+    let density = FULL_DENSITY / LAYERS as f32;
+    // Shifts every layer by a different fraction of the distance between its blades,
+    // so that the blades of different layers do not grow in the same spots
+    let spacing = density.sqrt().recip();
+    let shift = index as f32 * 0.618_034;
+    let offset = Vec3::new(shift.fract(), 0., (shift * 0.618_034).fract()) * spacing;
+    (
+        GrassLayer(index),
+        WarblersBundle {
+            density_map: DensityMap::new(field.density_map.clone(), density),
             grass_color: GrassColor {
                 main_color: Color::rgb(0.3, 0.6, 0.0),
                 bottom_color: Color::rgb(0.2, 0.1, 0.),
             },
-            aabb,
-            spatial: SpatialBundle::from_transform(grass_transform),
+            aabb: field.aabb,
+            spatial: SpatialBundle::from_transform(Transform::from_translation(offset)),
             height: WarblerHeight::Uniform(1.2),
             ..default()
-        });
-    }
+        },
+    )
 }
@@ -6,8 +6,43 @@ use serde::{Deserialize, Serialize};
 #[reflect(Component, Serialize, Deserialize)]
 pub(crate) struct Ground;
 
+/// What the ground is made of. Soft ground keeps footprints.
+/// Can be put on a collider or any of its ancestors.
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Component, Reflect, Serialize, Deserialize, Default,
+)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) enum GroundSurface {
+    #[default]
+    Hard,
+    Sand,
+    Snow,
+    Mud,
+}
+
+impl GroundSurface {
+    pub(crate) fn is_soft(self) -> bool {
+        !matches!(self, Self::Hard)
+    }
+}
+
+/// How well characters on this collider can speed up and slow down, as a factor of their usual
+/// [`Walk::acceleration`](crate::movement::character_controller::Walk::acceleration) and deceleration.
+/// Colliders without one have full grip. Weather makes wet surfaces slippery with it.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+pub(crate) struct SurfaceGrip(pub(crate) f32);
+
+impl Default for SurfaceGrip {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
 pub(super) fn plugin(app: &mut App) {
     app.register_type::<Ground>()
+        .register_type::<GroundSurface>()
+        .register_type::<SurfaceGrip>()
         .add_systems(Update, spawn.run_if(in_state(GameState::Playing)));
 }
 
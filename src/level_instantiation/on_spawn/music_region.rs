@@ -0,0 +1,32 @@
+use crate::{movement::physics::CollisionLayer, GameState};
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A box-shaped volume that selects which music track plays while the player is inside.
+/// When volumes overlap, the one with the highest priority wins.
+/// The track names are defined in `assets/config/config.music.ron`.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct MusicRegion {
+    pub(crate) track: String,
+    pub(crate) half_extents: Vec3,
+    pub(crate) priority: i32,
+}
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<MusicRegion>()
+        .add_systems(Update, spawn.run_if(in_state(GameState::Playing)));
+}
+
+fn spawn(regions: Query<(Entity, &MusicRegion), Added<MusicRegion>>, mut commands: Commands) {
+    for (entity, region) in regions.iter() {
+        let size = region.half_extents * 2.;
+        commands.entity(entity).insert((
+            Collider::cuboid(size.x, size.y, size.z),
+            CollisionLayers::new([CollisionLayer::Sensor], [CollisionLayer::Player]),
+            Sensor,
+            CollidingEntities::default(),
+        ));
+    }
+}
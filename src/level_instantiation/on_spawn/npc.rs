@@ -1,10 +1,10 @@
 use crate::{
     level_instantiation::on_spawn::player,
-    movement::{character_controller::CharacterControllerBundle, physics::CollisionLayer},
+    movement::character_controller::{CharacterControllerBundle, MovementProfile},
+    world_interaction::interaction_sensor::{InteractionSensor, SensorShape},
     GameState,
 };
 use bevy::prelude::*;
-use bevy_xpbd_3d::prelude::*;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Component, Clone, PartialEq, Default, Reflect, Serialize, Deserialize)]
@@ -16,22 +16,23 @@ pub(super) fn plugin(app: &mut App) {
         .add_systems(Update, spawn.run_if(in_state(GameState::Playing)));
 }
 
-fn spawn(follower: Query<(Entity, &Transform), Added<Npc>>, mut commands: Commands) {
-    for (entity, transform) in follower.iter() {
-        commands
-            .entity(entity)
-            .insert((CharacterControllerBundle::capsule(
-                player::HEIGHT,
-                player::RADIUS,
-                transform.scale.y,
-            ),))
-            .with_children(|parent| {
-                parent.spawn((
-                    Name::new("NPC Dialog Collider"),
-                    Collider::cylinder(player::HEIGHT / 2., player::RADIUS * 5.),
-                    CollisionLayers::new([CollisionLayer::Sensor], [CollisionLayer::Player]),
-                    Sensor,
-                ));
-            });
+fn spawn(
+    follower: Query<(Entity, &Transform, Has<InteractionSensor>), Added<Npc>>,
+    mut commands: Commands,
+) {
+    for (entity, transform, has_sensor) in follower.iter() {
+        commands.entity(entity).insert((
+            CharacterControllerBundle::capsule(player::HEIGHT, player::RADIUS, transform.scale.y),
+            MovementProfile::new("npc"),
+        ));
+        // Levels may give an NPC its own sensor, e.g. a cone for a shopkeeper
+        if !has_sensor {
+            commands
+                .entity(entity)
+                .insert(InteractionSensor::new(SensorShape::Cylinder {
+                    radius: player::RADIUS * 5.,
+                    height: player::HEIGHT / 2.,
+                }));
+        }
     }
 }
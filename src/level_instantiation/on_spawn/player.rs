@@ -1,5 +1,10 @@
 use crate::{
-    movement::{character_controller::CharacterControllerBundle, physics::CollisionLayer},
+    movement::{
+        character_controller::{
+            CharacterControllerBundle, LedgeGrab, MovementProfile, PushPriority, Stamina,
+        },
+        physics::CollisionLayer,
+    },
     particles,
     player_control::actions::{
         create_player_action_input_manager_bundle, create_ui_action_input_manager_bundle,
@@ -30,11 +35,16 @@ fn spawn(
     for (entity, transform) in player.iter() {
         let mut controller = CharacterControllerBundle::capsule(HEIGHT, RADIUS, transform.scale.y);
         controller.collision_layers.memberships |= CollisionLayer::Player;
+        // NPCs make way for the player instead of shoving it around
+        controller.push_priority = PushPriority(4.);
 
         commands
             .entity(entity)
             .insert((
                 controller,
+                LedgeGrab::default(),
+                Stamina::default(),
+                MovementProfile::new("player"),
                 create_player_action_input_manager_bundle(),
                 create_ui_action_input_manager_bundle(),
             ))
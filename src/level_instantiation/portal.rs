@@ -0,0 +1,535 @@
+use crate::{
+    level_instantiation::{
+        map::{spawn_level_scene, CurrentLevel, LevelRoot},
+        named_entities::NamedEntities,
+        on_spawn::{AwaitingColliders, ColliderMarker, Player},
+        prop_persistence::{PropPose, RestoredPose},
+        stable_id::{StableId, StableIdRegistry},
+    },
+    movement::{character_controller::Depenetrate, navigation::Companion},
+    player_control::screen_effects::{ScreenEffects, ScreenTransitionEvent},
+    util::math_trait_ext::{F32Ext, Vec3Ext},
+    world_interaction::{destructible::BrokenSegment, lamp::LampOverride, readable::AlreadyRead},
+    GameState,
+};
+use bevy::{
+    ecs::system::SystemParam,
+    gltf::Gltf,
+    prelude::*,
+    scene::SceneInstance,
+    utils::{HashMap, HashSet},
+};
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How long the player has after arriving before portals can be entered again,
+/// so that arriving next to the way back does not immediately travel back.
+const ARRIVAL_COOLDOWN: f32 = 1.;
+/// How long to wait for the target spawn point to appear in the new level and for its colliders to be baked.
+const SPAWN_POINT_TIMEOUT: f32 = 10.;
+/// How long the screen takes to fade out before travelling and to fade in again after arriving.
+const TRAVEL_FADE_DURATION: f32 = 0.3;
+
+/// Streams levels through [`Portal`]s. Approaching a portal loads its target level in the background.
+/// Entering it despawns the current level, spawns the target level and moves the player to the target spawn point,
+/// which is any entity with that [`Name`]. [`Companion`]s travel along with the player. Entities marked with [`LevelPersistent`] that were despawned,
+/// e.g. consumed pickups, stay despawned when coming back. So do the [`AlreadyRead`] state of readables, [`LampOverride`]s
+/// and [`BrokenSegment`]s of fences and railings. Props that were moved are put back where they were left,
+/// see [`prop_persistence`](crate::level_instantiation::prop_persistence). All of them are remembered by [`StableId`].
+/// The levels are exchanged behind a screen fade, so the player does not see the old level vanish.
+///
+/// Other systems can make the player travel with a [`TravelEvent`], e.g. when loading a save.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Portal>()
+        .register_type::<LevelPersistent>()
+        .register_type::<LevelStateCache>()
+        .init_resource::<LevelStateCache>()
+        .init_resource::<PreloadedLevels>()
+        .init_resource::<Travel>()
+        .add_event::<TravelEvent>()
+        .add_systems(
+            Update,
+            (
+                preload_portal_targets,
+                start_requested_travel,
+                enter_portals,
+                track_level_state,
+                advance_travel.in_set(TravelSystemSet),
+                fade_screen_for_travel,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Moves the player between levels. Systems that track the state of the current level run before it,
+/// so that they see the level that is left before it is despawned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemSet)]
+pub(crate) struct TravelSystemSet;
+
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct Portal {
+    /// Path of the target level's GLTF file, e.g. `"scenes/Cave.glb"`.
+    pub(crate) target_level: String,
+    /// Name of the entity in the target level the player arrives at.
+    pub(crate) target_spawn_point: String,
+    /// The target level starts loading when the player is this close.
+    pub(crate) preload_radius: f32,
+    /// The player travels when this close.
+    pub(crate) entry_radius: f32,
+}
+
+impl Default for Portal {
+    fn default() -> Self {
+        Self {
+            target_level: String::new(),
+            target_spawn_point: String::new(),
+            preload_radius: 20.,
+            entry_radius: 1.,
+        }
+    }
+}
+
+/// Marks an entity whose despawning is remembered by its level. Needs a [`StableId`] or a unique [`Name`] within the level.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Component, Reflect, Serialize, Deserialize, Default,
+)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct LevelPersistent;
+
+/// The dynamic state of levels the player has visited, by level id. Stored in saves.
+#[derive(Debug, Clone, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct LevelStateCache(pub(crate) HashMap<String, LevelState>);
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) struct LevelState {
+    /// [`LevelPersistent`] entities that were despawned.
+    pub(crate) despawned: HashSet<StableId>,
+    /// Readables that were read.
+    pub(crate) read: HashSet<StableId>,
+    /// Lamps that were switched on or off regardless of the time of day.
+    #[serde(default)]
+    pub(crate) lamp_overrides: HashMap<StableId, bool>,
+    /// Segments of fences and railings that were broken and not repaired.
+    #[serde(default)]
+    pub(crate) broken: HashSet<StableId>,
+    /// Props that were left away from where the level placed them. Props that were destroyed are in `despawned` instead.
+    #[serde(default)]
+    pub(crate) props: HashMap<StableId, PropPose>,
+}
+
+/// Makes the player travel to a level, which is reloaded if it is the current one.
+#[derive(Debug, Clone, PartialEq, Event)]
+pub(crate) struct TravelEvent {
+    /// Path of the level's GLTF file.
+    pub(crate) level: String,
+    pub(crate) arrival: Arrival,
+    /// Whether [`Companion`]s travel along. If not, they are despawned together with the level.
+    pub(crate) bring_companions: bool,
+}
+
+/// Where the player arrives in the new level.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Arrival {
+    /// At the position of the entity with this [`Name`].
+    SpawnPoint(String),
+    /// At this position and rotation, once the level's scene has spawned.
+    Transform(Transform),
+}
+
+#[derive(Debug, Default, Resource)]
+struct PreloadedLevels(HashMap<String, Handle<Gltf>>);
+
+#[derive(Debug, Default, Resource)]
+pub(crate) enum Travel {
+    #[default]
+    None,
+    Loading {
+        level: String,
+        arrival: Arrival,
+        bring_companions: bool,
+    },
+    Arriving {
+        arrival: Arrival,
+        timeout: Timer,
+    },
+    Cooldown(Timer),
+}
+
+impl Travel {
+    /// Whether a level is being exchanged right now. The player's level and position are not reliable during that time.
+    pub(crate) fn is_traveling(&self) -> bool {
+        matches!(self, Self::Loading { .. } | Self::Arriving { .. })
+    }
+}
+
+/// Whether the new level is ready for the player to arrive.
+#[derive(SystemParam)]
+struct LevelReadiness<'w, 's> {
+    scene_spawner: Res<'w, SceneSpawner>,
+    level_roots: Query<'w, 's, &'static SceneInstance, With<LevelRoot>>,
+    /// Markers that are not baked yet count as well, so that readiness is not reported before baking even started.
+    awaiting_colliders: Query<'w, 's, (), Or<(With<AwaitingColliders>, With<ColliderMarker>)>>,
+    screen_effects: Res<'w, ScreenEffects>,
+}
+
+impl LevelReadiness<'_, '_> {
+    fn is_spawned(&self) -> bool {
+        self.level_roots
+            .iter()
+            .any(|instance| self.scene_spawner.instance_is_ready(**instance))
+    }
+
+    /// Whether the ground the player arrives on can be stood on.
+    fn has_colliders(&self) -> bool {
+        self.awaiting_colliders.is_empty()
+    }
+
+    /// Whether the screen is covered, so that the old level can be despawned out of sight.
+    fn is_hidden(&self) -> bool {
+        self.screen_effects.is_covered()
+    }
+}
+
+/// Marks the player and its companions while they are carried over to another level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+struct Traveler;
+
+fn preload_portal_targets(
+    portals: Query<(&Portal, &GlobalTransform)>,
+    players: Query<&GlobalTransform, With<Player>>,
+    mut preloaded: ResMut<PreloadedLevels>,
+    asset_server: Res<AssetServer>,
+) {
+    for player in players.iter() {
+        for (portal, transform) in portals.iter() {
+            let distance_squared = transform
+                .translation()
+                .distance_squared(player.translation());
+            if distance_squared < portal.preload_radius.squared()
+                && !preloaded.0.contains_key(&portal.target_level)
+            {
+                info!("Preloading level {}", portal.target_level);
+                let handle = asset_server.load(portal.target_level.clone());
+                preloaded.0.insert(portal.target_level.clone(), handle);
+            }
+        }
+    }
+}
+
+fn start_requested_travel(mut travel_events: EventReader<TravelEvent>, mut travel: ResMut<Travel>) {
+    for event in travel_events.read() {
+        if travel.is_traveling() {
+            warn!(
+                "Cannot travel to level {} while already travelling",
+                event.level
+            );
+            continue;
+        }
+        *travel = Travel::Loading {
+            level: event.level.clone(),
+            arrival: event.arrival.clone(),
+            bring_companions: event.bring_companions,
+        };
+    }
+}
+
+fn enter_portals(
+    time: Res<Time>,
+    mut travel: ResMut<Travel>,
+    portals: Query<(&Portal, &GlobalTransform)>,
+    players: Query<&GlobalTransform, With<Player>>,
+) {
+    if let Travel::Cooldown(timer) = travel.as_mut() {
+        if timer.tick(time.delta()).finished() {
+            *travel = Travel::None;
+        }
+        return;
+    }
+    if !matches!(*travel, Travel::None) {
+        return;
+    }
+    for player in players.iter() {
+        let entered = portals.iter().find(|(portal, transform)| {
+            let distance_squared = transform
+                .translation()
+                .distance_squared(player.translation());
+            distance_squared < portal.entry_radius.squared()
+        });
+        if let Some((portal, _)) = entered {
+            *travel = Travel::Loading {
+                level: portal.target_level.clone(),
+                arrival: Arrival::SpawnPoint(portal.target_spawn_point.clone()),
+                bring_companions: true,
+            };
+            return;
+        }
+    }
+}
+
+fn track_level_state(
+    travel: Res<Travel>,
+    current_level: Res<CurrentLevel>,
+    mut cache: ResMut<LevelStateCache>,
+    persistent: Query<
+        (Entity, &StableId),
+        (
+            With<LevelPersistent>,
+            Or<(Added<LevelPersistent>, Changed<StableId>)>,
+        ),
+    >,
+    read: Query<&StableId, Added<AlreadyRead>>,
+    lamp_overrides: Query<(&StableId, &LampOverride), Changed<LampOverride>>,
+    broken: Query<&StableId, Added<BrokenSegment>>,
+    stable_ids: Query<&StableId>,
+    mut removed: RemovedComponents<LevelPersistent>,
+    mut removed_lamp_overrides: RemovedComponents<LampOverride>,
+    mut repaired: RemovedComponents<BrokenSegment>,
+    mut persistent_ids: Local<HashMap<Entity, StableId>>,
+) {
+    for (entity, id) in persistent.iter() {
+        persistent_ids.insert(entity, *id);
+    }
+    // Everything is despawned when leaving a level, which does not count
+    let is_unloading = matches!(*travel, Travel::Arriving { .. });
+    let state = cache.0.entry(current_level.0.clone()).or_default();
+    for entity in removed.read() {
+        if let Some(id) = persistent_ids.remove(&entity) {
+            if !is_unloading {
+                state.despawned.insert(id);
+            }
+        }
+    }
+    for id in read.iter() {
+        state.read.insert(*id);
+    }
+    for (id, lamp_override) in lamp_overrides.iter() {
+        state.lamp_overrides.insert(*id, lamp_override.0);
+    }
+    for entity in removed_lamp_overrides.read() {
+        // Despawned lamps keep their override
+        if let Ok(id) = stable_ids.get(entity) {
+            state.lamp_overrides.remove(id);
+        }
+    }
+    for id in broken.iter() {
+        state.broken.insert(*id);
+    }
+    for entity in repaired.read() {
+        // Like lamps, despawned segments stay broken
+        if let Ok(id) = stable_ids.get(entity) {
+            state.broken.remove(id);
+        }
+    }
+}
+
+fn advance_travel(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut travel: ResMut<Travel>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut preloaded: ResMut<PreloadedLevels>,
+    cache: Res<LevelStateCache>,
+    asset_server: Res<AssetServer>,
+    models: Res<Assets<Gltf>>,
+    named_entities: Res<NamedEntities>,
+    stable_ids: Res<StableIdRegistry>,
+    readiness: LevelReadiness,
+    level_roots: Query<Entity, With<LevelRoot>>,
+    players: Query<(Entity, Has<Traveler>), With<Player>>,
+    companions: Query<Entity, With<Companion>>,
+    spawn_points: Query<&GlobalTransform>,
+    mut travelers: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut LinearVelocity,
+            Option<&Companion>,
+        ),
+        With<Traveler>,
+    >,
+) {
+    match travel.as_mut() {
+        Travel::Loading {
+            level,
+            arrival,
+            bring_companions,
+        } => {
+            let handle = preloaded
+                .0
+                .entry(level.clone())
+                .or_insert_with(|| asset_server.load(level.clone()))
+                .clone();
+            if !asset_server.is_loaded_with_dependencies(&handle) || !readiness.is_hidden() {
+                return;
+            }
+            let Some(gltf) = models.get(&handle) else {
+                error!("Failed to load level {level}");
+                *travel = Travel::None;
+                return;
+            };
+            for (player, _) in players.iter() {
+                commands
+                    .entity(player)
+                    .remove_parent_in_place()
+                    .insert(Traveler);
+            }
+            for companion in companions.iter() {
+                if *bring_companions {
+                    commands
+                        .entity(companion)
+                        .remove_parent_in_place()
+                        .insert(Traveler);
+                } else {
+                    commands.entity(companion).despawn_recursive();
+                }
+            }
+            for root in level_roots.iter() {
+                commands.entity(root).despawn_recursive();
+            }
+            spawn_level_scene(&mut commands, gltf, level);
+            current_level.0.clone_from(level);
+            *travel = Travel::Arriving {
+                arrival: arrival.clone(),
+                timeout: Timer::from_seconds(SPAWN_POINT_TIMEOUT, TimerMode::Once),
+            };
+        }
+        Travel::Arriving { arrival, timeout } => {
+            // The new level brings its own player, but we keep the one that travelled
+            for (player, is_traveler) in players.iter() {
+                if !is_traveler {
+                    commands.entity(player).despawn_recursive();
+                }
+            }
+            let target = match arrival {
+                Arrival::SpawnPoint(spawn_point) => named_entities
+                    .get(spawn_point)
+                    .and_then(|entity| spawn_points.get(entity).ok())
+                    .map(GlobalTransform::compute_transform),
+                Arrival::Transform(transform) => readiness.is_spawned().then_some(*transform),
+            };
+            let has_colliders = readiness.has_colliders();
+            if (target.is_none() || !has_colliders) && !timeout.tick(time.delta()).finished() {
+                return;
+            }
+            if target.is_some() && !has_colliders {
+                warn!(
+                    "Placing the player before all colliders of level {} are baked",
+                    current_level.0
+                );
+            }
+            match target {
+                Some(target) => {
+                    for (_, mut transform, mut velocity, companion) in travelers.iter_mut() {
+                        match companion {
+                            // Companions arrive behind the player
+                            Some(companion) => {
+                                let behind = (*target.back()).horizontal().normalize_or_zero();
+                                transform.translation =
+                                    target.translation + behind * companion.min_distance;
+                            }
+                            None => {
+                                transform.translation = target.translation;
+                                // Spawn points only decide where the player arrives, not where it looks
+                                if matches!(arrival, Arrival::Transform(_)) {
+                                    transform.rotation = target.rotation;
+                                }
+                            }
+                        }
+                        velocity.0 = Vec3::ZERO;
+                    }
+                }
+                None => match arrival {
+                    Arrival::SpawnPoint(spawn_point) => error!(
+                        "Level {} has no spawn point named \"{spawn_point}\"",
+                        current_level.0
+                    ),
+                    Arrival::Transform(_) => {
+                        error!("Level {} did not finish spawning", current_level.0)
+                    }
+                },
+            }
+            if let Some(state) = cache.0.get(&current_level.0) {
+                restore_level_state(&mut commands, &current_level.0, state, &stable_ids);
+            }
+            for (traveler, ..) in travelers.iter() {
+                commands
+                    .entity(traveler)
+                    .remove::<Traveler>()
+                    .insert(Depenetrate::default());
+            }
+            *travel = Travel::Cooldown(Timer::from_seconds(ARRIVAL_COOLDOWN, TimerMode::Once));
+        }
+        Travel::None | Travel::Cooldown(_) => {}
+    }
+}
+
+/// Fades the screen out when travel starts and back in once it is over, also when it failed.
+fn fade_screen_for_travel(
+    travel: Res<Travel>,
+    mut transition_events: EventWriter<ScreenTransitionEvent>,
+    mut was_traveling: Local<bool>,
+) {
+    let is_traveling = travel.is_traveling();
+    if is_traveling == *was_traveling {
+        return;
+    }
+    *was_traveling = is_traveling;
+    transition_events.send(if is_traveling {
+        ScreenTransitionEvent::fade_out(TRAVEL_FADE_DURATION)
+    } else {
+        ScreenTransitionEvent::fade_in(TRAVEL_FADE_DURATION)
+    });
+}
+
+fn restore_level_state(
+    commands: &mut Commands,
+    level: &str,
+    state: &LevelState,
+    stable_ids: &StableIdRegistry,
+) {
+    let resolve = |id: StableId, what: &str| {
+        let entity = stable_ids.get(id);
+        if entity.is_none() {
+            warn!(
+                "Level {level} remembers a {what} entity with the id {:?}, but no such entity exists anymore. \
+                If it was renamed in the level file, give it its old id as a `StableId` component",
+                id.0
+            );
+        }
+        entity
+    };
+    for id in &state.despawned {
+        if let Some(entity) = resolve(*id, "despawned") {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    for id in &state.read {
+        if let Some(entity) = resolve(*id, "read") {
+            commands.entity(entity).insert(AlreadyRead);
+        }
+    }
+    for (id, &on) in &state.lamp_overrides {
+        if let Some(entity) = resolve(*id, "switched lamp") {
+            commands.entity(entity).insert(LampOverride(on));
+        }
+    }
+    for id in &state.broken {
+        if let Some(entity) = resolve(*id, "broken") {
+            commands.entity(entity).insert(BrokenSegment::gap());
+        }
+    }
+    for (id, &pose) in &state.props {
+        // Broken props are restored as gaps, never intact
+        if state.despawned.contains(id) || state.broken.contains(id) {
+            continue;
+        }
+        if let Some(entity) = resolve(*id, "moved") {
+            commands.entity(entity).insert(RestoredPose::new(pose));
+        }
+    }
+}
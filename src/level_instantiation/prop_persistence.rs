@@ -0,0 +1,414 @@
+use crate::{
+    level_instantiation::{
+        map::{CurrentLevel, LevelRoot},
+        portal::{LevelState, LevelStateCache, Travel, TravelSystemSet},
+        stable_id::StableId,
+    },
+    movement::{character_controller::deepest_push, physics::CollisionLayer},
+    world_interaction::destructible::BrokenSegment,
+    GameState,
+};
+use bevy::{prelude::*, utils::HashMap};
+use bevy_tnua::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::iter;
+
+/// Props closer than this to where the level placed them are not remembered, in meters.
+const POSITION_TOLERANCE: f32 = 0.05;
+/// Props turned less than this from how the level placed them are not remembered, in radians.
+const ROTATION_TOLERANCE: f32 = 0.05;
+/// How often a restored prop is pushed out of the terrain it overlaps before giving up.
+const MAX_PUSHES: usize = 4;
+/// How far a restored prop may be pushed away from where it was remembered, in meters.
+const MAX_DEPENETRATION: f32 = 0.5;
+
+/// Remembers where props were left, so that a crate pushed into a corner is still there when coming back or loading a save.
+/// The poses are stored in the [`LevelState`](crate::level_instantiation::portal::LevelState) of the level by [`StableId`]:
+/// - A prop that moves further than [`POSITION_TOLERANCE`] or turns further than [`ROTATION_TOLERANCE`] from its spawn pose
+///   is remembered, without its velocity. Props that are nudged only slightly are left to the level file.
+/// - Props that are despawned, e.g. consumed or destroyed, are remembered as despawned. Broken props are remembered
+///   as [`BrokenSegment`]s, so they are never put back intact.
+/// - On arrival, remembered props are placed at their pose and put to sleep. Props that would overlap terrain there,
+///   e.g. because the level was edited since, are pushed out of it. If that does not work, they are put back
+///   where the level placed them with a warning.
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            record_spawn_poses,
+            track_prop_poses.before(TravelSystemSet),
+            restore_prop_poses,
+        )
+            .chain()
+            .run_if(in_state(GameState::Playing)),
+    );
+}
+
+/// Where a prop is, relative to its parent.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) struct PropPose {
+    pub(crate) translation: Vec3,
+    pub(crate) rotation: Quat,
+}
+
+impl PropPose {
+    fn from_transform(transform: &Transform) -> Self {
+        Self {
+            translation: transform.translation,
+            rotation: transform.rotation,
+        }
+    }
+
+    /// Whether the poses are further apart than the [`POSITION_TOLERANCE`] or the [`ROTATION_TOLERANCE`].
+    fn differs_from(&self, other: &Self) -> bool {
+        self.translation.distance(other.translation) > POSITION_TOLERANCE
+            || self.rotation.angle_between(other.rotation) > ROTATION_TOLERANCE
+    }
+}
+
+/// Inserted on arrival on props that were remembered away from their spawn pose.
+/// Removed once the prop was placed there and put to sleep.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub(crate) struct RestoredPose {
+    pose: PropPose,
+    /// Whether the prop was placed in the previous frame, so that it can be put to sleep once physics has seen the new pose.
+    placed: bool,
+}
+
+impl RestoredPose {
+    pub(crate) fn new(pose: PropPose) -> Self {
+        Self {
+            pose,
+            placed: false,
+        }
+    }
+}
+
+/// How the level placed a prop. Only props with one are remembered.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct SpawnPose(PropPose);
+
+fn record_spawn_poses(
+    mut commands: Commands,
+    props: Query<(Entity, &Transform, &RigidBody), (Added<StableId>, Without<TnuaController>)>,
+    parents: Query<&Parent>,
+    level_roots: Query<(), With<LevelRoot>>,
+) {
+    for (entity, transform, rigid_body) in props.iter() {
+        // Fence segments only turn dynamic when broken, and are remembered as broken anyway
+        let is_in_level = parents
+            .iter_ancestors(entity)
+            .any(|ancestor| level_roots.contains(ancestor));
+        if rigid_body.is_dynamic() && is_in_level {
+            commands
+                .entity(entity)
+                .insert(SpawnPose(PropPose::from_transform(transform)));
+        }
+    }
+}
+
+fn track_prop_poses(
+    travel: Res<Travel>,
+    current_level: Res<CurrentLevel>,
+    mut cache: ResMut<LevelStateCache>,
+    new_props: Query<(Entity, &StableId), Added<SpawnPose>>,
+    moved: Query<
+        (&StableId, &SpawnPose, &Transform),
+        (
+            Changed<Transform>,
+            Without<RestoredPose>,
+            Without<BrokenSegment>,
+        ),
+    >,
+    broken: Query<&StableId, (With<SpawnPose>, Added<BrokenSegment>)>,
+    mut removed: RemovedComponents<SpawnPose>,
+    mut prop_ids: Local<HashMap<Entity, StableId>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("track_prop_poses").entered();
+    for (entity, id) in new_props.iter() {
+        prop_ids.insert(entity, *id);
+    }
+    // Like in the rest of the level state, unloading does not count, and neither do the props of the new level
+    // settling before their remembered poses are restored
+    let is_unloading = matches!(*travel, Travel::Arriving { .. });
+    let state = cache.0.entry(current_level.0.clone()).or_default();
+    for entity in removed.read() {
+        if let Some(id) = prop_ids.remove(&entity) {
+            if !is_unloading {
+                state.props.remove(&id);
+                state.despawned.insert(id);
+            }
+        }
+    }
+    if is_unloading {
+        return;
+    }
+    for (id, spawn_pose, transform) in moved.iter() {
+        record(state, *id, spawn_pose, PropPose::from_transform(transform));
+    }
+    for id in broken.iter() {
+        state.props.remove(id);
+    }
+}
+
+/// Remembers a pose, unless it is so close to the spawn pose that putting the prop back there would not be noticed.
+fn record(state: &mut LevelState, id: StableId, spawn_pose: &SpawnPose, pose: PropPose) {
+    if pose.differs_from(&spawn_pose.0) {
+        state.props.insert(id, pose);
+    } else {
+        state.props.remove(&id);
+    }
+}
+
+fn restore_prop_poses(
+    mut commands: Commands,
+    current_level: Res<CurrentLevel>,
+    mut cache: ResMut<LevelStateCache>,
+    mut props: Query<(
+        Entity,
+        &StableId,
+        &mut RestoredPose,
+        Option<&SpawnPose>,
+        Option<&Parent>,
+        &mut Transform,
+        &mut LinearVelocity,
+        &mut AngularVelocity,
+    )>,
+    global_transforms: Query<&GlobalTransform>,
+    children: Query<&Children>,
+    colliders: Query<(&Collider, &ColliderTransform)>,
+    obstacles: Query<(&Collider, &Position, &Rotation)>,
+    spatial_query: SpatialQuery,
+) {
+    for (
+        entity,
+        id,
+        mut restored,
+        spawn_pose,
+        parent,
+        mut transform,
+        mut linear_velocity,
+        mut angular_velocity,
+    ) in props.iter_mut()
+    {
+        if restored.placed {
+            // XPBD wakes bodies whose velocity was changed outside of the physics step
+            linear_velocity.bypass_change_detection().0 = Vec3::ZERO;
+            angular_velocity.bypass_change_detection().0 = Vec3::ZERO;
+            commands
+                .entity(entity)
+                .remove::<RestoredPose>()
+                .insert(Sleeping);
+            continue;
+        }
+        let parent_transform = parent
+            .and_then(|parent| global_transforms.get(parent.get()).ok())
+            .copied()
+            .unwrap_or_default();
+        let pieces: Vec<_> = iter::once(entity)
+            .chain(children.iter_descendants(entity))
+            .filter_map(|piece| Some((piece, colliders.get(piece).ok()?)))
+            .collect();
+        let filter = SpatialQueryFilter::from_mask(CollisionLayer::Terrain.to_bits())
+            .with_excluded_entities(pieces.iter().map(|(piece, _)| *piece));
+        let placed = Transform {
+            translation: restored.pose.translation,
+            rotation: restored.pose.rotation,
+            scale: transform.scale,
+        };
+        let world_pose = parent_transform.mul_transform(placed);
+        let (_, rotation, translation) = world_pose.to_scale_rotation_translation();
+
+        let mut free_translation = Some(translation);
+        for _ in 0..MAX_PUSHES {
+            let Some(current) = free_translation else {
+                break;
+            };
+            let push = pieces
+                .iter()
+                .filter_map(|(_, (collider, offset))| {
+                    deepest_push(
+                        &spatial_query,
+                        &obstacles,
+                        collider,
+                        current + rotation * offset.translation,
+                        rotation * offset.rotation.0,
+                        &filter,
+                    )
+                })
+                .max_by(|a, b| a.length_squared().total_cmp(&b.length_squared()));
+            let Some(push) = push else {
+                break;
+            };
+            free_translation = Some(current + push)
+                .filter(|pushed| pushed.distance(translation) <= MAX_DEPENETRATION);
+        }
+        let is_free = free_translation.is_some_and(|current| {
+            pieces.iter().all(|(_, (collider, offset))| {
+                spatial_query
+                    .shape_intersections(
+                        collider,
+                        current + rotation * offset.translation,
+                        rotation * offset.rotation.0,
+                        filter.clone(),
+                    )
+                    .is_empty()
+            })
+        });
+
+        let pose = match (free_translation, is_free) {
+            (Some(free_translation), true) => {
+                let free = GlobalTransform::from(Transform {
+                    translation: free_translation,
+                    ..world_pose.compute_transform()
+                });
+                PropPose::from_transform(&free.reparented_to(&parent_transform))
+            }
+            _ => {
+                warn!(
+                    "Prop {entity:?} was remembered inside of terrain at {translation}, \
+                    so it is put back where the level placed it"
+                );
+                spawn_pose.map_or(restored.pose, |spawn_pose| spawn_pose.0)
+            }
+        };
+        transform.translation = pose.translation;
+        transform.rotation = pose.rotation;
+        linear_velocity.0 = Vec3::ZERO;
+        angular_velocity.0 = Vec3::ZERO;
+        restored.placed = true;
+        if let Some(spawn_pose) = spawn_pose {
+            let state = cache.0.entry(current_level.0.clone()).or_default();
+            record(state, *id, spawn_pose, pose);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{assert_near, TestApp};
+
+    /// Where the level places the crate of [`spawn_crate`].
+    const SPAWN: Vec3 = Vec3::new(0., 10., 0.);
+
+    /// Tracks and restores props in the level "test", returning its root.
+    fn prop_app() -> (TestApp, Entity) {
+        let mut app = TestApp::new();
+        app.add_plugins(plugin)
+            .init_resource::<Travel>()
+            .insert_resource(CurrentLevel("test".to_string()))
+            .init_resource::<LevelStateCache>();
+        let level = app
+            .world_mut()
+            .spawn((LevelRoot("test".to_string()), SpatialBundle::default()))
+            .id();
+        (app, level)
+    }
+
+    /// A floating 1 m crate placed by the level at [`SPAWN`].
+    fn spawn_crate(app: &mut TestApp, level: Entity) -> Entity {
+        app.world_mut()
+            .spawn((
+                StableId::derive("test", "Crate"),
+                RigidBody::Dynamic,
+                Collider::cuboid(1., 1., 1.),
+                GravityScale(0.),
+                SpatialBundle::from_transform(Transform::from_translation(SPAWN)),
+            ))
+            .set_parent(level)
+            .id()
+    }
+
+    /// Spawns the crate like on arrival in a level that remembered it at `translation`, and lets it be placed.
+    fn restore_crate_at(app: &mut TestApp, level: Entity, translation: Vec3) -> Entity {
+        // Lets the spatial query pick up the terrain
+        app.step(2);
+        let prop = spawn_crate(app, level);
+        app.world_mut()
+            .entity_mut(prop)
+            .insert(RestoredPose::new(PropPose {
+                translation,
+                rotation: Quat::IDENTITY,
+            }));
+        app.step(2);
+        prop
+    }
+
+    #[test]
+    fn only_props_moved_beyond_the_tolerance_are_remembered() {
+        let (mut app, level) = prop_app();
+        let id = StableId::derive("test", "Crate");
+        let spawn = SPAWN;
+        let prop = spawn_crate(&mut app, level);
+        app.step(2);
+        let move_to = |app: &mut TestApp, translation: Vec3| {
+            app.world_mut()
+                .get_mut::<Transform>(prop)
+                .unwrap()
+                .translation = translation;
+            app.step(1);
+        };
+        let state = |app: &TestApp| app.resource::<LevelStateCache>().0["test"].clone();
+
+        move_to(&mut app, spawn + Vec3::X * 0.01);
+        assert!(state(&app).props.is_empty());
+
+        let pushed = spawn + Vec3::X * 2.;
+        move_to(&mut app, pushed);
+        let pose = state(&app).props.get(&id).copied().unwrap();
+        assert_near(pose.translation, pushed, 0.01);
+
+        // Pushing it back where it was is the same as never moving it
+        move_to(&mut app, spawn);
+        assert!(state(&app).props.is_empty());
+
+        move_to(&mut app, pushed);
+        app.world_mut().entity_mut(prop).despawn_recursive();
+        app.step(1);
+        let state = state(&app);
+        assert!(state.props.is_empty());
+        assert!(state.despawned.contains(&id));
+    }
+
+    #[test]
+    fn remembered_poses_are_restored_asleep() {
+        let (mut app, level) = prop_app();
+        let remembered = Vec3::new(5., 10., 0.);
+        let prop = restore_crate_at(&mut app, level, remembered);
+
+        assert_near(app.translation(prop), remembered, 0.01);
+        let prop = app.world().entity(prop);
+        assert!(prop.contains::<Sleeping>());
+        assert!(!prop.contains::<RestoredPose>());
+    }
+
+    #[test]
+    fn remembered_poses_inside_terrain_are_pushed_out() {
+        let (mut app, level) = prop_app();
+        app.spawn_ground();
+        // Sunk 0.3 m into the ground, e.g. because the ground was raised since
+        let prop = restore_crate_at(&mut app, level, Vec3::new(2., 0.2, 0.));
+
+        assert_near(app.translation(prop), Vec3::new(2., 0.5, 0.), 0.05);
+        assert!(app.world().entity(prop).contains::<Sleeping>());
+        let pose =
+            app.resource::<LevelStateCache>().0["test"].props[&StableId::derive("test", "Crate")];
+        assert_near(pose.translation, Vec3::new(2., 0.5, 0.), 0.05);
+    }
+
+    #[test]
+    fn props_that_cannot_be_pushed_out_of_terrain_go_back_to_their_spawn_pose() {
+        let (mut app, level) = prop_app();
+        app.spawn_ground();
+        // Further below the surface than a prop may be pushed
+        let prop = restore_crate_at(&mut app, level, Vec3::new(2., -0.8, 0.));
+
+        assert_near(app.translation(prop), SPAWN, 0.01);
+        assert!(app.world().entity(prop).contains::<Sleeping>());
+        assert!(app.resource::<LevelStateCache>().0["test"].props.is_empty());
+    }
+}
@@ -0,0 +1,454 @@
+use crate::{
+    level_instantiation::{
+        spawn_queue::SpawnRequest,
+        stable_id::{StableId, StableIdRegistry},
+        tags::Tags,
+    },
+    GameState,
+};
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bevy_common_assets::ron::RonAssetPlugin;
+use serde::{Deserialize, Serialize};
+
+/// Spawns the blueprints listed in `.spawns.ron` files through the spawn queue, see [`SpawnManifestPath`].
+/// When a manifest changes while the game runs, e.g. while hand-editing it with hot-reloading, only the difference is applied:
+/// entries whose transform changed are moved, added entries are spawned and removed ones despawned.
+/// Entries that did not change are left alone, together with their runtime state, e.g. an opened door.
+/// Entries are matched by their `id` if they have one, and otherwise by their blueprint, their parent entry
+/// and how many entries of the same blueprint come before them under that parent.
+pub(super) fn plugin(app: &mut App) {
+    app.add_plugins(RonAssetPlugin::<SpawnManifest>::new(&["spawns.ron"]))
+        .register_type::<SpawnManifestPath>()
+        .add_systems(
+            Update,
+            (load_spawn_manifests, apply_spawn_manifests)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Spawns the entries of a `.spawns.ron` file as children of this entity, e.g. `"levels/village.spawns.ron"`.
+#[derive(Debug, Clone, PartialEq, Eq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct SpawnManifestPath(pub(crate) String);
+
+/// The contents of a `.spawns.ron` file.
+#[derive(Debug, Clone, PartialEq, Asset, Reflect, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct SpawnManifest {
+    pub(crate) entries: Vec<ManifestEntry>,
+}
+
+/// A blueprint to spawn, like a [`SpawnRequest`] without the random variation.
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct ManifestEntry {
+    pub(crate) blueprint: String,
+    /// Relative to the parent entry, or to the entity with the [`SpawnManifestPath`].
+    pub(crate) transform: Transform,
+    /// Defaults to the blueprint's name.
+    pub(crate) name: Option<String>,
+    /// Defaults to an id derived from the manifest's path and where the entry is in it.
+    pub(crate) id: Option<StableId>,
+    pub(crate) tags: Vec<String>,
+    pub(crate) children: Vec<ManifestEntry>,
+}
+
+/// What a manifest entry was last applied as.
+#[derive(Debug, Clone, PartialEq)]
+struct SpawnedBy {
+    id: StableId,
+    blueprint: String,
+    transform: Transform,
+    name: Option<String>,
+    tags: Vec<String>,
+    /// The key of the parent entry.
+    parent: Option<String>,
+}
+
+/// The loaded manifest of a [`SpawnManifestPath`] and what it spawned, by entry key.
+#[derive(Debug, Clone, PartialEq, Component)]
+struct ManifestSpawner {
+    handle: Handle<SpawnManifest>,
+    /// `None` until the manifest is first applied.
+    spawned: Option<HashMap<String, SpawnedBy>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ManifestChange {
+    /// Spawns below the entity with the parent's id, or below the manifest's entity.
+    Spawn {
+        parent: Option<StableId>,
+        request: SpawnRequest,
+    },
+    Despawn(StableId),
+    Update(SpawnedBy),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct DiffSummary {
+    spawned: usize,
+    despawned: usize,
+    /// Changed their blueprint or parent entry, which needs spawning them anew.
+    respawned: usize,
+    moved: usize,
+    /// Only their name or tags changed.
+    updated: usize,
+    unchanged: usize,
+}
+
+/// The changes that turn what was `spawned` into what the `manifest` lists.
+struct ManifestDiff<'a> {
+    path: &'a str,
+    spawned: &'a HashMap<String, SpawnedBy>,
+    changes: Vec<ManifestChange>,
+    record: HashMap<String, SpawnedBy>,
+    summary: DiffSummary,
+}
+
+impl<'a> ManifestDiff<'a> {
+    fn new(
+        path: &'a str,
+        spawned: &'a HashMap<String, SpawnedBy>,
+        manifest: &SpawnManifest,
+    ) -> Self {
+        let mut diff = Self {
+            path,
+            spawned,
+            changes: Vec::new(),
+            record: HashMap::default(),
+            summary: DiffSummary::default(),
+        };
+        diff.compare(&manifest.entries, None, None);
+        let removed: Vec<_> = spawned
+            .iter()
+            .filter(|(key, _)| !diff.record.contains_key(*key))
+            .map(|(_, spawned)| spawned.id)
+            .collect();
+        for id in removed {
+            diff.changes.push(ManifestChange::Despawn(id));
+            diff.summary.despawned += 1;
+        }
+        diff
+    }
+
+    /// Matches the `entries` below one parent against what was spawned.
+    fn compare(
+        &mut self,
+        entries: &[ManifestEntry],
+        parent_key: Option<&str>,
+        parent_id: Option<StableId>,
+    ) {
+        let spawned_before = self.spawned;
+        for (entry, key) in entries.iter().zip(entry_keys(entries, parent_key)) {
+            let spawned = self.record_entry(entry, &key, parent_key);
+            match spawned_before.get(&key) {
+                Some(previous)
+                    if previous.blueprint == spawned.blueprint
+                        && previous.parent == spawned.parent =>
+                {
+                    if previous.transform != spawned.transform {
+                        self.summary.moved += 1;
+                    } else if previous.name != spawned.name || previous.tags != spawned.tags {
+                        self.summary.updated += 1;
+                    } else {
+                        self.summary.unchanged += 1;
+                    }
+                    if *previous != spawned {
+                        self.changes.push(ManifestChange::Update(spawned.clone()));
+                    }
+                    self.compare(&entry.children, Some(&key), Some(spawned.id));
+                }
+                Some(previous) => {
+                    self.changes.push(ManifestChange::Despawn(previous.id));
+                    self.summary.respawned += 1;
+                    self.spawn(entry, key, parent_id);
+                }
+                None => {
+                    self.summary.spawned += 1;
+                    self.spawn(entry, key, parent_id);
+                }
+            }
+        }
+    }
+
+    fn spawn(&mut self, entry: &ManifestEntry, key: String, parent: Option<StableId>) {
+        let request = self.request(entry, &key);
+        self.changes.push(ManifestChange::Spawn { parent, request });
+    }
+
+    /// A request for the entry and its children, which are all spawned anew along with it.
+    fn request(&mut self, entry: &ManifestEntry, key: &str) -> SpawnRequest {
+        let id = self.record[key].id;
+        let spawned_before = self.spawned;
+        let children = entry
+            .children
+            .iter()
+            .zip(entry_keys(&entry.children, Some(key)))
+            .map(|(child, child_key)| {
+                // A child with an id may have been moved here from somewhere else
+                if let Some(previous) = spawned_before.get(&child_key) {
+                    self.changes.push(ManifestChange::Despawn(previous.id));
+                }
+                self.record_entry(child, &child_key, Some(key));
+                self.request(child, &child_key)
+            })
+            .collect();
+        SpawnRequest {
+            name: entry.name.clone(),
+            id: Some(id),
+            tags: entry.tags.clone(),
+            children,
+            ..SpawnRequest::new(&entry.blueprint, entry.transform)
+        }
+    }
+
+    fn record_entry(
+        &mut self,
+        entry: &ManifestEntry,
+        key: &str,
+        parent_key: Option<&str>,
+    ) -> SpawnedBy {
+        let spawned = SpawnedBy {
+            id: entry.id.unwrap_or_else(|| StableId::derive(self.path, key)),
+            blueprint: entry.blueprint.clone(),
+            transform: entry.transform,
+            name: entry.name.clone(),
+            tags: entry.tags.clone(),
+            parent: parent_key.map(ToString::to_string),
+        };
+        self.record.insert(key.to_string(), spawned.clone());
+        spawned
+    }
+}
+
+/// Entries are keyed by their id, or else by their parent's key, their blueprint and how many entries of it come before them.
+/// Unlike the plain index, this keeps adding or removing an entry from changing the keys of different blueprints.
+fn entry_keys<'a>(
+    entries: &'a [ManifestEntry],
+    parent_key: Option<&'a str>,
+) -> impl Iterator<Item = String> + 'a {
+    let mut counts = HashMap::<&str, usize>::default();
+    entries.iter().map(move |entry| match entry.id {
+        Some(id) => id.0.to_string(),
+        None => {
+            let count = counts.entry(entry.blueprint.as_str()).or_default();
+            let key = format!(
+                "{}/{}#{count}",
+                parent_key.unwrap_or_default(),
+                entry.blueprint
+            );
+            *count += 1;
+            key
+        }
+    })
+}
+
+fn load_spawn_manifests(
+    mut commands: Commands,
+    paths: Query<(Entity, &SpawnManifestPath), Added<SpawnManifestPath>>,
+    asset_server: Res<AssetServer>,
+) {
+    for (entity, path) in paths.iter() {
+        commands.entity(entity).insert(ManifestSpawner {
+            handle: asset_server.load(path.0.clone()),
+            spawned: None,
+        });
+    }
+}
+
+fn apply_spawn_manifests(
+    mut commands: Commands,
+    mut asset_events: EventReader<AssetEvent<SpawnManifest>>,
+    manifests: Res<Assets<SpawnManifest>>,
+    mut spawners: Query<(Entity, &SpawnManifestPath, &mut ManifestSpawner)>,
+    registry: Res<StableIdRegistry>,
+    parents: Query<&Parent>,
+    mut spawn_requests: EventWriter<SpawnRequest>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("apply_spawn_manifests").entered();
+    let modified: HashSet<_> = asset_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    for (root, path, mut spawner) in spawners.iter_mut() {
+        let is_first = spawner.spawned.is_none();
+        if !is_first && !modified.contains(&spawner.handle.id()) {
+            continue;
+        }
+        let Some(manifest) = manifests.get(&spawner.handle) else {
+            continue;
+        };
+        let spawned = spawner.spawned.take().unwrap_or_default();
+        let diff = ManifestDiff::new(&path.0, &spawned, manifest);
+        let summary = diff.summary;
+        apply_changes(
+            &mut commands,
+            root,
+            diff.changes,
+            &registry,
+            &parents,
+            &mut spawn_requests,
+        );
+        spawner.spawned = Some(diff.record);
+        if is_first {
+            info!("Spawning {} entries of {}", summary.spawned, path.0);
+        } else {
+            info!(
+                "Reloaded {}: {} spawned, {} despawned, {} respawned, {} moved, {} renamed or retagged, {} unchanged",
+                path.0,
+                summary.spawned,
+                summary.despawned,
+                summary.respawned,
+                summary.moved,
+                summary.updated,
+                summary.unchanged
+            );
+        }
+    }
+}
+
+fn apply_changes(
+    commands: &mut Commands,
+    root: Entity,
+    changes: Vec<ManifestChange>,
+    registry: &StableIdRegistry,
+    parents: &Query<&Parent>,
+    spawn_requests: &mut EventWriter<SpawnRequest>,
+) {
+    // Entities that were consumed or are still queued have nothing to change
+    let despawned: HashSet<_> = changes
+        .iter()
+        .filter_map(|change| match change {
+            ManifestChange::Despawn(id) => registry.get(*id),
+            _ => None,
+        })
+        .collect();
+    for change in changes {
+        match change {
+            ManifestChange::Spawn { parent, request } => {
+                let parent = match parent {
+                    Some(id) => registry.get(id),
+                    None => Some(root),
+                };
+                let Some(parent) = parent else {
+                    warn!(
+                        "Cannot spawn \"{}\" from a manifest, because its parent entry has not spawned",
+                        request.blueprint
+                    );
+                    continue;
+                };
+                spawn_requests.send(SpawnRequest {
+                    parent: Some(parent),
+                    ..request
+                });
+            }
+            ManifestChange::Despawn(id) => {
+                let Some(entity) = registry.get(id) else {
+                    continue;
+                };
+                // Despawning an ancestor already takes care of it
+                if parents
+                    .iter_ancestors(entity)
+                    .any(|ancestor| despawned.contains(&ancestor))
+                {
+                    continue;
+                }
+                if let Some(entity_commands) = commands.get_entity(entity) {
+                    entity_commands.despawn_recursive();
+                }
+            }
+            ManifestChange::Update(spawned) => {
+                let Some(mut entity_commands) = registry
+                    .get(spawned.id)
+                    .and_then(|entity| commands.get_entity(entity))
+                else {
+                    continue;
+                };
+                entity_commands.insert((
+                    spawned.transform,
+                    Name::new(spawned.name.unwrap_or(spawned.blueprint)),
+                ));
+                if spawned.tags.is_empty() {
+                    entity_commands.remove::<Tags>();
+                } else {
+                    entity_commands.insert(Tags(spawned.tags));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(blueprint: &str, x: f32) -> ManifestEntry {
+        ManifestEntry {
+            blueprint: blueprint.to_string(),
+            transform: Transform::from_xyz(x, 0., 0.),
+            ..default()
+        }
+    }
+
+    #[test]
+    fn only_changed_entries_are_applied() {
+        let path = "levels/village.spawns.ron";
+        let door_id = StableId::derive("test", "Door");
+        let table = ManifestEntry {
+            children: vec![entry("Lamp", 0.)],
+            ..entry("Table", 1.)
+        };
+        let door = ManifestEntry {
+            id: Some(door_id),
+            ..entry("Door", 2.)
+        };
+        let before = SpawnManifest {
+            entries: vec![
+                entry("Crate", 0.),
+                table.clone(),
+                door.clone(),
+                entry("Crate", 3.),
+            ],
+        };
+        let first = ManifestDiff::new(path, &HashMap::default(), &before);
+        assert_eq!(first.summary.spawned, 4);
+        assert_eq!(first.changes.len(), 4);
+
+        // The first crate is removed from the file, the door is moved to the front and the table's lamp is moved
+        let mut table = table;
+        table.children[0].transform.translation.y = 1.;
+        let after = SpawnManifest {
+            entries: vec![door, table, entry("Crate", 3.), entry("Barrel", 4.)],
+        };
+        let diff = ManifestDiff::new(path, &first.record, &after);
+        assert_eq!(
+            diff.summary,
+            DiffSummary {
+                spawned: 1,
+                despawned: 1,
+                respawned: 0,
+                // The second crate is now the first one and takes its place
+                moved: 2,
+                updated: 0,
+                unchanged: 2,
+            }
+        );
+        let lamp = &first.record["/Table#0/Lamp#0"];
+        assert!(diff.changes.iter().any(|change| matches!(
+            change,
+            ManifestChange::Update(spawned) if spawned.id == lamp.id
+        )));
+        let crate_ = &first.record["/Crate#1"];
+        assert!(diff.changes.contains(&ManifestChange::Despawn(crate_.id)));
+        assert_eq!(diff.record["/Crate#0"].id, first.record["/Crate#0"].id);
+        assert_eq!(diff.record[&door_id.0.to_string()].id, door_id);
+    }
+}
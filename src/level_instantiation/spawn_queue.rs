@@ -0,0 +1,319 @@
+use crate::{
+    determinism::{GameRng, RngStream},
+    level_instantiation::{stable_id::StableId, tags::Tags},
+    GameState,
+};
+use bevy::{
+    prelude::*,
+    utils::{HashMap, Instant},
+};
+use bevy_common_assets::ron::RonAssetPlugin;
+use bevy_gltf_blueprints::{BlueprintName, SpawnHere};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, time::Duration};
+
+/// Spreads [`SpawnRequest`]s over multiple frames so that large bursts do not cause hitches.
+/// Every frame, at most [`SpawnBudget::max_per_frame`] requests are spawned, and spawning stops early once
+/// [`SpawnBudget::max_time`] is used up. Requests are spawned in the order they were sent.
+/// Blueprints listed in `assets/config/config.variants.ron` spawn one of their variants instead, see [`SpawnRequest::variant`].
+pub(crate) fn plugin(app: &mut App) {
+    app.add_plugins(RonAssetPlugin::<BlueprintVariants>::new(&["variants.ron"]))
+        .register_type::<BlueprintVariant>()
+        .add_event::<SpawnRequest>()
+        .add_event::<SpawnQueueDrained>()
+        .init_resource::<SpawnBudget>()
+        .init_resource::<SpawnQueue>()
+        .add_systems(
+            Update,
+            (
+                enqueue_spawn_requests,
+                spawn_requested.run_if(in_state(GameState::Playing)),
+            )
+                .chain(),
+        );
+}
+
+/// Spawns a blueprint from the `scenes/library` folder, followed by its children.
+#[derive(Debug, Clone, PartialEq, Event)]
+pub struct SpawnRequest {
+    pub blueprint: String,
+    /// Relative to the parent, if there is one.
+    pub transform: Transform,
+    /// Defaults to the blueprint's name.
+    pub name: Option<String>,
+    /// Defaults to a new random id.
+    pub id: Option<StableId>,
+    /// Rotates the blueprint by a random angle around the Y axis.
+    pub random_yaw: bool,
+    /// Multiplies the scale by a random factor in `min..max`.
+    pub scale_jitter: Option<(f32, f32)>,
+    /// Which variant of the blueprint to spawn, if it has any. Defaults to a random one.
+    pub variant: Option<u32>,
+    /// Added as [`Tags`] when not empty.
+    pub tags: Vec<String>,
+    /// Spawned right after this request and parented to it.
+    pub children: Vec<SpawnRequest>,
+    /// Parents the blueprint to an entity that already exists, e.g. to add to a spawned object.
+    pub parent: Option<Entity>,
+}
+
+impl SpawnRequest {
+    pub fn new(blueprint: impl Into<String>, transform: Transform) -> Self {
+        Self {
+            blueprint: blueprint.into(),
+            transform,
+            name: None,
+            id: None,
+            random_yaw: false,
+            scale_jitter: None,
+            variant: None,
+            tags: Vec::new(),
+            children: Vec::new(),
+            parent: None,
+        }
+    }
+
+    /// The blueprint to actually spawn, together with the index of the variant if there was one to choose.
+    fn resolve_variant(
+        &self,
+        table: Option<&BlueprintVariants>,
+        rng: &mut RngStream,
+    ) -> (String, Option<u32>) {
+        let Some(variants) = table
+            .and_then(|table| table.variants.get(&self.blueprint))
+            .filter(|variants| !variants.is_empty())
+        else {
+            if self.variant.is_some() {
+                warn!(
+                    "Requested a variant of \"{}\", but it has no variants",
+                    self.blueprint
+                );
+            }
+            return (self.blueprint.clone(), None);
+        };
+        let index = match self.variant {
+            Some(index) if (index as usize) < variants.len() => index as usize,
+            Some(index) => {
+                warn!(
+                    "\"{}\" has no variant {index}, only {} variants",
+                    self.blueprint,
+                    variants.len()
+                );
+                0
+            }
+            None => rng.range_usize(0..variants.len()),
+        };
+        (variants[index].clone(), Some(index as u32))
+    }
+
+    fn jittered_transform(&self, rng: &mut RngStream) -> Transform {
+        let mut transform = self.transform;
+        if self.random_yaw {
+            let yaw = rng.range_f32(0.0..std::f32::consts::TAU);
+            transform.rotate_local_y(yaw);
+        }
+        if let Some((min, max)) = self.scale_jitter {
+            let factor = if max > min {
+                rng.range_f32(min..max)
+            } else {
+                min
+            };
+            transform.scale *= factor;
+        }
+        transform
+    }
+}
+
+/// Maps blueprint names to the names of their variants, e.g. `"Wall"` to `["WallA", "WallB", "WallCracked"]`.
+#[derive(Debug, Clone, PartialEq, Asset, Reflect, Serialize, Deserialize, Default)]
+pub(crate) struct BlueprintVariants {
+    pub(crate) variants: HashMap<String, Vec<String>>,
+}
+
+/// Which variant of a blueprint an entity was spawned as.
+#[derive(Debug, Clone, PartialEq, Eq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct BlueprintVariant {
+    /// The blueprint that was requested.
+    pub(crate) base: String,
+    pub(crate) index: u32,
+}
+
+/// Sent once all queued [`SpawnRequest`]s are spawned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub struct SpawnQueueDrained;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource)]
+pub struct SpawnBudget {
+    pub max_per_frame: usize,
+    pub max_time: Duration,
+}
+
+impl Default for SpawnBudget {
+    fn default() -> Self {
+        Self {
+            max_per_frame: 64,
+            max_time: Duration::from_millis(4),
+        }
+    }
+}
+
+#[derive(Debug, Default, Resource)]
+struct SpawnQueue {
+    /// Requests together with the entity they will be parented to.
+    pending: VecDeque<(SpawnRequest, Option<Entity>)>,
+}
+
+/// Runs outside of [`GameState::Playing`] as well, so that requests sent while loading are not lost.
+fn enqueue_spawn_requests(mut requests: EventReader<SpawnRequest>, mut queue: ResMut<SpawnQueue>) {
+    queue.pending.extend(
+        requests
+            .read()
+            .map(|request| (request.clone(), request.parent)),
+    );
+}
+
+fn spawn_requested(
+    mut commands: Commands,
+    mut queue: ResMut<SpawnQueue>,
+    budget: Res<SpawnBudget>,
+    mut drained_events: EventWriter<SpawnQueueDrained>,
+    variant_tables: Res<Assets<BlueprintVariants>>,
+    game_rng: Res<GameRng>,
+    mut rng: Local<Option<RngStream>>,
+    mut variation_rng: Local<Option<RngStream>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("spawn_requested").entered();
+    if queue.pending.is_empty() {
+        return;
+    }
+    let rng = rng.get_or_insert_with(|| game_rng.fork("stable_ids"));
+    // Separate from the ids so that adding variation to a request does not change the ids of everything after it
+    let variation_rng = variation_rng.get_or_insert_with(|| game_rng.fork("spawn_variation"));
+    let variant_table = variant_tables.iter().next().map(|(_, table)| table);
+    let start = Instant::now();
+    let mut spawned = 0;
+    while spawned < budget.max_per_frame && start.elapsed() < budget.max_time {
+        let Some((request, parent)) = queue.pending.pop_front() else {
+            break;
+        };
+        let (blueprint, variant) = request.resolve_variant(variant_table, variation_rng);
+        // The jitter is baked into the transform, so that saving it restores the exact same placement
+        let transform = request.jittered_transform(variation_rng);
+        let name = request.name.unwrap_or_else(|| request.blueprint.clone());
+        let mut entity_commands = commands.spawn((
+            BlueprintName(blueprint),
+            SpawnHere,
+            SpatialBundle::from_transform(transform),
+            Name::new(name),
+            request.id.unwrap_or_else(|| StableId::from_rng(rng)),
+        ));
+        if let Some(index) = variant {
+            entity_commands.insert(BlueprintVariant {
+                base: request.blueprint,
+                index,
+            });
+        }
+        if !request.tags.is_empty() {
+            entity_commands.insert(Tags(request.tags));
+        }
+        if let Some(parent) = parent {
+            entity_commands.set_parent(parent);
+        }
+        let entity = entity_commands.id();
+        // Children go to the front so that they still come before any requests sent after their parent
+        for child in request.children.into_iter().rev() {
+            queue.pending.push_front((child, Some(entity)));
+        }
+        spawned += 1;
+    }
+    if queue.pending.is_empty() {
+        drained_events.send(SpawnQueueDrained);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        level_instantiation::named_entities::{self, NamedEntities},
+        testing::TestApp,
+    };
+
+    #[test]
+    fn spawns_children_under_their_parent_by_name() {
+        let mut app = TestApp::new();
+        app.add_plugins((plugin, named_entities::plugin))
+            .insert_resource(GameRng::new(0))
+            .record_events::<SpawnQueueDrained>();
+        let lamp = SpawnRequest {
+            name: Some("Reading Lamp".to_string()),
+            ..SpawnRequest::new("Lamp", Transform::from_xyz(0., 1., 0.))
+        };
+        app.send_event(SpawnRequest {
+            name: Some("Desk".to_string()),
+            children: vec![lamp],
+            ..SpawnRequest::new("Table", Transform::from_xyz(2., 0., 0.))
+        });
+        app.step(2);
+
+        let names = app.resource::<NamedEntities>();
+        let desk = names.get("Desk").expect("The parent was not spawned");
+        let lamp = names
+            .get("Reading Lamp")
+            .expect("The child was not spawned");
+        let world = app.world();
+        assert_eq!(world.get::<Parent>(lamp).map(Parent::get), Some(desk));
+        assert_eq!(world.get::<BlueprintName>(desk).unwrap().0, "Table");
+        assert_eq!(world.get::<BlueprintName>(lamp).unwrap().0, "Lamp");
+        assert_ne!(world.get::<StableId>(desk), world.get::<StableId>(lamp));
+        assert_eq!(
+            world.get::<GlobalTransform>(lamp).unwrap().translation(),
+            Vec3::new(2., 1., 0.)
+        );
+        assert_eq!(app.events::<SpawnQueueDrained>().len(), 1);
+    }
+
+    #[test]
+    fn spawns_at_most_the_budget_per_frame_in_order() {
+        let mut app = TestApp::new();
+        app.add_plugins(plugin)
+            .insert_resource(GameRng::new(0))
+            .insert_resource(SpawnBudget {
+                max_per_frame: 10,
+                max_time: Duration::from_secs(60),
+            })
+            .record_events::<SpawnQueueDrained>();
+        for index in 0..25 {
+            app.send_event(SpawnRequest {
+                name: Some(format!("Crate {index}")),
+                ..SpawnRequest::new("Crate", Transform::default())
+            });
+        }
+        let spawned_names = |app: &mut TestApp| {
+            let mut names: Vec<_> = app
+                .world_mut()
+                .query_filtered::<&Name, With<BlueprintName>>()
+                .iter(app.world())
+                .map(|name| name.as_str().to_string())
+                .collect();
+            names.sort_by_key(|name| name["Crate ".len()..].parse::<usize>().unwrap());
+            names
+        };
+        let expected =
+            |count: usize| -> Vec<_> { (0..count).map(|index| format!("Crate {index}")).collect() };
+
+        for count in [10, 20, 25] {
+            app.step(1);
+            assert_eq!(spawned_names(&mut app), expected(count));
+            assert_eq!(
+                app.events::<SpawnQueueDrained>().len(),
+                usize::from(count == 25)
+            );
+        }
+        app.step(1);
+        assert_eq!(spawned_names(&mut app).len(), 25);
+        assert_eq!(app.events::<SpawnQueueDrained>().len(), 1);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::{
+    determinism::{fnv1a, RngStream},
+    level_instantiation::map::LevelRoot,
+};
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet, Uuid},
+};
+use serde::{Deserialize, Serialize};
+
+/// Keeps the [`StableIdRegistry`] in sync with all entities that have a [`StableId`].
+/// Named entities in a level that were not given an id in the level file get one derived from the level and their [`Name`],
+/// so that they keep it across reloads.
+pub(crate) fn plugin(app: &mut App) {
+    app.register_type::<StableId>()
+        .init_resource::<StableIdRegistry>()
+        .add_systems(
+            PostUpdate,
+            (
+                derive_scene_ids.in_set(StableIdSystemSet::Derive),
+                update_registry.in_set(StableIdSystemSet::Register),
+            )
+                .chain(),
+        );
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemSet)]
+pub(crate) enum StableIdSystemSet {
+    /// Gives scene entities without a [`StableId`] their derived one.
+    Derive,
+    /// Updates the [`StableIdRegistry`]. Entities despawned before this never show up in it.
+    Register,
+}
+
+/// Identifies an entity across level reloads and saves, unlike its [`Entity`], which changes, or its [`Name`],
+/// which may be shared with other entities.
+/// Set it in the level file for entities that are referenced from elsewhere, otherwise it is assigned when spawning.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Component, Reflect, Serialize, Deserialize, Default,
+)]
+#[reflect(Component, Serialize, Deserialize, Hash, PartialEq)]
+pub struct StableId(pub Uuid);
+
+impl StableId {
+    pub(crate) fn from_rng(rng: &mut RngStream) -> Self {
+        Self(Uuid::from_u64_pair(rng.next_u64(), rng.next_u64()))
+    }
+
+    /// The id an entity named `name` in `level` gets when the level file does not give it one.
+    pub(crate) fn derive(level: &str, name: &str) -> Self {
+        Self(Uuid::from_u64_pair(
+            fnv1a(level.as_bytes()),
+            fnv1a(name.as_bytes()),
+        ))
+    }
+}
+
+/// Finds entities by their [`StableId`] and the other way around.
+/// When multiple entities share an id, a warning is logged once and the one registered first wins.
+#[derive(Debug, Default, Resource)]
+pub(crate) struct StableIdRegistry {
+    entities: HashMap<StableId, Entity>,
+    ids: HashMap<Entity, StableId>,
+    reported_duplicates: HashSet<StableId>,
+}
+
+impl StableIdRegistry {
+    pub(crate) fn get(&self, id: StableId) -> Option<Entity> {
+        self.entities.get(&id).copied()
+    }
+
+    pub(crate) fn id_of(&self, entity: Entity) -> Option<StableId> {
+        self.ids.get(&entity).copied()
+    }
+
+    fn insert(&mut self, entity: Entity, id: StableId, name: Option<&Name>) {
+        self.remove(entity);
+        match self.entities.get(&id) {
+            Some(&other) if other != entity => {
+                if self.reported_duplicates.insert(id) {
+                    let name = name.map_or_else(|| format!("{entity:?}"), |name| name.to_string());
+                    warn!(
+                        "\"{name}\" has the id {:?}, which {other:?} already has. \
+                        Give one of them a different `StableId` or `Name` in the level file",
+                        id.0
+                    );
+                }
+                return;
+            }
+            _ => {
+                self.entities.insert(id, entity);
+            }
+        }
+        self.ids.insert(entity, id);
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        let Some(id) = self.ids.remove(&entity) else {
+            return;
+        };
+        if self.entities.get(&id) == Some(&entity) {
+            self.entities.remove(&id);
+        }
+    }
+}
+
+fn derive_scene_ids(
+    mut commands: Commands,
+    unidentified: Query<(Entity, &Name), (Added<Name>, Without<StableId>)>,
+    parents: Query<&Parent>,
+    level_roots: Query<&LevelRoot>,
+) {
+    for (entity, name) in unidentified.iter() {
+        let Some(level) = parents
+            .iter_ancestors(entity)
+            .find_map(|ancestor| level_roots.get(ancestor).ok())
+        else {
+            continue;
+        };
+        commands
+            .entity(entity)
+            .insert(StableId::derive(&level.0, name.as_str()));
+    }
+}
+
+fn update_registry(
+    mut registry: ResMut<StableIdRegistry>,
+    ids: Query<(Entity, &StableId, Option<&Name>), Changed<StableId>>,
+    mut removed_ids: RemovedComponents<StableId>,
+) {
+    for entity in removed_ids.read() {
+        registry.remove(entity);
+    }
+    for (entity, id, name) in &ids {
+        registry.insert(entity, *id, name);
+    }
+}
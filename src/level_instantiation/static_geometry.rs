@@ -0,0 +1,353 @@
+use bevy::{
+    hierarchy::ValidParentCheckPlugin,
+    prelude::*,
+    transform::TransformSystem::TransformPropagate,
+    utils::{HashMap, HashSet},
+};
+use bevy_gltf_blueprints::{BlueprintName, SpawnHere};
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::iter;
+
+/// Freezes spawned objects that never move, e.g. walls, grass and most props, so that [`transform_propagation_plugin`]
+/// does not walk their hierarchies every frame. Once a blueprint has finished spawning, its whole subtree is marked
+/// [`StaticFrozen`] unless something in it or above it moves on its own: a [`RigidBody`] that is not static,
+/// an [`AnimationPlayer`] or a [`NotStatic`] marker. Their [`GlobalTransform`]s keep the values they had when frozen.
+///
+/// Moving an ancestor that is not frozen, e.g. the level root, still moves the frozen objects below it without thawing them.
+///
+/// A frozen object thaws again, has its transforms propagated and refreezes if it is still static when:
+/// - its [`Transform`] is edited, e.g. with the editor gizmo.
+/// - it is reparented or gets children that are not frozen.
+/// - it turns into a moving body, e.g. a fence segment that breaks.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<NotStatic>()
+        .register_type::<StaticFrozen>()
+        .add_systems(
+            PostUpdate,
+            (
+                thaw_changed_objects.before(TransformPropagate),
+                freeze_static_objects.after(TransformPropagate),
+            ),
+        );
+}
+
+/// Replaces Bevy's [`TransformPlugin`], see [`bevy_config`](crate::bevy_config).
+/// Propagates [`Transform`]s to [`GlobalTransform`]s like it does, but skips [`StaticFrozen`] subtrees.
+pub(crate) fn transform_propagation_plugin(app: &mut App) {
+    app.register_type::<Transform>()
+        .register_type::<GlobalTransform>()
+        .add_plugins(ValidParentCheckPlugin::<GlobalTransform>::default())
+        .add_systems(PostStartup, propagate_transforms.in_set(TransformPropagate))
+        .add_systems(PostUpdate, propagate_transforms.in_set(TransformPropagate));
+}
+
+/// Keeps an object that looks static from being frozen, e.g. a lift that is moved by a signal.
+/// Works on the blueprint, anywhere in it or on one of its ancestors.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Component, Reflect, Serialize, Deserialize, Default,
+)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct NotStatic;
+
+/// On every entity of a static object whose transforms are no longer propagated, see [`plugin`].
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect)]
+#[reflect(Component)]
+pub(crate) struct StaticFrozen {
+    /// The blueprint whose subtree was frozen together.
+    pub(crate) root: Entity,
+    /// The [`Transform`] when it was frozen, so that only real edits thaw it.
+    transform: Transform,
+}
+
+/// On a blueprint whose subtree is frozen once all of it has spawned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+struct PendingFreeze;
+
+type Movers<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Option<&'static RigidBody>,
+        Has<AnimationPlayer>,
+        Has<NotStatic>,
+    ),
+>;
+
+/// The entities below a root whose transforms are propagated.
+type PropagatedNodes<'w, 's> = Query<
+    'w,
+    's,
+    (Ref<'static, Transform>, &'static mut GlobalTransform),
+    (With<Parent>, Without<StaticFrozen>),
+>;
+
+/// The frozen entities, which are only propagated into when an ancestor moved.
+type FrozenNodes<'w, 's> = Query<
+    'w,
+    's,
+    (&'static Transform, &'static mut GlobalTransform),
+    (With<Parent>, With<StaticFrozen>),
+>;
+
+fn moves_on_its_own(movers: &Movers, entity: Entity) -> bool {
+    movers
+        .get(entity)
+        .is_ok_and(|(rigid_body, has_animation, not_static)| {
+            rigid_body.is_some_and(|rigid_body| !rigid_body.is_static())
+                || has_animation
+                || not_static
+        })
+}
+
+fn freeze_static_objects(
+    mut commands: Commands,
+    mut spawned: RemovedComponents<SpawnHere>,
+    blueprints: Query<(), With<BlueprintName>>,
+    pending: Query<Entity, With<PendingFreeze>>,
+    spawning: Query<(), With<SpawnHere>>,
+    movers: Movers,
+    children: Query<&Children>,
+    parents: Query<&Parent>,
+    transforms: Query<&Transform>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("freeze_static_objects").entered();
+    let candidates: HashSet<_> = spawned
+        .read()
+        .filter(|&entity| blueprints.contains(entity))
+        .chain(pending.iter())
+        .collect();
+    for root in candidates {
+        let subtree: Vec<_> = iter::once(root)
+            .chain(children.iter_descendants(root))
+            .collect();
+        // Nested blueprints may still be spawning
+        if subtree.iter().any(|&entity| spawning.contains(entity)) {
+            commands.entity(root).insert(PendingFreeze);
+            continue;
+        }
+        commands.entity(root).remove::<PendingFreeze>();
+        let moves = subtree
+            .iter()
+            .copied()
+            .chain(parents.iter_ancestors(root))
+            .any(|entity| moves_on_its_own(&movers, entity));
+        if moves {
+            continue;
+        }
+        for entity in subtree {
+            if let Ok(transform) = transforms.get(entity) {
+                commands.entity(entity).insert(StaticFrozen {
+                    root,
+                    transform: *transform,
+                });
+            }
+        }
+    }
+}
+
+fn thaw_changed_objects(
+    mut commands: Commands,
+    frozen: Query<
+        (
+            Entity,
+            &StaticFrozen,
+            Ref<Transform>,
+            Option<Ref<Parent>>,
+            Option<Ref<Children>>,
+        ),
+        Or<(
+            Changed<Transform>,
+            Changed<Parent>,
+            Changed<Children>,
+            Changed<RigidBody>,
+            Added<AnimationPlayer>,
+            Added<NotStatic>,
+        )>,
+    >,
+    is_frozen: Query<(), With<StaticFrozen>>,
+    movers: Movers,
+    children: Query<&Children>,
+) {
+    let mut roots: HashMap<Entity, Entity> = default();
+    for (entity, frozen, transform, parent, entity_children) in frozen.iter() {
+        let is_edited = transform.is_changed() && *transform != frozen.transform;
+        let is_reparented = parent.is_some_and(|parent| parent.is_changed());
+        let has_new_children = entity_children.is_some_and(|entity_children| {
+            entity_children.is_changed()
+                && entity_children
+                    .iter()
+                    .any(|&child| !is_frozen.contains(child))
+        });
+        if is_edited || is_reparented || has_new_children || moves_on_its_own(&movers, entity) {
+            roots.entry(frozen.root).or_insert(entity);
+        }
+    }
+    for (root, changed) in roots {
+        debug!("Thawing {root:?}, since {changed:?} changed");
+        for entity in iter::once(root).chain(children.iter_descendants(root)) {
+            if is_frozen.contains(entity) {
+                commands.entity(entity).remove::<StaticFrozen>();
+            }
+        }
+        if let Some(mut root_commands) = commands.get_entity(root) {
+            root_commands.insert(PendingFreeze);
+        }
+    }
+}
+
+/// Like the propagation of Bevy's [`TransformPlugin`], but without descending into [`StaticFrozen`] entities
+/// unless an ancestor of theirs moved.
+/// Runs on a single thread, which is cheaper than walking every frozen hierarchy on all of them.
+fn propagate_transforms(
+    mut roots: Query<
+        (Entity, Ref<Transform>, &mut GlobalTransform),
+        (Without<Parent>, Without<StaticFrozen>),
+    >,
+    mut nodes: PropagatedNodes,
+    mut frozen_nodes: FrozenNodes,
+    children: Query<&Children>,
+    parents: Query<Ref<Parent>>,
+    mut orphaned: RemovedComponents<Parent>,
+    mut orphaned_entities: Local<Vec<Entity>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("propagate_transforms").entered();
+    orphaned_entities.clear();
+    orphaned_entities.extend(orphaned.read());
+    orphaned_entities.sort_unstable();
+    for (entity, transform, mut global_transform) in roots.iter_mut() {
+        let changed = transform.is_changed()
+            || global_transform.is_added()
+            || orphaned_entities.binary_search(&entity).is_ok();
+        if changed {
+            *global_transform = GlobalTransform::from(*transform);
+        }
+        let Ok(root_children) = children.get(entity) else {
+            continue;
+        };
+        for &child in root_children {
+            propagate_recursive(
+                *global_transform,
+                child,
+                changed,
+                &mut nodes,
+                &mut frozen_nodes,
+                &children,
+                &parents,
+            );
+        }
+    }
+}
+
+fn propagate_recursive(
+    parent_transform: GlobalTransform,
+    entity: Entity,
+    mut changed: bool,
+    nodes: &mut PropagatedNodes,
+    frozen_nodes: &mut FrozenNodes,
+    children: &Query<&Children>,
+    parents: &Query<Ref<Parent>>,
+) {
+    changed |= parents.get(entity).is_ok_and(|parent| parent.is_changed());
+    let global_transform = if let Ok((transform, mut global_transform)) = nodes.get_mut(entity) {
+        changed |= transform.is_changed() || global_transform.is_added();
+        if changed {
+            *global_transform = parent_transform.mul_transform(*transform);
+        }
+        *global_transform
+    } else if changed {
+        // A moved ancestor carries frozen subtrees along, they stay frozen
+        let Ok((transform, mut global_transform)) = frozen_nodes.get_mut(entity) else {
+            return;
+        };
+        *global_transform = parent_transform.mul_transform(*transform);
+        *global_transform
+    } else {
+        // Frozen subtrees end here
+        return;
+    };
+    let Ok(entity_children) = children.get(entity) else {
+        return;
+    };
+    for &child in entity_children {
+        propagate_recursive(
+            global_transform,
+            child,
+            changed,
+            nodes,
+            frozen_nodes,
+            children,
+            parents,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    fn spawn_blueprint(app: &mut TestApp, translation: Vec3, parent: Entity) -> (Entity, Entity) {
+        let world = app.world_mut();
+        let root = world
+            .spawn((
+                BlueprintName("Wall".to_string()),
+                SpawnHere,
+                SpatialBundle::from_transform(Transform::from_translation(translation)),
+            ))
+            .set_parent(parent)
+            .id();
+        let mesh = world
+            .spawn(SpatialBundle::from_transform(Transform::from_xyz(
+                0., 1., 0.,
+            )))
+            .set_parent(root)
+            .id();
+        (root, mesh)
+    }
+
+    fn global_translation(app: &TestApp, entity: Entity) -> Vec3 {
+        app.world()
+            .get::<GlobalTransform>(entity)
+            .unwrap()
+            .translation()
+    }
+
+    #[test]
+    fn spawned_static_objects_stop_propagating_until_edited() {
+        let mut app = TestApp::new();
+        app.add_plugins(plugin);
+        let level = app.world_mut().spawn(SpatialBundle::default()).id();
+        let (wall, wall_mesh) = spawn_blueprint(&mut app, Vec3::X, level);
+        let (lift, lift_mesh) = spawn_blueprint(&mut app, Vec3::Z, level);
+        app.world_mut().entity_mut(lift).insert(NotStatic);
+        app.step(1);
+        for blueprint in [wall, lift] {
+            app.world_mut().entity_mut(blueprint).remove::<SpawnHere>();
+        }
+        app.step(1);
+        assert!(app.world().get::<StaticFrozen>(wall_mesh).is_some());
+        assert!(app.world().get::<StaticFrozen>(lift_mesh).is_none());
+
+        // Moving the level moves everything below it, and the wall stays frozen
+        app.world_mut()
+            .get_mut::<Transform>(level)
+            .unwrap()
+            .translation = Vec3::Y * 10.;
+        app.step(1);
+        assert_eq!(global_translation(&app, wall_mesh), Vec3::new(1., 11., 0.));
+        assert_eq!(global_translation(&app, lift_mesh), Vec3::new(0., 11., 1.));
+        assert!(app.world().get::<StaticFrozen>(wall_mesh).is_some());
+
+        // An edit is applied and the wall is frozen again right away
+        app.world_mut()
+            .get_mut::<Transform>(wall)
+            .unwrap()
+            .translation = Vec3::X * 2.;
+        app.step(1);
+        assert_eq!(global_translation(&app, wall_mesh), Vec3::new(2., 11., 0.));
+        assert!(app.world().get::<StaticFrozen>(wall_mesh).is_some());
+    }
+}
@@ -0,0 +1,199 @@
+use crate::world_interaction::dialog::{commands::DialogPosition, YarnCommandsAppExt};
+use bevy::{prelude::*, utils::HashMap};
+use bevy_yarnspinner::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Keeps the [`TagRegistry`] in sync with all entities that have [`Tags`], so that scripting can act on groups of entities.
+/// Registers the yarn command `<<despawn_tagged tag>>`, which sends a [`DespawnTaggedEvent`].
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Tags>()
+        .init_resource::<TagRegistry>()
+        .add_event::<DespawnTaggedEvent>()
+        .add_yarn_commands(register_commands)
+        .add_systems(Update, despawn_tagged)
+        .add_systems(PostUpdate, update_registry);
+}
+
+fn register_commands(dialogue_runner: &mut DialogueRunner) {
+    dialogue_runner
+        .commands_mut()
+        .add_command("despawn_tagged", despawn_tagged_command);
+}
+
+/// Groups that an entity belongs to, e.g. `["props", "breakable"]`. Set in the level file or through
+/// [`SpawnRequest::tags`](crate::level_instantiation::spawn_queue::SpawnRequest::tags).
+/// Tags are written without the `#` that the dev tools and yarn commands accept in front of them.
+#[derive(Debug, Clone, PartialEq, Eq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+pub(crate) struct Tags(pub(crate) Vec<String>);
+
+/// Strips the `#` in front of a tag, so that `#props` and `props` are the same tag.
+pub(crate) fn normalize(tag: &str) -> &str {
+    tag.trim().trim_start_matches('#')
+}
+
+/// Finds entities by their [`Tags`] in constant time. Updated in [`PostUpdate`], so entities tagged
+/// or despawned this frame only show up or disappear from the next frame on. Entities may therefore have been despawned
+/// in the meantime, which [`Commands::get_entity`] guards against.
+#[derive(Debug, Default, Resource)]
+pub(crate) struct TagRegistry {
+    by_tag: HashMap<String, Vec<Entity>>,
+    tags: HashMap<Entity, Vec<String>>,
+}
+
+impl TagRegistry {
+    /// All entities with the tag, in the order they got it.
+    /// [`Entity`] indices are recycled, so this is not the order of their ids.
+    pub(crate) fn entities(&self, tag: &str) -> &[Entity] {
+        self.by_tag.get(normalize(tag)).map_or(&[], Vec::as_slice)
+    }
+
+    pub(crate) fn count(&self, tag: &str) -> usize {
+        self.entities(tag).len()
+    }
+
+    pub(crate) fn tags_of(&self, entity: Entity) -> &[String] {
+        self.tags.get(&entity).map_or(&[], Vec::as_slice)
+    }
+
+    /// Every tag in use together with how many entities have it.
+    pub(crate) fn all(&self) -> impl Iterator<Item = (&str, usize)> + '_ {
+        self.by_tag
+            .iter()
+            .map(|(tag, entities)| (tag.as_str(), entities.len()))
+    }
+
+    fn insert(&mut self, entity: Entity, tags: &Tags) {
+        let mut own_tags: Vec<String> = Vec::with_capacity(tags.0.len());
+        for tag in tags.0.iter().map(|tag| normalize(tag)) {
+            if !tag.is_empty() && !own_tags.iter().any(|own| own == tag) {
+                own_tags.push(tag.to_string());
+            }
+        }
+        let old_tags = self
+            .tags
+            .insert(entity, own_tags.clone())
+            .unwrap_or_default();
+        for tag in old_tags.iter().filter(|tag| !own_tags.contains(tag)) {
+            self.remove_from_tag(entity, tag);
+        }
+        // Tags the entity already had keep its place among the other entities with them
+        for tag in own_tags.into_iter().filter(|tag| !old_tags.contains(tag)) {
+            self.by_tag.entry(tag).or_default().push(entity);
+        }
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        let Some(tags) = self.tags.remove(&entity) else {
+            return;
+        };
+        for tag in tags {
+            self.remove_from_tag(entity, &tag);
+        }
+    }
+
+    fn remove_from_tag(&mut self, entity: Entity, tag: &str) {
+        if let Some(entities) = self.by_tag.get_mut(tag) {
+            entities.retain(|&other| other != entity);
+            if entities.is_empty() {
+                self.by_tag.remove(tag);
+            }
+        }
+    }
+}
+
+/// Despawns every entity with the tag, together with its children.
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub(crate) struct DespawnTaggedEvent {
+    pub(crate) tag: String,
+}
+
+fn update_registry(
+    mut registry: ResMut<TagRegistry>,
+    tags: Query<(Entity, &Tags), Changed<Tags>>,
+    mut removed_tags: RemovedComponents<Tags>,
+) {
+    for entity in removed_tags.read() {
+        registry.remove(entity);
+    }
+    for (entity, tags) in &tags {
+        registry.insert(entity, tags);
+    }
+}
+
+fn despawn_tagged(
+    mut commands: Commands,
+    mut despawn_events: EventReader<DespawnTaggedEvent>,
+    registry: Res<TagRegistry>,
+) {
+    for event in despawn_events.read() {
+        for &entity in registry.entities(&event.tag) {
+            if let Some(entity) = commands.get_entity(entity) {
+                entity.despawn_recursive();
+            }
+        }
+    }
+}
+
+fn despawn_tagged_command(
+    In(tag): In<String>,
+    registry: Res<TagRegistry>,
+    position: Res<DialogPosition>,
+    mut despawn_events: EventWriter<DespawnTaggedEvent>,
+) {
+    if registry.count(&tag) == 0 {
+        warn!(
+            "<<despawn_tagged {tag}>> in {}: nothing is tagged \"{}\"",
+            *position,
+            normalize(&tag)
+        );
+    }
+    despawn_events.send(DespawnTaggedEvent { tag });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    #[test]
+    fn registry_follows_tag_changes_and_despawns() {
+        let mut app = TestApp::new();
+        app.add_plugins(plugin);
+        let tags = |tags: &[&str]| Tags(tags.iter().map(|tag| tag.to_string()).collect());
+        let crate_entity = app.world_mut().spawn(tags(&["props", "#wooden"])).id();
+        let barrel = app.world_mut().spawn(tags(&["props"])).id();
+        app.step(1);
+        let registry = app.resource::<TagRegistry>();
+        assert_eq!(registry.entities("#props"), &[crate_entity, barrel]);
+        assert_eq!(registry.entities("wooden"), &[crate_entity]);
+
+        // Keeping a tag keeps the entity's place, a new one puts it last
+        *app.world_mut().get_mut::<Tags>(crate_entity).unwrap() = tags(&["props"]);
+        app.world_mut()
+            .get_mut::<Tags>(barrel)
+            .unwrap()
+            .0
+            .push("wooden".to_string());
+        app.step(1);
+        let registry = app.resource::<TagRegistry>();
+        assert_eq!(registry.entities("props"), &[crate_entity, barrel]);
+        assert_eq!(registry.entities("wooden"), &[barrel]);
+
+        *app.world_mut().get_mut::<Tags>(barrel).unwrap() = tags(&["enemies"]);
+        app.step(1);
+        let registry = app.resource::<TagRegistry>();
+        assert_eq!(registry.entities("props"), &[crate_entity]);
+        assert_eq!(registry.count("enemies"), 1);
+
+        app.send_event(DespawnTaggedEvent {
+            tag: "#props".to_string(),
+        });
+        app.step(1);
+        assert!(app.world().get_entity(crate_entity).is_none());
+        let registry = app.resource::<TagRegistry>();
+        assert_eq!(registry.count("props"), 0);
+        assert_eq!(registry.count("wooden"), 0);
+        assert_eq!(registry.tags_of(barrel), &["enemies".to_string()]);
+    }
+}
@@ -0,0 +1,273 @@
+#[cfg(feature = "dev")]
+use crate::dev::dev_tools::{DevTools, RegisterDevToolExt};
+use crate::{
+    level_instantiation::{
+        on_spawn::{parse_property, PropertyError},
+        stable_id::StableId,
+    },
+    player_control::ui_layer::UiLayer,
+    GameState,
+};
+use bevy::{
+    gltf::{Gltf, GltfExtras},
+    prelude::*,
+    reflect::{serde::TypedReflectDeserializer, TypeRegistry},
+    utils::HashMap,
+};
+use bevy_egui::{egui, EguiContexts};
+use std::{collections::BTreeMap, fmt};
+
+/// Scales beyond this in any direction are most likely a mistake.
+const MAX_SCALE: f32 = 1000.;
+const MIN_SCALE: f32 = 1e-4;
+
+/// Checks every GLTF file for mistakes in its Blender components once it is loaded, before anything of it is spawned:
+/// unknown component names, payloads that do not deserialize, unknown or malformed `foxtrot:` custom properties,
+/// duplicate [`StableId`]s and broken transforms.
+/// Problems are logged and listed on the loading screen and in the menu instead of failing silently at spawn time.
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<LevelValidation>()
+        .add_systems(Update, validate_loaded_levels)
+        .add_systems(
+            Update,
+            display_validation_errors
+                .after(validate_loaded_levels)
+                .run_if(in_state(GameState::Loading).or_else(in_state(GameState::Menu))),
+        );
+    #[cfg(feature = "dev")]
+    app.register_dev_tool("Level Validation", None, reopen_validation_report)
+        .add_systems(
+            Update,
+            display_validation_errors
+                .after(validate_loaded_levels)
+                .run_if(
+                    in_state(GameState::Playing).and_then(|dev_tools: Res<DevTools>| {
+                        dev_tools.is_active("Level Validation")
+                    }),
+                ),
+        );
+}
+
+/// The problems found in each loaded GLTF file, by path.
+#[derive(Debug, Default, Resource)]
+pub(crate) struct LevelValidation {
+    pub(crate) reports: BTreeMap<String, Vec<ValidationError>>,
+    dismissed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ValidationError {
+    /// The name of the GLTF node, i.e. the object in Blender.
+    pub(crate) node: String,
+    pub(crate) problem: ValidationProblem,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ValidationProblem {
+    /// The extras are not a JSON object of component names to RON strings.
+    MalformedExtras(String),
+    UnknownComponent(String),
+    /// See [`BlenderProperty`](crate::level_instantiation::on_spawn::blender_properties::BlenderProperty).
+    InvalidProperty(PropertyError),
+    InvalidPayload {
+        component: String,
+        /// Includes the line and column within the payload.
+        error: String,
+    },
+    DuplicateStableId {
+        id: StableId,
+        other_node: String,
+    },
+    InvalidTransform(String),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let node = &self.node;
+        match &self.problem {
+            ValidationProblem::MalformedExtras(error) => {
+                write!(f, "{node}: custom properties are malformed: {error}")
+            }
+            ValidationProblem::UnknownComponent(component) => write!(
+                f,
+                "{node}: unknown component \"{component}\". Is it misspelled or not registered?"
+            ),
+            ValidationProblem::InvalidProperty(error) => write!(f, "{node}: {error}"),
+            ValidationProblem::InvalidPayload { component, error } => {
+                write!(f, "{node}: component \"{component}\" is invalid: {error}")
+            }
+            ValidationProblem::DuplicateStableId { id, other_node } => write!(
+                f,
+                "{node}: has the same StableId {:?} as {other_node}",
+                id.0
+            ),
+            ValidationProblem::InvalidTransform(problem) => {
+                write!(f, "{node}: transform {problem}")
+            }
+        }
+    }
+}
+
+/// Checks all entities of a scene loaded from a GLTF file.
+pub(crate) fn validate_scene(scene: &Scene, registry: &TypeRegistry) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    let mut stable_ids = HashMap::<StableId, String>::new();
+    for entity in scene.world.iter_entities() {
+        let node = entity
+            .get::<Name>()
+            .map_or_else(|| format!("{:?}", entity.id()), |name| name.to_string());
+        let mut report = |problem| {
+            errors.push(ValidationError {
+                node: node.clone(),
+                problem,
+            })
+        };
+
+        if let Some(transform) = entity.get::<Transform>() {
+            if let Some(problem) = validate_transform(transform) {
+                report(ValidationProblem::InvalidTransform(problem));
+            }
+        }
+
+        let Some(extras) = entity.get::<GltfExtras>() else {
+            continue;
+        };
+        let components =
+            match serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&extras.value)
+            {
+                Ok(components) => components,
+                Err(error) => {
+                    report(ValidationProblem::MalformedExtras(error.to_string()));
+                    continue;
+                }
+            };
+        for (component, payload) in components {
+            if let Some(property) = parse_property(&component, &payload) {
+                if let Err(error) = property {
+                    report(ValidationProblem::InvalidProperty(error));
+                }
+                continue;
+            }
+            let Some(registration) = registry
+                .get_with_short_type_path(&component)
+                .or_else(|| registry.get_with_type_path(&component))
+            else {
+                report(ValidationProblem::UnknownComponent(component));
+                continue;
+            };
+            let payload = match payload.as_str() {
+                Some("") => "()".to_string(),
+                Some(payload) => payload.to_string(),
+                None => payload.to_string(),
+            };
+            let deserializer = TypedReflectDeserializer::new(registration, registry);
+            let value = match ron::Options::default().from_str_seed(&payload, deserializer) {
+                Ok(value) => value,
+                Err(error) => {
+                    report(ValidationProblem::InvalidPayload {
+                        component,
+                        error: error.to_string(),
+                    });
+                    continue;
+                }
+            };
+            if let Some(id) = StableId::from_reflect(value.as_reflect()) {
+                if let Some(other_node) = stable_ids.insert(id, node.clone()) {
+                    report(ValidationProblem::DuplicateStableId { id, other_node });
+                }
+            }
+        }
+    }
+    errors
+}
+
+fn validate_transform(transform: &Transform) -> Option<String> {
+    if !transform.translation.is_finite() {
+        return Some(format!(
+            "has a non-finite translation {}",
+            transform.translation
+        ));
+    }
+    if !transform.rotation.is_finite() || !transform.rotation.is_normalized() {
+        return Some(format!("has an invalid rotation {}", transform.rotation));
+    }
+    let scale = transform.scale.abs();
+    if !scale.is_finite() || scale.max_element() > MAX_SCALE || scale.min_element() < MIN_SCALE {
+        return Some(format!("has an absurd scale {}", transform.scale));
+    }
+    None
+}
+
+fn validate_loaded_levels(
+    mut gltf_events: EventReader<AssetEvent<Gltf>>,
+    gltfs: Res<Assets<Gltf>>,
+    scenes: Res<Assets<Scene>>,
+    asset_server: Res<AssetServer>,
+    type_registry: Res<AppTypeRegistry>,
+    mut validation: ResMut<LevelValidation>,
+) {
+    for event in gltf_events.read() {
+        let (AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }) = event
+        else {
+            continue;
+        };
+        let Some(gltf) = gltfs.get(*id) else {
+            continue;
+        };
+        let path = asset_server
+            .get_path(*id)
+            .map_or_else(|| format!("{id:?}"), |path| path.to_string());
+        let registry = type_registry.read();
+        let errors: Vec<_> = gltf
+            .scenes
+            .iter()
+            .filter_map(|scene| scenes.get(scene))
+            .flat_map(|scene| validate_scene(scene, &registry))
+            .collect();
+        for error in &errors {
+            warn!("Invalid level {path}: {error}");
+        }
+        if errors.is_empty() {
+            validation.reports.remove(&path);
+        } else {
+            validation.reports.insert(path, errors);
+            validation.dismissed = false;
+        }
+    }
+}
+
+fn display_validation_errors(
+    mut validation: ResMut<LevelValidation>,
+    mut egui_contexts: EguiContexts,
+) {
+    if validation.dismissed || validation.reports.is_empty() {
+        return;
+    }
+    let mut dismissed = false;
+    egui::Window::new("Level Problems")
+        .order(UiLayer::Dev.order())
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0., 20.))
+        .default_width(600.)
+        .show(egui_contexts.ctx_mut(), |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(400.)
+                .show(ui, |ui| {
+                    for (path, errors) in &validation.reports {
+                        ui.heading(path);
+                        for error in errors {
+                            ui.label(error.to_string());
+                        }
+                    }
+                });
+            dismissed = ui.button("Dismiss").clicked();
+        });
+    validation.dismissed = dismissed;
+}
+
+#[cfg(feature = "dev")]
+fn reopen_validation_report(In(active): In<bool>, mut validation: ResMut<LevelValidation>) {
+    if active {
+        validation.dismissed = false;
+    }
+}
@@ -12,9 +12,12 @@
 
 use bevy::prelude::*;
 mod bevy_config;
+mod determinism;
 #[cfg(feature = "dev")]
 mod dev;
 mod file_system_interaction;
+mod gameplay_log;
+mod graphics;
 mod ingame_menu;
 mod level_instantiation;
 mod menu;
@@ -22,11 +25,15 @@ pub(crate) mod movement;
 pub(crate) mod particles;
 mod player_control;
 mod shader;
+#[cfg(feature = "telemetry")]
+mod telemetry;
+#[cfg(test)]
+mod testing;
 pub(crate) mod util;
 mod world_interaction;
 
 #[derive(States, Default, Clone, Eq, PartialEq, Debug, Hash)]
-enum GameState {
+pub enum GameState {
     /// During the loading State the loading_plugin will load our assets
     #[default]
     Loading,
@@ -40,6 +47,7 @@ enum GameState {
 ///
 /// The top-level plugins are:
 /// - [`bevy_config::plugin`]: Sets up the bevy configuration.
+/// - [`determinism::plugin`]: Handles random seeds and input replays.
 /// - [`menu::plugin`]: Handles the menu.
 /// - [`movement::plugin`]: Handles the movement of entities.
 /// - [`player_control::plugin`]: Handles the player's control.
@@ -50,12 +58,21 @@ enum GameState {
 /// - [`dev::plugin`]: Handles the dev tools.
 /// - [`ingame_menu::plugin`]: Handles the ingame menu accessed via ESC.
 /// - [`particles::plugin`]: Handles the particle system.
+/// - [`graphics::plugin`]: Handles graphics settings like shadows, and decals.
+/// - [`gameplay_log::plugin`]: Records gameplay events for debugging.
+/// - [`telemetry::plugin`]: Records playtesting sessions into local files.
 pub struct GamePlugin;
 
+pub use level_instantiation::{
+    spawn_queue::{SpawnBudget, SpawnQueueDrained, SpawnRequest},
+    stable_id::StableId,
+};
+
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.init_state::<GameState>().add_plugins((
             bevy_config::plugin,
+            determinism::plugin,
             menu::plugin,
             movement::plugin,
             player_control::plugin,
@@ -65,8 +82,12 @@ impl Plugin for GamePlugin {
             shader::plugin,
             ingame_menu::plugin,
             particles::plugin,
+            graphics::plugin,
+            gameplay_log::plugin,
             #[cfg(feature = "dev")]
             dev::plugin,
+            #[cfg(feature = "telemetry")]
+            telemetry::plugin,
         ));
     }
 }
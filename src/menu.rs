@@ -1,4 +1,7 @@
-use crate::GameState;
+use crate::{
+    file_system_interaction::localization::{t, Strings},
+    GameState,
+};
 use bevy::prelude::*;
 use bevy_egui::{
     egui,
@@ -16,7 +19,11 @@ pub(super) fn plugin(app: &mut App) {
     app.add_systems(Update, setup_menu.run_if(in_state(GameState::Menu)));
 }
 
-fn setup_menu(mut egui_contexts: EguiContexts, mut next_state: ResMut<NextState<GameState>>) {
+fn setup_menu(
+    mut egui_contexts: EguiContexts,
+    mut next_state: ResMut<NextState<GameState>>,
+    strings: Strings,
+) {
     get_menu_panel().show(egui_contexts.ctx_mut(), |ui| {
         set_menu_style(ui.style_mut());
         ui.vertical_centered_justified(|ui| {
@@ -24,7 +31,7 @@ fn setup_menu(mut egui_contexts: EguiContexts, mut next_state: ResMut<NextState<
             ui.heading("Foxtrot");
             ui.separator();
             ui.add_space(50.);
-            if ui.button("Play").clicked() {
+            if ui.button(t!(strings, "menu.play")).clicked() {
                 next_state.set(GameState::Playing);
             }
         })
@@ -2,8 +2,11 @@ use bevy::prelude::*;
 
 pub(crate) mod character_controller;
 
-mod navigation;
+pub(crate) mod jump_pad;
+pub(crate) mod navigation;
 pub(crate) mod physics;
+pub(crate) mod slow_motion;
+pub(crate) mod water;
 
 /// This plugin handles all physical movement that is not exclusive to the player.
 /// It is further split into the following sub-plugins:
@@ -11,11 +14,17 @@ pub(crate) mod physics;
 /// - [`character_controller::plugin`]: Handles kinematic character controller movement. A "character" in
 /// this sense is anything that behaves in a not-quite completely physical way, like a player, an npc, an elevator, a moving platform, etc.
 /// Contrast this with pure rigidbodies like a ball, a crate, etc.
-/// - [`navigation::plugin`]: Handles npc pathfinding via bevy_pathmesh integration.
+/// - [`jump_pad::plugin`]: Launches characters and props that land on jump pads.
+/// - [`navigation::plugin`]: Handles npc pathfinding via oxidized_navigation integration.
+/// - [`slow_motion::plugin`]: Slows the game and its physics down for dramatic moments.
+/// - [`water::plugin`]: Makes props float in water and currents carry them and characters along.
 pub(super) fn plugin(app: &mut App) {
     app.add_plugins((
         physics::plugin,
         character_controller::plugin,
+        jump_pad::plugin,
         navigation::plugin,
+        slow_motion::plugin,
+        water::plugin,
     ));
 }
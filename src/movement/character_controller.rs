@@ -1,28 +1,93 @@
-use crate::GameState;
-pub(crate) use animation::AnimationState;
+use crate::{
+    level_instantiation::on_spawn::SurfaceGrip,
+    movement::water::{swimming_drift, Submerged},
+    util::math_trait_ext::Vec3Ext,
+    GameState,
+};
+pub(crate) use animation::{AnimationState, HeldAnimation, PlayOneShotAnimation};
 use bevy::prelude::*;
-use bevy_tnua::prelude::*;
+use bevy_tnua::{prelude::*, TnuaProximitySensor};
 use bevy_tnua_xpbd3d::*;
-use bevy_xpbd_3d::PhysicsSet;
+use bevy_xpbd_3d::{prelude::*, PhysicsSet};
 pub(crate) use components::*;
+pub(crate) use depenetration::{deepest_push, CharacterStuckEvent, Depenetrate};
+pub(crate) use gravity::{CharacterGravityScale, GravityChange, GravityZone};
+pub(crate) use grounding::{FootstepEvent, GroundedState, LandedEvent, LeftGroundEvent};
+pub(crate) use hitbox::{HitboxOverlapEvent, TimedHitbox};
+pub(crate) use ledge_grab::{LedgeGrab, LedgeHang};
+pub(crate) use modifiers::{
+    ActiveMovementModifiers, LevelMovementModifiers, MovementModifierZone, MovementModifiers,
+    RestoredMovementModifiers,
+};
+pub(crate) use platforms::Carried;
+pub(crate) use profiles::{MovementConfig, MovementProfile};
+pub(crate) use separation::{PushPriority, SeparationPush};
+pub(crate) use stamina::Stamina;
+use stopping::Stopping;
+pub(crate) use turn_in_place::RotationMode;
+use turn_in_place::TurningInPlace;
 
 mod animation;
 mod components;
+mod depenetration;
+mod gravity;
+mod grounding;
+mod hitbox;
+mod ledge_grab;
 mod models;
+mod modifiers;
+mod platforms;
+mod profiles;
+mod separation;
+mod stamina;
+mod stopping;
+mod tunneling;
+mod turn_in_place;
+
+/// How fast characters slide down ground that is too steep to walk on, in m/s.
+const SLIDE_SPEED: f32 = 6.;
 
 /// This plugin communicates with the Tnua character controller by propagating settings found in
 /// the control components [`Walk`] and [`Jump`]. It also controls a state machine to determine which animations to play.
+/// The tuning of these components comes from the [`MovementProfile`] of each character, see [`profiles::plugin`].
+/// Zones and levels can change the rules on top of that, see [`modifiers::plugin`].
+/// Sprinting and climbing wear characters out, see [`stamina::plugin`].
+/// Characters ride along on ground that is moved without physics, see [`platforms::plugin`].
 pub(super) fn plugin(app: &mut App) {
-    app.add_plugins((components::plugin, animation::plugin, models::plugin))
-        .add_plugins((TnuaXpbd3dPlugin::default(), TnuaControllerPlugin::default()))
-        .add_systems(
-            Update,
-            (apply_jumping, apply_walking)
-                .chain()
-                .in_set(GeneralMovementSystemSet)
-                .before(PhysicsSet::Prepare)
-                .run_if(in_state(GameState::Playing)),
-        );
+    app.add_plugins((
+        components::plugin,
+        animation::plugin,
+        models::plugin,
+        depenetration::plugin,
+        gravity::plugin,
+        grounding::plugin,
+        hitbox::plugin,
+        ledge_grab::plugin,
+        modifiers::plugin,
+        platforms::plugin,
+        profiles::plugin,
+        separation::plugin,
+        stamina::plugin,
+        tunneling::plugin,
+        turn_in_place::plugin,
+    ))
+    .add_plugins((TnuaXpbd3dPlugin::default(), TnuaControllerPlugin::default()))
+    .add_systems(
+        Update,
+        (
+            gravity::apply_gravity,
+            apply_jumping,
+            turn_in_place::update_turning_in_place,
+            stopping::update_stops,
+            stamina::update_stamina,
+            apply_walking,
+            update_movement_stats,
+        )
+            .chain()
+            .in_set(GeneralMovementSystemSet)
+            .before(PhysicsSet::Prepare)
+            .run_if(in_state(GameState::Playing)),
+    );
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
@@ -34,39 +99,152 @@ fn apply_walking(
         &mut Walk,
         Option<&Sprinting>,
         &FloatHeight,
+        Option<&Submerged>,
+        Option<&SeparationPush>,
+        Option<&TnuaProximitySensor>,
+        Option<&TurningInPlace>,
+        Option<&Stopping>,
+        Option<&Stamina>,
+        Option<&ActiveMovementModifiers>,
+        Option<&GroundedState>,
     )>,
+    grips: Query<&SurfaceGrip>,
 ) {
     #[cfg(feature = "tracing")]
     let _span = info_span!("apply_walking").entered();
-    for (mut controller, mut walking, sprinting, float_height) in &mut character_query {
+    for (
+        mut controller,
+        mut walking,
+        sprinting,
+        float_height,
+        submerged,
+        push,
+        sensor,
+        turning,
+        stopping,
+        stamina,
+        modifiers,
+        grounded,
+    ) in &mut character_query
+    {
+        let modifiers = modifiers.map(|modifiers| modifiers.0).unwrap_or_default();
+        // The horizontal part of a slope's normal points downhill
+        let downhill = grounded
+            .filter(|grounded| grounded.sliding)
+            .map(|grounded| grounded.ground_normal.horizontal().normalize_or_zero());
         let direction = walking.direction.unwrap_or_default();
-        let sprinting_multiplier = sprinting
-            .filter(|s| s.requested)
-            .map(|s| s.multiplier)
-            .unwrap_or(1.);
-        let speed = walking.speed * sprinting_multiplier;
+        // Walking up a slope that is too steep does nothing, walking along it still steers the slide
+        let direction = downhill.map_or(direction, |downhill| {
+            direction + downhill * direction.dot(-downhill).max(0.)
+        });
+        let slide = downhill.map_or(Vec3::ZERO, |downhill| downhill * SLIDE_SPEED);
+        let (sprinting_multiplier, sprinting_acceleration) = sprinting
+            .filter(|s| s.requested && !modifiers.disable_sprint)
+            .filter(|_| stamina.map_or(true, Stamina::can_sprint))
+            .map_or((1., 1.), |s| (s.multiplier, s.acceleration));
+        let stamina_factor = stamina.map_or(1., Stamina::speed_factor);
+        let speed = walking.update_gait(direction.length())
+            * sprinting_multiplier
+            * modifiers.speed
+            * stamina_factor;
+        // Tnua would cancel out a current applied as a force, so it is part of the velocity the character aims for instead
+        let drift = swimming_drift(submerged);
+        let push = push.map_or(Vec3::ZERO, |push| push.0);
+        let grip = sensor
+            .and_then(|sensor| sensor.output.as_ref())
+            .and_then(|output| grips.get(output.entity).ok())
+            .map_or(1., |grip| grip.0.max(0.));
+        let is_standing = direction.is_approx_zero();
+        let stopping = stopping.filter(|_| is_standing);
+        let turning = turning.filter(|_| is_standing && stopping.is_none());
+        let walk_velocity =
+            stopping.map_or(direction.normalize_or_zero() * speed, Stopping::velocity);
+        let deceleration = stopping.map_or(walking.deceleration, Stopping::deceleration);
+        let defaults = TnuaBuiltinWalk::default();
         controller.basis(TnuaBuiltinWalk {
-            desired_velocity: direction * speed,
-            desired_forward: direction.normalize_or_zero(),
+            desired_velocity: walk_velocity + drift + push + slide,
+            desired_forward: turning.map_or(
+                walking
+                    .keep_facing
+                    .unwrap_or_else(|| direction.normalize_or_zero()),
+                |turning| turning.target,
+            ),
+            turning_angvel: turning
+                .map_or(defaults.turning_angvel, |turning| turning.angular_speed),
             float_height: float_height.0,
             cling_distance: 0.1,
-            ..Default::default()
+            max_slope: walking.max_slope_angle,
+            acceleration: grip
+                * if is_standing {
+                    deceleration
+                } else {
+                    walking.acceleration * modifiers.acceleration * sprinting_acceleration
+                },
+            ..defaults
         });
         walking.direction = None;
+        walking.facing = None;
+        walking.keep_facing = None;
     }
 }
 
-fn apply_jumping(mut character_query: Query<(&mut TnuaController, &mut Jump)>) {
+fn apply_jumping(
+    time: Res<Time>,
+    mut character_query: Query<(
+        &mut TnuaController,
+        &mut Jump,
+        &GroundedState,
+        &mut LinearVelocity,
+        Option<&ActiveMovementModifiers>,
+    )>,
+) {
     #[cfg(feature = "tracing")]
     let _span = info_span!("apply_jumping").entered();
-    for (mut controller, mut jump) in &mut character_query {
-        if jump.requested {
-            controller.action(TnuaBuiltinJump {
-                height: jump.height,
+    let dt = time.delta_seconds();
+    for (mut controller, mut jump, grounded, mut velocity, modifiers) in &mut character_query {
+        let modifiers = modifiers.map(|modifiers| modifiers.0).unwrap_or_default();
+        if modifiers.disable_jump {
+            // A jump requested where jumping is disabled is dropped instead of happening once it is allowed again
+            jump.cancel_request();
+            continue;
+        }
+        let is_jumping = controller.concrete_action::<TnuaBuiltinJump>().is_some();
+        let control =
+            jump.update_request(dt, is_jumping, grounded.time_since_grounded, velocity.0.y);
+        match control {
+            JumpControl::Hold => controller.action(TnuaBuiltinJump {
+                height: modifiers.jump_height(jump.height),
                 takeoff_extra_gravity: 10.0,
+                // The coyote time is handled by the Jump itself
+                allow_in_air: true,
+                // Letting go early is handled by the Jump as well, so that a jump that was held long enough keeps its full arc
+                shorten_extra_gravity: 0.,
+                // Braking right before the apex would reach it earlier than a jump tuned by its time to apex promises
+                peak_prevention_extra_gravity: if jump.gravity.is_some() { 0. } else { 20. },
                 ..Default::default()
-            });
-            jump.requested = false;
+            }),
+            JumpControl::Cut => velocity.0.y *= jump.cut_factor,
+            JumpControl::Release => {}
+        }
+    }
+}
+
+fn update_movement_stats(
+    gravity: Res<Gravity>,
+    mut character_query: Query<(
+        &Walk,
+        Option<&Sprinting>,
+        &Jump,
+        &GravityScale,
+        &mut MovementStats,
+    )>,
+) {
+    let world_gravity = gravity.0.length();
+    for (walk, sprinting, jump, gravity_scale, mut stats) in &mut character_query {
+        let gravity = world_gravity * gravity_scale.0;
+        let new_stats = MovementStats::compute(walk, sprinting, jump, gravity);
+        if *stats != new_stats {
+            *stats = new_stats;
         }
     }
 }
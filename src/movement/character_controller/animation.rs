@@ -1,17 +1,75 @@
-use bevy::{animation::AnimationPlayer, prelude::*};
+use crate::{
+    file_system_interaction::config::GameConfig,
+    level_instantiation::on_spawn::Player,
+    movement::character_controller::{
+        stopping::Stopping, turn_in_place::TurningInPlace, GroundedState, RotationMode, Stamina,
+        Walk,
+    },
+    player_control::camera::IngameCamera,
+    world_interaction::{carry::Carrying, dialog::CurrentDialogTarget},
+};
+use anyhow::Context;
+use bevy::{
+    animation::{AnimationPlayer, RepeatAnimation},
+    prelude::*,
+};
 use bevy_gltf_blueprints::{AnimationPlayerLink, Animations};
 use bevy_mod_sysfail::prelude::*;
 use bevy_tnua::{
-    builtins::TnuaBuiltinWalk, controller::TnuaController, TnuaAnimatingState,
-    TnuaAnimatingStateDirective,
+    builtins::{TnuaBuiltinJump, TnuaBuiltinWalk},
+    controller::TnuaController,
+    TnuaAnimatingState, TnuaAnimatingStateDirective,
 };
 use std::time::Duration;
 
+/// Off-screen characters further than this many times the animation LOD distance do not animate at all.
+const LOD_PAUSE_FACTOR: f32 = 4.;
+/// How long the walk and run animations blend into each other, in seconds.
+const GAIT_BLEND_TIME: f32 = 0.3;
+/// Characters without a run animation play the aerial one above this speed, in m/s, e.g. while sprinting.
+const FALLBACK_RUN_SPEED: f32 = 10.;
+/// How long a character needs to be off the ground before the airborne animation plays, in seconds.
+/// Walking down a slope loses the ground for a frame here and there, which should keep playing the walk animation.
+const AIRBORNE_ANIMATION_DELAY: f32 = 0.1;
+
 pub(super) fn plugin(app: &mut App) {
     app.register_type::<CharacterAnimationNames>()
-        .add_systems(Update, play_animations);
+        .add_event::<PlayOneShotAnimation>()
+        .add_systems(
+            Update,
+            (
+                play_one_shot_animations,
+                finish_one_shot_animations,
+                stop_held_animations,
+                play_held_animations,
+                play_animations,
+                update_animation_lod.run_if(resource_exists::<GameConfig>),
+            )
+                .chain(),
+        );
 }
 
+/// Plays a named animation of a character once.
+/// The regular animations driven by [`play_animations`] are suspended until it finished.
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub(crate) struct PlayOneShotAnimation {
+    pub(crate) entity: Entity,
+    pub(crate) animation: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Component)]
+struct OneShotAnimation {
+    remaining: Timer,
+}
+
+/// Loops a named animation instead of the ones picked by [`play_animations`] for as long as this component exists,
+/// e.g. while a character is sitting. One-shot animations still play on top and the held animation resumes after them.
+#[derive(Debug, Clone, PartialEq, Eq, Component)]
+pub(crate) struct HeldAnimation(pub(crate) String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+struct HeldAnimationPlaying;
+
 /// Managed by [`play_animations`]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub(crate) enum AnimationState {
@@ -19,32 +77,184 @@ pub(crate) enum AnimationState {
     Airborne,
     Walking(f32),
     Running(f32),
+    /// Walking while dragging a prop.
+    Dragging(f32),
+    /// Catching breath after running out of [`Stamina`].
+    OutOfBreath,
+    TurningLeft,
+    TurningRight,
+    StoppingLeft,
+    StoppingRight,
+}
+
+/// How often a character's animation is sampled, managed by [`update_animation_lod`].
+#[derive(Debug, Clone, Copy, PartialEq, Component, Default)]
+struct AnimationLod {
+    /// Whether the animation player is paused to hold the pose between samples.
+    holding: bool,
+    frames_since_sample: u32,
+    /// Animation time that passed since the last sample, in seconds.
+    skipped: f32,
 }
 
 #[derive(Debug, Clone, PartialEq, Component, Reflect, Default)]
 #[reflect(Component)]
-struct CharacterAnimationNames {
+pub(super) struct CharacterAnimationNames {
     idle: String,
     walk: String,
+    /// Played instead of the walk animation while the character moves faster than its [`Walk::walk_speed`],
+    /// see [`Walk::is_running_at`]. When both are set, they blend into each other in step.
+    /// Going by the speed instead of the input means NPCs run whenever they are fast enough, too.
+    #[reflect(default)]
+    run: Option<String>,
     aerial: String,
+    /// Played while turning in place, see [`RotationMode`]. Without it, the character turns without animating.
+    #[reflect(default)]
+    pub(super) turn_left: Option<String>,
+    #[reflect(default)]
+    pub(super) turn_right: Option<String>,
+    /// Played when stopping after running with the left foot in front. Without it, the character slows down in idle.
+    #[reflect(default)]
+    pub(super) stop_left: Option<String>,
+    #[reflect(default)]
+    pub(super) stop_right: Option<String>,
+    /// Played while dragging a prop, also when standing still. Without it, the character walks and idles as usual.
+    #[reflect(default)]
+    drag: Option<String>,
+    /// Played while out of breath after running out of [`Stamina`], also when walking slowly.
+    /// Without it, the character only slows down.
+    #[reflect(default)]
+    exhausted: Option<String>,
 }
 
 #[sysfail(Log<anyhow::Error, Error>)]
-fn play_animations(
+fn play_one_shot_animations(
+    mut commands: Commands,
+    mut requests: EventReader<PlayOneShotAnimation>,
+    characters: Query<(&AnimationPlayerLink, &Animations)>,
+    mut animation_players: Query<&mut AnimationPlayer>,
+    clips: Res<Assets<AnimationClip>>,
+) {
+    for request in requests.read() {
+        let (link, animations) = characters
+            .get(request.entity)
+            .context("Cannot play a one-shot animation on an entity without animations")?;
+        let clip = animations
+            .named_animations
+            .get(&request.animation)
+            .with_context(|| format!("No animation named \"{}\"", request.animation))?;
+        let duration = clips.get(clip).map_or(0., |clip| clip.duration());
+        animation_players
+            .get_mut(link.0)?
+            .play_with_transition(clip.clone_weak(), Duration::from_secs_f32(0.2));
+        commands
+            .entity(request.entity)
+            .insert(OneShotAnimation {
+                remaining: Timer::from_seconds(duration, TimerMode::Once),
+            })
+            .remove::<HeldAnimationPlaying>();
+    }
+}
+
+fn finish_one_shot_animations(
+    mut commands: Commands,
+    time: Res<Time>,
     mut query: Query<(
         Entity,
+        &mut OneShotAnimation,
         &mut TnuaAnimatingState<AnimationState>,
-        &TnuaController,
-        &AnimationPlayerLink,
-        &Animations,
     )>,
+) {
+    for (entity, mut one_shot, mut animating_state) in query.iter_mut() {
+        if one_shot.remaining.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<OneShotAnimation>();
+            // Forces the state machine to pick an animation again
+            *animating_state = default();
+        }
+    }
+}
+
+fn stop_held_animations(
+    mut commands: Commands,
+    mut removed: RemovedComponents<HeldAnimation>,
+    mut animating_states: Query<&mut TnuaAnimatingState<AnimationState>>,
+) {
+    for entity in removed.read() {
+        let Ok(mut animating_state) = animating_states.get_mut(entity) else {
+            continue;
+        };
+        commands.entity(entity).remove::<HeldAnimationPlaying>();
+        // Forces the state machine to pick an animation again
+        *animating_state = default();
+    }
+}
+
+#[sysfail(Log<anyhow::Error, Error>)]
+fn play_held_animations(
+    mut commands: Commands,
+    characters: Query<
+        (Entity, &HeldAnimation, &AnimationPlayerLink, &Animations),
+        (
+            Without<OneShotAnimation>,
+            Or<(Without<HeldAnimationPlaying>, Changed<HeldAnimation>)>,
+        ),
+    >,
+    mut animation_players: Query<&mut AnimationPlayer>,
+) {
+    for (entity, held, link, animations) in characters.iter() {
+        commands.entity(entity).insert(HeldAnimationPlaying);
+        let clip = animations
+            .named_animations
+            .get(&held.0)
+            .with_context(|| format!("No animation named \"{}\"", held.0))?;
+        animation_players
+            .get_mut(link.0)?
+            .play_with_transition(clip.clone_weak(), Duration::from_secs_f32(0.2))
+            .repeat();
+    }
+}
+
+#[sysfail(Log<anyhow::Error, Error>)]
+fn play_animations(
+    mut query: Query<
+        (
+            Entity,
+            &mut TnuaAnimatingState<AnimationState>,
+            &TnuaController,
+            &AnimationPlayerLink,
+            &Animations,
+            Option<&TurningInPlace>,
+            Option<&RotationMode>,
+            Option<&Stopping>,
+            Option<&Walk>,
+            Option<&Carrying>,
+            Option<&Stamina>,
+            Option<&GroundedState>,
+        ),
+        (Without<OneShotAnimation>, Without<HeldAnimation>),
+    >,
     children: Query<&Children>,
     animation_names: Query<&CharacterAnimationNames>,
     mut animation_players: Query<&mut AnimationPlayer>,
+    clips: Res<Assets<AnimationClip>>,
 ) {
     #[cfg(feature = "tracing")]
     let _span = info_span!("play_animations").entered();
-    for (entity, mut animating_state, controller, link, animations) in query.iter_mut() {
+    for (
+        entity,
+        mut animating_state,
+        controller,
+        link,
+        animations,
+        turning,
+        rotation_mode,
+        stopping,
+        walk,
+        carrying,
+        stamina,
+        grounded,
+    ) in query.iter_mut()
+    {
         let Some(animation_names) = children
             .iter_descendants(entity)
             .filter_map(|entity| animation_names.get(entity).ok())
@@ -53,17 +263,51 @@ fn play_animations(
             continue;
         };
         let mut animation_player = animation_players.get_mut(link.0)?;
+        let was_running = matches!(animating_state.get(), Some(AnimationState::Running(..)));
         match animating_state.update_by_discriminant({
             let Some((_, basis_state)) = controller.concrete_basis::<TnuaBuiltinWalk>() else {
                 continue;
             };
             let speed = basis_state.running_velocity.length();
-            if controller.is_airborne()? {
+            let is_running = if animation_names.run.is_some() {
+                walk.is_some_and(|walk| walk.is_running_at(speed, was_running))
+            } else {
+                speed > FALLBACK_RUN_SPEED
+            };
+            let is_dragging = animation_names.drag.is_some()
+                && carrying.is_some_and(|carrying| carrying.dragging);
+            let is_out_of_breath = animation_names.exhausted.is_some()
+                && stamina.is_some_and(Stamina::is_out_of_breath);
+            let is_airborne = controller.is_airborne()?;
+            let is_jumping = controller.concrete_action::<TnuaBuiltinJump>().is_some();
+            let is_falling = grounded.map_or(is_airborne, |grounded| {
+                grounded.sliding
+                    || (is_airborne
+                        && (is_jumping || grounded.time_since_grounded > AIRBORNE_ANIMATION_DELAY))
+            });
+            if is_falling {
                 AnimationState::Airborne
-            } else if speed > 10.0 {
+            } else if is_dragging {
+                AnimationState::Dragging(speed)
+            } else if is_out_of_breath {
+                AnimationState::OutOfBreath
+            } else if let Some(stopping) = stopping.filter(|stopping| stopping.animated) {
+                // The stop animation plays out while the speed falls, instead of cutting to walking and idle
+                if stopping.left_foot {
+                    AnimationState::StoppingLeft
+                } else {
+                    AnimationState::StoppingRight
+                }
+            } else if speed > 0.01 && is_running {
                 AnimationState::Running(speed)
             } else if speed > 0.01 {
                 AnimationState::Walking(speed)
+            } else if let Some(turning) = turning.filter(|turning| turning.animated) {
+                if turning.left {
+                    AnimationState::TurningLeft
+                } else {
+                    AnimationState::TurningRight
+                }
             } else {
                 AnimationState::Standing
             }
@@ -73,13 +317,21 @@ fn play_animations(
                     let anim_speed = (speed / 7.0).max(1.0);
                     animation_player.set_speed(anim_speed);
                 }
+                if let AnimationState::Dragging(speed) = state {
+                    // Holds the pose while standing still
+                    animation_player.set_speed(speed.min(1.));
+                }
             }
-            TnuaAnimatingStateDirective::Alter {
-                // We don't need the old state here, but it's available for transition
-                // animations.
-                old_state: _,
-                state,
-            } => match state {
+            TnuaAnimatingStateDirective::Alter { old_state, state } => match state {
+                AnimationState::Running(..) if animation_names.run.is_some() => {
+                    let clip = animation_names
+                        .run
+                        .as_ref()
+                        .and_then(|name| animations.named_animations.get(name))
+                        .context("No run animation")?;
+                    let was_walking = matches!(old_state, Some(AnimationState::Walking(..)));
+                    play_gait(&mut animation_player, clip, was_walking, &clips);
+                }
                 AnimationState::Airborne | AnimationState::Running(..) => {
                     animation_player
                         .play_with_transition(
@@ -93,6 +345,13 @@ fn play_animations(
                         .repeat();
                 }
                 AnimationState::Standing => {
+                    let was_turning = matches!(
+                        old_state,
+                        Some(AnimationState::TurningLeft | AnimationState::TurningRight)
+                    );
+                    let transition = rotation_mode
+                        .filter(|_| was_turning)
+                        .map_or(0.2, |rotation_mode| rotation_mode.blend_time);
                     animation_player
                         .play_with_transition(
                             animations
@@ -100,23 +359,202 @@ fn play_animations(
                                 .get(&animation_names.idle)
                                 .unwrap()
                                 .clone_weak(),
-                            Duration::from_secs_f32(0.2),
+                            Duration::from_secs_f32(transition),
                         )
                         .repeat();
                 }
-                AnimationState::Walking(_speed) => {
+                AnimationState::TurningLeft | AnimationState::TurningRight => {
+                    let name = if *state == AnimationState::TurningLeft {
+                        &animation_names.turn_left
+                    } else {
+                        &animation_names.turn_right
+                    };
+                    let clip = name
+                        .as_ref()
+                        .and_then(|name| animations.named_animations.get(name))
+                        .context("Turning in place without a turning animation")?;
+                    let transition =
+                        rotation_mode.map_or(0.2, |rotation_mode| rotation_mode.blend_time);
                     animation_player
                         .play_with_transition(
-                            animations
-                                .named_animations
-                                .get(&animation_names.walk)
-                                .unwrap()
-                                .clone_weak(),
-                            Duration::from_secs_f32(0.1),
+                            clip.clone_weak(),
+                            Duration::from_secs_f32(transition),
                         )
+                        .set_speed(1.);
+                }
+                AnimationState::StoppingLeft | AnimationState::StoppingRight => {
+                    let name = if *state == AnimationState::StoppingLeft {
+                        &animation_names.stop_left
+                    } else {
+                        &animation_names.stop_right
+                    };
+                    let clip = name
+                        .as_ref()
+                        .and_then(|name| animations.named_animations.get(name))
+                        .context("Stopping without a stop animation")?;
+                    // The clip needs to play at its own pace to match the deceleration, even when coming from a sped up run
+                    animation_player
+                        .play_with_transition(clip.clone_weak(), Duration::from_secs_f32(0.1))
+                        .set_speed(1.);
+                }
+                AnimationState::Dragging(..) => {
+                    let clip = animation_names
+                        .drag
+                        .as_ref()
+                        .and_then(|name| animations.named_animations.get(name))
+                        .context("No drag animation")?;
+                    animation_player
+                        .play_with_transition(clip.clone_weak(), Duration::from_secs_f32(0.2))
                         .repeat();
                 }
+                AnimationState::OutOfBreath => {
+                    let clip = animation_names
+                        .exhausted
+                        .as_ref()
+                        .and_then(|name| animations.named_animations.get(name))
+                        .context("No exhausted animation")?;
+                    animation_player
+                        .play_with_transition(clip.clone_weak(), Duration::from_secs_f32(0.2))
+                        .repeat()
+                        .set_speed(1.);
+                }
+                AnimationState::Walking(_speed) => {
+                    let clip = animations
+                        .named_animations
+                        .get(&animation_names.walk)
+                        .context("No walk animation")?;
+                    let was_running = animation_names.run.is_some()
+                        && matches!(old_state, Some(AnimationState::Running(..)));
+                    play_gait(&mut animation_player, clip, was_running, &clips);
+                }
             },
         }
     }
 }
+
+/// Plays a walk or run clip. Switching between the two blends them over [`GAIT_BLEND_TIME`] and starts the new clip
+/// at the same point of the stride as the old one, so that the feet stay in step. Both clips need to start on the same foot.
+fn play_gait(
+    animation_player: &mut AnimationPlayer,
+    clip: &Handle<AnimationClip>,
+    switches_gait: bool,
+    clips: &Assets<AnimationClip>,
+) {
+    let duration = |clip: &Handle<AnimationClip>| {
+        clips
+            .get(clip)
+            .map_or(0., AnimationClip::duration)
+            .max(f32::EPSILON)
+    };
+    if !switches_gait {
+        animation_player
+            .play_with_transition(clip.clone_weak(), Duration::from_secs_f32(0.1))
+            .repeat();
+        return;
+    }
+    let phase =
+        (animation_player.seek_time() / duration(animation_player.animation_clip())).rem_euclid(1.);
+    animation_player
+        .play_with_transition(clip.clone_weak(), Duration::from_secs_f32(GAIT_BLEND_TIME))
+        .repeat()
+        .seek_to(phase * duration(clip));
+}
+
+/// Skeletal animation is expensive, so off-screen characters far from the camera only sample their animation
+/// every 2nd or 4th frame, and beyond [`LOD_PAUSE_FACTOR`] times the [`Graphics::animation_lod_distance`](crate::file_system_interaction::config::Graphics::animation_lod_distance) not at all.
+/// Between samples, the animation player is paused, which holds the pose. The time that passed meanwhile is
+/// added when sampling again, so that the clip stays in sync. [`play_animations`] keeps running for these characters,
+/// so they resume with the right animation.
+/// Characters on screen, the player and the current dialog partner always animate at full rate.
+fn update_animation_lod(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    dialog_target: Res<CurrentDialogTarget>,
+    cameras: Query<&GlobalTransform, With<IngameCamera>>,
+    mut characters: Query<(
+        Entity,
+        &GlobalTransform,
+        &AnimationPlayerLink,
+        Option<&mut AnimationLod>,
+        Has<Player>,
+    )>,
+    children: Query<&Children>,
+    visibilities: Query<&ViewVisibility, With<Handle<Mesh>>>,
+    mut animation_players: Query<&mut AnimationPlayer>,
+    clips: Res<Assets<AnimationClip>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_animation_lod").entered();
+    let Some(camera) = cameras.iter().next().map(GlobalTransform::translation) else {
+        return;
+    };
+    let lod_distance = config.graphics.resolve().animation_lod_distance.max(0.);
+    for (entity, transform, link, lod, is_player) in characters.iter_mut() {
+        let Some(mut lod) = lod else {
+            commands.entity(entity).insert(AnimationLod::default());
+            continue;
+        };
+        let Ok(mut animation_player) = animation_players.get_mut(link.0) else {
+            continue;
+        };
+        let distance = transform.translation().distance(camera);
+        let is_exempt = is_player
+            || dialog_target.0 == Some(entity)
+            || distance < lod_distance
+            || children.iter_descendants(entity).any(|child| {
+                visibilities
+                    .get(child)
+                    .is_ok_and(|visibility| visibility.get())
+            });
+        let sample_interval = if is_exempt {
+            1
+        } else if distance < 2. * lod_distance {
+            2
+        } else if distance < LOD_PAUSE_FACTOR * lod_distance {
+            4
+        } else {
+            // Never sampled until the character comes closer
+            u32::MAX
+        };
+
+        if sample_interval == 1 {
+            if lod.holding {
+                advance_animation(&mut animation_player, lod.skipped, &clips);
+                animation_player.resume();
+                *lod = default();
+            }
+            continue;
+        }
+        if !lod.holding {
+            animation_player.pause();
+            lod.holding = true;
+        }
+        // Only touching the player mutably on samples lets Bevy skip it in between
+        lod.skipped += time.delta_seconds() * animation_player.speed();
+        lod.frames_since_sample += 1;
+        if lod.frames_since_sample >= sample_interval {
+            advance_animation(&mut animation_player, lod.skipped, &clips);
+            lod.frames_since_sample = 0;
+            lod.skipped = 0.;
+        }
+    }
+}
+
+fn advance_animation(
+    animation_player: &mut AnimationPlayer,
+    seconds: f32,
+    clips: &Assets<AnimationClip>,
+) {
+    let duration = clips
+        .get(animation_player.animation_clip())
+        .map_or(0., AnimationClip::duration);
+    let mut seek_time = animation_player.seek_time() + seconds;
+    if duration > 0. {
+        seek_time = match animation_player.repeat_mode() {
+            RepeatAnimation::Never => seek_time.clamp(0., duration),
+            _ => seek_time.rem_euclid(duration),
+        };
+    }
+    animation_player.seek_to(seek_time);
+}
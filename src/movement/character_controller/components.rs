@@ -1,12 +1,27 @@
-use crate::movement::{character_controller::AnimationState, physics::CollisionLayer};
+use crate::movement::{
+    character_controller::{
+        AnimationState, CharacterGravityScale, Depenetrate, GroundedState, PushPriority,
+        RotationMode, SeparationPush,
+    },
+    physics::CollisionLayer,
+};
 use bevy::prelude::*;
 use bevy_tnua::{prelude::*, TnuaAnimatingState};
 use bevy_tnua_xpbd3d::*;
 use bevy_xpbd_3d::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_4;
+
+/// Shorter durations are treated as this in the derived-parameter constructors, to avoid infinite accelerations.
+const MIN_DURATION: f32 = 1e-3;
+/// How far the speed needs to be above or below the [`Walk::walk_speed`] to count as running or walking again,
+/// as a fraction of the walk speed.
+const RUN_SPEED_HYSTERESIS: f32 = 0.1;
 
 pub(super) fn plugin(app: &mut App) {
-    app.register_type::<Jump>().register_type::<Walk>();
+    app.register_type::<Jump>()
+        .register_type::<Walk>()
+        .register_type::<MovementStats>();
 }
 
 #[derive(Bundle)]
@@ -22,6 +37,14 @@ pub(crate) struct CharacterControllerBundle {
     pub(crate) tnua_controller: TnuaControllerBundle,
     pub(crate) float_height: FloatHeight,
     pub(crate) animation_state: TnuaAnimatingState<AnimationState>,
+    pub(crate) depenetrate: Depenetrate,
+    pub(crate) grounded_state: GroundedState,
+    pub(crate) gravity_scale: GravityScale,
+    pub(crate) character_gravity_scale: CharacterGravityScale,
+    pub(crate) movement_stats: MovementStats,
+    pub(crate) push_priority: PushPriority,
+    pub(crate) separation_push: SeparationPush,
+    pub(crate) rotation_mode: RotationMode,
 }
 
 impl CharacterControllerBundle {
@@ -49,42 +72,265 @@ impl CharacterControllerBundle {
             tnua_controller: default(),
             float_height: FloatHeight((height / 2. + radius) * scale_y),
             animation_state: default(),
+            depenetrate: default(),
+            grounded_state: default(),
+            gravity_scale: GravityScale(1.),
+            character_gravity_scale: default(),
+            movement_stats: default(),
+            push_priority: default(),
+            separation_push: default(),
+            rotation_mode: default(),
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
-#[reflect(Component, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
 pub(crate) struct Walk {
-    /// Top speed on the ground
+    /// Top speed on the ground, reached while running at full tilt.
     pub(crate) speed: f32,
-    /// Direction in which we want to walk and turn this tick.
+    /// Top speed while walking, i.e. while the [`Walk::direction`] is shorter than the [`Walk::run_threshold`].
+    pub(crate) walk_speed: f32,
+    /// How long the [`Walk::direction`] needs to be for the character to run instead of walk. Between 0 and 1.
+    pub(crate) run_threshold: f32,
+    /// A running character only walks again once the direction is this much shorter than the [`Walk::run_threshold`],
+    /// so that a stick held right at the threshold does not flicker between the two.
+    pub(crate) run_hysteresis: f32,
+    /// How quickly the character speeds up on the ground, in m/s².
+    pub(crate) acceleration: f32,
+    /// How quickly the character slows down on the ground when it stops walking, in m/s².
+    /// This is the friction the character experiences.
+    pub(crate) deceleration: f32,
+    /// The steepest ground the character can stand and walk on, in radians. It slides down anything steeper.
+    pub(crate) max_slope_angle: f32,
+    /// Direction in which we want to walk and turn this tick. Its length picks the speed, e.g. how far a stick is tilted.
     pub(crate) direction: Option<Vec3>,
+    /// Direction in which we want to face this tick while not walking.
+    /// See [`RotationMode`] for how the character turns towards it.
+    pub(crate) facing: Option<Vec3>,
+    /// Direction to keep facing this tick while walking, e.g. while dragging something backwards.
+    /// Without it, a walking character turns towards the [`Walk::direction`].
+    pub(crate) keep_facing: Option<Vec3>,
+    /// Whether the character currently runs, updated from the length of the [`Walk::direction`].
+    #[serde(skip)]
+    pub(crate) running: bool,
 }
 
 impl Default for Walk {
     fn default() -> Self {
         Self {
             speed: 8.,
+            walk_speed: 3.,
+            run_threshold: 0.6,
+            run_hysteresis: 0.1,
+            acceleration: 60.,
+            deceleration: 60.,
+            max_slope_angle: FRAC_PI_4,
             direction: None,
+            facing: None,
+            keep_facing: None,
+            running: false,
         }
     }
 }
 
+impl Walk {
+    /// Walks at up to `max_speed` m/s, taking `time_to_max_speed` seconds to get there from standing
+    /// and `stop_time` seconds to come to a halt again.
+    ///
+    /// Tnua changes the velocity at a constant rate, so starting from rest with an acceleration `a`,
+    /// the character reaches `max_speed` after `max_speed / a` seconds. Thus `a = max_speed / time_to_max_speed`,
+    /// and the same goes for the deceleration with `stop_time`.
+    pub(crate) fn from_speeds(max_speed: f32, time_to_max_speed: f32, stop_time: f32) -> Self {
+        let defaults = Self::default();
+        Self {
+            speed: max_speed,
+            walk_speed: defaults.walk_speed.min(max_speed),
+            acceleration: max_speed / time_to_max_speed.max(MIN_DURATION),
+            deceleration: max_speed / stop_time.max(MIN_DURATION),
+            ..defaults
+        }
+    }
+
+    /// Updates [`Walk::running`] for a direction of the given length and returns how fast the character should move.
+    /// Walking reaches [`Walk::walk_speed`] where the hysteresis starts and running only speeds up beyond the threshold,
+    /// so that switching between the two does not change the speed. Running reaches [`Walk::speed`] at full length.
+    pub(crate) fn update_gait(&mut self, length: f32) -> f32 {
+        let walk_end = self.walk_end();
+        self.running = if self.running {
+            length > walk_end
+        } else {
+            length >= self.run_threshold
+        };
+        if self.running {
+            let progress =
+                (length - self.run_threshold) / (1. - self.run_threshold).max(f32::EPSILON);
+            self.walk_speed + (self.speed - self.walk_speed) * progress.clamp(0., 1.)
+        } else {
+            self.walk_speed * (length / walk_end).min(1.)
+        }
+    }
+
+    /// The longest [`Walk::direction`] at which a running character walks again, see [`Walk::run_hysteresis`].
+    /// Clamping the direction to this keeps the character walking.
+    pub(crate) fn walk_end(&self) -> f32 {
+        (self.run_threshold - self.run_hysteresis).max(f32::EPSILON)
+    }
+
+    /// Whether moving at `speed` m/s looks like running, e.g. to pick an animation. Unlike [`Walk::running`], this goes by
+    /// how fast the character actually moves, so that a character blocked by a wall or still speeding up does not run in place.
+    pub(crate) fn is_running_at(&self, speed: f32, was_running: bool) -> bool {
+        let hysteresis = if was_running {
+            -RUN_SPEED_HYSTERESIS
+        } else {
+            RUN_SPEED_HYSTERESIS
+        };
+        speed > self.walk_speed * (1. + hysteresis)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
-#[reflect(Component, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
 pub(crate) struct Jump {
     /// The full height of the jump, if the player does not release the button
     pub(crate) height: f32,
-    /// Was jump requested this frame?
+    /// The gravity this character experiences, in m/s². Uses the world's gravity when `None`.
+    /// Stronger gravity makes for a snappier jump of the same height.
+    pub(crate) gravity: Option<f32>,
+    /// How long after walking off a ledge the character can still jump, in seconds.
+    pub(crate) coyote_time: f32,
+    /// How long a jump requested slightly too early is remembered, e.g. right before landing, in seconds.
+    pub(crate) jump_buffer: f32,
+    /// How long after takeoff letting go of the button still cuts the jump short, in seconds.
+    /// Holding it longer than this or until the apex jumps the full [`Jump::height`].
+    pub(crate) max_hold_time: f32,
+    /// What the upward velocity is multiplied with when the button is let go early.
+    pub(crate) cut_factor: f32,
+    /// Was jump requested this frame? Requesting it every frame holds the button, and not requesting it anymore lets go of it.
     pub(crate) requested: bool,
+    /// Whether jump was requested in the last frame. Only a new request is remembered and takes off,
+    /// so that holding the button through a landing does not jump again.
+    was_requested: bool,
+    /// Seconds until the last new request is forgotten.
+    buffered: f32,
+    /// Seconds since the character took off, until it is back on the ground.
+    since_takeoff: Option<f32>,
+    /// Whether the character was in the air since it took off.
+    left_ground: bool,
+    /// Seconds the button has been held since takeoff, while the jump can still be cut short.
+    held: Option<f32>,
+}
+
+/// What [`Jump::update_request`] wants done with the jump action this frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum JumpControl {
+    /// Do not feed the action. A jump in progress follows gravity for the rest of its arc.
+    Release,
+    /// Feed the action, taking off or holding the jump.
+    Hold,
+    /// The button was let go early. Do not feed the action and multiply the upward velocity with [`Jump::cut_factor`].
+    Cut,
+}
+
+impl Jump {
+    /// Jumps `apex_height` meters high, reaching the apex after `time_to_apex` seconds.
+    ///
+    /// Jumping off with a speed `v` against a constant gravity `g`, the character reaches its apex when `v - g t = 0`,
+    /// i.e. after `t = v / g` seconds, at a height of `h = v t - g t² / 2 = g t² / 2`.
+    /// Thus `g = 2 h / t²` and `v = g t = 2 h / t`. Tnua derives `v` from the height and the gravity on its own,
+    /// so only `g` needs to be stored.
+    pub(crate) fn from_height_and_time(apex_height: f32, time_to_apex: f32) -> Self {
+        let time_to_apex = time_to_apex.max(MIN_DURATION);
+        Self {
+            height: apex_height,
+            gravity: Some(2. * apex_height / (time_to_apex * time_to_apex)),
+            ..default()
+        }
+    }
+
+    /// Consumes this frame's request and returns what to do with the jump action.
+    /// `time_since_grounded` is [`GroundedState::time_since_grounded`] and `vertical_speed` is the upward velocity of the character.
+    ///
+    /// A new request, i.e. one that was not there in the last frame, is remembered for [`Jump::jump_buffer`] seconds and takes off while the character is on the ground
+    /// or left it less than [`Jump::coyote_time`] seconds ago. Only one takeoff is possible until the character
+    /// lands again, so the two windows can not be combined into a second jump.
+    ///
+    /// Once in the air, the jump is held as long as it is requested, up to [`Jump::max_hold_time`] seconds.
+    /// Past the apex, e.g. after bumping into a ceiling, it can neither be held nor cut anymore.
+    pub(crate) fn update_request(
+        &mut self,
+        dt: f32,
+        is_jumping: bool,
+        time_since_grounded: f32,
+        vertical_speed: f32,
+    ) -> JumpControl {
+        let requested = std::mem::take(&mut self.requested);
+        let is_new_request = requested && !self.was_requested;
+        self.was_requested = requested;
+        self.buffered = if is_new_request {
+            self.jump_buffer
+        } else {
+            (self.buffered - dt).max(0.)
+        };
+        if let Some(since_takeoff) = self.since_takeoff.as_mut() {
+            *since_takeoff += dt;
+            let is_grounded = time_since_grounded <= 0.;
+            self.left_ground |= !is_grounded;
+            // Something like a low ceiling kept the character from ever leaving the ground
+            let has_failed = !is_jumping && *since_takeoff > self.coyote_time;
+            if is_grounded && (self.left_ground || has_failed) {
+                self.since_takeoff = None;
+                self.left_ground = false;
+            }
+        }
+        if is_jumping {
+            // Holding the button keeps the jump going, but must not leave a request behind that jumps again on landing
+            self.buffered = 0.;
+            let Some(held) = self.held.as_mut() else {
+                // Pressing again after the jump was let go does not extend it
+                return JumpControl::Release;
+            };
+            *held += dt;
+            if vertical_speed <= 0. || *held >= self.max_hold_time {
+                self.held = None;
+                return JumpControl::Release;
+            }
+            if !requested {
+                self.held = None;
+                return JumpControl::Cut;
+            }
+            return JumpControl::Hold;
+        }
+        let can_take_off = self.since_takeoff.is_none() && time_since_grounded <= self.coyote_time;
+        if self.buffered > 0. && can_take_off {
+            self.buffered = 0.;
+            self.since_takeoff = Some(0.);
+            self.held = Some(0.);
+            JumpControl::Hold
+        } else {
+            self.held = None;
+            JumpControl::Release
+        }
+    }
+
+    /// Forgets the current request, including a remembered one. Holding the button on afterwards does not jump either.
+    pub(crate) fn cancel_request(&mut self) {
+        self.was_requested = std::mem::take(&mut self.requested);
+        self.buffered = 0.;
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
-#[reflect(Component, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
 pub(crate) struct Sprinting {
     /// The speed multiplier when sprinting
     pub(crate) multiplier: f32,
+    /// Multiplies the [`Walk::acceleration`] when sprinting, so that the higher top speed is reached as quickly.
+    /// Only applies on the ground, air control stays the same.
+    pub(crate) acceleration: f32,
     /// Was sprinting requested?
     pub(crate) requested: bool,
 }
@@ -93,6 +339,7 @@ impl Default for Sprinting {
     fn default() -> Self {
         Self {
             multiplier: 1.5,
+            acceleration: 1.5,
             requested: false,
         }
     }
@@ -108,7 +355,276 @@ impl Default for Jump {
     fn default() -> Self {
         Self {
             height: 1.0,
+            gravity: None,
+            coyote_time: 0.1,
+            jump_buffer: 0.15,
+            max_hold_time: 0.3,
+            cut_factor: 0.5,
             requested: false,
+            was_requested: false,
+            buffered: 0.,
+            since_takeoff: None,
+            left_ground: false,
+            held: None,
+        }
+    }
+}
+
+/// The movement values currently in effect for a character, derived from its [`Walk`], [`Sprinting`] and [`Jump`]
+/// as well as the gravity it currently experiences. Recomputed every frame, so changing it has no effect.
+/// Meant for checking in the inspector that the tuning results in the intended movement.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Default)]
+#[reflect(Component)]
+pub(crate) struct MovementStats {
+    /// Including the sprint multiplier while sprinting, in m/s.
+    max_speed: f32,
+    /// In seconds.
+    time_to_max_speed: f32,
+    /// In seconds.
+    stop_time: f32,
+    /// In m/s².
+    gravity: f32,
+    /// In m.
+    jump_height: f32,
+    /// In m/s.
+    jump_takeoff_speed: f32,
+    /// In seconds.
+    time_to_apex: f32,
+}
+
+impl MovementStats {
+    pub(crate) fn compute(
+        walk: &Walk,
+        sprinting: Option<&Sprinting>,
+        jump: &Jump,
+        gravity: f32,
+    ) -> Self {
+        let (sprinting_multiplier, sprinting_acceleration) = sprinting
+            .filter(|s| s.requested)
+            .map_or((1., 1.), |s| (s.multiplier, s.acceleration));
+        let max_speed = walk.speed * sprinting_multiplier;
+        let acceleration = walk.acceleration * sprinting_acceleration;
+        let jump_takeoff_speed = (2. * gravity * jump.height).max(0.).sqrt();
+        Self {
+            max_speed,
+            time_to_max_speed: max_speed / acceleration.max(f32::EPSILON),
+            stop_time: max_speed / walk.deceleration.max(f32::EPSILON),
+            gravity,
+            jump_height: jump.height,
+            jump_takeoff_speed,
+            time_to_apex: jump_takeoff_speed / gravity.max(f32::EPSILON),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        player_control::actions::PlayerAction,
+        testing::{InputScript, TestApp, TICK},
+    };
+
+    #[test]
+    fn gait_switches_with_hysteresis() {
+        let mut walk = Walk::default();
+        let walk_end = walk.run_threshold - walk.run_hysteresis;
+        assert_eq!(walk.update_gait(walk_end), walk.walk_speed);
+        assert!(!walk.running);
+        assert_eq!(walk.update_gait(1.), walk.speed);
+        assert!(walk.running);
+        // Just below the threshold, the character keeps running at the same speed it would walk at
+        assert_eq!(walk.update_gait(walk.run_threshold - 0.01), walk.walk_speed);
+        assert!(walk.running);
+        assert!(walk.update_gait(walk_end - 0.01) < walk.walk_speed);
+        assert!(!walk.running);
+        // Right at the end of the hysteresis, a running character walks again
+        walk.update_gait(1.);
+        assert_eq!(walk.update_gait(walk_end), walk.walk_speed);
+        assert!(!walk.running);
+
+        // By actual speed, running also has some leeway around the walk speed
+        assert!(!walk.is_running_at(walk.walk_speed, false));
+        assert!(walk.is_running_at(walk.walk_speed * 1.2, false));
+        assert!(walk.is_running_at(walk.walk_speed, true));
+        assert!(!walk.is_running_at(walk.walk_speed * 0.8, true));
+        assert_eq!(walk.update_gait(0.), 0.);
+    }
+
+    #[test]
+    fn jumps_are_buffered_and_allowed_shortly_after_leaving_the_ground() {
+        let dt = 0.02;
+        let takes_off = |jump: &mut Jump, is_jumping, time_since_grounded| {
+            jump.update_request(dt, is_jumping, time_since_grounded, 1.) == JumpControl::Hold
+        };
+        let mut jump = Jump::default();
+        // Pressed a bit before landing
+        jump.requested = true;
+        assert!(!takes_off(&mut jump, false, 0.5));
+        assert!(!takes_off(&mut jump, false, 0.52));
+        assert!(takes_off(&mut jump, false, 0.));
+
+        // Releasing and pressing again while still in the coyote window does not jump twice
+        assert!(!takes_off(&mut jump, true, 0.));
+        jump.requested = true;
+        assert!(!takes_off(&mut jump, false, 0.02));
+        for _ in 0..10 {
+            assert!(!takes_off(&mut jump, false, 0.3));
+        }
+
+        // After landing, walking off a ledge still allows a jump shortly after
+        assert!(!takes_off(&mut jump, false, 0.));
+        jump.requested = true;
+        assert!(takes_off(&mut jump, false, 0.05));
+        assert!(!takes_off(&mut jump, false, 0.2));
+        assert!(!takes_off(&mut jump, false, 0.));
+
+        // Too late for the coyote window and not remembered long enough to jump on landing
+        jump.requested = true;
+        assert!(!takes_off(&mut jump, false, 0.2));
+        for _ in 0..10 {
+            assert!(!takes_off(&mut jump, false, 0.3));
+        }
+        assert!(!takes_off(&mut jump, false, 0.));
+    }
+
+    #[test]
+    fn letting_go_early_cuts_the_jump_only_until_the_apex() {
+        let dt = 0.02;
+        let mut jump = Jump::default();
+        jump.requested = true;
+        assert_eq!(jump.update_request(dt, false, 0., 0.), JumpControl::Hold);
+        jump.requested = true;
+        assert_eq!(jump.update_request(dt, true, dt, 5.), JumpControl::Hold);
+        assert_eq!(
+            jump.update_request(dt, true, 2. * dt, 4.5),
+            JumpControl::Cut
+        );
+        // Pressing again does not bring the jump back
+        jump.requested = true;
+        assert_eq!(
+            jump.update_request(dt, true, 3. * dt, 2.),
+            JumpControl::Release
+        );
+
+        // Bumping into a ceiling ends the jump, so letting go afterwards does nothing
+        for _ in 0..10 {
+            jump.update_request(dt, false, 0., 0.);
         }
+        jump.requested = true;
+        assert_eq!(jump.update_request(dt, false, 0., 0.), JumpControl::Hold);
+        jump.requested = true;
+        assert_eq!(jump.update_request(dt, true, dt, 5.), JumpControl::Hold);
+        jump.requested = true;
+        assert_eq!(
+            jump.update_request(dt, true, 2. * dt, 0.),
+            JumpControl::Release
+        );
+        assert_eq!(
+            jump.update_request(dt, true, 3. * dt, -1.),
+            JumpControl::Release
+        );
+    }
+
+    #[test]
+    fn holding_jump_through_the_landing_takes_off_only_once() {
+        let mut app = TestApp::new();
+        app.spawn_ground();
+        let player = app.spawn_player(Vec3::new(0., 1., 0.));
+        app.step(60);
+
+        // Long enough to land and stand for a while
+        app.script(InputScript::new().hold(PlayerAction::Jump, 180));
+        let mut takeoffs = 0;
+        let mut was_airborne = false;
+        for _ in 0..180 {
+            app.step(1);
+            let airborne = app.world().get::<GroundedState>(player).unwrap().airborne;
+            if airborne && !was_airborne {
+                takeoffs += 1;
+            }
+            was_airborne = airborne;
+        }
+        assert_eq!(takeoffs, 1);
+        assert!(!was_airborne, "the player did not land");
+    }
+
+    /// Within 2%, but at least a tick, since that is as precise as the samples are.
+    fn assert_within_2_percent(actual: f32, expected: f32, what: &str) {
+        let tolerance = (expected.abs() * 0.02).max(TICK);
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{what} is {actual}, expected {expected}"
+        );
+    }
+
+    #[test]
+    fn derived_jumps_reach_their_apex_height_at_the_predicted_time() {
+        let mut app = TestApp::new();
+        app.spawn_ground();
+        let player = app.spawn_player(Vec3::new(0., 1., 0.));
+        app.world_mut()
+            .entity_mut(player)
+            .insert(Jump::from_height_and_time(3., 1.));
+        app.step(60);
+        let start = app.translation(player).y;
+
+        app.script(InputScript::new().hold(PlayerAction::Jump, 120));
+        let mut takeoff = None;
+        let (mut apex, mut apex_tick) = (0., 0);
+        for tick in 0..120 {
+            app.step(1);
+            let height = app.translation(player).y - start;
+            if takeoff.is_none() && height > 1e-3 {
+                takeoff = Some(tick);
+            }
+            if height > apex {
+                (apex, apex_tick) = (height, tick);
+            }
+        }
+        let takeoff = takeoff.expect("the player did not jump");
+
+        assert_within_2_percent(apex, 3., "the apex height");
+        // The first tick that rose above the ground already moved for a whole tick
+        let time_to_apex = (apex_tick - takeoff + 1) as f32 * TICK;
+        assert_within_2_percent(time_to_apex, 1., "the time to apex");
+    }
+
+    #[test]
+    fn derived_walks_speed_up_and_stop_in_the_predicted_time() {
+        let mut app = TestApp::new();
+        app.spawn_ground();
+        let player = app.spawn_player(Vec3::new(0., 1., 0.));
+        app.world_mut()
+            .entity_mut(player)
+            .insert(Walk::from_speeds(6., 1., 0.5));
+        app.step(60);
+        let speed = |app: &TestApp| {
+            app.world()
+                .get::<LinearVelocity>(player)
+                .unwrap()
+                .0
+                .xz()
+                .length()
+        };
+        // Counts the ticks until `reached` holds for the speed
+        let ticks_until = |app: &mut TestApp, reached: &dyn Fn(f32) -> bool| {
+            (1..=120)
+                .find(|_| {
+                    app.step(1);
+                    reached(speed(app))
+                })
+                .expect("the speed was never reached")
+        };
+
+        app.script(InputScript::new().walk(Vec2::Y, 120));
+        let time_to_max_speed = ticks_until(&mut app, &|speed| speed >= 6. * 0.995) as f32 * TICK;
+        assert_within_2_percent(time_to_max_speed, 1., "the time to max speed");
+        app.step(30);
+        assert_within_2_percent(speed(&app), 6., "the max speed");
+
+        app.script(InputScript::new().idle(120));
+        let stop_time = ticks_until(&mut app, &|speed| speed <= 6. * 0.005) as f32 * TICK;
+        assert_within_2_percent(stop_time, 0.5, "the stop time");
     }
 }
@@ -0,0 +1,193 @@
+use crate::{
+    movement::{character_controller::GeneralMovementSystemSet, physics::CollisionLayer},
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_tnua::prelude::*;
+use bevy_xpbd_3d::{plugins::collision::contact_query, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+/// Contacts deeper than this make the solver launch or sink characters, so we resolve them ourselves.
+const LARGE_PENETRATION: f32 = 0.15;
+/// Extra distance added to each push so that the character ends up clearly outside.
+const SKIN_WIDTH: f32 = 0.01;
+/// How far away from its original position a stuck character may be moved.
+const SEARCH_RADIUS: f32 = 3.;
+const SEARCH_RINGS: usize = 6;
+const SEARCH_DIRECTIONS: usize = 12;
+
+/// Moves characters out of geometry they overlap with, e.g. after a teleport or when a level was edited.
+/// Characters are checked when they spawn, when they are marked with [`Depenetrate`],
+/// and whenever the physics engine reports a large penetration for them.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Depenetrate>()
+        .add_event::<CharacterStuckEvent>()
+        .add_systems(
+            Update,
+            (detect_large_penetrations, depenetrate_characters)
+                .chain()
+                .after(GeneralMovementSystemSet)
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Insert this on a character after moving it without physics, e.g. when teleporting.
+/// It is removed once the character no longer overlaps any terrain.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct Depenetrate {
+    /// How many more frames the character is pushed out before searching for a free spot instead.
+    pub(crate) frames_left: u32,
+}
+
+impl Default for Depenetrate {
+    fn default() -> Self {
+        Self { frames_left: 4 }
+    }
+}
+
+/// Sent when a character overlaps terrain and there is no free position within a few meters.
+/// The character is left where it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub(crate) struct CharacterStuckEvent {
+    pub(crate) entity: Entity,
+}
+
+fn detect_large_penetrations(
+    mut commands: Commands,
+    collisions: Res<Collisions>,
+    characters: Query<(Entity, &RigidBody), (With<TnuaController>, Without<Depenetrate>)>,
+    sensors: Query<(), With<Sensor>>,
+) {
+    for (entity, rigid_body) in characters.iter() {
+        // Characters that are not dynamic, e.g. while sitting, are placed deliberately
+        if !rigid_body.is_dynamic() {
+            continue;
+        }
+        let is_deeply_penetrating = collisions
+            .collisions_with_entity(entity)
+            .filter(|contacts| {
+                !sensors.contains(contacts.entity1) && !sensors.contains(contacts.entity2)
+            })
+            .flat_map(|contacts| contacts.manifolds.iter())
+            .flat_map(|manifold| manifold.contacts.iter())
+            .any(|contact| contact.penetration > LARGE_PENETRATION);
+        if is_deeply_penetrating {
+            commands.entity(entity).insert(Depenetrate::default());
+        }
+    }
+}
+
+fn depenetrate_characters(
+    mut commands: Commands,
+    mut characters: Query<(
+        Entity,
+        &mut Transform,
+        &mut LinearVelocity,
+        &Collider,
+        &mut Depenetrate,
+    )>,
+    obstacles: Query<(&Collider, &Position, &Rotation)>,
+    spatial_query: SpatialQuery,
+    mut stuck_events: EventWriter<CharacterStuckEvent>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("depenetrate_characters").entered();
+    for (entity, mut transform, mut velocity, collider, mut depenetrate) in &mut characters {
+        let filter = SpatialQueryFilter::from_mask(CollisionLayer::Terrain.to_bits())
+            .with_excluded_entities([entity]);
+        let position = transform.translation;
+        // Characters only rotate around the Y axis, which does not change the shape of a capsule
+        let push = deepest_push(
+            &spatial_query,
+            &obstacles,
+            collider,
+            position,
+            Quat::IDENTITY,
+            &filter,
+        );
+        let Some(push) = push else {
+            commands.entity(entity).remove::<Depenetrate>();
+            continue;
+        };
+
+        if depenetrate.frames_left > 0 {
+            depenetrate.frames_left -= 1;
+            transform.translation += push;
+            let direction = push.normalize_or_zero();
+            velocity.0 -= direction * velocity.0.dot(direction);
+            continue;
+        }
+
+        commands.entity(entity).remove::<Depenetrate>();
+        if let Some(free_position) = find_free_position(&spatial_query, collider, position, &filter)
+        {
+            transform.translation = free_position;
+            velocity.0 = Vec3::ZERO;
+        } else {
+            warn!("Character {entity:?} is stuck in terrain at {position}");
+            stuck_events.send(CharacterStuckEvent { entity });
+        }
+    }
+}
+
+/// The minimal translation that moves a collider out of the obstacle it penetrates the deepest.
+/// Also used for props, see [`prop_persistence`](crate::level_instantiation::prop_persistence).
+pub(crate) fn deepest_push(
+    spatial_query: &SpatialQuery,
+    obstacles: &Query<(&Collider, &Position, &Rotation)>,
+    collider: &Collider,
+    position: Vec3,
+    rotation: Quat,
+    filter: &SpatialQueryFilter,
+) -> Option<Vec3> {
+    spatial_query
+        .shape_intersections(collider, position, rotation, filter.clone())
+        .into_iter()
+        .filter_map(|obstacle| {
+            let (obstacle_collider, obstacle_position, obstacle_rotation) =
+                obstacles.get(obstacle).ok()?;
+            contact_query::contact(
+                collider,
+                position,
+                rotation,
+                obstacle_collider,
+                obstacle_position.0,
+                obstacle_rotation.0,
+                0.,
+            )
+            .ok()
+            .flatten()
+        })
+        .filter(|contact| contact.penetration > 0.)
+        .max_by(|a, b| a.penetration.total_cmp(&b.penetration))
+        // The normal is in the local space of the collider
+        .map(|contact| rotation * -contact.normal1 * (contact.penetration + SKIN_WIDTH))
+}
+
+/// Searches rings of increasing size around the character for a spot without any overlaps, preferring spots above it.
+fn find_free_position(
+    spatial_query: &SpatialQuery,
+    collider: &Collider,
+    position: Vec3,
+    filter: &SpatialQueryFilter,
+) -> Option<Vec3> {
+    let is_free = |candidate: Vec3| {
+        spatial_query
+            .shape_intersections(collider, candidate, Quat::IDENTITY, filter.clone())
+            .is_empty()
+    };
+    (1..=SEARCH_RINGS)
+        .map(|ring| ring as f32 / SEARCH_RINGS as f32 * SEARCH_RADIUS)
+        .find_map(|distance| {
+            let above = position + Vec3::Y * distance;
+            let around = (0..SEARCH_DIRECTIONS).map(move |index| {
+                let angle = index as f32 / SEARCH_DIRECTIONS as f32 * TAU;
+                position + Quat::from_rotation_y(angle) * Vec3::X * distance
+            });
+            std::iter::once(above)
+                .chain(around)
+                .find(|&candidate| is_free(candidate))
+        })
+}
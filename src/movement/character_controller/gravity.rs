@@ -0,0 +1,192 @@
+use crate::movement::character_controller::{ActiveMovementModifiers, Jump};
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<CharacterGravityScale>()
+        .register_type::<GravityZone>();
+}
+
+/// Multiplies the gravity a character experiences on top of its [`Jump::gravity`], e.g. for a character that floats slightly.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+pub(crate) struct CharacterGravityScale(pub(crate) f32);
+
+impl Default for CharacterGravityScale {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+/// A box around the entity's origin that changes gravity for dynamic bodies inside, e.g. a moon room or an anti-gravity puzzle.
+/// Zones may be rotated around the Y axis, but not tilted.
+///
+/// Where zones overlap, they apply one after the other: first by [`GravityZone::priority`], and for the same priority
+/// from the largest to the smallest zone, so that a zone nested inside another one goes last.
+/// A [`GravityChange::Override`] thus discards what the zones before it did, while [`GravityChange::Multiply`] builds on it.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct GravityZone {
+    pub(crate) half_extents: Vec3,
+    pub(crate) change: GravityChange,
+    pub(crate) priority: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize, Default)]
+pub(crate) enum GravityChange {
+    /// Multiplies the gravity a body would experience otherwise.
+    Multiply(f32),
+    /// Replaces the gravity a body would experience otherwise, in m/s².
+    Override(f32),
+}
+
+impl Default for GravityChange {
+    fn default() -> Self {
+        Self::Multiply(1.)
+    }
+}
+
+/// The [`GravityScale`] a body had before [`apply_gravity`] first touched it, e.g. a floating prop's `GravityScale(0.)`.
+/// It takes the place of the world gravity as the base the zones start from.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct AuthoredGravityScale(f32);
+
+/// Sets the [`GravityScale`] of dynamic bodies from their [`AuthoredGravityScale`], the [`Jump::gravity`], [`CharacterGravityScale`] and
+/// [`ActiveMovementModifiers`] of characters and the [`GravityZone`]s the bodies are in. It is recomputed from scratch every frame, so that leaving a zone restores
+/// the previous gravity exactly. Tnua derives the jump's takeoff speed from the gravity the character experiences,
+/// so jumps keep their configured height inside zones.
+pub(super) fn apply_gravity(
+    mut commands: Commands,
+    gravity: Res<Gravity>,
+    zones: Query<(&GravityZone, &GlobalTransform)>,
+    mut bodies: Query<(
+        Entity,
+        &RigidBody,
+        &Position,
+        Option<&Jump>,
+        Option<&CharacterGravityScale>,
+        Option<&ActiveMovementModifiers>,
+        Option<&AuthoredGravityScale>,
+        Option<&mut GravityScale>,
+    )>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("apply_gravity").entered();
+    let world_gravity = gravity.0.length();
+    let mut zones: Vec<_> = zones.iter().collect();
+    zones.sort_by(|(a, _), (b, _)| {
+        a.priority.cmp(&b.priority).then_with(|| {
+            let volume = |zone: &GravityZone| {
+                zone.half_extents.x * zone.half_extents.y * zone.half_extents.z
+            };
+            volume(b).partial_cmp(&volume(a)).unwrap_or(Ordering::Equal)
+        })
+    });
+
+    for (
+        entity,
+        rigid_body,
+        position,
+        jump,
+        character_scale,
+        modifiers,
+        authored_scale,
+        gravity_scale,
+    ) in bodies.iter_mut()
+    {
+        if !rigid_body.is_dynamic() {
+            continue;
+        }
+        let authored_scale = match authored_scale {
+            Some(authored_scale) => authored_scale.0,
+            None => {
+                let authored_scale = gravity_scale.as_deref().map_or(1., |scale| scale.0);
+                commands
+                    .entity(entity)
+                    .insert(AuthoredGravityScale(authored_scale));
+                authored_scale
+            }
+        };
+        let base_gravity = jump.and_then(|jump| jump.gravity).unwrap_or(world_gravity);
+        let mut effective_gravity =
+            base_gravity * authored_scale * character_scale.copied().unwrap_or_default().0;
+        for (zone, transform) in &zones {
+            let local = transform.affine().inverse().transform_point3(position.0);
+            if local.abs().cmpgt(zone.half_extents).any() {
+                continue;
+            }
+            effective_gravity = match zone.change {
+                GravityChange::Multiply(factor) => effective_gravity * factor,
+                GravityChange::Override(gravity) => gravity,
+            };
+        }
+        // Multiplies even an overriding zone, so that e.g. a dream sequence stays floaty everywhere
+        if let Some(modifiers) = modifiers {
+            effective_gravity *= modifiers.0.gravity;
+        }
+        let scale = if world_gravity > 0. {
+            effective_gravity / world_gravity
+        } else {
+            1.
+        };
+        match gravity_scale {
+            Some(mut gravity_scale) => {
+                if gravity_scale.0 != scale {
+                    gravity_scale.0 = scale;
+                }
+            }
+            // Props only get a gravity scale once they need one
+            None if scale != 1. => {
+                commands.entity(entity).insert(GravityScale(scale));
+            }
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    #[test]
+    fn authored_gravity_scale_survives_a_zone() {
+        let mut app = TestApp::new();
+        let zone = GravityZone {
+            half_extents: Vec3::splat(2.),
+            change: GravityChange::Multiply(2.),
+            ..default()
+        };
+        app.world_mut().spawn((
+            zone,
+            TransformBundle::from_transform(Transform::from_xyz(10., 0., 0.)),
+        ));
+        let prop = app
+            .world_mut()
+            .spawn((
+                RigidBody::Dynamic,
+                Collider::cuboid(1., 1., 1.),
+                GravityScale(0.5),
+                SpatialBundle::default(),
+            ))
+            .id();
+        let gravity_scale = |app: &TestApp| app.world().get::<GravityScale>(prop).unwrap().0;
+        let move_to = |app: &mut TestApp, translation: Vec3| {
+            app.world_mut().get_mut::<Position>(prop).unwrap().0 = translation;
+            app.step(2);
+        };
+
+        app.step(2);
+        assert_eq!(gravity_scale(&app), 0.5);
+
+        move_to(&mut app, Vec3::new(10., 0., 0.));
+        assert_eq!(gravity_scale(&app), 1.);
+
+        move_to(&mut app, Vec3::ZERO);
+        assert_eq!(gravity_scale(&app), 0.5);
+    }
+}
@@ -0,0 +1,384 @@
+use crate::{
+    movement::{
+        character_controller::{Carried, FloatHeight, GeneralMovementSystemSet, Walk},
+        physics::CollisionLayer,
+    },
+    util::math_trait_ext::Vec3Ext,
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_tnua::{prelude::*, TnuaProximitySensor};
+use bevy_xpbd_3d::prelude::*;
+
+/// Characters slower than this stand still and make no footsteps.
+const MIN_STEP_SPEED: f32 = 0.3;
+/// The stride length is this plus [`STRIDE_PER_SPEED`] times the speed, so that running makes longer strides.
+const BASE_STRIDE: f32 = 0.4;
+const STRIDE_PER_SPEED: f32 = 0.1;
+const MAX_STRIDE: f32 = 1.5;
+
+/// Sends [`LandedEvent`] and [`LeftGroundEvent`] when a character's grounded state changes,
+/// and a [`FootstepEvent`] for every stride a character walks on the ground.
+/// Landings are measured against the ground, so stepping onto a sinking raft or a descending lift is not a hard landing.
+/// Also tracks the slope of the ground, see [`GroundedState::sliding`].
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<GroundedState>()
+        .add_event::<LandedEvent>()
+        .add_event::<LeftGroundEvent>()
+        .add_event::<FootstepEvent>()
+        .add_systems(
+            Update,
+            (detect_grounded_changes, send_footsteps)
+                .chain()
+                .after(PhysicsSet::Sync)
+                .after(GeneralMovementSystemSet)
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub(crate) struct LeftGroundEvent {
+    pub(crate) entity: Entity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub(crate) struct LandedEvent {
+    pub(crate) entity: Entity,
+    /// How fast the character was falling towards the ground right before landing,
+    /// i.e. relative to the ground's own velocity where it was touched.
+    pub(crate) impact_speed: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub(crate) struct FootstepEvent {
+    pub(crate) entity: Entity,
+    /// Where the character touches the ground, between its feet.
+    pub(crate) position: Vec3,
+    pub(crate) normal: Vec3,
+    /// The horizontal direction the character is moving in.
+    pub(crate) direction: Vec3,
+    /// The horizontal speed of the character.
+    pub(crate) speed: f32,
+    pub(crate) left_foot: bool,
+    /// The collider that was stepped on.
+    pub(crate) ground: Entity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Default)]
+#[reflect(Component)]
+pub(crate) struct GroundedState {
+    pub(crate) airborne: bool,
+    /// Seconds since the character last stood on the ground, 0 while it does.
+    pub(crate) time_since_grounded: f32,
+    /// The normal of the ground below the character, or zero while there is none within reach.
+    pub(crate) ground_normal: Vec3,
+    /// Whether the ground below the character is steeper than its [`Walk::max_slope_angle`], so that it slides down.
+    pub(crate) sliding: bool,
+    /// The velocity during the last airborne frame.
+    fall_velocity: Vec3,
+    /// How far the character walked since the last footstep.
+    stride_progress: f32,
+    left_foot: bool,
+    /// The collider the character stands on, `None` while airborne.
+    pub(crate) ground: Option<Entity>,
+}
+
+impl GroundedState {
+    /// Whether the left foot is in front while walking at `speed`. The foot that stepped last leads for the first half of the stride.
+    pub(crate) fn is_left_foot_forward(&self, speed: f32) -> bool {
+        let is_first_half = self.stride_progress < stride_length(speed) / 2.;
+        self.left_foot == is_first_half
+    }
+}
+
+fn stride_length(speed: f32) -> f32 {
+    (BASE_STRIDE + STRIDE_PER_SPEED * speed).min(MAX_STRIDE)
+}
+
+/// The ground a character stands on, to measure how fast the character hit it.
+type Grounds<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Position,
+        &'static Rotation,
+        &'static LinearVelocity,
+        Option<&'static AngularVelocity>,
+        Option<&'static CenterOfMass>,
+    ),
+>;
+
+fn detect_grounded_changes(
+    time: Res<Time>,
+    mut characters: Query<(
+        Entity,
+        &TnuaController,
+        &LinearVelocity,
+        &Transform,
+        Option<&TnuaProximitySensor>,
+        Option<&Walk>,
+        Option<&Carried>,
+        &mut GroundedState,
+    )>,
+    grounds: Grounds,
+    collider_parents: Query<&ColliderParent>,
+    mut landed_events: EventWriter<LandedEvent>,
+    mut left_ground_events: EventWriter<LeftGroundEvent>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("detect_grounded_changes").entered();
+    for (entity, controller, velocity, transform, sensor, walk, carried, mut state) in
+        characters.iter_mut()
+    {
+        // Fails while the controller is disabled or has not run yet, e.g. while sitting
+        let Ok(airborne) = controller.is_airborne() else {
+            continue;
+        };
+        match (state.airborne, airborne) {
+            (false, true) => left_ground_events.send(LeftGroundEvent { entity }),
+            (true, false) => {
+                let ground_velocity =
+                    sensor
+                        .and_then(|sensor| sensor.output.as_ref())
+                        .map_or(Vec3::ZERO, |output| {
+                            let contact = transform.translation - Vec3::Y * output.proximity;
+                            // Ground moved through its transform has no velocity of its own
+                            let carried_velocity = carried
+                                .filter(|carried| carried.ground == output.entity)
+                                .map_or(Vec3::ZERO, |carried| carried.velocity);
+                            let body = collider_parents
+                                .get(output.entity)
+                                .map_or(output.entity, |parent| parent.get());
+                            ground_velocity_at(&grounds, body, contact) + carried_velocity
+                        });
+                landed_events.send(LandedEvent {
+                    entity,
+                    impact_speed: impact_speed(state.fall_velocity, ground_velocity),
+                })
+            }
+            _ => default(),
+        };
+        let output = sensor.and_then(|sensor| sensor.output.as_ref());
+        state.ground_normal = output.map_or(Vec3::ZERO, |output| Vec3::from(output.normal));
+        state.sliding = walk.is_some_and(|walk| {
+            output.is_some() && is_too_steep(state.ground_normal, walk.max_slope_angle)
+        });
+        if airborne {
+            state.fall_velocity = velocity.0;
+            state.time_since_grounded += time.delta_seconds();
+        } else {
+            state.time_since_grounded = 0.;
+        }
+        state.airborne = airborne;
+        state.ground = sensor
+            .and_then(|sensor| sensor.output.as_ref())
+            .filter(|_| !airborne)
+            .map(|output| output.entity);
+    }
+}
+
+/// Whether ground with the given normal is steeper than `max_slope_angle`, in radians.
+fn is_too_steep(normal: Vec3, max_slope_angle: f32) -> bool {
+    normal.angle_between(Vec3::Y) > max_slope_angle
+}
+
+/// The velocity of the point `contact` on the rigid body `ground`, e.g. a dynamic prop that is falling or spinning,
+/// or a kinematic platform. Zero for anything that does not move through its velocity.
+fn ground_velocity_at(grounds: &Grounds, ground: Entity, contact: Vec3) -> Vec3 {
+    let Ok((position, rotation, linear_velocity, angular_velocity, center_of_mass)) =
+        grounds.get(ground)
+    else {
+        return Vec3::ZERO;
+    };
+    let center = position.0 + rotation.0 * center_of_mass.map_or(Vec3::ZERO, |center| center.0);
+    let angular_velocity = angular_velocity.map_or(Vec3::ZERO, |velocity| velocity.0);
+    point_velocity(linear_velocity.0, angular_velocity, contact - center)
+}
+
+/// The velocity of a point `offset` away from the center of mass of a body moving with the given velocities.
+fn point_velocity(linear_velocity: Vec3, angular_velocity: Vec3, offset: Vec3) -> Vec3 {
+    linear_velocity + angular_velocity.cross(offset)
+}
+
+/// How fast a character falling with `fall_velocity` hits ground moving with `ground_velocity`.
+fn impact_speed(fall_velocity: Vec3, ground_velocity: Vec3) -> f32 {
+    (ground_velocity.y - fall_velocity.y).max(0.)
+}
+
+fn send_footsteps(
+    time: Res<Time>,
+    mut characters: Query<(
+        Entity,
+        &Transform,
+        &LinearVelocity,
+        &FloatHeight,
+        &mut GroundedState,
+    )>,
+    spatial_query: SpatialQuery,
+    mut footstep_events: EventWriter<FootstepEvent>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("send_footsteps").entered();
+    let dt = time.delta_seconds();
+    let filter = SpatialQueryFilter::from_mask(CollisionLayer::Terrain.to_bits());
+    for (entity, transform, velocity, float_height, mut state) in characters.iter_mut() {
+        let horizontal_velocity = velocity.0.horizontal();
+        let speed = horizontal_velocity.length();
+        if state.airborne || speed < MIN_STEP_SPEED {
+            state.stride_progress = 0.;
+            continue;
+        }
+        state.stride_progress += speed * dt;
+        let stride = stride_length(speed);
+        if state.stride_progress < stride {
+            continue;
+        }
+        state.stride_progress -= stride;
+        state.left_foot = !state.left_foot;
+
+        let origin = transform.translation;
+        let Some(hit) = spatial_query.cast_ray(
+            origin,
+            Direction3d::NEG_Y,
+            float_height.0 * 1.5,
+            true,
+            filter.clone(),
+        ) else {
+            continue;
+        };
+        footstep_events.send(FootstepEvent {
+            entity,
+            position: origin - Vec3::Y * hit.time_of_impact,
+            normal: hit.normal,
+            direction: horizontal_velocity / speed,
+            speed,
+            left_foot: state.left_foot,
+            ground: hit.entity,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    const PLATFORM_SPEED: f32 = 2.;
+
+    #[derive(Component)]
+    struct Lift;
+
+    /// A platform rising from the height 0 whose collider is a child of it, like the ones in levels.
+    fn spawn_platform(app: &mut TestApp, rigid_body: RigidBody) -> Entity {
+        let world = app.world_mut();
+        let platform = world
+            .spawn((
+                Name::new("Platform"),
+                TransformBundle::default(),
+                rigid_body,
+            ))
+            .id();
+        world
+            .spawn((
+                TransformBundle::from_transform(Transform::from_xyz(0., -0.25, 0.)),
+                Collider::cuboid(4., 0.5, 4.),
+                CollisionLayers::new(
+                    [CollisionLayer::Terrain, CollisionLayer::CameraObstacle],
+                    [CollisionLayer::Character],
+                ),
+            ))
+            .set_parent(platform);
+        platform
+    }
+
+    /// Drops the player onto the rising platform and returns its landing.
+    fn land_on_platform(mut app: TestApp) -> (LandedEvent, GroundedState) {
+        let player = app.spawn_player(Vec3::new(0., 2.5, 0.));
+        app.record_events::<LandedEvent>();
+        for _ in 0..120 {
+            app.step(1);
+            if let [landing] = app.events::<LandedEvent>() {
+                let state = *app.world().get::<GroundedState>(player).unwrap();
+                return (*landing, state);
+            }
+        }
+        panic!("The player never landed");
+    }
+
+    #[test]
+    fn landings_on_kinematic_platforms_are_measured_against_their_body() {
+        let mut app = TestApp::new();
+        let platform = spawn_platform(&mut app, RigidBody::Kinematic);
+        app.world_mut()
+            .entity_mut(platform)
+            .insert(LinearVelocity(Vec3::Y * PLATFORM_SPEED));
+
+        let (landing, state) = land_on_platform(app);
+        let expected = impact_speed(state.fall_velocity, Vec3::Y * PLATFORM_SPEED);
+        assert!(
+            (landing.impact_speed - expected).abs() < 0.1,
+            "Landed at {} m/s instead of {expected} m/s",
+            landing.impact_speed
+        );
+    }
+
+    #[test]
+    fn landings_on_platforms_moved_by_transform_are_measured_against_their_motion() {
+        let mut app = TestApp::new();
+        let platform = spawn_platform(&mut app, RigidBody::Static);
+        app.world_mut().entity_mut(platform).insert(Lift);
+        app.add_systems(
+            Update,
+            |time: Res<Time>, mut lifts: Query<&mut Transform, With<Lift>>| {
+                for mut transform in lifts.iter_mut() {
+                    transform.translation.y += PLATFORM_SPEED * time.delta_seconds();
+                }
+            },
+        );
+
+        let (landing, state) = land_on_platform(app);
+        let expected = impact_speed(state.fall_velocity, Vec3::Y * PLATFORM_SPEED);
+        assert!(
+            (landing.impact_speed - expected).abs() < 0.1,
+            "Landed at {} m/s instead of {expected} m/s",
+            landing.impact_speed
+        );
+    }
+
+    #[test]
+    fn impacts_are_measured_against_the_ground_velocity() {
+        let fall_velocity = Vec3::new(1., -4., 0.);
+        assert_eq!(impact_speed(fall_velocity, Vec3::ZERO), 4.);
+        // Sinking as fast as the character falls, e.g. a raft in water
+        let sinking = Vec3::new(0., -4., 0.);
+        assert!(impact_speed(fall_velocity, sinking).abs() < 1e-5);
+        // Rising into the character, e.g. a lift going up
+        let rising = Vec3::new(0., 2., 0.);
+        assert_eq!(impact_speed(fall_velocity, rising), 6.);
+        // A platform falling faster than the character can not be hit at all
+        assert_eq!(impact_speed(fall_velocity, Vec3::NEG_Y * 10.), 0.);
+    }
+
+    #[test]
+    fn slopes_steeper_than_the_maximum_are_too_steep() {
+        let max_slope_angle = 45_f32.to_radians();
+        let slope = |degrees: f32| {
+            let angle = degrees.to_radians();
+            Vec3::new(angle.sin(), angle.cos(), 0.)
+        };
+        assert!(!is_too_steep(Vec3::Y, max_slope_angle));
+        assert!(!is_too_steep(slope(30.), max_slope_angle));
+        assert!(is_too_steep(slope(60.), max_slope_angle));
+        // A wall
+        assert!(is_too_steep(Vec3::X, max_slope_angle));
+    }
+
+    #[test]
+    fn spinning_ground_moves_faster_away_from_its_center() {
+        // A raft tilting around the Z axis, touched 2 m to the right of its center
+        let velocity = point_velocity(Vec3::NEG_Y, Vec3::Z, Vec3::X * 2.);
+        assert!(velocity.distance(Vec3::new(0., 1., 0.)) < 1e-5);
+        let velocity = point_velocity(Vec3::NEG_Y, Vec3::Z, Vec3::NEG_X * 2.);
+        assert!(velocity.distance(Vec3::new(0., -3., 0.)) < 1e-5);
+    }
+}
@@ -0,0 +1,251 @@
+use crate::{movement::physics::CollisionLayer, GameState};
+use bevy::{prelude::*, transform::TransformSystem::TransformPropagate, utils::HashSet};
+use bevy_gltf_blueprints::{AnimationPlayerLink, Animations};
+use bevy_xpbd_3d::{prelude::*, PhysicsSet};
+use serde::{Deserialize, Serialize};
+
+/// What hitboxes can hit. Props are part of the terrain.
+const HIT_LAYERS: [CollisionLayer; 3] = [
+    CollisionLayer::Player,
+    CollisionLayer::Character,
+    CollisionLayer::Terrain,
+];
+
+/// Turns [`TimedHitbox`] colliders on during a window of a character's animation, e.g. the swing of an attack,
+/// and off otherwise. While on, every collider the hitbox touches is reported once with a [`HitboxOverlapEvent`].
+/// Overlaps are queried directly instead of waiting for the next physics step, so that hits are not delayed by a frame.
+/// This runs once this frame's animations, physics and transforms are done, so the hitbox is checked where it is shown.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<TimedHitbox>()
+        .add_event::<HitboxOverlapEvent>()
+        .add_systems(
+            PostUpdate,
+            (init_hitboxes, update_hitboxes, send_hitbox_overlaps)
+                .chain()
+                .after(PhysicsSet::Sync)
+                .after(TransformPropagate)
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Put this on a collider below a character. It becomes a sensor that is only active while
+/// the character plays [`TimedHitbox::animation`] between [`TimedHitbox::start`] and [`TimedHitbox::end`].
+/// When `start` is after `end`, the window wraps around the end of a looping animation.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct TimedHitbox {
+    pub(crate) animation: String,
+    /// In seconds since the start of the animation.
+    pub(crate) start: f32,
+    /// In seconds since the start of the animation.
+    pub(crate) end: f32,
+}
+
+/// Sent when an active [`TimedHitbox`] touches a collider. Each collider is reported at most once per activation,
+/// so a swing only hits every target once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub(crate) struct HitboxOverlapEvent {
+    /// The character the hitbox belongs to.
+    pub(crate) owner: Entity,
+    pub(crate) hitbox: Entity,
+    pub(crate) other: Entity,
+}
+
+#[derive(Debug, Clone, PartialEq, Component, Default)]
+struct HitboxState {
+    /// The closest ancestor with animations.
+    owner: Option<Entity>,
+    /// The playback time of the animation last frame, while it was playing.
+    previous_time: Option<f32>,
+    active: bool,
+    /// Everything hit since the hitbox was activated.
+    hit: HashSet<Entity>,
+}
+
+fn init_hitboxes(mut commands: Commands, hitboxes: Query<Entity, Added<TimedHitbox>>) {
+    for entity in hitboxes.iter() {
+        commands
+            .entity(entity)
+            .insert((Sensor, CollisionLayers::NONE, HitboxState::default()));
+    }
+}
+
+fn update_hitboxes(
+    mut hitboxes: Query<(Entity, &TimedHitbox, &mut HitboxState, &mut CollisionLayers)>,
+    parents: Query<&Parent>,
+    characters: Query<(&AnimationPlayerLink, &Animations)>,
+    animation_players: Query<&AnimationPlayer>,
+) {
+    for (entity, hitbox, mut state, mut collision_layers) in hitboxes.iter_mut() {
+        if state.owner.is_none() {
+            state.owner = parents
+                .iter_ancestors(entity)
+                .find(|&ancestor| characters.contains(ancestor));
+        }
+        let current_time = state.owner.and_then(|owner| {
+            let (link, animations) = characters.get(owner).ok()?;
+            let clip = animations.named_animations.get(&hitbox.animation)?;
+            let player = animation_players.get(link.0).ok()?;
+            // Any other animation, e.g. after being interrupted by a hit, turns the hitbox off
+            (player.animation_clip().id() == clip.id()).then_some(player.seek_time())
+        });
+        let active = current_time.is_some_and(|current_time| {
+            let previous_time = state.previous_time.unwrap_or(current_time);
+            is_window_active(hitbox.start, hitbox.end, previous_time, current_time)
+        });
+        state.previous_time = current_time;
+
+        if active == state.active {
+            continue;
+        }
+        state.active = active;
+        if active {
+            state.hit.clear();
+            *collision_layers = CollisionLayers::new([CollisionLayer::Sensor], HIT_LAYERS);
+        } else {
+            *collision_layers = CollisionLayers::NONE;
+        }
+    }
+}
+
+fn send_hitbox_overlaps(
+    mut hitboxes: Query<(Entity, &Collider, &GlobalTransform, &mut HitboxState)>,
+    parents: Query<&Parent>,
+    spatial_query: SpatialQuery,
+    mut overlap_events: EventWriter<HitboxOverlapEvent>,
+) {
+    let filter = SpatialQueryFilter::from_mask(HIT_LAYERS);
+    for (hitbox, collider, transform, mut state) in hitboxes.iter_mut() {
+        let Some(owner) = state.owner.filter(|_| state.active) else {
+            continue;
+        };
+        let (_, rotation, translation) = transform.to_scale_rotation_translation();
+        let overlaps =
+            spatial_query.shape_intersections(collider, translation, rotation, filter.clone());
+        for other in overlaps {
+            // Characters do not hit themselves
+            let is_own = other == owner
+                || parents
+                    .iter_ancestors(other)
+                    .any(|ancestor| ancestor == owner);
+            if is_own || !state.hit.insert(other) {
+                continue;
+            }
+            overlap_events.send(HitboxOverlapEvent {
+                owner,
+                hitbox,
+                other,
+            });
+        }
+    }
+}
+
+/// Whether the window was active at any time since the last frame. This also catches windows that are shorter than
+/// a frame, which would otherwise be skipped entirely at low frame rates.
+/// Playback time going backwards means a looping animation started over.
+fn is_window_active(start: f32, end: f32, previous_time: f32, current_time: f32) -> bool {
+    let is_inside = if start <= end {
+        (start..end).contains(&current_time)
+    } else {
+        current_time >= start || current_time < end
+    };
+    let crossed_start = if current_time >= previous_time {
+        previous_time < start && start <= current_time
+    } else {
+        start > previous_time || start <= current_time
+    };
+    is_inside || crossed_start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    #[test]
+    fn windows_are_active_while_inside_or_crossed_in_a_frame() {
+        assert!(is_window_active(0.2, 0.4, 0.25, 0.3));
+        assert!(!is_window_active(0.2, 0.4, 0.1, 0.15));
+        assert!(!is_window_active(0.2, 0.4, 0.4, 0.5));
+        // Shorter than a frame
+        assert!(is_window_active(0.3, 0.31, 0.29, 0.35));
+    }
+
+    #[test]
+    fn windows_wrap_around_the_end_of_looping_animations() {
+        // From shortly before the end of the loop to shortly after its start
+        let (start, end) = (0.9, 0.1);
+        assert!(is_window_active(start, end, 0.92, 0.95));
+        assert!(is_window_active(start, end, 0.02, 0.05));
+        assert!(!is_window_active(start, end, 0.4, 0.5));
+        // Jumping over the window at the loop boundary still counts
+        assert!(is_window_active(start, end, 0.85, 0.15));
+
+        // A regular window at the very end is crossed when the animation starts over
+        assert!(is_window_active(0.95, 1., 0.9, 0.02));
+        assert!(!is_window_active(0.5, 0.6, 0.9, 0.02));
+    }
+
+    #[test]
+    fn interrupted_animations_turn_hitboxes_off() {
+        let mut app = TestApp::new();
+        let mut clips = app.world_mut().resource_mut::<Assets<AnimationClip>>();
+        let swing = clips.add(AnimationClip::default());
+        let stagger = clips.add(AnimationClip::default());
+        let mut animation_player = AnimationPlayer::default();
+        animation_player.play(swing.clone()).seek_to(0.3);
+        let world = app.world_mut();
+        let animation_player = world.spawn(animation_player).id();
+        let character = world
+            .spawn((
+                SpatialBundle::default(),
+                AnimationPlayerLink(animation_player),
+                Animations {
+                    named_animations: [
+                        ("Swing".to_string(), swing.clone()),
+                        ("Stagger".to_string(), stagger.clone()),
+                    ]
+                    .into_iter()
+                    .collect(),
+                },
+            ))
+            .id();
+        let hitbox = world
+            .spawn((
+                TransformBundle::default(),
+                Collider::sphere(0.5),
+                TimedHitbox {
+                    animation: "Swing".to_string(),
+                    start: 0.2,
+                    end: 0.4,
+                },
+            ))
+            .set_parent(character)
+            .id();
+        let is_on = |app: &TestApp| {
+            *app.world().get::<CollisionLayers>(hitbox).unwrap() != CollisionLayers::NONE
+        };
+        let play = |app: &mut TestApp, clip: &Handle<AnimationClip>, time: f32| {
+            app.world_mut()
+                .get_mut::<AnimationPlayer>(animation_player)
+                .unwrap()
+                .play(clip.clone())
+                .seek_to(time);
+            app.step(1);
+        };
+
+        app.step(2);
+        assert!(is_on(&app));
+        // Getting hit mid-swing
+        play(&mut app, &stagger, 0.3);
+        assert!(!is_on(&app));
+        play(&mut app, &stagger, 0.35);
+        assert!(!is_on(&app));
+        // The next swing starts over
+        play(&mut app, &swing, 0.);
+        assert!(!is_on(&app));
+        play(&mut app, &swing, 0.25);
+        assert!(is_on(&app));
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{
+    movement::{
+        character_controller::{
+            ActiveMovementModifiers, Depenetrate, FloatHeight, GeneralMovementSystemSet,
+            PlayOneShotAnimation, Stamina,
+        },
+        physics::CollisionLayer,
+    },
+    util::math_trait_ext::Vec3Ext,
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_gltf_blueprints::Animations;
+use bevy_tnua::{prelude::*, TnuaToggle};
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Radius of the sphere that is cast forward to find walls.
+const WALL_PROBE_RADIUS: f32 = 0.1;
+/// Walls steeper than this, measured as the vertical part of their normal, cannot be grabbed.
+const MAX_WALL_NORMAL_Y: f32 = 0.3;
+/// How long after dropping off a ledge the character cannot grab one again.
+const REGRAB_COOLDOWN: f32 = 0.5;
+
+/// Lets airborne characters grab ledges between [`LedgeGrab::min_height`] and [`LedgeGrab::max_height`] in front of them.
+/// While hanging, [`LedgeGrab::climb_requested`] climbs up and [`LedgeGrab::drop_requested`] lets go.
+/// Characters with [`Stamina`] need enough of it to climb up, otherwise they keep hanging.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<LedgeGrab>().add_systems(
+        Update,
+        (detect_ledges, update_hanging_characters)
+            .chain()
+            .before(GeneralMovementSystemSet)
+            .run_if(in_state(GameState::Playing)),
+    );
+}
+
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct LedgeGrab {
+    /// Lowest ledge that can be grabbed, measured from the character's feet.
+    pub(crate) min_height: f32,
+    /// Highest ledge that can be grabbed, measured from the character's feet.
+    pub(crate) max_height: f32,
+    /// How fast the character needs to move towards the wall to grab it.
+    pub(crate) min_approach_speed: f32,
+    /// How far in front of the character's collider a wall is detected.
+    pub(crate) reach: f32,
+    /// How far the character's origin hangs below the ledge.
+    pub(crate) hang_offset: f32,
+    pub(crate) climb_animation: String,
+    /// Was climbing up requested this frame?
+    pub(crate) climb_requested: bool,
+    /// Was letting go requested this frame?
+    pub(crate) drop_requested: bool,
+}
+
+impl Default for LedgeGrab {
+    fn default() -> Self {
+        Self {
+            min_height: 0.7,
+            max_height: 1.6,
+            min_approach_speed: 1.,
+            reach: 0.3,
+            hang_offset: 0.5,
+            climb_animation: "climb_up".to_string(),
+            climb_requested: false,
+            drop_requested: false,
+        }
+    }
+}
+
+/// Present while a character hangs on or climbs up a ledge.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub(crate) struct LedgeHang {
+    /// Points away from the wall.
+    pub(crate) wall_normal: Vec3,
+    hang_position: Vec3,
+    climb_target: Vec3,
+    climbing: Option<Timer>,
+}
+
+#[derive(Debug, Clone, PartialEq, Component)]
+struct LedgeGrabCooldown(Timer);
+
+fn detect_ledges(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut characters: Query<
+        (
+            Entity,
+            &Transform,
+            &LinearVelocity,
+            &Collider,
+            &FloatHeight,
+            &TnuaController,
+            &LedgeGrab,
+            Option<&mut LedgeGrabCooldown>,
+        ),
+        Without<LedgeHang>,
+    >,
+    spatial_query: SpatialQuery,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("detect_ledges").entered();
+    for (entity, transform, velocity, collider, float_height, controller, grab, cooldown) in
+        &mut characters
+    {
+        if let Some(mut cooldown) = cooldown {
+            if !cooldown.0.tick(time.delta()).finished() {
+                continue;
+            }
+            commands.entity(entity).remove::<LedgeGrabCooldown>();
+        }
+        if !controller.is_airborne().unwrap_or(false) {
+            continue;
+        }
+        let forward = transform.forward().horizontal().normalize_or_zero();
+        if velocity.0.horizontal().dot(forward) < grab.min_approach_speed {
+            continue;
+        }
+        let filter = SpatialQueryFilter::from_mask(CollisionLayer::Terrain.to_bits())
+            .with_excluded_entities([entity]);
+        let Some(hang) = find_ledge(
+            &spatial_query,
+            &filter,
+            transform.translation,
+            forward,
+            collider,
+            float_height.0,
+            grab,
+        ) else {
+            continue;
+        };
+        commands.entity(entity).insert((
+            hang,
+            TnuaToggle::Disabled,
+            RigidBody::Kinematic,
+            LinearVelocity::default(),
+        ));
+    }
+}
+
+fn find_ledge(
+    spatial_query: &SpatialQuery,
+    filter: &SpatialQueryFilter,
+    position: Vec3,
+    forward: Vec3,
+    collider: &Collider,
+    float_height: f32,
+    grab: &LedgeGrab,
+) -> Option<LedgeHang> {
+    let radius = collider
+        .shape_scaled()
+        .compute_local_aabb()
+        .half_extents()
+        .x;
+    let feet = position - Vec3::Y * float_height;
+    let direction = Direction3d::new(forward).ok()?;
+    let wall = spatial_query.cast_shape(
+        &Collider::sphere(WALL_PROBE_RADIUS),
+        position,
+        Quat::IDENTITY,
+        direction,
+        radius + grab.reach,
+        true,
+        filter.clone(),
+    )?;
+    let wall_normal = wall.normal1.horizontal().normalize_or_zero();
+    if wall.normal1.y.abs() > MAX_WALL_NORMAL_Y || wall_normal == Vec3::ZERO {
+        return None;
+    }
+
+    // Look down onto the top of the wall, a bit behind its face
+    let probe_top = feet.y + grab.max_height + WALL_PROBE_RADIUS;
+    let probe_origin = Vec3::new(wall.point1.x, probe_top, wall.point1.z) - wall_normal * 0.2;
+    let top = spatial_query.cast_ray(
+        probe_origin,
+        Direction3d::NEG_Y,
+        grab.max_height - grab.min_height,
+        true,
+        filter.clone(),
+    )?;
+    let ledge_height = probe_top - top.time_of_impact;
+    if ledge_height - feet.y < grab.min_height {
+        return None;
+    }
+
+    // The space above the character needs to be free to pull itself up
+    let headroom = ledge_height + float_height - position.y;
+    if headroom > 0.
+        && spatial_query
+            .cast_ray(position, Direction3d::Y, headroom, true, filter.clone())
+            .is_some()
+    {
+        return None;
+    }
+
+    let wall_point = Vec3::new(wall.point1.x, ledge_height, wall.point1.z);
+    Some(LedgeHang {
+        wall_normal,
+        hang_position: wall_point + wall_normal * radius - Vec3::Y * grab.hang_offset,
+        climb_target: wall_point - wall_normal * radius * 2. + Vec3::Y * float_height,
+        climbing: None,
+    })
+}
+
+fn update_hanging_characters(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut characters: Query<(
+        Entity,
+        &mut Transform,
+        &mut LedgeGrab,
+        &mut LedgeHang,
+        &Collider,
+        Option<&Animations>,
+        Option<&mut Stamina>,
+        Option<&ActiveMovementModifiers>,
+    )>,
+    clips: Res<Assets<AnimationClip>>,
+    spatial_query: SpatialQuery,
+    mut one_shot_events: EventWriter<PlayOneShotAnimation>,
+) {
+    for (entity, mut transform, mut grab, mut hang, collider, animations, stamina, modifiers) in
+        &mut characters
+    {
+        let climb_requested = std::mem::take(&mut grab.climb_requested);
+        let drop_requested = std::mem::take(&mut grab.drop_requested);
+        if let Some(climbing) = hang.climbing.as_mut() {
+            if climbing.tick(time.delta()).finished() {
+                transform.translation = hang.climb_target;
+                release(&mut commands, entity);
+            }
+            continue;
+        }
+
+        transform.translation = hang.hang_position;
+        transform.look_to(-hang.wall_normal, Vec3::Y);
+        if drop_requested {
+            transform.translation += hang.wall_normal * 0.1;
+            release(&mut commands, entity);
+            commands
+                .entity(entity)
+                .insert(LedgeGrabCooldown(Timer::from_seconds(
+                    REGRAB_COOLDOWN,
+                    TimerMode::Once,
+                )));
+        } else if climb_requested {
+            let filter = SpatialQueryFilter::from_mask(CollisionLayer::Terrain.to_bits())
+                .with_excluded_entities([entity]);
+            let is_blocked = !spatial_query
+                .shape_intersections(collider, hang.climb_target, Quat::IDENTITY, filter)
+                .is_empty();
+            if is_blocked {
+                continue;
+            }
+            let stamina_drain = modifiers.map_or(1., |modifiers| modifiers.0.stamina_drain);
+            if let Some(mut stamina) = stamina {
+                let cost = stamina.climb_cost * stamina_drain;
+                if !stamina.try_spend(cost) {
+                    continue;
+                }
+            }
+            let duration = animations
+                .and_then(|animations| animations.named_animations.get(&grab.climb_animation))
+                .and_then(|clip| clips.get(clip))
+                .map_or(0., |clip| clip.duration());
+            one_shot_events.send(PlayOneShotAnimation {
+                entity,
+                animation: grab.climb_animation.clone(),
+            });
+            hang.climbing = Some(Timer::from_seconds(duration, TimerMode::Once));
+        }
+    }
+}
+
+fn release(commands: &mut Commands, entity: Entity) {
+    commands.entity(entity).remove::<LedgeHang>().insert((
+        TnuaToggle::Enabled,
+        RigidBody::Dynamic,
+        Depenetrate::default(),
+    ));
+}
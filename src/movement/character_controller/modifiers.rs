@@ -0,0 +1,226 @@
+use crate::{
+    level_instantiation::{on_spawn::Player, portal::Travel},
+    movement::character_controller::{GeneralMovementSystemSet, Walk},
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// For this many seconds after arriving from a loaded save, the player keeps the modifiers it was saved with
+/// until a zone covering it has spawned.
+const RESTORE_GRACE: f32 = 1.;
+
+/// Changes the movement rules of characters in parts of a level or in a whole level, e.g. floaty low gravity in a dream
+/// sequence, no jumping indoors or boosted speed in a race.
+/// - A [`MovementModifierZone`] applies to the characters inside it.
+/// - A [`LevelMovementModifiers`] in the level file applies to every character in the level.
+///
+/// The modifiers of all of these that apply to a character are stacked into its [`ActiveMovementModifiers`] every frame,
+/// so that leaving a zone restores the previous rules exactly. The player's modifiers are saved with it.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<MovementModifiers>()
+        .register_type::<MovementModifierZone>()
+        .register_type::<LevelMovementModifiers>()
+        .register_type::<ActiveMovementModifiers>()
+        .init_resource::<RestoredMovementModifiers>()
+        .add_systems(
+            Update,
+            update_movement_modifiers
+                .before(GeneralMovementSystemSet)
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Stacks by multiplying the factors and combining the flags with "or".
+#[derive(Debug, Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct MovementModifiers {
+    /// Multiplies the gravity the character experiences.
+    pub(crate) gravity: f32,
+    /// Multiplies [`Walk::acceleration`].
+    pub(crate) acceleration: f32,
+    /// Multiplies the walking and running speed.
+    pub(crate) speed: f32,
+    /// Multiplies the speed a jump takes off with. Since jumps keep their height under changed gravity,
+    /// a low gravity zone on its own only makes jumps floatier.
+    pub(crate) jump: f32,
+    /// Multiplies the [`Stamina`](super::Stamina) that sprinting and climbing cost.
+    pub(crate) stamina_drain: f32,
+    /// Multiplies how fast [`Stamina`](super::Stamina) is restored.
+    pub(crate) stamina_regen: f32,
+    pub(crate) disable_jump: bool,
+    pub(crate) disable_sprint: bool,
+}
+
+impl Default for MovementModifiers {
+    fn default() -> Self {
+        Self {
+            gravity: 1.,
+            acceleration: 1.,
+            speed: 1.,
+            jump: 1.,
+            stamina_drain: 1.,
+            stamina_regen: 1.,
+            disable_jump: false,
+            disable_sprint: false,
+        }
+    }
+}
+
+impl MovementModifiers {
+    pub(crate) fn stack(self, other: Self) -> Self {
+        Self {
+            gravity: self.gravity * other.gravity,
+            acceleration: self.acceleration * other.acceleration,
+            speed: self.speed * other.speed,
+            jump: self.jump * other.jump,
+            stamina_drain: self.stamina_drain * other.stamina_drain,
+            stamina_regen: self.stamina_regen * other.stamina_regen,
+            disable_jump: self.disable_jump || other.disable_jump,
+            disable_sprint: self.disable_sprint || other.disable_sprint,
+        }
+    }
+
+    /// Whether these change nothing.
+    pub(crate) fn is_neutral(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// What a jump of `height` becomes. The height grows with the square of the takeoff speed.
+    pub(crate) fn jump_height(&self, height: f32) -> f32 {
+        height * self.jump * self.jump
+    }
+}
+
+/// A box around the entity's origin that applies its modifiers to characters inside.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct MovementModifierZone {
+    pub(crate) half_extents: Vec3,
+    pub(crate) modifiers: MovementModifiers,
+}
+
+/// The modifiers for every character in the level, described in the level file. Levels have at most one.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct LevelMovementModifiers(pub(crate) MovementModifiers);
+
+/// The stacked modifiers that currently apply to a character. Only present while they change anything.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Default)]
+#[reflect(Component)]
+pub(crate) struct ActiveMovementModifiers(pub(crate) MovementModifiers);
+
+/// The player's modifiers from a loaded save. The zones that caused them have not spawned yet when the player arrives.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Default)]
+pub(crate) struct RestoredMovementModifiers {
+    modifiers: Option<MovementModifiers>,
+    grace: f32,
+}
+
+impl RestoredMovementModifiers {
+    pub(crate) fn new(modifiers: MovementModifiers) -> Self {
+        Self {
+            modifiers: Some(modifiers),
+            grace: RESTORE_GRACE,
+        }
+    }
+}
+
+fn update_movement_modifiers(
+    mut commands: Commands,
+    time: Res<Time>,
+    travel: Option<Res<Travel>>,
+    mut restored: ResMut<RestoredMovementModifiers>,
+    zones: Query<(&MovementModifierZone, &GlobalTransform)>,
+    levels: Query<&LevelMovementModifiers>,
+    characters: Query<
+        (
+            Entity,
+            &Position,
+            Option<&ActiveMovementModifiers>,
+            Has<Player>,
+        ),
+        With<Walk>,
+    >,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_movement_modifiers").entered();
+    let is_traveling = travel.is_some_and(|travel| travel.is_traveling());
+    if !is_traveling {
+        restored.grace -= time.delta_seconds();
+    }
+    if restored.grace <= 0. {
+        restored.modifiers = None;
+    }
+    let level = levels
+        .iter()
+        .fold(MovementModifiers::default(), |modifiers, level| {
+            modifiers.stack(level.0)
+        });
+    for (entity, position, active, is_player) in characters.iter() {
+        let mut in_zone = false;
+        let modifiers = match restored.modifiers.filter(|_| is_player) {
+            // Positions are not reliable while the level is exchanged
+            Some(modifiers) if is_traveling => modifiers,
+            _ if is_traveling => continue,
+            saved => {
+                let mut modifiers = level;
+                for (zone, transform) in zones.iter() {
+                    let local = transform.affine().inverse().transform_point3(position.0);
+                    if local.abs().cmple(zone.half_extents).all() {
+                        modifiers = modifiers.stack(zone.modifiers);
+                        in_zone = true;
+                    }
+                }
+                saved.filter(|_| !in_zone).unwrap_or(modifiers)
+            }
+        };
+        if is_player && in_zone {
+            restored.modifiers = None;
+        }
+        let active_modifiers = active.map(|active| active.0).unwrap_or_default();
+        if modifiers == active_modifiers {
+            continue;
+        }
+        if modifiers.is_neutral() {
+            commands.entity(entity).remove::<ActiveMovementModifiers>();
+        } else {
+            commands
+                .entity(entity)
+                .insert(ActiveMovementModifiers(modifiers));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlapping_modifiers_multiply_and_combine_flags() {
+        let low_gravity = MovementModifiers {
+            gravity: 0.5,
+            speed: 0.8,
+            ..default()
+        };
+        let race = MovementModifiers {
+            speed: 1.5,
+            disable_jump: true,
+            ..default()
+        };
+
+        let stacked = low_gravity.stack(race);
+
+        assert_eq!(stacked.gravity, 0.5);
+        assert!((stacked.speed - 1.2).abs() < 1e-5);
+        assert!(stacked.disable_jump);
+        assert!(!stacked.disable_sprint);
+        assert!(MovementModifiers::default()
+            .stack(MovementModifiers::default())
+            .is_neutral());
+    }
+}
@@ -0,0 +1,252 @@
+use crate::{
+    movement::character_controller::{GeneralMovementSystemSet, GroundedState},
+    util::math_trait_ext::Vec3Ext,
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_tnua::{prelude::*, TnuaProximitySensor, TnuaToggle};
+use bevy_xpbd_3d::{prelude::*, PhysicsSet};
+
+/// Carries characters along with the ground they stand on when that ground is moved through its [`Transform`],
+/// e.g. a platform with an animation or a lift moved by a signal. Tnua already follows ground that moves through
+/// its velocity, so those are left to it.
+/// - While a character stands on such ground, it is moved along with it every frame. On rotating ground,
+///   it orbits along and turns with it around the vertical axis.
+/// - When it leaves the ground into the air, e.g. by jumping or stepping off the edge, it keeps the ground's velocity once.
+///   After that, it is up to air control.
+/// - While such ground is below a character in the air, its velocity is still tracked, so that landings on it
+///   are measured against it, see [`LandedEvent`](super::LandedEvent).
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        carry_characters
+            .before(GeneralMovementSystemSet)
+            .before(PhysicsSet::Prepare)
+            .run_if(in_state(GameState::Playing)),
+    );
+}
+
+/// On characters above or on ground that is moved without physics.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub(crate) struct Carried {
+    /// The collider below the character.
+    pub(crate) ground: Entity,
+    /// Where the ground was in the last frame.
+    ground_transform: GlobalTransform,
+    /// How fast the ground moved where the character is in the last frame.
+    pub(crate) velocity: Vec3,
+    /// Whether the character stood on the ground in the last frame, instead of being in the air above it.
+    riding: bool,
+}
+
+fn carry_characters(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut characters: Query<
+        (
+            Entity,
+            &GroundedState,
+            Option<&TnuaProximitySensor>,
+            &mut Transform,
+            &mut LinearVelocity,
+            Option<&mut Carried>,
+            Option<&TnuaToggle>,
+        ),
+        With<TnuaController>,
+    >,
+    grounds: Query<&GlobalTransform>,
+    collider_parents: Query<&ColliderParent>,
+    bodies: Query<(Option<&LinearVelocity>, Option<&AngularVelocity>), Without<TnuaController>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("carry_characters").entered();
+    let dt = time.delta_seconds();
+    // Other characters are moved by physics, too
+    let is_moved_by_physics = |ground: Entity| {
+        let body = collider_parents
+            .get(ground)
+            .map_or(ground, |parent| parent.get());
+        bodies
+            .get(body)
+            .map_or(true, |(linear_velocity, angular_velocity)| {
+                linear_velocity.is_some_and(|velocity| !velocity.0.is_approx_zero())
+                    || angular_velocity.is_some_and(|velocity| !velocity.0.is_approx_zero())
+            })
+    };
+    for (entity, state, sensor, mut transform, mut velocity, carried, toggle) in
+        characters.iter_mut()
+    {
+        // Sitting or hanging characters are placed deliberately
+        let is_controlled = toggle.map_or(true, |toggle| *toggle != TnuaToggle::Disabled);
+        // Also the ground below the character while it is in the air, to know how fast it moves when landing on it
+        let ground = sensor
+            .and_then(|sensor| sensor.output.as_ref())
+            .map(|output| output.entity)
+            .filter(|&ground| is_controlled && !is_moved_by_physics(ground))
+            .and_then(|ground| Some((ground, *grounds.get(ground).ok()?)));
+        let riding = !state.airborne;
+        match (carried, ground) {
+            (Some(mut carried), Some((ground, ground_transform))) if carried.ground == ground => {
+                if !riding && carried.riding {
+                    velocity.0 += carried.velocity;
+                }
+                // Ground that rests for a moment does not touch the character, so that it is not marked as changed
+                let delta = if carried.ground_transform == ground_transform {
+                    Vec3::ZERO
+                } else if riding {
+                    carry(&carried.ground_transform, &ground_transform, &mut transform)
+                } else {
+                    moved_point(
+                        &carried.ground_transform,
+                        &ground_transform,
+                        transform.translation,
+                    ) - transform.translation
+                };
+                carried.set_if_neq(Carried {
+                    ground,
+                    ground_transform,
+                    velocity: if dt > 0. { delta / dt } else { Vec3::ZERO },
+                    riding,
+                });
+            }
+            (carried, ground) => {
+                if let Some(carried) = carried {
+                    if is_controlled && state.airborne && carried.riding {
+                        velocity.0 += carried.velocity;
+                    }
+                    commands.entity(entity).remove::<Carried>();
+                }
+                if let Some((ground, ground_transform)) = ground {
+                    commands.entity(entity).insert(Carried {
+                        ground,
+                        ground_transform,
+                        velocity: Vec3::ZERO,
+                        riding: false,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Moves `transform` along with ground that moved from `from` to `to` and returns how far it was moved.
+/// Only the ground's turn around the vertical axis is applied to the rotation, so that the character stays upright.
+fn carry(from: &GlobalTransform, to: &GlobalTransform, transform: &mut Transform) -> Vec3 {
+    let carried = moved_point(from, to, transform.translation);
+    let (_, from_rotation, _) = from.to_scale_rotation_translation();
+    let (_, to_rotation, _) = to.to_scale_rotation_translation();
+    let (yaw, _, _) = (to_rotation * from_rotation.inverse()).to_euler(EulerRot::YXZ);
+    transform.rotation = Quat::from_rotation_y(yaw) * transform.rotation;
+    let delta = carried - transform.translation;
+    transform.translation = carried;
+    delta
+}
+
+/// Where `point` ends up when it moves along with ground that moved from `from` to `to`.
+fn moved_point(from: &GlobalTransform, to: &GlobalTransform, point: Vec3) -> Vec3 {
+    let local = from.affine().inverse().transform_point3(point);
+    to.transform_point(local)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        movement::physics::CollisionLayer,
+        player_control::actions::PlayerAction,
+        testing::{assert_near, InputScript, TestApp},
+    };
+    use std::f32::consts::FRAC_PI_2;
+
+    const PLATFORM_VELOCITY: Vec3 = Vec3::new(2., 0., 0.);
+
+    #[derive(Component)]
+    struct Conveyor;
+
+    /// A static platform at the height 0 that an animation slides along +X, with the player standing on it.
+    fn ride_platform() -> (TestApp, Entity) {
+        let mut app = TestApp::new();
+        app.world_mut().spawn((
+            Name::new("Platform"),
+            Conveyor,
+            TransformBundle::from_transform(Transform::from_xyz(0., -0.25, 0.)),
+            RigidBody::Static,
+            Collider::cuboid(20., 0.5, 4.),
+            CollisionLayers::new(
+                [CollisionLayer::Terrain, CollisionLayer::CameraObstacle],
+                [CollisionLayer::Character],
+            ),
+        ));
+        app.add_systems(
+            Update,
+            (|time: Res<Time>, mut conveyors: Query<&mut Transform, With<Conveyor>>| {
+                for mut transform in conveyors.iter_mut() {
+                    transform.translation += PLATFORM_VELOCITY * time.delta_seconds();
+                }
+            })
+            .before(carry_characters),
+        );
+        let player = app.spawn_player(Vec3::new(-8., 1., 0.));
+        app.step(60);
+        (app, player)
+    }
+
+    #[test]
+    fn jumping_off_moving_ground_keeps_its_velocity_once() {
+        let (mut app, player) = ride_platform();
+        assert!(app.world().get::<Carried>(player).unwrap().riding);
+        let before = app.translation(player);
+        app.step(30);
+        assert_near(
+            app.translation(player) - before,
+            PLATFORM_VELOCITY * 0.5,
+            0.05,
+        );
+
+        app.script(InputScript::new().hold(PlayerAction::Jump, 10));
+        let is_riding = |app: &TestApp| {
+            app.world()
+                .get::<Carried>(player)
+                .is_some_and(|carried| carried.riding)
+        };
+        for _ in 0..10 {
+            app.step(1);
+            if !is_riding(&app) {
+                break;
+            }
+        }
+        assert!(!is_riding(&app), "the player never took off");
+        // Standing still on the platform, all of this comes from it. Air control already brakes it a bit
+        let velocity = app.world().get::<LinearVelocity>(player).unwrap().0;
+        assert_near(
+            Vec3::new(velocity.x, 0., velocity.z),
+            PLATFORM_VELOCITY,
+            0.5,
+        );
+    }
+
+    #[test]
+    fn characters_orbit_and_turn_with_rotating_ground() {
+        let from = GlobalTransform::from_xyz(0., 1., 0.);
+        let mut transform = Transform::from_xyz(2., 2., 0.);
+
+        // Rising lifts the character without turning it
+        let risen = GlobalTransform::from_xyz(0., 3., 0.);
+        let delta = carry(&from, &risen, &mut transform);
+        assert_near(delta, Vec3::Y * 2., 1e-4);
+        assert_near(transform.translation, Vec3::new(2., 4., 0.), 1e-4);
+        assert_eq!(transform.rotation, Quat::IDENTITY);
+
+        // A quarter turn carries the character around the center and turns it by as much
+        let turned = GlobalTransform::from(
+            Transform::from_xyz(0., 3., 0.).with_rotation(Quat::from_rotation_y(FRAC_PI_2)),
+        );
+        carry(&risen, &turned, &mut transform);
+        assert_near(transform.translation, Vec3::new(0., 4., -2.), 1e-4);
+        assert_near(
+            transform.rotation * Vec3::X,
+            Quat::from_rotation_y(FRAC_PI_2) * Vec3::X,
+            1e-4,
+        );
+    }
+}
@@ -0,0 +1,345 @@
+#[cfg(feature = "dev")]
+use crate::dev::dev_tools::{DevTools, RegisterDevToolExt};
+#[cfg(feature = "dev")]
+use crate::level_instantiation::on_spawn::Player;
+#[cfg(feature = "dev")]
+use crate::player_control::ui_layer::UiLayer;
+use crate::{
+    movement::character_controller::{Jump, Sprinting, Stamina, Walk},
+    GameState,
+};
+use bevy::{prelude::*, utils::HashMap};
+use bevy_common_assets::ron::RonAssetPlugin;
+#[cfg(feature = "dev")]
+use bevy_egui::{egui, EguiContexts};
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Applies the movement tuning in `assets/config/config.movement.ron` to every character with a [`MovementProfile`].
+/// The values are applied again whenever the file changes, so the feel can be tweaked while the game runs.
+/// Only the tuning is overwritten; runtime state like requested jumps or the walking direction is kept.
+pub(super) fn plugin(app: &mut App) {
+    app.add_plugins(RonAssetPlugin::<MovementConfig>::new(&["movement.ron"]))
+        .register_type::<MovementProfile>()
+        .add_systems(
+            Update,
+            apply_movement_profiles.run_if(in_state(GameState::Playing)),
+        );
+    #[cfg(feature = "dev")]
+    app.register_dev_tool("Movement Profiles", None, |_: In<bool>| {})
+        .add_systems(
+            Update,
+            select_player_profile.run_if(
+                in_state(GameState::Playing)
+                    .and_then(|dev_tools: Res<DevTools>| dev_tools.is_active("Movement Profiles")),
+            ),
+        );
+}
+
+#[derive(Debug, Clone, PartialEq, Asset, Reflect, Serialize, Deserialize, Default)]
+pub(crate) struct MovementConfig {
+    pub(crate) profiles: HashMap<String, MovementProfileSettings>,
+}
+
+/// Unset values are inherited from the profile this `extends`, or else keep the defaults of the components.
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct MovementProfileSettings {
+    pub(crate) extends: Option<String>,
+    /// See [`Walk::speed`].
+    pub(crate) speed: Option<f32>,
+    /// See [`Walk::walk_speed`].
+    pub(crate) walk_speed: Option<f32>,
+    /// See [`Walk::acceleration`].
+    pub(crate) acceleration: Option<f32>,
+    /// See [`Walk::deceleration`].
+    pub(crate) deceleration: Option<f32>,
+    /// Seconds to reach `speed` from standing, see [`Walk::from_speeds`]. Takes precedence over `acceleration`.
+    pub(crate) time_to_max_speed: Option<f32>,
+    /// Seconds to come to a halt from `speed`, see [`Walk::from_speeds`]. Takes precedence over `deceleration`.
+    pub(crate) stop_time: Option<f32>,
+    /// See [`Sprinting::multiplier`].
+    pub(crate) sprint_multiplier: Option<f32>,
+    /// See [`Sprinting::acceleration`].
+    pub(crate) sprint_acceleration: Option<f32>,
+    /// See [`Jump::height`].
+    pub(crate) jump_height: Option<f32>,
+    /// See [`Jump::gravity`].
+    pub(crate) gravity: Option<f32>,
+    /// Seconds to reach the apex of a jump, see [`Jump::from_height_and_time`]. Takes precedence over `gravity`.
+    pub(crate) time_to_apex: Option<f32>,
+    /// How strongly the character's velocity decays on its own, e.g. while airborne.
+    pub(crate) linear_damping: Option<f32>,
+    /// In kg. Defaults to the mass computed from the collider.
+    pub(crate) mass: Option<f32>,
+    /// See [`Stamina::max`]. Only affects characters that have [`Stamina`].
+    pub(crate) stamina: Option<f32>,
+    /// See [`Stamina::regen_rate`].
+    pub(crate) stamina_regen_rate: Option<f32>,
+    /// See [`Stamina::regen_delay`].
+    pub(crate) stamina_regen_delay: Option<f32>,
+    /// See [`Stamina::sprint_drain`].
+    pub(crate) sprint_stamina_drain: Option<f32>,
+    /// See [`Stamina::climb_cost`].
+    pub(crate) climb_stamina_cost: Option<f32>,
+    /// See [`Stamina::recovery_threshold`].
+    pub(crate) stamina_recovery_threshold: Option<f32>,
+}
+
+impl MovementProfileSettings {
+    /// Fills in the values that are unset here with those of `parent`.
+    fn inherit(&mut self, parent: &Self) {
+        self.speed = self.speed.or(parent.speed);
+        self.walk_speed = self.walk_speed.or(parent.walk_speed);
+        self.acceleration = self.acceleration.or(parent.acceleration);
+        self.deceleration = self.deceleration.or(parent.deceleration);
+        self.time_to_max_speed = self.time_to_max_speed.or(parent.time_to_max_speed);
+        self.stop_time = self.stop_time.or(parent.stop_time);
+        self.sprint_multiplier = self.sprint_multiplier.or(parent.sprint_multiplier);
+        self.sprint_acceleration = self.sprint_acceleration.or(parent.sprint_acceleration);
+        self.jump_height = self.jump_height.or(parent.jump_height);
+        self.gravity = self.gravity.or(parent.gravity);
+        self.time_to_apex = self.time_to_apex.or(parent.time_to_apex);
+        self.linear_damping = self.linear_damping.or(parent.linear_damping);
+        self.mass = self.mass.or(parent.mass);
+        self.stamina = self.stamina.or(parent.stamina);
+        self.stamina_regen_rate = self.stamina_regen_rate.or(parent.stamina_regen_rate);
+        self.stamina_regen_delay = self.stamina_regen_delay.or(parent.stamina_regen_delay);
+        self.sprint_stamina_drain = self.sprint_stamina_drain.or(parent.sprint_stamina_drain);
+        self.climb_stamina_cost = self.climb_stamina_cost.or(parent.climb_stamina_cost);
+        self.stamina_recovery_threshold = self
+            .stamina_recovery_threshold
+            .or(parent.stamina_recovery_threshold);
+    }
+}
+
+impl MovementConfig {
+    /// The settings of a profile with everything it extends merged in.
+    pub(crate) fn resolve(&self, name: &str) -> Option<MovementProfileSettings> {
+        let mut resolved = self.profiles.get(name)?.clone();
+        let mut visited = vec![name];
+        let mut parent_name = resolved.extends.as_deref();
+        while let Some(name) = parent_name {
+            if visited.contains(&name) {
+                error!(
+                    "Movement profile \"{}\" extends itself via \"{name}\"",
+                    visited[0]
+                );
+                break;
+            }
+            let Some(parent) = self.profiles.get(name) else {
+                error!(
+                    "Movement profile \"{}\" extends unknown profile \"{name}\"",
+                    visited[0]
+                );
+                break;
+            };
+            resolved.inherit(parent);
+            visited.push(name);
+            parent_name = parent.extends.as_deref();
+        }
+        Some(resolved)
+    }
+}
+
+/// The name of the movement profile in `assets/config/config.movement.ron` that tunes this character.
+#[derive(Debug, Clone, PartialEq, Eq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct MovementProfile(pub(crate) String);
+
+impl MovementProfile {
+    pub(crate) fn new(name: impl Into<String>) -> Self {
+        Self(name.into())
+    }
+}
+
+fn apply_movement_profiles(
+    mut config_events: EventReader<AssetEvent<MovementConfig>>,
+    configs: Res<Assets<MovementConfig>>,
+    mut characters: Query<(
+        Ref<MovementProfile>,
+        &mut Walk,
+        &mut Sprinting,
+        &mut Jump,
+        Option<&mut LinearDamping>,
+        Option<&mut Mass>,
+        Option<&mut InverseMass>,
+        Option<&mut Stamina>,
+    )>,
+) {
+    let config_changed = config_events.read().any(|event| {
+        matches!(
+            event,
+            AssetEvent::LoadedWithDependencies { .. } | AssetEvent::Modified { .. }
+        )
+    });
+    let Some(config) = configs.iter().next().map(|(_, config)| config) else {
+        return;
+    };
+    for (profile, mut walk, mut sprinting, mut jump, damping, mass, inverse_mass, stamina) in
+        characters.iter_mut()
+    {
+        if !config_changed && !profile.is_changed() {
+            continue;
+        }
+        let Some(settings) = config.resolve(&profile.0) else {
+            error!("Unknown movement profile \"{}\"", profile.0);
+            continue;
+        };
+        let defaults = Walk::default();
+        let speed = settings.speed.unwrap_or(defaults.speed);
+        let acceleration = settings.acceleration.unwrap_or(defaults.acceleration);
+        let deceleration = settings.deceleration.unwrap_or(defaults.deceleration);
+        let tuned_walk = Walk::from_speeds(
+            speed,
+            settings
+                .time_to_max_speed
+                .unwrap_or(speed / acceleration.max(f32::EPSILON)),
+            settings
+                .stop_time
+                .unwrap_or(speed / deceleration.max(f32::EPSILON)),
+        );
+        walk.speed = tuned_walk.speed;
+        walk.walk_speed = settings.walk_speed.unwrap_or(tuned_walk.walk_speed);
+        walk.acceleration = tuned_walk.acceleration;
+        walk.deceleration = tuned_walk.deceleration;
+        let sprinting_defaults = Sprinting::default();
+        sprinting.multiplier = settings
+            .sprint_multiplier
+            .unwrap_or(sprinting_defaults.multiplier);
+        sprinting.acceleration = settings
+            .sprint_acceleration
+            .unwrap_or(sprinting_defaults.acceleration);
+        let height = settings.jump_height.unwrap_or(Jump::default().height);
+        let tuned_jump = match settings.time_to_apex {
+            Some(time_to_apex) => Jump::from_height_and_time(height, time_to_apex),
+            None => Jump {
+                height,
+                gravity: settings.gravity,
+                ..default()
+            },
+        };
+        jump.height = tuned_jump.height;
+        jump.gravity = tuned_jump.gravity;
+        if let (Some(linear_damping), Some(mut damping)) = (settings.linear_damping, damping) {
+            damping.0 = linear_damping;
+        }
+        if let (Some(new_mass), Some(mut mass), Some(mut inverse_mass)) =
+            (settings.mass, mass, inverse_mass)
+        {
+            mass.0 = new_mass;
+            inverse_mass.0 = 1. / new_mass.max(f32::EPSILON);
+        }
+        if let Some(mut stamina) = stamina {
+            let defaults = Stamina::default();
+            stamina.set_max(settings.stamina.unwrap_or(defaults.max));
+            stamina.regen_rate = settings.stamina_regen_rate.unwrap_or(defaults.regen_rate);
+            stamina.regen_delay = settings.stamina_regen_delay.unwrap_or(defaults.regen_delay);
+            stamina.sprint_drain = settings
+                .sprint_stamina_drain
+                .unwrap_or(defaults.sprint_drain);
+            stamina.climb_cost = settings.climb_stamina_cost.unwrap_or(defaults.climb_cost);
+            stamina.recovery_threshold = settings
+                .stamina_recovery_threshold
+                .unwrap_or(defaults.recovery_threshold);
+        }
+    }
+}
+
+#[cfg(feature = "dev")]
+fn select_player_profile(
+    configs: Res<Assets<MovementConfig>>,
+    mut players: Query<&mut MovementProfile, With<Player>>,
+    mut egui_contexts: EguiContexts,
+) {
+    let Some(config) = configs.iter().next().map(|(_, config)| config) else {
+        return;
+    };
+    let mut names: Vec<_> = config.profiles.keys().collect();
+    names.sort();
+    egui::Window::new("Movement Profiles")
+        .order(UiLayer::Dev.order())
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10., -10.))
+        .show(egui_contexts.ctx_mut(), |ui| {
+            for mut profile in players.iter_mut() {
+                let mut selected = profile.0.clone();
+                egui::ComboBox::from_label("Player")
+                    .selected_text(&selected)
+                    .show_ui(ui, |ui| {
+                        for name in &names {
+                            ui.selectable_value(&mut selected, name.to_string(), name.as_str());
+                        }
+                    });
+                if selected != profile.0 {
+                    profile.0 = selected;
+                }
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    #[test]
+    fn shipped_profiles_parse_and_extend_existing_profiles() {
+        let config: MovementConfig =
+            ron::from_str(include_str!("../../../assets/config/config.movement.ron")).unwrap();
+        for (name, settings) in &config.profiles {
+            if let Some(parent) = &settings.extends {
+                assert!(
+                    config.profiles.contains_key(parent),
+                    "Movement profile \"{name}\" extends unknown profile \"{parent}\""
+                );
+            }
+        }
+        for name in ["player", "player_floaty", "player_snappy"] {
+            let player = config.resolve(name).unwrap();
+            assert_eq!(player.sprint_acceleration, Some(1.5));
+            assert!(player.jump_height.is_some());
+        }
+        assert_eq!(config.resolve("player").unwrap().jump_height, Some(1.0));
+    }
+
+    #[test]
+    fn profile_times_are_turned_into_rates() {
+        let mut app = TestApp::new();
+        let config = MovementConfig {
+            profiles: HashMap::from_iter([(
+                "timed".to_string(),
+                MovementProfileSettings {
+                    speed: Some(6.),
+                    time_to_max_speed: Some(0.5),
+                    stop_time: Some(0.25),
+                    jump_height: Some(2.),
+                    time_to_apex: Some(0.5),
+                    ..default()
+                },
+            )]),
+        };
+        app.world_mut()
+            .resource_mut::<Assets<MovementConfig>>()
+            .add(config);
+        let character = app
+            .world_mut()
+            .spawn((
+                MovementProfile::new("timed"),
+                Walk::default(),
+                Sprinting::default(),
+                Jump::default(),
+            ))
+            .id();
+        app.step(1);
+
+        let walk = app.world().get::<Walk>(character).unwrap();
+        assert_eq!(walk.speed, 6.);
+        assert_eq!(walk.acceleration, 12.);
+        assert_eq!(walk.deceleration, 24.);
+        let jump = app.world().get::<Jump>(character).unwrap();
+        assert_eq!(jump.height, 2.);
+        assert_eq!(jump.gravity, Some(16.));
+    }
+}
@@ -0,0 +1,137 @@
+use crate::{
+    movement::character_controller::GeneralMovementSystemSet,
+    util::math_trait_ext::{F32Ext, Vec3Ext},
+    world_interaction::dialog::CurrentDialogTarget,
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_tnua::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Overlapping characters are pushed apart fast enough to separate within this many seconds.
+const SEPARATION_TIME: f32 = 0.25;
+/// Caps the push a character gets from all its neighbors together, so that a crowd pressed together does not
+/// fling the characters at its edge away.
+const MAX_PUSH_SPEED: f32 = 3.;
+
+/// Pushes characters whose capsules overlap apart horizontally, so that they do not end up standing inside each other.
+/// Of each pair, the character with more mass times [`PushPriority`] yields less. The current dialog partner does not yield at all.
+///
+/// The push is part of the velocity Tnua aims for, like a water current, since Tnua would cancel it out as a force.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<PushPriority>()
+        .register_type::<SeparationPush>()
+        .add_systems(
+            Update,
+            separate_characters
+                .before(GeneralMovementSystemSet)
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Scales how little a character yields when overlapping another one. Characters without it have a priority of 1.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+pub(crate) struct PushPriority(pub(crate) f32);
+
+impl Default for PushPriority {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+/// The horizontal velocity with which a character currently moves out of the characters it overlaps.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Default)]
+#[reflect(Component)]
+pub(crate) struct SeparationPush(pub(crate) Vec3);
+
+struct Separating {
+    position: Vec3,
+    radius: f32,
+    half_height: f32,
+    /// Infinite for characters that never yield.
+    weight: f32,
+}
+
+fn separate_characters(
+    dialog_target: Res<CurrentDialogTarget>,
+    mut characters: Query<
+        (
+            Entity,
+            &Position,
+            &Collider,
+            &Mass,
+            Option<&PushPriority>,
+            &mut SeparationPush,
+        ),
+        With<TnuaController>,
+    >,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("separate_characters").entered();
+    let separating: Vec<_> = characters
+        .iter()
+        .map(|(entity, position, collider, mass, priority, _)| {
+            let half_extents = collider.shape_scaled().compute_local_aabb().half_extents();
+            let weight = if dialog_target.0 == Some(entity) {
+                f32::INFINITY
+            } else {
+                mass.0 * priority.copied().unwrap_or_default().0
+            };
+            Separating {
+                position: position.0,
+                radius: half_extents.x,
+                half_height: half_extents.y,
+                weight,
+            }
+        })
+        .collect();
+
+    let mut pushes = vec![Vec3::ZERO; separating.len()];
+    for (i, a) in separating.iter().enumerate() {
+        for (j, b) in separating.iter().enumerate().skip(i + 1) {
+            if (a.position.y - b.position.y).abs() >= a.half_height + b.half_height {
+                continue;
+            }
+            let offset = (a.position - b.position).horizontal();
+            let min_distance = a.radius + b.radius;
+            if offset.length_squared() >= min_distance.squared() {
+                continue;
+            }
+            let distance = offset.length();
+            // Characters exactly on top of each other still need to go somewhere
+            let direction = if distance > 1e-4 {
+                offset / distance
+            } else {
+                Vec3::X
+            };
+            let speed = (min_distance - distance) / SEPARATION_TIME;
+            pushes[i] += direction * speed * yield_share(a.weight, b.weight);
+            pushes[j] -= direction * speed * yield_share(b.weight, a.weight);
+        }
+    }
+
+    for ((.., mut push), new_push) in characters.iter_mut().zip(pushes) {
+        let new_push = new_push.clamp_length_max(MAX_PUSH_SPEED);
+        if push.0 != new_push {
+            push.0 = new_push;
+        }
+    }
+}
+
+/// How much of the separation of a pair a character with `weight` does.
+fn yield_share(weight: f32, other_weight: f32) -> f32 {
+    if weight.is_infinite() {
+        return 0.;
+    }
+    if other_weight.is_infinite() {
+        return 1.;
+    }
+    let total = weight + other_weight;
+    if total > 0. {
+        other_weight / total
+    } else {
+        0.5
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{
+    movement::character_controller::{ActiveMovementModifiers, Sprinting, Walk},
+    util::math_trait_ext::Vec3Ext,
+};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How long walking is slowed down after running out of stamina, in seconds.
+const OUT_OF_BREATH_DURATION: f32 = 1.5;
+/// Multiplies the walking and running speed while out of breath.
+const OUT_OF_BREATH_SPEED: f32 = 0.6;
+
+/// Lets sprinting and climbing up ledges wear characters out, see [`Stamina`].
+/// Sprinting drains it every second and climbing costs a fixed chunk. Once it runs empty, the character is out of breath for a moment,
+/// walking slower, and cannot sprint until it has recovered a part of it. Movement modifiers can scale the costs and the regeneration.
+/// Stamina is spent in [`update_stamina`], which runs with the general movement systems.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Stamina>();
+}
+
+/// Limits how long a character can sprint and how often it climbs. Players and NPCs use the same component,
+/// so that AI can check it, e.g. an enemy that tires and slows down in a long chase.
+/// The tuning comes from the character's [`MovementProfile`](super::MovementProfile).
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct Stamina {
+    pub(crate) current: f32,
+    pub(crate) max: f32,
+    /// How much is restored per second.
+    pub(crate) regen_rate: f32,
+    /// How long after spending stamina it starts to be restored, in seconds.
+    pub(crate) regen_delay: f32,
+    /// How much sprinting costs per second.
+    pub(crate) sprint_drain: f32,
+    /// How much climbing up a ledge costs. Characters with less than this keep hanging.
+    pub(crate) climb_cost: f32,
+    /// After running empty, the character cannot sprint until this fraction of [`Stamina::max`] is restored.
+    pub(crate) recovery_threshold: f32,
+    #[serde(skip)]
+    exhausted: bool,
+    /// Seconds since stamina was last spent.
+    #[serde(skip)]
+    since_spent: f32,
+    /// Seconds that walking is still slowed down for after running empty.
+    #[serde(skip)]
+    out_of_breath: f32,
+}
+
+impl Default for Stamina {
+    fn default() -> Self {
+        Self {
+            current: 100.,
+            max: 100.,
+            regen_rate: 20.,
+            regen_delay: 1.,
+            sprint_drain: 15.,
+            climb_cost: 25.,
+            recovery_threshold: 0.3,
+            exhausted: false,
+            since_spent: 0.,
+            out_of_breath: 0.,
+        }
+    }
+}
+
+impl Stamina {
+    /// How full the stamina is, between 0 and 1.
+    pub(crate) fn fraction(&self) -> f32 {
+        if self.max <= 0. {
+            return 1.;
+        }
+        (self.current / self.max).clamp(0., 1.)
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.current >= self.max
+    }
+
+    /// Whether the character ran empty and has not recovered up to the [`Stamina::recovery_threshold`] since.
+    pub(crate) fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    /// Whether the character just ran empty and walks slower for a moment.
+    pub(crate) fn is_out_of_breath(&self) -> bool {
+        self.out_of_breath > 0.
+    }
+
+    pub(crate) fn can_sprint(&self) -> bool {
+        !self.exhausted && self.current > 0.
+    }
+
+    /// Multiplies the character's speed.
+    pub(crate) fn speed_factor(&self) -> f32 {
+        if self.is_out_of_breath() {
+            OUT_OF_BREATH_SPEED
+        } else {
+            1.
+        }
+    }
+
+    /// Spends a fixed chunk of stamina, e.g. for climbing, if that much is left. Returns whether it was spent.
+    pub(crate) fn try_spend(&mut self, amount: f32) -> bool {
+        if self.current < amount {
+            return false;
+        }
+        self.spend(amount);
+        true
+    }
+
+    fn spend(&mut self, amount: f32) {
+        if amount <= 0. {
+            return;
+        }
+        self.current = (self.current - amount).max(0.);
+        self.since_spent = 0.;
+        if self.current <= 0. && !self.exhausted {
+            self.exhausted = true;
+            self.out_of_breath = OUT_OF_BREATH_DURATION;
+        }
+    }
+
+    /// Clamps the current stamina after the tuning changed.
+    pub(super) fn set_max(&mut self, max: f32) {
+        self.max = max.max(0.);
+        self.current = self.current.min(self.max);
+    }
+}
+
+/// Drains the stamina of sprinting characters and restores it for the others.
+/// Runs before [`apply_walking`](super::apply_walking), which stops exhausted characters from sprinting.
+pub(super) fn update_stamina(
+    time: Res<Time>,
+    mut characters: Query<(
+        &mut Stamina,
+        &Walk,
+        Option<&Sprinting>,
+        Option<&ActiveMovementModifiers>,
+    )>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_stamina").entered();
+    let dt = time.delta_seconds();
+    for (mut stamina, walk, sprinting, modifiers) in &mut characters {
+        let modifiers = modifiers.map(|modifiers| modifiers.0).unwrap_or_default();
+        stamina.out_of_breath = (stamina.out_of_breath - dt).max(0.);
+        let is_moving = walk
+            .direction
+            .is_some_and(|direction| !direction.is_approx_zero());
+        let is_sprinting = is_moving
+            && sprinting.is_some_and(|sprinting| sprinting.requested)
+            && !modifiers.disable_sprint
+            && stamina.can_sprint();
+        if is_sprinting {
+            let drain = stamina.sprint_drain * modifiers.stamina_drain * dt;
+            stamina.spend(drain);
+            continue;
+        }
+        stamina.since_spent += dt;
+        if stamina.since_spent >= stamina.regen_delay {
+            let regen = stamina.regen_rate * modifiers.stamina_regen * dt;
+            stamina.current = (stamina.current + regen).min(stamina.max);
+        }
+        if stamina.exhausted && stamina.current >= stamina.recovery_threshold * stamina.max {
+            stamina.exhausted = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{InputScript, TestApp};
+
+    #[test]
+    fn running_empty_stops_sprinting_until_partly_recovered() {
+        let mut app = TestApp::new();
+        app.spawn_ground();
+        let player = app.spawn_player(Vec3::new(0., 0.5, 0.));
+        app.world_mut().entity_mut(player).insert(Stamina {
+            current: 10.,
+            max: 10.,
+            sprint_drain: 5.,
+            regen_rate: 5.,
+            regen_delay: 0.5,
+            recovery_threshold: 0.5,
+            ..default()
+        });
+        let stamina = |app: &TestApp| app.world().get::<Stamina>(player).unwrap().clone();
+
+        // Two seconds of sprinting empty it, the rest is spent out of breath
+        app.script(InputScript::new().sprint(Vec2::Y, 160));
+        app.run_script();
+        let exhausted = stamina(&app);
+        assert!(exhausted.is_exhausted());
+        assert!(!exhausted.can_sprint());
+        // Regeneration started half a second after running empty, even with the button still held
+        assert!(exhausted.current > 0. && exhausted.current < 5.);
+
+        app.script(InputScript::new().idle(60));
+        app.run_script();
+        let recovered = stamina(&app);
+        assert!(!recovered.is_exhausted());
+        assert!(recovered.can_sprint());
+    }
+}
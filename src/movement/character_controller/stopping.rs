@@ -0,0 +1,174 @@
+use crate::{
+    movement::character_controller::{animation::CharacterAnimationNames, GroundedState, Walk},
+    util::math_trait_ext::Vec3Ext,
+};
+use bevy::prelude::*;
+use bevy_gltf_blueprints::Animations;
+use bevy_xpbd_3d::prelude::*;
+
+/// Characters need to move at least this fast, in m/s, for [`MIN_RUN_DURATION`] seconds before they skid to a stop.
+const MIN_RUN_SPEED: f32 = 5.;
+const MIN_RUN_DURATION: f32 = 0.5;
+/// How far a character keeps moving after it stopped walking, in meters.
+const STOP_DISTANCE: f32 = 0.5;
+
+/// How long a character has been running, tracked by [`update_stops`].
+#[derive(Debug, Clone, Copy, PartialEq, Component, Default)]
+struct RunTracking {
+    /// In seconds.
+    running_for: f32,
+}
+
+/// On characters that stop after running, managed by [`update_stops`].
+/// Instead of Tnua decelerating them, their speed follows a curve that brings them to a halt after [`STOP_DISTANCE`],
+/// so that the feet of the stop animation do not slide.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub(super) struct Stopping {
+    direction: Vec3,
+    initial_speed: f32,
+    /// In seconds.
+    elapsed: f32,
+    /// In seconds.
+    duration: f32,
+    /// The exponent of the deceleration curve.
+    falloff: f32,
+    /// Whether the character stops with the left foot in front.
+    pub(super) left_foot: bool,
+    /// Whether the character has an animation for this stop.
+    pub(super) animated: bool,
+}
+
+impl Stopping {
+    fn new(velocity: Vec3, duration: f32, left_foot: bool, animated: bool) -> Self {
+        let initial_speed = velocity.length();
+        Self {
+            direction: velocity / initial_speed,
+            initial_speed,
+            elapsed: 0.,
+            duration,
+            // Covering `initial_speed * (1 - t / duration)^falloff` over the duration travels
+            // `initial_speed * duration / (falloff + 1)` meters. Below an exponent of 1, the character would
+            // only stop abruptly at the end, so short clips stop a bit further instead.
+            falloff: (initial_speed * duration / STOP_DISTANCE - 1.).max(1.),
+            left_foot,
+            animated,
+        }
+    }
+
+    /// The velocity the character should have at this point of the stop.
+    pub(super) fn velocity(&self) -> Vec3 {
+        let progress = (self.elapsed / self.duration).min(1.);
+        self.direction * self.initial_speed * (1. - progress).powf(self.falloff)
+    }
+
+    /// Enough acceleration for Tnua to follow the curve of [`Stopping::velocity`], which is steepest at the start.
+    pub(super) fn deceleration(&self) -> f32 {
+        2. * self.initial_speed * self.falloff / self.duration
+    }
+}
+
+/// Starts a [`Stopping`] when a character that ran for a while stops walking and ends it once the character stands.
+/// Walking again interrupts the stop, and Tnua takes over from the velocity the character has at that point.
+pub(super) fn update_stops(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut characters: Query<(
+        Entity,
+        &Walk,
+        &LinearVelocity,
+        &GroundedState,
+        Option<&mut RunTracking>,
+        Option<&mut Stopping>,
+        Option<&Animations>,
+    )>,
+    children: Query<&Children>,
+    animation_names: Query<&CharacterAnimationNames>,
+    clips: Res<Assets<AnimationClip>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_stops").entered();
+    let dt = time.delta_seconds();
+    for (entity, walk, velocity, grounded, tracking, stopping, animations) in characters.iter_mut()
+    {
+        let Some(mut tracking) = tracking else {
+            commands.entity(entity).insert(RunTracking::default());
+            continue;
+        };
+        let is_walking = walk
+            .direction
+            .is_some_and(|direction| !direction.is_approx_zero());
+        if let Some(mut stopping) = stopping {
+            stopping.elapsed += dt;
+            if is_walking || grounded.airborne || stopping.elapsed >= stopping.duration {
+                commands.entity(entity).remove::<Stopping>();
+            }
+            continue;
+        }
+        let velocity = velocity.0.horizontal();
+        let speed = velocity.length();
+        if grounded.airborne {
+            tracking.running_for = 0.;
+            continue;
+        }
+        if is_walking {
+            if speed >= MIN_RUN_SPEED {
+                tracking.running_for += dt;
+            } else {
+                tracking.running_for = 0.;
+            }
+            continue;
+        }
+        let has_run = tracking.running_for >= MIN_RUN_DURATION;
+        tracking.running_for = 0.;
+        if !has_run || speed < MIN_RUN_SPEED {
+            continue;
+        }
+
+        let left_foot = grounded.is_left_foot_forward(speed);
+        let clip_duration = children
+            .iter_descendants(entity)
+            .find_map(|child| animation_names.get(child).ok())
+            .and_then(|names| {
+                if left_foot {
+                    names.stop_left.as_ref()
+                } else {
+                    names.stop_right.as_ref()
+                }
+            })
+            .and_then(|name| animations?.named_animations.get(name))
+            .and_then(|clip| clips.get(clip))
+            .map(AnimationClip::duration)
+            .filter(|duration| *duration > 0.);
+        // Without an animation, the speed eases out quadratically
+        let duration = clip_duration.unwrap_or(3. * STOP_DISTANCE / speed);
+        commands.entity(entity).insert(Stopping::new(
+            velocity,
+            duration,
+            left_foot,
+            clip_duration.is_some(),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stop_covers_stop_distance() {
+        for (speed, duration) in [(8., 3. * STOP_DISTANCE / 8.), (12., 0.6)] {
+            let mut stopping = Stopping::new(Vec3::new(speed, 0., 0.), duration, true, false);
+            let dt = 1e-4;
+            let mut distance = 0.;
+            while stopping.elapsed < stopping.duration {
+                distance += stopping.velocity().x * dt;
+                stopping.elapsed += dt;
+            }
+            assert!(
+                (distance - STOP_DISTANCE).abs() < 0.01,
+                "Stopping from {speed} m/s in {duration} s went {distance} m"
+            );
+            assert_eq!(stopping.velocity(), Vec3::ZERO);
+        }
+    }
+}
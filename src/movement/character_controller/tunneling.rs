@@ -0,0 +1,134 @@
+use crate::{
+    movement::{character_controller::GeneralMovementSystemSet, physics::CollisionLayer},
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_tnua::{prelude::*, TnuaPipelineStages};
+use bevy_xpbd_3d::prelude::*;
+
+/// Moving further than this fraction of the collider's radius in one frame risks skipping through thin geometry.
+const MAX_SAFE_STEP: f32 = 0.5;
+/// Characters stopped in front of a hit keep this much distance to it, so that the solver sees a regular contact next frame.
+const SKIN_WIDTH: f32 = 0.01;
+
+/// Keeps fast characters from tunneling through thin colliders, e.g. a floor at the end of a long fall.
+/// When a character would move further than [`MAX_SAFE_STEP`] times its radius this frame, its collider is cast along
+/// the movement first. If that hits terrain, the velocity into the hit surface is clamped so that the character ends up
+/// right in front of it, where the regular contacts take over. Physics steps in [`PostUpdate`], after all of this.
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        prevent_tunneling
+            .after(GeneralMovementSystemSet)
+            .after(TnuaPipelineStages::Motors)
+            .run_if(in_state(GameState::Playing)),
+    );
+}
+
+fn prevent_tunneling(
+    time: Res<Time>,
+    mut characters: Query<
+        (
+            Entity,
+            &RigidBody,
+            &Position,
+            &Collider,
+            &mut LinearVelocity,
+        ),
+        With<TnuaController>,
+    >,
+    spatial_query: SpatialQuery,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("prevent_tunneling").entered();
+    let delta = time.delta_seconds();
+    if delta <= 0. {
+        return;
+    }
+    for (entity, rigid_body, position, collider, mut velocity) in characters.iter_mut() {
+        if !rigid_body.is_dynamic() {
+            continue;
+        }
+        let step = velocity.0 * delta;
+        let half_extents = collider.shape_scaled().compute_local_aabb().half_extents();
+        let radius = half_extents.x.min(half_extents.z);
+        let distance = step.length();
+        if distance <= radius * MAX_SAFE_STEP {
+            continue;
+        }
+        let Ok(direction) = Direction3d::new(step) else {
+            continue;
+        };
+        let filter = SpatialQueryFilter::from_mask(CollisionLayer::Terrain.to_bits())
+            .with_excluded_entities([entity]);
+        // Characters only rotate around the Y axis, which does not change the shape of a capsule
+        let Some(hit) = spatial_query.cast_shape(
+            collider,
+            position.0,
+            Quat::IDENTITY,
+            direction,
+            distance,
+            true,
+            filter,
+        ) else {
+            continue;
+        };
+        // Only the movement into the surface is slowed down, so that e.g. sprinting along a wall keeps its speed
+        let normal = hit.normal1.normalize_or_zero();
+        let speed_into_surface = -velocity.0.dot(normal);
+        if speed_into_surface > 0. {
+            let reached_fraction = ((hit.time_of_impact - SKIN_WIDTH) / distance).max(0.);
+            velocity.0 += normal * speed_into_surface * (1. - reached_fraction);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{level_instantiation::on_spawn::player, testing::TestApp};
+    use bevy::{time::TimeUpdateStrategy, utils::Duration};
+
+    const FLOOR_THICKNESS: f32 = 0.05;
+    const DROP_HEIGHT: f32 = 500.;
+
+    #[test]
+    fn long_falls_land_on_thin_floors_at_any_frame_rate() {
+        let standing_height = player::HEIGHT / 2. + player::RADIUS;
+        for frame_rate in [30., 60., 144., 240.] {
+            let mut app = TestApp::new();
+            app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                1. / frame_rate,
+            )));
+            app.spawn_block(
+                Vec3::new(0., -FLOOR_THICKNESS / 2., 0.),
+                Vec3::new(100., FLOOR_THICKNESS, 100.),
+            );
+            let player = app.spawn_player(Vec3::new(0., DROP_HEIGHT, 0.));
+
+            let mut lowest = f32::INFINITY;
+            let mut fastest = 0_f32;
+            // Physics steps at most 1/60 s per frame, so the fall takes about ten seconds of physics at every frame rate
+            for _ in 0..(15. * frame_rate.max(60.)) as usize {
+                app.step(1);
+                lowest = lowest.min(app.translation(player).y);
+                let velocity = app.world().get::<LinearVelocity>(player).unwrap().0;
+                fastest = fastest.max(-velocity.y);
+            }
+
+            assert!(
+                fastest > 80.,
+                "Only fell at {fastest} m/s at {frame_rate} FPS"
+            );
+            assert!(
+                lowest > standing_height - FLOOR_THICKNESS,
+                "Sank to {lowest} at {frame_rate} FPS"
+            );
+            let height = app.translation(player).y;
+            assert!(
+                (height - standing_height).abs() < 0.1,
+                "Ended up at the height {height} instead of standing at {frame_rate} FPS"
+            );
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{
+    movement::character_controller::{
+        animation::CharacterAnimationNames, stopping::Stopping, Walk,
+    },
+    util::math_trait_ext::Vec3Ext,
+};
+use bevy::prelude::*;
+use bevy_gltf_blueprints::Animations;
+use bevy_tnua::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// A turn is finished once the character faces its target up to this angle, in radians.
+const FINISHED_ANGLE: f32 = 0.05;
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<RotationMode>();
+}
+
+/// How a character turns towards [`Walk::facing`] while standing still.
+/// While walking, the character always faces the direction it walks in.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct RotationMode {
+    /// A standing character only turns in place once the facing it should have is this far off, in radians.
+    /// Smaller differences are ignored so that the character does not fidget while the camera moves a bit.
+    pub(crate) turn_threshold: f32,
+    /// How quickly the character turns in place when it has no turning animation, in radians per second.
+    /// With an animation, the turn takes as long as the animation instead.
+    pub(crate) turn_speed: f32,
+    /// How long the turning animations blend in and out, in seconds.
+    pub(crate) blend_time: f32,
+}
+
+impl Default for RotationMode {
+    fn default() -> Self {
+        Self {
+            turn_threshold: PI / 3.,
+            turn_speed: 2. * PI,
+            blend_time: 0.15,
+        }
+    }
+}
+
+/// On characters that are currently turning in place, managed by [`update_turning_in_place`].
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub(super) struct TurningInPlace {
+    pub(super) target: Vec3,
+    /// In radians per second.
+    pub(super) angular_speed: f32,
+    /// Whether the character turns counterclockwise as seen from above.
+    pub(super) left: bool,
+    /// Whether the character has an animation for this turn.
+    pub(super) animated: bool,
+}
+
+/// Starts turning standing characters whose [`Walk::facing`] is further off than their [`RotationMode::turn_threshold`].
+/// Walking, stopping or leaving the ground cancels the turn.
+pub(super) fn update_turning_in_place(
+    mut commands: Commands,
+    mut characters: Query<(
+        Entity,
+        &Walk,
+        &RotationMode,
+        &Transform,
+        &TnuaController,
+        Option<&mut TurningInPlace>,
+        Option<&Animations>,
+        Has<Stopping>,
+    )>,
+    children: Query<&Children>,
+    animation_names: Query<&CharacterAnimationNames>,
+    clips: Res<Assets<AnimationClip>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_turning_in_place").entered();
+    for (entity, walk, rotation_mode, transform, controller, turning, animations, is_stopping) in
+        characters.iter_mut()
+    {
+        // A character still skidding to a stop only turns once it stands
+        let is_walking = is_stopping
+            || walk
+                .direction
+                .is_some_and(|direction| !direction.is_approx_zero());
+        let is_airborne = controller.is_airborne().unwrap_or(true);
+        let forward = transform.forward().horizontal().normalize_or_zero();
+        let target = walk
+            .facing
+            .map(|facing| facing.horizontal().normalize_or_zero())
+            .filter(|facing| !facing.is_approx_zero());
+
+        if let Some(mut turning) = turning {
+            if let Some(target) = target {
+                turning.target = target;
+            }
+            let is_finished = yaw_between(forward, turning.target).abs() < FINISHED_ANGLE;
+            if is_walking || is_airborne || is_finished {
+                commands.entity(entity).remove::<TurningInPlace>();
+            }
+            continue;
+        }
+        let Some(target) = target else {
+            continue;
+        };
+        let angle = yaw_between(forward, target);
+        if is_walking || is_airborne || angle.abs() < rotation_mode.turn_threshold {
+            continue;
+        }
+        let left = angle > 0.;
+        let clip_duration = children
+            .iter_descendants(entity)
+            .find_map(|child| animation_names.get(child).ok())
+            .and_then(|names| {
+                if left {
+                    names.turn_left.as_ref()
+                } else {
+                    names.turn_right.as_ref()
+                }
+            })
+            .and_then(|name| animations?.named_animations.get(name))
+            .and_then(|clip| clips.get(clip))
+            .map(AnimationClip::duration)
+            .filter(|duration| *duration > 0.);
+        let angular_speed =
+            clip_duration.map_or(rotation_mode.turn_speed, |duration| angle.abs() / duration);
+        commands.entity(entity).insert(TurningInPlace {
+            target,
+            angular_speed,
+            left,
+            animated: clip_duration.is_some(),
+        });
+    }
+}
+
+/// The angle around the Y axis by which `from` needs to be rotated to point in the direction of `to`, in radians.
+/// Positive angles turn counterclockwise as seen from above, i.e. to the left.
+fn yaw_between(from: Vec3, to: Vec3) -> f32 {
+    from.cross(to).y.atan2(from.dot(to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yaw_is_signed_towards_the_left() {
+        let forward = Vec3::NEG_Z;
+        assert!((yaw_between(forward, Vec3::NEG_X) - PI / 2.).abs() < 1e-5);
+        assert!((yaw_between(forward, Vec3::X) + PI / 2.).abs() < 1e-5);
+        assert!(yaw_between(forward, forward).abs() < 1e-5);
+        assert!((yaw_between(forward, Vec3::Z).abs() - PI).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,288 @@
+use crate::{
+    level_instantiation::on_spawn::Player,
+    movement::{
+        character_controller::{GeneralMovementSystemSet, Jump, LedgeHang},
+        physics::CollisionLayer,
+    },
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_tnua::{prelude::*, TnuaProximitySensor, TnuaToggle};
+use bevy_xpbd_3d::{prelude::*, PhysicsSet};
+use serde::{Deserialize, Serialize};
+
+/// Bodies rising faster than this are not launched, so that a pad does not fire again while launching something.
+const MAX_LANDING_SPEED: f32 = 0.5;
+/// For this many seconds after a launch, Tnua does not control the character,
+/// so that its floating spring does not eat the launch while it is still close to the ground.
+const LAUNCH_DURATION: f32 = 0.2;
+/// In seconds.
+const SQUASH_DURATION: f32 = 0.3;
+/// How much a pad is squashed at the height of its animation, as a fraction of its height.
+const SQUASH_AMOUNT: f32 = 0.35;
+
+/// Handles [`JumpPad`]s. Characters that land on a pad or drop into its trigger are launched,
+/// regardless of how far they fell, and the pad squashes.
+/// While a character is being launched, its jump requests are ignored, so that a jump buffered
+/// before landing on a pad does not fire on top of the launch, and landing on the next pad launches it again.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<JumpPad>().add_systems(
+        Update,
+        (
+            init_jump_pads,
+            launch_from_pads,
+            update_launched,
+            squash_pads,
+        )
+            .chain()
+            .before(GeneralMovementSystemSet)
+            .before(PhysicsSet::Prepare)
+            .run_if(in_state(GameState::Playing)),
+    );
+}
+
+/// Launches what lands on the entity. A trigger box is spawned around the entity's origin,
+/// so the pad's mesh can be a child with a regular collider.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct JumpPad {
+    /// The direction of the launch in the pad's local space.
+    pub(crate) direction: Vec3,
+    /// The speed of the launch, in m/s.
+    pub(crate) strength: f32,
+    /// Adds the launch to what the body falls with instead of stopping its fall first,
+    /// so that falling from higher up launches less high.
+    pub(crate) additive: bool,
+    /// The player is always launched, NPCs only with this.
+    pub(crate) affects_npcs: bool,
+    /// Dynamic rigid bodies that are not characters, e.g. crates.
+    pub(crate) affects_props: bool,
+    pub(crate) half_extents: Vec3,
+}
+
+impl Default for JumpPad {
+    fn default() -> Self {
+        Self {
+            direction: Vec3::Y,
+            strength: 15.,
+            additive: false,
+            affects_npcs: false,
+            affects_props: false,
+            half_extents: Vec3::new(1., 0.5, 1.),
+        }
+    }
+}
+
+impl JumpPad {
+    /// The velocity of something that had `velocity` when it was launched by this pad.
+    fn launch(&self, rotation: Quat, velocity: Vec3) -> Vec3 {
+        let launch = rotation * self.direction.normalize_or_zero() * self.strength;
+        let base = if self.additive {
+            velocity
+        } else {
+            Vec3::new(velocity.x, velocity.y.max(0.), velocity.z)
+        };
+        base + launch
+    }
+}
+
+/// On characters and props that were just launched.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct Launched {
+    pad: Entity,
+    /// In seconds.
+    remaining: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct PadSquash {
+    base_scale: Vec3,
+    elapsed: f32,
+}
+
+fn init_jump_pads(mut commands: Commands, pads: Query<(Entity, &JumpPad), Added<JumpPad>>) {
+    for (entity, pad) in pads.iter() {
+        let size = pad.half_extents * 2.;
+        let filters = if pad.affects_props {
+            LayerMask::ALL
+        } else {
+            LayerMask::from([CollisionLayer::Player, CollisionLayer::Character])
+        };
+        commands.entity(entity).insert((
+            Collider::cuboid(size.x, size.y, size.z),
+            CollisionLayers::new([CollisionLayer::Sensor], filters),
+            Sensor,
+            CollidingEntities::default(),
+        ));
+    }
+}
+
+fn launch_from_pads(
+    mut commands: Commands,
+    pads: Query<(Entity, &JumpPad, &GlobalTransform, &CollidingEntities)>,
+    parents: Query<&Parent>,
+    sensors: Query<(Entity, &TnuaProximitySensor)>,
+    mut bodies: Query<
+        (
+            &RigidBody,
+            &mut LinearVelocity,
+            Option<&Launched>,
+            Has<TnuaController>,
+            Has<Player>,
+            Option<&TnuaToggle>,
+        ),
+        Without<LedgeHang>,
+    >,
+    mut squashes: Query<&mut PadSquash>,
+    transforms: Query<&Transform>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("launch_from_pads").entered();
+    // Characters standing on the pad's own collider may be outside of its trigger
+    let standing_on = |pad: Entity| {
+        sensors.iter().filter_map(move |(character, sensor)| {
+            let ground = sensor.output.as_ref()?.entity;
+            std::iter::once(ground)
+                .chain(parents.iter_ancestors(ground))
+                .any(|entity| entity == pad)
+                .then_some(character)
+        })
+    };
+    for (pad_entity, pad, transform, colliding) in pads.iter() {
+        let (_, rotation, _) = transform.to_scale_rotation_translation();
+        let mut launched_any = false;
+        for body in colliding.iter().copied().chain(standing_on(pad_entity)) {
+            let Ok((rigid_body, mut velocity, launched, is_character, is_player, toggle)) =
+                bodies.get_mut(body)
+            else {
+                continue;
+            };
+            let is_affected = match (is_character, is_player) {
+                (_, true) => true,
+                (true, false) => pad.affects_npcs,
+                (false, _) => pad.affects_props && rigid_body.is_dynamic(),
+            };
+            // Sitting, hanging or noclipping characters are left alone
+            let is_controlled = toggle.map_or(true, |toggle| *toggle != TnuaToggle::Disabled);
+            let is_relaunch = launched.is_some_and(|launched| launched.pad == pad_entity);
+            if !is_affected || !is_controlled || is_relaunch || velocity.y > MAX_LANDING_SPEED {
+                continue;
+            }
+            velocity.0 = pad.launch(rotation, velocity.0);
+            let mut entity = commands.entity(body);
+            entity.insert(Launched {
+                pad: pad_entity,
+                remaining: LAUNCH_DURATION,
+            });
+            if is_character {
+                entity.insert(TnuaToggle::SenseOnly);
+            } else {
+                entity.remove::<Sleeping>();
+            }
+            launched_any = true;
+        }
+        if !launched_any {
+            continue;
+        }
+        if let Ok(mut squash) = squashes.get_mut(pad_entity) {
+            squash.elapsed = 0.;
+        } else if let Ok(pad_transform) = transforms.get(pad_entity) {
+            commands.entity(pad_entity).insert(PadSquash {
+                base_scale: pad_transform.scale,
+                elapsed: 0.,
+            });
+        }
+    }
+}
+
+fn update_launched(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut launched: Query<(
+        Entity,
+        &mut Launched,
+        Option<&mut Jump>,
+        Option<&TnuaToggle>,
+    )>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut launch, jump, toggle) in launched.iter_mut() {
+        if let Some(mut jump) = jump {
+            jump.cancel_request();
+        }
+        launch.remaining -= dt;
+        if launch.remaining > 0. {
+            continue;
+        }
+        let mut entity = commands.entity(entity);
+        entity.remove::<Launched>();
+        // Something else may have taken over the character in the meantime, e.g. a ledge grab
+        if toggle == Some(&TnuaToggle::SenseOnly) {
+            entity.insert(TnuaToggle::Enabled);
+        }
+    }
+}
+
+fn squash_pads(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut pads: Query<(Entity, &mut PadSquash, &mut Transform)>,
+) {
+    for (entity, mut squash, mut transform) in pads.iter_mut() {
+        squash.elapsed += time.delta_seconds();
+        let progress = (squash.elapsed / SQUASH_DURATION).min(1.);
+        let amount = SQUASH_AMOUNT * (progress * std::f32::consts::PI).sin();
+        transform.scale =
+            squash.base_scale * Vec3::new(1. + amount / 2., 1. - amount, 1. + amount / 2.);
+        if progress >= 1. {
+            transform.scale = squash.base_scale;
+            commands.entity(entity).remove::<PadSquash>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    #[test]
+    fn launch_does_not_depend_on_fall_speed() {
+        let pad = JumpPad::default();
+        let slow = pad.launch(Quat::IDENTITY, Vec3::new(1., -2., 0.));
+        let fast = pad.launch(Quat::IDENTITY, Vec3::new(1., -20., 0.));
+        assert_eq!(slow, fast);
+        assert_eq!(slow, Vec3::new(1., 15., 0.));
+
+        let additive = JumpPad {
+            additive: true,
+            ..default()
+        };
+        assert_eq!(
+            additive.launch(Quat::IDENTITY, Vec3::new(0., -5., 0.)),
+            Vec3::new(0., 10., 0.)
+        );
+    }
+
+    #[test]
+    fn player_landing_on_pad_is_launched() {
+        let mut app = TestApp::new();
+        app.spawn_ground();
+        app.world_mut().spawn((
+            Name::new("Jump pad"),
+            TransformBundle::from_transform(Transform::from_xyz(0., 0.25, 0.)),
+            JumpPad::default(),
+        ));
+        let player = app.spawn_player(Vec3::new(0., 3., 0.));
+        let mut highest = f32::MIN;
+        for _ in 0..90 {
+            app.step(1);
+            highest = highest.max(app.translation(player).y);
+        }
+        assert!(
+            highest > 6.,
+            "The player only got up to {highest} instead of being launched"
+        );
+    }
+}
@@ -1,27 +1,48 @@
 #[cfg(feature = "dev")]
-use crate::dev::dev_editor::DevEditorWindow;
+use crate::dev::dev_tools::{DevTools, RegisterDevToolExt};
 use crate::{
     level_instantiation::on_spawn::{player, Npc, Player},
-    movement::character_controller::{GeneralMovementSystemSet, Walk},
+    movement::{
+        character_controller::{GeneralMovementSystemSet, Sprinting, Stamina, Walk},
+        physics::CollisionLayer,
+    },
     util::math_trait_ext::{F32Ext, Vec3Ext},
+    world_interaction::respawn_grace::GracePeriod,
     GameState,
 };
-#[cfg(feature = "dev")]
-use anyhow::Context;
 use bevy::prelude::*;
 use bevy_mod_sysfail::prelude::*;
-use bevy_xpbd_3d::prelude::Collider;
+use bevy_xpbd_3d::prelude::*;
 #[cfg(feature = "dev")]
-use oxidized_navigation::debug_draw::{DrawNavMesh, DrawPath, OxidizedNavigationDebugDrawPlugin};
+use oxidized_navigation::debug_draw::{DrawNavMesh, OxidizedNavigationDebugDrawPlugin};
 use oxidized_navigation::{
     query::{find_polygon_path, perform_string_pulling_on_path},
     NavMesh, NavMeshSettings, OxidizedNavigationPlugin,
 };
+use serde::{Deserialize, Serialize};
+
+mod companion;
+mod ledge;
+mod wander;
+
+pub(crate) use companion::{Companion, CompanionState};
+pub(crate) use ledge::LedgeCaution;
+pub(crate) use wander::WanderState;
 
 /// Manually tweaked
 const CELL_WIDTH: f32 = 0.4 * player::RADIUS;
+/// How far the destination must move before a new path is requested.
+const REPATH_DISTANCE: f32 = 1.0;
+/// How close an agent needs to get to a corner before it walks to the next one.
+const CORNER_REACHED_DISTANCE: f32 = 0.3;
+/// Agents stop walking when they are this close to their destination.
+const STOPPING_DISTANCE: f32 = 3.0;
 
-/// Handles NPC pathfinding. Currently, all entities with the [`Npc`] component will follow the [`Player`].
+/// Handles NPC pathfinding. By default, all entities with the [`Npc`] component will follow the [`Player`].
+/// This can be overridden per NPC with a [`NavigationDestination`], which is what [`companion::plugin`] and [`wander::plugin`] do.
+/// Right after the player arrived, NPCs only start following it once its [`GracePeriod`] stops delaying aggro.
+/// [`ledge::plugin`] keeps NPCs from walking off ledges on the way.
+/// The path an NPC follows is stored in its [`NavigationPath`].
 pub(super) fn plugin(app: &mut App) {
     // consts manually tweaked
     app.add_plugins(OxidizedNavigationPlugin::<Collider>::new(NavMeshSettings {
@@ -40,82 +61,194 @@ pub(super) fn plugin(app: &mut App) {
         max_edge_length: 100,
         max_tile_generation_tasks: None,
     }))
+    .register_type::<NavigationPath>()
+    .register_type::<NavigationDestination>()
+    .add_plugins((companion::plugin, ledge::plugin, wander::plugin))
     .add_systems(
         Update,
-        query_mesh
+        (update_navigation_paths, follow_navigation_paths)
+            .chain()
             .before(GeneralMovementSystemSet)
             .run_if(in_state(GameState::Playing)),
     );
     #[cfg(feature = "dev")]
     app.add_plugins(OxidizedNavigationDebugDrawPlugin)
-        .add_systems(Update, draw_navmesh);
+        .register_dev_tool("Navmesh", Some(KeyCode::F4), toggle_navmesh_render)
+        .add_systems(Update, draw_navigation_paths);
+}
+
+/// The path an agent is walking along.
+/// Created and updated by [`update_navigation_paths`], consumed by [`follow_navigation_paths`].
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Default)]
+#[reflect(Component)]
+pub(crate) struct NavigationPath {
+    /// The corners of the path that are still ahead of the agent. The first one is the next one to walk to.
+    pub(crate) corners: Vec<Vec3>,
+    /// The position the agent wanted to reach when the path was computed.
+    pub(crate) destination: Vec3,
+}
+
+/// Overrides where an [`Npc`] walks to. Without this component, NPCs walk towards the [`Player`].
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct NavigationDestination {
+    pub(crate) position: Vec3,
+    /// The agent stops walking when it is this close to [`NavigationDestination::position`].
+    pub(crate) stopping_distance: f32,
+}
+
+impl NavigationPath {
+    pub(crate) fn next_corner(&self) -> Option<Vec3> {
+        self.corners.first().copied()
+    }
 }
 
 #[sysfail(Log<anyhow::Error, Error>)]
-fn query_mesh(
-    #[cfg(feature = "dev")] mut commands: Commands,
-    mut with_follower: Query<(&Transform, &mut Walk), (With<Npc>, Without<Player>)>,
-    with_player: Query<&Transform, (With<Player>, Without<Npc>)>,
+fn update_navigation_paths(
+    mut commands: Commands,
+    mut with_follower: Query<
+        (
+            Entity,
+            &Transform,
+            Option<&NavigationDestination>,
+            Option<&mut NavigationPath>,
+        ),
+        (With<Npc>, Without<Player>),
+    >,
+    with_player: Query<(&Transform, Option<&GracePeriod>), (With<Player>, Without<Npc>)>,
     nav_mesh_settings: Res<NavMeshSettings>,
     nav_mesh: Res<NavMesh>,
-    #[cfg(feature = "dev")] editor_state: Res<bevy_editor_pls::editor::Editor>,
+    spatial_query: SpatialQuery,
 ) {
     #[cfg(feature = "tracing")]
-    let _span = info_span!("query_mesh").entered();
-    if let Ok(nav_mesh) = nav_mesh.get().read() {
-        for (follower_transform, mut walking) in &mut with_follower {
-            for player_transform in &with_player {
-                let from = follower_transform.translation;
-                let to = player_transform.translation;
-                if (to - from).length_squared() < 3.0f32.squared() {
-                    continue;
-                }
-
-                if let Ok(path) =
-                    find_polygon_path(&nav_mesh, &nav_mesh_settings, from, to, None, None)
-                {
-                    let path = perform_string_pulling_on_path(&nav_mesh, from, to, &path)
-                        .map_err(|e| anyhow::Error::msg(format!("{e:?}")))?;
-                    #[cfg(feature = "dev")]
-                    {
-                        let nav_render_enabled = editor_state
-                            .window_state::<DevEditorWindow>()
-                            .context("Failed to read dev window state")?
-                            .navmesh_render_enabled;
-                        if nav_render_enabled {
-                            let shifted_path = path
-                                .iter()
-                                .map(|point| *point + Vec3::new(0., 0.2, 0.))
-                                .collect::<Vec<_>>();
-                            commands.spawn(DrawPath {
-                                timer: Some(Timer::from_seconds(4.0, TimerMode::Once)),
-                                pulled_path: shifted_path,
-                                color: Color::BLUE,
-                            });
-                        }
-                    }
-                    let dir = path
-                        .into_iter()
-                        .map(|next_point| (next_point - from).horizontal())
-                        .filter(|dir| dir.length_squared() > 1e-3f32.squared())
-                        .filter_map(|dir| dir.try_normalize())
-                        .next();
-                    walking.direction = dir;
-                }
+    let _span = info_span!("update_navigation_paths").entered();
+    let Ok(nav_mesh) = nav_mesh.get().read() else {
+        return Ok(());
+    };
+    let Some((player_transform, grace)) = with_player.iter().next() else {
+        return Ok(());
+    };
+    let delays_aggro = grace.is_some_and(GracePeriod::delays_aggro);
+    for (entity, follower_transform, destination, mut path) in &mut with_follower {
+        if destination.is_none() && delays_aggro {
+            if path.is_some() {
+                commands.entity(entity).remove::<NavigationPath>();
+            }
+            continue;
+        }
+        let from = follower_transform.translation;
+        let to = destination.map_or(player_transform.translation, |destination| {
+            destination.position
+        });
+        let needs_repath = match path.as_deref() {
+            None => true,
+            Some(path) => {
+                (path.destination - to).length_squared() > REPATH_DISTANCE.squared()
+                    || path
+                        .next_corner()
+                        .is_some_and(|corner| !has_line_of_sight(&spatial_query, from, corner))
             }
+        };
+        if !needs_repath {
+            continue;
+        }
+
+        let Ok(polygon_path) =
+            find_polygon_path(&nav_mesh, &nav_mesh_settings, from, to, None, None)
+        else {
+            continue;
+        };
+        let corners = perform_string_pulling_on_path(&nav_mesh, from, to, &polygon_path)
+            .map_err(|e| anyhow::Error::msg(format!("{e:?}")))?;
+        let new_path = NavigationPath {
+            corners,
+            destination: to,
+        };
+        if let Some(path) = path.as_mut() {
+            **path = new_path;
+        } else {
+            commands.entity(entity).insert(new_path);
+        }
+    }
+}
+
+fn follow_navigation_paths(
+    mut with_follower: Query<
+        (
+            &Transform,
+            &mut NavigationPath,
+            &mut Walk,
+            Option<&NavigationDestination>,
+            Option<(&mut Sprinting, &Stamina)>,
+        ),
+        With<Npc>,
+    >,
+    spatial_query: SpatialQuery,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("follow_navigation_paths").entered();
+    for (transform, mut path, mut walking, destination, stamina) in &mut with_follower {
+        // NPCs with stamina run after the player until they tire
+        if let Some((mut sprinting, stamina)) = stamina {
+            sprinting.requested = destination.is_none() && stamina.can_sprint();
+        }
+        let from = transform.translation;
+        let stopping_distance = destination.map_or(STOPPING_DISTANCE, |destination| {
+            destination.stopping_distance
+        });
+        if (path.destination - from).length_squared() < stopping_distance.squared() {
+            continue;
+        }
+        while path.next_corner().is_some_and(|corner| {
+            (corner - from).horizontal().length_squared() < CORNER_REACHED_DISTANCE.squared()
+        }) {
+            path.corners.remove(0);
+        }
+        // Cut corners when the one after the next is already visible
+        while path.corners.len() > 1 && has_line_of_sight(&spatial_query, from, path.corners[1]) {
+            path.corners.remove(0);
         }
+        walking.direction = path
+            .next_corner()
+            .map(|corner| (corner - from).horizontal())
+            .filter(|dir| dir.length_squared() > 1e-3f32.squared())
+            .and_then(|dir| dir.try_normalize());
     }
 }
 
+pub(crate) fn has_line_of_sight(spatial_query: &SpatialQuery, from: Vec3, to: Vec3) -> bool {
+    // Raise the ray a bit so that it does not graze the floor
+    let from = from + Vec3::Y * player::HEIGHT / 2.;
+    let to = to + Vec3::Y * player::HEIGHT / 2.;
+    let Ok(direction) = Direction3d::new(to - from) else {
+        return true;
+    };
+    let filter = SpatialQueryFilter::from_mask(CollisionLayer::Terrain.to_bits());
+    spatial_query
+        .cast_ray(from, direction, from.distance(to), true, filter)
+        .is_none()
+}
+
 #[cfg(feature = "dev")]
-#[sysfail(Log<anyhow::Error, Error>)]
-fn draw_navmesh(
-    editor: Res<bevy_editor_pls::editor::Editor>,
-    mut draw_nav_mesh: ResMut<DrawNavMesh>,
+fn toggle_navmesh_render(In(active): In<bool>, mut draw_nav_mesh: ResMut<DrawNavMesh>) {
+    draw_nav_mesh.0 = active;
+}
+
+#[cfg(feature = "dev")]
+fn draw_navigation_paths(
+    dev_tools: Res<DevTools>,
+    paths: Query<(&Transform, &NavigationPath)>,
+    mut gizmos: Gizmos,
 ) {
-    let nav_render_enabled = editor
-        .window_state::<DevEditorWindow>()
-        .context("Failed to read dev window state")?
-        .navmesh_render_enabled;
-    draw_nav_mesh.0 = nav_render_enabled;
+    if !dev_tools.is_active("Navmesh") {
+        return;
+    }
+    let offset = Vec3::new(0., 0.2, 0.);
+    for (transform, path) in &paths {
+        let points = std::iter::once(transform.translation)
+            .chain(path.corners.iter().copied())
+            .map(|point| point + offset);
+        gizmos.linestrip(points, Color::BLUE);
+        gizmos.sphere(path.destination + offset, Quat::IDENTITY, 0.2, Color::CYAN);
+    }
 }
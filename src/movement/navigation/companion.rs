@@ -0,0 +1,359 @@
+use crate::{
+    gameplay_log::{log_event, GameplayLog},
+    level_instantiation::on_spawn::{Npc, Player},
+    movement::{
+        character_controller::{Depenetrate, GeneralMovementSystemSet, Jump, Walk},
+        navigation::{
+            follow_navigation_paths, has_line_of_sight, update_navigation_paths,
+            NavigationDestination, NavigationPath,
+        },
+        physics::CollisionLayer,
+    },
+    player_control::camera::IngameCamera,
+    util::math_trait_ext::{F32Ext, Vec3Ext},
+    world_interaction::dialog::CurrentDialogTarget,
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_tnua::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+/// How many player jumps a companion remembers so that it can jump at the same spots.
+const MAX_JUMP_SPOTS: usize = 4;
+/// How close a companion needs to get to a remembered jump spot to jump itself.
+const JUMP_SPOT_REACHED_DISTANCE: f32 = 0.6;
+/// How long a companion holds its jump, which determines how high it jumps.
+const JUMP_HOLD_TIME: f32 = 0.4;
+
+/// Handles NPCs marked as [`Companion`]s. Instead of walking straight at the player,
+/// they trail behind the player and wait when the player stops.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Companion>().add_systems(
+        Update,
+        (
+            init_companions,
+            record_player_jumps,
+            update_companion_destinations.before(update_navigation_paths),
+            drive_companions
+                .after(follow_navigation_paths)
+                .before(GeneralMovementSystemSet),
+            teleport_stuck_companions.after(drive_companions),
+            remove_former_companion_state,
+        )
+            .chain()
+            .run_if(in_state(GameState::Playing)),
+    );
+}
+
+/// Marks an [`Npc`] as a companion that follows the player around.
+/// When removed, the NPC keeps its [`NavigationDestination`] and stays where it is unless that is changed.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct Companion {
+    /// The companion does not walk closer to the player than this.
+    pub(crate) min_distance: f32,
+    /// The companion starts following once the player is further away than this.
+    pub(crate) max_distance: f32,
+    /// When the companion is further away than this while off-screen, it is teleported behind the player.
+    pub(crate) teleport_distance: f32,
+    /// When the companion is unable to move for this many seconds while off-screen, it is teleported behind the player.
+    pub(crate) stuck_timeout: f32,
+}
+
+impl Default for Companion {
+    fn default() -> Self {
+        Self {
+            min_distance: 1.5,
+            max_distance: 4.0,
+            teleport_distance: 20.0,
+            stuck_timeout: 3.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Component, Default)]
+pub(crate) struct CompanionState {
+    following: bool,
+    stuck_time: f32,
+    jump_time_left: f32,
+    jump_spots: Vec<Vec3>,
+}
+
+impl CompanionState {
+    /// What the companion is doing, for debugging.
+    pub(crate) fn describe(&self) -> String {
+        let action = if self.following {
+            "Following the player"
+        } else {
+            "Waiting near the player"
+        };
+        format!("{action}, stuck for {:.1} s", self.stuck_time)
+    }
+}
+
+fn init_companions(
+    mut commands: Commands,
+    companions: Query<(Entity, &Transform, &Companion), (Added<Companion>, With<Npc>)>,
+) {
+    for (entity, transform, companion) in &companions {
+        commands.entity(entity).insert((
+            CompanionState::default(),
+            NavigationDestination {
+                position: transform.translation,
+                stopping_distance: companion.min_distance / 2.,
+            },
+        ));
+    }
+}
+
+fn record_player_jumps(
+    player_query: Query<(&Transform, &TnuaController, &LinearVelocity), With<Player>>,
+    mut companions: Query<&mut CompanionState>,
+    mut last_grounded_position: Local<Option<Vec3>>,
+) {
+    for (transform, controller, velocity) in &player_query {
+        let Ok(airborne) = controller.is_airborne() else {
+            continue;
+        };
+        if !airborne {
+            *last_grounded_position = Some(transform.translation);
+            continue;
+        }
+        let Some(takeoff) = last_grounded_position.take() else {
+            continue;
+        };
+        if velocity.y <= 0. {
+            // Walked off a ledge. The companion can do that on its own.
+            continue;
+        }
+        for mut state in &mut companions {
+            if state.jump_spots.len() >= MAX_JUMP_SPOTS {
+                state.jump_spots.remove(0);
+            }
+            state.jump_spots.push(takeoff);
+        }
+    }
+}
+
+fn update_companion_destinations(
+    mut companions: Query<
+        (
+            Entity,
+            &Transform,
+            &Companion,
+            &mut CompanionState,
+            &mut NavigationDestination,
+        ),
+        Without<Player>,
+    >,
+    player_query: Query<(&Transform, &LinearVelocity), With<Player>>,
+    camera_query: Query<&Transform, (With<IngameCamera>, Without<Player>, Without<Companion>)>,
+    mut log: ResMut<GameplayLog>,
+) {
+    let Some((player_transform, player_velocity)) = player_query.iter().next() else {
+        return;
+    };
+    let Some(camera_transform) = camera_query.iter().next() else {
+        return;
+    };
+    let player_position = player_transform.translation;
+    let mut camera_forward = camera_transform.forward().horizontal().normalize_or_zero();
+    // A camera looking straight down has no horizontal forward direction
+    if camera_forward == Vec3::ZERO {
+        camera_forward = player_transform.forward().horizontal().normalize_or_zero();
+    }
+    let player_is_moving = !player_velocity.0.horizontal().is_approx_zero();
+    for (entity, transform, companion, mut state, mut destination) in &mut companions {
+        let distance_squared = (transform.translation - player_position).length_squared();
+        let in_facing_cone =
+            is_in_facing_cone(player_position, transform.translation, camera_forward)
+                && distance_squared < companion.max_distance.squared();
+        let was_following = state.following;
+        if distance_squared > companion.max_distance.squared() || in_facing_cone {
+            state.following = true;
+        } else if !player_is_moving && distance_squared < companion.min_distance.squared() * 2. {
+            state.following = false;
+        }
+        if state.following != was_following {
+            let message = if state.following {
+                "Started following"
+            } else {
+                "Stopped following"
+            };
+            log_event!(
+                log,
+                Ai,
+                Some(entity),
+                message,
+                distance = distance_squared.sqrt(),
+                in_facing_cone = in_facing_cone
+            );
+        }
+        // Trailing behind the camera direction guarantees that we are not standing in the
+        // cone the player uses to pick interaction targets.
+        destination.position = player_position - camera_forward * companion.min_distance;
+    }
+}
+
+pub(super) fn drive_companions(
+    time: Res<Time>,
+    dialog_target: Res<CurrentDialogTarget>,
+    mut companions: Query<(
+        &Transform,
+        &LinearVelocity,
+        &mut CompanionState,
+        &mut Walk,
+        &mut Jump,
+    )>,
+) {
+    let dt = time.delta_seconds();
+    let in_dialog = dialog_target.0.is_some();
+    for (transform, velocity, mut state, mut walk, mut jump) in &mut companions {
+        if in_dialog || !state.following {
+            walk.direction = None;
+            state.stuck_time = 0.;
+            continue;
+        }
+
+        let position = transform.translation;
+        if let Some(index) = state.jump_spots.iter().position(|spot| {
+            (*spot - position).horizontal().length_squared() < JUMP_SPOT_REACHED_DISTANCE.squared()
+        }) {
+            // Forget about all jumps up to this one, we are past them.
+            state.jump_spots.drain(..=index);
+            state.jump_time_left = JUMP_HOLD_TIME;
+        }
+        if state.jump_time_left > 0. {
+            jump.requested = true;
+            state.jump_time_left -= dt;
+        }
+
+        let is_blocked = walk.direction.is_some() && velocity.0.horizontal().length() < 0.1;
+        if is_blocked {
+            state.stuck_time += dt;
+        } else {
+            state.stuck_time = 0.;
+        }
+    }
+}
+
+fn teleport_stuck_companions(
+    mut commands: Commands,
+    mut companions: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut LinearVelocity,
+            &Companion,
+            &mut CompanionState,
+        ),
+        Without<Player>,
+    >,
+    player_query: Query<&Transform, (With<Player>, Without<Companion>)>,
+    camera_query: Query<
+        (&Camera, &GlobalTransform, &Transform),
+        (With<IngameCamera>, Without<Player>, Without<Companion>),
+    >,
+    spatial_query: SpatialQuery,
+    mut log: ResMut<GameplayLog>,
+) {
+    let Some(player_transform) = player_query.iter().next() else {
+        return;
+    };
+    let Some((camera, camera_global_transform, camera_transform)) = camera_query.iter().next()
+    else {
+        return;
+    };
+    let player_position = player_transform.translation;
+    for (entity, mut transform, mut velocity, companion, mut state) in &mut companions {
+        let too_far = (transform.translation - player_position).length_squared()
+            > companion.teleport_distance.squared();
+        let stuck = state.stuck_time > companion.stuck_timeout;
+        if !(too_far || stuck) {
+            continue;
+        }
+        let on_screen = camera
+            .world_to_ndc(camera_global_transform, transform.translation)
+            .is_some_and(|ndc| ndc.x.abs() <= 1. && ndc.y.abs() <= 1. && ndc.z >= 0.);
+        if on_screen {
+            continue;
+        }
+        let Some(position) = find_position_behind_player(
+            &spatial_query,
+            player_transform,
+            camera_transform,
+            companion,
+        ) else {
+            continue;
+        };
+        log_event!(
+            log,
+            Ai,
+            Some(entity),
+            "Teleported behind the player",
+            too_far = too_far,
+            stuck = stuck
+        );
+        transform.translation = position;
+        velocity.0 = Vec3::ZERO;
+        state.stuck_time = 0.;
+        state.jump_spots.clear();
+        // Forces a new path to be computed from the new position
+        commands
+            .entity(entity)
+            .remove::<NavigationPath>()
+            .insert(Depenetrate::default());
+    }
+}
+
+fn remove_former_companion_state(
+    mut commands: Commands,
+    mut removed: RemovedComponents<Companion>,
+    states: Query<(), With<CompanionState>>,
+) {
+    for entity in removed.read() {
+        if states.contains(entity) {
+            commands.entity(entity).remove::<CompanionState>();
+        }
+    }
+}
+
+fn find_position_behind_player(
+    spatial_query: &SpatialQuery,
+    player_transform: &Transform,
+    camera_transform: &Transform,
+    companion: &Companion,
+) -> Option<Vec3> {
+    let player_position = player_transform.translation;
+    let mut back = camera_transform.back().horizontal().normalize_or_zero();
+    // A camera looking straight down has no horizontal backward direction
+    if back == Vec3::ZERO {
+        back = player_transform.back().horizontal().normalize_or_zero();
+    }
+    let side = back.cross(Vec3::Y);
+    let filter = SpatialQueryFilter::from_mask(CollisionLayer::Terrain.to_bits());
+    [
+        back,
+        (back + side).normalize(),
+        (back - side).normalize(),
+        side,
+        -side,
+    ]
+    .into_iter()
+    .map(|direction| player_position + direction * companion.min_distance)
+    .find(|&candidate| {
+        let has_ground = spatial_query
+            .cast_ray(candidate, Direction3d::NEG_Y, 3., true, filter.clone())
+            .is_some();
+        has_ground && has_line_of_sight(spatial_query, player_position, candidate)
+    })
+}
+
+fn is_in_facing_cone(player: Vec3, target: Vec3, camera_forward: Vec3) -> bool {
+    // Mirrors the check used to find interaction opportunities
+    let player_to_target = (target - player).horizontal();
+    camera_forward.angle_between(player_to_target) < TAU / 8.
+}
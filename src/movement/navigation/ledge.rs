@@ -0,0 +1,202 @@
+#[cfg(feature = "dev")]
+use crate::dev::dev_tools::DevTools;
+use crate::{
+    level_instantiation::on_spawn::{player, Npc},
+    movement::{
+        character_controller::{GeneralMovementSystemSet, Walk},
+        navigation::{
+            companion::drive_companions, follow_navigation_paths, wander::steer_wanderers,
+            NavigationDestination, NavigationPath,
+        },
+        physics::CollisionLayer,
+    },
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_tnua::TnuaProximitySensor;
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How far ahead of an NPC the ground is probed, in meters.
+const PROBE_DISTANCE: f32 = player::RADIUS * 3.;
+/// When walking straight on would drop too far, these turns away from the walking direction are tried, in order.
+const DETOUR_ANGLES: [f32; 4] = [45., -45., 90., -90.];
+
+/// Keeps NPCs from walking off ledges. Before an NPC walks on, the ground is probed a short distance ahead of it.
+/// If the drop there is higher than its [`LedgeCaution`] allows, the NPC walks along the ledge instead, or stops.
+/// NPCs whose path leads down to a lower corner are trusted to know the way, e.g. down a slope the navmesh connects.
+/// The probes are drawn with the "Navmesh" dev tool.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<LedgeCaution>().add_systems(
+        Update,
+        avoid_ledges
+            .after(follow_navigation_paths)
+            .after(steer_wanderers)
+            .after(drive_companions)
+            .before(GeneralMovementSystemSet)
+            .run_if(in_state(GameState::Playing)),
+    );
+    #[cfg(feature = "dev")]
+    app.add_systems(Update, draw_ledge_probes);
+}
+
+/// How far an [`Npc`] is willing to drop. NPCs without this use the default.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct LedgeCaution {
+    /// The highest drop the NPC walks down, in meters.
+    pub(crate) max_drop: f32,
+    /// The highest drop while the NPC follows the player instead of a [`NavigationDestination`], e.g. an enemy in a chase.
+    /// `None` uses [`LedgeCaution::max_drop`].
+    pub(crate) chase_max_drop: Option<f32>,
+}
+
+impl Default for LedgeCaution {
+    fn default() -> Self {
+        Self {
+            max_drop: 1.,
+            chase_max_drop: None,
+        }
+    }
+}
+
+/// The ground probes of the last frame, for drawing them.
+#[derive(Debug, Clone, PartialEq, Component, Default)]
+pub(super) struct LedgeProbes(Vec<LedgeProbe>);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LedgeProbe {
+    from: Vec3,
+    /// Where the ground ahead was found, if it is not too far down.
+    ground: Option<Vec3>,
+}
+
+fn avoid_ledges(
+    mut commands: Commands,
+    mut npcs: Query<
+        (
+            Entity,
+            &Position,
+            &mut Walk,
+            &TnuaProximitySensor,
+            Option<&LedgeCaution>,
+            Option<&NavigationPath>,
+            Has<NavigationDestination>,
+            Option<&mut LedgeProbes>,
+        ),
+        With<Npc>,
+    >,
+    spatial_query: SpatialQuery,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("avoid_ledges").entered();
+    let filter = SpatialQueryFilter::from_mask(CollisionLayer::Terrain.to_bits());
+    for (entity, position, mut walk, sensor, caution, path, has_destination, probes) in
+        npcs.iter_mut()
+    {
+        let mut new_probes = Vec::new();
+        // Characters in the air have already left whatever ledge there was
+        if let (Some(direction), Some(ground)) = (walk.direction, sensor.output.as_ref()) {
+            let caution = caution.copied().unwrap_or_default();
+            let max_drop = if has_destination {
+                caution.max_drop
+            } else {
+                caution.chase_max_drop.unwrap_or(caution.max_drop)
+            };
+            let ground_height = position.0.y - ground.proximity;
+            let leads_down = path
+                .and_then(NavigationPath::next_corner)
+                .is_some_and(|corner| corner.y < ground_height - max_drop);
+            if !leads_down {
+                // The probes start as high as the character, so that a step up ahead is not mistaken for a drop
+                let max_distance = ground.proximity + max_drop;
+                let mut probe = |direction: Vec3| {
+                    let from = position.0 + direction.normalize_or_zero() * PROBE_DISTANCE;
+                    let ground = spatial_query
+                        .cast_ray(from, Direction3d::NEG_Y, max_distance, true, filter.clone())
+                        .map(|hit| from - Vec3::Y * hit.time_of_impact);
+                    new_probes.push(LedgeProbe { from, ground });
+                    ground.is_some()
+                };
+                if !probe(direction) {
+                    walk.direction = DETOUR_ANGLES
+                        .iter()
+                        .map(|angle| Quat::from_rotation_y(angle.to_radians()) * direction)
+                        .find(|&detour| probe(detour))
+                        // Walk along the ledge only as fast as that gets the NPC where it wanted to go
+                        .map(|detour| detour * detour.dot(direction).max(0.))
+                        .filter(|detour| detour.length_squared() > 1e-3);
+                }
+            }
+        }
+        match probes {
+            Some(mut probes) => probes.0 = new_probes,
+            None => {
+                commands.entity(entity).insert(LedgeProbes(new_probes));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "dev")]
+fn draw_ledge_probes(dev_tools: Res<DevTools>, probes: Query<&LedgeProbes>, mut gizmos: Gizmos) {
+    if !dev_tools.is_active("Navmesh") {
+        return;
+    }
+    for probe in probes.iter().flat_map(|probes| &probes.0) {
+        match probe.ground {
+            Some(ground) => gizmos.line(probe.from, ground, Color::GREEN),
+            None => gizmos.line(probe.from, probe.from - Vec3::Y, Color::RED),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{movement::character_controller::CharacterControllerBundle, testing::TestApp};
+
+    #[test]
+    fn npc_patrolling_along_a_cliff_stays_on_the_platform() {
+        // The ledge checks are part of the movement the test app always has
+        let mut app = TestApp::new();
+        app.spawn_ground();
+        // A platform from -3 to 3 on both axes, 5 m above the ground
+        app.spawn_block(Vec3::new(0., 4.5, 0.), Vec3::new(6., 1., 6.));
+        let patrol = [Vec3::new(3.5, 5., -2.5), Vec3::new(3.5, 5., 2.5)];
+        let npc = app
+            .world_mut()
+            .spawn((
+                Name::new("Guard"),
+                Npc,
+                SpatialBundle::from_transform(Transform::from_xyz(1.5, 6., -2.5)),
+                CharacterControllerBundle::capsule(player::HEIGHT, player::RADIUS, 1.),
+                NavigationDestination {
+                    position: patrol[1],
+                    stopping_distance: 0.1,
+                },
+                // The patrol route runs just past the edge, as if the waypoints were placed carelessly
+                NavigationPath {
+                    corners: patrol.to_vec(),
+                    destination: patrol[1],
+                },
+            ))
+            .id();
+
+        let mut furthest = f32::MIN;
+        for _ in 0..300 {
+            app.step(1);
+            let translation = app.translation(npc);
+            assert!(
+                translation.y > 4.5 && translation.x < 3. + player::RADIUS,
+                "The NPC left the platform at {translation}"
+            );
+            furthest = furthest.max(translation.z);
+        }
+        assert!(
+            furthest > 0.,
+            "The NPC stopped at the edge instead of walking along it"
+        );
+    }
+}
@@ -0,0 +1,340 @@
+use crate::{
+    determinism::{GameRng, RngStream},
+    gameplay_log::{log_event, GameplayLog},
+    level_instantiation::on_spawn::Npc,
+    movement::{
+        character_controller::{GeneralMovementSystemSet, PlayOneShotAnimation, Walk},
+        navigation::{
+            follow_navigation_paths, has_line_of_sight, update_navigation_paths,
+            NavigationDestination,
+        },
+    },
+    util::math_trait_ext::Vec3Ext,
+    world_interaction::dialog::CurrentDialogTarget,
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_tnua::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use oxidized_navigation::{query::find_polygon_path, NavMesh, NavMeshSettings};
+use serde::{Deserialize, Serialize};
+
+/// A wanderer counts as arrived when it is horizontally this close to its target.
+const ARRIVAL_DISTANCE: f32 = 0.5;
+/// How many random points are tried before waiting a bit and trying again.
+const MAX_PICK_ATTEMPTS: usize = 6;
+/// How long to wait after no reachable point was found.
+const RETRY_PAUSE: f32 = 1.;
+/// A wanderer that got no closer to its target for this many seconds gives up on it and pauses.
+const STUCK_TIMEOUT: f32 = 2.;
+/// Other characters closer than this are steered around.
+const AVOIDANCE_RADIUS: f32 = 1.5;
+/// How strongly a character right next to a wanderer pushes its steering away, relative to the way it wants to go.
+const AVOIDANCE_STRENGTH: f32 = 1.5;
+/// In seconds. Steering changes are smoothed over about this long, so that a wanderer sandwiched between
+/// neighbors does not flip back and forth between dodging left and right.
+const STEERING_SMOOTHING: f32 = 0.3;
+/// In seconds, the range of time between two idle animations while pausing.
+const FIDGET_INTERVAL: (f32, f32) = (2., 5.);
+
+/// Makes NPCs with [`Wander`] stroll around their home, e.g. as background characters in a town.
+/// They pick a random reachable point, walk there along the navmesh, pause for a while playing idle animations and repeat.
+/// Points off the navmesh may still be picked when they are in plain sight, in which case the NPC walks there in a straight line
+/// and picks a new point if something blocks its way.
+/// While walking, wanderers steer around other characters, including the player and each other.
+/// A wanderer stands still while the player talks to it, and continues where it left off afterwards.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Wander>().add_systems(
+        Update,
+        (
+            init_wanderers,
+            update_wanderers.before(update_navigation_paths),
+            steer_wanderers
+                .after(follow_navigation_paths)
+                .before(GeneralMovementSystemSet),
+            remove_former_wanderer_state,
+        )
+            .chain()
+            .run_if(in_state(GameState::Playing)),
+    );
+}
+
+/// Lets an [`Npc`] wander around the position it spawned at.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct Wander {
+    /// How far from its home the NPC wanders, in meters.
+    pub(crate) radius: f32,
+    /// In seconds.
+    pub(crate) min_pause: f32,
+    /// In seconds.
+    pub(crate) max_pause: f32,
+    /// One-shot animations played now and then while pausing, e.g. looking around. Picked at random.
+    pub(crate) idle_animations: Vec<String>,
+}
+
+impl Default for Wander {
+    fn default() -> Self {
+        Self {
+            radius: 6.,
+            min_pause: 2.,
+            max_pause: 6.,
+            idle_animations: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Component)]
+pub(crate) struct WanderState {
+    home: Vec3,
+    phase: WanderPhase,
+    /// The smoothed direction the wanderer walks in.
+    steering: Vec3,
+    rng: RngStream,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WanderPhase {
+    Pausing {
+        remaining: f32,
+        until_fidget: f32,
+    },
+    Walking {
+        target: Vec3,
+        closest_distance: f32,
+        stuck_time: f32,
+    },
+}
+
+impl WanderState {
+    /// What the wanderer is doing, for debugging.
+    pub(crate) fn describe(&self) -> String {
+        match self.phase {
+            WanderPhase::Pausing { remaining, .. } => format!("Pausing for {remaining:.1} s"),
+            WanderPhase::Walking {
+                target, stuck_time, ..
+            } => format!(
+                "Walking to ({:.1}, {:.1}, {:.1}), stuck for {stuck_time:.1} s",
+                target.x, target.y, target.z
+            ),
+        }
+    }
+}
+
+impl WanderPhase {
+    fn pausing(wander: &Wander, rng: &mut RngStream) -> Self {
+        Self::Pausing {
+            remaining: rng.range_f32(wander.min_pause..wander.max_pause.max(wander.min_pause)),
+            until_fidget: rng.range_f32(FIDGET_INTERVAL.0..FIDGET_INTERVAL.1),
+        }
+    }
+}
+
+fn init_wanderers(
+    mut commands: Commands,
+    game_rng: Res<GameRng>,
+    wanderers: Query<(Entity, &Transform, &Wander), (Added<Wander>, With<Npc>)>,
+) {
+    for (entity, transform, wander) in &wanderers {
+        let mut rng = game_rng.fork_for("wander", entity);
+        commands.entity(entity).insert((
+            WanderState {
+                home: transform.translation,
+                phase: WanderPhase::pausing(wander, &mut rng),
+                steering: Vec3::ZERO,
+                rng,
+            },
+            // Without a destination, NPCs walk towards the player
+            NavigationDestination {
+                position: transform.translation,
+                stopping_distance: ARRIVAL_DISTANCE / 2.,
+            },
+        ));
+    }
+}
+
+fn update_wanderers(
+    time: Res<Time>,
+    dialog_target: Res<CurrentDialogTarget>,
+    nav_mesh: Res<NavMesh>,
+    nav_mesh_settings: Res<NavMeshSettings>,
+    spatial_query: SpatialQuery,
+    mut wanderers: Query<(
+        Entity,
+        &Transform,
+        &Wander,
+        &mut WanderState,
+        &mut NavigationDestination,
+    )>,
+    mut animation_requests: EventWriter<PlayOneShotAnimation>,
+    mut log: ResMut<GameplayLog>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_wanderers").entered();
+    let dt = time.delta_seconds();
+    let nav_mesh = nav_mesh.get();
+    let nav_mesh = nav_mesh.read().ok();
+    let is_reachable = |from: Vec3, to: Vec3| {
+        let has_path = nav_mesh.as_ref().is_some_and(|nav_mesh| {
+            find_polygon_path(nav_mesh, &nav_mesh_settings, from, to, None, None).is_ok()
+        });
+        has_path || has_line_of_sight(&spatial_query, from, to)
+    };
+
+    for (entity, transform, wander, mut state, mut destination) in &mut wanderers {
+        let position = transform.translation;
+        if dialog_target.0 == Some(entity) {
+            destination.position = position;
+            continue;
+        }
+        let state = &mut *state;
+        let next_phase = match &mut state.phase {
+            WanderPhase::Pausing {
+                remaining,
+                until_fidget,
+            } => {
+                *remaining -= dt;
+                *until_fidget -= dt;
+                if *until_fidget <= 0. {
+                    *until_fidget = state.rng.range_f32(FIDGET_INTERVAL.0..FIDGET_INTERVAL.1);
+                    if let Some(animation) = state.rng.choose(&wander.idle_animations) {
+                        animation_requests.send(PlayOneShotAnimation {
+                            entity,
+                            animation: animation.clone(),
+                        });
+                    }
+                }
+                if *remaining > 0. {
+                    continue;
+                }
+                let home = state.home;
+                let target = (0..MAX_PICK_ATTEMPTS)
+                    .map(|_| {
+                        let distance = wander.radius * state.rng.f32().sqrt();
+                        home + state.rng.horizontal_direction() * distance
+                    })
+                    .find(|&candidate| is_reachable(position, candidate));
+                match target {
+                    Some(target) => {
+                        log_event!(log, Ai, Some(entity), "Started wandering", target = target);
+                        WanderPhase::Walking {
+                            target,
+                            closest_distance: f32::INFINITY,
+                            stuck_time: 0.,
+                        }
+                    }
+                    None => {
+                        log_event!(
+                            log,
+                            Ai,
+                            Some(entity),
+                            "Found no reachable spot to wander to"
+                        );
+                        *remaining = RETRY_PAUSE;
+                        continue;
+                    }
+                }
+            }
+            WanderPhase::Walking {
+                target,
+                closest_distance,
+                stuck_time,
+            } => {
+                let distance = (*target - position).horizontal().length();
+                if distance < *closest_distance - 0.1 {
+                    *closest_distance = distance;
+                    *stuck_time = 0.;
+                } else {
+                    *stuck_time += dt;
+                }
+                if distance > ARRIVAL_DISTANCE && *stuck_time < STUCK_TIMEOUT {
+                    if destination.position != *target {
+                        destination.position = *target;
+                    }
+                    continue;
+                }
+                if *stuck_time >= STUCK_TIMEOUT {
+                    log_event!(
+                        log,
+                        Ai,
+                        Some(entity),
+                        "Gave up wandering",
+                        distance = distance
+                    );
+                } else {
+                    log_event!(log, Ai, Some(entity), "Arrived");
+                }
+                destination.position = position;
+                WanderPhase::pausing(wander, &mut state.rng)
+            }
+        };
+        state.phase = next_phase;
+    }
+}
+
+pub(super) fn steer_wanderers(
+    time: Res<Time>,
+    dialog_target: Res<CurrentDialogTarget>,
+    mut wanderers: Query<(Entity, &Transform, &mut WanderState, &mut Walk)>,
+    characters: Query<(Entity, &Transform), With<TnuaController>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("steer_wanderers").entered();
+    let blend = 1. - (-time.delta_seconds() / STEERING_SMOOTHING).exp();
+    for (entity, transform, mut state, mut walk) in &mut wanderers {
+        let position = transform.translation;
+        let WanderPhase::Walking { target, .. } = state.phase else {
+            state.steering = Vec3::ZERO;
+            continue;
+        };
+        if dialog_target.0 == Some(entity) {
+            walk.direction = None;
+            state.steering = Vec3::ZERO;
+            continue;
+        }
+        // Off the navmesh, there is no path to follow, so the wanderer heads straight for its target
+        let Some(desired) = walk
+            .direction
+            .or_else(|| (target - position).horizontal().try_normalize())
+        else {
+            continue;
+        };
+
+        let right = desired.cross(Vec3::Y);
+        let mut avoidance = Vec3::ZERO;
+        for (other, other_transform) in &characters {
+            if other == entity {
+                continue;
+            }
+            let offset = (position - other_transform.translation).horizontal();
+            let distance = offset.length();
+            // Characters behind the wanderer are not in its way
+            if distance >= AVOIDANCE_RADIUS || distance < 1e-4 || offset.dot(desired) > 0. {
+                continue;
+            }
+            let closeness = 1. - distance / AVOIDANCE_RADIUS;
+            // Also dodging to the right keeps two wanderers walking straight at each other from blocking each other
+            avoidance += (offset / distance + right * 0.5) * closeness;
+        }
+        let new_steering = (desired + avoidance * AVOIDANCE_STRENGTH).normalize_or_zero();
+        state.steering = if state.steering == Vec3::ZERO {
+            new_steering
+        } else {
+            state.steering.lerp(new_steering, blend)
+        };
+        walk.direction = state.steering.try_normalize();
+    }
+}
+
+fn remove_former_wanderer_state(
+    mut commands: Commands,
+    mut removed: RemovedComponents<Wander>,
+    states: Query<(), With<WanderState>>,
+) {
+    for entity in removed.read() {
+        if states.contains(entity) {
+            commands.entity(entity).remove::<WanderState>();
+        }
+    }
+}
@@ -1,11 +1,17 @@
 use bevy::prelude::*;
 use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+mod sleep;
 
 /// Sets up and configures the XPBD physics.
+/// - [`sleep::plugin`] settles freshly spawned props and puts props far from the player to sleep.
 pub(super) fn plugin(app: &mut App) {
-    app.add_plugins(PhysicsPlugins::default())
+    app.add_plugins((PhysicsPlugins::default(), sleep::plugin))
         // Using the default fixed timestep causes issues on faster (165 Hz) machines.
-        .insert_resource(Time::new_with(Physics::variable(1.0 / 60.)));
+        .insert_resource(Time::new_with(Physics::variable(1.0 / 60.)))
+        .register_type::<PhysicsSettings>()
+        .init_resource::<PhysicsSettings>();
 }
 
 #[derive(PhysicsLayer)]
@@ -16,3 +22,47 @@ pub(crate) enum CollisionLayer {
     CameraObstacle,
     Sensor,
 }
+
+/// Tuning for how props are put to sleep. Props are dynamic rigid bodies that are not characters, e.g. crates and debris.
+#[derive(Debug, Clone, PartialEq, Resource, Reflect, Serialize, Deserialize)]
+#[reflect(Resource, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct PhysicsSettings {
+    /// For this many seconds after spawning, props are damped strongly, so that props spawned
+    /// slightly inside each other or the ground come to rest instead of jittering.
+    pub(crate) settle_duration: f32,
+    /// The linear and angular damping of settling props.
+    pub(crate) settle_damping: f32,
+    /// A settling prop that stays slower than this for [`PhysicsSettings::sleep_delay`] is put to sleep right away, in m/s.
+    pub(crate) sleep_linear_speed: f32,
+    /// In rad/s.
+    pub(crate) sleep_angular_speed: f32,
+    /// In seconds.
+    pub(crate) sleep_delay: f32,
+    /// Props further than this from the player are put to sleep, and woken again once the player comes closer, in meters.
+    /// Sleeping props cost close to nothing.
+    pub(crate) activation_range: f32,
+    /// How much closer than the [`PhysicsSettings::activation_range`] the player has to come to wake props again,
+    /// so that props right at the edge do not toggle every check. In meters.
+    pub(crate) activation_hysteresis: f32,
+    /// How often the distance to the player is checked, in seconds.
+    pub(crate) activation_interval: f32,
+    /// A distant prop woken by a hit or an impulse stays awake for at least this many seconds, so that it can fly and land.
+    pub(crate) wake_grace: f32,
+}
+
+impl Default for PhysicsSettings {
+    fn default() -> Self {
+        Self {
+            settle_duration: 1.,
+            settle_damping: 8.,
+            sleep_linear_speed: 0.15,
+            sleep_angular_speed: 0.3,
+            sleep_delay: 0.2,
+            activation_range: 60.,
+            activation_hysteresis: 5.,
+            activation_interval: 0.5,
+            wake_grace: 3.,
+        }
+    }
+}
@@ -0,0 +1,359 @@
+#[cfg(feature = "dev")]
+use crate::{
+    dev::dev_tools::{DevTools, RegisterDevToolExt},
+    player_control::ui_layer::UiLayer,
+};
+use crate::{level_instantiation::on_spawn::Player, movement::physics::PhysicsSettings, GameState};
+use bevy::{prelude::*, utils::Duration};
+#[cfg(feature = "dev")]
+use bevy_egui::{egui, EguiContexts};
+use bevy_tnua::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+
+/// Keeps props from costing physics time while nothing happens to them:
+/// - Freshly spawned props are [`Settling`]: they are damped strongly for a moment and put to sleep as soon as they rest.
+/// - Every [`PhysicsSettings::activation_interval`], props further than the [`PhysicsSettings::activation_range`]
+///   from the player are put to sleep and the ones the player came close to again are woken.
+/// - Sleeping props that are hit by a moving body are woken. XPBD itself wakes bodies that get an impulse or force.
+///   Distant props woken this way stay awake for [`PhysicsSettings::wake_grace`] before they may sleep again.
+///
+/// The "Physics Sleep" dev tool counts the awake, settling and sleeping props.
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<ActivationTimer>().add_systems(
+        Update,
+        (
+            start_settling,
+            update_settling,
+            wake_hit_props,
+            update_distant_props,
+        )
+            .chain()
+            .run_if(in_state(GameState::Playing)),
+    );
+    #[cfg(feature = "dev")]
+    app.register_dev_tool("Physics Sleep", None, |_: In<bool>| {})
+        .add_systems(
+            Update,
+            display_sleep_stats.run_if(
+                in_state(GameState::Playing)
+                    .and_then(|dev_tools: Res<DevTools>| dev_tools.is_active("Physics Sleep")),
+            ),
+        );
+}
+
+/// Props are dynamic rigid bodies that are not characters.
+type PropFilter = Without<TnuaController>;
+
+/// A freshly spawned prop that is coming to rest.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct Settling {
+    /// In seconds.
+    remaining: f32,
+    /// How long the prop has been slower than the sleep thresholds, in seconds.
+    calm_time: f32,
+    /// The damping the prop had before settling, restored afterwards.
+    linear_damping: f32,
+    angular_damping: f32,
+}
+
+/// On props that were put to sleep because they were far from the player.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct DistantSleep;
+
+/// A distant prop that was woken by something else than the player coming close.
+/// Holds the seconds until it may be put to sleep again.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct WakeGrace(f32);
+
+#[derive(Debug, Resource, Default)]
+struct ActivationTimer(Timer);
+
+fn start_settling(
+    mut commands: Commands,
+    settings: Res<PhysicsSettings>,
+    props: Query<
+        (
+            Entity,
+            &RigidBody,
+            Option<&LinearDamping>,
+            Option<&AngularDamping>,
+        ),
+        (Added<RigidBody>, PropFilter),
+    >,
+) {
+    for (entity, rigid_body, linear_damping, angular_damping) in props.iter() {
+        if !rigid_body.is_dynamic() || settings.settle_duration <= 0. {
+            continue;
+        }
+        commands.entity(entity).insert((
+            Settling {
+                remaining: settings.settle_duration,
+                calm_time: 0.,
+                linear_damping: linear_damping.map_or(0., |damping| damping.0),
+                angular_damping: angular_damping.map_or(0., |damping| damping.0),
+            },
+            LinearDamping(settings.settle_damping),
+            AngularDamping(settings.settle_damping),
+        ));
+    }
+}
+
+fn update_settling(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<PhysicsSettings>,
+    mut props: Query<(
+        Entity,
+        &mut Settling,
+        &mut LinearVelocity,
+        &mut AngularVelocity,
+        &mut LinearDamping,
+        &mut AngularDamping,
+    )>,
+) {
+    let dt = time.delta_seconds();
+    for (
+        entity,
+        mut settling,
+        mut linear_velocity,
+        mut angular_velocity,
+        mut linear_damping,
+        mut angular_damping,
+    ) in props.iter_mut()
+    {
+        settling.remaining -= dt;
+        let is_calm = linear_velocity.length() < settings.sleep_linear_speed
+            && angular_velocity.length() < settings.sleep_angular_speed;
+        settling.calm_time = if is_calm { settling.calm_time + dt } else { 0. };
+        let is_rested = settling.calm_time >= settings.sleep_delay;
+        if !is_rested && settling.remaining > 0. {
+            continue;
+        }
+        if is_rested {
+            // XPBD wakes bodies whose velocity was changed outside of the physics step
+            linear_velocity.bypass_change_detection().0 = Vec3::ZERO;
+            angular_velocity.bypass_change_detection().0 = Vec3::ZERO;
+            commands.entity(entity).insert(Sleeping);
+        }
+        linear_damping.0 = settling.linear_damping;
+        angular_damping.0 = settling.angular_damping;
+        commands.entity(entity).remove::<Settling>();
+    }
+}
+
+fn wake_hit_props(
+    mut commands: Commands,
+    settings: Res<PhysicsSettings>,
+    mut collision_events: EventReader<CollisionStarted>,
+    mut sleeping: Query<(&RigidBody, Option<&mut TimeSleeping>), (With<Sleeping>, PropFilter)>,
+    hitters: Query<(&RigidBody, &LinearVelocity), Without<Sleeping>>,
+) {
+    for CollisionStarted(entity1, entity2) in collision_events.read() {
+        for (prop, hitter) in [(*entity1, *entity2), (*entity2, *entity1)] {
+            let Ok((rigid_body, time_sleeping)) = sleeping.get_mut(prop) else {
+                continue;
+            };
+            let is_hit = hitters.get(hitter).is_ok_and(|(hitter_body, velocity)| {
+                hitter_body.is_dynamic() && velocity.length() >= settings.sleep_linear_speed
+            });
+            if !rigid_body.is_dynamic() || !is_hit {
+                continue;
+            }
+            wake(&mut commands, prop, time_sleeping);
+            commands
+                .entity(prop)
+                .remove::<DistantSleep>()
+                .insert(WakeGrace(settings.wake_grace));
+        }
+    }
+}
+
+fn update_distant_props(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<PhysicsSettings>,
+    mut timer: ResMut<ActivationTimer>,
+    players: Query<&GlobalTransform, With<Player>>,
+    mut props: Query<
+        (
+            Entity,
+            &RigidBody,
+            &GlobalTransform,
+            Option<&mut TimeSleeping>,
+            Option<&mut WakeGrace>,
+            Has<Sleeping>,
+            Has<DistantSleep>,
+            Has<Settling>,
+        ),
+        PropFilter,
+    >,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_distant_props").entered();
+    let dt = time.delta_seconds();
+    for (entity, .., grace, _, _, _) in props.iter_mut() {
+        if let Some(mut grace) = grace {
+            grace.0 -= dt;
+            if grace.0 <= 0. {
+                commands.entity(entity).remove::<WakeGrace>();
+            }
+        }
+    }
+
+    let interval = Duration::from_secs_f32(settings.activation_interval.max(0.01));
+    if timer.0.duration() != interval {
+        timer.0 = Timer::new(interval, TimerMode::Repeating);
+    }
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Some(player) = players.iter().next().map(GlobalTransform::translation) else {
+        return;
+    };
+    let wake_range = (settings.activation_range - settings.activation_hysteresis).max(0.);
+    for (
+        entity,
+        rigid_body,
+        transform,
+        time_sleeping,
+        grace,
+        is_sleeping,
+        is_distant_sleeping,
+        is_settling,
+    ) in props.iter_mut()
+    {
+        if !rigid_body.is_dynamic() {
+            continue;
+        }
+        let distance = transform.translation().distance(player);
+        match (is_distant_sleeping, is_sleeping) {
+            (true, true) if distance < wake_range => {
+                wake(&mut commands, entity, time_sleeping);
+                commands.entity(entity).remove::<DistantSleep>();
+            }
+            // Woken by XPBD, e.g. through an impulse
+            (true, false) => {
+                commands
+                    .entity(entity)
+                    .remove::<DistantSleep>()
+                    .insert(WakeGrace(settings.wake_grace));
+            }
+            // The velocity is kept, so that e.g. a prop that was rolling away continues to roll when woken
+            (false, false)
+                if distance > settings.activation_range && grace.is_none() && !is_settling =>
+            {
+                commands.entity(entity).insert((Sleeping, DistantSleep));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn wake(commands: &mut Commands, entity: Entity, time_sleeping: Option<Mut<TimeSleeping>>) {
+    commands.entity(entity).remove::<Sleeping>();
+    // Otherwise XPBD puts a resting body right back to sleep
+    if let Some(mut time_sleeping) = time_sleeping {
+        time_sleeping.0 = 0.;
+    }
+}
+
+#[cfg(feature = "dev")]
+fn display_sleep_stats(
+    props: Query<(&RigidBody, Has<Sleeping>, Has<Settling>, Has<DistantSleep>), PropFilter>,
+    mut egui_contexts: EguiContexts,
+) {
+    let mut counts = [0_usize; 4];
+    for (rigid_body, is_sleeping, is_settling, is_distant_sleeping) in props.iter() {
+        if !rigid_body.is_dynamic() {
+            continue;
+        }
+        let index = match (is_sleeping, is_settling, is_distant_sleeping) {
+            (_, _, true) => 3,
+            (true, _, _) => 2,
+            (false, true, _) => 1,
+            (false, false, _) => 0,
+        };
+        counts[index] += 1;
+    }
+    egui::Window::new("Physics Sleep")
+        .order(UiLayer::Dev.order())
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(10., -10.))
+        .show(egui_contexts.ctx_mut(), |ui| {
+            egui::Grid::new("Physics Sleep Grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    let labels = ["Awake", "Settling", "Sleeping", "Sleeping far away"];
+                    for (label, count) in labels.iter().zip(counts) {
+                        ui.label(*label);
+                        ui.label(count.to_string());
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    fn spawn_crate(app: &mut TestApp, translation: Vec3) -> Entity {
+        app.world_mut()
+            .spawn((
+                Name::new("Crate"),
+                TransformBundle::from_transform(Transform::from_translation(translation)),
+                RigidBody::Dynamic,
+                Collider::cuboid(1., 1., 1.),
+            ))
+            .id()
+    }
+
+    fn is_sleeping(app: &TestApp, entity: Entity) -> bool {
+        app.world().get::<Sleeping>(entity).is_some()
+    }
+
+    #[test]
+    fn spawned_crate_settles_and_sleeps() {
+        let mut app = TestApp::new();
+        app.spawn_ground();
+        app.spawn_player(Vec3::new(0., 1., 0.));
+        // Slightly inside the ground
+        let crate_ = spawn_crate(&mut app, Vec3::new(3., 0.45, 0.));
+        app.step(60);
+        assert!(is_sleeping(&app, crate_));
+        assert!(app.world().get::<Settling>(crate_).is_none());
+        assert_eq!(app.world().get::<LinearDamping>(crate_).unwrap().0, 0.);
+    }
+
+    #[test]
+    fn distant_crate_sleeps_until_player_comes_close() {
+        let mut app = TestApp::new();
+        app.insert_resource(PhysicsSettings {
+            settle_duration: 0.,
+            activation_range: 20.,
+            ..default()
+        });
+        app.spawn_ground();
+        let player = app.spawn_player(Vec3::new(0., 1., 0.));
+        let crate_ = spawn_crate(&mut app, Vec3::new(30., 10., 0.));
+        app.step(60);
+        assert!(is_sleeping(&app, crate_));
+        assert!(
+            app.translation(crate_).y > 5.,
+            "The crate fell while asleep"
+        );
+
+        app.world_mut()
+            .get_mut::<Transform>(player)
+            .unwrap()
+            .translation = Vec3::new(28., 1., 0.);
+        app.step(180);
+        assert!(!is_sleeping(&app, crate_));
+        assert!(
+            app.translation(crate_).y < 1.,
+            "The crate did not fall after waking"
+        );
+    }
+}
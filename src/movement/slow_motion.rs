@@ -0,0 +1,213 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_xpbd_3d::prelude::*;
+
+/// Slows the game down for [`RequestSlowMotionEvent`]s by scaling [`Time<Virtual>`] and [`Time<Physics>`] together,
+/// so that jumps and thrown props follow the same arcs as at full speed, just slower.
+/// Everything that reads [`Time`], like movement, animations and particles, follows along without knowing about it.
+/// Things that should keep running at real time, like UI timers and the camera, read [`UnscaledTime`] instead.
+pub(super) fn plugin(app: &mut App) {
+    app.add_event::<RequestSlowMotionEvent>()
+        .register_type::<TimeScale>()
+        .init_resource::<TimeScale>()
+        .add_systems(Last, update_time_scale);
+}
+
+/// Slows the game down to `scale` and back. All durations are in real seconds.
+/// While a slow motion is active, further requests extend it: the slower scale and the later end win.
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub(crate) struct RequestSlowMotionEvent {
+    /// How fast the game runs compared to real time, e.g. `0.25` for a quarter of the normal speed.
+    pub(crate) scale: f32,
+    /// From the request until the game runs at full speed again, including both eases.
+    pub(crate) duration: f32,
+    /// How long it takes to slow down to `scale`.
+    pub(crate) ease_in: f32,
+    /// How long it takes to speed back up at the end.
+    pub(crate) ease_out: f32,
+}
+
+/// How fast the game currently runs compared to real time, managed by [`update_time_scale`].
+#[derive(Debug, Clone, PartialEq, Resource, Reflect)]
+#[reflect(Resource)]
+pub(crate) struct TimeScale {
+    /// 1 at full speed.
+    pub(crate) current: f32,
+    /// Real seconds that passed while the game was not paused, so that slow motion does not run out in the pause menu.
+    clock: f32,
+    #[reflect(ignore)]
+    slow_motion: Option<SlowMotion>,
+}
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        Self {
+            current: 1.,
+            clock: 0.,
+            slow_motion: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SlowMotion {
+    /// The scale the game ran at when the slow motion was last requested, which it eases from.
+    from: f32,
+    scale: f32,
+    /// All times are on [`TimeScale::clock`].
+    start: f32,
+    end: f32,
+    ease_in: f32,
+    ease_out: f32,
+}
+
+impl SlowMotion {
+    fn scale_at(&self, time: f32) -> f32 {
+        let eased_in = progress(time - self.start, self.ease_in);
+        let eased_out = progress(self.end - time, self.ease_out);
+        let slowed = self.from + (self.scale - self.from) * eased_in;
+        1. + (slowed - 1.) * eased_out
+    }
+}
+
+/// How far `elapsed` is into an ease of `duration` seconds, smoothed so that the scale does not jump.
+fn progress(elapsed: f32, duration: f32) -> f32 {
+    let linear = if duration > 0. {
+        (elapsed / duration).clamp(0., 1.)
+    } else {
+        1.
+    };
+    linear * linear * (3. - 2. * linear)
+}
+
+/// Seconds of real time that keep standing still while the game is paused, for UI timers and the camera,
+/// which should neither slow down with [`TimeScale`] nor run on in the pause menu.
+#[derive(SystemParam)]
+pub(crate) struct UnscaledTime<'w> {
+    real_time: Res<'w, Time<Real>>,
+    virtual_time: Res<'w, Time<Virtual>>,
+}
+
+impl UnscaledTime<'_> {
+    pub(crate) fn delta_seconds(&self) -> f32 {
+        if self.virtual_time.is_paused() {
+            0.
+        } else {
+            self.real_time.delta_seconds()
+        }
+    }
+}
+
+/// Runs in [`Last`], so that everything in this frame still sees the scale that its delta time was advanced with.
+fn update_time_scale(
+    mut time_scale: ResMut<TimeScale>,
+    mut requests: EventReader<RequestSlowMotionEvent>,
+    unscaled_time: UnscaledTime,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut physics_time: ResMut<Time<Physics>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_time_scale").entered();
+    time_scale.clock += unscaled_time.delta_seconds();
+    let now = time_scale.clock;
+    for request in requests.read() {
+        let scale = request.scale.clamp(0.01, 1.);
+        let end = now + request.duration.max(0.);
+        let from = time_scale.current;
+        time_scale.slow_motion = Some(match time_scale.slow_motion {
+            Some(active) if active.end > now => SlowMotion {
+                from,
+                scale: scale.min(active.scale),
+                start: now,
+                end: end.max(active.end),
+                ease_in: request.ease_in,
+                ease_out: if end > active.end {
+                    request.ease_out
+                } else {
+                    active.ease_out
+                },
+            },
+            _ => SlowMotion {
+                from,
+                scale,
+                start: now,
+                end,
+                ease_in: request.ease_in,
+                ease_out: request.ease_out,
+            },
+        });
+    }
+
+    let current = match time_scale.slow_motion {
+        Some(slow_motion) if slow_motion.end > now => slow_motion.scale_at(now),
+        _ => {
+            time_scale.slow_motion = None;
+            1.
+        }
+    };
+    time_scale.current = current;
+    if virtual_time.relative_speed() != current {
+        virtual_time.set_relative_speed(current);
+        physics_time.set_relative_speed(current);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        player_control::actions::PlayerAction,
+        testing::{InputScript, TestApp},
+    };
+
+    /// The highest point of a jump above where it started and how long it took to get there in game time,
+    /// together with how many real ticks that took.
+    fn jump_at_scale(scale: f32) -> (f32, f32, usize) {
+        let mut app = TestApp::new();
+        app.spawn_ground();
+        let player = app.spawn_player(Vec3::new(0., 0.5, 0.));
+        app.step(30);
+        app.send_event(RequestSlowMotionEvent {
+            scale,
+            duration: 100.,
+            ease_in: 0.,
+            ease_out: 0.,
+        });
+        // The scale applies from the next tick on
+        app.step(2);
+        assert_eq!(app.resource::<TimeScale>().current, scale);
+
+        let slowdown = (1. / scale) as usize;
+        let start = app.translation(player).y;
+        let start_time = app.resource::<Time<Virtual>>().elapsed_seconds();
+        app.script(InputScript::new().hold(PlayerAction::Jump, 40 * slowdown));
+        let (mut apex, mut apex_time, mut apex_tick) = (start, 0., 0);
+        for tick in 1..=120 * slowdown {
+            app.step(1);
+            let height = app.translation(player).y;
+            if height > apex {
+                apex = height;
+                apex_time = app.resource::<Time<Virtual>>().elapsed_seconds() - start_time;
+                apex_tick = tick;
+            }
+        }
+        (apex - start, apex_time, apex_tick)
+    }
+
+    #[test]
+    fn jumps_follow_the_same_arc_in_slow_motion() {
+        let (height, time, ticks) = jump_at_scale(1.);
+        let (slow_height, slow_time, slow_ticks) = jump_at_scale(0.25);
+        assert!(
+            (height - slow_height).abs() < 0.05,
+            "Jumped {height} high at full speed, but {slow_height} in slow motion"
+        );
+        assert!(
+            (time - slow_time).abs() < 2. * crate::testing::TICK,
+            "Reached the apex after {time} s at full speed, but {slow_time} s in slow motion"
+        );
+        assert!(
+            slow_ticks.abs_diff(4 * ticks) <= 8,
+            "Reached the apex after {ticks} ticks at full speed, but {slow_ticks} in slow motion"
+        );
+    }
+}
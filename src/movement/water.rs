@@ -0,0 +1,166 @@
+use crate::{
+    movement::character_controller::GeneralMovementSystemSet, util::math_trait_ext::Vec3Ext,
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_xpbd_3d::{prelude::*, PhysicsSet};
+use serde::{Deserialize, Serialize};
+
+/// How strongly the water drags submerged props along with it, per second.
+const LINEAR_DRAG: f32 = 1.5;
+/// How quickly submerged props stop spinning, per second.
+const ANGULAR_DRAG: f32 = 2.;
+
+/// Handles bodies of water. Entities inside a [`WaterVolume`] get a [`Submerged`] component:
+/// - [`Buoyant`] props float up, bob on the surface and drift along with the water's current.
+/// - Characters that are at least half submerged are carried by the current on top of their own walking,
+///   so walking against it makes slow progress.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<WaterVolume>()
+        .register_type::<Buoyant>()
+        .add_systems(
+            Update,
+            (init_buoyant_bodies, update_submersion, apply_buoyancy)
+                .chain()
+                .before(GeneralMovementSystemSet)
+                .before(PhysicsSet::Prepare)
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// A box of water around the entity's origin. The water surface is the top of the box.
+/// Volumes may be rotated around the Y axis, but not tilted.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct WaterVolume {
+    pub(crate) half_extents: Vec3,
+    /// The velocity of the flowing water in the volume's local space, in m/s. Zero for still water.
+    pub(crate) current: Vec3,
+}
+
+/// Makes a dynamic rigid body float in [`WaterVolume`]s.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct Buoyant {
+    /// The upward force when fully submerged, as a multiple of the body's weight.
+    /// Above 1, the body floats with only part of it submerged.
+    pub(crate) buoyancy: f32,
+}
+
+impl Default for Buoyant {
+    fn default() -> Self {
+        Self { buoyancy: 2. }
+    }
+}
+
+/// On entities that are in a [`WaterVolume`].
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub(crate) struct Submerged {
+    /// How much of the entity's height is below the surface, between 0 and 1.
+    pub(crate) fraction: f32,
+    /// The world space velocity of the water around the entity.
+    pub(crate) current: Vec3,
+}
+
+impl Submerged {
+    /// Whether a character is deep enough in the water to be carried by its current.
+    pub(crate) fn is_swimming(&self) -> bool {
+        self.fraction >= 0.5
+    }
+}
+
+fn init_buoyant_bodies(mut commands: Commands, bodies: Query<Entity, Added<Buoyant>>) {
+    for entity in bodies.iter() {
+        commands.entity(entity).insert((
+            ExternalForce::new(Vec3::ZERO).with_persistence(false),
+            ExternalTorque::new(Vec3::ZERO).with_persistence(false),
+        ));
+    }
+}
+
+fn update_submersion(
+    mut commands: Commands,
+    volumes: Query<(&WaterVolume, &GlobalTransform)>,
+    mut bodies: Query<(
+        Entity,
+        &RigidBody,
+        &Position,
+        &Collider,
+        Option<&mut Submerged>,
+    )>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_submersion").entered();
+    for (entity, rigid_body, position, collider, submerged) in bodies.iter_mut() {
+        if !rigid_body.is_dynamic() {
+            continue;
+        }
+        let half_height = collider
+            .shape_scaled()
+            .compute_local_aabb()
+            .half_extents()
+            .y;
+        let new_submerged = volumes.iter().find_map(|(volume, transform)| {
+            let local = transform.affine().inverse().transform_point3(position.0);
+            let extents = volume.half_extents;
+            let inside = local.x.abs() <= extents.x
+                && local.z.abs() <= extents.z
+                && local.y - half_height <= extents.y
+                && local.y + half_height >= -extents.y;
+            if !inside {
+                return None;
+            }
+            let bottom = local.y - half_height;
+            let fraction = ((extents.y - bottom) / (2. * half_height).max(1e-3)).clamp(0., 1.);
+            let (_, rotation, _) = transform.to_scale_rotation_translation();
+            Some(Submerged {
+                fraction,
+                current: rotation * volume.current,
+            })
+        });
+        match (submerged, new_submerged) {
+            (Some(mut submerged), Some(new_submerged)) => {
+                *submerged = new_submerged;
+            }
+            (None, Some(new_submerged)) => {
+                commands.entity(entity).insert(new_submerged);
+            }
+            (Some(_), None) => {
+                commands.entity(entity).remove::<Submerged>();
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+fn apply_buoyancy(
+    gravity: Res<Gravity>,
+    mut bodies: Query<(
+        &Buoyant,
+        &Submerged,
+        &Mass,
+        &LinearVelocity,
+        &AngularVelocity,
+        &mut ExternalForce,
+        &mut ExternalTorque,
+    )>,
+) {
+    for (buoyant, submerged, mass, velocity, angular_velocity, mut force, mut torque) in
+        bodies.iter_mut()
+    {
+        let lift = -gravity.0 * mass.0 * buoyant.buoyancy * submerged.fraction;
+        // Pulling the body towards the water's velocity both makes it drift and damps its bobbing
+        let drag = (submerged.current - velocity.0) * mass.0 * LINEAR_DRAG * submerged.fraction;
+        force.apply_force(lift + drag);
+        torque.apply_torque(-angular_velocity.0 * mass.0 * ANGULAR_DRAG * submerged.fraction);
+    }
+}
+
+/// The horizontal velocity a character in the water is carried along with.
+pub(crate) fn swimming_drift(submerged: Option<&Submerged>) -> Vec3 {
+    submerged
+        .filter(|submerged| submerged.is_swimming())
+        .map_or(Vec3::ZERO, |submerged| submerged.current.horizontal())
+}
@@ -1,6 +1,7 @@
 use crate::{
     file_system_interaction::config::GameConfig,
     level_instantiation::on_spawn::Player,
+    movement::character_controller::GroundedState,
     util::math_trait_ext::{F32Ext, Vec3Ext},
     GameState,
 };
@@ -11,12 +12,26 @@ use bevy_tnua::prelude::*;
 use bevy_xpbd_3d::PhysicsSet;
 pub(crate) use creation::*;
 
+pub(crate) mod attached;
+mod cpu;
 mod creation;
+pub(crate) mod emitter;
+mod footprints;
 
 /// Handles particle effects instantiation and playing.
+/// Looping effects use Hanabi on the GPU, while short bursts use the small CPU emitter in [`cpu::plugin`].
+/// Footprints on soft ground are handled by [`footprints::plugin`].
+/// Effects bound to entities and their bones, configured in an effect table, are handled by [`attached::plugin`].
+/// Ambient particles placed in levels, e.g. falling leaves, are emitted by [`emitter::plugin`].
 pub(super) fn plugin(app: &mut App) {
     app.register_type::<SprintingParticle>()
-        .add_plugins(HanabiPlugin)
+        .add_plugins((
+            HanabiPlugin,
+            attached::plugin,
+            cpu::plugin,
+            emitter::plugin,
+            footprints::plugin,
+        ))
         .add_systems(
             Update,
             play_sprinting_effect
@@ -31,19 +46,18 @@ struct SprintingParticle;
 
 #[sysfail(Log<anyhow::Error, Error>)]
 fn play_sprinting_effect(
-    with_player: Query<&TnuaController, With<Player>>,
+    with_player: Query<(&TnuaController, &GroundedState), With<Player>>,
     mut with_particle: Query<&mut EffectSpawner, With<SprintingParticle>>,
     config: Res<GameConfig>,
 ) {
-    for controller in with_player.iter() {
+    for (controller, grounded_state) in with_player.iter() {
         let Some((_, basis_state)) = controller.concrete_basis::<TnuaBuiltinWalk>() else {
             continue;
         };
         let horizontal_speed_squared = basis_state.running_velocity.horizontal().length_squared();
         for mut effect_spawner in with_particle.iter_mut() {
             let threshold = config.player.sprint_effect_speed_threshold;
-            let active = !controller.is_airborne().unwrap_or_default()
-                && horizontal_speed_squared > threshold.squared();
+            let active = !grounded_state.airborne && horizontal_speed_squared > threshold.squared();
             effect_spawner.set_active(active);
         }
     }
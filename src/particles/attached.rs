@@ -0,0 +1,495 @@
+use crate::{
+    file_system_interaction::spatial_audio::SoundEmitter,
+    level_instantiation::named_entities::EntityNames,
+    world_interaction::{
+        dialog::{commands::DialogPosition, YarnCommandsAppExt},
+        lamp::{perlin_noise, Flicker},
+    },
+    GameState,
+};
+use bevy::{pbr::NotShadowReceiver, prelude::*, utils::HashMap};
+use bevy_common_assets::ron::RonAssetPlugin;
+use bevy_hanabi::prelude::*;
+use bevy_yarnspinner::prelude::DialogueRunner;
+use serde::{Deserialize, Serialize};
+
+/// Spawns the effects of [`AttachedEffect`]s: looping particles, a light and a sound from the effect table in
+/// `assets/config/config.effects.ron`, bound to the entity or one of its bones, e.g. a torch's flame or a smoking chimney.
+/// Effects on bones wait until the bone has spawned, which takes a few frames for blueprints.
+/// Removing the component or despawning the entity removes the effect. Editing the table respawns all effects.
+/// Dialog attaches effects with `<<attach_effect entity_name effect>>` and `<<attach_effect_to_bone entity_name effect bone>>`
+/// and removes them with `<<detach_effect entity_name>>`.
+pub(super) fn plugin(app: &mut App) {
+    app.add_plugins(RonAssetPlugin::<EffectTable>::new(&["effects.ron"]))
+        .register_type::<AttachedEffect>()
+        .register_type::<EffectSocket>()
+        .init_resource::<EffectHandles>()
+        .add_yarn_commands(register_commands)
+        .add_systems(
+            Update,
+            (
+                reload_effects,
+                detach_effects,
+                attach_effects,
+                flicker_effect_lights,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+fn register_commands(dialogue_runner: &mut DialogueRunner) {
+    dialogue_runner
+        .commands_mut()
+        .add_command("attach_effect", attach_effect_command)
+        .add_command("attach_effect_to_bone", attach_effect_to_bone_command)
+        .add_command("detach_effect", detach_effect_command);
+}
+
+#[derive(Debug, Clone, PartialEq, Asset, Reflect, Serialize, Deserialize, Default)]
+pub(crate) struct EffectTable {
+    pub(crate) effects: HashMap<String, EffectDefinition>,
+}
+
+/// Everything is optional, so an effect can also be just a light or just a sound.
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct EffectDefinition {
+    pub(crate) particles: Option<EffectParticles>,
+    pub(crate) light: Option<EffectLight>,
+    pub(crate) sound: Option<SoundEmitter>,
+}
+
+/// Particles that spawn in a sphere and fly off in all directions, fading out over their lifetime.
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct EffectParticles {
+    pub(crate) color: Color,
+    /// Particles per second.
+    pub(crate) rate: f32,
+    /// In seconds.
+    pub(crate) lifetime: f32,
+    /// In meters. Particles shrink to half of this until they disappear.
+    pub(crate) size: f32,
+    /// In meters per second, away from the socket.
+    pub(crate) speed: f32,
+    /// Radius of the sphere the particles spawn in.
+    pub(crate) radius: f32,
+    /// E.g. upwards for smoke and downwards for sparks.
+    pub(crate) acceleration: Vec3,
+    /// How many particles can be alive at once.
+    pub(crate) capacity: u32,
+}
+
+impl Default for EffectParticles {
+    fn default() -> Self {
+        Self {
+            color: Color::rgb(1., 0.6, 0.2),
+            rate: 20.,
+            lifetime: 1.,
+            size: 0.05,
+            speed: 0.3,
+            radius: 0.1,
+            acceleration: Vec3::Y,
+            capacity: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct EffectLight {
+    pub(crate) color: Color,
+    /// In lumens.
+    pub(crate) intensity: f32,
+    /// In meters.
+    pub(crate) range: f32,
+    pub(crate) flicker: Option<Flicker>,
+}
+
+impl Default for EffectLight {
+    fn default() -> Self {
+        Self {
+            color: Color::rgb(1., 0.7, 0.4),
+            intensity: 800.,
+            range: 5.,
+            flicker: None,
+        }
+    }
+}
+
+/// Plays the effect called `effect` in the effect table at the `socket`.
+/// Changing either respawns the effect.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct AttachedEffect {
+    pub(crate) effect: String,
+    pub(crate) socket: EffectSocket,
+}
+
+/// Where an [`AttachedEffect`] plays.
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize, Default)]
+pub(crate) enum EffectSocket {
+    /// Relative to the entity itself.
+    Offset(Vec3),
+    /// Relative to the first descendant with this [`Name`], e.g. a bone or an empty in the entity's model.
+    /// The effect waits for as long as no such descendant exists.
+    Bone { name: String, offset: Vec3 },
+}
+
+impl Default for EffectSocket {
+    fn default() -> Self {
+        Self::Offset(Vec3::ZERO)
+    }
+}
+
+/// The effect spawned for an [`AttachedEffect`], or `None` when it is not in the effect table.
+/// Missing until the socket exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+struct AttachedEffectState {
+    instance: Option<Entity>,
+}
+
+/// On the spawned effect, pointing back to the entity with the [`AttachedEffect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+struct EffectOwner(Entity);
+
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct FlickeringLight {
+    intensity: f32,
+    flicker: Flicker,
+    seed: u64,
+}
+
+/// The particle effects built from the effect table so far, by effect name, so that all entities with the same effect
+/// share one [`EffectAsset`].
+#[derive(Debug, Default, Resource)]
+struct EffectHandles(HashMap<String, Handle<EffectAsset>>);
+
+fn reload_effects(
+    mut commands: Commands,
+    mut table_events: EventReader<AssetEvent<EffectTable>>,
+    mut handles: ResMut<EffectHandles>,
+    owners: Query<(Entity, &AttachedEffectState)>,
+) {
+    let modified = table_events
+        .read()
+        .any(|event| matches!(event, AssetEvent::Modified { .. }));
+    if !modified {
+        return;
+    }
+    info!("Respawning attached effects after the effect table changed");
+    handles.0.clear();
+    for (entity, state) in owners.iter() {
+        if let Some(instance) = state.instance {
+            commands.entity(instance).despawn_recursive();
+        }
+        commands.entity(entity).remove::<AttachedEffectState>();
+    }
+}
+
+fn detach_effects(
+    mut commands: Commands,
+    mut removed_effects: RemovedComponents<AttachedEffect>,
+    instances: Query<(Entity, &EffectOwner)>,
+) {
+    for owner in removed_effects.read() {
+        // Despawning the owner usually took the effect down with it, but not when the owner was despawned on its own
+        for (instance, _) in instances.iter().filter(|(_, effect)| effect.0 == owner) {
+            commands.entity(instance).despawn_recursive();
+        }
+        if let Some(mut owner) = commands.get_entity(owner) {
+            owner.remove::<AttachedEffectState>();
+        }
+    }
+}
+
+fn attach_effects(
+    mut commands: Commands,
+    owners: Query<
+        (Entity, &AttachedEffect, Option<&AttachedEffectState>),
+        Or<(Changed<AttachedEffect>, Without<AttachedEffectState>)>,
+    >,
+    children: Query<&Children>,
+    names: Query<&Name>,
+    tables: Res<Assets<EffectTable>>,
+    mut handles: ResMut<EffectHandles>,
+    mut effects: ResMut<Assets<EffectAsset>>,
+) {
+    let Some(table) = tables.iter().next().map(|(_, table)| table) else {
+        return;
+    };
+    for (entity, attached, state) in owners.iter() {
+        if let Some(state) = state {
+            if let Some(instance) = state.instance {
+                commands.entity(instance).despawn_recursive();
+            }
+            commands.entity(entity).remove::<AttachedEffectState>();
+        }
+        let Some(definition) = table.effects.get(&attached.effect) else {
+            error!(
+                "{entity:?} wants to play the effect \"{}\", which is not in the effect table",
+                attached.effect
+            );
+            commands
+                .entity(entity)
+                .insert(AttachedEffectState { instance: None });
+            continue;
+        };
+        let (socket, offset) = match &attached.socket {
+            EffectSocket::Offset(offset) => (entity, *offset),
+            EffectSocket::Bone { name, offset } => {
+                let Some(bone) = children.iter_descendants(entity).find(|&descendant| {
+                    names
+                        .get(descendant)
+                        .is_ok_and(|bone_name| bone_name.as_str() == name)
+                }) else {
+                    continue;
+                };
+                (bone, *offset)
+            }
+        };
+
+        let transform = Transform::from_translation(offset);
+        let instance = commands
+            .spawn((
+                Name::new(format!("Effect {}", attached.effect)),
+                EffectOwner(entity),
+                SpatialBundle::from_transform(transform),
+            ))
+            .set_parent(socket)
+            .id();
+        if let Some(particles) = &definition.particles {
+            let handle = handles
+                .0
+                .entry(attached.effect.clone())
+                .or_insert_with(|| create_effect(&attached.effect, particles, &mut effects))
+                .clone();
+            commands.entity(instance).insert((
+                ParticleEffectBundle {
+                    transform,
+                    ..ParticleEffectBundle::new(handle)
+                },
+                NotShadowReceiver,
+            ));
+        }
+        if let Some(light) = &definition.light {
+            commands.entity(instance).with_children(|parent| {
+                let mut light_entity = parent.spawn((
+                    Name::new("Effect light"),
+                    PointLightBundle {
+                        point_light: PointLight {
+                            color: light.color,
+                            intensity: light.intensity,
+                            range: light.range,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                ));
+                if let Some(flicker) = light.flicker {
+                    light_entity.insert(FlickeringLight {
+                        intensity: light.intensity,
+                        flicker,
+                        seed: entity.to_bits(),
+                    });
+                }
+            });
+        }
+        if let Some(sound) = &definition.sound {
+            commands.entity(instance).insert(sound.clone());
+        }
+        commands.entity(entity).insert(AttachedEffectState {
+            instance: Some(instance),
+        });
+    }
+}
+
+fn create_effect(
+    name: &str,
+    particles: &EffectParticles,
+    effects: &mut Assets<EffectAsset>,
+) -> Handle<EffectAsset> {
+    let color = Vec4::from(particles.color.as_rgba_f32());
+    let mut color_gradient = Gradient::new();
+    color_gradient.add_key(0.0, color);
+    color_gradient.add_key(1.0, color.truncate().extend(0.));
+
+    let mut size_gradient = Gradient::new();
+    size_gradient.add_key(0.0, Vec2::splat(particles.size));
+    size_gradient.add_key(1.0, Vec2::splat(particles.size * 0.5));
+
+    let mut module = Module::default();
+    let position_sphere_modifier = SetPositionSphereModifier {
+        center: module.lit(Vec3::ZERO),
+        radius: module.lit(particles.radius),
+        dimension: ShapeDimension::Volume,
+    };
+    let velocity_sphere_modifier = SetVelocitySphereModifier {
+        center: module.lit(Vec3::ZERO),
+        speed: module.lit(particles.speed),
+    };
+    let lifetime = SetAttributeModifier::new(Attribute::LIFETIME, module.lit(particles.lifetime));
+    let accel_modifier = AccelModifier::new(module.lit(particles.acceleration));
+    let orient_modifier = OrientModifier {
+        mode: OrientMode::FaceCameraPosition,
+        rotation: None,
+    };
+
+    effects.add(
+        EffectAsset::new(
+            particles.capacity,
+            Spawner::rate(particles.rate.into()),
+            module,
+        )
+        .with_name(name)
+        .init(position_sphere_modifier)
+        .init(velocity_sphere_modifier)
+        .init(lifetime)
+        .update(accel_modifier)
+        .render(orient_modifier)
+        .render(ColorOverLifetimeModifier {
+            gradient: color_gradient,
+        })
+        .render(SizeOverLifetimeModifier {
+            gradient: size_gradient,
+            screen_space_size: false,
+        }),
+    )
+}
+
+fn flicker_effect_lights(time: Res<Time>, mut lights: Query<(&FlickeringLight, &mut PointLight)>) {
+    for (flickering, mut light) in lights.iter_mut() {
+        let noise = perlin_noise(
+            time.elapsed_seconds() * flickering.flicker.frequency,
+            flickering.seed,
+        );
+        light.intensity =
+            flickering.intensity * (1. + flickering.flicker.amplitude * noise).max(0.);
+    }
+}
+
+fn attach_effect_command(
+    In((entity_name, effect)): In<(String, String)>,
+    mut commands: Commands,
+    names: EntityNames,
+    position: Res<DialogPosition>,
+) {
+    let context = format!("<<attach_effect>> in {}", *position);
+    if let Some(entity) = names.get_or_report(&entity_name, context) {
+        commands.entity(entity).insert(AttachedEffect {
+            effect,
+            socket: default(),
+        });
+    }
+}
+
+fn attach_effect_to_bone_command(
+    In((entity_name, effect, bone)): In<(String, String, String)>,
+    mut commands: Commands,
+    names: EntityNames,
+    position: Res<DialogPosition>,
+) {
+    let context = format!("<<attach_effect_to_bone>> in {}", *position);
+    if let Some(entity) = names.get_or_report(&entity_name, context) {
+        commands.entity(entity).insert(AttachedEffect {
+            effect,
+            socket: EffectSocket::Bone {
+                name: bone,
+                offset: Vec3::ZERO,
+            },
+        });
+    }
+}
+
+fn detach_effect_command(
+    In(entity_name): In<String>,
+    mut commands: Commands,
+    names: EntityNames,
+    effects: Query<(), With<AttachedEffect>>,
+    position: Res<DialogPosition>,
+) {
+    let context = format!("<<detach_effect>> in {}", *position);
+    let Some(entity) = names.get_or_report(&entity_name, &context) else {
+        return;
+    };
+    if !effects.contains(entity) {
+        warn!("{context}: \"{entity_name}\" has no attached effect");
+        return;
+    }
+    commands.entity(entity).remove::<AttachedEffect>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    #[test]
+    fn effects_wait_for_their_bone_and_leave_with_the_component() {
+        let mut app = TestApp::new();
+        app.add_plugins(plugin);
+        app.world_mut().init_resource::<Assets<EffectAsset>>();
+        let table = EffectTable {
+            effects: HashMap::from([(
+                "flame".to_string(),
+                EffectDefinition {
+                    light: Some(default()),
+                    ..default()
+                },
+            )]),
+        };
+        app.world_mut()
+            .resource_mut::<Assets<EffectTable>>()
+            .add(table);
+        let torch = app
+            .world_mut()
+            .spawn((
+                SpatialBundle::default(),
+                AttachedEffect {
+                    effect: "flame".to_string(),
+                    socket: EffectSocket::Bone {
+                        name: "Tip".to_string(),
+                        offset: Vec3::Y,
+                    },
+                },
+            ))
+            .id();
+        let instances = |app: &mut TestApp| {
+            app.world_mut()
+                .query::<(&EffectOwner, &Parent, &Transform)>()
+                .iter(app.world())
+                .map(|(owner, parent, transform)| (owner.0, parent.get(), transform.translation))
+                .collect::<Vec<_>>()
+        };
+        app.step(3);
+        assert!(instances(&mut app).is_empty());
+
+        let tip = app
+            .world_mut()
+            .spawn((SpatialBundle::default(), Name::new("Tip")))
+            .set_parent(torch)
+            .id();
+        app.step(1);
+        assert_eq!(instances(&mut app), vec![(torch, tip, Vec3::Y)]);
+        let lights = app
+            .world_mut()
+            .query::<&PointLight>()
+            .iter(app.world())
+            .count();
+        assert_eq!(lights, 1);
+
+        app.world_mut().entity_mut(torch).remove::<AttachedEffect>();
+        app.step(1);
+        assert!(instances(&mut app).is_empty());
+        let lights = app
+            .world_mut()
+            .query::<&PointLight>()
+            .iter(app.world())
+            .count();
+        assert_eq!(lights, 0);
+        assert!(app.world().get::<AttachedEffectState>(torch).is_none());
+    }
+}
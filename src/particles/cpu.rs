@@ -0,0 +1,242 @@
+use crate::{
+    determinism::{GameRng, RngStream},
+    file_system_interaction::config::GameConfig,
+    movement::character_controller::{FloatHeight, LandedEvent, LeftGroundEvent},
+    player_control::camera::IngameCamera,
+    util::math_trait_ext::F32Ext,
+    GameState,
+};
+use bevy::{pbr::NotShadowCaster, prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+/// No more than this many CPU particles exist at the same time, scaled by the [`Graphics::particle_budget`](crate::file_system_interaction::config::Graphics::particle_budget).
+/// Bursts beyond that are cut short.
+const MAX_PARTICLES: usize = 256;
+/// Bursts further away from the camera than this, scaled by the [`Graphics::effect_distance`](crate::file_system_interaction::config::Graphics::effect_distance),
+/// are not spawned at all.
+const CULL_DISTANCE: f32 = 40.;
+/// Landings slower than this do not kick up dust.
+const MIN_IMPACT_SPEED: f32 = 2.;
+/// A landing at this speed spawns a burst of [`CpuParticleEmitter::count`] particles.
+const REFERENCE_IMPACT_SPEED: f32 = 8.;
+const MAX_BURST_SCALE: f32 = 2.;
+/// Jumping off kicks up less dust than landing.
+const TAKEOFF_BURST_SCALE: f32 = 0.5;
+
+/// A minimal particle system running on the CPU, used for short bursts where setting up a GPU effect is overkill.
+/// Characters kick up dust at their feet when leaving the ground and when landing,
+/// configured through their [`CpuParticleEmitter`]. Ambient emitters placed in levels spawn their particles here as well.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<CpuParticleEmitter>()
+        .add_event::<ParticleBurst>()
+        .init_resource::<ParticleAssets>()
+        .add_systems(
+            Update,
+            (kick_up_dust, spawn_particle_bursts, update_particles)
+                .chain()
+                .run_if(in_state(GameState::Playing).and_then(resource_exists::<GameConfig>)),
+        );
+}
+
+/// How the particles of a burst look and move. Characters without one use the default dust.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct CpuParticleEmitter {
+    /// Path to the particle's texture. Plain quads are drawn when missing.
+    pub(crate) texture: Option<String>,
+    pub(crate) color: Color,
+    pub(crate) count: u32,
+    /// In seconds.
+    pub(crate) lifetime: f32,
+    pub(crate) size: f32,
+    pub(crate) speed: f32,
+    /// Particles are shot out within this many degrees of straight up.
+    pub(crate) cone_angle: f32,
+    /// Downwards acceleration. Negative values make the particles rise.
+    pub(crate) gravity: f32,
+}
+
+impl Default for CpuParticleEmitter {
+    fn default() -> Self {
+        Self {
+            texture: None,
+            color: Color::rgba(0.6, 0.55, 0.45, 0.5),
+            count: 12,
+            lifetime: 0.6,
+            size: 0.15,
+            speed: 1.5,
+            cone_angle: 80.,
+            gravity: 1.,
+        }
+    }
+}
+
+/// Spawns a burst of particles at `position`. `scale` multiplies the particle count and speed.
+#[derive(Debug, Clone, PartialEq, Event)]
+pub(crate) struct ParticleBurst {
+    pub(crate) position: Vec3,
+    pub(crate) emitter: CpuParticleEmitter,
+    pub(crate) scale: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Component)]
+struct CpuParticle {
+    velocity: Vec3,
+    age: f32,
+    lifetime: f32,
+    gravity: f32,
+    size: f32,
+    alpha: f32,
+    material: Handle<StandardMaterial>,
+}
+
+#[derive(Debug, Default, Resource)]
+struct ParticleAssets {
+    quad: Option<Handle<Mesh>>,
+    textures: HashMap<String, Handle<Image>>,
+}
+
+fn kick_up_dust(
+    mut landed_events: EventReader<LandedEvent>,
+    mut left_ground_events: EventReader<LeftGroundEvent>,
+    characters: Query<(&Transform, &FloatHeight, Option<&CpuParticleEmitter>)>,
+    mut bursts: EventWriter<ParticleBurst>,
+) {
+    let landings = landed_events
+        .read()
+        .filter(|event| event.impact_speed > MIN_IMPACT_SPEED)
+        .map(|event| {
+            let scale = (event.impact_speed / REFERENCE_IMPACT_SPEED).min(MAX_BURST_SCALE);
+            (event.entity, scale)
+        });
+    let takeoffs = left_ground_events
+        .read()
+        .map(|event| (event.entity, TAKEOFF_BURST_SCALE));
+    for (entity, scale) in landings.chain(takeoffs) {
+        let Ok((transform, float_height, emitter)) = characters.get(entity) else {
+            continue;
+        };
+        bursts.send(ParticleBurst {
+            position: transform.translation - Vec3::Y * float_height.0,
+            emitter: emitter.cloned().unwrap_or_default(),
+            scale,
+        });
+    }
+}
+
+pub(super) fn spawn_particle_bursts(
+    mut commands: Commands,
+    mut bursts: EventReader<ParticleBurst>,
+    particles: Query<(), With<CpuParticle>>,
+    cameras: Query<&Transform, With<IngameCamera>>,
+    config: Res<GameConfig>,
+    mut assets: ResMut<ParticleAssets>,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    game_rng: Res<GameRng>,
+    mut rng: Local<Option<RngStream>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("spawn_particle_bursts").entered();
+    let rng = rng.get_or_insert_with(|| game_rng.fork("cpu_particles"));
+    let Some(camera) = cameras.iter().next() else {
+        return;
+    };
+    let settings = config.graphics.resolve();
+    let max_particles = (MAX_PARTICLES as f32 * settings.particle_budget.max(0.)).round() as usize;
+    let cull_distance = CULL_DISTANCE * settings.effect_distance.max(0.);
+    let mut budget = max_particles.saturating_sub(particles.iter().count());
+    let quad = assets
+        .quad
+        .get_or_insert_with(|| meshes.add(Rectangle::new(1., 1.)))
+        .clone();
+    for burst in bursts.read() {
+        let is_visible =
+            burst.position.distance_squared(camera.translation) < cull_distance.squared();
+        if !is_visible || budget == 0 {
+            continue;
+        }
+        let emitter = &burst.emitter;
+        let texture = emitter.texture.as_ref().map(|path| {
+            assets
+                .textures
+                .entry(path.clone())
+                .or_insert_with(|| asset_server.load(path.clone()))
+                .clone()
+        });
+        let count = ((emitter.count as f32 * burst.scale).round() as usize).min(budget);
+        budget -= count;
+        for _ in 0..count {
+            let material = materials.add(StandardMaterial {
+                base_color: emitter.color,
+                base_color_texture: texture.clone(),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            });
+            let velocity = cone_direction(rng, emitter.cone_angle.to_radians())
+                * emitter.speed
+                * burst.scale
+                * rng.range_f32(0.5..1.);
+            commands.spawn((
+                Name::new("CPU particle"),
+                PbrBundle {
+                    mesh: quad.clone(),
+                    material: material.clone(),
+                    transform: Transform::from_translation(burst.position)
+                        .with_scale(Vec3::splat(emitter.size)),
+                    ..default()
+                },
+                NotShadowCaster,
+                CpuParticle {
+                    velocity,
+                    age: 0.,
+                    lifetime: emitter.lifetime * rng.range_f32(0.7..1.),
+                    gravity: emitter.gravity,
+                    size: emitter.size,
+                    alpha: emitter.color.a(),
+                    material,
+                },
+            ));
+        }
+    }
+}
+
+/// A random direction at most `max_angle` radians away from straight up.
+fn cone_direction(rng: &mut RngStream, max_angle: f32) -> Vec3 {
+    let polar = rng.range_f32(0.0..max_angle);
+    let horizontal = rng.horizontal_direction();
+    horizontal * polar.sin() + Vec3::Y * polar.cos()
+}
+
+fn update_particles(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut particles: Query<(Entity, &mut CpuParticle, &mut Transform), Without<IngameCamera>>,
+    cameras: Query<&Transform, With<IngameCamera>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_particles").entered();
+    let dt = time.delta_seconds();
+    let camera = cameras.iter().next().map(|camera| camera.translation);
+    for (entity, mut particle, mut transform) in particles.iter_mut() {
+        particle.age += dt;
+        if particle.age >= particle.lifetime {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        particle.velocity.y -= particle.gravity * dt;
+        transform.translation += particle.velocity * dt;
+        let progress = particle.age / particle.lifetime;
+        transform.scale = Vec3::splat(particle.size * (1. + progress));
+        if let Some(camera) = camera {
+            transform.look_at(camera, Vec3::Y);
+        }
+        if let Some(material) = materials.get_mut(&particle.material) {
+            material.base_color.set_a(particle.alpha * (1. - progress));
+        }
+    }
+}
@@ -68,3 +68,109 @@ fn create_sprinting_effect(effects: &mut Assets<EffectAsset>) -> Handle<EffectAs
         }),
     )
 }
+
+/// Rain falling around the camera. Attach it to the camera and toggle it with the [`EffectSpawner`].
+pub(crate) fn create_rain_particle_bundle(effects: &mut Assets<EffectAsset>) -> impl Bundle {
+    (
+        Name::new("Rain particle"),
+        ParticleEffectBundle::new(create_precipitation_effect(
+            effects,
+            Precipitation {
+                name: "Rain",
+                rate: 800.,
+                lifetime: 1.2,
+                fall_speed: 14.,
+                size: Vec2::new(0.015, 0.4),
+                color: Vec4::new(0.7, 0.75, 0.85, 0.35),
+                orient_mode: OrientMode::AlongVelocity,
+            },
+        )),
+        NotShadowReceiver,
+    )
+}
+
+/// Snow falling around the camera. Attach it to the camera and toggle it with the [`EffectSpawner`].
+pub(crate) fn create_snow_particle_bundle(effects: &mut Assets<EffectAsset>) -> impl Bundle {
+    (
+        Name::new("Snow particle"),
+        ParticleEffectBundle::new(create_precipitation_effect(
+            effects,
+            Precipitation {
+                name: "Snow",
+                rate: 150.,
+                lifetime: 8.,
+                fall_speed: 1.5,
+                size: Vec2::splat(0.06),
+                color: Vec4::new(1.2, 1.2, 1.2, 0.9),
+                orient_mode: OrientMode::FaceCameraPosition,
+            },
+        )),
+        NotShadowReceiver,
+    )
+}
+
+struct Precipitation {
+    name: &'static str,
+    /// Particles per second.
+    rate: f32,
+    lifetime: f32,
+    fall_speed: f32,
+    size: Vec2,
+    color: Vec4,
+    orient_mode: OrientMode,
+}
+
+/// Particles spawn in a disk above the emitter and fall straight down, so that they cover the view around a camera.
+fn create_precipitation_effect(
+    effects: &mut Assets<EffectAsset>,
+    precipitation: Precipitation,
+) -> Handle<EffectAsset> {
+    const RADIUS: f32 = 15.;
+    const HEIGHT: f32 = 8.;
+
+    let mut color_gradient = Gradient::new();
+    let transparent = precipitation.color.truncate().extend(0.0);
+    color_gradient.add_key(0.0, transparent);
+    color_gradient.add_key(0.1, precipitation.color);
+    color_gradient.add_key(0.9, precipitation.color);
+    color_gradient.add_key(1.0, transparent);
+
+    let mut module = Module::default();
+    let position_circle_modifier = SetPositionCircleModifier {
+        dimension: ShapeDimension::Volume,
+        radius: module.lit(RADIUS),
+        center: module.lit(Vec3::Y * HEIGHT),
+        axis: module.lit(Vec3::Y),
+    };
+    let velocity = SetAttributeModifier::new(
+        Attribute::VELOCITY,
+        module.lit(Vec3::NEG_Y * precipitation.fall_speed),
+    );
+    let lifetime =
+        SetAttributeModifier::new(Attribute::LIFETIME, module.lit(precipitation.lifetime));
+    let orient_modifier = OrientModifier {
+        mode: precipitation.orient_mode,
+        rotation: None,
+    };
+    let capacity = (precipitation.rate * precipitation.lifetime).ceil() as u32;
+
+    effects.add(
+        EffectAsset::new(
+            capacity,
+            Spawner::rate(precipitation.rate.into()).with_starts_active(false),
+            module,
+        )
+        .with_name(precipitation.name)
+        .init(position_circle_modifier)
+        .init(velocity)
+        .init(lifetime)
+        .render(orient_modifier)
+        .render(ColorOverLifetimeModifier {
+            gradient: color_gradient,
+        })
+        .render(SetSizeModifier {
+            size: precipitation.size.into(),
+            screen_space_size: false,
+        }),
+    )
+}
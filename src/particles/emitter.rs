@@ -0,0 +1,315 @@
+use crate::{
+    determinism::{GameRng, RngStream},
+    level_instantiation::on_spawn::Player,
+    movement::physics::PhysicsSettings,
+    particles::{
+        attached::{EffectParticles, EffectTable},
+        cpu::{spawn_particle_bursts, CpuParticleEmitter, ParticleBurst},
+    },
+    GameState,
+};
+use bevy::{prelude::*, transform::TransformSystem::TransformPropagate, utils::Duration};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+/// An emitter catching up after a long frame spawns no more than this many particles at once.
+const MAX_PARTICLES_PER_FRAME: u32 = 16;
+
+/// Handles [`ParticleEmitter`]s, which keep spawning ambient particles like falling leaves, fireflies or chimney smoke
+/// with the CPU particles of [`cpu::plugin`](super::cpu::plugin). Their particles count towards the same global cap as dust bursts,
+/// so many emitters in view thin each other out instead of adding up.
+/// Emitters further from the player than the [`PhysicsSettings::activation_range`] go dormant, just like props fall asleep there.
+/// Emission follows [`Time`], so it slows down with the game's time scale and stops while paused.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<ParticleEmitter>()
+        .register_type::<EmitterShape>()
+        .init_resource::<EmitterActivationTimer>()
+        .add_systems(
+            Update,
+            (init_emitters, emit_ambient_particles)
+                .chain()
+                .before(spawn_particle_bursts)
+                .run_if(in_state(GameState::Playing)),
+        )
+        // Needs the propagated transforms of freshly spawned emitters
+        .add_systems(
+            PostUpdate,
+            update_dormant_emitters
+                .after(TransformPropagate)
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Keeps spawning the particles of an effect in the effect table at `assets/config/config.effects.ron`, within the `shape`
+/// around the entity. The effect's `speed` shoots the particles in random directions and the vertical part of its `acceleration`
+/// lets them fall or rise.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct ParticleEmitter {
+    pub(crate) effect: String,
+    pub(crate) shape: EmitterShape,
+    /// Particles per second. `None` uses the rate of the effect.
+    pub(crate) rate: Option<f32>,
+}
+
+impl Default for ParticleEmitter {
+    fn default() -> Self {
+        Self {
+            effect: "dust_motes".to_string(),
+            shape: default(),
+            rate: None,
+        }
+    }
+}
+
+/// Where the particles of a [`ParticleEmitter`] spawn, relative to the entity's transform.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize, Default)]
+pub(crate) enum EmitterShape {
+    Point,
+    /// Anywhere inside the box, e.g. leaves below a tree's crown.
+    Box {
+        half_extents: Vec3,
+    },
+    /// On the surface of the sphere, e.g. fireflies around a lantern.
+    SphereSurface {
+        radius: f32,
+    },
+}
+
+impl Default for EmitterShape {
+    fn default() -> Self {
+        Self::Box {
+            half_extents: Vec3::ONE,
+        }
+    }
+}
+
+impl EmitterShape {
+    fn sample(self, rng: &mut RngStream) -> Vec3 {
+        match self {
+            Self::Point => Vec3::ZERO,
+            Self::Box { half_extents } => {
+                let mut coordinate = |half_extent: f32| {
+                    let half_extent = half_extent.abs();
+                    rng.range_f32(-half_extent..half_extent + f32::EPSILON)
+                };
+                Vec3::new(
+                    coordinate(half_extents.x),
+                    coordinate(half_extents.y),
+                    coordinate(half_extents.z),
+                )
+            }
+            Self::SphereSurface { radius } => random_direction(rng) * radius,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Component, Default)]
+struct EmitterState {
+    /// Particles owed from previous frames, since the rate rarely divides into whole particles per frame.
+    pending: f32,
+    dormant: bool,
+    /// Whether the missing effect was already reported.
+    reported_missing: bool,
+}
+
+#[derive(Debug, Resource, Default)]
+struct EmitterActivationTimer(Timer);
+
+fn init_emitters(
+    mut commands: Commands,
+    emitters: Query<Entity, (With<ParticleEmitter>, Without<EmitterState>)>,
+) {
+    for entity in emitters.iter() {
+        // Until its distance to the player was checked
+        commands.entity(entity).insert(EmitterState {
+            dormant: true,
+            ..default()
+        });
+    }
+}
+
+fn update_dormant_emitters(
+    time: Res<Time>,
+    settings: Res<PhysicsSettings>,
+    mut timer: ResMut<EmitterActivationTimer>,
+    players: Query<&GlobalTransform, With<Player>>,
+    mut emitters: Query<(&GlobalTransform, &mut EmitterState)>,
+) {
+    let interval = Duration::from_secs_f32(settings.activation_interval.max(0.01));
+    if timer.0.duration() != interval {
+        timer.0 = Timer::new(interval, TimerMode::Repeating);
+    }
+    let check_all = timer.0.tick(time.delta()).just_finished();
+    let Some(player) = players.iter().next().map(GlobalTransform::translation) else {
+        return;
+    };
+    let wake_range = (settings.activation_range - settings.activation_hysteresis).max(0.);
+    for (transform, mut state) in emitters.iter_mut() {
+        // New emitters are sorted right away, so that distant ones never emit
+        if !check_all && !state.is_added() {
+            continue;
+        }
+        let distance = transform.translation().distance(player);
+        if state.dormant && distance < wake_range {
+            state.dormant = false;
+        } else if !state.dormant && distance > settings.activation_range {
+            state.dormant = true;
+            state.pending = 0.;
+        }
+    }
+}
+
+fn emit_ambient_particles(
+    time: Res<Time>,
+    tables: Res<Assets<EffectTable>>,
+    mut emitters: Query<(
+        Entity,
+        &ParticleEmitter,
+        &mut EmitterState,
+        &GlobalTransform,
+    )>,
+    mut bursts: EventWriter<ParticleBurst>,
+    game_rng: Res<GameRng>,
+    mut rng: Local<Option<RngStream>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("emit_ambient_particles").entered();
+    let Some(table) = tables.iter().next().map(|(_, table)| table) else {
+        return;
+    };
+    let rng = rng.get_or_insert_with(|| game_rng.fork("particle_emitters"));
+    let dt = time.delta_seconds();
+    for (entity, emitter, mut state, transform) in emitters.iter_mut() {
+        if state.dormant {
+            continue;
+        }
+        let Some(particles) = table
+            .effects
+            .get(&emitter.effect)
+            .and_then(|definition| definition.particles.as_ref())
+        else {
+            if !state.reported_missing {
+                error!(
+                    "{entity:?} wants to emit the particles of \"{}\", which has no particles in the effect table",
+                    emitter.effect
+                );
+                state.reported_missing = true;
+            }
+            continue;
+        };
+        state.reported_missing = false;
+        let rate = emitter.rate.unwrap_or(particles.rate).max(0.);
+        state.pending += rate * dt;
+        let count = (state.pending.floor() as u32).min(MAX_PARTICLES_PER_FRAME);
+        state.pending = state.pending.fract();
+        if count == 0 {
+            continue;
+        }
+        let cpu_emitter = cpu_emitter(particles);
+        for _ in 0..count {
+            let position = transform.transform_point(emitter.shape.sample(rng));
+            bursts.send(ParticleBurst {
+                position,
+                emitter: cpu_emitter.clone(),
+                scale: 1.,
+            });
+        }
+    }
+}
+
+/// A single CPU particle that looks like the particles of an effect.
+fn cpu_emitter(particles: &EffectParticles) -> CpuParticleEmitter {
+    CpuParticleEmitter {
+        texture: None,
+        color: particles.color,
+        count: 1,
+        lifetime: particles.lifetime,
+        size: particles.size,
+        speed: particles.speed,
+        cone_angle: 180.,
+        gravity: -particles.acceleration.y,
+    }
+}
+
+/// A random direction, evenly spread over the sphere.
+fn random_direction(rng: &mut RngStream) -> Vec3 {
+    let height = rng.range_f32(-1.0..1.);
+    let angle = rng.range_f32(0.0..TAU);
+    let horizontal = (1. - height * height).max(0.).sqrt();
+    Vec3::new(horizontal * angle.cos(), height, horizontal * angle.sin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{particles::attached::EffectDefinition, testing::TestApp};
+    use bevy::utils::HashMap;
+
+    #[test]
+    fn emitters_stop_when_far_from_the_player_and_when_paused() {
+        let mut app = TestApp::new();
+        app.add_plugins(plugin)
+            .init_asset::<EffectTable>()
+            .insert_resource(GameRng::new(0))
+            .add_event::<ParticleBurst>()
+            .record_events::<ParticleBurst>();
+        let table = EffectTable {
+            effects: HashMap::from([(
+                "fireflies".to_string(),
+                EffectDefinition {
+                    particles: Some(EffectParticles {
+                        rate: 30.,
+                        ..default()
+                    }),
+                    ..default()
+                },
+            )]),
+        };
+        app.world_mut()
+            .resource_mut::<Assets<EffectTable>>()
+            .add(table);
+        app.spawn_player(Vec3::ZERO);
+        let emitter = ParticleEmitter {
+            effect: "fireflies".to_string(),
+            shape: EmitterShape::SphereSurface { radius: 2. },
+            rate: None,
+        };
+        app.world_mut().spawn((
+            emitter.clone(),
+            TransformBundle::from_transform(Transform::from_xyz(0., 1., -5.)),
+        ));
+        let range = PhysicsSettings::default().activation_range;
+        app.world_mut().spawn((
+            emitter,
+            TransformBundle::from_transform(Transform::from_xyz(0., 1., range + 10.)),
+        ));
+
+        // Half a second at 30 particles per second
+        app.step(31);
+        let positions: Vec<_> = app
+            .events::<ParticleBurst>()
+            .iter()
+            .map(|burst| burst.position)
+            .collect();
+        assert!(
+            (14..=16).contains(&positions.len()),
+            "Expected about 15 particles, got {}",
+            positions.len()
+        );
+        for position in positions {
+            let distance = position.distance(Vec3::new(0., 1., -5.));
+            assert!(
+                (distance - 2.).abs() < 1e-3,
+                "A particle of the near emitter spawned off its sphere at {position}"
+            );
+        }
+
+        app.clear_events::<ParticleBurst>();
+        app.world_mut().resource_mut::<Time<Virtual>>().pause();
+        app.step(30);
+        assert!(app.events::<ParticleBurst>().is_empty());
+    }
+}
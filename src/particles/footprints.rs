@@ -0,0 +1,186 @@
+use crate::{
+    file_system_interaction::config::GameConfig, level_instantiation::on_spawn::GroundSurface,
+    movement::character_controller::FootstepEvent, GameState,
+};
+use bevy::{pbr::NotShadowCaster, prelude::*};
+use std::collections::VecDeque;
+
+const FOOTPRINT_SIZE: Vec2 = Vec2::new(0.12, 0.26);
+/// How far each foot is from the center of the character.
+const FOOT_SPACING: f32 = 0.1;
+/// Lifts footprints off the ground. Together with [`DEPTH_BIAS`], this keeps them from z-fighting with it.
+const GROUND_OFFSET: f32 = 0.005;
+const DEPTH_BIAS: f32 = 8.;
+/// Footprints fade out during the last this many seconds of their lifetime.
+const FADE_DURATION: f32 = 3.;
+
+/// Leaves footprints where characters step on soft [`GroundSurface`]s.
+/// They are pooled: once the configured maximum, scaled by the graphics preset's particle budget, is reached, the oldest footprint is moved to the new position.
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<FootprintPool>().add_systems(
+        Update,
+        (leave_footprints, fade_footprints)
+            .chain()
+            .run_if(in_state(GameState::Playing).and_then(resource_exists::<GameConfig>)),
+    );
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct Footprint {
+    age: f32,
+    opacity: f32,
+}
+
+/// All footprints from oldest to newest.
+#[derive(Debug, Default, Resource)]
+struct FootprintPool {
+    footprints: VecDeque<Entity>,
+    mesh: Option<Handle<Mesh>>,
+}
+
+impl GroundSurface {
+    fn footprint_color(self) -> Color {
+        match self {
+            GroundSurface::Hard => Color::NONE,
+            GroundSurface::Sand => Color::rgba(0.45, 0.36, 0.24, 0.6),
+            GroundSurface::Snow => Color::rgba(0.62, 0.68, 0.78, 0.7),
+            GroundSurface::Mud => Color::rgba(0.18, 0.12, 0.07, 0.75),
+        }
+    }
+}
+
+fn leave_footprints(
+    mut commands: Commands,
+    mut footstep_events: EventReader<FootstepEvent>,
+    config: Res<GameConfig>,
+    surfaces: Query<&GroundSurface>,
+    parents: Query<&Parent>,
+    mut pool: ResMut<FootprintPool>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut footprints: Query<(
+        &mut Footprint,
+        &mut Transform,
+        &mut Visibility,
+        &Handle<StandardMaterial>,
+    )>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("leave_footprints").entered();
+    let settings = &config.footprints;
+    let max_count = if settings.enabled {
+        let budget = config.graphics.resolve().particle_budget.max(0.);
+        (settings.max_count as f32 * budget).round() as usize
+    } else {
+        0
+    };
+    // The cap can be lowered at runtime
+    while pool.footprints.len() > max_count {
+        if let Some(entity) = pool.footprints.pop_front() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    if max_count == 0 {
+        footstep_events.clear();
+        return;
+    }
+
+    for event in footstep_events.read() {
+        let Some(surface) = std::iter::once(event.ground)
+            .chain(parents.iter_ancestors(event.ground))
+            .find_map(|entity| surfaces.get(entity).ok())
+            .filter(|surface| surface.is_soft())
+        else {
+            continue;
+        };
+        let forward = event.direction - event.normal * event.direction.dot(event.normal);
+        let Some(forward) = forward.try_normalize() else {
+            continue;
+        };
+        let right = forward.cross(event.normal);
+        let side = if event.left_foot { -1. } else { 1. };
+        let position = event.position + right * side * FOOT_SPACING + event.normal * GROUND_OFFSET;
+        let transform = Transform::from_translation(position).looking_to(forward, event.normal);
+        let color = surface.footprint_color();
+        let footprint = Footprint {
+            age: 0.,
+            opacity: color.a(),
+        };
+
+        if pool.footprints.len() >= max_count {
+            let Some(entity) = pool.footprints.pop_front() else {
+                continue;
+            };
+            if let Ok((mut old_footprint, mut old_transform, mut visibility, material)) =
+                footprints.get_mut(entity)
+            {
+                *old_footprint = footprint;
+                *old_transform = transform;
+                *visibility = Visibility::Visible;
+                if let Some(material) = materials.get_mut(material) {
+                    material.base_color = color;
+                }
+            }
+            pool.footprints.push_back(entity);
+            continue;
+        }
+
+        let mesh = pool
+            .mesh
+            .get_or_insert_with(|| {
+                meshes.add(
+                    Plane3d::default()
+                        .mesh()
+                        .size(FOOTPRINT_SIZE.x, FOOTPRINT_SIZE.y),
+                )
+            })
+            .clone();
+        let material = materials.add(StandardMaterial {
+            base_color: color,
+            alpha_mode: AlphaMode::Blend,
+            perceptual_roughness: 1.,
+            depth_bias: DEPTH_BIAS,
+            ..default()
+        });
+        let entity = commands
+            .spawn((
+                Name::new("Footprint"),
+                PbrBundle {
+                    mesh,
+                    material,
+                    transform,
+                    ..default()
+                },
+                NotShadowCaster,
+                footprint,
+            ))
+            .id();
+        pool.footprints.push_back(entity);
+    }
+}
+
+fn fade_footprints(
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    mut footprints: Query<(&mut Footprint, &mut Visibility, &Handle<StandardMaterial>)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let lifetime = config.footprints.lifetime;
+    for (mut footprint, mut visibility, material) in footprints.iter_mut() {
+        if *visibility == Visibility::Hidden {
+            continue;
+        }
+        footprint.age += time.delta_seconds();
+        let remaining = lifetime - footprint.age;
+        if remaining <= 0. {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        if remaining < FADE_DURATION {
+            if let Some(material) = materials.get_mut(material) {
+                let fade = remaining / FADE_DURATION;
+                material.base_color.set_a(footprint.opacity * fade);
+            }
+        }
+    }
+}
@@ -2,13 +2,43 @@ use bevy::prelude::*;
 
 pub(crate) mod actions;
 pub(crate) mod camera;
+pub(crate) mod emote_wheel;
+mod modifier_icon;
+#[cfg(feature = "dev")]
+mod noclip;
 pub(crate) mod player_embodiment;
+pub(crate) mod rumble;
+pub(crate) mod screen_effects;
+mod stamina_bar;
+pub(crate) mod ui_layer;
+pub(crate) mod virtual_cursor;
 
 /// Handles systems exclusive to the player's control. Is split into the following sub-plugins:
 /// - [`actions::plugin`]: Handles player input such as mouse and keyboard and neatly packs it into a [`leafwing_input_manager::Actionlike`].
 /// - [`camera::plugin`]: Handles camera movement.
+/// - [`emote_wheel::plugin`]: Handles the radial menu for playing emotes.
+/// - `modifier_icon::plugin`: Shows an icon while zones or the level change how the player moves.
+/// - `noclip::plugin`: Lets the player fly through walls. Only in dev builds.
 /// - [`player_embodiment::plugin`]: Tells the components from [`super::movement::plugin`] about the desired [`actions::PlayerAction`]s.
 /// Also handles other systems that change how the player is physically represented in the world.
+/// - [`rumble::plugin`]: Rumbles the gamepad for landings, interactions and damage.
+/// - [`screen_effects::plugin`]: Fades the screen and slides in letterbox bars for transitions and cutscenes.
+/// - `stamina_bar::plugin`: Shows the player's stamina while it is not full.
+/// - [`ui_layer::plugin`]: Decides which UI is drawn on top and receives input.
+/// - [`virtual_cursor::plugin`]: Lets gamepads point at and click egui widgets in menus.
 pub(super) fn plugin(app: &mut App) {
-    app.add_plugins((actions::plugin, camera::plugin, player_embodiment::plugin));
+    app.add_plugins((
+        actions::plugin,
+        camera::plugin,
+        emote_wheel::plugin,
+        modifier_icon::plugin,
+        player_embodiment::plugin,
+        rumble::plugin,
+        screen_effects::plugin,
+        stamina_bar::plugin,
+        ui_layer::plugin,
+        virtual_cursor::plugin,
+    ));
+    #[cfg(feature = "dev")]
+    app.add_plugins(noclip::plugin);
 }
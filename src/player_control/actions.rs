@@ -1,13 +1,24 @@
 use crate::util::criteria::is_frozen;
-use bevy::prelude::*;
+use bevy::{
+    input::{gamepad::GamepadEvent, keyboard::KeyboardInput, mouse::MouseButtonInput},
+    prelude::*,
+    utils::HashMap,
+};
 use leafwing_input_manager::plugin::InputManagerSystem;
 use leafwing_input_manager::{axislike::DualAxisData, prelude::*};
 use serde::{Deserialize, Serialize};
 
+pub(crate) mod glyphs;
+pub(crate) mod ui_queue;
+
+pub(crate) use ui_queue::{UiActionQueueSystemSet, UiActions};
+
 #[derive(Resource, Default, Reflect, Serialize, Deserialize)]
 #[reflect(Resource, Serialize, Deserialize)]
 pub(crate) struct ActionsFrozen {
     freeze_count: usize,
+    /// Freezes of a single local player, e.g. the one in a dialog, so that the other players can keep playing.
+    player_freeze_counts: HashMap<Entity, usize>,
 }
 impl ActionsFrozen {
     pub(crate) fn freeze(&mut self) {
@@ -16,9 +27,32 @@ impl ActionsFrozen {
     pub(crate) fn unfreeze(&mut self) {
         self.freeze_count -= 1;
     }
+    pub(crate) fn freeze_player(&mut self, player: Entity) {
+        *self.player_freeze_counts.entry(player).or_default() += 1;
+    }
+    pub(crate) fn unfreeze_player(&mut self, player: Entity) {
+        if let Some(count) = self.player_freeze_counts.get_mut(&player) {
+            *count -= 1;
+            if *count == 0 {
+                self.player_freeze_counts.remove(&player);
+            }
+        }
+    }
+    /// Whether the actions of any player are frozen.
     pub(crate) fn is_frozen(&self) -> bool {
+        self.is_globally_frozen() || !self.player_freeze_counts.is_empty()
+    }
+    /// Whether the actions of all players are frozen, e.g. by a menu.
+    pub(crate) fn is_globally_frozen(&self) -> bool {
         self.freeze_count > 0
     }
+    pub(crate) fn is_player_frozen(&self, player: Entity) -> bool {
+        self.is_globally_frozen() || self.player_freeze_counts.contains_key(&player)
+    }
+    /// Whether the actions of all of `players` are frozen, e.g. for the shared camera while the only player is in a dialog.
+    pub(crate) fn are_all_players_frozen(&self, mut players: impl Iterator<Item = Entity>) -> bool {
+        self.is_globally_frozen() || players.all(|player| self.is_player_frozen(player))
+    }
 }
 
 /// Configures [`Actionlike`]s, the components that hold all player input.
@@ -27,40 +61,73 @@ pub(super) fn plugin(app: &mut App) {
         .register_type::<CameraAction>()
         .register_type::<UiAction>()
         .register_type::<ActionsFrozen>()
+        .register_type::<LastInputDevice>()
         .init_resource::<ActionsFrozen>()
+        .init_resource::<LastInputDevice>()
         .add_plugins((
             InputManagerPlugin::<PlayerAction>::default(),
             InputManagerPlugin::<CameraAction>::default(),
             InputManagerPlugin::<UiAction>::default(),
+            glyphs::plugin,
+            ui_queue::plugin,
         ))
         .add_systems(
             Update,
             remove_actions_when_frozen
                 .run_if(is_frozen)
                 .in_set(InputManagerSystem::ManualControl),
-        );
+        )
+        .add_systems(PreUpdate, track_last_input_device);
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Actionlike, Reflect, Default)]
+/// The kind of device the player used last. Used to show the right button prompts.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) enum LastInputDevice {
+    #[default]
+    KeyboardMouse,
+    Gamepad,
+}
+
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Hash, Actionlike, Reflect, Serialize, Deserialize, Default,
+)]
 pub(crate) enum PlayerAction {
     #[default]
     Move,
     Sprint,
+    /// Walks instead of running while held. Only needed on keyboards, where moving is always at full tilt.
+    Walk,
     Jump,
     Interact,
+    Emote,
+    PlaceWaypoint,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Actionlike, Reflect, Default)]
+#[derive(
+    Debug, Clone, Eq, PartialEq, Hash, Actionlike, Reflect, Serialize, Deserialize, Default,
+)]
 pub(crate) enum CameraAction {
     #[default]
     Orbit,
     Zoom,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Actionlike, Reflect, Default)]
+/// Navigation of menus, dialogs and other UI. Read them through [`UiActions`] instead of their [`ActionState`],
+/// so that a press handled by one piece of UI is not seen by other UI or by gameplay.
+#[derive(
+    Debug, Clone, Eq, PartialEq, Hash, Actionlike, Reflect, Serialize, Deserialize, Default,
+)]
 pub(crate) enum UiAction {
     #[default]
     TogglePause,
+    Confirm,
+    Cancel,
+    Up,
+    Down,
+    Left,
+    Right,
+    Tab,
 }
 
 pub(crate) fn create_player_action_input_manager_bundle() -> InputManagerBundle<PlayerAction> {
@@ -68,9 +135,20 @@ pub(crate) fn create_player_action_input_manager_bundle() -> InputManagerBundle<
         input_map: InputMap::new([
             (PlayerAction::Jump, KeyCode::Space),
             (PlayerAction::Sprint, KeyCode::ShiftLeft),
+            (PlayerAction::Walk, KeyCode::AltLeft),
             (PlayerAction::Interact, KeyCode::KeyE),
+            (PlayerAction::Emote, KeyCode::KeyQ),
+            (PlayerAction::PlaceWaypoint, KeyCode::KeyT),
+        ])
+        .insert_multiple([
+            (PlayerAction::Jump, GamepadButtonType::South),
+            (PlayerAction::Sprint, GamepadButtonType::LeftTrigger2),
+            (PlayerAction::Interact, GamepadButtonType::West),
+            (PlayerAction::Emote, GamepadButtonType::LeftTrigger),
+            (PlayerAction::PlaceWaypoint, GamepadButtonType::DPadUp),
         ])
         .insert(PlayerAction::Move, VirtualDPad::wasd())
+        .insert(PlayerAction::Move, DualAxis::left_stick())
         .build(),
         ..default()
     }
@@ -88,13 +166,46 @@ pub(crate) fn create_camera_action_input_manager_bundle() -> InputManagerBundle<
 
 pub(crate) fn create_ui_action_input_manager_bundle() -> InputManagerBundle<UiAction> {
     InputManagerBundle {
-        input_map: InputMap::new([(UiAction::TogglePause, KeyCode::Escape)]),
+        input_map: InputMap::new([
+            (UiAction::TogglePause, KeyCode::Escape),
+            (UiAction::Confirm, KeyCode::Enter),
+            (UiAction::Confirm, KeyCode::Space),
+            (UiAction::Confirm, KeyCode::KeyE),
+            (UiAction::Cancel, KeyCode::Backspace),
+            (UiAction::Up, KeyCode::ArrowUp),
+            (UiAction::Up, KeyCode::KeyW),
+            (UiAction::Down, KeyCode::ArrowDown),
+            (UiAction::Down, KeyCode::KeyS),
+            (UiAction::Left, KeyCode::ArrowLeft),
+            (UiAction::Left, KeyCode::KeyA),
+            (UiAction::Right, KeyCode::ArrowRight),
+            (UiAction::Right, KeyCode::KeyD),
+            (UiAction::Tab, KeyCode::Tab),
+        ])
+        .insert_multiple([
+            (UiAction::TogglePause, GamepadButtonType::Start),
+            (UiAction::Confirm, GamepadButtonType::South),
+            (UiAction::Confirm, GamepadButtonType::West),
+            (UiAction::Cancel, GamepadButtonType::East),
+            (UiAction::Up, GamepadButtonType::DPadUp),
+            (UiAction::Down, GamepadButtonType::DPadDown),
+            (UiAction::Left, GamepadButtonType::DPadLeft),
+            (UiAction::Right, GamepadButtonType::DPadRight),
+            (UiAction::Tab, GamepadButtonType::Select),
+        ])
+        .build(),
         ..default()
     }
 }
 
-fn remove_actions_when_frozen(mut player_actions_query: Query<&mut ActionState<PlayerAction>>) {
-    for mut player_actions in player_actions_query.iter_mut() {
+fn remove_actions_when_frozen(
+    actions_frozen: Res<ActionsFrozen>,
+    mut player_actions_query: Query<(Entity, &mut ActionState<PlayerAction>)>,
+) {
+    for (player, mut player_actions) in player_actions_query.iter_mut() {
+        if !actions_frozen.is_player_frozen(player) {
+            continue;
+        }
         player_actions
             .action_data_mut_or_default(&PlayerAction::Move)
             .axis_pair = Some(default());
@@ -102,6 +213,33 @@ fn remove_actions_when_frozen(mut player_actions_query: Query<&mut ActionState<P
     }
 }
 
+fn track_last_input_device(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut mouse_events: EventReader<MouseButtonInput>,
+    mut cursor_events: EventReader<CursorMoved>,
+    mut gamepad_events: EventReader<GamepadEvent>,
+    mut last_input_device: ResMut<LastInputDevice>,
+) {
+    let used_gamepad = gamepad_events.read().any(|event| match event {
+        GamepadEvent::Button(event) => event.value > 0.5,
+        GamepadEvent::Axis(event) => event.value.abs() > 0.5,
+        GamepadEvent::Connection(_) => false,
+    });
+    let used_keyboard_mouse =
+        keyboard_events.read().count() + mouse_events.read().count() + cursor_events.read().count()
+            > 0;
+    let device = if used_keyboard_mouse {
+        LastInputDevice::KeyboardMouse
+    } else if used_gamepad {
+        LastInputDevice::Gamepad
+    } else {
+        return;
+    };
+    if *last_input_device != device {
+        *last_input_device = device;
+    }
+}
+
 pub(crate) trait DualAxisDataExt {
     fn max_normalized(self) -> Option<Vec2>;
 }
@@ -0,0 +1,164 @@
+use crate::player_control::actions::LastInputDevice;
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use bevy_common_assets::ron::RonAssetPlugin;
+use bevy_egui::{egui, EguiContexts};
+use leafwing_input_manager::{prelude::*, user_input::InputKind};
+use serde::{Deserialize, Serialize};
+
+/// Height of a glyph in the UI, in points.
+const GLYPH_SIZE: f32 = 24.;
+
+/// Loads the [`GlyphAtlas`] that maps inputs to icons, so that prompts can show the actual buttons to press.
+/// See `assets/config/config.glyphs.ron`.
+pub(super) fn plugin(app: &mut App) {
+    app.add_plugins(RonAssetPlugin::<GlyphAtlas>::new(&["glyphs.ron"]))
+        .init_resource::<GlyphTextures>()
+        .add_systems(Update, register_glyph_textures);
+}
+
+#[derive(Debug, Clone, PartialEq, Asset, Reflect, Serialize, Deserialize, Default)]
+pub(crate) struct GlyphAtlas {
+    pub(crate) keyboard: GlyphSet,
+    pub(crate) gamepad: GlyphSet,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+pub(crate) struct GlyphSet {
+    /// Path of the texture containing all glyphs of this set.
+    pub(crate) texture: Option<String>,
+    /// Maps the names of [`KeyCode`]s, [`MouseButton`]s and [`GamepadButtonType`]s, e.g. `"KeyE"` or `"South"`,
+    /// to the region of the texture showing them.
+    pub(crate) glyphs: HashMap<String, GlyphRect>,
+}
+
+/// A region of a glyph texture in UV coordinates, i.e. from `(0, 0)` to `(1, 1)`.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect, Serialize, Deserialize, Default)]
+pub(crate) struct GlyphRect {
+    pub(crate) min: Vec2,
+    pub(crate) max: Vec2,
+}
+
+#[derive(Debug, Resource, Default)]
+struct GlyphTextures {
+    keyboard: Option<(Handle<Image>, egui::TextureId)>,
+    gamepad: Option<(Handle<Image>, egui::TextureId)>,
+}
+
+fn register_glyph_textures(
+    mut atlas_events: EventReader<AssetEvent<GlyphAtlas>>,
+    atlases: Res<Assets<GlyphAtlas>>,
+    asset_server: Res<AssetServer>,
+    mut egui_contexts: EguiContexts,
+    mut textures: ResMut<GlyphTextures>,
+) {
+    for event in atlas_events.read() {
+        let (AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }) = event
+        else {
+            continue;
+        };
+        let Some(atlas) = atlases.get(*id) else {
+            continue;
+        };
+        let mut register = |set: &GlyphSet| {
+            set.texture.as_ref().map(|path| {
+                let image: Handle<Image> = asset_server.load(path.clone());
+                let texture_id = egui_contexts.add_image(image.clone_weak());
+                (image, texture_id)
+            })
+        };
+        textures.keyboard = register(&atlas.keyboard);
+        textures.gamepad = register(&atlas.gamepad);
+    }
+}
+
+/// Draws the inputs bound to an action as glyphs, picking the ones for the [`LastInputDevice`].
+/// Inputs without a glyph are drawn as a rounded rectangle containing the key's name.
+#[derive(SystemParam)]
+pub(crate) struct ActionGlyphs<'w> {
+    atlases: Res<'w, Assets<GlyphAtlas>>,
+    textures: Res<'w, GlyphTextures>,
+    last_input_device: Res<'w, LastInputDevice>,
+}
+
+impl ActionGlyphs<'_> {
+    /// Shows the glyph of the input bound to `action`, followed by `verb`, e.g. "[E] Talk".
+    pub(crate) fn prompt<A: Actionlike>(
+        &self,
+        ui: &mut egui::Ui,
+        input_map: &InputMap<A>,
+        action: &A,
+        verb: &str,
+    ) {
+        ui.horizontal(|ui| {
+            if let Some(input) = self.find_input(input_map, action) {
+                self.glyph(ui, input);
+            }
+            ui.label(verb);
+        });
+    }
+
+    fn find_input<A: Actionlike>(&self, input_map: &InputMap<A>, action: &A) -> Option<InputKind> {
+        let wants_gamepad = *self.last_input_device == LastInputDevice::Gamepad;
+        let inputs = input_map.get(action)?;
+        let single_inputs = || {
+            inputs.iter().filter_map(|input| match input {
+                UserInput::Single(kind) => Some(*kind),
+                _ => None,
+            })
+        };
+        single_inputs()
+            .find(|kind| matches!(kind, InputKind::GamepadButton(_)) == wants_gamepad)
+            .or_else(|| single_inputs().next())
+    }
+
+    fn glyph(&self, ui: &mut egui::Ui, input: InputKind) {
+        let (is_gamepad, name) = match input {
+            InputKind::GamepadButton(button) => (true, format!("{button:?}")),
+            InputKind::PhysicalKey(key) => (false, format!("{key:?}")),
+            InputKind::Mouse(button) => (false, format!("Mouse{button:?}")),
+            other => (false, format!("{other:?}")),
+        };
+        let atlas = self.atlases.iter().next().map(|(_, atlas)| atlas);
+        let (set, texture) = if is_gamepad {
+            (atlas.map(|atlas| &atlas.gamepad), &self.textures.gamepad)
+        } else {
+            (atlas.map(|atlas| &atlas.keyboard), &self.textures.keyboard)
+        };
+        let glyph = set.and_then(|set| set.glyphs.get(&name));
+        if let (Some(glyph), Some((_, texture_id))) = (glyph, texture) {
+            let uv = egui::Rect::from_min_max(
+                egui::pos2(glyph.min.x, glyph.min.y),
+                egui::pos2(glyph.max.x, glyph.max.y),
+            );
+            let aspect_ratio = (glyph.max.x - glyph.min.x) / (glyph.max.y - glyph.min.y).max(1e-5);
+            let size = egui::vec2(GLYPH_SIZE * aspect_ratio, GLYPH_SIZE);
+            ui.add(egui::Image::new(egui::load::SizedTexture::new(*texture_id, size)).uv(uv));
+        } else {
+            fallback_glyph(ui, &display_name(&name));
+        }
+    }
+}
+
+fn fallback_glyph(ui: &mut egui::Ui, text: &str) {
+    let font = egui::FontId::proportional(GLYPH_SIZE * 0.6);
+    let color = ui.visuals().text_color();
+    let galley = ui.painter().layout_no_wrap(text.to_string(), font, color);
+    let width = (galley.size().x + GLYPH_SIZE * 0.5).max(GLYPH_SIZE);
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(width, GLYPH_SIZE), egui::Sense::hover());
+    let painter = ui.painter();
+    painter.rect(
+        rect,
+        GLYPH_SIZE * 0.25,
+        ui.visuals().extreme_bg_color,
+        egui::Stroke::new(1.5, color),
+    );
+    painter.galley(rect.center() - galley.size() / 2., galley, color);
+}
+
+/// Turns e.g. `"KeyE"` into `"E"` and `"Digit1"` into `"1"`.
+fn display_name(name: &str) -> String {
+    name.strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"))
+        .unwrap_or(name)
+        .to_string()
+}
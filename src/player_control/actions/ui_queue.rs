@@ -0,0 +1,89 @@
+use crate::player_control::{
+    actions::{PlayerAction, UiAction},
+    ui_layer::{UiLayer, UiLayers},
+};
+use bevy::{ecs::system::SystemParam, prelude::*};
+use leafwing_input_manager::{plugin::InputManagerSystem, prelude::*};
+
+/// Public to the crate so that headless tests of UI can fill the queue without the input plugins.
+pub(crate) fn plugin(app: &mut App) {
+    app.init_resource::<UiActionQueue>().add_systems(
+        PreUpdate,
+        fill_ui_action_queue
+            .after(InputManagerSystem::Update)
+            .in_set(UiActionQueueSystemSet),
+    );
+}
+
+/// Fills the queue read by [`UiActions`]. Consuming actions before UI does must happen after it.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
+pub(crate) struct UiActionQueueSystemSet;
+
+/// The [`UiAction`]s pressed this frame that no UI has handled yet.
+#[derive(Debug, Default, Resource)]
+struct UiActionQueue {
+    pending: Vec<UiAction>,
+}
+
+/// Reads [`UiAction`]s with explicit consumption. Once a piece of UI consumed an action, nothing else sees it this frame.
+/// UI below the topmost open modal [`UiLayer`] does not see any actions.
+/// Gameplay [`PlayerAction`]s bound to the same input are consumed as well, so that e.g. closing a note with E
+/// does not immediately interact with whatever is in front of the player.
+#[derive(SystemParam)]
+pub(crate) struct UiActions<'w, 's> {
+    queue: ResMut<'w, UiActionQueue>,
+    layers: Res<'w, UiLayers>,
+    players: Query<
+        'w,
+        's,
+        (
+            &'static InputMap<UiAction>,
+            &'static InputMap<PlayerAction>,
+            &'static mut ActionState<PlayerAction>,
+        ),
+    >,
+}
+
+impl UiActions<'_, '_> {
+    /// Whether `action` was pressed this frame and not consumed yet, as seen by UI on `layer`. Does not consume it.
+    pub(crate) fn peek(&self, layer: UiLayer, action: UiAction) -> bool {
+        self.layers.receives_input(layer) && self.queue.pending.contains(&action)
+    }
+
+    /// Returns whether `action` was pressed this frame and not consumed yet, as seen by UI on `layer`, and consumes it.
+    pub(crate) fn consume(&mut self, layer: UiLayer, action: UiAction) -> bool {
+        if !self.layers.receives_input(layer) {
+            return false;
+        }
+        let Some(index) = self
+            .queue
+            .pending
+            .iter()
+            .position(|&pending| pending == action)
+        else {
+            return false;
+        };
+        self.queue.pending.remove(index);
+        for (ui_map, player_map, mut player_actions) in self.players.iter_mut() {
+            let Some(ui_inputs) = ui_map.get(&action) else {
+                continue;
+            };
+            let shared_actions: Vec<_> = player_map
+                .iter()
+                .filter(|(_, inputs)| inputs.iter().any(|input| ui_inputs.contains(input)))
+                .map(|(player_action, _)| *player_action)
+                .collect();
+            for player_action in shared_actions {
+                player_actions.consume(&player_action);
+            }
+        }
+        true
+    }
+}
+
+fn fill_ui_action_queue(mut queue: ResMut<UiActionQueue>, actions: Query<&ActionState<UiAction>>) {
+    queue.pending.clear();
+    for actions in actions.iter() {
+        queue.pending.extend(actions.get_just_pressed());
+    }
+}
@@ -2,25 +2,29 @@ use crate::level_instantiation::on_spawn::Player;
 use crate::{
     player_control::camera::{
         cursor::grab_cursor,
+        fade::fade_player_near_camera,
         focus::set_camera_focus,
         kind::{update_drivers, update_kind},
-        rig::update_rig,
+        occlusion::{cut_to_camera_volumes, fade_occluders},
+        rig::{update_rig, update_rig_transform},
     },
     GameState,
 };
 use bevy::prelude::*;
 use bevy::transform::TransformSystem;
 use bevy_atmosphere::prelude::AtmospherePlugin;
-use bevy_dolly::prelude::Dolly;
 use bevy_xpbd_3d::PhysicsSet;
 use bevy_yarnspinner_example_dialogue_view::ExampleYarnSpinnerDialogueViewSystemSet;
-pub(crate) use cursor::ForceCursorGrabMode;
+pub(crate) use cursor::CursorGrabRequests;
+pub(crate) use occlusion::{CameraFadeable, CameraVolume};
 use serde::{Deserialize, Serialize};
 use ui::*;
 
 mod cursor;
+mod fade;
 mod focus;
 mod kind;
+mod occlusion;
 mod rig;
 mod ui;
 
@@ -31,6 +35,10 @@ pub(crate) struct IngameCamera {
     pub(crate) secondary_target: Option<Vec3>,
     pub(crate) desired_distance: f32,
     pub(crate) kind: IngameCameraKind,
+    /// Limits where the player can look, e.g. while sitting.
+    pub(crate) constraints: Option<CameraConstraints>,
+    /// Turning towards this horizontal direction is slowed down by the given fraction, e.g. near the level bounds.
+    pub(crate) resisted_direction: Option<(Vec3, f32)>,
 }
 
 impl Default for IngameCamera {
@@ -40,10 +48,31 @@ impl Default for IngameCamera {
             target: default(),
             secondary_target: default(),
             kind: default(),
+            constraints: default(),
+            resisted_direction: default(),
         }
     }
 }
 
+/// All angles are in degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) struct CameraConstraints {
+    /// The yaw the camera may turn away from.
+    pub(crate) yaw_center: f32,
+    /// How far the camera may turn away from [`CameraConstraints::yaw_center`] in either direction.
+    pub(crate) max_yaw_offset: f32,
+    pub(crate) min_pitch: f32,
+    pub(crate) max_pitch: f32,
+}
+
+impl CameraConstraints {
+    /// The yaw at which the camera looks along `direction`.
+    pub(crate) fn yaw_towards(direction: Vec3) -> f32 {
+        (-direction.x).atan2(-direction.z).to_degrees()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
 #[reflect(Serialize, Deserialize)]
 pub(crate) enum IngameCameraKind {
@@ -55,17 +84,22 @@ pub(crate) enum IngameCameraKind {
 
 /// Handles the main ingame camera, i.e. not the UI camera in the menu.
 /// Cameras are controlled with [`CameraAction`](crate::player_control::actions::CameraAction). Depending on the distance, a first person,
-/// third person or fixed angle camera is used.
+/// third person or fixed angle camera is used. The fixed angle camera fades out [`CameraFadeable`] geometry that hides the player,
+/// and cuts to a secondary position inside [`CameraVolume`]s while other geometry does.
 pub(super) fn plugin(app: &mut App) {
     app.add_plugins(AtmospherePlugin)
         .register_type::<UiCamera>()
         .register_type::<IngameCamera>()
         .register_type::<IngameCameraKind>()
-        .init_resource::<ForceCursorGrabMode>()
-        .add_systems(Update, Dolly::<IngameCamera>::update_active)
+        .register_type::<CameraConstraints>()
+        .register_type::<CameraFadeable>()
+        .register_type::<CameraVolume>()
+        .register_type::<CursorGrabRequests>()
+        .init_resource::<CursorGrabRequests>()
+        .add_systems(Update, update_rig_transform)
         .add_systems(Startup, spawn_ui_camera)
         .add_systems(OnEnter(GameState::Playing), despawn_ui_camera)
-        .add_systems(Update, grab_cursor.run_if(in_state(GameState::Playing)))
+        .add_systems(Update, grab_cursor)
         .add_systems(
             Update,
             (
@@ -73,6 +107,8 @@ pub(super) fn plugin(app: &mut App) {
                 update_drivers,
                 set_camera_focus.after(ExampleYarnSpinnerDialogueViewSystemSet),
                 update_rig,
+                fade_player_near_camera,
+                fade_occluders,
             )
                 .chain()
                 .in_set(CameraUpdateSystemSet)
@@ -80,7 +116,18 @@ pub(super) fn plugin(app: &mut App) {
                 .before(TransformSystem::TransformPropagate)
                 .run_if(in_state(GameState::Playing))
                 .run_if(any_with_component::<Player>),
+        )
+        .add_systems(
+            Update,
+            cut_to_camera_volumes
+                .after(update_rig_transform)
+                .after(CameraUpdateSystemSet)
+                .before(TransformSystem::TransformPropagate)
+                .run_if(in_state(GameState::Playing))
+                .run_if(any_with_component::<Player>),
         );
+    #[cfg(target_arch = "wasm32")]
+    app.add_systems(Update, cursor::relock_cursor_on_click.after(grab_cursor));
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
@@ -1,4 +1,4 @@
-use crate::player_control::actions::ActionsFrozen;
+use crate::GameState;
 use anyhow::Context;
 use bevy::{
     prelude::*,
@@ -7,29 +7,84 @@ use bevy::{
 use bevy_mod_sysfail::prelude::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, Resource, Serialize, Deserialize, Default)]
-pub(crate) struct ForceCursorGrabMode(pub(crate) Option<CursorGrabMode>);
+/// Counts the UIs that currently need a free cursor, e.g. dialog, the pause menu or the editor.
+/// The cursor is locked to the window while playing and none of them are open.
+/// Every [`CursorGrabRequests::request_free`] must be matched by a [`CursorGrabRequests::release`].
+#[derive(Debug, Clone, Copy, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct CursorGrabRequests {
+    free_count: usize,
+}
+
+impl CursorGrabRequests {
+    pub(crate) fn request_free(&mut self) {
+        self.free_count += 1;
+    }
+
+    pub(crate) fn release(&mut self) {
+        if self.free_count == 0 {
+            warn!("Released a cursor grab request that was never made");
+            return;
+        }
+        self.free_count -= 1;
+    }
+
+    pub(crate) fn wants_free(&self) -> bool {
+        self.free_count > 0
+    }
+}
 
 #[sysfail(Log<anyhow::Error, Error>)]
 pub(super) fn grab_cursor(
     mut primary_windows: Query<&mut Window, With<PrimaryWindow>>,
-    actions_frozen: Res<ActionsFrozen>,
-    force_cursor_grab: Res<ForceCursorGrabMode>,
+    requests: Res<CursorGrabRequests>,
+    state: Res<State<GameState>>,
 ) {
     #[cfg(feature = "tracing")]
     let _span = info_span!("cursor_grab_system").entered();
     let mut window = primary_windows
         .get_single_mut()
         .context("Failed to get primary window")?;
-    let cursor = &mut window.cursor;
-    if let Some(mode) = force_cursor_grab.0 {
-        cursor.grab_mode = mode;
-        cursor.visible = mode != CursorGrabMode::Locked;
-    } else if actions_frozen.is_frozen() {
-        cursor.grab_mode = CursorGrabMode::None;
-        cursor.visible = true;
+    // Alt-tabbing away must never leave the cursor trapped. Regaining focus locks it again
+    // unless a UI still wants it free.
+    let wants_lock = window.focused && *state.get() == GameState::Playing && !requests.wants_free();
+    let grab_mode = if wants_lock {
+        CursorGrabMode::Locked
     } else {
-        cursor.grab_mode = CursorGrabMode::Locked;
-        cursor.visible = false;
+        CursorGrabMode::None
+    };
+
+    // Only touch the window when something changed so that it is not marked as changed every frame
+    if window.cursor.grab_mode != grab_mode {
+        window.cursor.grab_mode = grab_mode;
     }
+    if window.cursor.visible == wants_lock {
+        window.cursor.visible = !wants_lock;
+    }
+}
+
+/// Browsers drop the pointer lock when Escape is pressed without telling us,
+/// so it needs to be requested again when the player clicks on the canvas.
+/// Releasing the lock for one frame makes [`grab_cursor`] send a new request to the browser.
+/// A lock the browser still holds is left alone, since releasing it would free the cursor on every click.
+#[cfg(target_arch = "wasm32")]
+pub(super) fn relock_cursor_on_click(
+    mut primary_windows: Query<&mut Window, With<PrimaryWindow>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+) {
+    if !mouse.just_pressed(MouseButton::Left) || browser_has_pointer_lock() {
+        return;
+    }
+    for mut window in &mut primary_windows {
+        if window.cursor.grab_mode == CursorGrabMode::Locked {
+            window.cursor.grab_mode = CursorGrabMode::None;
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn browser_has_pointer_lock() -> bool {
+    web_sys::window()
+        .and_then(|window| window.document())
+        .is_some_and(|document| document.pointer_lock_element().is_some())
 }
@@ -0,0 +1,112 @@
+use crate::{
+    file_system_interaction::config::GameConfig,
+    level_instantiation::on_spawn::Player,
+    player_control::camera::{IngameCamera, IngameCameraKind},
+};
+use bevy::prelude::*;
+
+/// How much the opacity changes per second.
+const FADE_SPEED: f32 = 5.;
+/// The shadow pass discards blended fragments with an alpha below 0.05, so fading stays above that to keep the shadow.
+const MIN_BLEND_ALPHA: f32 = 0.06;
+
+/// The material the player's mesh had before fading, restored once it is fully opaque again.
+/// While fading, the mesh uses a copy of it, since materials are shared with other instances of the same model.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub(super) struct FadedMaterial {
+    original: Handle<StandardMaterial>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct PlayerOpacity(f32);
+
+impl Default for PlayerOpacity {
+    fn default() -> Self {
+        Self(1.)
+    }
+}
+
+/// Fades out the player model in third person when the camera gets too close to it, e.g. when backed against a wall.
+/// Applies to every mesh below the player, so swapped models fade as well. With other camera kinds, the model is opaque.
+pub(super) fn fade_player_near_camera(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    camera_query: Query<(&Transform, &IngameCamera), Without<Player>>,
+    players: Query<(Entity, &Transform), With<Player>>,
+    children: Query<&Children>,
+    mut meshes: Query<(&mut Handle<StandardMaterial>, Option<&FadedMaterial>)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut opacity: Local<PlayerOpacity>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("fade_player_near_camera").entered();
+    let Some((camera_transform, camera)) = camera_query.iter().next() else {
+        return;
+    };
+    let settings = &config.camera.third_person;
+    for (player, player_transform) in players.iter() {
+        // Recomputed every frame, so that switching the camera kind mid-fade restores the model
+        let target = if camera.kind == IngameCameraKind::ThirdPerson {
+            let distance = camera_transform
+                .translation
+                .distance(player_transform.translation);
+            let fade_range = (settings.fade_distance - settings.hide_distance).max(1e-3);
+            ((distance - settings.hide_distance) / fade_range).clamp(0., 1.)
+        } else {
+            1.
+        };
+        let max_step = FADE_SPEED * time.delta_seconds();
+        let previous = opacity.0;
+        opacity.0 += (target - opacity.0).clamp(-max_step, max_step);
+        let changed = opacity.0 != previous;
+
+        for entity in children.iter_descendants(player) {
+            let Ok((mut material, faded)) = meshes.get_mut(entity) else {
+                continue;
+            };
+            if opacity.0 >= 1. {
+                if let Some(faded) = faded {
+                    *material = faded.original.clone();
+                    commands.entity(entity).remove::<FadedMaterial>();
+                }
+                continue;
+            }
+            if faded.is_some() && !changed {
+                continue;
+            }
+            let original = match faded {
+                Some(faded) => faded.original.clone(),
+                None => material.clone(),
+            };
+            let Some(mut faded_material) = materials.get(&original).cloned() else {
+                continue;
+            };
+            apply_opacity(&mut faded_material, opacity.0);
+            if faded.is_some() {
+                if let Some(material) = materials.get_mut(material.id()) {
+                    *material = faded_material;
+                }
+            } else {
+                commands.entity(entity).insert(FadedMaterial { original });
+                *material = materials.add(faded_material);
+            }
+        }
+    }
+}
+
+pub(super) fn apply_opacity(material: &mut StandardMaterial, opacity: f32) {
+    if opacity > 0. {
+        let alpha = material.base_color.a() * opacity;
+        material.base_color.set_a(alpha.max(MIN_BLEND_ALPHA));
+        material.alpha_mode = AlphaMode::Blend;
+    } else {
+        // Adding black draws nothing, but unlike a zero alpha, the mesh still casts its shadow
+        material.base_color = Color::BLACK;
+        material.base_color_texture = None;
+        material.emissive = Color::BLACK;
+        material.emissive_texture = None;
+        material.unlit = true;
+        material.alpha_mode = AlphaMode::Add;
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
     level_instantiation::on_spawn::{player, Player},
     player_control::camera::IngameCamera,
-    world_interaction::dialog::CurrentDialogTarget,
+    world_interaction::dialog::{CurrentDialogTarget, DialogInitiator},
 };
 use bevy::prelude::*;
 use bevy_mod_sysfail::prelude::*;
@@ -13,10 +13,15 @@ pub(super) fn set_camera_focus(
     player_query: Query<&Transform, With<Player>>,
     dialog_targets: Query<&Transform, Without<Player>>,
     dialog_target: Res<CurrentDialogTarget>,
+    dialog_initiator: Res<DialogInitiator>,
     mut dialogue_complete_event: EventReader<DialogueCompleteEvent>,
 ) {
     for mut camera in camera_query.iter_mut() {
-        let player_transform = player_query.get_single()?;
+        // Frames the player in the dialog, not whoever else is around
+        let player_transform = match dialog_initiator.0 {
+            Some(initiator) => player_query.get(initiator)?,
+            None => player_query.get_single()?,
+        };
         if let Some(dialog_target) = dialog_target.0 {
             let dialog_target_transform = dialog_targets.get(dialog_target)?;
             camera.secondary_target = Some(dialog_target_transform.translation);
@@ -0,0 +1,323 @@
+use crate::{
+    movement::{physics::CollisionLayer, slow_motion::UnscaledTime},
+    player_control::camera::{fade::apply_opacity, IngameCamera, IngameCameraKind},
+};
+use bevy::{prelude::*, utils::HashSet};
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How much the opacity changes per second.
+const FADE_SPEED: f32 = 4.;
+/// The rays towards the player start at these heights above and below the camera target,
+/// so that an occluder covering only the legs or the head is found as well.
+const SAMPLE_HEIGHTS: [f32; 3] = [-0.5, 0., 0.5];
+/// How many occluders a single ray finds at most.
+const MAX_OCCLUDERS: u32 = 8;
+
+/// Fades out geometry between the fixed angle camera and the player, e.g. a pillar the player walks behind,
+/// so that the player does not disappear. Only entities with a [`CameraFadeable`] on themselves or an ancestor of their collider fade.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct CameraFadeable {
+    /// The opacity while the entity hides the player, between 0 and 1.
+    pub(crate) opacity: f32,
+}
+
+impl Default for CameraFadeable {
+    fn default() -> Self {
+        Self { opacity: 0.3 }
+    }
+}
+
+/// A box around the entity's origin in which the fixed angle camera cuts to a secondary position while geometry
+/// that is not [`CameraFadeable`] hides the player, e.g. the back wall of a room. Volumes may be rotated.
+/// When volumes overlap, the first one containing the camera target is used.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct CameraVolume {
+    pub(crate) half_extents: Vec3,
+    /// Where the camera cuts to, relative to the volume. From there, it looks at the camera target.
+    pub(crate) cut_position: Vec3,
+    /// How many seconds the camera stays at either position before it may cut again,
+    /// so that it does not flip-flop while the player walks along the edge of an occluder.
+    pub(crate) min_hold_time: f32,
+}
+
+impl Default for CameraVolume {
+    fn default() -> Self {
+        Self {
+            half_extents: Vec3::ONE,
+            cut_position: Vec3::ZERO,
+            min_hold_time: 1.,
+        }
+    }
+}
+
+/// Whether the camera is cut to the position of a [`CameraVolume`], and for how long it has been since the last cut.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub(super) struct VolumeCut {
+    is_cut: bool,
+    held: f32,
+}
+
+/// The state of a fading [`CameraFadeable`]. While it exists, the meshes below the entity use copies of their materials,
+/// since materials are shared with other instances of the same model. The originals are put back once it is fully opaque again.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub(super) struct OccluderFade {
+    opacity: f32,
+    /// The meshes below the entity with their original material and the copy they use while fading.
+    materials: Vec<(Entity, Handle<StandardMaterial>, Handle<StandardMaterial>)>,
+}
+
+pub(super) fn fade_occluders(
+    mut commands: Commands,
+    time: Res<Time>,
+    camera_query: Query<(&Transform, &IngameCamera)>,
+    spatial_query: SpatialQuery,
+    parents: Query<&Parent>,
+    children: Query<&Children>,
+    fadeables: Query<(), With<CameraFadeable>>,
+    mut occluders: Query<(Entity, &CameraFadeable, &mut OccluderFade)>,
+    mut meshes: Query<&mut Handle<StandardMaterial>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("fade_occluders").entered();
+    let mut occluding = HashSet::new();
+    for (transform, camera) in camera_query.iter() {
+        // The other kinds keep the player in view by moving the camera in front of obstacles
+        if camera.kind != IngameCameraKind::FixedAngle {
+            continue;
+        }
+        for hits in sample_occluders(&spatial_query, transform.translation, camera.target) {
+            for hit in hits {
+                let fadeable = std::iter::once(hit.entity)
+                    .chain(parents.iter_ancestors(hit.entity))
+                    .find(|&entity| fadeables.contains(entity));
+                occluding.extend(fadeable);
+            }
+        }
+    }
+
+    for &entity in &occluding {
+        if occluders.contains(entity) {
+            continue;
+        }
+        let mut fade_materials = Vec::new();
+        for mesh in std::iter::once(entity).chain(children.iter_descendants(entity)) {
+            let Ok(mut handle) = meshes.get_mut(mesh) else {
+                continue;
+            };
+            let Some(material) = materials.get(handle.id()).cloned() else {
+                continue;
+            };
+            let copy = materials.add(material);
+            fade_materials.push((mesh, handle.clone(), copy.clone()));
+            *handle = copy;
+        }
+        commands.entity(entity).insert(OccluderFade {
+            opacity: 1.,
+            materials: fade_materials,
+        });
+    }
+
+    let max_step = FADE_SPEED * time.delta_seconds();
+    for (entity, fadeable, mut fade) in occluders.iter_mut() {
+        let is_occluding = occluding.contains(&entity);
+        let target = if is_occluding {
+            fadeable.opacity.clamp(0., 1.)
+        } else {
+            1.
+        };
+        let previous = fade.opacity;
+        fade.opacity += (target - fade.opacity).clamp(-max_step, max_step);
+        if fade.opacity >= 1. && !is_occluding {
+            for (mesh, original, _) in &fade.materials {
+                if let Ok(mut handle) = meshes.get_mut(*mesh) {
+                    *handle = original.clone();
+                }
+            }
+            commands.entity(entity).remove::<OccluderFade>();
+            continue;
+        }
+        if fade.opacity == previous {
+            continue;
+        }
+        for (_, original, copy) in &fade.materials {
+            let Some(mut faded_material) = materials.get(original).cloned() else {
+                continue;
+            };
+            apply_opacity(&mut faded_material, fade.opacity);
+            if let Some(material) = materials.get_mut(copy) {
+                *material = faded_material;
+            }
+        }
+    }
+}
+
+/// Cuts the fixed angle camera to the [`CameraVolume::cut_position`] of the volume around the camera target while
+/// geometry that does not fade hides the target on every sampled ray, see [`VolumeCut`]. Runs after the rig placed the camera,
+/// so the camera's [`Transform`] is its regular position here. Since the rig keeps its own state, cutting back is instant.
+pub(super) fn cut_to_camera_volumes(
+    mut commands: Commands,
+    time: UnscaledTime,
+    mut camera_query: Query<(
+        Entity,
+        &mut Transform,
+        &IngameCamera,
+        Option<&mut VolumeCut>,
+    )>,
+    volumes: Query<(&CameraVolume, &GlobalTransform)>,
+    spatial_query: SpatialQuery,
+    parents: Query<&Parent>,
+    fadeables: Query<(), With<CameraFadeable>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("cut_to_camera_volumes").entered();
+    let dt = time.delta_seconds();
+    for (entity, mut transform, camera, cut) in camera_query.iter_mut() {
+        let Some(mut cut) = cut else {
+            commands.entity(entity).insert(VolumeCut {
+                is_cut: false,
+                held: f32::INFINITY,
+            });
+            continue;
+        };
+        // Like for fading, the other kinds move in front of obstacles themselves
+        let volume = volumes
+            .iter()
+            .filter(|_| camera.kind == IngameCameraKind::FixedAngle)
+            .find(|(volume, volume_transform)| {
+                let local = volume_transform
+                    .affine()
+                    .inverse()
+                    .transform_point3(camera.target);
+                local.abs().cmple(volume.half_extents).all()
+            });
+        let Some((volume, volume_transform)) = volume else {
+            // Without a volume, there is nowhere to stay cut to
+            if cut.is_cut {
+                *cut = VolumeCut {
+                    is_cut: false,
+                    held: 0.,
+                };
+            }
+            continue;
+        };
+        cut.held += dt;
+        let is_hidden = sample_occluders(&spatial_query, transform.translation, camera.target)
+            .iter()
+            .all(|hits| {
+                hits.iter().any(|hit| {
+                    !std::iter::once(hit.entity)
+                        .chain(parents.iter_ancestors(hit.entity))
+                        .any(|entity| fadeables.contains(entity))
+                })
+            });
+        if is_hidden != cut.is_cut && cut.held >= volume.min_hold_time {
+            *cut = VolumeCut {
+                is_cut: is_hidden,
+                held: 0.,
+            };
+        }
+        if cut.is_cut {
+            let position = volume_transform.transform_point(volume.cut_position);
+            *transform = Transform::from_translation(position).looking_at(camera.target, Vec3::Y);
+        }
+    }
+}
+
+/// The hits of every ray from `from` towards the [`SAMPLE_HEIGHTS`] around `target`.
+fn sample_occluders(
+    spatial_query: &SpatialQuery,
+    from: Vec3,
+    target: Vec3,
+) -> Vec<Vec<RayHitData>> {
+    let filter = SpatialQueryFilter::from_mask(CollisionLayer::CameraObstacle.to_bits());
+    SAMPLE_HEIGHTS
+        .into_iter()
+        .filter_map(|height| {
+            let offset = target + Vec3::Y * height - from;
+            let direction = Direction3d::new(offset).ok()?;
+            Some(spatial_query.ray_hits(
+                from,
+                direction,
+                offset.length(),
+                MAX_OCCLUDERS,
+                true,
+                filter.clone(),
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{assert_near, TestApp, TICK};
+
+    const REGULAR_POSITION: Vec3 = Vec3::new(0., 10., 10.);
+    const CUT_POSITION: Vec3 = Vec3::new(10., 5., 0.);
+
+    #[test]
+    fn fixed_angle_camera_cuts_while_hidden_and_holds_each_position() {
+        let mut app = TestApp::new();
+        app.add_systems(
+            Update,
+            (
+                // Stands in for the rig, which puts the camera back to its regular position every frame
+                |mut cameras: Query<&mut Transform, With<IngameCamera>>| {
+                    for mut transform in cameras.iter_mut() {
+                        *transform = Transform::from_translation(REGULAR_POSITION);
+                    }
+                },
+                cut_to_camera_volumes,
+            )
+                .chain(),
+        );
+        let camera = app
+            .world_mut()
+            .spawn((
+                IngameCamera {
+                    target: Vec3::Y,
+                    kind: IngameCameraKind::FixedAngle,
+                    ..default()
+                },
+                Transform::from_translation(REGULAR_POSITION),
+            ))
+            .id();
+        let min_hold_time = 0.5;
+        app.world_mut().spawn((
+            CameraVolume {
+                half_extents: Vec3::splat(5.),
+                cut_position: CUT_POSITION,
+                min_hold_time,
+            },
+            TransformBundle::default(),
+        ));
+        let camera_position =
+            |app: &TestApp| app.world().get::<Transform>(camera).unwrap().translation;
+        app.step(2);
+        assert_near(camera_position(&app), REGULAR_POSITION, 1e-4);
+
+        let wall = app.spawn_block(Vec3::new(0., 5., 5.), Vec3::new(4., 4., 0.5));
+        app.step(2);
+        assert_near(camera_position(&app), CUT_POSITION, 1e-4);
+
+        // Right after the cut, the camera stays even though the player is visible again
+        app.world_mut().despawn(wall);
+        app.step(2);
+        assert_near(camera_position(&app), CUT_POSITION, 1e-4);
+        app.step((min_hold_time / TICK) as usize);
+        assert_near(camera_position(&app), REGULAR_POSITION, 1e-4);
+
+        // Likewise, it does not cut again right after cutting back
+        app.spawn_block(Vec3::new(0., 5., 5.), Vec3::new(4., 4., 0.5));
+        app.step(2);
+        assert_near(camera_position(&app), REGULAR_POSITION, 1e-4);
+        app.step((min_hold_time / TICK) as usize);
+        assert_near(camera_position(&app), CUT_POSITION, 1e-4);
+    }
+}
@@ -1,10 +1,12 @@
 use crate::{
     file_system_interaction::config::GameConfig,
+    level_instantiation::on_spawn::Player,
+    movement::slow_motion::UnscaledTime,
     player_control::{
         actions::CameraAction,
         camera::{
             rig::arm::{get_arm_distance, get_zoom_smoothness, set_arm},
-            IngameCamera, IngameCameraKind,
+            CameraConstraints, IngameCamera, IngameCameraKind,
         },
     },
     util::math_trait_ext::Vec2Ext,
@@ -21,7 +23,7 @@ mod arm;
 
 #[sysfail(Log<anyhow::Error, Error>)]
 pub(super) fn update_rig(
-    time: Res<Time<Virtual>>,
+    time: UnscaledTime,
     mut camera_query: Query<(
         &mut IngameCamera,
         &mut Rig,
@@ -31,12 +33,15 @@ pub(super) fn update_rig(
     config: Res<GameConfig>,
     spatial_query: SpatialQuery,
     actions_frozen: Res<ActionsFrozen>,
+    players: Query<Entity, With<Player>>,
 ) {
     let dt = time.delta_seconds();
+    // The camera is shared, so other players keep looking around while one of them is in a dialog
+    let is_frozen = actions_frozen.are_all_players_frozen(players.iter());
     for (mut camera, mut rig, actions, transform) in camera_query.iter_mut() {
         set_look_at(&mut rig, &camera);
         set_position(&mut rig, &camera);
-        if actions_frozen.is_frozen() {
+        if is_frozen {
             continue;
         }
         if camera.kind == IngameCameraKind::FixedAngle {
@@ -48,6 +53,9 @@ pub(super) fn update_rig(
             if !camera_movement.is_approx_zero() {
                 set_yaw_pitch(&mut rig, &camera, camera_movement, &config);
             }
+            if let Some(constraints) = camera.constraints {
+                apply_constraints(&mut rig, constraints);
+            }
         }
 
         set_desired_distance(&mut camera, actions, &config);
@@ -61,6 +69,19 @@ pub(super) fn update_rig(
     }
 }
 
+/// Moves the camera along its rig on real time, so that the camera stays responsive in slow motion.
+pub(super) fn update_rig_transform(
+    time: UnscaledTime,
+    mut camera_query: Query<(&mut Transform, &mut Rig), With<IngameCamera>>,
+) {
+    let dt = time.delta_seconds();
+    for (mut transform, mut rig) in camera_query.iter_mut() {
+        let rig_transform = rig.update(dt);
+        transform.translation = rig_transform.translation;
+        transform.rotation = rig_transform.rotation;
+    }
+}
+
 fn get_camera_movement(actions: &ActionState<CameraAction>) -> Vec2 {
     actions
         .axis_pair(&CameraAction::Orbit)
@@ -70,13 +91,34 @@ fn get_camera_movement(actions: &ActionState<CameraAction>) -> Vec2 {
 
 fn set_yaw_pitch(rig: &mut Rig, camera: &IngameCamera, camera_movement: Vec2, config: &GameConfig) {
     let yaw_pitch = rig.driver_mut::<YawPitch>();
-    let yaw = -camera_movement.x * config.camera.mouse_sensitivity_x;
+    let mut yaw = -camera_movement.x * config.camera.mouse_sensitivity_x;
     let pitch = -camera_movement.y * config.camera.mouse_sensitivity_y;
+    if let Some((direction, resistance)) = camera.resisted_direction {
+        let facing = |yaw_degrees: f32| {
+            let yaw = yaw_degrees.to_radians();
+            Vec3::new(-yaw.sin(), 0., -yaw.cos()).dot(direction)
+        };
+        let current = yaw_pitch.yaw_degrees;
+        if facing(current + yaw.to_degrees()) > facing(current) {
+            yaw *= 1. - resistance.clamp(0., 1.);
+        }
+    }
     yaw_pitch.rotate_yaw_pitch(yaw.to_degrees(), pitch.to_degrees());
     let (min_pitch, max_pitch) = get_pitch_extrema(config, camera);
     yaw_pitch.pitch_degrees = yaw_pitch.pitch_degrees.clamp(min_pitch, max_pitch);
 }
 
+fn apply_constraints(rig: &mut Rig, constraints: CameraConstraints) {
+    let yaw_pitch = rig.driver_mut::<YawPitch>();
+    let yaw_offset =
+        (yaw_pitch.yaw_degrees - constraints.yaw_center + 180.).rem_euclid(360.) - 180.;
+    let yaw_offset = yaw_offset.clamp(-constraints.max_yaw_offset, constraints.max_yaw_offset);
+    yaw_pitch.yaw_degrees = constraints.yaw_center + yaw_offset;
+    yaw_pitch.pitch_degrees = yaw_pitch
+        .pitch_degrees
+        .clamp(constraints.min_pitch, constraints.max_pitch);
+}
+
 fn set_look_at(rig: &mut Rig, camera: &IngameCamera) {
     if let Some(look_at) = rig.try_driver_mut::<LookAt>() {
         if let Some(secondary_target) = camera.secondary_target {
@@ -0,0 +1,321 @@
+use crate::{
+    file_system_interaction::{
+        config::{ActionMode, GameConfig},
+        localization::Strings,
+    },
+    level_instantiation::on_spawn::{Npc, Player},
+    movement::character_controller::PlayOneShotAnimation,
+    player_control::{
+        actions::{ActionsFrozen, CameraAction, PlayerAction},
+        camera::{CameraUpdateSystemSet, CursorGrabRequests},
+        ui_layer::{UiLayer, UiLayers},
+    },
+    world_interaction::dialog::{CurrentDialogTarget, DialogInitiator, YarnNode},
+    GameState,
+};
+use bevy::{prelude::*, utils::HashMap, window::PrimaryWindow};
+use bevy_common_assets::ron::RonAssetPlugin;
+use bevy_egui::{egui, EguiContexts};
+use bevy_yarnspinner::prelude::DialogueRunner;
+use leafwing_input_manager::{plugin::InputManagerSystem, prelude::ActionState};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+/// Stick deflections below this do not select an emote.
+const STICK_DEAD_ZONE: f32 = 0.5;
+/// How many pixels the mouse needs to move to fully point at an emote.
+const MOUSE_RADIUS: f32 = 120.;
+const WHEEL_RADIUS: f32 = 110.;
+const ICON_SIZE: f32 = 40.;
+/// NPCs further away than this do not react to emotes.
+const REACTION_DISTANCE: f32 = 5.;
+
+/// Shows a radial menu of emotes while [`PlayerAction::Emote`] is held. Releasing it plays the selected emote
+/// as a one-shot animation and sends an [`EmoteEvent`]. With the toggling emote wheel mode, pressing it opens the wheel
+/// and pressing it again plays the emote. The emotes are configured in `assets/config/config.emotes.ron`.
+pub(super) fn plugin(app: &mut App) {
+    app.add_plugins(RonAssetPlugin::<EmoteTable>::new(&["emotes.ron"]))
+        .add_event::<EmoteEvent>()
+        .init_resource::<EmoteWheel>()
+        .init_resource::<EmoteIcons>()
+        .add_systems(
+            Update,
+            (
+                handle_emote_wheel_input
+                    .in_set(InputManagerSystem::ManualControl)
+                    .before(CameraUpdateSystemSet),
+                register_emote_icons,
+                display_emote_wheel,
+                react_to_emotes,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+#[derive(Debug, Clone, PartialEq, Asset, Reflect, Serialize, Deserialize, Default)]
+pub(crate) struct EmoteTable {
+    pub(crate) emotes: Vec<Emote>,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+pub(crate) struct Emote {
+    pub(crate) name: String,
+    /// Path to an image shown in the wheel. The name is shown instead when missing.
+    #[serde(default)]
+    pub(crate) icon: Option<String>,
+    /// Name of the one-shot animation played by the player's model.
+    pub(crate) animation: String,
+    /// Suffix of the yarn node a nearby NPC plays in response, e.g. `wave_back` starts `Sheep_wave_back`
+    /// for an NPC whose [`YarnNode`] is `Sheep`. Nothing happens when the NPC has no such node.
+    #[serde(default)]
+    pub(crate) reaction: Option<String>,
+}
+
+/// Sent when a character plays an emote.
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub(crate) struct EmoteEvent {
+    pub(crate) entity: Entity,
+    pub(crate) emote: String,
+}
+
+#[derive(Debug, Default, Resource)]
+struct EmoteWheel {
+    open: bool,
+    /// Where the player points at, inside the unit circle.
+    pointer: Vec2,
+    selected: Option<usize>,
+}
+
+#[derive(Debug, Default, Resource)]
+struct EmoteIcons(HashMap<String, (Handle<Image>, egui::TextureId)>);
+
+fn handle_emote_wheel_input(
+    mut wheel: ResMut<EmoteWheel>,
+    actions_frozen: Res<ActionsFrozen>,
+    tables: Res<Assets<EmoteTable>>,
+    mut players: Query<(Entity, &mut ActionState<PlayerAction>), With<Player>>,
+    mut camera_actions: Query<&mut ActionState<CameraAction>>,
+    mut one_shot_events: EventWriter<PlayOneShotAnimation>,
+    mut emote_events: EventWriter<EmoteEvent>,
+    config: Res<GameConfig>,
+) {
+    let Some(table) = tables.iter().next().map(|(_, table)| table) else {
+        return;
+    };
+    for (player, mut actions) in players.iter_mut() {
+        if !wheel.open {
+            if actions.just_pressed(&PlayerAction::Emote) && !table.emotes.is_empty() {
+                wheel.open = true;
+            }
+            continue;
+        }
+        if actions_frozen.is_player_frozen(player) {
+            *wheel = default();
+            continue;
+        }
+        let should_close = match config.accessibility.emote_wheel_mode {
+            ActionMode::Hold => !actions.pressed(&PlayerAction::Emote),
+            ActionMode::Toggle => actions.just_pressed(&PlayerAction::Emote),
+        };
+        if should_close {
+            if let Some(emote) = wheel.selected.and_then(|index| table.emotes.get(index)) {
+                one_shot_events.send(PlayOneShotAnimation {
+                    entity: player,
+                    animation: emote.animation.clone(),
+                });
+                emote_events.send(EmoteEvent {
+                    entity: player,
+                    emote: emote.name.clone(),
+                });
+            }
+            *wheel = default();
+            continue;
+        }
+
+        let stick = actions
+            .axis_pair(&PlayerAction::Move)
+            .map(|axis| axis.xy())
+            .unwrap_or_default();
+        if stick.length() > STICK_DEAD_ZONE {
+            wheel.pointer = stick.normalize();
+        }
+        for camera_actions in camera_actions.iter() {
+            let mouse = camera_actions
+                .axis_pair(&CameraAction::Orbit)
+                .map(|axis| axis.xy())
+                .unwrap_or_default();
+            // Screen coordinates point down, the wheel points up
+            let pointer = wheel.pointer + Vec2::new(mouse.x, -mouse.y) / MOUSE_RADIUS;
+            wheel.pointer = pointer.clamp_length_max(1.);
+        }
+        wheel.selected = (wheel.pointer.length() > STICK_DEAD_ZONE)
+            .then(|| sector(wheel.pointer, table.emotes.len()));
+
+        // The wheel uses the movement and camera input, so neither should move anything else.
+        // Unlike when freezing, the world keeps running.
+        actions
+            .action_data_mut_or_default(&PlayerAction::Move)
+            .axis_pair = Some(default());
+        actions.consume(&PlayerAction::Jump);
+        actions.consume(&PlayerAction::Interact);
+        for mut camera_actions in camera_actions.iter_mut() {
+            camera_actions
+                .action_data_mut_or_default(&CameraAction::Orbit)
+                .axis_pair = Some(default());
+        }
+    }
+}
+
+/// The index of the sector `pointer` points at, starting at the top and going clockwise.
+fn sector(pointer: Vec2, sector_count: usize) -> usize {
+    let angle = pointer.x.atan2(pointer.y).rem_euclid(TAU);
+    let sector_size = TAU / sector_count as f32;
+    ((angle / sector_size).round() as usize) % sector_count
+}
+
+fn register_emote_icons(
+    tables: Res<Assets<EmoteTable>>,
+    asset_server: Res<AssetServer>,
+    mut egui_contexts: EguiContexts,
+    mut icons: ResMut<EmoteIcons>,
+) {
+    if !tables.is_changed() {
+        return;
+    }
+    for (_, table) in tables.iter() {
+        for path in table.emotes.iter().filter_map(|emote| emote.icon.as_ref()) {
+            if icons.0.contains_key(path) {
+                continue;
+            }
+            let image: Handle<Image> = asset_server.load(path.clone());
+            let texture_id = egui_contexts.add_image(image.clone_weak());
+            icons.0.insert(path.clone(), (image, texture_id));
+        }
+    }
+}
+
+fn display_emote_wheel(
+    wheel: Res<EmoteWheel>,
+    tables: Res<Assets<EmoteTable>>,
+    icons: Res<EmoteIcons>,
+    primary_windows: Query<&Window, With<PrimaryWindow>>,
+    mut egui_contexts: EguiContexts,
+    mut ui_layers: ResMut<UiLayers>,
+    strings: Strings,
+) {
+    if !wheel.open {
+        return;
+    }
+    let layer = UiLayer::GameplayModal;
+    ui_layers.show(layer);
+    let Some(table) = tables.iter().next().map(|(_, table)| table) else {
+        return;
+    };
+    let Ok(window) = primary_windows.get_single() else {
+        return;
+    };
+    let center = egui::pos2(window.width() / 2., window.height() / 2.);
+    let sector_count = table.emotes.len();
+    egui::Area::new("Emote Wheel")
+        .order(layer.order())
+        .fixed_pos(egui::Pos2::ZERO)
+        .interactable(false)
+        .show(egui_contexts.ctx_mut(), |ui| {
+            let painter = ui.painter();
+            painter.circle_filled(
+                center,
+                WHEEL_RADIUS + ICON_SIZE,
+                egui::Color32::from_black_alpha(160),
+            );
+            for (index, emote) in table.emotes.iter().enumerate() {
+                let angle = index as f32 / sector_count as f32 * TAU;
+                let position = center + egui::vec2(angle.sin(), -angle.cos()) * WHEEL_RADIUS;
+                let is_selected = wheel.selected == Some(index);
+                if is_selected {
+                    painter.circle_filled(
+                        position,
+                        ICON_SIZE * 0.8,
+                        egui::Color32::from_white_alpha(60),
+                    );
+                }
+                let icon = emote.icon.as_ref().and_then(|path| icons.0.get(path));
+                if let Some((_, texture_id)) = icon {
+                    let rect = egui::Rect::from_center_size(position, egui::Vec2::splat(ICON_SIZE));
+                    let uv = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1., 1.));
+                    painter.image(*texture_id, rect, uv, egui::Color32::WHITE);
+                } else {
+                    painter.text(
+                        position,
+                        egui::Align2::CENTER_CENTER,
+                        strings.t(&emote.name),
+                        egui::FontId::proportional(16.),
+                        egui::Color32::WHITE,
+                    );
+                }
+            }
+            if let Some(emote) = wheel.selected.and_then(|index| table.emotes.get(index)) {
+                painter.text(
+                    center,
+                    egui::Align2::CENTER_CENTER,
+                    strings.t(&emote.name),
+                    egui::FontId::proportional(20.),
+                    egui::Color32::WHITE,
+                );
+            }
+        });
+}
+
+fn react_to_emotes(
+    mut emote_events: EventReader<EmoteEvent>,
+    tables: Res<Assets<EmoteTable>>,
+    transforms: Query<&GlobalTransform>,
+    npcs: Query<(Entity, &GlobalTransform, &YarnNode), With<Npc>>,
+    mut dialogue_runners: Query<&mut DialogueRunner>,
+    mut current_dialog_target: ResMut<CurrentDialogTarget>,
+    mut dialog_initiator: ResMut<DialogInitiator>,
+    mut freeze: ResMut<ActionsFrozen>,
+    mut cursor_grab: ResMut<CursorGrabRequests>,
+) {
+    let Some(table) = tables.iter().next().map(|(_, table)| table) else {
+        return;
+    };
+    for event in emote_events.read() {
+        let Some(reaction) = table
+            .emotes
+            .iter()
+            .find(|emote| emote.name == event.emote)
+            .and_then(|emote| emote.reaction.as_ref())
+        else {
+            continue;
+        };
+        let Ok(origin) = transforms.get(event.entity) else {
+            continue;
+        };
+        let Ok(mut dialogue_runner) = dialogue_runners.get_single_mut() else {
+            continue;
+        };
+        if dialogue_runner.is_running() {
+            continue;
+        }
+        let nearest = npcs
+            .iter()
+            .filter(|(entity, ..)| *entity != event.entity)
+            .map(|(entity, transform, yarn_node)| {
+                let distance = transform.translation().distance(origin.translation());
+                (entity, distance, format!("{}_{}", yarn_node.0, reaction))
+            })
+            .filter(|(_, distance, node)| {
+                *distance < REACTION_DISTANCE && dialogue_runner.node_exists(node)
+            })
+            .min_by(|(_, a, _), (_, b, _)| a.total_cmp(b));
+        let Some((npc, _, node)) = nearest else {
+            continue;
+        };
+        dialogue_runner.start_node(node);
+        current_dialog_target.0.replace(npc);
+        dialog_initiator.0 = Some(event.entity);
+        freeze.freeze_player(event.entity);
+        cursor_grab.request_free();
+    }
+}
@@ -0,0 +1,69 @@
+use crate::{
+    file_system_interaction::config::GameConfig,
+    level_instantiation::on_spawn::Player,
+    movement::character_controller::ActiveMovementModifiers,
+    player_control::ui_layer::{UiLayer, UiLayers},
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+const ICON_SIZE: f32 = 32.;
+
+/// Shows an icon in the bottom left corner while the player has [`ActiveMovementModifiers`], so that changed movement
+/// rules, e.g. disabled jumping, do not feel like a bug. Can be turned off with
+/// [`PlayerEffects::movement_modifier_icon`](crate::file_system_interaction::config::PlayerEffects::movement_modifier_icon).
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        display_modifier_icon.run_if(
+            in_state(GameState::Playing)
+                .and_then(|config: Res<GameConfig>| config.player.movement_modifier_icon),
+        ),
+    );
+}
+
+fn display_modifier_icon(
+    players: Query<&ActiveMovementModifiers, With<Player>>,
+    ui_layers: Res<UiLayers>,
+    mut egui_contexts: EguiContexts,
+) {
+    let Ok(modifiers) = players.get_single() else {
+        return;
+    };
+    if !ui_layers.is_visible(UiLayer::Hud) {
+        return;
+    }
+    let modifiers = modifiers.0;
+    // Struck through when something is disabled instead of only scaled
+    let is_restricted = modifiers.disable_jump || modifiers.disable_sprint;
+    egui::Area::new("Movement Modifier Icon")
+        .order(UiLayer::Hud.order())
+        .anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(20., -20.))
+        .interactable(false)
+        .show(egui_contexts.ctx_mut(), |ui| {
+            let (rect, _) =
+                ui.allocate_exact_size(egui::Vec2::splat(ICON_SIZE), egui::Sense::hover());
+            let painter = ui.painter();
+            let color = egui::Color32::from_white_alpha(200);
+            painter.circle_stroke(
+                rect.center(),
+                ICON_SIZE / 2. - 1.,
+                egui::Stroke::new(2., color),
+            );
+            painter.text(
+                rect.center(),
+                egui::Align2::CENTER_CENTER,
+                "⚡",
+                egui::FontId::proportional(ICON_SIZE * 0.6),
+                color,
+            );
+            if is_restricted {
+                let offset = egui::Vec2::splat(ICON_SIZE * 0.35);
+                painter.line_segment(
+                    [rect.center() - offset, rect.center() + offset],
+                    egui::Stroke::new(2., color),
+                );
+            }
+        });
+}
@@ -0,0 +1,125 @@
+use crate::{
+    dev::dev_tools::{DevTools, RegisterDevToolExt},
+    level_instantiation::on_spawn::Player,
+    movement::character_controller::{Depenetrate, GeneralMovementSystemSet},
+    player_control::{
+        actions::{DualAxisDataExt, PlayerAction},
+        camera::{IngameCamera, IngameCameraKind},
+    },
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_tnua::controller::TnuaToggle;
+use bevy_xpbd_3d::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+/// In m/s.
+const SPEED: f32 = 10.;
+const SPRINT_MULTIPLIER: f32 = 4.;
+const DESCEND_KEYS: [KeyCode; 2] = [KeyCode::ControlLeft, KeyCode::KeyC];
+
+/// Lets the player fly through walls to inspect levels. While active, the player has no collisions, so it cannot interact
+/// with anything either, and ignores gravity. The movement input flies in the direction the camera looks,
+/// [`PlayerAction::Jump`] flies up, left control or C fly down and [`PlayerAction::Sprint`] flies faster.
+/// When turned off, the player is pushed out of any geometry it ended up in.
+pub(super) fn plugin(app: &mut App) {
+    app.register_dev_tool("Noclip", Some(KeyCode::F6), toggle_noclip)
+        .add_systems(
+            Update,
+            fly.before(GeneralMovementSystemSet).run_if(
+                in_state(GameState::Playing)
+                    .and_then(|dev_tools: Res<DevTools>| dev_tools.is_active("Noclip")),
+            ),
+        );
+}
+
+/// On the player while noclip is active.
+#[derive(Debug, Clone, PartialEq, Component)]
+struct Noclip {
+    /// The collision layers to restore afterwards.
+    collision_layers: CollisionLayers,
+}
+
+fn toggle_noclip(
+    In(active): In<bool>,
+    mut commands: Commands,
+    mut players: Query<
+        (
+            Entity,
+            &mut CollisionLayers,
+            &mut LinearVelocity,
+            Option<&Noclip>,
+        ),
+        With<Player>,
+    >,
+) {
+    for (entity, mut collision_layers, mut velocity, noclip) in players.iter_mut() {
+        velocity.0 = Vec3::ZERO;
+        match (active, noclip) {
+            (true, None) => {
+                commands.entity(entity).insert((
+                    Noclip {
+                        collision_layers: *collision_layers,
+                    },
+                    TnuaToggle::Disabled,
+                    RigidBody::Kinematic,
+                ));
+                *collision_layers = CollisionLayers::NONE;
+            }
+            (false, Some(noclip)) => {
+                *collision_layers = noclip.collision_layers;
+                commands.entity(entity).remove::<Noclip>().insert((
+                    TnuaToggle::Enabled,
+                    RigidBody::Dynamic,
+                    Depenetrate::default(),
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn fly(
+    time: Res<Time>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut players: Query<
+        (
+            &ActionState<PlayerAction>,
+            &mut Transform,
+            &mut LinearVelocity,
+        ),
+        (With<Player>, With<Noclip>),
+    >,
+    cameras: Query<(&IngameCamera, &Transform), Without<Player>>,
+) {
+    let Some((camera, camera_transform)) = cameras.iter().next() else {
+        return;
+    };
+    for (actions, mut transform, mut velocity) in players.iter_mut() {
+        let movement = actions
+            .axis_pair(&PlayerAction::Move)
+            .and_then(|axis| axis.max_normalized())
+            .unwrap_or_default();
+        let forward = if camera.kind == IngameCameraKind::FixedAngle {
+            *camera_transform.up()
+        } else {
+            *camera_transform.forward()
+        };
+        let right = *camera_transform.right();
+        let mut vertical = 0.;
+        if actions.pressed(&PlayerAction::Jump) {
+            vertical += 1.;
+        }
+        if keys.any_pressed(DESCEND_KEYS) {
+            vertical -= 1.;
+        }
+        let direction = forward * movement.y + right * movement.x + Vec3::Y * vertical;
+        let speed = if actions.pressed(&PlayerAction::Sprint) {
+            SPEED * SPRINT_MULTIPLIER
+        } else {
+            SPEED
+        };
+        transform.translation += direction.clamp_length_max(1.) * speed * time.delta_seconds();
+        velocity.0 = Vec3::ZERO;
+    }
+}
@@ -1,15 +1,25 @@
 use crate::{
-    file_system_interaction::audio::AudioHandles,
+    file_system_interaction::{
+        audio::AudioHandles,
+        config::{ActionMode, GameConfig},
+    },
     movement::character_controller::*,
     player_control::{
         actions::{DualAxisDataExt, PlayerAction},
-        camera::{CameraUpdateSystemSet, IngameCamera, IngameCameraKind},
+        camera::{CameraConstraints, CameraUpdateSystemSet, IngameCamera, IngameCameraKind},
     },
 };
 
 use crate::{
-    level_instantiation::on_spawn::Player, util::math_trait_ext::Vec3Ext,
-    world_interaction::dialog::CurrentDialogTarget, GameState,
+    level_instantiation::on_spawn::Player,
+    util::math_trait_ext::Vec3Ext,
+    world_interaction::{
+        carry::Carrying,
+        dialog::{CurrentDialogTarget, DialogInitiator},
+        mount::Riding,
+        seat::Seated,
+    },
+    GameState,
 };
 use anyhow::Context;
 use bevy::prelude::*;
@@ -19,9 +29,12 @@ use bevy_tnua::{builtins::TnuaBuiltinWalk, controller::TnuaController};
 use leafwing_input_manager::plugin::InputManagerSystem;
 use leafwing_input_manager::prelude::ActionState;
 
+/// How far the movement input counts while dragging a prop, compared to pushing it all the way.
+const MAX_DRAG_INPUT: f32 = 0.3;
+
 /// This plugin handles everything that has to do with the player's physical representation in the world.
 /// This includes movement and rotation that differ from the way the [`crate::movement::plugin`] already handles characters in general.
-pub(super) fn plugin(app: &mut App) {
+pub(crate) fn plugin(app: &mut App) {
     app.register_type::<Timer>()
         .register_type::<Player>()
         .add_systems(
@@ -29,8 +42,10 @@ pub(super) fn plugin(app: &mut App) {
             (
                 handle_jump,
                 handle_horizontal_movement,
+                handle_ledge_grab,
                 rotate_to_speaker,
-                control_walking_sound,
+                // Headless test apps have no audio
+                control_walking_sound.run_if(resource_exists::<AudioHandles>),
                 handle_camera_kind,
             )
                 .chain()
@@ -41,7 +56,17 @@ pub(super) fn plugin(app: &mut App) {
         );
 }
 
-fn handle_jump(mut player_query: Query<(&ActionState<PlayerAction>, &mut Jump), With<Player>>) {
+fn handle_jump(
+    mut player_query: Query<
+        (&ActionState<PlayerAction>, &mut Jump),
+        (
+            With<Player>,
+            Without<Seated>,
+            Without<Riding>,
+            Without<LedgeHang>,
+        ),
+    >,
+) {
     #[cfg(feature = "tracing")]
     let _span = info_span!("handle_jump").entered();
     for (actions, mut jump) in &mut player_query {
@@ -51,8 +76,23 @@ fn handle_jump(mut player_query: Query<(&ActionState<PlayerAction>, &mut Jump),
 
 #[sysfail(Log<anyhow::Error, Error>)]
 fn handle_horizontal_movement(
-    mut player_query: Query<(&ActionState<PlayerAction>, &mut Walk, &mut Sprinting), With<Player>>,
+    mut player_query: Query<
+        (
+            &ActionState<PlayerAction>,
+            &mut Walk,
+            &mut Sprinting,
+            Option<&Carrying>,
+        ),
+        (
+            With<Player>,
+            Without<Seated>,
+            Without<Riding>,
+            Without<LedgeHang>,
+        ),
+    >,
     camera_query: Query<(&IngameCamera, &Transform), Without<Player>>,
+    config: Res<GameConfig>,
+    mut sprint_toggled: Local<bool>,
 ) {
     #[cfg(feature = "tracing")]
     let _span = info_span!("handle_horizontal_movement").entered();
@@ -60,19 +100,29 @@ fn handle_horizontal_movement(
         return Ok(());
     };
 
-    for (actions, mut walk, mut sprint) in &mut player_query {
+    for (actions, mut walk, mut sprint, carrying) in &mut player_query {
         let Some(axis) = actions.axis_pair(&PlayerAction::Move) else {
             continue;
         };
-        if let Some(movement) = axis.max_normalized() {
-            let forward = if camera.kind == IngameCameraKind::FixedAngle {
-                camera_transform.up()
-            } else {
-                camera_transform.forward()
-            }
-            .horizontal()
-            .normalize();
-
+        // Mirrors the button while holding, so that switching to toggling mid-sprint keeps sprinting
+        *sprint_toggled = match config.accessibility.sprint_mode {
+            ActionMode::Hold => actions.pressed(&PlayerAction::Sprint),
+            ActionMode::Toggle => *sprint_toggled ^ actions.just_pressed(&PlayerAction::Sprint),
+        };
+        let movement = axis.max_normalized();
+        let dragging = carrying.filter(|carrying| carrying.dragging);
+        // A toggled sprint ends when the player stops moving
+        if movement.is_none() {
+            *sprint_toggled = false;
+        }
+        let forward = if camera.kind == IngameCameraKind::FixedAngle {
+            camera_transform.up()
+        } else {
+            camera_transform.forward()
+        }
+        .horizontal()
+        .normalize();
+        if let Some(movement) = movement {
             let sideways = forward.cross(Vec3::Y);
             let forward_action = forward * movement.y;
             let sideways_action = sideways * movement.x;
@@ -84,16 +134,81 @@ fn handle_horizontal_movement(
             } else {
                 1.
             };
-            let direction = forward_action * modifier + sideways_action;
+            let mut direction = forward_action * modifier + sideways_action;
+            if actions.pressed(&PlayerAction::Walk) {
+                direction = direction.clamp_length_max(walk.walk_end());
+            }
+
+            if let Some(dragging) = dragging {
+                // Dragging only allows pulling the prop along or pushing it ahead, slowly and while facing it
+                direction = dragging.direction
+                    * direction
+                        .dot(dragging.direction)
+                        .clamp(-MAX_DRAG_INPUT, MAX_DRAG_INPUT);
+                walk.keep_facing = Some(dragging.direction);
+                *sprint_toggled = false;
+            }
 
             walk.direction = Some(direction);
-            sprint.requested = actions.pressed(&PlayerAction::Sprint);
+            sprint.requested = *sprint_toggled;
+        } else if let Some(dragging) = dragging {
+            walk.facing = Some(dragging.direction);
+        } else if camera.kind == IngameCameraKind::ThirdPerson {
+            // Standing still, the player turns to where the camera looks once it looks far enough away
+            walk.facing = Some(forward);
+        }
+    }
+}
+
+fn handle_ledge_grab(
+    mut player_query: Query<
+        (
+            &ActionState<PlayerAction>,
+            &mut LedgeGrab,
+            Option<&LedgeHang>,
+        ),
+        With<Player>,
+    >,
+    mut camera_query: Query<&mut IngameCamera>,
+    mut constrained_camera: Local<bool>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("handle_ledge_grab").entered();
+    for (actions, mut ledge_grab, hang) in &mut player_query {
+        let movement = actions
+            .axis_pair(&PlayerAction::Move)
+            .and_then(|axis| axis.max_normalized())
+            .unwrap_or_default();
+        ledge_grab.climb_requested = actions.just_pressed(&PlayerAction::Jump) || movement.y > 0.5;
+        ledge_grab.drop_requested = movement.y < -0.5;
+
+        // Keeps the camera from swinging around into the wall while hanging
+        match hang {
+            Some(hang) if !*constrained_camera => {
+                for mut camera in camera_query.iter_mut() {
+                    camera.constraints = Some(CameraConstraints {
+                        yaw_center: CameraConstraints::yaw_towards(-hang.wall_normal),
+                        max_yaw_offset: 60.,
+                        min_pitch: -50.,
+                        max_pitch: 30.,
+                    });
+                }
+                *constrained_camera = true;
+            }
+            None if *constrained_camera => {
+                for mut camera in camera_query.iter_mut() {
+                    camera.constraints = None;
+                }
+                *constrained_camera = false;
+            }
+            _ => {}
         }
     }
 }
 
 fn handle_camera_kind(
-    mut with_player: Query<(&mut Transform, &mut Visibility), With<Player>>,
+    // Riders are placed and shown by their mount
+    mut with_player: Query<(&mut Transform, &mut Visibility), (With<Player>, Without<Riding>)>,
     camera_query: Query<(&Transform, &IngameCamera), Without<Player>>,
 ) {
     #[cfg(feature = "tracing")]
@@ -118,6 +233,7 @@ fn handle_camera_kind(
 #[sysfail(Log<anyhow::Error, Error>)]
 fn rotate_to_speaker(
     dialog_target: Res<CurrentDialogTarget>,
+    dialog_initiator: Res<DialogInitiator>,
     mut with_player: Query<(&Transform, &mut TnuaController, &FloatHeight), With<Player>>,
     speakers: Query<&Transform, Without<Player>>,
 ) {
@@ -127,7 +243,10 @@ fn rotate_to_speaker(
 
     #[cfg(feature = "tracing")]
     let _span = info_span!("rotate_to_speaker").entered();
-    let (player_transform, mut controller, float_height) = with_player.get_single_mut()?;
+    let (player_transform, mut controller, float_height) = match dialog_initiator.0 {
+        Some(initiator) => with_player.get_mut(initiator)?,
+        None => with_player.get_single_mut()?,
+    };
     let speaker_transform = speakers.get(dialog_target)?;
     let direction = (speaker_transform.translation - player_transform.translation).horizontal();
     controller.basis(TnuaBuiltinWalk {
@@ -163,3 +282,105 @@ fn control_walking_sound(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        level_instantiation::on_spawn::player::RADIUS,
+        testing::{assert_near, InputScript, TestApp},
+    };
+
+    /// Where the player floats above ground at the height 0.
+    const STANDING_HEIGHT: f32 = 0.5;
+
+    fn app_with_standing_player() -> (TestApp, Entity) {
+        let mut app = TestApp::new();
+        app.spawn_ground();
+        let player = app.spawn_player(Vec3::new(0., STANDING_HEIGHT, 0.));
+        // Lets the controller settle on its float height
+        app.step(30);
+        (app, player)
+    }
+
+    #[test]
+    fn jump_reaches_jump_height_and_lands_in_place() {
+        let (mut app, player) = app_with_standing_player();
+        let start = app.translation(player);
+        let jump_height = app.world().get::<Jump>(player).unwrap().height;
+
+        app.script(InputScript::new().hold(PlayerAction::Jump, 40).idle(80));
+        let mut apex = start.y;
+        for _ in 0..120 {
+            app.step(1);
+            apex = apex.max(app.translation(player).y);
+        }
+
+        assert!(
+            (apex - start.y - jump_height).abs() < 0.25,
+            "Jumped {} high instead of {jump_height}",
+            apex - start.y
+        );
+        assert_near(app.translation(player), start, 0.1);
+    }
+
+    #[test]
+    fn walking_into_a_wall_stops_at_the_wall() {
+        let (mut app, player) = app_with_standing_player();
+        // Its face towards the player is at z = -3.5
+        app.spawn_block(Vec3::new(0., 2., -4.), Vec3::new(10., 4., 1.));
+
+        app.script(InputScript::new().walk(Vec2::Y, 120).idle(10));
+        app.run_script();
+
+        let stop = Vec3::new(0., STANDING_HEIGHT, -3.5 + RADIUS);
+        assert_near(app.translation(player), stop, 0.1);
+    }
+
+    #[test]
+    fn walking_off_a_ledge_lands_below() {
+        let mut app = TestApp::new();
+        // A ledge ending at z = -2, above a floor at the height -3
+        app.spawn_block(Vec3::new(0., -0.5, 0.), Vec3::new(4., 1., 4.));
+        app.spawn_block(Vec3::new(0., -3.5, 0.), Vec3::new(100., 1., 100.));
+        let player = app.spawn_player(Vec3::new(0., STANDING_HEIGHT, 0.));
+        app.record_events::<LandedEvent>();
+        app.step(30);
+        app.clear_events::<LandedEvent>();
+
+        app.script(InputScript::new().walk(Vec2::Y, 30).idle(60));
+        app.run_script();
+
+        let landings = app.events::<LandedEvent>();
+        assert_eq!(landings.len(), 1, "Expected one landing, got {landings:?}");
+        assert_eq!(landings[0].entity, player);
+        // Falling three meters ends at almost 8 m/s
+        assert!(
+            landings[0].impact_speed > 5.,
+            "Landed at only {} m/s",
+            landings[0].impact_speed
+        );
+        assert!((app.translation(player).y - (STANDING_HEIGHT - 3.)).abs() < 0.1);
+        assert!(app.translation(player).z < -2.);
+    }
+
+    #[test]
+    fn holding_walk_never_runs() {
+        let (mut app, player) = app_with_standing_player();
+        let is_running = |app: &TestApp| app.world().get::<Walk>(player).unwrap().running;
+
+        // Running at full tilt, then holding walk without letting go of the stick
+        app.script(InputScript::new().walk(Vec2::Y, 30));
+        app.run_script();
+        assert!(is_running(&app));
+        app.script(
+            InputScript::new()
+                .walk_holding(Vec2::Y, PlayerAction::Walk, 60)
+                .walk_holding(Vec2::X, PlayerAction::Walk, 60),
+        );
+        for _ in 0..120 {
+            app.step(1);
+            assert!(!is_running(&app), "The player runs while holding walk");
+        }
+    }
+}
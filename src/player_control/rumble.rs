@@ -0,0 +1,233 @@
+use crate::{
+    file_system_interaction::config::GameConfig,
+    level_instantiation::on_spawn::Player,
+    movement::character_controller::LandedEvent,
+    world_interaction::{
+        interaction_ui::{InteractRequestEvent, InteractionOpportunityEntered},
+        nameplate::Health,
+    },
+    GameState,
+};
+use bevy::{
+    input::gamepad::{GamepadRumbleIntensity, GamepadRumbleRequest},
+    prelude::*,
+    utils::HashMap,
+    window::PrimaryWindow,
+};
+use std::time::Duration;
+
+/// Landings slower than this do not rumble.
+const MIN_LANDING_SPEED: f32 = 4.;
+/// Landings at this speed or faster rumble at full strength.
+const MAX_LANDING_SPEED: f32 = 16.;
+
+/// Rumbles all connected gamepads. Gameplay sends [`RumbleEvent`]s, which are mixed: while rumbles of different
+/// priorities are active, only the highest priority is felt, and each motor runs at the strongest intensity requested for it.
+/// Every rumble is scaled by the configured intensity. Rumble stops right away when the game is paused or the window loses focus,
+/// and rumbles requested meanwhile are dropped.
+///
+/// The player feels landings, confirming interactions, taking damage and a subtle pulse when something new can be interacted with.
+pub(super) fn plugin(app: &mut App) {
+    app.add_event::<RumbleEvent>()
+        .init_resource::<ActiveRumbles>()
+        .add_systems(
+            Update,
+            (
+                (
+                    rumble_on_landing,
+                    rumble_on_interaction,
+                    rumble_on_interaction_opportunity,
+                    rumble_on_damage,
+                ),
+                update_rumble,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing).and_then(resource_exists::<GameConfig>)),
+        )
+        .add_systems(OnExit(GameState::Playing), stop_rumble);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub(crate) struct RumbleEvent {
+    /// The intensity of the high-frequency motor, between 0 and 1.
+    pub(crate) weak: f32,
+    /// The intensity of the low-frequency motor, between 0 and 1.
+    pub(crate) strong: f32,
+    /// In seconds.
+    pub(crate) duration: f32,
+    pub(crate) priority: RumblePriority,
+}
+
+/// While a rumble of a higher priority is active, those of lower priorities are not felt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub(crate) enum RumblePriority {
+    /// Subtle feedback, e.g. for something that can be interacted with.
+    Low,
+    #[default]
+    Normal,
+    /// Feedback that must not be drowned out, e.g. for taking damage.
+    High,
+}
+
+#[derive(Debug, Default, Resource)]
+struct ActiveRumbles {
+    /// With their remaining duration in seconds.
+    rumbles: Vec<(RumbleEvent, f32)>,
+    /// The intensity the gamepads were last told to rumble at.
+    current: Option<GamepadRumbleIntensity>,
+}
+
+fn rumble_on_landing(
+    mut landed_events: EventReader<LandedEvent>,
+    players: Query<(), With<Player>>,
+    mut rumble_events: EventWriter<RumbleEvent>,
+) {
+    for event in landed_events.read() {
+        if !players.contains(event.entity) {
+            continue;
+        }
+        let impact =
+            (event.impact_speed - MIN_LANDING_SPEED) / (MAX_LANDING_SPEED - MIN_LANDING_SPEED);
+        if impact <= 0. {
+            continue;
+        }
+        let impact = impact.min(1.);
+        rumble_events.send(RumbleEvent {
+            weak: 0.3 * impact,
+            strong: impact,
+            duration: 0.1 + 0.2 * impact,
+            priority: RumblePriority::Normal,
+        });
+    }
+}
+
+fn rumble_on_interaction(
+    mut interact_requests: EventReader<InteractRequestEvent>,
+    players: Query<(), With<Player>>,
+    mut rumble_events: EventWriter<RumbleEvent>,
+) {
+    for request in interact_requests.read() {
+        if players.contains(request.initiator) && !request.by_hit {
+            rumble_events.send(RumbleEvent {
+                weak: 0.4,
+                strong: 0.,
+                duration: 0.08,
+                priority: RumblePriority::Normal,
+            });
+        }
+    }
+}
+
+fn rumble_on_interaction_opportunity(
+    mut entered_events: EventReader<InteractionOpportunityEntered>,
+    mut rumble_events: EventWriter<RumbleEvent>,
+) {
+    if entered_events.read().last().is_some() {
+        rumble_events.send(RumbleEvent {
+            weak: 0.15,
+            strong: 0.,
+            duration: 0.05,
+            priority: RumblePriority::Low,
+        });
+    }
+}
+
+fn rumble_on_damage(
+    players: Query<(Entity, &Health), (With<Player>, Changed<Health>)>,
+    mut rumble_events: EventWriter<RumbleEvent>,
+    mut previous_health: Local<HashMap<Entity, f32>>,
+) {
+    for (entity, health) in players.iter() {
+        let previous = previous_health.insert(entity, health.current);
+        let Some(lost) = previous.map(|previous| previous - health.current) else {
+            continue;
+        };
+        if lost <= 0. {
+            continue;
+        }
+        let severity = (lost / health.max.max(1e-3) * 4.).clamp(0.3, 1.);
+        rumble_events.send(RumbleEvent {
+            weak: 0.5 * severity,
+            strong: severity,
+            duration: 0.25,
+            priority: RumblePriority::High,
+        });
+    }
+}
+
+fn update_rumble(
+    time: Res<Time<Real>>,
+    virtual_time: Res<Time<Virtual>>,
+    config: Res<GameConfig>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    gamepads: Res<Gamepads>,
+    mut rumble_events: EventReader<RumbleEvent>,
+    mut active: ResMut<ActiveRumbles>,
+    mut rumble_requests: EventWriter<GamepadRumbleRequest>,
+) {
+    let is_focused = windows.iter().any(|window| window.focused);
+    let settings = &config.rumble;
+    if !settings.enabled || virtual_time.is_paused() || !is_focused {
+        rumble_events.clear();
+        active.rumbles.clear();
+    }
+    let delta = time.delta_seconds();
+    active.rumbles.retain_mut(|(_, remaining)| {
+        *remaining -= delta;
+        *remaining > 0.
+    });
+    active.rumbles.extend(
+        rumble_events
+            .read()
+            .filter(|event| event.duration > 0.)
+            .map(|event| (*event, event.duration)),
+    );
+
+    let top_priority = active.rumbles.iter().map(|(event, _)| event.priority).max();
+    let mut intensity = GamepadRumbleIntensity {
+        strong_motor: 0.,
+        weak_motor: 0.,
+    };
+    let mut duration = 0_f32;
+    for (event, remaining) in &active.rumbles {
+        if Some(event.priority) != top_priority {
+            continue;
+        }
+        intensity.weak_motor = intensity.weak_motor.max(event.weak);
+        intensity.strong_motor = intensity.strong_motor.max(event.strong);
+        duration = duration.max(*remaining);
+    }
+    let scale = settings.intensity.clamp(0., 1.);
+    intensity.weak_motor = (intensity.weak_motor * scale).clamp(0., 1.);
+    intensity.strong_motor = (intensity.strong_motor * scale).clamp(0., 1.);
+
+    let is_silent = intensity.weak_motor <= 0. && intensity.strong_motor <= 0.;
+    let new = (!is_silent).then_some(intensity);
+    if new == active.current {
+        return;
+    }
+    active.current = new;
+    for gamepad in gamepads.iter() {
+        // Requests add up on the gamepad, so the mix replaces whatever rumbled before
+        rumble_requests.send(GamepadRumbleRequest::Stop { gamepad });
+        if let Some(intensity) = new {
+            rumble_requests.send(GamepadRumbleRequest::Add {
+                gamepad,
+                duration: Duration::from_secs_f32(duration),
+                intensity,
+            });
+        }
+    }
+}
+
+fn stop_rumble(
+    gamepads: Res<Gamepads>,
+    mut active: ResMut<ActiveRumbles>,
+    mut rumble_requests: EventWriter<GamepadRumbleRequest>,
+) {
+    active.rumbles.clear();
+    active.current = None;
+    for gamepad in gamepads.iter() {
+        rumble_requests.send(GamepadRumbleRequest::Stop { gamepad });
+    }
+}
@@ -0,0 +1,299 @@
+use crate::{player_control::ui_layer::UiLayer, util::math_trait_ext::F32Ext};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::collections::VecDeque;
+
+/// Each letterbox bar covers this fraction of the screen's height.
+const LETTERBOX_HEIGHT: f32 = 0.12;
+/// How fast letterbox bars that take no time slide, in slides per second.
+/// Finite, so that it does not turn into NaN when multiplied with a frame that took no time.
+const INSTANT_SPEED: f32 = f32::MAX;
+
+/// Covers the game view with fades, drawn on [`UiLayer::ScreenEffect`], and cinematic letterbox bars, drawn on [`UiLayer::Letterbox`].
+/// Flows like portals, deaths or cutscenes send [`ScreenTransitionEvent`]s instead of drawing their own overlays.
+/// Transitions run one after another in the order they were requested, and each one sends a [`ScreenTransitionFinished`]
+/// once it is done, so that callers can sequence e.g. "fade out, teleport, fade in" without caring about other requests.
+/// Fades start from whatever the screen currently shows and their duration shrinks with the distance left to go,
+/// so requesting a fade out while the screen is already covered finishes right away.
+/// [`LetterboxEvent`]s slide the bars in and out independently of the transitions.
+/// Everything runs on real time, so pausing the game does not freeze the screen halfway.
+pub(super) fn plugin(app: &mut App) {
+    app.add_event::<ScreenTransitionEvent>()
+        .add_event::<ScreenTransitionFinished>()
+        .add_event::<LetterboxEvent>()
+        .init_resource::<ScreenEffects>()
+        .add_systems(
+            Update,
+            (
+                queue_screen_transitions,
+                advance_screen_transitions,
+                slide_letterbox,
+                display_screen_effects,
+            )
+                .chain(),
+        );
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub(crate) struct ScreenTransitionEvent {
+    pub(crate) kind: ScreenTransitionKind,
+    /// In seconds. For fades, this is the duration of a full fade.
+    pub(crate) duration: f32,
+    /// The color the screen is covered with. Fading out from a partly covered screen blends from the previous color.
+    /// Ignored by [`ScreenTransitionKind::FadeIn`], which keeps the current color.
+    pub(crate) color: Color,
+}
+
+impl ScreenTransitionEvent {
+    pub(crate) fn fade_out(duration: f32) -> Self {
+        Self {
+            kind: ScreenTransitionKind::FadeOut,
+            duration,
+            color: Color::BLACK,
+        }
+    }
+
+    pub(crate) fn fade_in(duration: f32) -> Self {
+        Self {
+            kind: ScreenTransitionKind::FadeIn,
+            duration,
+            color: Color::BLACK,
+        }
+    }
+
+    pub(crate) fn cut(duration: f32) -> Self {
+        Self {
+            kind: ScreenTransitionKind::Cut,
+            duration,
+            color: Color::BLACK,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum ScreenTransitionKind {
+    /// Gradually covers the screen.
+    FadeOut,
+    /// Gradually uncovers the screen.
+    FadeIn,
+    /// Covers the screen at once and holds it for the duration before finishing.
+    Cut,
+}
+
+/// Sent once a [`ScreenTransitionEvent`] is done, with the request it belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub(crate) struct ScreenTransitionFinished(pub(crate) ScreenTransitionEvent);
+
+/// Slides the letterbox bars in or out over `duration` seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub(crate) struct LetterboxEvent {
+    pub(crate) shown: bool,
+    pub(crate) duration: f32,
+}
+
+#[derive(Debug, Resource)]
+pub(crate) struct ScreenEffects {
+    queue: VecDeque<ScreenTransitionEvent>,
+    /// The transition in progress, with the seconds that have passed since it started.
+    current: Option<(ScreenTransitionEvent, f32)>,
+    /// The opacity and color the current transition started from.
+    start: (f32, Color),
+    /// How much the screen is covered, between 0 and 1.
+    opacity: f32,
+    color: Color,
+    /// How far the letterbox bars have slid in, between 0 and 1.
+    letterbox: f32,
+    /// Whether the bars should be shown and how much they move per second.
+    letterbox_target: (bool, f32),
+}
+
+impl Default for ScreenEffects {
+    fn default() -> Self {
+        Self {
+            queue: default(),
+            current: None,
+            start: (0., Color::BLACK),
+            opacity: 0.,
+            color: Color::BLACK,
+            letterbox: 0.,
+            letterbox_target: (false, INSTANT_SPEED),
+        }
+    }
+}
+
+impl ScreenEffects {
+    /// Whether a transition is running or waiting to run.
+    pub(crate) fn is_busy(&self) -> bool {
+        self.current.is_some() || !self.queue.is_empty()
+    }
+
+    /// Whether the screen is fully covered, e.g. to hide a teleport.
+    pub(crate) fn is_covered(&self) -> bool {
+        self.opacity >= 1.
+    }
+
+    /// Whether the letterbox bars are shown or on their way in or out, which means a cutscene is playing.
+    pub(crate) fn is_letterboxed(&self) -> bool {
+        self.letterbox > 0. || self.letterbox_target.0
+    }
+}
+
+fn queue_screen_transitions(
+    mut transition_events: EventReader<ScreenTransitionEvent>,
+    mut letterbox_events: EventReader<LetterboxEvent>,
+    mut effects: ResMut<ScreenEffects>,
+) {
+    effects.queue.extend(transition_events.read().copied());
+    if let Some(event) = letterbox_events.read().last() {
+        let speed = if event.duration > 0. {
+            1. / event.duration
+        } else {
+            INSTANT_SPEED
+        };
+        effects.letterbox_target = (event.shown, speed);
+    }
+}
+
+fn advance_screen_transitions(
+    time: Res<Time<Real>>,
+    mut effects: ResMut<ScreenEffects>,
+    mut finished_events: EventWriter<ScreenTransitionFinished>,
+) {
+    let mut delta = time.delta_seconds();
+    // Transitions that take no time all finish this frame, in order
+    loop {
+        if effects.current.is_none() {
+            let Some(next) = effects.queue.pop_front() else {
+                return;
+            };
+            effects.start = (effects.opacity, effects.color);
+            effects.current = Some((next, 0.));
+        }
+        let Some((transition, elapsed)) = effects.current else {
+            return;
+        };
+        let (start_opacity, start_color) = effects.start;
+        let full_duration = transition.duration.max(0.);
+        let (target, duration) = match transition.kind {
+            ScreenTransitionKind::FadeOut => (1., full_duration * (1. - start_opacity)),
+            ScreenTransitionKind::FadeIn => (0., full_duration * start_opacity),
+            ScreenTransitionKind::Cut => (1., full_duration),
+        };
+        let elapsed = elapsed + delta;
+        let progress = if duration > 0. {
+            (elapsed / duration).min(1.)
+        } else {
+            1.
+        };
+        match transition.kind {
+            ScreenTransitionKind::FadeOut => {
+                effects.opacity = start_opacity + (target - start_opacity) * progress;
+                // A clear screen has no color to blend from
+                let from = if start_opacity > 0. {
+                    start_color
+                } else {
+                    transition.color
+                };
+                effects.color = lerp_color(from, transition.color, progress);
+            }
+            ScreenTransitionKind::FadeIn => {
+                effects.opacity = start_opacity + (target - start_opacity) * progress;
+            }
+            ScreenTransitionKind::Cut => {
+                effects.opacity = 1.;
+                effects.color = transition.color;
+            }
+        }
+        if progress < 1. {
+            effects.current = Some((transition, elapsed));
+            return;
+        }
+        effects.opacity = target;
+        effects.current = None;
+        finished_events.send(ScreenTransitionFinished(transition));
+        // The time left over goes to the next transition
+        delta = (elapsed - duration).max(0.);
+    }
+}
+
+fn slide_letterbox(time: Res<Time<Real>>, mut effects: ResMut<ScreenEffects>) {
+    let (shown, speed) = effects.letterbox_target;
+    let letterbox = slide(effects.letterbox, shown, speed, time.delta_seconds());
+    if effects.letterbox != letterbox {
+        effects.letterbox = letterbox;
+    }
+}
+
+/// Where letterbox bars that slid in by `letterbox` are after sliding for `dt` seconds at `speed`.
+fn slide(letterbox: f32, shown: bool, speed: f32, dt: f32) -> f32 {
+    let target = if shown { 1. } else { 0. };
+    let max_step = speed * dt;
+    // Clamping with NaN bounds panics
+    if speed.is_infinite() || !max_step.is_finite() {
+        return target;
+    }
+    letterbox + (target - letterbox).clamp(-max_step, max_step)
+}
+
+fn display_screen_effects(effects: Res<ScreenEffects>, mut egui_contexts: EguiContexts) {
+    if effects.opacity <= 0. && effects.letterbox <= 0. {
+        return;
+    }
+    let ctx = egui_contexts.ctx_mut();
+    let screen = ctx.screen_rect();
+    if effects.letterbox > 0. {
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            UiLayer::Letterbox.order(),
+            egui::Id::new("Letterbox"),
+        ));
+        // Eases out, so that the bars settle into place
+        let eased = 1. - (1. - effects.letterbox).squared();
+        let height = screen.height() * LETTERBOX_HEIGHT * eased;
+        let top = egui::Rect::from_min_size(screen.min, egui::vec2(screen.width(), height));
+        let bottom =
+            egui::Rect::from_min_max(egui::pos2(screen.min.x, screen.max.y - height), screen.max);
+        painter.rect_filled(top, 0., egui::Color32::BLACK);
+        painter.rect_filled(bottom, 0., egui::Color32::BLACK);
+    }
+    if effects.opacity > 0. {
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            UiLayer::ScreenEffect.order(),
+            egui::Id::new("Screen Effects"),
+        ));
+        let [r, g, b, _] = effects.color.as_rgba_u8();
+        let alpha = (effects.opacity.clamp(0., 1.) * 255.) as u8;
+        painter.rect_filled(
+            screen,
+            0.,
+            egui::Color32::from_rgba_unmultiplied(r, g, b, alpha),
+        );
+    }
+}
+
+fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    let from = from.as_rgba_f32();
+    let to = to.as_rgba_f32();
+    Color::rgba(
+        from[0] + (to[0] - from[0]) * t,
+        from[1] + (to[1] - from[1]) * t,
+        from[2] + (to[2] - from[2]) * t,
+        from[3] + (to[3] - from[3]) * t,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letterbox_slides_without_nan_on_frames_that_take_no_time() {
+        // The first frame takes no time, e.g. right at startup
+        assert_eq!(slide(0., false, INSTANT_SPEED, 0.), 0.);
+        assert_eq!(slide(0., true, INSTANT_SPEED, 0.), 0.);
+        assert_eq!(slide(0., true, f32::INFINITY, 0.), 1.);
+        // Instant slides finish on the next frame, even a long one
+        assert_eq!(slide(0., true, INSTANT_SPEED, 1. / 60.), 1.);
+        assert_eq!(slide(1., false, INSTANT_SPEED, 2.), 0.);
+        assert_eq!(slide(0., true, 2., 0.25), 0.5);
+    }
+}
@@ -0,0 +1,62 @@
+use crate::{
+    level_instantiation::on_spawn::Player,
+    movement::character_controller::Stamina,
+    player_control::ui_layer::{UiLayer, UiLayers},
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+const BAR_SIZE: egui::Vec2 = egui::vec2(160., 8.);
+/// How long the bar takes to fade in or out, in seconds.
+const FADE_DURATION: f32 = 0.5;
+
+/// Shows the player's [`Stamina`] as a bar at the bottom of the screen. It appears as soon as stamina is spent
+/// and fades out once it is full again. While the player is exhausted, the bar turns red.
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        display_stamina_bar.run_if(in_state(GameState::Playing)),
+    );
+}
+
+fn display_stamina_bar(
+    time: Res<Time>,
+    players: Query<&Stamina, With<Player>>,
+    ui_layers: Res<UiLayers>,
+    mut egui_contexts: EguiContexts,
+    mut opacity: Local<f32>,
+) {
+    let Ok(stamina) = players.get_single() else {
+        return;
+    };
+    let target = if stamina.is_full() { 0. } else { 1. };
+    let step = time.delta_seconds() / FADE_DURATION;
+    *opacity = if *opacity < target {
+        (*opacity + step).min(target)
+    } else {
+        (*opacity - step).max(target)
+    };
+    if *opacity <= 0. || !ui_layers.is_visible(UiLayer::Hud) {
+        return;
+    }
+    let alpha = |alpha: u8| (alpha as f32 * *opacity) as u8;
+    let fill = if stamina.is_exhausted() {
+        egui::Color32::from_rgba_unmultiplied(220, 70, 60, alpha(230))
+    } else {
+        egui::Color32::from_rgba_unmultiplied(240, 220, 120, alpha(230))
+    };
+    egui::Area::new("Stamina Bar")
+        .order(UiLayer::Hud.order())
+        .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0., -40.))
+        .interactable(false)
+        .show(egui_contexts.ctx_mut(), |ui| {
+            let (rect, _) = ui.allocate_exact_size(BAR_SIZE, egui::Sense::hover());
+            let painter = ui.painter();
+            let rounding = BAR_SIZE.y / 2.;
+            painter.rect_filled(rect, rounding, egui::Color32::from_black_alpha(alpha(150)));
+            let mut filled = rect;
+            filled.set_width(rect.width() * stamina.fraction());
+            painter.rect_filled(filled, rounding, fill);
+        });
+}
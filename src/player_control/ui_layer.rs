@@ -0,0 +1,101 @@
+use bevy::{prelude::*, utils::HashSet};
+use bevy_egui::egui;
+
+/// Decides how egui UI stacks. Every piece of UI belongs to a [`UiLayer`] and is drawn in its [`UiLayer::order`]:
+/// - Open modals call [`UiLayers::show`] every frame.
+/// - Only the topmost open modal layer and the layers above it receive [`UiAction`](crate::player_control::actions::UiAction)s
+///   through [`UiActions`](crate::player_control::actions::UiActions), so e.g. closing the pause menu does not also close a note below it.
+/// - The HUD hides while any modal is open.
+/// - Dev windows always float above everything else.
+///
+/// Public to the crate so that headless tests of UI can stack layers.
+pub(crate) fn plugin(app: &mut App) {
+    app.init_resource::<UiLayers>()
+        .add_systems(Last, advance_ui_layers);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum UiLayer {
+    /// Cinematic letterbox bars. On the lowest order, so that modals like dialog stay fully visible in cutscenes.
+    Letterbox,
+    /// Information laid over the game, like interaction prompts, nameplates or the compass.
+    Hud,
+    /// Effects covering the game view, like fades. Covers the HUD, but not the modals, so that e.g. dialog stays readable in cutscenes.
+    ScreenEffect,
+    /// UI that takes the player's attention away from the character, like dialog, notes or the emote wheel.
+    GameplayModal,
+    /// UI that takes the player out of the game, like the pause menu.
+    SystemModal,
+    /// Dev tools. Never blocks anything below it.
+    Dev,
+}
+
+impl UiLayer {
+    /// Pass this to [`egui::Area::order`] or [`egui::Window::order`].
+    pub(crate) fn order(self) -> egui::Order {
+        match self {
+            Self::Letterbox | Self::Hud => egui::Order::Background,
+            // The only order between the background and the middle
+            Self::ScreenEffect => egui::Order::PanelResizeLine,
+            Self::GameplayModal => egui::Order::Middle,
+            Self::SystemModal => egui::Order::Foreground,
+            Self::Dev => egui::Order::Tooltip,
+        }
+    }
+
+    fn is_modal(self) -> bool {
+        matches!(self, Self::GameplayModal | Self::SystemModal)
+    }
+}
+
+/// Which [`UiLayer`]s have open UI.
+#[derive(Debug, Default, Resource)]
+pub(crate) struct UiLayers {
+    /// UI that closes still counts as open until the end of the frame, so that the input
+    /// that closed it is not seen by the UI below it.
+    last_frame: HashSet<UiLayer>,
+    this_frame: HashSet<UiLayer>,
+}
+
+impl UiLayers {
+    /// Marks the layer as open for this frame.
+    pub(crate) fn show(&mut self, layer: UiLayer) {
+        self.this_frame.insert(layer);
+    }
+
+    pub(crate) fn is_open(&self, layer: UiLayer) -> bool {
+        self.last_frame.contains(&layer) || self.this_frame.contains(&layer)
+    }
+
+    /// The highest modal layer that is open.
+    pub(crate) fn top_modal(&self) -> Option<UiLayer> {
+        self.last_frame
+            .iter()
+            .chain(self.this_frame.iter())
+            .copied()
+            .filter(|layer| layer.is_modal())
+            .max()
+    }
+
+    /// Whether UI on the layer should be drawn. Only the HUD is ever hidden.
+    pub(crate) fn is_visible(&self, layer: UiLayer) -> bool {
+        layer != UiLayer::Hud || self.top_modal().is_none()
+    }
+
+    /// Whether UI on the layer may react to input.
+    pub(crate) fn receives_input(&self, layer: UiLayer) -> bool {
+        self.top_modal().map_or(true, |top| layer >= top)
+    }
+}
+
+/// A run condition for UI on `layer` that reads input itself instead of through [`UiActions`](crate::player_control::actions::UiActions),
+/// e.g. the dialogue view.
+pub(crate) fn receives_input(layer: UiLayer) -> impl Fn(Res<UiLayers>) -> bool + Clone {
+    move |ui_layers: Res<UiLayers>| ui_layers.receives_input(layer)
+}
+
+fn advance_ui_layers(mut layers: ResMut<UiLayers>) {
+    let layers = layers.as_mut();
+    std::mem::swap(&mut layers.last_frame, &mut layers.this_frame);
+    layers.this_frame.clear();
+}
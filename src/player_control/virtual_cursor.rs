@@ -0,0 +1,304 @@
+use crate::{
+    player_control::{
+        actions::{LastInputDevice, UiAction, UiActionQueueSystemSet, UiActions},
+        ui_layer::UiLayers,
+    },
+    GameState,
+};
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts, EguiInput, EguiSet};
+
+/// Stick deflections below this do not move the cursor.
+const STICK_DEAD_ZONE: f32 = 0.15;
+/// The speed of the cursor when the right stick is fully tilted, in points per second.
+const BASE_SPEED: f32 = 300.;
+/// How much faster the cursor gets per second the right stick is held, in points per second squared.
+const ACCELERATION: f32 = 1200.;
+const MAX_SPEED: f32 = 1500.;
+/// The left stick counts as flicked when it is tilted further than this after having been released.
+const FLICK_THRESHOLD: f32 = 0.8;
+/// The left stick counts as released below this, so that a new flick can start.
+const FLICK_RELEASE: f32 = 0.3;
+/// How far one flick scrolls, in points.
+const SCROLL_STEP: f32 = 80.;
+/// How far a target may lie to the side of a d-pad direction, compared to how far it lies in it.
+/// Beyond that, it is not considered to be in that direction.
+const MAX_SNAP_SLOPE: f32 = 2.;
+const CURSOR_SIZE: f32 = 18.;
+
+/// Lets gamepad players point at egui widgets, e.g. the buttons of the pause menu or the wares of a shop.
+/// While a modal [`UiLayer`](crate::player_control::ui_layer::UiLayer) with [`CursorTargetExt::cursor_target`]s is open and the [`LastInputDevice`] is a gamepad,
+/// a cursor is drawn on top of the UI and fed to egui as its pointer:
+/// - The right stick moves it, speeding up the longer it is held.
+/// - The south button clicks instead of sending [`UiAction::Confirm`], and flicking the left stick scrolls.
+/// - A d-pad direction snaps it to the nearest cursor target in that direction, as laid out in the previous frame.
+///
+/// Moving the mouse hands back to the OS cursor right away. During gameplay no modal is open,
+/// so the right stick is never taken away from the camera.
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<VirtualCursor>()
+        .init_resource::<CursorTargets>()
+        .add_systems(
+            PreUpdate,
+            update_virtual_cursor
+                .after(UiActionQueueSystemSet)
+                .after(EguiSet::ProcessInput)
+                .before(EguiSet::BeginFrame)
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            Update,
+            draw_virtual_cursor.run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Marks widgets the virtual cursor of the [`plugin`] snaps to when a d-pad direction is pressed,
+/// e.g. the buttons of a menu. Disabled widgets are skipped. A modal without any shows no virtual cursor, e.g. dialog that is navigated with [`UiAction`]s.
+pub(crate) trait CursorTargetExt {
+    fn cursor_target(self) -> Self;
+}
+
+impl CursorTargetExt for egui::Response {
+    fn cursor_target(self) -> Self {
+        if self.enabled {
+            let rect = self.rect;
+            self.ctx.data_mut(|data| {
+                data.get_temp_mut_or_default::<Vec<egui::Rect>>(cursor_targets_id())
+                    .push(rect);
+            });
+        }
+        self
+    }
+}
+
+fn cursor_targets_id() -> egui::Id {
+    egui::Id::new("Virtual Cursor Targets")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Default)]
+struct VirtualCursor {
+    /// Where the cursor points, in egui points. `None` while the OS cursor is used.
+    position: Option<egui::Pos2>,
+    /// How long the right stick has been tilted, in seconds.
+    move_time: f32,
+    is_pressed: bool,
+    /// Whether the left stick went back to the middle since the last flick.
+    can_flick: bool,
+}
+
+/// The [`CursorTargetExt::cursor_target`]s of the previous frame.
+#[derive(Debug, Clone, PartialEq, Resource, Default)]
+struct CursorTargets(Vec<egui::Rect>);
+
+fn update_virtual_cursor(
+    time: Res<Time>,
+    last_input_device: Res<LastInputDevice>,
+    ui_layers: Res<UiLayers>,
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    mut cursor_moved_events: EventReader<CursorMoved>,
+    mut ui_actions: UiActions,
+    mut egui_contexts: EguiContexts,
+    mut egui_inputs: Query<&mut EguiInput, With<PrimaryWindow>>,
+    mut cursor: ResMut<VirtualCursor>,
+    mut targets: ResMut<CursorTargets>,
+) {
+    let Some(ctx) = egui_contexts.try_ctx_mut() else {
+        return;
+    };
+    targets.0 = ctx.data_mut(|data| {
+        std::mem::take(data.get_temp_mut_or_default::<Vec<egui::Rect>>(cursor_targets_id()))
+    });
+    let Ok(mut egui_input) = egui_inputs.get_single_mut() else {
+        return;
+    };
+    let top_modal = ui_layers.top_modal();
+    let mouse_moved = cursor_moved_events.read().count() > 0;
+    let is_active = *last_input_device == LastInputDevice::Gamepad
+        && top_modal.is_some()
+        && !targets.0.is_empty()
+        && !mouse_moved;
+    if !is_active {
+        if cursor.position.is_some() {
+            release_pointer(&mut cursor, &mut egui_input);
+        }
+        return;
+    }
+    let screen = ctx.screen_rect();
+    let mut position = cursor.position.unwrap_or_else(|| screen.center());
+
+    let stick = |x, y| {
+        gamepads.iter().fold(Vec2::ZERO, |sum, gamepad| {
+            let axis = |axis_type| axes.get(GamepadAxis::new(gamepad, axis_type)).unwrap_or(0.);
+            sum + Vec2::new(axis(x), axis(y))
+        })
+    };
+    let right_stick = stick(GamepadAxisType::RightStickX, GamepadAxisType::RightStickY);
+    if right_stick.length() > STICK_DEAD_ZONE {
+        cursor.move_time += time.delta_seconds();
+        let speed = (BASE_SPEED + ACCELERATION * cursor.move_time).min(MAX_SPEED);
+        let delta = right_stick.clamp_length_max(1.) * speed * time.delta_seconds();
+        // egui's y axis points down
+        position += egui::vec2(delta.x, -delta.y);
+    } else {
+        cursor.move_time = 0.;
+    }
+
+    let just_pressed = |button_type| {
+        gamepads
+            .iter()
+            .any(|gamepad| buttons.just_pressed(GamepadButton::new(gamepad, button_type)))
+    };
+    let directions = [
+        (GamepadButtonType::DPadUp, egui::vec2(0., -1.)),
+        (GamepadButtonType::DPadDown, egui::vec2(0., 1.)),
+        (GamepadButtonType::DPadLeft, egui::vec2(-1., 0.)),
+        (GamepadButtonType::DPadRight, egui::vec2(1., 0.)),
+    ];
+    for (button_type, direction) in directions {
+        if !just_pressed(button_type) {
+            continue;
+        }
+        if let Some(target) = nearest_target(position, direction, &targets.0) {
+            position = target;
+        }
+    }
+    position = position.clamp(screen.min, screen.max);
+    egui_input.events.push(egui::Event::PointerMoved(position));
+
+    let is_pressed = gamepads
+        .iter()
+        .any(|gamepad| buttons.pressed(GamepadButton::new(gamepad, GamepadButtonType::South)));
+    if just_pressed(GamepadButtonType::South) {
+        // The click replaces the confirmation, so that e.g. a page button does not also close the note
+        if let Some(layer) = top_modal {
+            ui_actions.consume(layer, UiAction::Confirm);
+        }
+    }
+    if is_pressed != cursor.is_pressed {
+        egui_input.events.push(egui::Event::PointerButton {
+            pos: position,
+            button: egui::PointerButton::Primary,
+            pressed: is_pressed,
+            modifiers: egui::Modifiers::NONE,
+        });
+    }
+
+    let left_stick = stick(GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY);
+    if left_stick.y.abs() < FLICK_RELEASE {
+        cursor.can_flick = true;
+    } else if cursor.can_flick && left_stick.y.abs() > FLICK_THRESHOLD {
+        cursor.can_flick = false;
+        // Flicking up scrolls up, showing what is above
+        let scroll = SCROLL_STEP * left_stick.y.signum();
+        egui_input
+            .events
+            .push(egui::Event::Scroll(egui::vec2(0., scroll)));
+    }
+
+    cursor.position = Some(position);
+    cursor.is_pressed = is_pressed;
+}
+
+fn release_pointer(cursor: &mut VirtualCursor, egui_input: &mut EguiInput) {
+    if let (Some(position), true) = (cursor.position, cursor.is_pressed) {
+        egui_input.events.push(egui::Event::PointerButton {
+            pos: position,
+            button: egui::PointerButton::Primary,
+            pressed: false,
+            modifiers: egui::Modifiers::NONE,
+        });
+    }
+    *cursor = default();
+}
+
+/// The center of the target nearest to `from` that lies in `direction`, preferring targets straight ahead over ones to the side.
+/// The target `from` is over is skipped.
+fn nearest_target(
+    from: egui::Pos2,
+    direction: egui::Vec2,
+    targets: &[egui::Rect],
+) -> Option<egui::Pos2> {
+    let side = egui::vec2(-direction.y, direction.x);
+    targets
+        .iter()
+        .filter(|target| !target.contains(from))
+        .filter_map(|target| {
+            let offset = target.center() - from;
+            let ahead = offset.dot(direction);
+            let aside = offset.dot(side).abs();
+            (ahead > 0. && aside <= ahead * MAX_SNAP_SLOPE)
+                .then_some((target.center(), ahead + 2. * aside))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(center, _)| center)
+}
+
+fn draw_virtual_cursor(cursor: Res<VirtualCursor>, mut egui_contexts: EguiContexts) {
+    let Some(position) = cursor.position else {
+        return;
+    };
+    // Above all UI, even dev windows
+    let painter = egui_contexts.ctx_mut().layer_painter(egui::LayerId::new(
+        egui::Order::Tooltip,
+        egui::Id::new("Virtual Cursor"),
+    ));
+    // An arrow pointing to the top left, like the usual OS cursor
+    let points = [
+        egui::vec2(0., 0.),
+        egui::vec2(0., 1.),
+        egui::vec2(0.28, 0.75),
+        egui::vec2(0.7, 0.7),
+    ]
+    .map(|point| position + point * CURSOR_SIZE)
+    .to_vec();
+    let fill = if cursor.is_pressed {
+        egui::Color32::from_gray(180)
+    } else {
+        egui::Color32::WHITE
+    };
+    painter.add(egui::Shape::convex_polygon(
+        points,
+        fill,
+        egui::Stroke::new(1.5, egui::Color32::BLACK),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snaps_to_the_nearest_target_in_the_pressed_direction() {
+        let button =
+            |x: f32, y: f32| egui::Rect::from_center_size(egui::pos2(x, y), egui::vec2(80., 20.));
+        // A column of buttons with one off to the side
+        let targets = [
+            button(100., 100.),
+            button(100., 150.),
+            button(100., 200.),
+            button(300., 160.),
+        ];
+        let down = egui::vec2(0., 1.);
+        let up = egui::vec2(0., -1.);
+        let right = egui::vec2(1., 0.);
+        let left = egui::vec2(-1., 0.);
+        let from = egui::pos2(100., 100.);
+        assert_eq!(
+            nearest_target(from, down, &targets),
+            Some(egui::pos2(100., 150.))
+        );
+        assert_eq!(nearest_target(from, up, &targets), None);
+        assert_eq!(
+            nearest_target(egui::pos2(100., 150.), right, &targets),
+            Some(egui::pos2(300., 160.))
+        );
+        assert_eq!(nearest_target(from, left, &targets), None);
+        // From outside of every target, e.g. after moving the cursor with the stick
+        assert_eq!(
+            nearest_target(egui::pos2(100., 10.), down, &targets),
+            Some(egui::pos2(100., 100.))
+        );
+    }
+}
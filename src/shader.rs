@@ -3,14 +3,26 @@ use anyhow::Result;
 
 use bevy::prelude::*;
 
-use bevy::render::render_resource::{AsBindGroup, ShaderRef};
+use bevy::{
+    pbr::{MaterialPipeline, MaterialPipelineKey},
+    render::{
+        mesh::MeshVertexBufferLayout,
+        render_resource::{
+            AsBindGroup, CompareFunction, RenderPipelineDescriptor, ShaderRef,
+            SpecializedMeshPipelineError,
+        },
+    },
+};
 
 /// Handles instantiation of shaders. The shaders can be found in the [`shaders`](https://github.com/janhohenheim/foxtrot/tree/main/assets/shaders) directory.
 /// Shaders are stored in [`Material`]s which can be used on objects by attaching a `Handle<Material>` to an entity.
 /// The handles can be stored and retrieved in the [`ShaderMaterials`] resource.
 pub(super) fn plugin(app: &mut App) {
-    app.add_plugins(MaterialPlugin::<GlowyMaterial>::default())
-        .add_systems(OnExit(GameState::Loading), setup_shader);
+    app.add_plugins((
+        MaterialPlugin::<GlowyMaterial>::default(),
+        MaterialPlugin::<BeaconMaterial>::default(),
+    ))
+    .add_systems(OnExit(GameState::Loading), setup_shader);
 }
 
 #[derive(Resource, Debug, Clone)]
@@ -43,3 +55,34 @@ impl Material for GlowyMaterial {
         "shaders/glowy.wgsl".into()
     }
 }
+
+#[derive(AsBindGroup, Debug, Clone, Asset, TypePath)]
+/// Material for [`beacon.wgsl`](https://github.com/janhohenheim/foxtrot/blob/main/assets/shaders/beacon.wgsl).
+/// A flat, translucent color that ignores depth, so it is drawn on top of everything, e.g. for waypoint beacons.
+pub(crate) struct BeaconMaterial {
+    #[uniform(0)]
+    pub(crate) color: Color,
+}
+
+impl Material for BeaconMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/beacon.wgsl".into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Blend
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayout,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if let Some(depth_stencil) = descriptor.depth_stencil.as_mut() {
+            depth_stencil.depth_compare = CompareFunction::Always;
+            depth_stencil.depth_write_enabled = false;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,300 @@
+use crate::{
+    file_system_interaction::config::GameConfig,
+    level_instantiation::{
+        level_bounds::FellOutOfBoundsEvent, on_spawn::Player, portal::TravelEvent,
+        stable_id::StableId,
+    },
+    world_interaction::{
+        interaction_ui::InteractRequestEvent,
+        nameplate::Health,
+        world_flags::{FlagValue, WorldFlagChanged},
+    },
+    GameState,
+};
+use bevy::{
+    prelude::*,
+    utils::{HashSet, Uuid},
+};
+use bevy_gltf_blueprints::BlueprintName;
+use bevy_yarnspinner::events::{NodeCompleteEvent, NodeStartEvent};
+use serde::Serialize;
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const TELEMETRY_DIRECTORY: &str = "telemetry";
+
+/// Records what playtesters do into one JSON lines file per session in the `telemetry` directory:
+/// which interactables they use, which dialog nodes they see, where and how they die, which levels they reach
+/// and how long it takes them to set each [`WorldFlags`](crate::world_interaction::world_flags::WorldFlags) flag,
+/// which is how quest progress is tracked. `examples/telemetry_summary.rs` aggregates a folder of sessions.
+/// Only compiled with the `telemetry` feature, and only records while `telemetry.enabled` is set in the game config.
+/// The files are written on a background thread, so gameplay never waits for the disk. Nothing is sent anywhere.
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            update_session,
+            (
+                record_interactions,
+                record_dialogs,
+                record_deaths,
+                record_levels,
+                record_flags,
+            )
+                .run_if(resource_exists::<TelemetrySession>),
+        )
+            .chain()
+            .run_if(in_state(GameState::Playing)),
+    );
+}
+
+/// The open session file. Dropping it lets the writer thread flush what is left and close the file.
+#[derive(Debug, Resource)]
+struct TelemetrySession {
+    lines: Sender<String>,
+    /// [`Time<Real>`] when the session started, in seconds.
+    started: f32,
+    /// When the last flag was set, in seconds since the session started.
+    last_flag: f32,
+}
+
+impl TelemetrySession {
+    fn record(&self, time: &Time<Real>, event: TelemetryEvent) {
+        let line = TelemetryLine {
+            time: time.elapsed_seconds() - self.started,
+            event,
+        };
+        match serde_json::to_string(&line) {
+            Ok(line) => {
+                // Only fails once the writer thread gave up, which it already logged
+                let _ = self.lines.send(line);
+            }
+            Err(error) => error!("Failed to serialize telemetry: {error}"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct TelemetryLine {
+    /// Real seconds since the session started.
+    time: f32,
+    #[serde(flatten)]
+    event: TelemetryEvent,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum TelemetryEvent {
+    /// Always the first line of a session.
+    SessionStarted {
+        session: Uuid,
+        build: &'static str,
+        /// Seconds since the Unix epoch.
+        started_at: u64,
+    },
+    Interaction {
+        /// The target's blueprint or name.
+        target: Option<String>,
+        target_id: Option<StableId>,
+        position: Vec3,
+        by_hit: bool,
+    },
+    DialogNodeStarted {
+        node: String,
+    },
+    DialogNodeFinished {
+        node: String,
+    },
+    Death {
+        position: Vec3,
+        cause: DeathCause,
+    },
+    LevelReached {
+        level: String,
+    },
+    FlagChanged {
+        flag: String,
+        /// `None` when the flag was cleared.
+        value: Option<FlagValue>,
+        /// Seconds since the previous flag changed or the session started, i.e. how long the objective took.
+        duration: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum DeathCause {
+    /// The player's [`Health`] ran out.
+    Damage,
+    FellOutOfBounds,
+}
+
+fn update_session(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    session: Option<Res<TelemetrySession>>,
+    time: Res<Time<Real>>,
+) {
+    match (config.telemetry.enabled, session.is_some()) {
+        (true, false) => {
+            let id = Uuid::new_v4();
+            let (lines, receiver) = mpsc::channel();
+            let path = PathBuf::from(TELEMETRY_DIRECTORY).join(format!("{id}.jsonl"));
+            info!("Recording telemetry to {}", path.display());
+            thread::spawn(move || write_lines(path, receiver));
+            let session = TelemetrySession {
+                lines,
+                started: time.elapsed_seconds(),
+                last_flag: 0.,
+            };
+            let started_at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs());
+            session.record(
+                &time,
+                TelemetryEvent::SessionStarted {
+                    session: id,
+                    build: env!("CARGO_PKG_VERSION"),
+                    started_at,
+                },
+            );
+            commands.insert_resource(session);
+        }
+        (false, true) => {
+            commands.remove_resource::<TelemetrySession>();
+        }
+        _ => {}
+    }
+}
+
+/// Runs on its own thread until the [`TelemetrySession`] is dropped.
+fn write_lines(path: PathBuf, lines: Receiver<String>) {
+    let file = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| File::create(&path));
+    let mut writer = match file {
+        Ok(file) => BufWriter::new(file),
+        Err(error) => {
+            error!("Failed to create {}: {error}", path.display());
+            return;
+        }
+    };
+    // Blocks until the next line arrives, then writes everything that queued up in the meantime in one go
+    while let Ok(line) = lines.recv() {
+        let result = std::iter::once(line)
+            .chain(lines.try_iter())
+            .try_for_each(|line| writeln!(writer, "{line}"))
+            .and_then(|_| writer.flush());
+        if let Err(error) = result {
+            error!("Failed to write to {}: {error}", path.display());
+            return;
+        }
+    }
+}
+
+fn record_interactions(
+    session: Res<TelemetrySession>,
+    time: Res<Time<Real>>,
+    mut interact_requests: EventReader<InteractRequestEvent>,
+    players: Query<&GlobalTransform, With<Player>>,
+    targets: Query<(Option<&BlueprintName>, Option<&Name>, Option<&StableId>)>,
+) {
+    for request in interact_requests.read() {
+        let Ok(transform) = players.get(request.initiator) else {
+            continue;
+        };
+        let (blueprint, name, id) = targets.get(request.target).unwrap_or_default();
+        let target = blueprint
+            .map(|blueprint| blueprint.0.clone())
+            .or_else(|| name.map(ToString::to_string));
+        session.record(
+            &time,
+            TelemetryEvent::Interaction {
+                target,
+                target_id: id.copied(),
+                position: transform.translation(),
+                by_hit: request.by_hit,
+            },
+        );
+    }
+}
+
+fn record_dialogs(
+    session: Res<TelemetrySession>,
+    time: Res<Time<Real>>,
+    mut node_start_events: EventReader<NodeStartEvent>,
+    mut node_complete_events: EventReader<NodeCompleteEvent>,
+) {
+    for event in node_start_events.read() {
+        let node = event.node_name.clone();
+        session.record(&time, TelemetryEvent::DialogNodeStarted { node });
+    }
+    for event in node_complete_events.read() {
+        let node = event.node_name.clone();
+        session.record(&time, TelemetryEvent::DialogNodeFinished { node });
+    }
+}
+
+fn record_deaths(
+    session: Res<TelemetrySession>,
+    time: Res<Time<Real>>,
+    players: Query<(Entity, &Health, &GlobalTransform), (With<Player>, Changed<Health>)>,
+    mut falls: EventReader<FellOutOfBoundsEvent>,
+    is_player: Query<(), With<Player>>,
+    mut dead: Local<HashSet<Entity>>,
+) {
+    for (entity, health, transform) in players.iter() {
+        if health.current > 0. {
+            dead.remove(&entity);
+        } else if dead.insert(entity) {
+            let position = transform.translation();
+            let cause = DeathCause::Damage;
+            session.record(&time, TelemetryEvent::Death { position, cause });
+        }
+    }
+    for fall in falls.read() {
+        if is_player.contains(fall.entity) {
+            let position = fall.position;
+            let cause = DeathCause::FellOutOfBounds;
+            session.record(&time, TelemetryEvent::Death { position, cause });
+        }
+    }
+}
+
+fn record_levels(
+    session: Res<TelemetrySession>,
+    time: Res<Time<Real>>,
+    mut travel_events: EventReader<TravelEvent>,
+) {
+    for event in travel_events.read() {
+        let level = event.level.clone();
+        session.record(&time, TelemetryEvent::LevelReached { level });
+    }
+}
+
+fn record_flags(
+    mut session: ResMut<TelemetrySession>,
+    time: Res<Time<Real>>,
+    mut flag_changes: EventReader<WorldFlagChanged>,
+) {
+    for change in flag_changes.read() {
+        let now = time.elapsed_seconds() - session.started;
+        let duration = now - session.last_flag;
+        session.last_flag = now;
+        session.record(
+            &time,
+            TelemetryEvent::FlagChanged {
+                flag: change.flag.clone(),
+                value: change.value,
+                duration,
+            },
+        );
+    }
+}
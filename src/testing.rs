@@ -0,0 +1,337 @@
+use crate::{
+    file_system_interaction::config::GameConfig,
+    gameplay_log::GameplayLog,
+    level_instantiation::{
+        on_spawn::{player, Player},
+        static_geometry::transform_propagation_plugin,
+    },
+    movement::{
+        self,
+        character_controller::{CharacterControllerBundle, LedgeGrab, Stamina},
+        physics::CollisionLayer,
+    },
+    player_control::{actions::PlayerAction, camera::IngameCamera, player_embodiment},
+    world_interaction::{
+        dialog::{CurrentDialogTarget, DialogInitiator},
+        world_flags::WorldFlags,
+    },
+    GameState,
+};
+use bevy::{
+    app::Plugins,
+    ecs::schedule::ScheduleLabel,
+    prelude::*,
+    time::TimeUpdateStrategy,
+    utils::{Duration, Instant},
+};
+use bevy_xpbd_3d::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+use std::collections::VecDeque;
+
+/// Every [`TestApp::step`] advances time by exactly this many seconds, like `--deterministic` does for real runs.
+pub(crate) const TICK: f32 = 1. / 60.;
+
+/// A headless app for scenario tests. It starts out in [`GameState::Playing`] with physics, [`movement::plugin`]
+/// and [`player_embodiment::plugin`], which turns [`PlayerAction`]s into movement, but nothing that renders or plays audio.
+/// Scenarios add the plugins and systems of the feature they test on top, e.g. the interaction sensors.
+///
+/// Input is scripted with an [`InputScript`], which is applied to the player's [`ActionState`] at the start of each tick:
+/// ```ignore
+/// let mut app = TestApp::new();
+/// app.spawn_ground();
+/// let player = app.spawn_player(Vec3::new(0., 0.5, 0.));
+/// app.script(InputScript::new().walk(Vec2::Y, 30));
+/// app.run_script();
+/// assert_near(app.translation(player), Vec3::new(0., 0.5, -3.5), 0.5);
+/// ```
+pub(crate) struct TestApp {
+    app: App,
+}
+
+impl TestApp {
+    pub(crate) fn new() -> Self {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            transform_propagation_plugin,
+            HierarchyPlugin,
+            AssetPlugin::default(),
+        ))
+        // Only the asset types are needed, not the plugins that render or play them
+        .init_asset::<Mesh>()
+        .init_asset::<AnimationClip>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+            TICK,
+        )))
+        .insert_state(GameState::Playing)
+        .init_resource::<GameConfig>()
+        .init_resource::<CurrentDialogTarget>()
+        .init_resource::<DialogInitiator>()
+        .init_resource::<GameplayLog>()
+        .init_resource::<WorldFlags>()
+        .init_resource::<ScriptedInput>()
+        .add_systems(PreUpdate, apply_scripted_input);
+        // The dev tools draw with gizmos, e.g. the navigation paths
+        #[cfg(feature = "dev")]
+        app.init_asset::<bevy::render::render_resource::Shader>()
+            .add_plugins(bevy::gizmos::GizmoPlugin);
+        app.add_plugins((movement::plugin, player_embodiment::plugin));
+        Self { app }
+    }
+
+    pub(crate) fn add_plugins<M>(&mut self, plugins: impl Plugins<M>) -> &mut Self {
+        self.app.add_plugins(plugins);
+        self
+    }
+
+    pub(crate) fn add_systems<M>(
+        &mut self,
+        schedule: impl ScheduleLabel,
+        systems: impl IntoSystemConfigs<M>,
+    ) -> &mut Self {
+        self.app.add_systems(schedule, systems);
+        self
+    }
+
+    pub(crate) fn init_resource<R: Resource + FromWorld>(&mut self) -> &mut Self {
+        self.app.init_resource::<R>();
+        self
+    }
+
+    pub(crate) fn insert_resource<R: Resource>(&mut self, resource: R) -> &mut Self {
+        self.app.insert_resource(resource);
+        self
+    }
+
+    pub(crate) fn init_asset<A: Asset>(&mut self) -> &mut Self {
+        self.app.init_asset::<A>();
+        self
+    }
+
+    pub(crate) fn add_event<E: Event>(&mut self) -> &mut Self {
+        self.app.add_event::<E>();
+        self
+    }
+
+    pub(crate) fn world(&self) -> &World {
+        &self.app.world
+    }
+
+    pub(crate) fn world_mut(&mut self) -> &mut World {
+        &mut self.app.world
+    }
+
+    pub(crate) fn resource<R: Resource>(&self) -> &R {
+        self.app.world.resource::<R>()
+    }
+
+    pub(crate) fn send_event<E: Event>(&mut self, event: E) {
+        self.app.world.send_event(event);
+    }
+
+    /// Keeps every `E` sent from now on, see [`TestApp::events`].
+    pub(crate) fn record_events<E: Event + Clone>(&mut self) -> &mut Self {
+        self.app
+            .insert_resource(RecordedEvents::<E>(Vec::new()))
+            .add_systems(Last, record_events::<E>);
+        self
+    }
+
+    /// All `E` sent since [`TestApp::record_events`] or the last [`TestApp::clear_events`].
+    pub(crate) fn events<E: Event + Clone>(&self) -> &[E] {
+        &self.app.world.resource::<RecordedEvents<E>>().0
+    }
+
+    pub(crate) fn clear_events<E: Event + Clone>(&mut self) {
+        self.app.world.resource_mut::<RecordedEvents<E>>().0.clear();
+    }
+
+    /// Runs all schedules `ticks` times.
+    pub(crate) fn step(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.app.update();
+        }
+    }
+
+    /// Queues the frames of `script` after the ones that are not played yet.
+    pub(crate) fn script(&mut self, script: InputScript) {
+        self.app
+            .world
+            .resource_mut::<ScriptedInput>()
+            .frames
+            .extend(script.frames);
+    }
+
+    /// Steps until all scripted frames are played.
+    pub(crate) fn run_script(&mut self) {
+        let remaining = self.app.world.resource::<ScriptedInput>().frames.len();
+        self.step(remaining);
+    }
+
+    pub(crate) fn translation(&self, entity: Entity) -> Vec3 {
+        self.app
+            .world
+            .get::<GlobalTransform>(entity)
+            .expect("Entity has no transform")
+            .translation()
+    }
+
+    /// A large static floor whose top is at the height 0.
+    pub(crate) fn spawn_ground(&mut self) -> Entity {
+        self.spawn_block(Vec3::new(0., -0.5, 0.), Vec3::new(100., 1., 100.))
+    }
+
+    /// A static box of the given full `size`, blocking characters like level geometry does.
+    pub(crate) fn spawn_block(&mut self, center: Vec3, size: Vec3) -> Entity {
+        self.app
+            .world
+            .spawn((
+                Name::new("Block"),
+                TransformBundle::from_transform(Transform::from_translation(center)),
+                RigidBody::Static,
+                Collider::cuboid(size.x, size.y, size.z),
+                CollisionLayers::new(
+                    [CollisionLayer::Terrain, CollisionLayer::CameraObstacle],
+                    [CollisionLayer::Character],
+                ),
+            ))
+            .id()
+    }
+
+    /// The player as spawned from a level, minus its input maps and particles.
+    /// Also spawns an [`IngameCamera`] looking along -Z, since the player walks relative to it.
+    pub(crate) fn spawn_player(&mut self, translation: Vec3) -> Entity {
+        let mut controller = CharacterControllerBundle::capsule(player::HEIGHT, player::RADIUS, 1.);
+        controller.collision_layers.memberships |= CollisionLayer::Player;
+        let player = self
+            .app
+            .world
+            .spawn((
+                Name::new("Player"),
+                Player,
+                SpatialBundle::from_transform(Transform::from_translation(translation)),
+                controller,
+                LedgeGrab::default(),
+                Stamina::default(),
+                ActionState::<PlayerAction>::default(),
+            ))
+            .id();
+        self.app.world.spawn((
+            Name::new("Camera"),
+            IngameCamera::default(),
+            TransformBundle::from_transform(
+                Transform::from_translation(translation + Vec3::new(0., 2., 5.))
+                    .looking_to(Vec3::NEG_Z, Vec3::Y),
+            ),
+        ));
+        player
+    }
+}
+
+/// Panics when `actual` is further than `tolerance` away from `expected`.
+#[track_caller]
+pub(crate) fn assert_near(actual: Vec3, expected: Vec3, tolerance: f32) {
+    let distance = actual.distance(expected);
+    assert!(
+        distance <= tolerance,
+        "Expected {actual} to be within {tolerance} of {expected}, but it was {distance} away"
+    );
+}
+
+/// The player's input for a sequence of ticks, built up one stretch at a time.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct InputScript {
+    frames: Vec<ScriptedFrame>,
+}
+
+impl InputScript {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Touches nothing for `ticks` ticks.
+    pub(crate) fn idle(self, ticks: usize) -> Self {
+        self.frames(ticks, None, &[])
+    }
+
+    /// Holds `action` for `ticks` ticks. Pressing it again in the next stretch does not count as a new press.
+    pub(crate) fn hold(self, action: PlayerAction, ticks: usize) -> Self {
+        self.frames(ticks, None, &[action])
+    }
+
+    /// Tilts the movement stick in `direction` for `ticks` ticks. Up is away from the camera.
+    pub(crate) fn walk(self, direction: Vec2, ticks: usize) -> Self {
+        self.frames(ticks, Some(direction), &[])
+    }
+
+    /// Like [`InputScript::walk`], while holding [`PlayerAction::Sprint`].
+    pub(crate) fn sprint(self, direction: Vec2, ticks: usize) -> Self {
+        self.frames(ticks, Some(direction), &[PlayerAction::Sprint])
+    }
+
+    /// Like [`InputScript::walk`], while holding `action`, e.g. [`PlayerAction::Walk`].
+    pub(crate) fn walk_holding(self, direction: Vec2, action: PlayerAction, ticks: usize) -> Self {
+        self.frames(ticks, Some(direction), &[action])
+    }
+
+    fn frames(mut self, ticks: usize, movement: Option<Vec2>, pressed: &[PlayerAction]) -> Self {
+        let frame = ScriptedFrame {
+            movement,
+            pressed: pressed.to_vec(),
+        };
+        self.frames.extend(std::iter::repeat(frame).take(ticks));
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+struct ScriptedFrame {
+    movement: Option<Vec2>,
+    pressed: Vec<PlayerAction>,
+}
+
+#[derive(Debug, Default, Resource)]
+struct ScriptedInput {
+    frames: VecDeque<ScriptedFrame>,
+    /// Released once a frame does not press them anymore.
+    pressed: Vec<PlayerAction>,
+}
+
+#[derive(Debug, Resource)]
+struct RecordedEvents<E: Event>(Vec<E>);
+
+/// Once the script is played, nothing is pressed anymore.
+fn apply_scripted_input(
+    mut script: ResMut<ScriptedInput>,
+    mut actions: Query<&mut ActionState<PlayerAction>, With<Player>>,
+) {
+    let frame = script.frames.pop_front().unwrap_or_default();
+    let released: Vec<_> = script
+        .pressed
+        .iter()
+        .filter(|action| !frame.pressed.contains(action))
+        .copied()
+        .collect();
+    for mut actions in actions.iter_mut() {
+        // Turns last tick's presses into holds
+        let now = Instant::now();
+        actions.tick(now, now);
+        for action in &released {
+            actions.release(action);
+        }
+        for action in &frame.pressed {
+            actions.press(action);
+        }
+        actions
+            .action_data_mut_or_default(&PlayerAction::Move)
+            .axis_pair = frame.movement.map(Into::into);
+    }
+    script.pressed = frame.pressed;
+}
+
+fn record_events<E: Event + Clone>(
+    mut events: EventReader<E>,
+    mut recorded: ResMut<RecordedEvents<E>>,
+) {
+    recorded.0.extend(events.read().cloned());
+}
@@ -4,3 +4,7 @@ use bevy::prelude::*;
 pub(crate) fn is_frozen(actions_frozen: Res<ActionsFrozen>) -> bool {
     actions_frozen.is_frozen()
 }
+
+pub(crate) fn is_globally_frozen(actions_frozen: Res<ActionsFrozen>) -> bool {
+    actions_frozen.is_globally_frozen()
+}
@@ -1,11 +1,86 @@
 use bevy::prelude::*;
 
+pub(crate) mod carry;
+pub(crate) mod destructible;
 pub(crate) mod dialog;
-mod interaction_ui;
+pub(crate) mod hazard;
+pub(crate) mod interaction_cue;
+pub(crate) mod interaction_sensor;
+pub(crate) mod interaction_ui;
+pub(crate) mod inventory;
+pub(crate) mod lamp;
+pub(crate) mod mount;
+pub(crate) mod nameplate;
+pub(crate) mod npc_memory;
+pub(crate) mod on_hit;
+pub(crate) mod party;
+pub(crate) mod race;
+pub(crate) mod readable;
+pub(crate) mod respawn_grace;
+pub(crate) mod seat;
+pub(crate) mod shop;
+pub(crate) mod subtitles;
+pub(crate) mod time_of_day;
+pub(crate) mod tutorial;
+pub(crate) mod waypoint;
+pub(crate) mod weather;
+pub(crate) mod world_flags;
 
 /// Handles player to world interactions. Split into the following sub-plugins:
+/// - [`carry::plugin`] lets the player take or drag props, depending on how heavy they are
+/// - [`destructible::plugin`] breaks fences and railings apart when they are hit hard enough
 /// - [`dialog::plugin`] handles dialog trees
+/// - [`hazard::plugin`] hurts and knocks back characters in hazards like lava pools and spike traps
+/// - [`interaction_cue::plugin`] chimes from the direction of things the player could interact with if it turned around
+/// - [`interaction_sensor::plugin`] builds the sensor colliders within which the player can interact with something
 /// - [`interaction_ui::plugin`] handles the UI for interacting with an object in front of the player.
+/// - [`inventory::plugin`] keeps track of the items the player carries
+/// - [`lamp::plugin`] switches lamps on and off with the time of day
+/// - [`mount::plugin`] lets the player ride horses, carts and other mounts
+/// - [`nameplate::plugin`] draws names and health bars above characters
+/// - [`npc_memory::plugin`] remembers how the player has interacted with each NPC for dialog to branch on
+/// - [`on_hit::plugin`] lets thrown props and projectiles interact with what they hit
+/// - [`party::plugin`] lets dialog add NPCs to the player's party and remove them again
+/// - [`race::plugin`] runs timed races through checkpoints and keeps the best times
+/// - [`readable::plugin`] handles signs, notes and books the player can read
+/// - [`respawn_grace::plugin`] protects the player for a moment after arriving in a level
+/// - [`seat::plugin`] handles chairs and benches characters can sit on
+/// - [`shop::plugin`] lets the player buy and sell items at shops
+/// - [`subtitles::plugin`] shows subtitles for speech outside of the dialog box
+/// - [`time_of_day::plugin`] runs the in-game clock
+/// - [`tutorial::plugin`] shows tutorial prompts the first time the player does something
+/// - [`waypoint::plugin`] handles the custom waypoint and the compass
+/// - [`weather::plugin`] changes the weather and shows it outdoors
+/// - [`world_flags::plugin`] keeps track of flags set by dialog choices and triggers, which gate spawns and interactions
 pub(super) fn plugin(app: &mut App) {
-    app.add_plugins((dialog::plugin, interaction_ui::plugin));
+    app.add_plugins((
+        carry::plugin,
+        dialog::plugin,
+        interaction_sensor::plugin,
+        interaction_ui::plugin,
+        inventory::plugin,
+        lamp::plugin,
+        nameplate::plugin,
+        on_hit::plugin,
+        party::plugin,
+        readable::plugin,
+        seat::plugin,
+        shop::plugin,
+        subtitles::plugin,
+        time_of_day::plugin,
+        tutorial::plugin,
+    ))
+    // Bevy only accepts up to 15 plugins at once
+    .add_plugins((
+        destructible::plugin,
+        hazard::plugin,
+        interaction_cue::plugin,
+        mount::plugin,
+        npc_memory::plugin,
+        race::plugin,
+        respawn_grace::plugin,
+        waypoint::plugin,
+        weather::plugin,
+        world_flags::plugin,
+    ));
 }
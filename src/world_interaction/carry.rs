@@ -0,0 +1,445 @@
+use crate::{
+    file_system_interaction::{
+        config::GameConfig,
+        localization::{t, Strings},
+    },
+    gameplay_log::{log_event, GameplayLog},
+    level_instantiation::on_spawn::{player, Player},
+    movement::{
+        character_controller::{GroundedState, PlayOneShotAnimation},
+        physics::CollisionLayer,
+        slow_motion::UnscaledTime,
+    },
+    player_control::{
+        actions::PlayerAction,
+        ui_layer::{UiLayer, UiLayers},
+    },
+    util::math_trait_ext::Vec3Ext,
+    world_interaction::interaction_sensor::{InteractionSensor, SensorShape},
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_tnua::TnuaProximitySensor;
+use bevy_xpbd_3d::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+use serde::{Deserialize, Serialize};
+
+/// How far in front of the carrier's center a taken prop is held, in meters.
+const HOLD_DISTANCE: f32 = player::RADIUS + 0.5;
+/// How far above the carrier's center a taken prop is held, in meters.
+const HOLD_HEIGHT: f32 = 0.2;
+/// A dragged prop stays at least this far from the carrier's center, in meters.
+const MIN_DRAG_DISTANCE: f32 = player::RADIUS + 0.3;
+/// A prop that ends up this much further away than it is held, e.g. because it got stuck, is let go. In meters.
+const MAX_HOLD_DEVIATION: f32 = 1.5;
+/// The fastest a carried prop moves to catch up with where it should be, in m/s.
+const MAX_CATCH_UP_SPEED: f32 = 8.;
+/// How strongly a dragged prop is pulled back to its distance from the carrier, per second.
+const DRAG_STIFFNESS: f32 = 6.;
+/// For how long the "Too heavy" toast is shown, in seconds.
+const REFUSAL_DURATION: f32 = 2.;
+
+/// Lets the player take or drag [`Carryable`] props, depending on how heavy and large they are compared to the player's [`Strength`].
+/// - Light props are taken: they float in front of the player and do not collide with characters.
+/// - Heavier props are dragged: the player holds on with both hands and can only walk slowly towards or away from them.
+///   Dragging ends on its own on ground steeper than [`Carry::max_drag_slope`](crate::file_system_interaction::config::Carry::max_drag_slope)
+///   or when leaving the ground.
+/// - Anything heavier is refused with a one-shot animation and a "Too heavy" toast.
+///
+/// [`PlayerAction::Interact`] lets go again.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Carryable>()
+        .register_type::<Strength>()
+        .add_event::<CarryRequest>()
+        .init_resource::<CarryRefusal>()
+        .add_systems(
+            Update,
+            (
+                spawn_carryables,
+                update_carry_weights,
+                release_carried_props,
+                start_carrying,
+                move_carried_props,
+                display_refusal_toast,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// A prop the player can pick up or drag. Needs to be a rigid body.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Component, Reflect, Serialize, Deserialize, Default,
+)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct Carryable;
+
+/// How much a character can carry. Characters without one use the defaults, which upgrades may raise.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct Strength {
+    /// In kilograms.
+    pub(crate) max_mass: f32,
+    /// In cubic meters.
+    pub(crate) max_volume: f32,
+    /// How many times the maximum mass or volume can still be dragged.
+    pub(crate) drag_factor: f32,
+}
+
+impl Default for Strength {
+    fn default() -> Self {
+        Self {
+            max_mass: 15.,
+            max_volume: 0.4,
+            drag_factor: 4.,
+        }
+    }
+}
+
+impl Strength {
+    pub(crate) fn carryability(&self, weight: &CarryWeight) -> Carryability {
+        let load = (weight.mass / self.max_mass.max(f32::EPSILON))
+            .max(weight.volume / self.max_volume.max(f32::EPSILON));
+        if load <= 1. {
+            Carryability::Take
+        } else if load <= self.drag_factor {
+            Carryability::Drag
+        } else {
+            Carryability::TooHeavy
+        }
+    }
+}
+
+/// The mass and collider volume of a [`Carryable`], kept up to date by [`update_carry_weights`].
+#[derive(Debug, Clone, Copy, PartialEq, Component, Default)]
+pub(crate) struct CarryWeight {
+    /// In kilograms.
+    pub(crate) mass: f32,
+    /// In cubic meters.
+    pub(crate) volume: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Carryability {
+    Take,
+    Drag,
+    TooHeavy,
+}
+
+/// Makes a character take or drag a [`Carryable`]. Props that are too heavy are refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub(crate) struct CarryRequest {
+    pub(crate) character: Entity,
+    pub(crate) prop: Entity,
+}
+
+/// Present on a character holding a prop.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub(crate) struct Carrying {
+    pub(crate) prop: Entity,
+    /// Whether the prop is dragged instead of taken.
+    pub(crate) dragging: bool,
+    /// How far from the character's center the prop is held, in meters.
+    distance: f32,
+    /// The horizontal direction from the character to where the prop is held.
+    pub(crate) direction: Vec3,
+}
+
+/// Present on a prop held by a character, with what it was like before.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct Carried {
+    carrier: Entity,
+    body: RigidBody,
+    layers: Option<CollisionLayers>,
+}
+
+/// Seconds left to show the "Too heavy" toast for.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Default)]
+struct CarryRefusal(f32);
+
+fn spawn_carryables(
+    mut commands: Commands,
+    carryables: Query<Entity, (Added<Carryable>, Without<InteractionSensor>)>,
+) {
+    for entity in carryables.iter() {
+        commands
+            .entity(entity)
+            .insert(InteractionSensor::new(SensorShape::Sphere(1.)));
+    }
+}
+
+fn update_carry_weights(
+    mut commands: Commands,
+    carryables: Query<
+        (Entity, Option<&Mass>),
+        (With<Carryable>, Or<(Without<CarryWeight>, Changed<Mass>)>),
+    >,
+    children: Query<&Children>,
+    colliders: Query<&Collider, Without<Sensor>>,
+) {
+    for (entity, mass) in carryables.iter() {
+        // A density of 1 makes the mass equal to the volume
+        let volume = std::iter::once(entity)
+            .chain(children.iter_descendants(entity))
+            .filter_map(|entity| colliders.get(entity).ok())
+            .map(|collider| collider.mass_properties(1.).mass.0)
+            .sum();
+        commands.entity(entity).insert(CarryWeight {
+            mass: mass.map_or(0., |mass| mass.0),
+            volume,
+        });
+    }
+}
+
+fn release_carried_props(
+    mut commands: Commands,
+    carriers: Query<(
+        Entity,
+        &Carrying,
+        &GlobalTransform,
+        Option<&ActionState<PlayerAction>>,
+        Option<&TnuaProximitySensor>,
+        Option<&GroundedState>,
+    )>,
+    props: Query<(Entity, &Carried, &GlobalTransform)>,
+    config: Res<GameConfig>,
+    mut log: ResMut<GameplayLog>,
+) {
+    for (carrier, carrying, transform, actions, sensor, grounded) in carriers.iter() {
+        let Ok((_, carried, prop_transform)) = props.get(carrying.prop) else {
+            commands.entity(carrier).remove::<Carrying>();
+            continue;
+        };
+        let distance = (prop_transform.translation() - transform.translation())
+            .horizontal()
+            .length();
+        let is_too_far = distance > carrying.distance + MAX_HOLD_DEVIATION;
+        let lets_go = actions.is_some_and(|actions| actions.just_pressed(&PlayerAction::Interact));
+        let is_too_steep = sensor
+            .and_then(|sensor| sensor.output.as_ref())
+            .is_some_and(|output| {
+                output.normal.angle_between(Vec3::Y).to_degrees() > config.carry.max_drag_slope
+            });
+        let is_airborne = grounded.is_some_and(|grounded| grounded.airborne);
+        let loses_grip = carrying.dragging && (is_too_steep || is_airborne);
+        if !(lets_go || is_too_far || loses_grip) {
+            continue;
+        }
+        log_event!(
+            log,
+            Interaction,
+            Some(carrier),
+            "Let go of a prop",
+            prop = carrying.prop,
+            too_far = is_too_far,
+            lost_grip = loses_grip
+        );
+        commands.entity(carrier).remove::<Carrying>();
+        release_prop(&mut commands, carrying.prop, carried);
+    }
+    // Props whose carrier is gone or holds something else
+    for (prop, carried, _) in props.iter() {
+        let is_held = carriers
+            .get(carried.carrier)
+            .is_ok_and(|(_, carrying, ..)| carrying.prop == prop);
+        if !is_held {
+            release_prop(&mut commands, prop, carried);
+        }
+    }
+}
+
+fn release_prop(commands: &mut Commands, prop: Entity, carried: &Carried) {
+    let mut entity_commands = commands.entity(prop);
+    entity_commands
+        .remove::<(Carried, SleepingDisabled)>()
+        .insert(carried.body);
+    match carried.layers {
+        Some(layers) => entity_commands.insert(layers),
+        None => entity_commands.remove::<CollisionLayers>(),
+    };
+}
+
+fn start_carrying(
+    mut commands: Commands,
+    mut requests: EventReader<CarryRequest>,
+    carriers: Query<(&GlobalTransform, Option<&Strength>, Has<Player>), Without<Carrying>>,
+    props: Query<
+        (
+            &GlobalTransform,
+            &CarryWeight,
+            &RigidBody,
+            Option<&CollisionLayers>,
+        ),
+        (With<Carryable>, Without<Carried>),
+    >,
+    config: Res<GameConfig>,
+    mut one_shot_events: EventWriter<PlayOneShotAnimation>,
+    mut refusal: ResMut<CarryRefusal>,
+    mut log: ResMut<GameplayLog>,
+) {
+    for request in requests.read() {
+        let Ok((transform, strength, is_player)) = carriers.get(request.character) else {
+            continue;
+        };
+        let Ok((prop_transform, weight, body, layers)) = props.get(request.prop) else {
+            continue;
+        };
+        let carryability = strength.copied().unwrap_or_default().carryability(weight);
+        if carryability == Carryability::TooHeavy {
+            log_event!(
+                log,
+                Interaction,
+                Some(request.character),
+                "Too heavy to carry",
+                prop = request.prop,
+                mass = weight.mass
+            );
+            if let Some(animation) = &config.carry.refuse_animation {
+                one_shot_events.send(PlayOneShotAnimation {
+                    entity: request.character,
+                    animation: animation.clone(),
+                });
+            }
+            if is_player {
+                refusal.0 = REFUSAL_DURATION;
+            }
+            continue;
+        }
+        let dragging = carryability == Carryability::Drag;
+        let to_prop = (prop_transform.translation() - transform.translation()).horizontal();
+        let direction = to_prop
+            .try_normalize()
+            .unwrap_or_else(|| transform.forward().horizontal().normalize_or_zero());
+        let distance = if dragging {
+            to_prop.length().max(MIN_DRAG_DISTANCE)
+        } else {
+            HOLD_DISTANCE
+        };
+        log_event!(
+            log,
+            Interaction,
+            Some(request.character),
+            if dragging {
+                "Started dragging"
+            } else {
+                "Took a prop"
+            },
+            prop = request.prop
+        );
+        commands.entity(request.character).insert(Carrying {
+            prop: request.prop,
+            dragging,
+            distance,
+            direction,
+        });
+        let mut prop_commands = commands.entity(request.prop);
+        prop_commands.insert((
+            Carried {
+                carrier: request.character,
+                body: *body,
+                layers: layers.copied(),
+            },
+            SleepingDisabled,
+        ));
+        if !dragging {
+            // A taken prop would otherwise push its carrier around
+            let mut carried_layers = layers.copied().unwrap_or_default();
+            carried_layers
+                .filters
+                .remove([CollisionLayer::Player, CollisionLayer::Character]);
+            prop_commands.insert((RigidBody::Kinematic, carried_layers));
+        }
+    }
+}
+
+fn move_carried_props(
+    time: Res<Time>,
+    mut carriers: Query<(&GlobalTransform, &mut Carrying, &LinearVelocity), Without<Carried>>,
+    mut props: Query<(&GlobalTransform, &mut LinearVelocity, &mut AngularVelocity), With<Carried>>,
+) {
+    let dt = time.delta_seconds();
+    if dt <= 0. {
+        return;
+    }
+    for (transform, mut carrying, carrier_velocity) in carriers.iter_mut() {
+        let Ok((prop_transform, mut velocity, mut angular_velocity)) = props.get_mut(carrying.prop)
+        else {
+            continue;
+        };
+        let position = transform.translation();
+        let prop_position = prop_transform.translation();
+        if carrying.dragging {
+            // The carrier swivels with the prop, so that it can be pulled around corners
+            if let Some(direction) = (prop_position - position).horizontal().try_normalize() {
+                carrying.direction = direction;
+            }
+            let target = position + carrying.direction * carrying.distance;
+            let correction = (target - prop_position).horizontal() * DRAG_STIFFNESS;
+            let horizontal =
+                (carrier_velocity.0.horizontal() + correction).clamp_length_max(MAX_CATCH_UP_SPEED);
+            // Gravity keeps pulling the prop down onto the ground
+            velocity.0 = horizontal + Vec3::Y * velocity.0.y;
+        } else {
+            carrying.direction = transform.forward().horizontal().normalize_or_zero();
+            let target = position + carrying.direction * carrying.distance + Vec3::Y * HOLD_HEIGHT;
+            velocity.0 = ((target - prop_position) / dt).clamp_length_max(MAX_CATCH_UP_SPEED);
+            angular_velocity.0 = Vec3::ZERO;
+        }
+    }
+}
+
+fn display_refusal_toast(
+    time: UnscaledTime,
+    mut refusal: ResMut<CarryRefusal>,
+    ui_layers: Res<UiLayers>,
+    strings: Strings,
+    mut egui_contexts: EguiContexts,
+) {
+    if refusal.0 <= 0. {
+        return;
+    }
+    refusal.0 -= time.delta_seconds();
+    if !ui_layers.is_visible(UiLayer::Hud) {
+        return;
+    }
+    // Fades out over the last second
+    let alpha = (refusal.0.clamp(0., 1.) * 255.) as u8;
+    egui::Area::new("Carry Refusal")
+        .order(UiLayer::Hud.order())
+        .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0., 60.))
+        .interactable(false)
+        .show(egui_contexts.ctx_mut(), |ui| {
+            ui.label(
+                egui::RichText::new(t!(strings, "carry.too_heavy"))
+                    .size(20.)
+                    .color(egui::Color32::from_white_alpha(alpha)),
+            );
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn carryability_depends_on_the_heavier_of_mass_and_volume() {
+        let strength = Strength::default();
+        let weight = |mass, volume| CarryWeight { mass, volume };
+
+        assert_eq!(strength.carryability(&weight(5., 0.1)), Carryability::Take);
+        assert_eq!(strength.carryability(&weight(15., 0.4)), Carryability::Take);
+        // Light but bulky, e.g. an empty crate
+        assert_eq!(strength.carryability(&weight(2., 1.)), Carryability::Drag);
+        assert_eq!(strength.carryability(&weight(40., 0.2)), Carryability::Drag);
+        assert_eq!(
+            strength.carryability(&weight(80., 0.2)),
+            Carryability::TooHeavy
+        );
+        assert_eq!(
+            strength.carryability(&weight(2., 2.)),
+            Carryability::TooHeavy
+        );
+    }
+}
@@ -0,0 +1,587 @@
+use crate::{
+    determinism::{GameRng, RngStream},
+    movement::character_controller::{Sprinting, Walk},
+    util::math_trait_ext::Vec3Ext,
+    world_interaction::{
+        on_hit::ProjectileImpactEvent,
+        world_flags::{FlagCondition, WorldFlagChanged, WorldFlags},
+    },
+    GameState,
+};
+use bevy::{prelude::*, utils::HashMap};
+use bevy_xpbd_3d::prelude::*;
+use oxidized_navigation::NavMeshAffector;
+use serde::{Deserialize, Serialize};
+use std::iter;
+
+/// Lets fences, railings and other structures made of [`BreakableSegment`]s be broken apart.
+/// A segment is static level geometry until a hit with enough impulse breaks it. It then turns into a dynamic body in place:
+/// its colliders are kept and only change their layers in the same frame, so nothing can pass through it in between.
+/// Neighboring segments of the same structure may break along with it. The debris is cleared after a while, leaving a gap.
+///
+/// Hits come from:
+/// - Thrown props, see [`ProjectileImpactEvent`].
+/// - Characters slamming into a segment faster than they can run, e.g. when knocked back.
+/// - [`SegmentHitEvent`]s sent by other systems.
+///
+/// Broken segments are remembered by their level, so gaps persist when coming back.
+/// [`RepairWhenFlags`] and [`RepairStructureEvent`] put the segments of a structure back up, e.g. for puzzles.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<BreakableSegment>()
+        .register_type::<RepairWhenFlags>()
+        .add_event::<SegmentHitEvent>()
+        .add_event::<StructureBrokenEvent>()
+        .add_event::<RepairStructureEvent>()
+        .add_systems(
+            Update,
+            (
+                detect_segment_hits,
+                break_segments,
+                clear_debris,
+                restore_gaps,
+                send_flag_repairs,
+                repair_structures,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// A part of a fence or railing that breaks when hit hard enough. Put it on a static body with [`Collider`]s on it or below it.
+/// The segments of one structure are children of the same entity.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct BreakableSegment {
+    /// The impulse a single hit needs to break the segment, in N·s.
+    pub(crate) break_impulse: f32,
+    /// The density of the debris, in kg/m³.
+    pub(crate) density: f32,
+    /// How likely each neighbor breaks along with this segment. Between 0 and 1.
+    pub(crate) chain_chance: f32,
+    /// Every segment further down a chain is this much less likely to break along, e.g. 0.5 halves the chance each time.
+    pub(crate) chain_falloff: f32,
+    /// Segments of the same structure whose origins are at most this far apart are neighbors, in meters.
+    pub(crate) neighbor_distance: f32,
+    /// How long the debris lies around before it is cleared, in seconds.
+    pub(crate) debris_lifetime: f32,
+}
+
+impl Default for BreakableSegment {
+    fn default() -> Self {
+        Self {
+            break_impulse: 400.,
+            density: 600.,
+            chain_chance: 0.5,
+            chain_falloff: 0.5,
+            neighbor_distance: 2.5,
+            debris_lifetime: 10.,
+        }
+    }
+}
+
+/// Repairs all broken segments of the structure it is on whenever its condition starts being met.
+/// Put it on the structure or on a single segment.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+pub(crate) struct RepairWhenFlags(pub(crate) FlagCondition);
+
+/// Hits a [`BreakableSegment`], which breaks if the `impulse` is at least its [`BreakableSegment::break_impulse`].
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub(crate) struct SegmentHitEvent {
+    /// The segment or one of its colliders.
+    pub(crate) target: Entity,
+    /// In N·s. The debris is pushed along it.
+    pub(crate) impulse: Vec3,
+    /// What dealt the hit, e.g. the thrower of a prop.
+    pub(crate) by: Option<Entity>,
+}
+
+/// Sent for every segment that breaks, including those that break along with a neighbor.
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub(crate) struct StructureBrokenEvent {
+    pub(crate) segment: Entity,
+    /// The parent of the segment, or the segment itself if it stands alone.
+    pub(crate) structure: Entity,
+    pub(crate) by: Option<Entity>,
+    /// Whether the segment broke along with a neighbor instead of being hit.
+    pub(crate) chained: bool,
+}
+
+/// Puts all broken segments at or below the `structure` back up.
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub(crate) struct RepairStructureEvent {
+    pub(crate) structure: Entity,
+}
+
+/// On a [`BreakableSegment`] that is broken. While there is debris, the segment is a dynamic body; afterwards it is a hidden gap.
+/// Inserting [`BrokenSegment::gap`] turns an intact segment into a gap right away, e.g. when its level is restored.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub(crate) struct BrokenSegment {
+    /// Seconds until the debris is cleared. `None` once only the gap is left.
+    debris_left: Option<f32>,
+    /// How the intact segment stood relative to its parent and looked. `None` until it is stashed.
+    intact: Option<(Transform, Visibility)>,
+    colliders: Vec<StashedCollider>,
+}
+
+impl BrokenSegment {
+    pub(crate) fn gap() -> Self {
+        Self {
+            debris_left: None,
+            intact: None,
+            colliders: Vec::new(),
+        }
+    }
+}
+
+/// A collider of an intact segment, kept to restore it on repair.
+#[derive(Debug, Clone, PartialEq)]
+struct StashedCollider {
+    entity: Entity,
+    layers: CollisionLayers,
+    density: Option<ColliderDensity>,
+    nav_mesh_affector: bool,
+}
+
+/// What [`StashedCollider`] keeps of a collider.
+type StashedComponents = (
+    &'static CollisionLayers,
+    Option<&'static ColliderDensity>,
+    Has<NavMeshAffector>,
+);
+
+/// Whether the condition of a [`RepairWhenFlags`] was met when the flags last changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+struct RepairConditionMet(bool);
+
+fn detect_segment_hits(
+    mut collision_events: EventReader<CollisionStarted>,
+    mut impact_events: EventReader<ProjectileImpactEvent>,
+    segments: Query<(), (With<BreakableSegment>, Without<BrokenSegment>)>,
+    parents: Query<&Parent>,
+    characters: Query<(Entity, &Walk, Option<&Sprinting>, &LinearVelocity)>,
+    masses: Query<&Mass>,
+    transforms: Query<&GlobalTransform>,
+    mut hit_events: EventWriter<SegmentHitEvent>,
+    // Physics has already stopped a character by the time its collision is reported, so its speed is taken from before
+    mut speeds_before_step: Local<HashMap<Entity, Vec3>>,
+) {
+    let is_segment = |entity: Entity| {
+        iter::once(entity)
+            .chain(parents.iter_ancestors(entity))
+            .any(|entity| segments.contains(entity))
+    };
+    for impact in impact_events.read() {
+        if !is_segment(impact.target) {
+            continue;
+        }
+        let (Ok(projectile), Ok(target)) = (
+            transforms.get(impact.projectile),
+            transforms.get(impact.target),
+        ) else {
+            continue;
+        };
+        let direction = (target.translation() - projectile.translation()).normalize_or_zero();
+        let mass = masses.get(impact.projectile).map_or(1., |mass| mass.0);
+        hit_events.send(SegmentHitEvent {
+            target: impact.target,
+            impulse: direction * impact.speed * mass,
+            by: Some(impact.thrower),
+        });
+    }
+    for CollisionStarted(entity1, entity2) in collision_events.read() {
+        for (character, target) in [(*entity1, *entity2), (*entity2, *entity1)] {
+            let Ok((_, walk, sprinting, ..)) = characters.get(character) else {
+                continue;
+            };
+            let Some(&velocity) = speeds_before_step.get(&character) else {
+                continue;
+            };
+            if !is_segment(target) {
+                continue;
+            }
+            // Only what goes beyond running into the segment counts, so that characters do not break it just by walking
+            let top_speed = walk.speed * sprinting.map_or(1., |sprinting| sprinting.multiplier);
+            let excess = velocity.length() - top_speed;
+            if excess <= 0. {
+                continue;
+            }
+            let mass = masses.get(character).map_or(1., |mass| mass.0);
+            hit_events.send(SegmentHitEvent {
+                target,
+                impulse: velocity.normalize_or_zero() * excess * mass,
+                by: Some(character),
+            });
+        }
+    }
+    speeds_before_step.clear();
+    for (character, .., velocity) in characters.iter() {
+        // Landing on a segment should not break it
+        speeds_before_step.insert(character, velocity.0.horizontal());
+    }
+}
+
+fn break_segments(
+    mut commands: Commands,
+    mut hit_events: EventReader<SegmentHitEvent>,
+    segments: Query<
+        (&BreakableSegment, &Transform, &Visibility, Option<&Parent>),
+        Without<BrokenSegment>,
+    >,
+    parents: Query<&Parent>,
+    children: Query<&Children>,
+    colliders: Query<StashedComponents, With<Collider>>,
+    game_rng: Res<GameRng>,
+    mut rng: Local<Option<RngStream>>,
+    mut broken_events: EventWriter<StructureBrokenEvent>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("break_segments").entered();
+    let rng = rng.get_or_insert_with(|| game_rng.fork("destructible"));
+    // Commands only take effect next frame, so a segment hit twice in one frame would break twice
+    let mut broken = Vec::new();
+    for hit in hit_events.read() {
+        let Some((segment, breakable)) = iter::once(hit.target)
+            .chain(parents.iter_ancestors(hit.target))
+            .find_map(|entity| Some((entity, segments.get(entity).ok()?.0)))
+        else {
+            continue;
+        };
+        if broken.contains(&segment) || hit.impulse.length() < breakable.break_impulse {
+            continue;
+        }
+        // Neighbors that break along, with the chance to break along of their own neighbors
+        let mut chain = vec![(segment, hit.impulse, false, breakable.chain_chance)];
+        while let Some((segment, impulse, chained, chain_chance)) = chain.pop() {
+            if broken.contains(&segment) {
+                continue;
+            }
+            let Ok((breakable, transform, visibility, parent)) = segments.get(segment) else {
+                continue;
+            };
+            broken.push(segment);
+            let stashed = stash_colliders(segment, &children, &colliders);
+            for collider in &stashed {
+                // Debris collides with the ground and props like any other prop
+                let mut collider_commands = commands.entity(collider.entity);
+                collider_commands
+                    .insert((
+                        CollisionLayers::default(),
+                        ColliderDensity(breakable.density),
+                    ))
+                    .remove::<NavMeshAffector>();
+            }
+            commands.entity(segment).insert((
+                RigidBody::Dynamic,
+                ExternalImpulse::new(impulse),
+                BrokenSegment {
+                    debris_left: Some(breakable.debris_lifetime),
+                    intact: Some((*transform, *visibility)),
+                    colliders: stashed,
+                },
+            ));
+            let structure = parent.map_or(segment, |parent| parent.get());
+            broken_events.send(StructureBrokenEvent {
+                segment,
+                structure,
+                by: hit.by,
+                chained,
+            });
+
+            let Some(parent) = parent else {
+                continue;
+            };
+            for &neighbor in children.get(parent.get()).into_iter().flatten() {
+                let Ok((_, neighbor_transform, ..)) = segments.get(neighbor) else {
+                    continue;
+                };
+                let distance = neighbor_transform
+                    .translation
+                    .distance(transform.translation);
+                if broken.contains(&neighbor)
+                    || distance > breakable.neighbor_distance
+                    || !rng.chance(chain_chance)
+                {
+                    continue;
+                }
+                chain.push((
+                    neighbor,
+                    impulse * breakable.chain_falloff,
+                    true,
+                    chain_chance * breakable.chain_falloff,
+                ));
+            }
+        }
+    }
+}
+
+/// The colliders on a segment and below it.
+fn stash_colliders(
+    segment: Entity,
+    children: &Query<&Children>,
+    colliders: &Query<StashedComponents, With<Collider>>,
+) -> Vec<StashedCollider> {
+    iter::once(segment)
+        .chain(children.iter_descendants(segment))
+        .filter_map(|entity| {
+            let (layers, density, nav_mesh_affector) = colliders.get(entity).ok()?;
+            Some(StashedCollider {
+                entity,
+                layers: *layers,
+                density: density.copied(),
+                nav_mesh_affector,
+            })
+        })
+        .collect()
+}
+
+/// Leaves only a gap where the segment stood: nothing collides with it and it is hidden, but it is kept for repairs.
+fn to_gap(commands: &mut Commands, segment: Entity, broken: &BrokenSegment) {
+    for collider in &broken.colliders {
+        if let Some(mut collider_commands) = commands.get_entity(collider.entity) {
+            collider_commands
+                .insert(CollisionLayers::NONE)
+                .remove::<NavMeshAffector>();
+        }
+    }
+    let mut segment_commands = commands.entity(segment);
+    segment_commands
+        .insert((RigidBody::Static, Visibility::Hidden))
+        .remove::<ExternalImpulse>();
+    if let Some((transform, _)) = broken.intact {
+        segment_commands.insert(transform);
+    }
+}
+
+fn clear_debris(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut broken_segments: Query<(Entity, &mut BrokenSegment)>,
+) {
+    for (segment, mut broken) in broken_segments.iter_mut() {
+        let Some(debris_left) = broken.debris_left.as_mut() else {
+            continue;
+        };
+        *debris_left -= time.delta_seconds();
+        if *debris_left <= 0. {
+            broken.debris_left = None;
+            to_gap(&mut commands, segment, &broken);
+        }
+    }
+}
+
+/// Turns segments that got a [`BrokenSegment::gap`] into gaps, stashing how they were first.
+fn restore_gaps(
+    mut commands: Commands,
+    mut broken_segments: Query<
+        (Entity, &Transform, &Visibility, &mut BrokenSegment),
+        Added<BrokenSegment>,
+    >,
+    children: Query<&Children>,
+    colliders: Query<StashedComponents, With<Collider>>,
+) {
+    for (segment, transform, visibility, mut broken) in broken_segments.iter_mut() {
+        if broken.intact.is_some() {
+            continue;
+        }
+        broken.intact = Some((*transform, *visibility));
+        broken.colliders = stash_colliders(segment, &children, &colliders);
+        to_gap(&mut commands, segment, &broken);
+    }
+}
+
+fn send_flag_repairs(
+    mut commands: Commands,
+    mut flag_events: EventReader<WorldFlagChanged>,
+    flags: Res<WorldFlags>,
+    new_receivers: Query<(Entity, &RepairWhenFlags), Without<RepairConditionMet>>,
+    mut receivers: Query<(Entity, &RepairWhenFlags, &mut RepairConditionMet)>,
+    mut repair_events: EventWriter<RepairStructureEvent>,
+) {
+    // Only a change counts, so that segments broken while the condition is met stay broken
+    for (entity, repair) in new_receivers.iter() {
+        commands
+            .entity(entity)
+            .insert(RepairConditionMet(repair.0.is_met(&flags)));
+    }
+    if flag_events.read().count() == 0 {
+        return;
+    }
+    for (entity, repair, mut met) in receivers.iter_mut() {
+        let is_met = repair.0.is_met(&flags);
+        if is_met && !met.0 {
+            repair_events.send(RepairStructureEvent { structure: entity });
+        }
+        met.0 = is_met;
+    }
+}
+
+fn repair_structures(
+    mut commands: Commands,
+    mut repair_events: EventReader<RepairStructureEvent>,
+    parents: Query<&Parent>,
+    children: Query<&Children>,
+    segments: Query<&BreakableSegment>,
+    broken_segments: Query<&BrokenSegment>,
+) {
+    for event in repair_events.read() {
+        // A receiver on a single segment repairs its whole structure
+        let structure = match parents.get(event.structure) {
+            Ok(parent) if segments.contains(event.structure) => parent.get(),
+            _ => event.structure,
+        };
+        for segment in iter::once(structure).chain(children.iter_descendants(structure)) {
+            let Ok(broken) = broken_segments.get(segment) else {
+                continue;
+            };
+            for collider in &broken.colliders {
+                let Some(mut collider_commands) = commands.get_entity(collider.entity) else {
+                    continue;
+                };
+                collider_commands.insert(collider.layers);
+                match collider.density {
+                    Some(density) => collider_commands.insert(density),
+                    None => collider_commands.remove::<ColliderDensity>(),
+                };
+                if collider.nav_mesh_affector {
+                    collider_commands.insert(NavMeshAffector);
+                }
+            }
+            let mut segment_commands = commands.entity(segment);
+            segment_commands
+                .insert((
+                    RigidBody::Static,
+                    LinearVelocity::ZERO,
+                    AngularVelocity::ZERO,
+                ))
+                .remove::<(BrokenSegment, ExternalImpulse)>();
+            if let Some((transform, visibility)) = broken.intact {
+                segment_commands.insert((transform, visibility));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{movement::physics::CollisionLayer, testing::TestApp};
+
+    fn spawn_fence(app: &mut TestApp, segment: BreakableSegment) -> (Entity, Vec<Entity>) {
+        let fence = app
+            .world_mut()
+            .spawn((Name::new("Fence"), SpatialBundle::default()))
+            .id();
+        let segments = (0..3)
+            .map(|index| {
+                let collider = app
+                    .world_mut()
+                    .spawn((
+                        TransformBundle::default(),
+                        Collider::cuboid(2., 1., 0.1),
+                        CollisionLayers::new(
+                            [CollisionLayer::Terrain],
+                            [CollisionLayer::Character],
+                        ),
+                        NavMeshAffector,
+                    ))
+                    .id();
+                app.world_mut()
+                    .spawn((
+                        segment,
+                        SpatialBundle::from_transform(Transform::from_xyz(
+                            index as f32 * 2.,
+                            0.5,
+                            0.,
+                        )),
+                        RigidBody::Static,
+                    ))
+                    .add_child(collider)
+                    .set_parent(fence)
+                    .id()
+            })
+            .collect();
+        (fence, segments)
+    }
+
+    fn is_static(app: &TestApp, segment: Entity) -> bool {
+        app.world().get::<RigidBody>(segment) == Some(&RigidBody::Static)
+    }
+
+    #[test]
+    fn broken_segments_leave_a_gap_until_repaired() {
+        let mut app = TestApp::new();
+        app.add_plugins(plugin)
+            .insert_resource(GameRng::new(7))
+            .add_event::<ProjectileImpactEvent>()
+            .add_event::<WorldFlagChanged>()
+            .record_events::<StructureBrokenEvent>();
+        app.spawn_ground();
+        let (fence, segments) = spawn_fence(
+            &mut app,
+            BreakableSegment {
+                chain_chance: 1.,
+                chain_falloff: 1.,
+                debris_lifetime: 1.,
+                ..default()
+            },
+        );
+        app.step(1);
+
+        app.send_event(SegmentHitEvent {
+            target: segments[0],
+            impulse: Vec3::Z * 100.,
+            by: None,
+        });
+        app.step(1);
+        assert!(segments.iter().all(|&segment| is_static(&app, segment)));
+
+        app.send_event(SegmentHitEvent {
+            target: segments[1],
+            impulse: Vec3::Z * 500.,
+            by: None,
+        });
+        app.step(1);
+        // Both neighbors break along, but the hit segment is the only one not chained
+        let events = app.events::<StructureBrokenEvent>();
+        assert_eq!(events.len(), 3);
+        assert!(events.iter().all(|event| event.structure == fence));
+        assert_eq!(events.iter().filter(|event| !event.chained).count(), 1);
+        assert!(segments.iter().all(|&segment| !is_static(&app, segment)));
+
+        app.step(90);
+        for &segment in &segments {
+            assert!(is_static(&app, segment));
+            assert_eq!(
+                app.world().get::<Visibility>(segment),
+                Some(&Visibility::Hidden)
+            );
+            let collider = app.world().get::<Children>(segment).unwrap()[0];
+            assert_eq!(
+                app.world().get::<CollisionLayers>(collider),
+                Some(&CollisionLayers::NONE)
+            );
+        }
+
+        app.send_event(RepairStructureEvent { structure: fence });
+        app.step(1);
+        for &segment in &segments {
+            assert!(is_static(&app, segment));
+            assert!(app.world().get::<BrokenSegment>(segment).is_none());
+            assert_eq!(
+                app.world().get::<Visibility>(segment),
+                Some(&Visibility::Inherited)
+            );
+            let collider = app.world().get::<Children>(segment).unwrap()[0];
+            assert!(app.world().get::<NavMeshAffector>(collider).is_some());
+            assert_eq!(
+                app.world().get::<CollisionLayers>(collider),
+                Some(&CollisionLayers::new(
+                    [CollisionLayer::Terrain],
+                    [CollisionLayer::Character]
+                ))
+            );
+        }
+    }
+}
@@ -1,29 +1,60 @@
-use crate::player_control::{actions::ActionsFrozen, camera::IngameCamera};
+use crate::{
+    file_system_interaction::{config::GameConfig, localization},
+    level_instantiation::stable_id::StableId,
+    player_control::{
+        actions::{ActionsFrozen, PlayerAction},
+        camera::{CursorGrabRequests, IngameCamera},
+        ui_layer::{receives_input, UiLayer, UiLayers},
+    },
+};
 use bevy::prelude::*;
 use bevy_egui::EguiPlugin;
-use bevy_yarnspinner::{events::DialogueCompleteEvent, prelude::*};
+use bevy_yarnspinner::{
+    events::{DialogueCompleteEvent, PresentLineEvent, PresentOptionsEvent},
+    prelude::*,
+};
 use bevy_yarnspinner_example_dialogue_view::{prelude::*, UiRootNode};
-use leafwing_input_manager::plugin::InputManagerSystem;
+use leafwing_input_manager::{plugin::InputManagerSystem, prelude::ActionState};
 use serde::{Deserialize, Serialize};
 
+pub(crate) mod commands;
+pub(crate) mod small_talk;
+pub(crate) mod stage_directions;
+
 pub(super) fn plugin(app: &mut App) {
-    app.add_plugins((
-        EguiPlugin,
-        YarnSpinnerPlugin::new(),
-        ExampleYarnSpinnerDialogueViewPlugin::new(),
-    ))
-    .add_systems(
-        Update,
-        (
-            spawn_dialogue_runner.run_if(resource_added::<YarnProject>),
-            unfreeze_after_dialog.after(InputManagerSystem::ManualControl),
-            set_ui_target_camera,
+    app.init_resource::<YarnCommandRegistrations>()
+        .add_plugins((
+            EguiPlugin,
+            YarnSpinnerPlugin::new().with_localizations(localization::dialogue_localizations()),
+            ExampleYarnSpinnerDialogueViewPlugin::new(),
+            commands::plugin,
+            small_talk::plugin,
+            stage_directions::plugin,
+        ))
+        .add_systems(
+            Update,
+            (
+                spawn_dialogue_runner.run_if(resource_added::<YarnProject>),
+                unfreeze_after_dialog.after(InputManagerSystem::ManualControl),
+                clear_dialog_context,
+                set_ui_target_camera,
+                auto_advance_dialog.run_if(resource_exists::<GameConfig>),
+                show_dialog_layer,
+            )
+                .after(ExampleYarnSpinnerDialogueViewSystemSet),
+        )
+        // E.g. the pause menu opened during a dialog should not advance it
+        .configure_sets(
+            Update,
+            ExampleYarnSpinnerDialogueViewSystemSet.run_if(receives_input(UiLayer::GameplayModal)),
         )
-            .after(ExampleYarnSpinnerDialogueViewSystemSet),
-    )
-    .init_resource::<CurrentDialogTarget>()
-    .register_type::<YarnNode>()
-    .register_type::<CurrentDialogTarget>();
+        .init_resource::<CurrentDialogTarget>()
+        .init_resource::<DialogInitiator>()
+        .init_resource::<DialogContext>()
+        .register_type::<YarnNode>()
+        .register_type::<DialogContextVariable>()
+        .register_type::<CurrentDialogTarget>()
+        .register_type::<DialogInitiator>();
 }
 
 #[derive(Component, Debug, Clone, Eq, PartialEq, Reflect, Serialize, Deserialize)]
@@ -34,9 +65,83 @@ pub(crate) struct YarnNode(pub(crate) String);
 #[reflect(Resource, Serialize, Deserialize)]
 pub(crate) struct CurrentDialogTarget(pub(crate) Option<Entity>);
 
-fn spawn_dialogue_runner(mut commands: Commands, project: Res<YarnProject>) {
+/// The player who started the running dialog. Only this player is frozen and turned towards the [`CurrentDialogTarget`],
+/// so that other local players can keep playing.
+#[derive(Resource, Debug, Clone, Copy, Eq, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct DialogInitiator(pub(crate) Option<Entity>);
+
+/// Lets one yarn node talk about different objects, e.g. `It's {$subject}.` or `<<if $subject == "Hammer">>`.
+/// Starting the dialog of the entity's [`YarnNode`] sets this yarn variable to the entity's name,
+/// and the variable with `_id` appended to its [`StableId`]. Both are set back to empty strings when the dialog ends.
+/// The leading `$` of the variable name is optional.
+#[derive(Component, Debug, Clone, Eq, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct DialogContextVariable(pub(crate) String);
+
+/// The yarn variables set through a [`DialogContextVariable`] for the running dialog.
+#[derive(Resource, Debug, Clone, Default)]
+pub(crate) struct DialogContext {
+    variables: Vec<String>,
+}
+
+impl DialogContext {
+    /// Writes the `name` and `id` of the dialog's subject into the variables of `context` before its dialog starts.
+    /// A missing name or id is written as an empty string, so that yarn can check for it.
+    pub(crate) fn set(
+        &mut self,
+        dialogue_runner: &mut DialogueRunner,
+        context: &DialogContextVariable,
+        name: Option<&Name>,
+        id: Option<&StableId>,
+    ) {
+        let variable = format!("${}", context.0.trim_start_matches('$'));
+        let values = [
+            (variable.clone(), name.map(|name| name.to_string())),
+            (format!("{variable}_id"), id.map(|id| id.0.to_string())),
+        ];
+        for (variable, value) in values {
+            let value = value.unwrap_or_default();
+            match dialogue_runner
+                .variable_storage_mut()
+                .set(variable.clone(), value.into())
+            {
+                Ok(()) => self.variables.push(variable),
+                Err(error) => error!("Failed to set the dialog context {variable}: {error:?}"),
+            }
+        }
+    }
+}
+
+/// Functions that register custom yarn commands on the dialogue runner once it is created.
+#[derive(Resource, Default)]
+struct YarnCommandRegistrations(Vec<fn(&mut DialogueRunner)>);
+
+pub(crate) trait YarnCommandsAppExt {
+    /// Registers custom yarn commands, e.g. `<<spawn Wall 3 0 2>>`, through `DialogueRunner::commands_mut`.
+    fn add_yarn_commands(&mut self, register: fn(&mut DialogueRunner)) -> &mut Self;
+}
+
+impl YarnCommandsAppExt for App {
+    fn add_yarn_commands(&mut self, register: fn(&mut DialogueRunner)) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(YarnCommandRegistrations::default)
+            .0
+            .push(register);
+        self
+    }
+}
+
+fn spawn_dialogue_runner(
+    mut commands: Commands,
+    project: Res<YarnProject>,
+    registrations: Res<YarnCommandRegistrations>,
+) {
     // Create a dialogue runner from the project.
-    let dialogue_runner = project.create_dialogue_runner();
+    let mut dialogue_runner = project.create_dialogue_runner();
+    for register in &registrations.0 {
+        register(&mut dialogue_runner);
+    }
     // Immediately start showing the dialogue to the player
     commands.spawn(dialogue_runner);
 }
@@ -44,11 +149,54 @@ fn spawn_dialogue_runner(mut commands: Commands, project: Res<YarnProject>) {
 fn unfreeze_after_dialog(
     mut dialogue_complete_event: EventReader<DialogueCompleteEvent>,
     mut dialog_target: ResMut<CurrentDialogTarget>,
+    mut dialog_initiator: ResMut<DialogInitiator>,
     mut freeze: ResMut<ActionsFrozen>,
+    mut cursor_grab: ResMut<CursorGrabRequests>,
+    mut player_actions: Query<(Entity, &mut ActionState<PlayerAction>)>,
 ) {
     for _event in dialogue_complete_event.read() {
         dialog_target.0 = None;
-        freeze.unfreeze();
+        let initiator = dialog_initiator.0.take();
+        match initiator {
+            Some(initiator) => freeze.unfreeze_player(initiator),
+            None => freeze.unfreeze(),
+        }
+        cursor_grab.release();
+        // The press that advanced past the last line should not also make the player jump or talk again
+        for (player, mut actions) in player_actions.iter_mut() {
+            if initiator.is_some_and(|initiator| initiator != player) {
+                continue;
+            }
+            actions.consume(&PlayerAction::Jump);
+            actions.consume(&PlayerAction::Interact);
+        }
+    }
+}
+
+/// Keeps the subject of one dialog from leaking into the next one.
+fn clear_dialog_context(
+    mut dialogue_complete_events: EventReader<DialogueCompleteEvent>,
+    mut context: ResMut<DialogContext>,
+    mut dialogue_runners: Query<&mut DialogueRunner>,
+) {
+    if dialogue_complete_events.read().count() == 0 || context.variables.is_empty() {
+        return;
+    }
+    for variable in context.variables.drain(..) {
+        for mut dialogue_runner in dialogue_runners.iter_mut() {
+            if let Err(error) = dialogue_runner
+                .variable_storage_mut()
+                .set(variable.clone(), String::new().into())
+            {
+                error!("Failed to clear the dialog context {variable}: {error:?}");
+            }
+        }
+    }
+}
+
+fn show_dialog_layer(dialogue_runners: Query<&DialogueRunner>, mut ui_layers: ResMut<UiLayers>) {
+    if dialogue_runners.iter().any(DialogueRunner::is_running) {
+        ui_layers.show(UiLayer::GameplayModal);
     }
 }
 
@@ -65,3 +213,42 @@ fn set_ui_target_camera(
         }
     }
 }
+
+/// The line waiting to be auto-advanced and how long it has been shown.
+#[derive(Debug, Clone, Default)]
+struct AutoAdvance {
+    line: Option<(String, f32)>,
+}
+
+fn auto_advance_dialog(
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    mut line_events: EventReader<PresentLineEvent>,
+    mut option_events: EventReader<PresentOptionsEvent>,
+    mut dialogue_complete_events: EventReader<DialogueCompleteEvent>,
+    mut dialogue_runners: Query<&mut DialogueRunner>,
+    mut auto_advance: Local<AutoAdvance>,
+) {
+    for event in line_events.read() {
+        auto_advance.line = Some((event.line.text.clone(), 0.));
+    }
+    // Choosing an option is always up to the player
+    if option_events.read().count() > 0 || dialogue_complete_events.read().count() > 0 {
+        auto_advance.line = None;
+    }
+    let Some((text, elapsed)) = auto_advance.line.as_mut() else {
+        return;
+    };
+    *elapsed += time.delta_seconds();
+    if !config.accessibility.auto_advance_dialog
+        || *elapsed < config.accessibility.reading_time(text)
+    {
+        return;
+    }
+    for mut dialogue_runner in dialogue_runners.iter_mut() {
+        if dialogue_runner.is_running() {
+            dialogue_runner.continue_in_next_update();
+        }
+    }
+    auto_advance.line = None;
+}
@@ -0,0 +1,293 @@
+use crate::{
+    level_instantiation::{
+        named_entities::EntityNames, on_spawn::Player, spawn_queue::SpawnRequest,
+    },
+    movement::character_controller::{Depenetrate, PlayOneShotAnimation},
+    world_interaction::{
+        dialog::{CurrentDialogTarget, YarnCommandsAppExt},
+        interaction_ui::InteractRequestEvent,
+        time_of_day::TimeOfDay,
+    },
+};
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use bevy_yarnspinner::{
+    events::{NodeStartEvent, PresentLineEvent},
+    prelude::*,
+};
+
+/// Registers the yarn commands that let dialog drive gameplay:
+/// - `<<spawn blueprint_name x y z>>` spawns a blueprint from the `scenes/library` folder.
+/// - `<<play_anim entity_name animation_name>>` plays an animation of a character once.
+/// - `<<teleport_player entity_name>>` moves the player to the position of an entity.
+/// - `<<interact initiator_name target_name>>` makes a character use an interactable, e.g. sit down on a bench.
+/// - `<<despawn_target>>` despawns what the player is talking to, e.g. a prop that is picked up during the dialog.
+/// - `<<set_time hour>>` sets the time of day, e.g. `<<set_time 21.5>>` for half past nine in the evening.
+///
+/// Commands taking an entity name also accept the name of a dialog's subject, e.g. `<<play_anim {$subject} Wobble>>`.
+///
+/// Other plugins can add their own commands via [`YarnCommandsAppExt::add_yarn_commands`],
+/// e.g. [`inventory`](crate::world_interaction::inventory) adds `<<give_item>>` and `<<take_item>>`.
+/// There is deliberately no `<<start_quest>>`, since the game has no quests yet.
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<DialogPosition>()
+        .add_yarn_commands(register_commands)
+        .add_systems(Update, track_dialog_position);
+}
+
+fn register_commands(dialogue_runner: &mut DialogueRunner) {
+    dialogue_runner
+        .commands_mut()
+        .add_command("spawn", spawn)
+        .add_command("play_anim", play_anim)
+        .add_command("teleport_player", teleport_player)
+        .add_command("interact", interact)
+        .add_command("despawn_target", despawn_target)
+        .add_command("set_time", set_time);
+}
+
+/// Where the dialogue runner currently is, used to give context to errors in commands.
+#[derive(Debug, Clone, PartialEq, Resource, Default)]
+pub(crate) struct DialogPosition {
+    pub(crate) node: String,
+    pub(crate) line: Option<String>,
+}
+
+impl std::fmt::Display for DialogPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.line {
+            Some(line) => write!(f, "node \"{}\", after line {}", self.node, line),
+            None => write!(f, "node \"{}\", before the first line", self.node),
+        }
+    }
+}
+
+fn track_dialog_position(
+    mut node_start_events: EventReader<NodeStartEvent>,
+    mut present_line_events: EventReader<PresentLineEvent>,
+    mut position: ResMut<DialogPosition>,
+) {
+    for event in node_start_events.read() {
+        position.node.clone_from(&event.node_name);
+        position.line = None;
+    }
+    for event in present_line_events.read() {
+        position.line = Some(event.line.id.0.clone());
+    }
+}
+
+fn spawn(
+    In((blueprint, x, y, z)): In<(String, f32, f32, f32)>,
+    mut spawn_requests: EventWriter<SpawnRequest>,
+) {
+    spawn_requests.send(SpawnRequest::new(blueprint, Transform::from_xyz(x, y, z)));
+}
+
+fn play_anim(
+    In((entity_name, animation)): In<(String, String)>,
+    names: EntityNames,
+    position: Res<DialogPosition>,
+    mut one_shot_events: EventWriter<PlayOneShotAnimation>,
+) {
+    let context = format!("<<play_anim>> in {}", *position);
+    let Some(entity) = names.get_or_report(&entity_name, context) else {
+        return;
+    };
+    one_shot_events.send(PlayOneShotAnimation { entity, animation });
+}
+
+fn teleport_player(
+    In(target_name): In<String>,
+    mut commands: Commands,
+    names: EntityNames,
+    targets: Query<&GlobalTransform>,
+    mut player_query: Query<(Entity, &mut Transform, &mut LinearVelocity), With<Player>>,
+    position: Res<DialogPosition>,
+) {
+    let context = format!("<<teleport_player>> in {}", *position);
+    let Some(target) = names
+        .get_or_report(&target_name, context)
+        .and_then(|entity| targets.get(entity).ok())
+    else {
+        return;
+    };
+    for (entity, mut transform, mut velocity) in player_query.iter_mut() {
+        transform.translation = target.translation();
+        velocity.0 = Vec3::ZERO;
+        commands.entity(entity).insert(Depenetrate::default());
+    }
+}
+
+fn interact(
+    In((initiator_name, target_name)): In<(String, String)>,
+    names: EntityNames,
+    position: Res<DialogPosition>,
+    mut interact_requests: EventWriter<InteractRequestEvent>,
+) {
+    let context = format!("<<interact>> in {}", *position);
+    let Some(initiator) = names.get_or_report(&initiator_name, &context) else {
+        return;
+    };
+    let Some(target) = names.get_or_report(&target_name, &context) else {
+        return;
+    };
+    interact_requests.send(InteractRequestEvent {
+        initiator,
+        target,
+        by_hit: false,
+    });
+}
+
+fn despawn_target(
+    mut commands: Commands,
+    mut dialog_target: ResMut<CurrentDialogTarget>,
+    position: Res<DialogPosition>,
+) {
+    // Clearing the target also keeps the speaker from turning to an entity that is gone
+    let Some(target) = dialog_target.0.take() else {
+        error!(
+            "<<despawn_target>> in {}: the player is not talking to anything",
+            *position
+        );
+        return;
+    };
+    if let Some(target) = commands.get_entity(target) {
+        target.despawn_recursive();
+    }
+}
+
+fn set_time(In(hour): In<f32>, mut time_of_day: ResMut<TimeOfDay>, position: Res<DialogPosition>) {
+    if !(0. ..24.).contains(&hour) {
+        error!(
+            "<<set_time {hour}>> in {}: the hour must be at least 0 and less than 24",
+            *position
+        );
+        return;
+    }
+    time_of_day.hour = hour;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        level_instantiation::named_entities,
+        testing::TestApp,
+        world_interaction::{
+            dialog::spawn_dialogue_runner,
+            inventory::{self, Inventory},
+            time_of_day::TimeOfDay,
+        },
+    };
+
+    const COMMANDS: &str = "title: Commands
+---
+<<spawn Wall 3 0 2>>
+<<play_anim Npc Wobble>>
+<<teleport_player Bench>>
+<<interact Npc Bench>>
+<<despawn_target>>
+<<give_item apple 3>>
+<<take_item apple 1>>
+<<set_time 21.5>>
+<<set_time 25>>
+===
+";
+
+    fn dialog_app() -> TestApp {
+        let mut app = TestApp::new();
+        app.add_plugins((
+            YarnSpinnerPlugin::with_yarn_source(YarnFileSource::InMemory(YarnFile::new(
+                "commands.yarn",
+                COMMANDS,
+            ))),
+            named_entities::plugin,
+            inventory::plugin,
+            plugin,
+        ))
+        .add_event::<SpawnRequest>()
+        .add_event::<InteractRequestEvent>()
+        .init_resource::<TimeOfDay>()
+        .add_systems(
+            Update,
+            spawn_dialogue_runner.run_if(resource_added::<YarnProject>),
+        )
+        .record_events::<SpawnRequest>()
+        .record_events::<PlayOneShotAnimation>()
+        .record_events::<InteractRequestEvent>();
+        // The project is compiled from the in-memory file over the first few frames
+        for _ in 0..30 {
+            if app
+                .world_mut()
+                .query::<&DialogueRunner>()
+                .iter(app.world())
+                .next()
+                .is_some()
+            {
+                return app;
+            }
+            app.step(1);
+        }
+        panic!("the yarn project was not compiled");
+    }
+
+    #[test]
+    fn dialog_runs_each_command() {
+        let mut app = dialog_app();
+        app.spawn_ground();
+        let player = app.spawn_player(Vec3::new(0., 1., 0.));
+        let npc = app
+            .world_mut()
+            .spawn((Name::new("Npc"), SpatialBundle::default()))
+            .id();
+        let bench = app
+            .world_mut()
+            .spawn((
+                Name::new("Bench"),
+                SpatialBundle::from_transform(Transform::from_xyz(4., 1., 2.)),
+            ))
+            .id();
+        let pickup = app
+            .world_mut()
+            .spawn((Name::new("Pickup"), SpatialBundle::default()))
+            .id();
+        app.world_mut().resource_mut::<CurrentDialogTarget>().0 = Some(pickup);
+        app.step(1);
+
+        app.world_mut()
+            .query::<&mut DialogueRunner>()
+            .single_mut(app.world_mut())
+            .start_node("Commands");
+        app.step(3);
+
+        assert_eq!(
+            app.events::<SpawnRequest>(),
+            [SpawnRequest::new("Wall", Transform::from_xyz(3., 0., 2.))]
+        );
+        assert_eq!(
+            app.events::<PlayOneShotAnimation>(),
+            [PlayOneShotAnimation {
+                entity: npc,
+                animation: "Wobble".to_string(),
+            }]
+        );
+        let translation = app.translation(player);
+        assert!(
+            translation.xz().distance(Vec2::new(4., 2.)) < 0.1,
+            "the player was not teleported to the bench: {translation}"
+        );
+        assert_eq!(
+            app.events::<InteractRequestEvent>(),
+            [InteractRequestEvent {
+                initiator: npc,
+                target: bench,
+                by_hit: false,
+            }]
+        );
+        assert!(app.world().get_entity(pickup).is_none());
+        assert_eq!(app.resource::<CurrentDialogTarget>().0, None);
+        assert_eq!(app.resource::<Inventory>().count("apple"), 2);
+        // The out of range `<<set_time 25>>` is rejected
+        assert_eq!(app.resource::<TimeOfDay>().hour, 21.5);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::{
+    determinism::{GameRng, RngStream},
+    level_instantiation::stable_id::{StableId, StableIdRegistry},
+    world_interaction::npc_memory::NpcMemory,
+};
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use serde::{Deserialize, Serialize};
+
+/// Lets a dialog target rotate through several yarn nodes instead of starting its [`YarnNode`](super::YarnNode) every time,
+/// so that NPCs do not greet the player or bark the same line over and over. See [`SmallTalk`].
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<SmallTalk>()
+        .register_type::<SmallTalkLine>()
+        .register_type::<SmallTalkHistory>();
+}
+
+/// The yarn nodes a dialog target picks from when talked to. Each node is used once, in the listed order.
+/// After that, each dialog picks one of the [`SmallTalkLine::repeatable`] nodes at random, weighted by [`SmallTalkLine::weight`].
+/// Without repeatable nodes, the target falls back to its [`YarnNode`](super::YarnNode).
+/// Which nodes were used is remembered in the NPC's [`NpcRecord`](crate::world_interaction::npc_memory::NpcRecord),
+/// so it persists in saves. Targets without a [`StableId`] only remember it until the level is left.
+#[derive(Component, Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct SmallTalk(pub(crate) Vec<SmallTalkLine>);
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct SmallTalkLine {
+    pub(crate) node: String,
+    /// Whether the node may be picked again once every node was used.
+    pub(crate) repeatable: bool,
+    /// How likely the node is picked, relative to the other repeatable nodes.
+    pub(crate) weight: f32,
+}
+
+impl Default for SmallTalkLine {
+    fn default() -> Self {
+        Self {
+            node: String::new(),
+            repeatable: false,
+            weight: 1.,
+        }
+    }
+}
+
+/// Which [`SmallTalk`] nodes a dialog target has already used.
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct SmallTalkHistory {
+    pub(crate) used: HashSet<String>,
+    /// How many nodes were picked in total. Seeds the random picks, so that they can be previewed.
+    pub(crate) picks: u32,
+}
+
+impl SmallTalk {
+    /// The node the next dialog starts, without remembering it as used. `None` means the [`YarnNode`](super::YarnNode).
+    /// Random picks depend only on `rng`, so the same stream previews exactly the pick that [`SmallTalk::pick`] makes.
+    pub(crate) fn peek(&self, history: &SmallTalkHistory, rng: &mut RngStream) -> Option<&str> {
+        if let Some(line) = self
+            .0
+            .iter()
+            .find(|line| !history.used.contains(&line.node))
+        {
+            return Some(&line.node);
+        }
+        let repeatable: Vec<_> = self
+            .0
+            .iter()
+            .filter(|line| line.repeatable && line.weight > 0.)
+            .collect();
+        let total: f32 = repeatable.iter().map(|line| line.weight).sum();
+        if total <= 0. {
+            return None;
+        }
+        let mut roll = rng.range_f32(0.0..total);
+        for line in &repeatable {
+            if roll < line.weight {
+                return Some(&line.node);
+            }
+            roll -= line.weight;
+        }
+        repeatable.last().map(|line| line.node.as_str())
+    }
+
+    /// Like [`SmallTalk::peek`], but remembers the node as used.
+    pub(crate) fn pick(
+        &self,
+        history: &mut SmallTalkHistory,
+        rng: &mut RngStream,
+    ) -> Option<String> {
+        let node = self.peek(history, rng)?.to_string();
+        history.used.insert(node.clone());
+        history.picks += 1;
+        Some(node)
+    }
+}
+
+/// What [`SmallTalkRotation::next_node`] picks next for the NPC with `id`, e.g. for checking a rotation while authoring it.
+/// `None` means the NPC's [`YarnNode`](super::YarnNode).
+pub(crate) fn preview_small_talk<'a>(
+    small_talk: &'a SmallTalk,
+    history: &SmallTalkHistory,
+    game_rng: &GameRng,
+    id: StableId,
+) -> Option<&'a str> {
+    let mut rng = small_talk_rng(game_rng, &stable_key(id), history);
+    small_talk.peek(history, &mut rng)
+}
+
+/// The random stream of a target's next pick. `key` identifies the target.
+fn small_talk_rng(game_rng: &GameRng, key: &str, history: &SmallTalkHistory) -> RngStream {
+    game_rng.fork(&format!("small_talk {key} {}", history.picks))
+}
+
+fn stable_key(id: StableId) -> String {
+    format!("{:?}", id.0)
+}
+
+/// Chooses which node a dialog target starts, see [`SmallTalk`].
+#[derive(SystemParam)]
+pub(crate) struct SmallTalkRotation<'w, 's> {
+    targets: Query<'w, 's, (&'static SmallTalk, Option<&'static Name>)>,
+    registry: Res<'w, StableIdRegistry>,
+    memory: ResMut<'w, NpcMemory>,
+    game_rng: Res<'w, GameRng>,
+    /// The history of targets without a [`StableId`], which cannot be kept in the [`NpcMemory`].
+    unremembered: Local<'s, HashMap<Entity, SmallTalkHistory>>,
+}
+
+impl SmallTalkRotation<'_, '_> {
+    /// The node to start for `target`, whose [`YarnNode`](super::YarnNode) is `default`.
+    pub(crate) fn next_node(&mut self, target: Entity, default: &str) -> String {
+        let Ok((small_talk, name)) = self.targets.get(target) else {
+            return default.to_string();
+        };
+        let (key, history) = match self.registry.id_of(target) {
+            Some(id) => {
+                let record = self.memory.entry(id);
+                // So that the console finds NPCs that only ever barked
+                if record.name.is_empty() {
+                    if let Some(name) = name {
+                        record.name = name.to_string();
+                    }
+                }
+                (stable_key(id), &mut record.small_talk)
+            }
+            None => (
+                format!("{target:?}"),
+                self.unremembered.entry(target).or_default(),
+            ),
+        };
+        let mut rng = small_talk_rng(&self.game_rng, &key, history);
+        small_talk
+            .pick(history, &mut rng)
+            .unwrap_or_else(|| default.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(node: &str, repeatable: bool, weight: f32) -> SmallTalkLine {
+        SmallTalkLine {
+            node: node.to_string(),
+            repeatable,
+            weight,
+        }
+    }
+
+    #[test]
+    fn small_talk_uses_every_node_once_before_repeating() {
+        let small_talk = SmallTalk(vec![
+            line("Baker_Weather", true, 1.),
+            line("Baker_Harvest", false, 1.),
+            line("Baker_Bread", true, 3.),
+        ]);
+        let game_rng = GameRng::new(7);
+        let id = StableId::derive("levels/village.glb", "Baker");
+        let mut history = SmallTalkHistory::default();
+        let pick = |history: &mut SmallTalkHistory| {
+            let mut rng = small_talk_rng(&game_rng, &stable_key(id), history);
+            small_talk.pick(history, &mut rng)
+        };
+
+        assert_eq!(pick(&mut history).as_deref(), Some("Baker_Weather"));
+        assert_eq!(pick(&mut history).as_deref(), Some("Baker_Harvest"));
+        assert_eq!(pick(&mut history).as_deref(), Some("Baker_Bread"));
+
+        let mut counts = HashMap::<String, u32>::default();
+        for _ in 0..400 {
+            let preview =
+                preview_small_talk(&small_talk, &history, &game_rng, id).map(ToString::to_string);
+            let node = pick(&mut history).unwrap();
+            assert_eq!(preview.as_deref(), Some(node.as_str()));
+            *counts.entry(node).or_default() += 1;
+        }
+        assert!(!counts.contains_key("Baker_Harvest"));
+        let bread = counts["Baker_Bread"];
+        assert!(
+            (220..=380).contains(&bread),
+            "Expected about 300 of 400 picks to be the heavier node, got {bread}"
+        );
+
+        // Nothing repeatable falls back to the yarn node
+        let once = SmallTalk(vec![line("Baker_Harvest", false, 1.)]);
+        assert_eq!(preview_small_talk(&once, &history, &game_rng, id), None);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{
+    level_instantiation::named_entities::EntityNames,
+    movement::character_controller::PlayOneShotAnimation,
+    world_interaction::dialog::CurrentDialogTarget,
+};
+use bevy::{
+    prelude::*,
+    render::mesh::morph::MorphWeights,
+    utils::{HashMap, HashSet},
+};
+use bevy_common_assets::ron::RonAssetPlugin;
+use bevy_yarnspinner::events::{DialogueCompleteEvent, PresentLineEvent, PresentOptionsEvent};
+use serde::{Deserialize, Serialize};
+
+/// Turns hashtags on yarn lines, e.g. `The Follower: Over there! #point_left #happy`, into expressions and gestures of the speaker.
+/// What each tag does per character is configured in `assets/config/config.directions.ron`.
+/// Expressions last until the next line, the options or the end of the dialog, after which the face returns to neutral.
+pub(super) fn plugin(app: &mut App) {
+    app.add_plugins(RonAssetPlugin::<StageDirectionTable>::new(&[
+        "directions.ron",
+    ]))
+    .init_resource::<ActiveExpression>()
+    .add_systems(Update, (reset_expressions, apply_stage_directions).chain());
+}
+
+#[derive(Debug, Clone, PartialEq, Asset, Reflect, Serialize, Deserialize, Default)]
+pub(crate) struct StageDirectionTable {
+    /// By the speaker's name as written in the yarn files.
+    pub(crate) characters: HashMap<String, CharacterDirections>,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct CharacterDirections {
+    /// The [`Name`] of the entity playing the speaker. Defaults to the speaker's name.
+    /// If no entity has this name, the character being talked to is used.
+    pub(crate) entity: Option<String>,
+    /// By tag, without the `#`.
+    pub(crate) tags: HashMap<String, StageDirection>,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+pub(crate) enum StageDirection {
+    /// Sets the weights of the speaker's morph targets by name, e.g. `{"Smile": 1.0}`. Morph targets not mentioned stay neutral.
+    Expression(HashMap<String, f32>),
+    /// Plays an animation of the speaker once.
+    Gesture(String),
+}
+
+/// The morph weights that an expression changed, together with their neutral values.
+#[derive(Debug, Default, Resource)]
+struct ActiveExpression {
+    neutral_weights: Vec<(Entity, Vec<f32>)>,
+}
+
+fn reset_expressions(
+    mut line_events: EventReader<PresentLineEvent>,
+    mut option_events: EventReader<PresentOptionsEvent>,
+    mut dialogue_complete_events: EventReader<DialogueCompleteEvent>,
+    mut active_expression: ResMut<ActiveExpression>,
+    mut morph_weights: Query<&mut MorphWeights>,
+) {
+    let line_advanced = line_events.read().count() > 0;
+    let ended = option_events.read().count() > 0 || dialogue_complete_events.read().count() > 0;
+    if !line_advanced && !ended {
+        return;
+    }
+    for (entity, neutral) in active_expression.neutral_weights.drain(..) {
+        if let Ok(mut weights) = morph_weights.get_mut(entity) {
+            weights.weights_mut().copy_from_slice(&neutral);
+        }
+    }
+}
+
+fn apply_stage_directions(
+    mut line_events: EventReader<PresentLineEvent>,
+    tables: Res<Assets<StageDirectionTable>>,
+    names: EntityNames,
+    dialog_target: Res<CurrentDialogTarget>,
+    children: Query<&Children>,
+    mut morph_weights: Query<&mut MorphWeights>,
+    meshes: Res<Assets<Mesh>>,
+    mut active_expression: ResMut<ActiveExpression>,
+    mut one_shot_events: EventWriter<PlayOneShotAnimation>,
+    mut reported_tags: Local<HashSet<String>>,
+) {
+    // Read on every line, so that changes to the table apply right away
+    let Some(table) = tables.iter().next().map(|(_, table)| table) else {
+        line_events.clear();
+        return;
+    };
+    for event in line_events.read() {
+        let Some(speaker) = event.line.character_name() else {
+            continue;
+        };
+        let directions = table.characters.get(speaker);
+        // Line ids and other metadata use the `key:value` form
+        let tags = event.line.metadata.iter().filter(|tag| !tag.contains(':'));
+        let mut speaker_entity = None;
+        for tag in tags {
+            let Some(direction) = directions.and_then(|directions| directions.tags.get(tag)) else {
+                if reported_tags.insert(format!("{speaker}#{tag}")) {
+                    debug!("Ignoring tag #{tag} on a line by \"{speaker}\", since it has no stage direction");
+                }
+                continue;
+            };
+            let Some(entity) = *speaker_entity.get_or_insert_with(|| {
+                let name = directions
+                    .and_then(|directions| directions.entity.as_deref())
+                    .unwrap_or(speaker);
+                names.get(name).or(dialog_target.0)
+            }) else {
+                warn!("Cannot play #{tag}, since no entity plays \"{speaker}\"");
+                continue;
+            };
+            match direction {
+                StageDirection::Gesture(animation) => {
+                    one_shot_events.send(PlayOneShotAnimation {
+                        entity,
+                        animation: animation.clone(),
+                    });
+                }
+                StageDirection::Expression(targets) => {
+                    for descendant in children.iter_descendants(entity) {
+                        let Ok(mut weights) = morph_weights.get_mut(descendant) else {
+                            continue;
+                        };
+                        let Some(target_names) = weights
+                            .first_mesh()
+                            .and_then(|mesh| meshes.get(mesh))
+                            .and_then(|mesh| mesh.morph_target_names())
+                        else {
+                            continue;
+                        };
+                        let target_names = target_names.to_vec();
+                        if !active_expression
+                            .neutral_weights
+                            .iter()
+                            .any(|(entity, _)| *entity == descendant)
+                        {
+                            active_expression
+                                .neutral_weights
+                                .push((descendant, weights.weights().to_vec()));
+                        }
+                        for (name, weight) in target_names.iter().zip(weights.weights_mut()) {
+                            if let Some(target) = targets.get(name) {
+                                *weight = *target;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,348 @@
+use crate::{
+    movement::{character_controller::Walk, physics::CollisionLayer},
+    world_interaction::{nameplate::Health, respawn_grace::GracePeriod},
+    GameState,
+};
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bevy_xpbd_3d::{prelude::*, PhysicsSet};
+use oxidized_navigation::{NavMeshAffector, NavMeshAreaType};
+use serde::{Deserialize, Serialize};
+
+/// How often [`HazardDamage::PerSecond`] hits, in seconds.
+const TICK_INTERVAL: f32 = 0.5;
+/// For this many seconds after a hazard hit a character, no hazard hits it again,
+/// so that being knocked back and falling right back in at the edge of a volume does not hit twice.
+const INVULNERABILITY: f32 = 0.4;
+/// Contacts at a new position only show up after a physics step, so volumes entered this soon after arriving count as arrived in.
+const ARRIVAL_SETTLE_TIME: f32 = 0.2;
+
+/// Handles [`HazardVolume`]s, e.g. lava pools, spike traps and poison gas. Characters inside a volume are sent [`DamageEvent`]s
+/// and knocked back. Every character keeps one tick timer per [`HazardVolume::damage_type`], so overlapping volumes of the same type
+/// do not hit more often than one; only the strongest of them counts. Volumes can be children of moving entities, e.g. a crusher.
+///
+/// [`DamageEvent`]s lower the target's [`Health`], whatever sent them, unless the target is in a [`GracePeriod`].
+/// Characters with [`IgnoredHazards`] are not hit by the volumes they arrived in until they have left them.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<HazardVolume>()
+        .register_type::<HazardDamage>()
+        .add_event::<DamageEvent>()
+        .add_systems(
+            Update,
+            (
+                init_hazard_volumes,
+                update_ignored_hazards,
+                damage_in_hazards,
+                apply_damage,
+            )
+                .chain()
+                .before(PhysicsSet::Prepare)
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// A box around the entity's origin that hurts the characters inside it.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct HazardVolume {
+    pub(crate) half_extents: Vec3,
+    pub(crate) damage: HazardDamage,
+    /// The speed characters are pushed away from the volume's center with when it hits them, in m/s.
+    pub(crate) knockback: f32,
+    /// E.g. "fire" or "poison". Volumes of the same type share their tick timer, see [`plugin`].
+    pub(crate) damage_type: String,
+    /// Keeps NPCs from walking into the volume. Turn this off for volumes that move, since the navmesh is rebuilt wherever they go.
+    pub(crate) nav_obstacle: bool,
+}
+
+impl Default for HazardVolume {
+    fn default() -> Self {
+        Self {
+            half_extents: Vec3::ONE,
+            damage: default(),
+            knockback: 0.,
+            damage_type: "generic".to_string(),
+            nav_obstacle: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize, Default)]
+pub(crate) enum HazardDamage {
+    /// Hits every [`TICK_INTERVAL`] while a character is inside, e.g. lava or poison gas.
+    PerSecond(f32),
+    /// Hits once when a character enters, e.g. spikes. It hits again only after the character has left.
+    PerTouch(f32),
+}
+
+impl Default for HazardDamage {
+    fn default() -> Self {
+        Self::PerSecond(10.)
+    }
+}
+
+impl HazardDamage {
+    /// The damage of a single hit.
+    fn per_hit(self) -> f32 {
+        match self {
+            Self::PerSecond(damage) => damage * TICK_INTERVAL,
+            Self::PerTouch(damage) => damage,
+        }
+    }
+
+    /// How long until the next hit after one.
+    fn cooldown(self) -> f32 {
+        match self {
+            Self::PerSecond(_) => TICK_INTERVAL,
+            Self::PerTouch(_) => f32::INFINITY,
+        }
+    }
+}
+
+/// Lowers the [`Health`] of the `target`.
+#[derive(Debug, Clone, PartialEq, Event)]
+pub(crate) struct DamageEvent {
+    pub(crate) target: Entity,
+    pub(crate) amount: f32,
+    pub(crate) damage_type: String,
+    /// What dealt the damage, e.g. a [`HazardVolume`].
+    pub(crate) source: Option<Entity>,
+}
+
+/// Hazard volumes that do not hit this character until it has left them once, e.g. the ones it arrived inside.
+#[derive(Debug, Clone, PartialEq, Component, Default)]
+pub(crate) struct IgnoredHazards {
+    volumes: HashSet<Entity>,
+    /// While above 0, every volume the character is in is ignored as well. In seconds.
+    settle_time: f32,
+}
+
+impl IgnoredHazards {
+    /// Ignores the volumes the character is in right after arriving somewhere.
+    pub(crate) fn on_arrival() -> Self {
+        Self {
+            volumes: default(),
+            settle_time: ARRIVAL_SETTLE_TIME,
+        }
+    }
+}
+
+/// On characters that are in a [`HazardVolume`] or were hit by one recently.
+#[derive(Debug, Clone, PartialEq, Component, Default)]
+struct HazardExposure {
+    /// Seconds until the next hit, for every damage type of the volumes the character is in.
+    cooldowns: HashMap<String, f32>,
+    /// Seconds until hazards can hit again.
+    invulnerable: f32,
+}
+
+fn init_hazard_volumes(
+    mut commands: Commands,
+    volumes: Query<(Entity, &HazardVolume), Added<HazardVolume>>,
+) {
+    for (entity, volume) in volumes.iter() {
+        let size = volume.half_extents * 2.;
+        let mut entity = commands.entity(entity);
+        entity.insert((
+            Collider::cuboid(size.x, size.y, size.z),
+            CollisionLayers::new(
+                [CollisionLayer::Sensor],
+                [CollisionLayer::Player, CollisionLayer::Character],
+            ),
+            Sensor,
+            CollidingEntities::default(),
+        ));
+        if volume.nav_obstacle {
+            entity.insert((NavMeshAffector, NavMeshAreaType(None)));
+        }
+    }
+}
+
+fn update_ignored_hazards(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut characters: Query<(Entity, &mut IgnoredHazards)>,
+    volumes: Query<(Entity, &CollidingEntities), With<HazardVolume>>,
+) {
+    for (character, mut ignored) in characters.iter_mut() {
+        let is_settling = ignored.settle_time > 0.;
+        ignored.settle_time -= time.delta_seconds();
+        for (volume, colliding) in volumes.iter() {
+            if !colliding.contains(&character) {
+                ignored.volumes.remove(&volume);
+            } else if is_settling {
+                ignored.volumes.insert(volume);
+            }
+        }
+        if !is_settling && ignored.volumes.is_empty() {
+            commands.entity(character).remove::<IgnoredHazards>();
+        }
+    }
+}
+
+fn damage_in_hazards(
+    mut commands: Commands,
+    time: Res<Time>,
+    volumes: Query<(Entity, &HazardVolume, &GlobalTransform, &CollidingEntities)>,
+    ignored: Query<&IgnoredHazards>,
+    mut characters: Query<
+        (
+            Entity,
+            &Position,
+            Option<&Mass>,
+            Option<&mut HazardExposure>,
+            Option<&mut ExternalImpulse>,
+        ),
+        With<Walk>,
+    >,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("damage_in_hazards").entered();
+    // The strongest volume of each damage type every character is in
+    let mut strongest: HashMap<Entity, HashMap<&str, (Entity, &HazardVolume, Vec3)>> = default();
+    for (entity, volume, transform, colliding) in volumes.iter() {
+        for &character in colliding.iter() {
+            let is_ignored = ignored
+                .get(character)
+                .is_ok_and(|ignored| ignored.volumes.contains(&entity));
+            if !characters.contains(character) || is_ignored {
+                continue;
+            }
+            let volumes = strongest.entry(character).or_default();
+            let current = volumes.entry(&volume.damage_type).or_insert((
+                entity,
+                volume,
+                transform.translation(),
+            ));
+            if volume.damage.per_hit() > current.1.damage.per_hit() {
+                *current = (entity, volume, transform.translation());
+            }
+        }
+    }
+
+    let dt = time.delta_seconds();
+    for (character, position, mass, exposure, impulse) in characters.iter_mut() {
+        let volumes = strongest.remove(&character).unwrap_or_default();
+        let mut added = None;
+        let exposure = match exposure {
+            Some(exposure) => exposure.into_inner(),
+            None if volumes.is_empty() => continue,
+            None => added.insert(HazardExposure::default()),
+        };
+        exposure.invulnerable = (exposure.invulnerable - dt).max(0.);
+        // Leaving all volumes of a type resets its timer, so that entering again hits right away
+        exposure
+            .cooldowns
+            .retain(|damage_type, _| volumes.contains_key(damage_type.as_str()));
+        let mut was_hit = false;
+        let mut knockback = None;
+        for (damage_type, (source, volume, center)) in volumes {
+            let cooldown = exposure
+                .cooldowns
+                .entry(damage_type.to_string())
+                .or_insert(0.);
+            *cooldown -= dt;
+            if *cooldown > 0. || exposure.invulnerable > 0. {
+                continue;
+            }
+            *cooldown = volume.damage.cooldown();
+            damage_events.send(DamageEvent {
+                target: character,
+                amount: volume.damage.per_hit(),
+                damage_type: damage_type.to_string(),
+                source: Some(source),
+            });
+            was_hit = true;
+            if volume.knockback > 0. {
+                knockback = Some((volume.knockback, center));
+            }
+        }
+        if was_hit {
+            exposure.invulnerable = INVULNERABILITY;
+        }
+        let is_exposed = !exposure.cooldowns.is_empty() || exposure.invulnerable > 0.;
+        match added {
+            Some(exposure) => {
+                commands.entity(character).insert(exposure);
+            }
+            None if !is_exposed => {
+                commands.entity(character).remove::<HazardExposure>();
+            }
+            None => {}
+        }
+        let Some((speed, center)) = knockback else {
+            continue;
+        };
+        let direction = (position.0 - center).try_normalize().unwrap_or(Vec3::Y);
+        // The knockback is a change in speed, so heavier characters get a stronger impulse
+        let mass = mass.map_or(1., |mass| mass.0);
+        let knockback = direction * speed * mass;
+        match impulse {
+            Some(mut impulse) => {
+                impulse.apply_impulse(knockback);
+            }
+            None => {
+                commands
+                    .entity(character)
+                    .insert(ExternalImpulse::new(knockback));
+            }
+        }
+    }
+}
+
+fn apply_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    mut health: Query<(&mut Health, Option<&GracePeriod>)>,
+) {
+    for event in damage_events.read() {
+        let Ok((mut health, grace)) = health.get_mut(event.target) else {
+            continue;
+        };
+        if grace.is_some_and(GracePeriod::is_invulnerable) {
+            continue;
+        }
+        health.current = (health.current - event.amount).max(0.);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    #[test]
+    fn overlapping_volumes_of_one_type_hit_like_one() {
+        let mut app = TestApp::new();
+        app.add_plugins(plugin);
+        app.spawn_ground();
+        for x in [0., 0.5] {
+            app.world_mut().spawn((
+                Name::new("Lava"),
+                TransformBundle::from_transform(Transform::from_xyz(x, 0.5, 0.)),
+                HazardVolume {
+                    half_extents: Vec3::new(2., 1., 2.),
+                    damage: HazardDamage::PerSecond(10.),
+                    damage_type: "fire".to_string(),
+                    nav_obstacle: false,
+                    ..default()
+                },
+            ));
+        }
+        let player = app.spawn_player(Vec3::new(0., 1., 0.));
+        app.world_mut().entity_mut(player).insert(Health {
+            current: 100.,
+            max: 100.,
+        });
+
+        // Long enough for three ticks, but not for a fourth
+        app.step(70);
+
+        let health = app.world().get::<Health>(player).unwrap();
+        assert_eq!(health.current, 100. - 3. * 10. * TICK_INTERVAL);
+    }
+}
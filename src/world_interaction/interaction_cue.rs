@@ -0,0 +1,130 @@
+use crate::{
+    file_system_interaction::{
+        config::GameConfig,
+        spatial_audio::{PlaySpatialSoundEvent, SoundEmitter},
+    },
+    player_control::actions::ActionsFrozen,
+    world_interaction::{
+        dialog::CurrentDialogTarget, interaction_ui::InteractionOpportunityBehind,
+    },
+    GameState,
+};
+use bevy::{prelude::*, utils::HashMap};
+
+/// How long a target stays silent after chiming, in seconds.
+const CUE_COOLDOWN: f32 = 10.;
+const CUE_VOLUME: f32 = 0.4;
+/// In meters. Only matters for how quickly the chime fades with the distance to the camera.
+const CUE_RANGE: f32 = 15.;
+
+/// Helps players notice what they could interact with but are not facing, if enabled in the accessibility settings:
+/// an [`InteractionOpportunityBehind`] plays a soft chime from the target's direction.
+/// Each target chimes at most once per [`CUE_COOLDOWN`], never during dialog or while the player is frozen,
+/// and only when the camera can see it, so that nothing chimes through walls.
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<CueCooldowns>().add_systems(
+        Update,
+        play_interaction_cues
+            .run_if(in_state(GameState::Playing).and_then(resource_exists::<GameConfig>)),
+    );
+}
+
+/// Seconds until a target may chime again, by target.
+#[derive(Debug, Clone, PartialEq, Resource, Default)]
+struct CueCooldowns(HashMap<Entity, f32>);
+
+fn play_interaction_cues(
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    dialog_target: Res<CurrentDialogTarget>,
+    actions_frozen: Res<ActionsFrozen>,
+    mut behind_events: EventReader<InteractionOpportunityBehind>,
+    targets: Query<&GlobalTransform>,
+    mut cooldowns: ResMut<CueCooldowns>,
+    mut sound_events: EventWriter<PlaySpatialSoundEvent>,
+) {
+    let dt = time.delta_seconds();
+    cooldowns.0.retain(|_, remaining| {
+        *remaining -= dt;
+        *remaining > 0.
+    });
+    let settings = &config.accessibility;
+    let sound = settings
+        .interaction_cue_sound
+        .as_ref()
+        .filter(|_| settings.interaction_cue && dialog_target.0.is_none());
+    for behind in behind_events.read() {
+        let Some(sound) = sound else {
+            continue;
+        };
+        if actions_frozen.is_player_frozen(behind.player)
+            || cooldowns.0.contains_key(&behind.target)
+        {
+            continue;
+        }
+        let Ok(target_transform) = targets.get(behind.target) else {
+            continue;
+        };
+        cooldowns.0.insert(behind.target, CUE_COOLDOWN);
+        sound_events.send(PlaySpatialSoundEvent {
+            emitter: SoundEmitter {
+                sound: sound.clone(),
+                volume: CUE_VOLUME,
+                range: CUE_RANGE,
+            },
+            position: target_transform.translation(),
+            source: Some(behind.target),
+            needs_line_of_sight: true,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TestApp, TICK};
+
+    #[test]
+    fn targets_chime_once_per_cooldown_and_never_during_dialog() {
+        let mut app = TestApp::new();
+        let mut config = GameConfig::default();
+        config.accessibility.interaction_cue = true;
+        config.accessibility.interaction_cue_sound = Some("audio/chime.ogg".to_string());
+        app.add_plugins(plugin)
+            .insert_resource(config)
+            .init_resource::<ActionsFrozen>()
+            .add_event::<InteractionOpportunityBehind>()
+            .add_event::<PlaySpatialSoundEvent>()
+            .record_events::<PlaySpatialSoundEvent>();
+        let player = app.world_mut().spawn(SpatialBundle::default()).id();
+        let target = app
+            .world_mut()
+            .spawn(SpatialBundle::from_transform(Transform::from_xyz(
+                0., 0., 2.,
+            )))
+            .id();
+        let behind = InteractionOpportunityBehind { player, target };
+
+        for _ in 0..30 {
+            app.send_event(behind);
+            app.step(1);
+        }
+        assert_eq!(app.events::<PlaySpatialSoundEvent>().len(), 1);
+        assert_eq!(
+            app.events::<PlaySpatialSoundEvent>()[0].position,
+            Vec3::new(0., 0., 2.)
+        );
+
+        // Once the cooldown is over, the target chimes again, unless the player is talking to someone
+        app.step((CUE_COOLDOWN / TICK) as usize);
+        app.clear_events::<PlaySpatialSoundEvent>();
+        app.world_mut().resource_mut::<CurrentDialogTarget>().0 = Some(target);
+        app.send_event(behind);
+        app.step(1);
+        assert!(app.events::<PlaySpatialSoundEvent>().is_empty());
+        app.world_mut().resource_mut::<CurrentDialogTarget>().0 = None;
+        app.send_event(behind);
+        app.step(1);
+        assert_eq!(app.events::<PlaySpatialSoundEvent>().len(), 1);
+    }
+}
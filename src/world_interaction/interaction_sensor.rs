@@ -0,0 +1,138 @@
+use crate::{movement::physics::CollisionLayer, util::math_trait_ext::Vec3Ext, GameState};
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Turns each [`InteractionSensor`] into a sensor collider below its entity, which the player needs to touch to interact
+/// with the entity. The collider is recreated whenever the component changes, e.g. when tweaking it in the inspector,
+/// and is removed with the component. Like any child collider, it scales with the entity's transform.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<InteractionSensor>()
+        .register_type::<SensorShape>()
+        .add_systems(
+            Update,
+            (remove_sensor_colliders, spawn_sensor_colliders)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct InteractionSensor {
+    pub(crate) shape: SensorShape,
+    /// Where the sensor's center is, relative to the entity.
+    pub(crate) offset: Vec3,
+}
+
+impl InteractionSensor {
+    pub(crate) fn new(shape: SensorShape) -> Self {
+        Self {
+            shape,
+            offset: Vec3::ZERO,
+        }
+    }
+
+    /// Whether something at `position` is in front of a target with `transform` as far as a [`SensorShape::Cone`] is concerned.
+    /// Always true for the other shapes.
+    pub(crate) fn is_facing(&self, transform: &GlobalTransform, position: Vec3) -> bool {
+        let SensorShape::Cone { angle, .. } = self.shape else {
+            return true;
+        };
+        let forward = transform.forward().horizontal();
+        let to_position = (position - transform.translation()).horizontal();
+        if forward.is_approx_zero() || to_position.is_approx_zero() {
+            return true;
+        }
+        forward.angle_between(to_position) <= angle
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize, Default)]
+pub(crate) enum SensorShape {
+    Sphere(f32),
+    Box(Vec3),
+    /// An upright cylinder, like the sensors of NPCs.
+    Cylinder {
+        radius: f32,
+        height: f32,
+    },
+    /// Only notices the player within `range` when they are in front of the entity, e.g. for a shopkeeper
+    /// who should only be talked to across the counter.
+    Cone {
+        range: f32,
+        /// How far from the entity's forward direction the player may stand, in radians.
+        angle: f32,
+    },
+}
+
+impl Default for SensorShape {
+    fn default() -> Self {
+        Self::Sphere(1.)
+    }
+}
+
+impl SensorShape {
+    fn collider(self) -> Collider {
+        match self {
+            Self::Sphere(radius) => Collider::sphere(radius),
+            Self::Box(half_extents) => Collider::cuboid(
+                half_extents.x * 2.,
+                half_extents.y * 2.,
+                half_extents.z * 2.,
+            ),
+            Self::Cylinder { radius, height } => Collider::cylinder(height, radius),
+            // The facing is checked by the interaction itself, see `InteractionSensor::is_facing`
+            Self::Cone { range, .. } => Collider::sphere(range),
+        }
+    }
+}
+
+/// The child holding the collider of an [`InteractionSensor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+struct SensorCollider(Entity);
+
+fn remove_sensor_colliders(
+    mut commands: Commands,
+    mut removed: RemovedComponents<InteractionSensor>,
+    colliders: Query<&SensorCollider, Without<InteractionSensor>>,
+) {
+    for entity in removed.read() {
+        let Ok(collider) = colliders.get(entity) else {
+            continue;
+        };
+        if let Some(collider) = commands.get_entity(collider.0) {
+            collider.despawn_recursive();
+        }
+        commands.entity(entity).remove::<SensorCollider>();
+    }
+}
+
+fn spawn_sensor_colliders(
+    mut commands: Commands,
+    sensors: Query<
+        (Entity, &InteractionSensor, Option<&SensorCollider>),
+        Changed<InteractionSensor>,
+    >,
+) {
+    for (entity, sensor, previous) in sensors.iter() {
+        if let Some(previous) = previous {
+            if let Some(previous) = commands.get_entity(previous.0) {
+                previous.despawn_recursive();
+            }
+        }
+        let collider = commands
+            .spawn((
+                Name::new("Interaction Sensor"),
+                TransformBundle::from_transform(Transform::from_translation(sensor.offset)),
+                sensor.shape.collider(),
+                CollisionLayers::new([CollisionLayer::Sensor], [CollisionLayer::Player]),
+                Sensor,
+            ))
+            .set_parent(entity)
+            .id();
+        commands.entity(entity).insert(SensorCollider(collider));
+    }
+}
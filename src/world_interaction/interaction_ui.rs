@@ -1,66 +1,216 @@
 use crate::{
-    level_instantiation::on_spawn::Player,
+    file_system_interaction::{
+        config::{ActionMode, GameConfig},
+        localization::{t, Strings},
+    },
+    gameplay_log::{log_event, GameplayLog},
+    level_instantiation::{on_spawn::Player, stable_id::StableId},
+    movement::{navigation::Companion, slow_motion::UnscaledTime},
     player_control::{
-        actions::{ActionsFrozen, PlayerAction},
-        camera::{IngameCamera, IngameCameraKind},
+        actions::{glyphs::ActionGlyphs, ActionsFrozen, PlayerAction},
+        camera::{CursorGrabRequests, IngameCamera, IngameCameraKind},
+        ui_layer::{UiLayer, UiLayers},
+    },
+    util::criteria::is_globally_frozen,
+    world_interaction::{
+        carry::{CarryRequest, CarryWeight, Carryability, Carryable, Carrying, Strength},
+        dialog::{
+            small_talk::SmallTalkRotation, CurrentDialogTarget, DialogContext,
+            DialogContextVariable, DialogInitiator, YarnNode,
+        },
+        interaction_sensor::InteractionSensor,
+        mount::{MountRequest, Mountable, Riding},
+        on_hit::OnHitInteraction,
+        race::{RaceStart, StartRaceRequest},
+        readable::{AlreadyRead, CurrentReadTarget, Readable},
+        seat::{Seat, SeatOccupant, SitDownRequest},
+        shop::{CurrentShop, Shop},
+        world_flags::{RequiresFlags, WorldFlags},
     },
-    util::criteria::is_frozen,
-    world_interaction::dialog::{CurrentDialogTarget, YarnNode},
     GameState,
 };
-use bevy::{prelude::*, transform::TransformSystem::TransformPropagate, window::PrimaryWindow};
+use bevy::{
+    ecs::system::SystemParam, prelude::*, transform::TransformSystem::TransformPropagate,
+    utils::HashMap, window::PrimaryWindow,
+};
 use bevy_egui::{egui, EguiContexts};
 use bevy_mod_sysfail::prelude::*;
 use bevy_xpbd_3d::prelude::*;
-use bevy_yarnspinner::prelude::DialogueRunner;
-use leafwing_input_manager::prelude::ActionState;
+use bevy_yarnspinner::{events::DialogueCompleteEvent, prelude::DialogueRunner};
+use leafwing_input_manager::prelude::{ActionState, InputMap};
 use serde::{Deserialize, Serialize};
 use std::f32::consts::TAU;
-use std::iter;
+use std::{iter, mem};
 
+/// Finds what every local player can interact with and shows a prompt for it, then carries out the interactions
+/// the players or NPCs and scripts ask for. A player frozen on its own, e.g. while in a dialog it started, keeps its opportunity
+/// but gets no prompt until it is unfrozen.
 pub(super) fn plugin(app: &mut App) {
-    app.register_type::<InteractionOpportunity>()
-        .init_resource::<InteractionOpportunity>()
+    app.register_type::<InteractionOpportunities>()
+        .register_type::<HoldToInteract>()
+        .register_type::<PlayerOnly>()
+        .add_event::<InteractionOpportunityEntered>()
+        .add_event::<InteractionOpportunityBehind>()
+        .add_event::<InteractRequestEvent>()
+        .init_resource::<InteractionOpportunities>()
+        .init_resource::<InteractionPrompts>()
         .add_systems(
             Update,
             (
                 update_interaction_opportunities
                     .after(PhysicsSet::Sync)
                     .after(TransformPropagate),
+                request_interactions,
                 display_interaction_prompt,
             )
                 .chain()
                 .run_if(
-                    not(is_frozen)
+                    not(is_globally_frozen)
                         .and_then(in_state(GameState::Playing))
                         .and_then(any_with_component::<DialogueRunner>),
                 ),
+        )
+        .add_systems(
+            Update,
+            handle_interact_requests.after(request_interactions).run_if(
+                in_state(GameState::Playing).and_then(any_with_component::<DialogueRunner>),
+            ),
         );
 }
 
+/// How far from its target an initiator other than the player may be.
+/// The player instead needs to touch the target's sensor.
+const MAX_INTERACTION_DISTANCE: f32 = 2.;
+/// How far the prompts of further local players are stacked below the first one, in logical pixels.
+const PROMPT_SPACING: f32 = 48.;
+
+/// What every player can interact with right now, by player.
 #[derive(Debug, Clone, Eq, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
 #[reflect(Resource, Serialize, Deserialize)]
-struct InteractionOpportunity(Option<Entity>);
+struct InteractionOpportunities(HashMap<Entity, Entity>);
+
+impl InteractionOpportunities {
+    fn get(&self, player: Entity) -> Option<Entity> {
+        self.0.get(&player).copied()
+    }
+}
+
+/// Sent when a player can newly interact with something.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub(crate) struct InteractionOpportunityEntered {
+    pub(crate) player: Entity,
+    pub(crate) target: Entity,
+}
+
+/// Sent every frame for what a player could interact with if it was facing it, while it cannot interact with anything else.
+/// The target is in range and passes everything but the facing check, e.g. its [`RequiresFlags`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub(crate) struct InteractionOpportunityBehind {
+    pub(crate) player: Entity,
+    pub(crate) target: Entity,
+}
+
+/// Makes something use an interactable as if the player pressed [`PlayerAction::Interact`] on it, without showing a prompt.
+/// Lets NPCs and scripts sit down or start dialog. The initiator needs to be in range and facing the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub(crate) struct InteractRequestEvent {
+    pub(crate) initiator: Entity,
+    pub(crate) target: Entity,
+    /// The initiator hit the target with something it threw, see [`OnHitInteraction`].
+    /// Skips the range and facing checks and only starts dialog.
+    pub(crate) by_hit: bool,
+}
+
+/// Only the player may interact with this. [`Readable`]s and [`Shop`]s are always player-only, since they open in the player's UI,
+/// and so are [`Carryable`]s, [`Mountable`]s and [`RaceStart`]s.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Component, Reflect, Serialize, Deserialize, Default,
+)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct PlayerOnly;
+
+/// Makes interacting with this require holding [`PlayerAction::Interact`] instead of pressing it.
+/// With the toggling interact mode, one press starts holding and another press cancels it.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct HoldToInteract {
+    /// In seconds, before applying the accessibility hold duration multiplier.
+    pub(crate) duration: f32,
+}
+
+/// The prompts to show this frame, see [`request_interactions`].
+#[derive(Debug, Clone, PartialEq, Resource, Default)]
+struct InteractionPrompts(Vec<InteractionPrompt>);
+
+#[derive(Debug, Clone, PartialEq)]
+struct InteractionPrompt {
+    player: Entity,
+    /// Where the prompt is stacked, see [`prompt_window`].
+    index: usize,
+    verb: String,
+    /// Shown in a weaker color, e.g. for notes that were already read.
+    dimmed: bool,
+    hold_progress: Option<f32>,
+}
+
+/// Progress of holding the interaction button.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct InteractionHold {
+    target: Option<Entity>,
+    elapsed: f32,
+    toggled: bool,
+}
 
 #[sysfail(Log<anyhow::Error, Error>)]
 fn update_interaction_opportunities(
     mut collisions: EventReader<Collision>,
-    player_query: Query<&GlobalTransform, With<Player>>,
+    // Riders can only get off, see `request_interactions`
+    player_query: Query<&GlobalTransform, (With<Player>, Without<Riding>)>,
     parents: Query<&Parent>,
     target_query: Query<
-        (Entity, &GlobalTransform),
-        (With<YarnNode>, Without<Player>, Without<IngameCamera>),
+        (
+            Entity,
+            &GlobalTransform,
+            Option<&InteractionSensor>,
+            Has<Companion>,
+            Option<&RequiresFlags>,
+        ),
+        (
+            Or<(
+                With<YarnNode>,
+                With<Readable>,
+                With<Seat>,
+                With<Shop>,
+                With<Carryable>,
+                With<Mountable>,
+                With<RaceStart>,
+            )>,
+            Without<Player>,
+            Without<IngameCamera>,
+        ),
     >,
     camera_query: Query<(&IngameCamera, &GlobalTransform), Without<Player>>,
-    mut interaction_opportunity: ResMut<InteractionOpportunity>,
+    flags: Res<WorldFlags>,
+    actions_frozen: Res<ActionsFrozen>,
+    mut interaction_opportunities: ResMut<InteractionOpportunities>,
+    mut entered_events: EventWriter<InteractionOpportunityEntered>,
+    mut behind_events: EventWriter<InteractionOpportunityBehind>,
+    mut previous_opportunities: Local<HashMap<Entity, Entity>>,
 ) {
-    interaction_opportunity.0 = None;
+    // Frozen players cannot interact, so what they could interact with before stays as it was
+    interaction_opportunities
+        .0
+        .retain(|&player, _| actions_frozen.is_player_frozen(player));
+    let mut trailing_companions = HashMap::default();
+    let mut behind = Vec::new();
 
     for Collision(ref contacts) in collisions.read() {
-        // Check if the player is colliding with anything
-        let Some((player, sensor)) =
-            get_player_and_target(&player_query, contacts.entity1, contacts.entity2)
-        else {
+        // Check if a player is colliding with anything
+        let Some((player, sensor)) = get_initiator_and_target(
+            |entity| player_query.contains(entity) && !actions_frozen.is_player_frozen(entity),
+            contacts.entity1,
+            contacts.entity2,
+        ) else {
             continue;
         };
 
@@ -68,95 +218,877 @@ fn update_interaction_opportunities(
         let mut ancestors = iter::once(sensor).chain(parents.iter_ancestors(sensor));
 
         // Check if what we are colliding with is a dialog target
-        let Some((target, target_transform)) =
+        let Some((target, target_transform, sensor, is_companion, requirement)) =
             ancestors.find_map(|entity| target_query.get(entity).ok())
         else {
             continue;
         };
+        if requirement.is_some_and(|requirement| !requirement.0.is_met(&flags)) {
+            continue;
+        }
 
         if !contacts.during_current_frame {
             continue;
         }
 
-        // Check if we are facing the right way
+        // Cone sensors only notice the player in front of the target
         let player_translation = player_query.get(player).unwrap().translation();
+        if sensor.is_some_and(|sensor| !sensor.is_facing(target_transform, player_translation)) {
+            continue;
+        }
+
+        // Check if we are facing the right way
         let Some((camera, camera_transform)) = camera_query.iter().next() else {
             continue;
         };
-        let is_facing_target = is_facing_target(
-            player_translation,
-            target_transform.translation(),
-            camera_transform.compute_transform(),
-            camera,
-        );
+        // With a fixed camera angle, the player cannot choose where to look
+        let is_facing_target = camera.kind == IngameCameraKind::FixedAngle
+            || is_facing_target(
+                player_translation,
+                camera_transform.forward(),
+                target_transform.translation(),
+            );
         if is_facing_target {
-            interaction_opportunity.0.replace(target);
+            interaction_opportunities.0.insert(player, target);
+        } else if is_companion {
+            // Companions trail behind the player, so they can be talked to without turning around
+            trailing_companions.insert(player, target);
+        } else {
+            behind.push(InteractionOpportunityBehind { player, target });
         }
     }
+    // Anything the player is facing takes precedence
+    for (player, companion) in trailing_companions {
+        interaction_opportunities
+            .0
+            .entry(player)
+            .or_insert(companion);
+    }
+    behind.retain(|behind| !interaction_opportunities.0.contains_key(&behind.player));
+    behind_events.send_batch(behind);
+    if interaction_opportunities.0 != *previous_opportunities {
+        let mut entered: Vec<_> = interaction_opportunities
+            .0
+            .iter()
+            .filter(|(player, target)| previous_opportunities.get(*player) != Some(*target))
+            .map(|(&player, &target)| InteractionOpportunityEntered { player, target })
+            .collect();
+        entered.sort_unstable_by_key(|entered| entered.player);
+        entered_events.send_batch(entered);
+        previous_opportunities.clone_from(&interaction_opportunities.0);
+    }
 }
 
-fn get_player_and_target(
-    player_query: &Query<&GlobalTransform, With<Player>>,
+/// Sorts two colliding entities into the one that could start an interaction and the other one.
+fn get_initiator_and_target(
+    is_initiator: impl Fn(Entity) -> bool,
     entity_a: Entity,
     entity_b: Entity,
 ) -> Option<(Entity, Entity)> {
-    if player_query.contains(entity_a) {
+    if is_initiator(entity_a) {
         Some((entity_a, entity_b))
-    } else if player_query.contains(entity_b) {
+    } else if is_initiator(entity_b) {
         Some((entity_b, entity_a))
     } else {
         None
     }
 }
 
-fn is_facing_target(
-    player: Vec3,
-    target: Vec3,
-    camera_transform: Transform,
-    camera: &IngameCamera,
-) -> bool {
-    if camera.kind == IngameCameraKind::FixedAngle {
-        return true;
-    }
-    let camera_to_player = camera_transform.forward();
-    let player_to_target = target - player;
-    let angle = camera_to_player.angle_between(player_to_target);
+fn is_facing_target(initiator: Vec3, forward: Vec3, target: Vec3) -> bool {
+    let initiator_to_target = target - initiator;
+    let angle = forward.angle_between(initiator_to_target);
     angle < TAU / 8.
 }
 
+fn is_in_interaction_range(initiator: Vec3, target: Vec3) -> bool {
+    initiator.distance_squared(target) <= MAX_INTERACTION_DISTANCE * MAX_INTERACTION_DISTANCE
+}
+
+/// Decides which prompt every player gets and sends an [`InteractRequestEvent`] when a player interacts.
+/// UI that consumes [`PlayerAction::Interact`] through [`UiActions`](crate::player_control::actions::UiActions),
+/// e.g. a note closed with the same button, needs to run before this.
 #[sysfail(Log<anyhow::Error, Error>)]
+pub(super) fn request_interactions(
+    interaction_opportunities: Res<InteractionOpportunities>,
+    players: Query<
+        (
+            Entity,
+            Option<&ActionState<PlayerAction>>,
+            Option<&Strength>,
+            Has<Carrying>,
+            Has<Riding>,
+        ),
+        With<Player>,
+    >,
+    strings: Strings,
+    target_query: Query<(
+        Option<&YarnNode>,
+        Has<Readable>,
+        Has<AlreadyRead>,
+        Has<SeatOccupant>,
+        Option<&HoldToInteract>,
+        Has<Companion>,
+        Has<Shop>,
+        Option<&CarryWeight>,
+        Has<Mountable>,
+        Has<RaceStart>,
+    )>,
+    mut interact_requests: EventWriter<InteractRequestEvent>,
+    time: UnscaledTime,
+    config: Res<GameConfig>,
+    ui_layers: Res<UiLayers>,
+    actions_frozen: Res<ActionsFrozen>,
+    mut prompts: ResMut<InteractionPrompts>,
+    mut holds: Local<HashMap<Entity, InteractionHold>>,
+) {
+    prompts.0.clear();
+    // Hiding the prompt under modals also stops interacting through them
+    if !ui_layers.is_visible(UiLayer::Hud) {
+        holds.clear();
+        return Ok(());
+    }
+    holds.retain(|&player, _| interaction_opportunities.get(player).is_some());
+    let mut players: Vec<_> = players.iter().collect();
+    // Keeps every player's prompt in the same place
+    players.sort_unstable_by_key(|(player, ..)| *player);
+    for (index, (player, actions, strength, is_carrying, is_riding)) in
+        players.into_iter().enumerate()
+    {
+        if actions_frozen.is_player_frozen(player) {
+            continue;
+        }
+        if is_riding {
+            // Getting off is handled by the mount
+            holds.remove(&player);
+            prompts.0.push(InteractionPrompt {
+                player,
+                index,
+                verb: t!(strings, "interaction.dismount"),
+                dimmed: false,
+                hold_progress: None,
+            });
+            continue;
+        }
+        let Some(opportunity) = interaction_opportunities.get(player) else {
+            continue;
+        };
+
+        let (
+            dialog_target,
+            is_readable,
+            already_read,
+            is_occupied,
+            hold_to_interact,
+            is_companion,
+            is_shop,
+            carry_weight,
+            is_mountable,
+            is_race_start,
+        ) = target_query.get(opportunity)?;
+        // Letting go takes precedence over anything else the player could interact with
+        if is_occupied || is_carrying {
+            continue;
+        }
+        let carryability =
+            carry_weight.map(|weight| strength.copied().unwrap_or_default().carryability(weight));
+        let verb = if dialog_target.is_some() || is_companion {
+            t!(strings, "interaction.talk")
+        } else if is_shop {
+            t!(strings, "interaction.trade")
+        } else if is_readable {
+            t!(strings, "interaction.read")
+        } else if is_mountable {
+            t!(strings, "interaction.ride")
+        } else if is_race_start {
+            t!(strings, "interaction.race")
+        } else if let Some(carryability) = carryability {
+            match carryability {
+                Carryability::Take => t!(strings, "interaction.take"),
+                Carryability::Drag => t!(strings, "interaction.drag"),
+                Carryability::TooHeavy => t!(strings, "interaction.too_heavy"),
+            }
+        } else {
+            t!(strings, "interaction.sit")
+        };
+        let hold = holds.entry(player).or_default();
+        if hold.target != Some(opportunity) {
+            *hold = InteractionHold {
+                target: Some(opportunity),
+                ..default()
+            };
+        }
+        // Read every frame, so that changing the settings affects a hold that is already in progress
+        let hold_duration = hold_to_interact.map(|hold_to_interact| {
+            hold_to_interact.duration * config.accessibility.hold_duration_multiplier
+        });
+        let is_interacting = match (actions, hold_duration) {
+            (None, _) => false,
+            (Some(actions), None) => actions.just_pressed(&PlayerAction::Interact),
+            (Some(actions), Some(hold_duration)) => {
+                hold.toggled = match config.accessibility.interact_mode {
+                    ActionMode::Hold => actions.pressed(&PlayerAction::Interact),
+                    ActionMode::Toggle => {
+                        hold.toggled ^ actions.just_pressed(&PlayerAction::Interact)
+                    }
+                };
+                hold.elapsed = if hold.toggled {
+                    hold.elapsed + time.delta_seconds()
+                } else {
+                    0.
+                };
+                let is_done = hold.elapsed >= hold_duration;
+                if is_done {
+                    hold.elapsed = 0.;
+                    hold.toggled = false;
+                }
+                is_done
+            }
+        };
+        let hold_progress = hold_duration
+            .filter(|_| hold.elapsed > 0.)
+            .map(|duration| (hold.elapsed / duration.max(1e-5)).min(1.));
+
+        prompts.0.push(InteractionPrompt {
+            player,
+            index,
+            verb,
+            dimmed: already_read || carryability == Some(Carryability::TooHeavy),
+            hold_progress,
+        });
+        if is_interacting {
+            interact_requests.send(InteractRequestEvent {
+                initiator: player,
+                target: opportunity,
+                by_hit: false,
+            });
+        }
+    }
+}
+
 fn display_interaction_prompt(
-    interaction_opportunity: Res<InteractionOpportunity>,
-    mut dialogue_runner: Query<&mut DialogueRunner>,
+    prompts: Res<InteractionPrompts>,
     mut egui_contexts: EguiContexts,
-    actions: Query<&ActionState<PlayerAction>>,
+    players: Query<Option<&InputMap<PlayerAction>>, With<Player>>,
+    input_maps: Query<&InputMap<PlayerAction>>,
+    glyphs: ActionGlyphs,
     primary_windows: Query<&Window, With<PrimaryWindow>>,
-    dialog_target_query: Query<(Entity, &YarnNode)>,
-    mut freeze: ResMut<ActionsFrozen>,
-    mut current_dialog_target: ResMut<CurrentDialogTarget>,
 ) {
-    let Some(opportunity) = interaction_opportunity.0 else {
-        return Ok(());
-    };
     let Ok(window) = primary_windows.get_single() else {
-        return Ok(());
+        return;
     };
+    for prompt in &prompts.0 {
+        // Players without their own bindings use the ones of the first player
+        let input_map = players
+            .get(prompt.player)
+            .ok()
+            .flatten()
+            .or_else(|| input_maps.iter().next());
+        prompt_window(window, prompt.index).show(egui_contexts.ctx_mut(), |ui| {
+            if prompt.dimmed {
+                ui.visuals_mut().override_text_color = Some(ui.visuals().weak_text_color());
+            }
+            if let Some(input_map) = input_map {
+                glyphs.prompt(ui, input_map, &PlayerAction::Interact, &prompt.verb);
+            }
+            if let Some(progress) = prompt.hold_progress {
+                ui.add(egui::ProgressBar::new(progress).desired_width(120.));
+            }
+        });
+    }
+}
 
-    let (entity, dialog_target) = dialog_target_query.get(opportunity)?;
+/// The prompt of the first player is centered on the screen, the ones of further local players are stacked below it.
+fn prompt_window(window: &Window, index: usize) -> egui::Window<'static> {
     egui::Window::new("Interaction")
+        .id(egui::Id::new(("Interaction", index)))
+        .order(UiLayer::Hud.order())
         .collapsible(false)
         .title_bar(false)
         .auto_sized()
-        .fixed_pos(egui::Pos2::new(window.width() / 2., window.height() / 2.))
-        .show(egui_contexts.ctx_mut(), |ui| {
-            ui.label("E: Talk");
-        });
-    for actions in actions.iter() {
-        if actions.just_pressed(&PlayerAction::Interact) {
+        .fixed_pos(egui::Pos2::new(
+            window.width() / 2.,
+            window.height() / 2. + index as f32 * PROMPT_SPACING,
+        ))
+}
+
+/// What is needed to start the dialog of a target.
+#[derive(SystemParam)]
+struct DialogStarts<'w, 's> {
+    subjects: Query<
+        'w,
+        's,
+        (
+            &'static DialogContextVariable,
+            Option<&'static Name>,
+            Option<&'static StableId>,
+        ),
+    >,
+    context: ResMut<'w, DialogContext>,
+    small_talk: SmallTalkRotation<'w, 's>,
+    completed: EventReader<'w, 's, DialogueCompleteEvent>,
+    /// Dialog requested while another one is running, e.g. through `<<interact>>`, which starts once that one ended.
+    queued: Local<'s, Vec<InteractRequestEvent>>,
+}
+
+/// Interactions that take more than a frame, sent to the plugins that carry them out.
+#[derive(SystemParam)]
+struct InteractionHandoffs<'w> {
+    sit_down: EventWriter<'w, SitDownRequest>,
+    carry: EventWriter<'w, CarryRequest>,
+    mount: EventWriter<'w, MountRequest>,
+    start_race: EventWriter<'w, StartRaceRequest>,
+}
+
+/// Validates and carries out interactions, no matter whether the player pressed a button or an NPC or script asked for it.
+/// Other interactions go ahead during a dialog, but starting another dialog waits until the running one ended.
+fn handle_interact_requests(
+    mut interact_requests: EventReader<InteractRequestEvent>,
+    interaction_opportunities: Res<InteractionOpportunities>,
+    initiators: Query<(&GlobalTransform, Has<Player>, Has<Riding>)>,
+    target_query: Query<
+        (
+            &GlobalTransform,
+            Option<&YarnNode>,
+            Has<Readable>,
+            Has<Seat>,
+            Has<SeatOccupant>,
+            Has<PlayerOnly>,
+            Has<Shop>,
+            Has<Carryable>,
+            Has<Mountable>,
+            Has<RaceStart>,
+            Option<&InteractionSensor>,
+            Option<&RequiresFlags>,
+        ),
+        Or<(
+            With<YarnNode>,
+            With<Readable>,
+            With<Seat>,
+            With<Shop>,
+            With<Carryable>,
+            With<Mountable>,
+            With<RaceStart>,
+        )>,
+    >,
+    mut dialogue_runner: Query<&mut DialogueRunner>,
+    mut dialog_starts: DialogStarts,
+    mut freeze: ResMut<ActionsFrozen>,
+    mut cursor_grab: ResMut<CursorGrabRequests>,
+    mut current_dialog_target: ResMut<CurrentDialogTarget>,
+    mut dialog_initiator: ResMut<DialogInitiator>,
+    mut current_read_target: ResMut<CurrentReadTarget>,
+    mut current_shop: ResMut<CurrentShop>,
+    mut handoffs: InteractionHandoffs,
+    flags: Res<WorldFlags>,
+    mut log: ResMut<GameplayLog>,
+) {
+    // Ending a dialog also unfreezes its initiator and clears its context, which must not hit the next one
+    let dialog_ended = dialog_starts.completed.read().count() > 0;
+    let can_start_dialog = !dialog_ended && !dialogue_runner.single().is_running();
+    let queued = if can_start_dialog {
+        mem::take(&mut *dialog_starts.queued)
+    } else {
+        Vec::new()
+    };
+    for request in queued.into_iter().chain(interact_requests.read().copied()) {
+        let Ok((initiator_transform, is_player, is_riding)) = initiators.get(request.initiator)
+        else {
+            warn!(
+                "Interaction initiator {:?} has no transform",
+                request.initiator
+            );
+            continue;
+        };
+        let Ok((
+            target_transform,
+            dialog_target,
+            is_readable,
+            is_seat,
+            is_occupied,
+            player_only,
+            is_shop,
+            is_carryable,
+            is_mountable,
+            is_race_start,
+            sensor,
+            requirement,
+        )) = target_query.get(request.target)
+        else {
+            debug!("{:?} is not interactable", request.target);
+            continue;
+        };
+        if is_riding {
+            debug!(
+                "{:?} cannot interact with {:?} while riding",
+                request.initiator, request.target
+            );
+            continue;
+        }
+        if requirement.is_some_and(|requirement| !requirement.0.is_met(&flags)) {
+            log_event!(
+                log,
+                Interaction,
+                Some(request.initiator),
+                "Flags for the interaction are not set",
+                target = request.target
+            );
+            continue;
+        }
+        let is_for_player = is_readable || is_shop || is_carryable || is_mountable || is_race_start;
+        if !is_player && (player_only || is_for_player) {
+            debug!("{:?} can only be used by the player", request.target);
+            continue;
+        }
+        if is_occupied {
+            continue;
+        }
+        if request.by_hit && dialog_target.is_none() {
+            debug!("{:?} cannot be used by hitting it", request.target);
+            continue;
+        }
+        // The player's range and facing were already checked against the sensor and camera when finding the opportunity
+        let was_checked = request.by_hit
+            || is_player
+                && interaction_opportunities.get(request.initiator) == Some(request.target);
+        if !was_checked {
+            let initiator = initiator_transform.translation();
+            let target = target_transform.translation();
+            if !is_in_interaction_range(initiator, target)
+                || !is_facing_target(initiator, initiator_transform.forward(), target)
+                || sensor.is_some_and(|sensor| !sensor.is_facing(target_transform, initiator))
+            {
+                debug!(
+                    "{:?} is not in range of or facing {:?}",
+                    request.initiator, request.target
+                );
+                log_event!(
+                    log,
+                    Interaction,
+                    Some(request.initiator),
+                    "Not in range of or facing the target",
+                    target = request.target
+                );
+                continue;
+            }
+        }
+
+        if is_seat {
+            log_event!(
+                log,
+                Interaction,
+                Some(request.initiator),
+                "Sitting down",
+                seat = request.target
+            );
+            handoffs.sit_down.send(SitDownRequest {
+                character: request.initiator,
+                seat: request.target,
+            });
+            continue;
+        }
+        if is_carryable && dialog_target.is_none() {
+            handoffs.carry.send(CarryRequest {
+                character: request.initiator,
+                prop: request.target,
+            });
+            continue;
+        }
+        if is_mountable {
+            log_event!(
+                log,
+                Interaction,
+                Some(request.initiator),
+                "Mounting",
+                mount = request.target
+            );
+            handoffs.mount.send(MountRequest {
+                character: request.initiator,
+                mount: request.target,
+            });
+            continue;
+        }
+        if is_race_start {
+            handoffs.start_race.send(StartRaceRequest {
+                racer: request.initiator,
+                start: request.target,
+            });
+            continue;
+        }
+        if let Some(dialog_target) = dialog_target {
             let mut dialogue_runner = dialogue_runner.single_mut();
-            dialogue_runner.start_node(&dialog_target.0);
-            current_dialog_target.0.replace(entity);
-            freeze.freeze();
+            if !can_start_dialog || dialogue_runner.is_running() {
+                debug!(
+                    "Starting dialog with {:?} once the running dialog ended",
+                    request.target
+                );
+                dialog_starts.queued.push(request);
+                continue;
+            }
+            if let Ok((context, name, id)) = dialog_starts.subjects.get(request.target) {
+                dialog_starts
+                    .context
+                    .set(&mut dialogue_runner, context, name, id);
+            }
+            let node = dialog_starts
+                .small_talk
+                .next_node(request.target, &dialog_target.0);
+            dialogue_runner.start_node(&node);
+            log_event!(
+                log,
+                Dialog,
+                Some(request.initiator),
+                format!("Started dialog {node}"),
+                target = request.target,
+                by_hit = request.by_hit
+            );
+            if !is_player {
+                // Dialog between NPCs, e.g. barks, plays without taking control away from the player
+                continue;
+            }
+            current_dialog_target.0.replace(request.target);
+            dialog_initiator.0 = Some(request.initiator);
+            // Other local players keep playing during the dialog
+            freeze.freeze_player(request.initiator);
+            cursor_grab.request_free();
+            continue;
+        } else if is_shop {
+            log_event!(
+                log,
+                Interaction,
+                Some(request.initiator),
+                "Opened shop",
+                shop = request.target
+            );
+            current_shop.0.replace(request.target);
+        } else if is_readable {
+            log_event!(
+                log,
+                Interaction,
+                Some(request.initiator),
+                "Started reading",
+                readable = request.target
+            );
+            current_read_target.0.replace(request.target);
+        } else {
+            continue;
         }
+        freeze.freeze();
+        cursor_grab.request_free();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        determinism::rng::GameRng,
+        file_system_interaction::localization::{CurrentLocale, StringTable},
+        level_instantiation::stable_id,
+        player_control::actions::{
+            create_player_action_input_manager_bundle, create_ui_action_input_manager_bundle,
+            ui_queue, UiAction, UiActions,
+        },
+        testing::{InputScript, TestApp},
+        world_interaction::{
+            interaction_sensor::{self, SensorShape},
+            npc_memory::NpcMemory,
+        },
+    };
+    use bevy_yarnspinner::prelude::*;
+    use std::f32::consts::FRAC_PI_4;
+
+    fn interaction_app() -> TestApp {
+        let mut app = TestApp::new();
+        app.add_plugins(interaction_sensor::plugin)
+            .init_resource::<InteractionOpportunities>()
+            .init_resource::<ActionsFrozen>()
+            .add_event::<InteractionOpportunityEntered>()
+            .add_systems(
+                Update,
+                update_interaction_opportunities
+                    .after(PhysicsSet::Sync)
+                    .after(TransformPropagate),
+            )
+            .record_events::<InteractionOpportunityEntered>();
+        app.spawn_ground();
+        app
+    }
+
+    /// A shopkeeper-like target that only notices players in front of it.
+    /// It faces +Z when `facing_player` and turns its back to players coming from there otherwise.
+    fn spawn_shopkeeper(app: &mut TestApp, translation: Vec3, facing_player: bool) -> Entity {
+        let facing = if facing_player { Vec3::Z } else { Vec3::NEG_Z };
+        app.world_mut()
+            .spawn((
+                Name::new("Shopkeeper"),
+                YarnNode("Shopkeeper".to_string()),
+                InteractionSensor::new(SensorShape::Cone {
+                    range: 2.,
+                    angle: FRAC_PI_4,
+                }),
+                SpatialBundle::from_transform(
+                    Transform::from_translation(translation).looking_to(facing, Vec3::Y),
+                ),
+            ))
+            .id()
+    }
+
+    /// Walks every player 4 m straight ahead along -Z.
+    fn walk_up(app: &mut TestApp) {
+        app.script(InputScript::new().walk(Vec2::Y, 25).idle(30));
+        app.run_script();
+    }
+
+    /// Walks the player from 4 m in front of a shopkeeper at the origin up to it.
+    fn approach_target(facing_player: bool) -> (TestApp, Entity, Entity) {
+        let mut app = interaction_app();
+        let player = app.spawn_player(Vec3::new(0., 0.5, 4.));
+        let target = spawn_shopkeeper(&mut app, Vec3::new(0., 0.5, 0.), facing_player);
+        walk_up(&mut app);
+        (app, player, target)
+    }
+
+    #[test]
+    fn approaching_a_dialog_target_from_the_front_offers_talking() {
+        let (app, player, target) = approach_target(true);
+
+        assert_eq!(
+            app.resource::<InteractionOpportunities>().get(player),
+            Some(target)
+        );
+        assert_eq!(
+            app.events::<InteractionOpportunityEntered>(),
+            [InteractionOpportunityEntered { player, target }]
+        );
+    }
+
+    #[test]
+    fn approaching_a_dialog_target_from_behind_offers_nothing() {
+        let (app, player, _) = approach_target(false);
+
+        assert_eq!(app.resource::<InteractionOpportunities>().get(player), None);
+        assert!(app.events::<InteractionOpportunityEntered>().is_empty());
+    }
+
+    #[test]
+    fn local_players_get_the_opportunities_in_front_of_them() {
+        let mut app = interaction_app();
+        let left_player = app.spawn_player(Vec3::new(-3., 0.5, 4.));
+        let right_player = app.spawn_player(Vec3::new(3., 0.5, 4.));
+        let left_target = spawn_shopkeeper(&mut app, Vec3::new(-3., 0.5, 0.), true);
+        let right_target = spawn_shopkeeper(&mut app, Vec3::new(3., 0.5, 0.), true);
+        walk_up(&mut app);
+
+        let opportunities = app.resource::<InteractionOpportunities>();
+        assert_eq!(opportunities.get(left_player), Some(left_target));
+        assert_eq!(opportunities.get(right_player), Some(right_target));
+        let entered = app.events::<InteractionOpportunityEntered>();
+        assert_eq!(entered.len(), 2);
+        for (player, target) in [(left_player, left_target), (right_player, right_target)] {
+            assert!(entered.contains(&InteractionOpportunityEntered { player, target }));
+        }
+    }
+
+    /// Stands in for a note that closes on [`UiAction::Confirm`], like a [`Readable`].
+    #[derive(Debug, Resource)]
+    struct NoteOpen(bool);
+
+    fn close_note(
+        mut open: ResMut<NoteOpen>,
+        mut ui_layers: ResMut<UiLayers>,
+        mut ui_actions: UiActions,
+    ) {
+        if !open.0 {
+            return;
+        }
+        ui_layers.show(UiLayer::GameplayModal);
+        if ui_actions.consume(UiLayer::GameplayModal, UiAction::Confirm) {
+            open.0 = false;
+        }
+    }
+
+    #[test]
+    fn closing_a_note_with_interact_does_not_also_start_a_dialog() {
+        let (mut app, player, target) = approach_target(true);
+        app.add_plugins(ui_queue::plugin)
+            .init_resource::<UiLayers>()
+            .init_resource::<InteractionPrompts>()
+            .init_resource::<CurrentLocale>()
+            .init_asset::<StringTable>()
+            .insert_resource(NoteOpen(false))
+            .add_event::<InteractRequestEvent>()
+            .add_systems(
+                Update,
+                (close_note, request_interactions)
+                    .chain()
+                    .after(update_interaction_opportunities),
+            )
+            .record_events::<InteractRequestEvent>();
+        // E is bound to both UiAction::Confirm and PlayerAction::Interact
+        app.world_mut().entity_mut(player).insert((
+            create_ui_action_input_manager_bundle(),
+            create_player_action_input_manager_bundle().input_map,
+        ));
+        let press_e = |app: &mut TestApp| {
+            app.world_mut()
+                .get_mut::<ActionState<UiAction>>(player)
+                .unwrap()
+                .press(&UiAction::Confirm);
+            app.script(InputScript::new().hold(PlayerAction::Interact, 1));
+            app.step(1);
+            app.world_mut()
+                .get_mut::<ActionState<UiAction>>(player)
+                .unwrap()
+                .release(&UiAction::Confirm);
+        };
+
+        // Without a note, E talks to the shopkeeper
+        press_e(&mut app);
+        assert_eq!(
+            app.events::<InteractRequestEvent>(),
+            [InteractRequestEvent {
+                initiator: player,
+                target,
+                by_hit: false,
+            }]
+        );
+        app.clear_events::<InteractRequestEvent>();
+        app.step(1);
+
+        // With a note open, E only closes the note
+        app.world_mut().resource_mut::<NoteOpen>().0 = true;
+        app.step(1);
+        press_e(&mut app);
+        assert!(!app.resource::<NoteOpen>().0);
+        let actions = app
+            .world()
+            .get::<ActionState<PlayerAction>>(player)
+            .unwrap();
+        assert!(!actions.just_pressed(&PlayerAction::Interact));
+        assert!(app.events::<InteractRequestEvent>().is_empty());
+    }
+
+    const TWO_NODES: &str = "title: First
+---
+Hello.
+===
+title: Second
+---
+Bye.
+===
+";
+
+    fn spawn_runner(mut commands: Commands, project: Res<YarnProject>) {
+        commands.spawn(project.create_dialogue_runner());
+    }
+
+    /// Carries out interact requests against a dialogue runner for [`TWO_NODES`], without any prompts.
+    fn dialog_app() -> TestApp {
+        let mut app = TestApp::new();
+        app.add_plugins((
+            YarnSpinnerPlugin::with_yarn_source(YarnFileSource::InMemory(YarnFile::new(
+                "two_nodes.yarn",
+                TWO_NODES,
+            ))),
+            stable_id::plugin,
+        ))
+        .insert_resource(GameRng::new(0))
+        .init_resource::<InteractionOpportunities>()
+        .init_resource::<ActionsFrozen>()
+        .init_resource::<CursorGrabRequests>()
+        .init_resource::<CurrentReadTarget>()
+        .init_resource::<CurrentShop>()
+        .init_resource::<DialogContext>()
+        .init_resource::<NpcMemory>()
+        .add_event::<InteractRequestEvent>()
+        .add_event::<SitDownRequest>()
+        .add_event::<CarryRequest>()
+        .add_event::<MountRequest>()
+        .add_event::<StartRaceRequest>()
+        .add_systems(
+            Update,
+            (
+                spawn_runner.run_if(resource_added::<YarnProject>),
+                handle_interact_requests.run_if(any_with_component::<DialogueRunner>),
+            ),
+        )
+        .record_events::<SitDownRequest>();
+        // The project is compiled from the in-memory file over the first few frames
+        for _ in 0..30 {
+            if app
+                .world_mut()
+                .query::<&DialogueRunner>()
+                .iter(app.world())
+                .next()
+                .is_some()
+            {
+                return app;
+            }
+            app.step(1);
+        }
+        panic!("the yarn project was not compiled");
+    }
+
+    fn current_node(app: &mut TestApp) -> Option<String> {
+        app.world_mut()
+            .query::<&DialogueRunner>()
+            .single(app.world())
+            .current_node()
+    }
+
+    #[test]
+    fn interacting_during_a_dialog_starts_another_dialog_only_after_it() {
+        let mut app = dialog_app();
+        let npc = app
+            .world_mut()
+            .spawn((
+                Name::new("Npc"),
+                SpatialBundle::from_transform(Transform::from_xyz(0., 0.5, 0.)),
+            ))
+            .id();
+        let speaker = app
+            .world_mut()
+            .spawn((
+                YarnNode("Second".to_string()),
+                SpatialBundle::from_transform(Transform::from_xyz(0., 0.5, -1.)),
+            ))
+            .id();
+        let seat = app
+            .world_mut()
+            .spawn((
+                Seat::default(),
+                SpatialBundle::from_transform(Transform::from_xyz(0., 0.5, -1.5)),
+            ))
+            .id();
+        app.world_mut()
+            .query::<&mut DialogueRunner>()
+            .single_mut(app.world_mut())
+            .start_node("First");
+        app.step(1);
+
+        // Like `<<interact>>` commands issued from the running dialog
+        for target in [speaker, seat] {
+            app.send_event(InteractRequestEvent {
+                initiator: npc,
+                target,
+                by_hit: false,
+            });
+        }
+        app.step(1);
+        assert_eq!(
+            app.events::<SitDownRequest>(),
+            [SitDownRequest {
+                character: npc,
+                seat,
+            }]
+        );
+        assert_eq!(current_node(&mut app).as_deref(), Some("First"));
+
+        app.world_mut()
+            .query::<&mut DialogueRunner>()
+            .single_mut(app.world_mut())
+            .stop();
+        app.step(3);
+        assert_eq!(current_node(&mut app).as_deref(), Some("Second"));
     }
 }
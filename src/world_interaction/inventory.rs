@@ -0,0 +1,80 @@
+use crate::world_interaction::dialog::{commands::DialogPosition, YarnCommandsAppExt};
+use bevy::{prelude::*, utils::HashMap};
+use bevy_yarnspinner::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Keeps track of the items the player carries. Money is an item as well, see [`ShopTable::currency`](crate::world_interaction::shop::ShopTable::currency).
+/// Dialog hands out and takes items:
+/// - `<<give_item item_id count>>` adds items to the inventory.
+/// - `<<take_item item_id count>>` removes items, as many as there are if there are fewer.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Inventory>()
+        .init_resource::<Inventory>()
+        .add_yarn_commands(register_commands);
+}
+
+fn register_commands(dialogue_runner: &mut DialogueRunner) {
+    dialogue_runner
+        .commands_mut()
+        .add_command("give_item", give_item)
+        .add_command("take_item", take_item);
+}
+
+/// How many of each item the player has, by item id. Items the player has none of are not listed. Stored in saves.
+#[derive(Debug, Clone, PartialEq, Eq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct Inventory(HashMap<String, u32>);
+
+impl Inventory {
+    pub(crate) fn count(&self, item: &str) -> u32 {
+        self.0.get(item).copied().unwrap_or_default()
+    }
+
+    pub(crate) fn add(&mut self, item: &str, count: u32) {
+        if count > 0 {
+            *self.0.entry(item.to_string()).or_default() += count;
+        }
+    }
+
+    /// Removes `count` items if there are that many. Returns whether it did.
+    pub(crate) fn remove(&mut self, item: &str, count: u32) -> bool {
+        let available = self.count(item);
+        if available < count {
+            return false;
+        }
+        if available == count {
+            self.0.remove(item);
+        } else {
+            self.0.insert(item.to_string(), available - count);
+        }
+        true
+    }
+
+    /// The ids and counts of all items, sorted by id.
+    pub(crate) fn items(&self) -> Vec<(&str, u32)> {
+        let mut items: Vec<_> = self
+            .0
+            .iter()
+            .map(|(item, &count)| (item.as_str(), count))
+            .collect();
+        items.sort_unstable();
+        items
+    }
+}
+
+fn give_item(In((item, count)): In<(String, f32)>, mut inventory: ResMut<Inventory>) {
+    inventory.add(&item, count.max(0.) as u32);
+}
+
+fn take_item(
+    In((item, count)): In<(String, f32)>,
+    mut inventory: ResMut<Inventory>,
+    position: Res<DialogPosition>,
+) {
+    let count = count.max(0.) as u32;
+    if !inventory.remove(&item, count) {
+        let available = inventory.count(&item);
+        warn!("<<take_item>> in {}: wanted {count} of \"{item}\", but the player only has {available}", *position);
+        inventory.remove(&item, available);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::{
+    determinism::{fnv1a, GameRng},
+    level_instantiation::named_entities::EntityNames,
+    world_interaction::{
+        dialog::{commands::DialogPosition, YarnCommandsAppExt},
+        time_of_day::{TimeOfDay, TimeOfDayEvent},
+    },
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_yarnspinner::prelude::DialogueRunner;
+use serde::{Deserialize, Serialize};
+
+/// How long a lamp takes to turn fully on or off, in game minutes.
+const FADE_DURATION: f32 = 1.;
+
+/// Turns [`Lamp`]s on at dusk and off at dawn, fading the intensity of their point lights and the emissive color of
+/// their glowing materials together, so that bulbs don't glow while the lamp is off.
+/// A [`LampOverride`] wins over the time of day. It is remembered with the level's state and thus stored in saves.
+/// Dialog sets it with `<<switch_lamp lamp_name on>>`, `<<switch_lamp lamp_name off>>` and `<<switch_lamp lamp_name auto>>`.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Lamp>()
+        .register_type::<LampOverride>()
+        .add_yarn_commands(register_lamp_command)
+        .add_systems(
+            Update,
+            (init_lamps, schedule_lamps, update_lamps)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Put this on an entity with [`PointLight`]s below it, e.g. a street lamp or a torch.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct Lamp {
+    /// Each lamp switches a random delay of up to this many game minutes after dusk and dawn,
+    /// so that the lamps along a street do not all switch at once.
+    pub(crate) max_delay: f32,
+    pub(crate) flicker: Option<Flicker>,
+}
+
+impl Default for Lamp {
+    fn default() -> Self {
+        Self {
+            max_delay: 10.,
+            flicker: None,
+        }
+    }
+}
+
+/// Makes a lamp's intensity waver like a flame while it is on.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct Flicker {
+    /// How far the intensity wavers, as a fraction of the full intensity.
+    pub(crate) amplitude: f32,
+    /// Roughly how many times per second the intensity changes direction.
+    pub(crate) frequency: f32,
+}
+
+impl Default for Flicker {
+    fn default() -> Self {
+        Self {
+            amplitude: 0.2,
+            frequency: 4.,
+        }
+    }
+}
+
+/// Forces a [`Lamp`] on (`true`) or off (`false`) regardless of the time of day. Remove it to go back to the schedule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct LampOverride(pub(crate) bool);
+
+#[derive(Debug, Clone, PartialEq, Component)]
+struct LampState {
+    /// Whether the time of day wants the lamp on.
+    scheduled_on: bool,
+    /// The switch waiting for the lamp's delay to pass, with the game minutes left until then.
+    pending: Option<(bool, f32)>,
+    delay: f32,
+    /// Between 0 for off and 1 for on.
+    brightness: f32,
+    flicker_seed: u64,
+    /// The lights below the lamp and their full intensity.
+    lights: Vec<(Entity, f32)>,
+    /// The glowing materials below the lamp and their full emissive color.
+    materials: Vec<(Handle<StandardMaterial>, Color)>,
+}
+
+fn register_lamp_command(dialogue_runner: &mut DialogueRunner) {
+    dialogue_runner
+        .commands_mut()
+        .add_command("switch_lamp", switch_lamp);
+}
+
+fn switch_lamp(
+    In((lamp_name, state)): In<(String, String)>,
+    mut commands: Commands,
+    names: EntityNames,
+    lamps: Query<(), With<Lamp>>,
+    position: Res<DialogPosition>,
+) {
+    let context = format!("<<switch_lamp>> in {}", *position);
+    let Some(lamp) = names.get_or_report(&lamp_name, &context) else {
+        return;
+    };
+    if !lamps.contains(lamp) {
+        error!("{context}: \"{lamp_name}\" is not a lamp");
+        return;
+    }
+    match state.as_str() {
+        "on" => {
+            commands.entity(lamp).insert(LampOverride(true));
+        }
+        "off" => {
+            commands.entity(lamp).insert(LampOverride(false));
+        }
+        "auto" => {
+            commands.entity(lamp).remove::<LampOverride>();
+        }
+        _ => error!("{context}: expected \"on\", \"off\" or \"auto\", but got \"{state}\""),
+    }
+}
+
+/// Lamps are set up once their lights have spawned, which may take a few frames for blueprints.
+fn init_lamps(
+    mut commands: Commands,
+    lamps: Query<(Entity, &Lamp, Option<&LampOverride>), Without<LampState>>,
+    children: Query<&Children>,
+    lights: Query<&PointLight>,
+    material_handles: Query<&Handle<StandardMaterial>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    time_of_day: Res<TimeOfDay>,
+    game_rng: Res<GameRng>,
+) {
+    for (entity, lamp, lamp_override) in lamps.iter() {
+        let lamp_lights: Vec<_> = std::iter::once(entity)
+            .chain(children.iter_descendants(entity))
+            .filter_map(|light| Some((light, lights.get(light).ok()?.intensity)))
+            .collect();
+        if lamp_lights.is_empty() {
+            continue;
+        }
+        let mut lamp_materials = Vec::new();
+        for mesh in std::iter::once(entity).chain(children.iter_descendants(entity)) {
+            let Some(material) = material_handles
+                .get(mesh)
+                .ok()
+                .and_then(|handle| materials.get(handle))
+            else {
+                continue;
+            };
+            let emissive = material.emissive;
+            if emissive == Color::BLACK {
+                continue;
+            }
+            // Other lamps may share the material, but not the brightness
+            let material = materials.add(material.clone());
+            commands.entity(mesh).insert(material.clone());
+            lamp_materials.push((material, emissive));
+        }
+        let mut rng = game_rng.fork_for("lamps", entity);
+        let scheduled_on = time_of_day.is_night();
+        let is_on = lamp_override.map_or(scheduled_on, |lamp_override| lamp_override.0);
+        commands.entity(entity).insert(LampState {
+            scheduled_on,
+            pending: None,
+            delay: rng.f32() * lamp.max_delay.max(0.),
+            brightness: if is_on { 1. } else { 0. },
+            flicker_seed: rng.next_u64(),
+            lights: lamp_lights,
+            materials: lamp_materials,
+        });
+    }
+}
+
+fn schedule_lamps(
+    mut time_of_day_events: EventReader<TimeOfDayEvent>,
+    mut lamps: Query<&mut LampState>,
+) {
+    for event in time_of_day_events.read() {
+        let on = *event == TimeOfDayEvent::Dusk;
+        for mut lamp in lamps.iter_mut() {
+            lamp.pending = Some((on, lamp.delay));
+        }
+    }
+}
+
+fn update_lamps(
+    time: Res<Time>,
+    time_of_day: Res<TimeOfDay>,
+    mut lamps: Query<(&Lamp, &mut LampState, Option<&LampOverride>)>,
+    mut lights: Query<&mut PointLight>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_lamps").entered();
+    let minutes = time_of_day.minutes_in(time.delta_seconds());
+    for (lamp, mut state, lamp_override) in lamps.iter_mut() {
+        if let Some((on, minutes_left)) = state.pending {
+            let minutes_left = minutes_left - minutes;
+            if minutes_left <= 0. {
+                state.scheduled_on = on;
+                state.pending = None;
+            } else {
+                state.pending = Some((on, minutes_left));
+            }
+        }
+        let is_on = lamp_override.map_or(state.scheduled_on, |lamp_override| lamp_override.0);
+        let target = if is_on { 1. } else { 0. };
+        let max_step = minutes / FADE_DURATION;
+        let brightness = state.brightness + (target - state.brightness).clamp(-max_step, max_step);
+        state.brightness = brightness;
+
+        let flicker = lamp.flicker.map_or(1., |flicker| {
+            let noise = perlin_noise(
+                time.elapsed_seconds() * flicker.frequency,
+                state.flicker_seed,
+            );
+            (1. + flicker.amplitude * noise).max(0.)
+        });
+        let factor = brightness * flicker;
+        for &(entity, intensity) in &state.lights {
+            if let Ok(mut light) = lights.get_mut(entity) {
+                let intensity = intensity * factor;
+                if light.intensity != intensity {
+                    light.intensity = intensity;
+                }
+            }
+        }
+        for (handle, emissive) in &state.materials {
+            let emissive = *emissive * factor;
+            if materials
+                .get(handle)
+                .is_some_and(|material| material.emissive != emissive)
+            {
+                if let Some(material) = materials.get_mut(handle) {
+                    material.emissive = emissive;
+                }
+            }
+        }
+    }
+}
+
+/// One-dimensional Perlin noise, roughly between -1 and 1, with a new gradient at every integer.
+pub(crate) fn perlin_noise(x: f32, seed: u64) -> f32 {
+    let gradient = |cell: f32| {
+        let hash = fnv1a(&[(cell as i64).to_le_bytes(), seed.to_le_bytes()].concat());
+        (hash >> 40) as f32 / (1u64 << 24) as f32 * 2. - 1.
+    };
+    let cell = x.floor();
+    let t = x - cell;
+    let from_left = gradient(cell) * t;
+    let from_right = gradient(cell + 1.) * (t - 1.);
+    let fade = t * t * t * (t * (t * 6. - 15.) + 10.);
+    // One-dimensional Perlin noise only reaches ±0.5
+    2. * (from_left + (from_right - from_left) * fade)
+}
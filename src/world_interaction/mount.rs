@@ -0,0 +1,459 @@
+use crate::{
+    file_system_interaction::config::{ActionMode, GameConfig},
+    level_instantiation::on_spawn::{player, Player},
+    movement::{
+        character_controller::{
+            CharacterControllerBundle, Depenetrate, GeneralMovementSystemSet, HeldAnimation,
+            MovementProfile, Sprinting, Walk,
+        },
+        physics::CollisionLayer,
+    },
+    player_control::{
+        actions::{DualAxisDataExt, PlayerAction},
+        camera::{CameraConstraints, IngameCamera, IngameCameraKind},
+    },
+    util::math_trait_ext::Vec3Ext,
+    world_interaction::{
+        carry::Carrying,
+        interaction_sensor::{InteractionSensor, SensorShape},
+        seat::Seated,
+    },
+    GameState,
+};
+use bevy::{prelude::*, transform::TransformSystem::TransformPropagate};
+use bevy_tnua::TnuaToggle;
+use bevy_xpbd_3d::{prelude::*, PhysicsSet};
+use leafwing_input_manager::{plugin::InputManagerSystem, prelude::ActionState};
+use serde::{Deserialize, Serialize};
+
+/// How far below a dismount spot the ground may be, in meters.
+const MAX_DISMOUNT_DROP: f32 = 1.5;
+
+/// Handles [`Mountable`]s, e.g. horses and carts. Mounting puts the player on the mount's seat and hands the movement input to the mount,
+/// which is a character of its own with the "mount" movement profile. Forward and back drive the mount, left and right turn it.
+/// Mounts cannot strafe, and they only turn as fast as their [`Mountable::turn_speed`] allows, which makes for a wide turn radius at speed.
+/// While riding, the camera follows from behind, and interacting with anything but getting off is disabled.
+/// [`PlayerAction::Interact`] gets off at the first free [`Mountable::dismount_offsets`], keeping some of the mount's momentum.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Mountable>()
+        .register_type::<MountRider>()
+        .add_event::<MountRequest>()
+        .add_systems(
+            Update,
+            (
+                spawn_mounts,
+                start_riding,
+                request_dismounting.after(InputManagerSystem::ManualControl),
+                drive_mounts.after(InputManagerSystem::ManualControl),
+            )
+                .chain()
+                .before(GeneralMovementSystemSet)
+                .run_if(in_state(GameState::Playing)),
+        )
+        // The rider follows the mount where physics moved it this frame, not where it was before
+        .add_systems(
+            PostUpdate,
+            place_riders
+                .after(PhysicsSet::Sync)
+                .before(TransformPropagate)
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Something the player can ride. All offsets are relative to the mount's transform, whose origin is the center of its capsule.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct Mountable {
+    /// Where the rider's origin is while riding.
+    pub(crate) seat_offset: Vec3,
+    /// Where the rider may be placed when getting off, tried in order. Spots inside something or above a drop are skipped.
+    pub(crate) dismount_offsets: Vec<Vec3>,
+    /// The height of the cylindrical part of the mount's capsule.
+    pub(crate) height: f32,
+    pub(crate) radius: f32,
+    /// How fast the mount turns at most, in degrees per second.
+    pub(crate) turn_speed: f32,
+    /// How much of the mount's velocity the rider keeps when getting off. Between 0 and 1.
+    pub(crate) velocity_transfer: f32,
+    pub(crate) riding_animation: String,
+    /// Hides the rider, e.g. inside a carriage.
+    pub(crate) hide_rider: bool,
+    pub(crate) camera_distance: f32,
+    pub(crate) camera_min_pitch: f32,
+    pub(crate) camera_max_pitch: f32,
+}
+
+impl Default for Mountable {
+    fn default() -> Self {
+        Self {
+            seat_offset: Vec3::new(0., 1., 0.),
+            dismount_offsets: vec![
+                Vec3::new(-1.2, -0.4, 0.),
+                Vec3::new(1.2, -0.4, 0.),
+                Vec3::new(0., -0.4, 1.8),
+            ],
+            height: 1.,
+            radius: 0.5,
+            turn_speed: 90.,
+            velocity_transfer: 0.5,
+            riding_animation: "riding".to_string(),
+            hide_rider: false,
+            camera_distance: 8.,
+            camera_min_pitch: -40.,
+            camera_max_pitch: 20.,
+        }
+    }
+}
+
+/// The character currently riding a [`Mountable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component, Reflect)]
+#[reflect(Component)]
+pub(crate) struct MountRider(pub(crate) Entity);
+
+/// Puts a character on a mount. Ignored when the mount already has a rider or the character is busy, e.g. seated or carrying something.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub(crate) struct MountRequest {
+    pub(crate) character: Entity,
+    pub(crate) mount: Entity,
+}
+
+/// Present on a character riding a [`Mountable`]. Its movement input drives the mount instead of itself until it is removed.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub(crate) struct Riding {
+    mount: Entity,
+    /// Where the rider steers the mount, which turns towards it with the delay of any character.
+    heading: Vec3,
+    /// The rider's own collision layers, restored after getting off. While riding, it collides with nothing.
+    collision_layers: CollisionLayers,
+    /// The camera's kind and distance before mounting, restored after getting off. `None` for riders other than the player.
+    camera: Option<(IngameCameraKind, f32)>,
+}
+
+fn spawn_mounts(
+    mut commands: Commands,
+    mounts: Query<(Entity, &Mountable, &Transform), (Added<Mountable>, Without<Walk>)>,
+) {
+    for (entity, mountable, transform) in mounts.iter() {
+        commands.entity(entity).insert((
+            CharacterControllerBundle::capsule(
+                mountable.height,
+                mountable.radius,
+                transform.scale.y,
+            ),
+            MovementProfile::new("mount"),
+            InteractionSensor::new(SensorShape::Cylinder {
+                radius: mountable.radius + 1.,
+                height: mountable.height + player::HEIGHT,
+            }),
+        ));
+    }
+}
+
+fn start_riding(
+    mut commands: Commands,
+    mut requests: EventReader<MountRequest>,
+    mounts: Query<(&Mountable, &Transform), Without<MountRider>>,
+    mut characters: Query<
+        (
+            &CollisionLayers,
+            &mut LinearVelocity,
+            Has<Player>,
+            Has<Seated>,
+            Has<Carrying>,
+            Has<Riding>,
+        ),
+        Without<Mountable>,
+    >,
+    mut cameras: Query<&mut IngameCamera>,
+) {
+    for request in requests.read() {
+        let Ok((mountable, mount_transform)) = mounts.get(request.mount) else {
+            continue;
+        };
+        let Ok((collision_layers, mut velocity, is_player, is_seated, is_carrying, is_riding)) =
+            characters.get_mut(request.character)
+        else {
+            continue;
+        };
+        if is_seated || is_carrying || is_riding {
+            debug!(
+                "{:?} is busy and cannot mount {:?}",
+                request.character, request.mount
+            );
+            continue;
+        }
+        let camera = cameras
+            .iter_mut()
+            .filter(|_| is_player)
+            .map(|mut camera| {
+                let previous = (camera.kind, camera.desired_distance);
+                camera.kind = IngameCameraKind::ThirdPerson;
+                camera.desired_distance = mountable.camera_distance;
+                previous
+            })
+            .last();
+        velocity.0 = Vec3::ZERO;
+        let mut character = commands.entity(request.character);
+        character.insert((
+            Riding {
+                mount: request.mount,
+                heading: mount_transform.forward().horizontal().normalize_or_zero(),
+                collision_layers: *collision_layers,
+                camera,
+            },
+            TnuaToggle::Disabled,
+            RigidBody::Kinematic,
+            CollisionLayers::new(collision_layers.memberships, LayerMask::NONE),
+            HeldAnimation(mountable.riding_animation.clone()),
+        ));
+        if mountable.hide_rider {
+            character.insert(Visibility::Hidden);
+        }
+        commands
+            .entity(request.mount)
+            .insert(MountRider(request.character));
+    }
+}
+
+fn request_dismounting(
+    mut commands: Commands,
+    mut riders: Query<(
+        Entity,
+        Ref<Riding>,
+        &mut Transform,
+        &mut LinearVelocity,
+        Option<&ActionState<PlayerAction>>,
+    )>,
+    mounts: Query<(&Mountable, &Transform, &LinearVelocity), Without<Riding>>,
+    spatial_query: SpatialQuery,
+    mut cameras: Query<&mut IngameCamera>,
+) {
+    for (rider, riding, mut transform, mut velocity, actions) in riders.iter_mut() {
+        let Ok((mountable, mount_transform, mount_velocity)) = mounts.get(riding.mount) else {
+            // The mount is gone, e.g. despawned by a level change, so the rider is dropped where it is
+            release_rider(&mut commands, rider, &riding, &mut cameras);
+            continue;
+        };
+        // The press that mounted does not get off again right away
+        let is_pressed =
+            actions.is_some_and(|actions| actions.just_pressed(&PlayerAction::Interact));
+        if !is_pressed || riding.is_added() {
+            continue;
+        }
+        let Some(spot) = find_dismount_spot(
+            &spatial_query,
+            mountable,
+            mount_transform,
+            [rider, riding.mount],
+        ) else {
+            debug!("No free spot to get off {:?}", riding.mount);
+            continue;
+        };
+        transform.translation = spot;
+        transform.rotation = mount_transform.rotation;
+        velocity.0 = mount_velocity.0 * mountable.velocity_transfer.clamp(0., 1.);
+        commands.entity(riding.mount).remove::<MountRider>();
+        release_rider(&mut commands, rider, &riding, &mut cameras);
+    }
+}
+
+/// The first dismount offset where the rider fits and has ground below.
+fn find_dismount_spot(
+    spatial_query: &SpatialQuery,
+    mountable: &Mountable,
+    mount_transform: &Transform,
+    excluded: [Entity; 2],
+) -> Option<Vec3> {
+    let collider = Collider::capsule(player::HEIGHT, player::RADIUS);
+    let obstacles = SpatialQueryFilter::from_mask([
+        CollisionLayer::Terrain,
+        CollisionLayer::Character,
+        CollisionLayer::Player,
+    ])
+    .with_excluded_entities(excluded);
+    let terrain = SpatialQueryFilter::from_mask(CollisionLayer::Terrain.to_bits());
+    mountable
+        .dismount_offsets
+        .iter()
+        .map(|&offset| mount_transform.transform_point(offset))
+        .find(|&spot| {
+            spatial_query
+                .shape_intersections(&collider, spot, Quat::IDENTITY, obstacles.clone())
+                .is_empty()
+                && spatial_query
+                    .cast_ray(
+                        spot,
+                        Direction3d::NEG_Y,
+                        player::HEIGHT / 2. + player::RADIUS + MAX_DISMOUNT_DROP,
+                        true,
+                        terrain.clone(),
+                    )
+                    .is_some()
+        })
+}
+
+/// Gives control back to a rider where it is.
+fn release_rider(
+    commands: &mut Commands,
+    rider: Entity,
+    riding: &Riding,
+    cameras: &mut Query<&mut IngameCamera>,
+) {
+    commands
+        .entity(rider)
+        .remove::<(Riding, HeldAnimation)>()
+        .insert((
+            TnuaToggle::Enabled,
+            RigidBody::Dynamic,
+            riding.collision_layers,
+            Visibility::Inherited,
+            Depenetrate::default(),
+        ));
+    let Some((kind, distance)) = riding.camera else {
+        return;
+    };
+    for mut camera in cameras.iter_mut() {
+        camera.kind = kind;
+        camera.desired_distance = distance;
+        camera.constraints = None;
+    }
+}
+
+fn drive_mounts(
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    mut riders: Query<(&mut Riding, &ActionState<PlayerAction>)>,
+    mut mounts: Query<(&Mountable, &mut Walk, &mut Sprinting)>,
+    mut cameras: Query<&mut IngameCamera>,
+    mut sprint_toggled: Local<bool>,
+) {
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("drive_mounts").entered();
+    for (mut riding, actions) in riders.iter_mut() {
+        let Ok((mountable, mut walk, mut sprint)) = mounts.get_mut(riding.mount) else {
+            continue;
+        };
+        let movement = actions
+            .axis_pair(&PlayerAction::Move)
+            .and_then(|axis| axis.max_normalized())
+            .unwrap_or_default();
+        riding.heading = steer(
+            riding.heading,
+            movement.x,
+            mountable.turn_speed,
+            time.delta_seconds(),
+        );
+        *sprint_toggled = match config.accessibility.sprint_mode {
+            ActionMode::Hold => actions.pressed(&PlayerAction::Sprint),
+            ActionMode::Toggle => *sprint_toggled ^ actions.just_pressed(&PlayerAction::Sprint),
+        } && movement.y > 0.;
+        let max_throttle = if actions.pressed(&PlayerAction::Walk) {
+            walk.run_threshold - walk.run_hysteresis
+        } else {
+            1.
+        };
+        // Mounts back up at half the speed
+        let throttle = movement.y.clamp(-max_throttle * 0.5, max_throttle);
+        walk.direction = (throttle.abs() > 1e-3).then_some(riding.heading * throttle);
+        walk.keep_facing = Some(riding.heading);
+        walk.facing = Some(riding.heading);
+        sprint.requested = *sprint_toggled;
+
+        // Zooming in all the way would switch to first person, which makes no sense on a mount
+        for mut camera in cameras.iter_mut() {
+            camera.kind = IngameCameraKind::ThirdPerson;
+            camera.constraints = Some(CameraConstraints {
+                yaw_center: CameraConstraints::yaw_towards(riding.heading),
+                max_yaw_offset: 180.,
+                min_pitch: mountable.camera_min_pitch,
+                max_pitch: mountable.camera_max_pitch,
+            });
+        }
+    }
+}
+
+/// Turns a horizontal `heading` by the horizontal movement input. Pushing right turns clockwise when seen from above.
+fn steer(heading: Vec3, turn_input: f32, turn_speed: f32, dt: f32) -> Vec3 {
+    let angle = -turn_input.clamp(-1., 1.) * turn_speed.to_radians() * dt;
+    (Quat::from_rotation_y(angle) * heading)
+        .horizontal()
+        .try_normalize()
+        .unwrap_or(Vec3::NEG_Z)
+}
+
+fn place_riders(
+    mut riders: Query<(&Riding, &mut Transform, &mut LinearVelocity)>,
+    mounts: Query<(&Mountable, &Transform), Without<Riding>>,
+) {
+    for (riding, mut transform, mut velocity) in riders.iter_mut() {
+        let Ok((mountable, mount_transform)) = mounts.get(riding.mount) else {
+            continue;
+        };
+        transform.translation = mount_transform.transform_point(mountable.seat_offset);
+        transform.rotation = mount_transform.rotation;
+        velocity.0 = Vec3::ZERO;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{InputScript, TestApp};
+
+    #[test]
+    fn riding_forward_and_getting_off_keeps_some_momentum() {
+        let mut app = TestApp::new();
+        app.add_plugins(plugin);
+        app.spawn_ground();
+        let mount = app
+            .world_mut()
+            .spawn((
+                Name::new("Horse"),
+                Mountable::default(),
+                SpatialBundle::from_transform(Transform::from_xyz(0., 1., 0.)),
+            ))
+            .id();
+        let player = app.spawn_player(Vec3::new(3., 0.5, 0.));
+        app.step(5);
+        app.send_event(MountRequest {
+            character: player,
+            mount,
+        });
+        app.step(5);
+        assert!(app.world().get::<Riding>(player).is_some());
+
+        app.script(InputScript::new().walk(Vec2::Y, 60));
+        app.run_script();
+        let seat = app.translation(mount) + Mountable::default().seat_offset;
+        assert!(
+            app.translation(mount).z < -3.,
+            "The mount did not ride forward"
+        );
+        assert!(app.translation(player).distance(seat) < 0.1);
+
+        app.script(InputScript::new().hold(PlayerAction::Interact, 1));
+        app.run_script();
+        let world = app.world();
+        assert!(world.get::<Riding>(player).is_none());
+        assert!(world.get::<MountRider>(mount).is_none());
+        assert!(matches!(
+            world.get::<RigidBody>(player),
+            Some(RigidBody::Dynamic)
+        ));
+        assert!(matches!(
+            world.get::<TnuaToggle>(player),
+            Some(TnuaToggle::Enabled)
+        ));
+        let offset = app.translation(player) - app.translation(mount);
+        assert!(
+            offset.horizontal().length() > 1.,
+            "The player got off on top of the mount"
+        );
+        let velocity = world.get::<LinearVelocity>(player).unwrap();
+        assert!(
+            velocity.z < -1.,
+            "The player lost the mount's momentum: {velocity:?}"
+        );
+    }
+}
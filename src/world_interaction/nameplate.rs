@@ -0,0 +1,216 @@
+use crate::{
+    level_instantiation::on_spawn::{player, Player},
+    movement::navigation::has_line_of_sight,
+    player_control::{
+        camera::{IngameCamera, IngameCameraKind},
+        ui_layer::{UiLayer, UiLayers},
+    },
+    util::criteria::is_globally_frozen,
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// At most this many plates are drawn at once, the closest ones win.
+const MAX_VISIBLE_PLATES: usize = 24;
+/// Plates start fading out at this fraction of their [`Nameplate::max_distance`].
+const FADE_START: f32 = 0.7;
+/// How long the lost health stays visible before it starts shrinking, in seconds.
+const CHIP_DELAY: f32 = 0.6;
+/// The fraction of the health bar the chip shrinks by per second.
+const CHIP_SPEED: f32 = 0.8;
+const HEALTH_BAR_SIZE: egui::Vec2 = egui::vec2(80., 7.);
+
+/// Draws floating plates with the name and health of characters above their heads.
+/// Plates fade out with distance and are hidden when something blocks the view to them,
+/// while the actions of all players are frozen, e.g. in menus or while reading, and while any modal UI like a dialog is open.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Nameplate>()
+        .register_type::<Health>()
+        .add_systems(
+            Update,
+            (init_health_chips, update_health_chips, display_nameplates)
+                .chain()
+                .run_if(in_state(GameState::Playing).and_then(not(is_globally_frozen))),
+        );
+}
+
+/// Shows a plate above this entity. The name is taken from its [`Name`], the health from its [`Health`].
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct Nameplate {
+    pub(crate) show_name: bool,
+    pub(crate) show_health: bool,
+    /// Beyond this distance to the camera, the plate is hidden.
+    pub(crate) max_distance: f32,
+}
+
+impl Default for Nameplate {
+    fn default() -> Self {
+        Self {
+            show_name: true,
+            show_health: true,
+            max_distance: 15.,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct Health {
+    pub(crate) current: f32,
+    pub(crate) max: f32,
+}
+
+impl Health {
+    pub(crate) fn fraction(&self) -> f32 {
+        if self.max <= 0. {
+            return 0.;
+        }
+        (self.current / self.max).clamp(0., 1.)
+    }
+}
+
+/// The recently lost part of a health bar, which lingers for a moment before shrinking.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct HealthChip {
+    fraction: f32,
+    delay: f32,
+}
+
+fn init_health_chips(
+    health: Query<(Entity, &Health), (With<Nameplate>, Without<HealthChip>)>,
+    mut commands: Commands,
+) {
+    for (entity, health) in health.iter() {
+        commands.entity(entity).insert(HealthChip {
+            fraction: health.fraction(),
+            delay: 0.,
+        });
+    }
+}
+
+fn update_health_chips(time: Res<Time>, mut health: Query<(Ref<Health>, &mut HealthChip)>) {
+    let dt = time.delta_seconds();
+    for (health, mut chip) in health.iter_mut() {
+        let fraction = health.fraction();
+        if fraction >= chip.fraction {
+            // Healing is shown right away
+            chip.fraction = fraction;
+            chip.delay = 0.;
+            continue;
+        }
+        if health.is_changed() && !health.is_added() {
+            chip.delay = CHIP_DELAY;
+        }
+        if chip.delay > 0. {
+            chip.delay -= dt;
+        } else {
+            chip.fraction = (chip.fraction - CHIP_SPEED * dt).max(fraction);
+        }
+    }
+}
+
+fn display_nameplates(
+    plates: Query<(
+        Entity,
+        &Nameplate,
+        &GlobalTransform,
+        Option<&Name>,
+        Option<&Health>,
+        Option<&HealthChip>,
+        Has<Player>,
+    )>,
+    cameras: Query<(&Camera, &GlobalTransform, &IngameCamera)>,
+    ui_layers: Res<UiLayers>,
+    spatial_query: SpatialQuery,
+    mut egui_contexts: EguiContexts,
+) {
+    if !ui_layers.is_visible(UiLayer::Hud) {
+        return;
+    }
+    let Some((camera, camera_transform, ingame_camera)) = cameras.iter().next() else {
+        return;
+    };
+    let is_first_person = ingame_camera.kind == IngameCameraKind::FirstPerson;
+    let camera_position = camera_transform.translation();
+
+    let mut visible: Vec<_> = plates
+        .iter()
+        .filter(|(.., is_player)| !(is_first_person && *is_player))
+        .filter_map(|(entity, plate, transform, name, health, chip, _)| {
+            let (scale, _, position) = transform.to_scale_rotation_translation();
+            let head = position + Vec3::Y * (player::HEIGHT / 2. + 0.4) * scale.y;
+            let distance = camera_position.distance(head);
+            (distance <= plate.max_distance)
+                .then_some((entity, plate, head, distance, name, health, chip))
+        })
+        .collect();
+    visible.sort_by(|a, b| a.3.total_cmp(&b.3));
+
+    let ctx = egui_contexts.ctx_mut();
+    for (entity, plate, head, distance, name, health, chip) in visible
+        .into_iter()
+        // Only raycast for plates that could be drawn.
+        // `has_line_of_sight` lifts both ends from the feet of a character, which we already did ourselves.
+        .filter(|(_, _, head, ..)| {
+            let lift = Vec3::Y * player::HEIGHT / 2.;
+            has_line_of_sight(&spatial_query, camera_position - lift, *head - lift)
+        })
+        .take(MAX_VISIBLE_PLATES)
+    {
+        let Some(screen_position) = camera.world_to_viewport(camera_transform, head) else {
+            continue;
+        };
+        let fade_start = plate.max_distance * FADE_START;
+        let opacity = 1.
+            - ((distance - fade_start) / (plate.max_distance - fade_start).max(1e-5)).clamp(0., 1.);
+        let alpha = (opacity * 255.) as u8;
+
+        egui::Area::new(egui::Id::new(("Nameplate", entity)))
+            .order(UiLayer::Hud.order())
+            .fixed_pos(egui::pos2(screen_position.x, screen_position.y))
+            .pivot(egui::Align2::CENTER_BOTTOM)
+            .interactable(false)
+            .show(ctx, |ui| {
+                ui.vertical_centered(|ui| {
+                    if let Some(name) = name.filter(|_| plate.show_name) {
+                        ui.label(
+                            egui::RichText::new(name.as_str())
+                                .color(egui::Color32::from_white_alpha(alpha)),
+                        );
+                    }
+                    if let Some(health) = health.filter(|_| plate.show_health) {
+                        let chip = chip.map_or(health.fraction(), |chip| chip.fraction);
+                        draw_health_bar(ui, health.fraction(), chip, alpha);
+                    }
+                });
+            });
+    }
+}
+
+fn draw_health_bar(ui: &mut egui::Ui, health: f32, chip: f32, alpha: u8) {
+    let (rect, _) = ui.allocate_exact_size(HEALTH_BAR_SIZE, egui::Sense::hover());
+    let painter = ui.painter();
+    let with_width = |fraction: f32| {
+        egui::Rect::from_min_size(rect.min, egui::vec2(rect.width() * fraction, rect.height()))
+    };
+    let alpha = alpha as f32 / 255.;
+    painter.rect_filled(
+        rect,
+        2.,
+        egui::Color32::from_black_alpha(160).gamma_multiply(alpha),
+    );
+    painter.rect_filled(
+        with_width(chip),
+        2.,
+        egui::Color32::from_rgb(240, 200, 120).gamma_multiply(alpha),
+    );
+    painter.rect_filled(
+        with_width(health),
+        2.,
+        egui::Color32::from_rgb(200, 40, 40).gamma_multiply(alpha),
+    );
+}
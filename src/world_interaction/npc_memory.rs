@@ -0,0 +1,310 @@
+use crate::{
+    level_instantiation::{
+        on_spawn::Npc,
+        stable_id::{StableId, StableIdRegistry},
+    },
+    world_interaction::{
+        dialog::{
+            commands::DialogPosition, small_talk::SmallTalkHistory, CurrentDialogTarget,
+            YarnCommandsAppExt,
+        },
+        inventory::Inventory,
+        time_of_day::TimeOfDay,
+    },
+    GameState,
+};
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet, Uuid},
+};
+use bevy_yarnspinner::{
+    events::{DialogueCompleteEvent, DialogueStartEvent, NodeCompleteEvent},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+
+/// Remembers how the player has interacted with each NPC, so that NPCs can greet differently based on it,
+/// e.g. "Back again already?". A talk counts once its dialog has ended, so a greeting sees the talks before the current one.
+///
+/// Dialog reads the memory through functions that take the NPC's name or its [`StableId`], e.g. `{$subject_id}`:
+/// - `times_talked("blacksmith")` is how often the player has talked to the NPC.
+/// - `hours_since_talked("blacksmith")` is how many game hours ago the last talk ended, -1 if there was none.
+/// - `node_completed("blacksmith", "Blacksmith_Quest")` is true if a dialog with the NPC went through the node.
+/// - `gifts_given("blacksmith", "apple")` is how many of the item the player has given the NPC.
+///
+/// `<<gift item_id count>>` takes items from the player and remembers them as given to the NPC the player is talking to.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<NpcMemory>()
+        .register_type::<NpcRecord>()
+        .init_resource::<NpcMemory>()
+        .init_resource::<YarnNpcMemory>()
+        .init_resource::<Conversation>()
+        .add_yarn_commands(register_memory_commands)
+        .add_systems(
+            Update,
+            (
+                register_memory_functions,
+                remember_conversations,
+                mirror_memory_to_yarn,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// The interaction history of every NPC the player has interacted with, by the NPC's [`StableId`],
+/// so that it outlives the NPC's entity when its level is left. Stored in saves.
+#[derive(Debug, Clone, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct NpcMemory(HashMap<StableId, NpcRecord>);
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct NpcRecord {
+    /// The NPC's name when it was last interacted with, so that dialog and the console can refer to it.
+    pub(crate) name: String,
+    pub(crate) times_talked: u32,
+    /// See [`TimeOfDay::total_hours`].
+    pub(crate) last_talked: Option<f32>,
+    /// The yarn nodes dialogs with the NPC went through.
+    pub(crate) completed_nodes: HashSet<String>,
+    /// How many of each item the player has given the NPC, by item id.
+    pub(crate) gifts: HashMap<String, u32>,
+    /// Which of the NPC's [`SmallTalk`](crate::world_interaction::dialog::small_talk::SmallTalk) nodes were already used.
+    pub(crate) small_talk: SmallTalkHistory,
+}
+
+impl NpcRecord {
+    /// How many game hours ago the last talk ended.
+    pub(crate) fn hours_since_talked(&self, time_of_day: &TimeOfDay) -> Option<f32> {
+        self.last_talked
+            .map(|last_talked| (time_of_day.total_hours() - last_talked).max(0.))
+    }
+
+    pub(crate) fn gifts_given(&self, item: &str) -> u32 {
+        self.gifts.get(item).copied().unwrap_or_default()
+    }
+}
+
+impl NpcMemory {
+    pub(crate) fn get(&self, id: StableId) -> Option<&NpcRecord> {
+        self.0.get(&id)
+    }
+
+    /// The record of an NPC, created empty if the player has not interacted with it yet.
+    pub(crate) fn entry(&mut self, id: StableId) -> &mut NpcRecord {
+        self.0.entry(id).or_default()
+    }
+
+    /// Removes everything remembered about an NPC.
+    pub(crate) fn forget(&mut self, id: StableId) {
+        self.0.remove(&id);
+    }
+
+    /// Finds an NPC by its [`StableId`] or, ignoring case, by the name it had when it was last interacted with.
+    pub(crate) fn find(&self, npc: &str) -> Option<(StableId, &NpcRecord)> {
+        if let Ok(uuid) = Uuid::parse_str(npc.trim()) {
+            let id = StableId(uuid);
+            return self.get(id).map(|record| (id, record));
+        }
+        self.0
+            .iter()
+            .find(|(_, record)| record.name.eq_ignore_ascii_case(npc.trim()))
+            .map(|(&id, record)| (id, record))
+    }
+
+    /// All remembered NPCs, sorted by name.
+    pub(crate) fn records(&self) -> Vec<(StableId, &NpcRecord)> {
+        let mut records: Vec<_> = self.0.iter().map(|(&id, record)| (id, record)).collect();
+        records.sort_unstable_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+        records
+    }
+}
+
+/// The NPC the running dialog is with, kept until the dialog ends because [`CurrentDialogTarget`] is cleared in the same frame.
+#[derive(Debug, Clone, Default, Resource)]
+struct Conversation {
+    partner: Option<(StableId, String)>,
+}
+
+/// A copy of the [`NpcMemory`] and the game time for the yarn functions, which cannot access the world.
+#[derive(Debug, Clone, Default, Resource)]
+struct YarnNpcMemory(Arc<RwLock<(NpcMemory, TimeOfDay)>>);
+
+impl YarnNpcMemory {
+    fn update(&self, memory: Option<&NpcMemory>, time_of_day: &TimeOfDay) {
+        match self.0.write() {
+            Ok(mut copy) => {
+                if let Some(memory) = memory {
+                    copy.0.clone_from(memory);
+                }
+                copy.1 = *time_of_day;
+            }
+            Err(error) => error!("Failed to update the NPC memory for yarn: {error}"),
+        }
+    }
+
+    fn read<T>(&self, npc: &str, default: T, read: impl FnOnce(&NpcRecord, &TimeOfDay) -> T) -> T {
+        match self.0.read() {
+            Ok(copy) => {
+                let (memory, time_of_day) = &*copy;
+                memory
+                    .find(npc)
+                    .map_or(default, |(_, record)| read(record, time_of_day))
+            }
+            Err(_) => default,
+        }
+    }
+}
+
+fn register_memory_commands(dialogue_runner: &mut DialogueRunner) {
+    dialogue_runner.commands_mut().add_command("gift", gift);
+}
+
+fn gift(
+    In((item, count)): In<(String, f32)>,
+    dialog_target: Res<CurrentDialogTarget>,
+    registry: Res<StableIdRegistry>,
+    mut inventory: ResMut<Inventory>,
+    mut memory: ResMut<NpcMemory>,
+    conversation: Res<Conversation>,
+    yarn_memory: Res<YarnNpcMemory>,
+    time_of_day: Res<TimeOfDay>,
+    position: Res<DialogPosition>,
+) {
+    let count = count.max(0.) as u32;
+    let id = dialog_target
+        .0
+        .and_then(|target| registry.id_of(target))
+        .or(conversation.partner.as_ref().map(|(id, _)| *id));
+    let Some(id) = id else {
+        error!(
+            "<<gift>> in {}: the player is not talking to an NPC with a stable id",
+            *position
+        );
+        return;
+    };
+    if !inventory.remove(&item, count) {
+        warn!(
+            "<<gift>> in {}: wanted to give {count} of \"{item}\", but the player only has {}",
+            *position,
+            inventory.count(&item)
+        );
+        return;
+    }
+    *memory.entry(id).gifts.entry(item).or_default() += count;
+    // The rest of the node may already ask about the gift in this frame
+    yarn_memory.update(Some(&memory), &time_of_day);
+}
+
+fn register_memory_functions(
+    mut dialogue_runners: Query<&mut DialogueRunner, Added<DialogueRunner>>,
+    yarn_memory: Res<YarnNpcMemory>,
+) {
+    for mut dialogue_runner in dialogue_runners.iter_mut() {
+        let times_talked = yarn_memory.clone();
+        let hours_since_talked = yarn_memory.clone();
+        let node_completed = yarn_memory.clone();
+        let gifts_given = yarn_memory.clone();
+        dialogue_runner
+            .library_mut()
+            .add_function("times_talked", move |npc: String| {
+                times_talked.read(&npc, 0., |record, _| record.times_talked as f32)
+            })
+            .add_function("hours_since_talked", move |npc: String| {
+                hours_since_talked.read(&npc, -1., |record, time_of_day| {
+                    record.hours_since_talked(time_of_day).unwrap_or(-1.)
+                })
+            })
+            .add_function("node_completed", move |npc: String, node: String| {
+                node_completed.read(&npc, false, |record, _| {
+                    record.completed_nodes.contains(&node)
+                })
+            })
+            .add_function("gifts_given", move |npc: String, item: String| {
+                gifts_given.read(&npc, 0., |record, _| record.gifts_given(&item) as f32)
+            });
+    }
+}
+
+fn remember_conversations(
+    mut dialogue_start_events: EventReader<DialogueStartEvent>,
+    mut node_complete_events: EventReader<NodeCompleteEvent>,
+    mut dialogue_complete_events: EventReader<DialogueCompleteEvent>,
+    dialog_target: Res<CurrentDialogTarget>,
+    npcs: Query<(&StableId, Option<&Name>), With<Npc>>,
+    time_of_day: Res<TimeOfDay>,
+    mut conversation: ResMut<Conversation>,
+    mut memory: ResMut<NpcMemory>,
+) {
+    if dialogue_start_events.read().count() > 0 {
+        // Dialogs started by triggers or cutscenes have no target and are not remembered
+        conversation.partner = dialog_target
+            .0
+            .and_then(|target| npcs.get(target).ok())
+            .map(|(id, name)| (*id, name.map(ToString::to_string).unwrap_or_default()));
+    }
+    let Some((id, name)) = conversation.partner.clone() else {
+        node_complete_events.clear();
+        dialogue_complete_events.clear();
+        return;
+    };
+    for event in node_complete_events.read() {
+        memory
+            .entry(id)
+            .completed_nodes
+            .insert(event.node_name.clone());
+    }
+    if dialogue_complete_events.read().count() == 0 {
+        return;
+    }
+    let record = memory.entry(id);
+    if !name.is_empty() {
+        record.name = name;
+    }
+    record.times_talked += 1;
+    record.last_talked = Some(time_of_day.total_hours());
+    conversation.partner = None;
+}
+
+fn mirror_memory_to_yarn(
+    memory: Res<NpcMemory>,
+    time_of_day: Res<TimeOfDay>,
+    yarn_memory: Res<YarnNpcMemory>,
+) {
+    if memory.is_changed() {
+        yarn_memory.update(Some(&memory), &time_of_day);
+    } else if time_of_day.is_changed() {
+        yarn_memory.update(None, &time_of_day);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn npcs_are_found_by_id_or_name() {
+        let mut memory = NpcMemory::default();
+        let id = StableId::derive("levels/village.glb", "Blacksmith");
+        let record = memory.entry(id);
+        record.name = "Blacksmith".to_string();
+        record.times_talked = 2;
+        record.last_talked = Some(20.);
+
+        let (found, record) = memory.find("blacksmith").unwrap();
+        assert_eq!(found, id);
+        assert_eq!(record.times_talked, 2);
+        assert_eq!(memory.find(&id.0.to_string()).unwrap().0, id);
+        assert!(memory.find("Baker").is_none());
+
+        let time_of_day = TimeOfDay {
+            hour: 2.,
+            day: 1,
+            ..default()
+        };
+        assert_eq!(record.hours_since_talked(&time_of_day), Some(6.));
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{world_interaction::interaction_ui::InteractRequestEvent, GameState};
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::iter;
+
+/// Lets interactables with an [`OnHitInteraction`] be used by hitting them, e.g. ringing a bell by throwing a rock at it.
+/// Anything [`Thrown`] that starts touching such a target, or a [`ProjectileImpactEvent`] sent by other systems,
+/// interacts with it in the name of the thrower. Unlike regular interactions, range and facing are not checked.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<OnHitInteraction>()
+        .register_type::<Thrown>()
+        .add_event::<ProjectileImpactEvent>()
+        .add_systems(
+            Update,
+            (detect_thrown_impacts, interact_on_hit)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Hitting this with something [`Thrown`] fast enough interacts with it. Only dialog is started this way:
+/// hitting a seat or a readable does nothing, since the thrower is not there to sit down or read.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct OnHitInteraction {
+    /// In m/s. Slower hits are ignored, so that e.g. a prop rolling against the target does not count.
+    pub(crate) min_impact_speed: f32,
+    /// In seconds. Hits during this time after a successful one are ignored, so that a bouncing prop only counts once.
+    pub(crate) cooldown: f32,
+}
+
+impl Default for OnHitInteraction {
+    fn default() -> Self {
+        Self {
+            min_impact_speed: 3.,
+            cooldown: 1.,
+        }
+    }
+}
+
+/// Put this on a prop or projectile when something throws or shoots it, so that its hits are credited to the thrower.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect)]
+#[reflect(Component)]
+pub(crate) struct Thrown {
+    pub(crate) by: Entity,
+    /// Physics has already slowed the body down by the time its collision is reported, so the impact speed is taken from before.
+    #[reflect(ignore)]
+    velocity_before_step: Vec3,
+}
+
+impl Thrown {
+    pub(crate) fn by(thrower: Entity) -> Self {
+        Self {
+            by: thrower,
+            velocity_before_step: Vec3::ZERO,
+        }
+    }
+}
+
+/// Sent when something [`Thrown`] hits a collider.
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub(crate) struct ProjectileImpactEvent {
+    pub(crate) projectile: Entity,
+    pub(crate) thrower: Entity,
+    /// The collider that was hit. May belong to a child of the entity that is interacted with.
+    pub(crate) target: Entity,
+    /// In m/s.
+    pub(crate) speed: f32,
+}
+
+/// Hits during an [`OnHitInteraction::cooldown`] are ignored.
+#[derive(Debug, Clone, PartialEq, Component)]
+struct OnHitCooldown(Timer);
+
+fn detect_thrown_impacts(
+    mut collision_events: EventReader<CollisionStarted>,
+    mut projectiles: Query<(&mut Thrown, Option<&LinearVelocity>)>,
+    velocities: Query<&LinearVelocity>,
+    mut impact_events: EventWriter<ProjectileImpactEvent>,
+) {
+    for CollisionStarted(entity1, entity2) in collision_events.read() {
+        for (projectile, target) in [(*entity1, *entity2), (*entity2, *entity1)] {
+            let Ok((thrown, _)) = projectiles.get(projectile) else {
+                continue;
+            };
+            // The thrower's own collider is touched right when throwing
+            if target == thrown.by {
+                continue;
+            }
+            let target_velocity = velocities
+                .get(target)
+                .map_or(Vec3::ZERO, |velocity| velocity.0);
+            impact_events.send(ProjectileImpactEvent {
+                projectile,
+                thrower: thrown.by,
+                target,
+                speed: (thrown.velocity_before_step - target_velocity).length(),
+            });
+        }
+    }
+    for (mut thrown, velocity) in projectiles.iter_mut() {
+        thrown.velocity_before_step = velocity.map_or(Vec3::ZERO, |velocity| velocity.0);
+    }
+}
+
+fn interact_on_hit(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut impact_events: EventReader<ProjectileImpactEvent>,
+    parents: Query<&Parent>,
+    targets: Query<&OnHitInteraction>,
+    mut cooldowns: Query<(Entity, &mut OnHitCooldown)>,
+    mut interact_requests: EventWriter<InteractRequestEvent>,
+) {
+    for (entity, mut cooldown) in cooldowns.iter_mut() {
+        if cooldown.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<OnHitCooldown>();
+        }
+    }
+    // The cooldowns inserted below only take effect next frame
+    let mut hit_this_frame = Vec::new();
+    for impact in impact_events.read() {
+        // Like sensors, a collider counts for any of its ancestors
+        let Some((target, on_hit)) = iter::once(impact.target)
+            .chain(parents.iter_ancestors(impact.target))
+            .find_map(|entity| Some((entity, targets.get(entity).ok()?)))
+        else {
+            continue;
+        };
+        let is_cooling_down = cooldowns
+            .get(target)
+            .is_ok_and(|(_, cooldown)| !cooldown.0.finished());
+        if impact.speed < on_hit.min_impact_speed
+            || is_cooling_down
+            || hit_this_frame.contains(&target)
+        {
+            continue;
+        }
+        hit_this_frame.push(target);
+        commands
+            .entity(target)
+            .insert(OnHitCooldown(Timer::from_seconds(
+                on_hit.cooldown.max(0.),
+                TimerMode::Once,
+            )));
+        interact_requests.send(InteractRequestEvent {
+            initiator: impact.thrower,
+            target,
+            by_hit: true,
+        });
+    }
+}
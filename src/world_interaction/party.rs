@@ -0,0 +1,222 @@
+use crate::{
+    level_instantiation::{
+        named_entities::EntityNames,
+        on_spawn::Npc,
+        stable_id::{StableId, StableIdRegistry, StableIdSystemSet},
+    },
+    movement::navigation::{Companion, NavigationDestination},
+    world_interaction::dialog::{
+        commands::DialogPosition, CurrentDialogTarget, YarnCommandsAppExt,
+    },
+    GameState,
+};
+use bevy::{
+    prelude::*,
+    utils::{HashMap, Uuid},
+};
+use bevy_yarnspinner::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How close a former party member walks to the location it was sent to by `<<leave_party>>`.
+const LEAVE_STOPPING_DISTANCE: f32 = 0.5;
+
+/// Lets dialog recruit NPCs as [`Companion`]s and dismiss them again:
+/// - `<<join_party npc_name>>` makes an NPC follow the player.
+/// - `<<leave_party npc_name>>` despawns a party member, `<<leave_party npc_name location_name>>` makes it walk to an entity instead.
+///
+/// NPCs are referred to by [`Name`] or by [`StableId`]. Party members travel through portals along with the player.
+/// A member that is still talking to the player leaves once the dialog is over.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Party>()
+        .init_resource::<Party>()
+        .add_yarn_commands(register_commands)
+        .add_systems(
+            Update,
+            (track_party_members, leave_party_after_dialog)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            PostUpdate,
+            restore_party_members
+                .after(StableIdSystemSet::Derive)
+                .before(StableIdSystemSet::Register)
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+fn register_commands(dialogue_runner: &mut DialogueRunner) {
+    dialogue_runner
+        .commands_mut()
+        .add_command("join_party", join_party)
+        .add_command("leave_party", leave_party);
+}
+
+/// The [`StableId`]s of all [`Companion`]s. Stored in saves.
+/// Despawning a companion, e.g. together with its level when loading a save, does not make it leave the party.
+/// When a level spawns an entity listed here, it becomes a companion again, e.g. after loading a save.
+/// If the member already exists, e.g. because it travelled here with the player, the new copy is removed instead.
+#[derive(Debug, Clone, PartialEq, Eq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct Party(pub(crate) Vec<StableId>);
+
+/// Set by `<<leave_party>>`, carried out by [`leave_party_after_dialog`].
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct LeavingParty {
+    /// Where to walk to. Without one, the member is despawned.
+    destination: Option<Vec3>,
+}
+
+fn join_party(
+    In(npc_name): In<String>,
+    mut commands: Commands,
+    names: EntityNames,
+    stable_ids: Res<StableIdRegistry>,
+    npcs: Query<Has<Companion>, With<Npc>>,
+    position: Res<DialogPosition>,
+) {
+    let context = format!("<<join_party>> in {}", *position);
+    let Some(npc) = find_npc(&npc_name, &names, &stable_ids, &context) else {
+        return;
+    };
+    match npcs.get(npc) {
+        Ok(true) => debug!("{context}: \"{npc_name}\" is already in the party"),
+        Ok(false) => {
+            commands
+                .entity(npc)
+                .remove::<LeavingParty>()
+                .insert(Companion::default());
+        }
+        Err(_) => error!("{context}: \"{npc_name}\" is not an NPC"),
+    }
+}
+
+fn leave_party(
+    In((npc_name, location_name)): In<(String, Option<String>)>,
+    mut commands: Commands,
+    names: EntityNames,
+    stable_ids: Res<StableIdRegistry>,
+    members: Query<(), With<Companion>>,
+    locations: Query<&GlobalTransform>,
+    position: Res<DialogPosition>,
+) {
+    let context = format!("<<leave_party>> in {}", *position);
+    let Some(npc) = find_npc(&npc_name, &names, &stable_ids, &context) else {
+        return;
+    };
+    if !members.contains(npc) {
+        error!("{context}: \"{npc_name}\" is not in the party");
+        return;
+    }
+    let destination = match location_name {
+        Some(location_name) => {
+            let Some(location) = names
+                .get_or_report(&location_name, &context)
+                .and_then(|entity| locations.get(entity).ok())
+            else {
+                return;
+            };
+            Some(location.translation())
+        }
+        None => None,
+    };
+    commands.entity(npc).insert(LeavingParty { destination });
+}
+
+/// Resolves an NPC by [`Name`] or, failing that, by the UUID of its [`StableId`].
+fn find_npc(
+    name: &str,
+    names: &EntityNames,
+    stable_ids: &StableIdRegistry,
+    context: &str,
+) -> Option<Entity> {
+    let by_id = || {
+        Uuid::parse_str(name)
+            .ok()
+            .and_then(|id| stable_ids.get(StableId(id)))
+    };
+    let entity = names.get(name).or_else(by_id);
+    if entity.is_none() {
+        error!("{context}: no entity named or with the id \"{name}\"");
+    }
+    entity
+}
+
+fn track_party_members(
+    mut party: ResMut<Party>,
+    joined: Query<(Entity, &StableId), Added<Companion>>,
+    mut removed: RemovedComponents<Companion>,
+    entities: &Entities,
+    mut member_ids: Local<HashMap<Entity, StableId>>,
+) {
+    for (entity, id) in joined.iter() {
+        member_ids.insert(entity, *id);
+        if !party.0.contains(id) {
+            party.0.push(*id);
+        }
+    }
+    for entity in removed.read() {
+        let Some(id) = member_ids.remove(&entity) else {
+            continue;
+        };
+        if entities.contains(entity) {
+            party.0.retain(|member| *member != id);
+        }
+    }
+}
+
+fn leave_party_after_dialog(
+    mut commands: Commands,
+    leaving: Query<(Entity, &LeavingParty, Option<&StableId>)>,
+    dialog_target: Res<CurrentDialogTarget>,
+    mut party: ResMut<Party>,
+) {
+    for (entity, leaving, id) in leaving.iter() {
+        if dialog_target.0 == Some(entity) {
+            continue;
+        }
+        match leaving.destination {
+            Some(position) => {
+                commands
+                    .entity(entity)
+                    .remove::<(LeavingParty, Companion)>()
+                    .insert(NavigationDestination {
+                        position,
+                        stopping_distance: LEAVE_STOPPING_DISTANCE,
+                    });
+            }
+            None => {
+                // Despawned members would otherwise stay in the party
+                if let Some(id) = id {
+                    party.0.retain(|member| member != id);
+                }
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+fn restore_party_members(
+    mut commands: Commands,
+    party: Res<Party>,
+    spawned: Query<
+        (Entity, &StableId),
+        (
+            With<Npc>,
+            Without<Companion>,
+            Or<(Added<StableId>, Added<Npc>)>,
+        ),
+    >,
+    members: Query<&StableId, With<Companion>>,
+) {
+    for (entity, id) in spawned.iter() {
+        if !party.0.contains(id) {
+            continue;
+        }
+        if members.iter().any(|member| member == id) {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            commands.entity(entity).insert(Companion::default());
+        }
+    }
+}
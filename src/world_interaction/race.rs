@@ -0,0 +1,754 @@
+use crate::{
+    file_system_interaction::localization::{t, Strings},
+    gameplay_log::{log_event, GameplayLog},
+    level_instantiation::portal::Travel,
+    player_control::{
+        actions::ActionsFrozen,
+        camera::{CursorGrabRequests, IngameCamera},
+        ui_layer::{UiLayer, UiLayers},
+    },
+    util::math_trait_ext::{F32Ext, Vec3Ext},
+    world_interaction::{
+        dialog::{CurrentDialogTarget, DialogInitiator},
+        interaction_sensor::{InteractionSensor, SensorShape},
+        nameplate::Health,
+        waypoint::MapMarker,
+        world_flags::{FlagValue, WorldFlags},
+    },
+    GameState,
+};
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use bevy_egui::{egui, EguiContexts};
+use bevy_xpbd_3d::prelude::*;
+use bevy_yarnspinner::prelude::DialogueRunner;
+use serde::{Deserialize, Serialize};
+use std::iter;
+
+/// How long the outcome of a run stays on screen, in seconds.
+const OUTCOME_DURATION: f32 = 4.;
+/// How long the delta of a split stays on screen, in seconds.
+const SPLIT_DURATION: f32 = 3.;
+/// How long "Go!" shows after the countdown, in seconds.
+const GO_DURATION: f32 = 0.6;
+const ARROW_LENGTH: f32 = 24.;
+const NEXT_VOLUME_ICON: &str = "▲";
+const NEXT_VOLUME_COLOR: Color = Color::rgb(0.3, 0.9, 1.);
+const FASTER_COLOR: egui::Color32 = egui::Color32::from_rgb(120, 230, 120);
+const SLOWER_COLOR: egui::Color32 = egui::Color32::from_rgb(240, 110, 90);
+
+/// Timed challenges made of a [`RaceStart`], ordered [`RaceCheckpoint`]s and a [`RaceFinish`], all sharing a race id.
+/// Interacting with the start freezes the player for a countdown, then the timer runs until the finish is reached.
+/// The checkpoints are sensors that must be entered in order: entering a later one first invalidates the run.
+/// The next one shows on the compass and a HUD arrow points to it, next to the timer and the split deltas against the best run.
+///
+/// The best run of every race is kept in the [`RaceRecords`], which are stored in saves. Finishing sets the flags of the
+/// [`RaceFinish`] and starts its yarn node, e.g. to hand out a reward. Walking too far away from the next checkpoint,
+/// dying or leaving the level abandons the run, after which the race can be started again right away.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<RaceStart>()
+        .register_type::<RaceCheckpoint>()
+        .register_type::<RaceFinish>()
+        .register_type::<RaceRecords>()
+        .register_type::<RaceRecord>()
+        .init_resource::<RaceRecords>()
+        .init_resource::<ActiveRace>()
+        .add_event::<StartRaceRequest>()
+        .add_systems(
+            Update,
+            (
+                spawn_race_starts,
+                start_races,
+                tick_races,
+                enter_race_volumes,
+                abandon_races,
+                mark_next_race_volume,
+                display_race_hud,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Starts a run of the race [`RaceStart::id`] when the player interacts with it.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct RaceStart {
+    /// Identifies the race in the [`RaceRecords`] and ties its [`RaceCheckpoint`]s and [`RaceFinish`] to it.
+    pub(crate) id: String,
+    /// In seconds. The player is frozen until it is over.
+    pub(crate) countdown: f32,
+    /// The run is abandoned when the player is this far away from the next checkpoint, in meters.
+    pub(crate) abandon_distance: f32,
+}
+
+impl Default for RaceStart {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            countdown: 3.,
+            abandon_distance: 60.,
+        }
+    }
+}
+
+/// A checkpoint of the race `race`. Put it on a sensor or on an ancestor of one.
+/// Checkpoints are passed in the order of their `index`, which does not need to be contiguous.
+#[derive(Debug, Clone, PartialEq, Eq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct RaceCheckpoint {
+    pub(crate) race: String,
+    pub(crate) index: u32,
+}
+
+/// The finish of the race `race`. Put it on a sensor or on an ancestor of one.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct RaceFinish {
+    pub(crate) race: String,
+    /// Set every time the race is finished.
+    pub(crate) flags: HashMap<String, FlagValue>,
+    /// Set when the run beats the best one, including the first finished run.
+    pub(crate) record_flags: HashMap<String, FlagValue>,
+    /// Started after finishing, once the flags are set.
+    pub(crate) node: Option<String>,
+}
+
+/// The best run of every race, by race id. Stored in saves.
+#[derive(Debug, Clone, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct RaceRecords(pub(crate) HashMap<String, RaceRecord>);
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct RaceRecord {
+    /// In seconds.
+    pub(crate) time: f32,
+    /// When each checkpoint was passed, in seconds since the start.
+    pub(crate) splits: Vec<f32>,
+}
+
+impl RaceRecords {
+    /// Keeps the run if it is the best one of the race so far and tells whether it was.
+    pub(crate) fn submit(&mut self, race: &str, run: RaceRecord) -> bool {
+        let is_best = self.0.get(race).map_or(true, |best| run.time < best.time);
+        if is_best {
+            self.0.insert(race.to_string(), run);
+        }
+        is_best
+    }
+}
+
+/// Starts a run of the race of `start` for `racer`. Sent by interacting with a [`RaceStart`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub(crate) struct StartRaceRequest {
+    pub(crate) racer: Entity,
+    pub(crate) start: Entity,
+}
+
+/// The run in progress, if any, and the outcome of the last one.
+#[derive(Debug, Default, Resource)]
+pub(crate) struct ActiveRace {
+    run: Option<RaceRun>,
+    /// Shown for a while after a run ends, counting down to zero.
+    outcome: Option<(RaceOutcome, f32)>,
+}
+
+impl ActiveRace {
+    pub(crate) fn is_racing(&self) -> bool {
+        self.run.is_some()
+    }
+
+    fn end(&mut self, outcome: RaceOutcome) -> Option<RaceRun> {
+        self.outcome = Some((outcome, OUTCOME_DURATION));
+        self.run.take()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct RaceRun {
+    race: String,
+    racer: Entity,
+    abandon_distance: f32,
+    /// Seconds left of the countdown. The timer runs once it is over.
+    countdown: f32,
+    elapsed: f32,
+    /// The checkpoints in the order they must be passed.
+    course: Vec<Entity>,
+    finishes: Vec<Entity>,
+    /// When each passed checkpoint was passed, in seconds since the start.
+    splits: Vec<f32>,
+    /// The run the splits are compared against.
+    best: Option<RaceRecord>,
+    /// Seconds since the last checkpoint was passed.
+    since_split: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Progress {
+    Passed,
+    Finished,
+    /// A checkpoint was left out.
+    Skipped,
+    /// An already passed checkpoint or one of another race.
+    Ignored,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum RaceOutcome {
+    Finished {
+        time: f32,
+        previous_best: Option<f32>,
+    },
+    MissedCheckpoint,
+    Abandoned,
+}
+
+impl RaceRun {
+    fn is_counting_down(&self) -> bool {
+        self.countdown > 0.
+    }
+
+    /// The checkpoints to pass next, or the finishes once all are passed.
+    fn next_volumes(&self) -> &[Entity] {
+        match self.course.get(self.splits.len()) {
+            Some(checkpoint) => std::slice::from_ref(checkpoint),
+            None => &self.finishes,
+        }
+    }
+
+    fn enter(&mut self, volume: Entity) -> Progress {
+        if self.finishes.contains(&volume) {
+            return if self.splits.len() == self.course.len() {
+                Progress::Finished
+            } else {
+                Progress::Skipped
+            };
+        }
+        let Some(index) = self
+            .course
+            .iter()
+            .position(|&checkpoint| checkpoint == volume)
+        else {
+            return Progress::Ignored;
+        };
+        if index < self.splits.len() {
+            Progress::Ignored
+        } else if index > self.splits.len() {
+            Progress::Skipped
+        } else {
+            self.splits.push(self.elapsed);
+            self.since_split = 0.;
+            Progress::Passed
+        }
+    }
+
+    /// How much faster (negative) or slower the last split was than the same one of the best run.
+    fn last_split_delta(&self) -> Option<f32> {
+        let index = self.splits.len().checked_sub(1)?;
+        let best = self.best.as_ref()?.splits.get(index)?;
+        Some(self.splits[index] - best)
+    }
+
+    fn record(&self) -> RaceRecord {
+        RaceRecord {
+            time: self.elapsed,
+            splits: self.splits.clone(),
+        }
+    }
+}
+
+/// What finishing a race hands out, see [`RaceFinish`].
+#[derive(SystemParam)]
+struct FinishRewards<'w, 's> {
+    flags: ResMut<'w, WorldFlags>,
+    dialogue_runners: Query<'w, 's, &'static mut DialogueRunner>,
+    current_dialog_target: ResMut<'w, CurrentDialogTarget>,
+    dialog_initiator: ResMut<'w, DialogInitiator>,
+    cursor_grab: ResMut<'w, CursorGrabRequests>,
+}
+
+impl FinishRewards<'_, '_> {
+    fn hand_out(
+        &mut self,
+        finish: Entity,
+        hooks: &RaceFinish,
+        racer: Entity,
+        is_record: bool,
+        freeze: &mut ActionsFrozen,
+    ) {
+        let record_flags = hooks.record_flags.iter().filter(|_| is_record);
+        for (flag, value) in hooks.flags.iter().chain(record_flags) {
+            if self.flags.get(flag) != Some(*value) {
+                self.flags.set(flag.clone(), *value);
+            }
+        }
+        let Some(node) = &hooks.node else {
+            return;
+        };
+        let Ok(mut dialogue_runner) = self.dialogue_runners.get_single_mut() else {
+            return;
+        };
+        if dialogue_runner.is_running() {
+            warn!("Cannot start {node} after finishing a race, since another dialog is running");
+            return;
+        }
+        dialogue_runner.start_node(node);
+        self.current_dialog_target.0.replace(finish);
+        self.dialog_initiator.0 = Some(racer);
+        freeze.freeze_player(racer);
+        self.cursor_grab.request_free();
+    }
+}
+
+fn spawn_race_starts(
+    mut commands: Commands,
+    starts: Query<Entity, (Added<RaceStart>, Without<InteractionSensor>)>,
+) {
+    for entity in starts.iter() {
+        commands
+            .entity(entity)
+            .insert(InteractionSensor::new(SensorShape::Cylinder {
+                radius: 1.5,
+                height: 1.,
+            }));
+    }
+}
+
+fn start_races(
+    mut start_requests: EventReader<StartRaceRequest>,
+    mut active_race: ResMut<ActiveRace>,
+    starts: Query<&RaceStart>,
+    checkpoints: Query<(Entity, &RaceCheckpoint)>,
+    finishes: Query<(Entity, &RaceFinish)>,
+    records: Res<RaceRecords>,
+    mut freeze: ResMut<ActionsFrozen>,
+    mut log: ResMut<GameplayLog>,
+) {
+    for request in start_requests.read() {
+        if active_race.is_racing() {
+            continue;
+        }
+        let Ok(start) = starts.get(request.start) else {
+            continue;
+        };
+        let finishes: Vec<_> = finishes
+            .iter()
+            .filter(|(_, finish)| finish.race == start.id)
+            .map(|(entity, _)| entity)
+            .collect();
+        if finishes.is_empty() {
+            warn!("Race {} has no finish in this level", start.id);
+            continue;
+        }
+        let mut course: Vec<_> = checkpoints
+            .iter()
+            .filter(|(_, checkpoint)| checkpoint.race == start.id)
+            .collect();
+        course.sort_by_key(|(_, checkpoint)| checkpoint.index);
+        log_event!(
+            log,
+            Interaction,
+            Some(request.racer),
+            format!("Started race {}", start.id),
+            checkpoints = course.len()
+        );
+        active_race.run = Some(RaceRun {
+            race: start.id.clone(),
+            racer: request.racer,
+            abandon_distance: start.abandon_distance,
+            countdown: start.countdown,
+            elapsed: 0.,
+            course: course.into_iter().map(|(entity, _)| entity).collect(),
+            finishes,
+            splits: Vec::new(),
+            best: records.0.get(&start.id).cloned(),
+            since_split: f32::INFINITY,
+        });
+        active_race.outcome = None;
+        if start.countdown > 0. {
+            freeze.freeze_player(request.racer);
+        }
+    }
+}
+
+fn tick_races(
+    time: Res<Time>,
+    mut active_race: ResMut<ActiveRace>,
+    mut freeze: ResMut<ActionsFrozen>,
+) {
+    let dt = time.delta_seconds();
+    if let Some((_, remaining)) = active_race.outcome.as_mut() {
+        *remaining -= dt;
+        if *remaining <= 0. {
+            active_race.outcome = None;
+        }
+    }
+    let Some(run) = active_race.run.as_mut() else {
+        return;
+    };
+    if run.is_counting_down() {
+        run.countdown -= dt;
+        if !run.is_counting_down() {
+            freeze.unfreeze_player(run.racer);
+        }
+        return;
+    }
+    run.elapsed += dt;
+    run.since_split += dt;
+}
+
+fn enter_race_volumes(
+    mut collision_events: EventReader<CollisionStarted>,
+    mut active_race: ResMut<ActiveRace>,
+    sensors: Query<(), With<Sensor>>,
+    parents: Query<&Parent>,
+    volumes: Query<(), Or<(With<RaceCheckpoint>, With<RaceFinish>)>>,
+    finishes: Query<&RaceFinish>,
+    mut records: ResMut<RaceRecords>,
+    mut rewards: FinishRewards,
+    mut freeze: ResMut<ActionsFrozen>,
+    mut log: ResMut<GameplayLog>,
+) {
+    for CollisionStarted(entity1, entity2) in collision_events.read() {
+        let Some(run) = active_race.run.as_mut() else {
+            collision_events.clear();
+            return;
+        };
+        if run.is_counting_down() {
+            continue;
+        }
+        let sensor = if *entity1 == run.racer {
+            *entity2
+        } else if *entity2 == run.racer {
+            *entity1
+        } else {
+            continue;
+        };
+        if !sensors.contains(sensor) {
+            continue;
+        }
+        let Some(volume) = iter::once(sensor)
+            .chain(parents.iter_ancestors(sensor))
+            .find(|&entity| volumes.contains(entity))
+        else {
+            continue;
+        };
+        match run.enter(volume) {
+            Progress::Passed | Progress::Ignored => {}
+            Progress::Skipped => {
+                let run = active_race.end(RaceOutcome::MissedCheckpoint);
+                if let Some(run) = run {
+                    log_event!(
+                        log,
+                        Interaction,
+                        Some(run.racer),
+                        format!("Missed a checkpoint of race {}", run.race)
+                    );
+                }
+            }
+            Progress::Finished => {
+                let record = run.record();
+                let previous_best = run.best.as_ref().map(|best| best.time);
+                let is_record = records.submit(&run.race, record);
+                let outcome = RaceOutcome::Finished {
+                    time: run.elapsed,
+                    previous_best,
+                };
+                let Some(run) = active_race.end(outcome) else {
+                    continue;
+                };
+                log_event!(
+                    log,
+                    Interaction,
+                    Some(run.racer),
+                    format!("Finished race {}", run.race),
+                    time = run.elapsed,
+                    record = is_record
+                );
+                if let Ok(hooks) = finishes.get(volume) {
+                    rewards.hand_out(volume, hooks, run.racer, is_record, &mut freeze);
+                }
+            }
+        }
+    }
+}
+
+fn abandon_races(
+    mut active_race: ResMut<ActiveRace>,
+    travel: Res<Travel>,
+    racers: Query<(&GlobalTransform, Option<&Health>)>,
+    volumes: Query<&GlobalTransform>,
+    mut freeze: ResMut<ActionsFrozen>,
+    mut log: ResMut<GameplayLog>,
+) {
+    let Some(run) = active_race.run.as_ref() else {
+        return;
+    };
+    let is_abandoned = travel.is_traveling()
+        || match racers.get(run.racer) {
+            Ok((transform, health)) => {
+                let is_dead = health.is_some_and(|health| health.current <= 0.);
+                let is_far = run
+                    .next_volumes()
+                    .iter()
+                    .filter_map(|&volume| volumes.get(volume).ok())
+                    .all(|volume| {
+                        volume
+                            .translation()
+                            .distance_squared(transform.translation())
+                            > run.abandon_distance.squared()
+                    });
+                is_dead || is_far
+            }
+            Err(_) => true,
+        };
+    if !is_abandoned {
+        return;
+    }
+    let Some(run) = active_race.end(RaceOutcome::Abandoned) else {
+        return;
+    };
+    if run.is_counting_down() {
+        freeze.unfreeze_player(run.racer);
+    }
+    log_event!(
+        log,
+        Interaction,
+        Some(run.racer),
+        format!("Abandoned race {}", run.race)
+    );
+}
+
+/// Marks the next checkpoint or finish on the compass. Unmarks everything once the run ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+struct NextRaceVolume;
+
+fn mark_next_race_volume(
+    mut commands: Commands,
+    active_race: Res<ActiveRace>,
+    marked: Query<Entity, With<NextRaceVolume>>,
+) {
+    let next = active_race
+        .run
+        .as_ref()
+        .map_or(&[][..], RaceRun::next_volumes);
+    for entity in marked.iter() {
+        if !next.contains(&entity) {
+            commands
+                .entity(entity)
+                .remove::<(NextRaceVolume, MapMarker)>();
+        }
+    }
+    for &entity in next {
+        if !marked.contains(entity) {
+            if let Some(mut entity_commands) = commands.get_entity(entity) {
+                entity_commands.insert((
+                    NextRaceVolume,
+                    MapMarker {
+                        icon: NEXT_VOLUME_ICON.to_string(),
+                        color: NEXT_VOLUME_COLOR,
+                    },
+                ));
+            }
+        }
+    }
+}
+
+fn display_race_hud(
+    active_race: Res<ActiveRace>,
+    transforms: Query<&GlobalTransform>,
+    cameras: Query<&GlobalTransform, With<IngameCamera>>,
+    ui_layers: Res<UiLayers>,
+    strings: Strings,
+    mut egui_contexts: EguiContexts,
+) {
+    if !ui_layers.is_visible(UiLayer::Hud) {
+        return;
+    }
+    let ctx = egui_contexts.ctx_mut();
+    if let Some((outcome, _)) = &active_race.outcome {
+        let text = match outcome {
+            RaceOutcome::Finished {
+                time,
+                previous_best,
+            } => {
+                let finished = t!(strings, "race.finished", time = format_race_time(*time));
+                match previous_best {
+                    Some(best) if best <= time => {
+                        let best = t!(strings, "race.best", time = format_race_time(*best));
+                        format!("{finished}\n{best}")
+                    }
+                    _ => format!("{finished}\n{}", t!(strings, "race.new_record")),
+                }
+            }
+            RaceOutcome::MissedCheckpoint => t!(strings, "race.missed_checkpoint"),
+            RaceOutcome::Abandoned => t!(strings, "race.abandoned"),
+        };
+        egui::Area::new(egui::Id::new("Race Outcome"))
+            .order(UiLayer::Hud.order())
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0., -80.))
+            .interactable(false)
+            .show(ctx, |ui| {
+                ui.label(egui::RichText::new(text).size(28.).strong());
+            });
+    }
+    let Some(run) = active_race.run.as_ref() else {
+        return;
+    };
+
+    if run.is_counting_down() || run.elapsed < GO_DURATION {
+        let text = if run.is_counting_down() {
+            run.countdown.ceil().to_string()
+        } else {
+            t!(strings, "race.go")
+        };
+        egui::Area::new(egui::Id::new("Race Countdown"))
+            .order(UiLayer::Hud.order())
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0., -80.))
+            .interactable(false)
+            .show(ctx, |ui| {
+                ui.label(egui::RichText::new(text).size(64.).strong());
+            });
+    }
+
+    let arrow_angle = cameras.iter().next().and_then(|camera| {
+        let racer = transforms.get(run.racer).ok()?;
+        let next = run
+            .next_volumes()
+            .iter()
+            .filter_map(|&volume| transforms.get(volume).ok())
+            .map(|volume| volume.translation())
+            .min_by(|a, b| {
+                a.distance_squared(racer.translation())
+                    .total_cmp(&b.distance_squared(racer.translation()))
+            })?;
+        let forward = camera.forward().horizontal().normalize_or_zero().xz();
+        let to_next = (next - racer.translation()).horizontal().xz();
+        // Positive to the right of the camera
+        let angle = forward.angle_between(to_next);
+        angle.is_finite().then_some(angle)
+    });
+    egui::Area::new(egui::Id::new("Race Timer"))
+        .order(UiLayer::Hud.order())
+        .anchor(egui::Align2::LEFT_TOP, egui::vec2(20., 20.))
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::none()
+                .fill(egui::Color32::from_black_alpha(150))
+                .rounding(6.)
+                .inner_margin(egui::Margin::same(10.))
+                .show(ui, |ui| {
+                    ui.label(
+                        egui::RichText::new(format_race_time(run.elapsed))
+                            .size(24.)
+                            .monospace(),
+                    );
+                    if let Some(best) = &run.best {
+                        ui.label(t!(strings, "race.best", time = format_race_time(best.time)));
+                    }
+                    ui.label(t!(
+                        strings,
+                        "race.checkpoints",
+                        passed = run.splits.len(),
+                        total = run.course.len()
+                    ));
+                    if let Some(delta) = run
+                        .last_split_delta()
+                        .filter(|_| run.since_split < SPLIT_DURATION)
+                    {
+                        let color = if delta <= 0. {
+                            FASTER_COLOR
+                        } else {
+                            SLOWER_COLOR
+                        };
+                        ui.label(
+                            egui::RichText::new(format!("{delta:+.2}"))
+                                .color(color)
+                                .monospace(),
+                        );
+                    }
+                    if let Some(angle) = arrow_angle {
+                        let (rect, _) = ui.allocate_exact_size(
+                            egui::Vec2::splat(ARROW_LENGTH * 2.),
+                            egui::Sense::hover(),
+                        );
+                        // Up on screen is straight ahead
+                        let direction = egui::vec2(angle.sin(), -angle.cos()) * ARROW_LENGTH;
+                        ui.painter().arrow(
+                            rect.center() - direction / 2.,
+                            direction,
+                            egui::Stroke::new(4., egui::Color32::WHITE),
+                        );
+                    }
+                });
+        });
+}
+
+/// E.g. "1:05.32" for 65.32 seconds.
+fn format_race_time(seconds: f32) -> String {
+    // Rounded before splitting off the minutes, so that e.g. 59.996 seconds become "1:00.00" instead of "0:60.00"
+    let hundredths = (seconds.max(0.) * 100.).round() as u32;
+    let (minutes, hundredths) = (hundredths / 6000, hundredths % 6000);
+    format!("{minutes}:{:02}.{:02}", hundredths / 100, hundredths % 100)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(best: Option<RaceRecord>) -> RaceRun {
+        RaceRun {
+            race: "Canyon".to_string(),
+            racer: Entity::from_raw(0),
+            abandon_distance: 60.,
+            countdown: 0.,
+            elapsed: 0.,
+            course: vec![Entity::from_raw(1), Entity::from_raw(2)],
+            finishes: vec![Entity::from_raw(3)],
+            splits: Vec::new(),
+            best,
+            since_split: f32::INFINITY,
+        }
+    }
+
+    #[test]
+    fn checkpoints_must_be_passed_in_order_and_only_faster_runs_are_kept() {
+        let [first, second, finish] = [1, 2, 3].map(Entity::from_raw);
+        let mut skipping = run(None);
+        assert_eq!(skipping.enter(second), Progress::Skipped);
+        assert_eq!(skipping.enter(finish), Progress::Skipped);
+
+        let mut records = RaceRecords::default();
+        let mut first_run = run(None);
+        first_run.elapsed = 10.;
+        assert_eq!(first_run.enter(first), Progress::Passed);
+        assert_eq!(first_run.enter(first), Progress::Ignored);
+        assert_eq!(first_run.next_volumes(), [second]);
+        first_run.elapsed = 20.;
+        assert_eq!(first_run.enter(second), Progress::Passed);
+        assert_eq!(first_run.next_volumes(), [finish]);
+        first_run.elapsed = 30.;
+        assert_eq!(first_run.enter(finish), Progress::Finished);
+        assert!(records.submit("Canyon", first_run.record()));
+
+        let mut second_run = run(records.0.get("Canyon").cloned());
+        second_run.elapsed = 9.;
+        second_run.enter(first);
+        assert_eq!(second_run.last_split_delta(), Some(-1.));
+        second_run.elapsed = 22.;
+        second_run.enter(second);
+        assert_eq!(second_run.last_split_delta(), Some(2.));
+        second_run.elapsed = 31.;
+        assert_eq!(second_run.enter(finish), Progress::Finished);
+        assert!(!records.submit("Canyon", second_run.record()));
+        assert_eq!(records.0["Canyon"].time, 30.);
+
+        assert_eq!(format_race_time(65.32), "1:05.32");
+        assert_eq!(format_race_time(59.996), "1:00.00");
+        assert_eq!(format_race_time(9.5), "0:09.50");
+    }
+}
@@ -0,0 +1,285 @@
+use crate::{
+    file_system_interaction::localization::{t, Strings},
+    player_control::{
+        actions::{ActionsFrozen, UiAction, UiActions},
+        camera::CursorGrabRequests,
+        ui_layer::{UiLayer, UiLayers},
+        virtual_cursor::CursorTargetExt,
+    },
+    world_interaction::{
+        interaction_sensor::{InteractionSensor, SensorShape},
+        interaction_ui::request_interactions,
+    },
+    GameState,
+};
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+const PAPER_COLOR: egui::Color32 = egui::Color32::from_rgb(236, 224, 196);
+const INK_COLOR: egui::Color32 = egui::Color32::from_rgb(48, 36, 24);
+/// A line containing only this separates the pages of a text.
+const PAGE_BREAK: &str = "---";
+
+/// Handles signs, notes and books the player can read by interacting with them.
+/// Their text is either stored inline in the [`Readable`] or in a `.txt` or `.md` asset,
+/// which is loaded in the background as soon as the readable spawns.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Readable>()
+        .register_type::<ReadableContent>()
+        .register_type::<AlreadyRead>()
+        .register_type::<CurrentReadTarget>()
+        .register_type::<CollectedNotes>()
+        .init_asset::<ReadableText>()
+        .register_asset_loader(ReadableTextLoader)
+        .init_resource::<CurrentReadTarget>()
+        .init_resource::<CollectedNotes>()
+        .init_resource::<OpenPages>()
+        .add_systems(
+            Update,
+            (
+                spawn_readables,
+                display_readable
+                    .before(request_interactions)
+                    .run_if(|target: Res<CurrentReadTarget>| target.0.is_some()),
+            )
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct Readable {
+    pub(crate) title: String,
+    pub(crate) content: ReadableContent,
+    /// Whether reading this adds its title to the [`CollectedNotes`].
+    pub(crate) collect: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) enum ReadableContent {
+    Text(String),
+    /// Path to a `.txt` or `.md` file.
+    Asset(String),
+}
+
+impl Default for ReadableContent {
+    fn default() -> Self {
+        Self::Text(String::new())
+    }
+}
+
+/// Marks a [`Readable`] the player has already read.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct AlreadyRead;
+
+/// The [`Readable`] that is currently open.
+#[derive(Resource, Debug, Clone, Copy, Eq, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct CurrentReadTarget(pub(crate) Option<Entity>);
+
+/// Titles of all collected notes, in the order they were read.
+#[derive(Resource, Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct CollectedNotes(pub(crate) Vec<String>);
+
+#[derive(Debug, Clone, PartialEq, Asset, Reflect)]
+pub(crate) struct ReadableText(pub(crate) String);
+
+#[derive(Debug, Clone, Component)]
+struct ReadableTextHandle(Handle<ReadableText>);
+
+/// The pages of the open [`Readable`]. Empty while its text is still loading.
+#[derive(Debug, Default, Resource)]
+struct OpenPages {
+    pages: Vec<String>,
+    current: usize,
+}
+
+#[derive(Default)]
+struct ReadableTextLoader;
+
+impl AssetLoader for ReadableTextLoader {
+    type Asset = ReadableText;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut text = String::new();
+            reader.read_to_string(&mut text).await?;
+            Ok(ReadableText(text))
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["txt", "md"]
+    }
+}
+
+fn spawn_readables(
+    mut commands: Commands,
+    readables: Query<(Entity, &Readable, Has<InteractionSensor>), Added<Readable>>,
+    asset_server: Res<AssetServer>,
+) {
+    for (entity, readable, has_sensor) in readables.iter() {
+        let mut entity_commands = commands.entity(entity);
+        if let ReadableContent::Asset(path) = &readable.content {
+            entity_commands.insert(ReadableTextHandle(asset_server.load(path.clone())));
+        }
+        if !has_sensor {
+            entity_commands.insert(InteractionSensor::new(SensorShape::Cylinder {
+                radius: 1.5,
+                height: 1.,
+            }));
+        }
+    }
+}
+
+fn display_readable(
+    mut commands: Commands,
+    mut current_target: ResMut<CurrentReadTarget>,
+    mut open_pages: ResMut<OpenPages>,
+    mut collected_notes: ResMut<CollectedNotes>,
+    mut freeze: ResMut<ActionsFrozen>,
+    mut cursor_grab: ResMut<CursorGrabRequests>,
+    mut egui_contexts: EguiContexts,
+    readables: Query<(&Readable, Option<&ReadableTextHandle>)>,
+    texts: Res<Assets<ReadableText>>,
+    mut ui_actions: UiActions,
+    mut ui_layers: ResMut<UiLayers>,
+    strings: Strings,
+) {
+    let Some(entity) = current_target.0 else {
+        return;
+    };
+    let Ok((readable, text_handle)) = readables.get(entity) else {
+        // The readable was despawned while open
+        close(
+            &mut current_target,
+            &mut open_pages,
+            &mut freeze,
+            &mut cursor_grab,
+        );
+        return;
+    };
+    if open_pages.pages.is_empty() {
+        let text = match (&readable.content, text_handle) {
+            (ReadableContent::Text(text), _) => Some(text.as_str()),
+            (ReadableContent::Asset(_), Some(handle)) => {
+                texts.get(&handle.0).map(|text| text.0.as_str())
+            }
+            (ReadableContent::Asset(_), None) => None,
+        };
+        if let Some(text) = text {
+            open_pages.pages = split_pages(text);
+        }
+    }
+
+    let layer = UiLayer::GameplayModal;
+    ui_layers.show(layer);
+    let mut next = false;
+    let mut previous = false;
+    let mut should_close = false;
+    next |= ui_actions.consume(layer, UiAction::Right);
+    previous |= ui_actions.consume(layer, UiAction::Left);
+    should_close |=
+        ui_actions.consume(layer, UiAction::Confirm) || ui_actions.consume(layer, UiAction::Cancel);
+
+    let page_count = open_pages.pages.len();
+    let current = open_pages.current;
+    let ctx = egui_contexts.ctx_mut();
+    let frame = egui::Frame::window(&ctx.style())
+        .fill(PAPER_COLOR)
+        .stroke(egui::Stroke::new(2., INK_COLOR));
+    // Readables can share a title, which would otherwise share the window's remembered state
+    egui::Window::new(strings.t(&readable.title))
+        .id(egui::Id::new(entity))
+        .frame(frame)
+        .collapsible(false)
+        .resizable(false)
+        .default_width(420.)
+        .order(layer.order())
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.visuals_mut().override_text_color = Some(INK_COLOR);
+            egui::ScrollArea::vertical()
+                .max_height(480.)
+                .show(ui, |ui| match open_pages.pages.get(current) {
+                    Some(page) => ui.label(page.as_str()),
+                    None => ui.label("..."),
+                });
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.add_enabled_ui(current > 0, |ui| {
+                    previous |= ui.button("<").cursor_target().clicked();
+                });
+                ui.label(format!("{} / {}", current + 1, page_count.max(1)));
+                ui.add_enabled_ui(current + 1 < page_count, |ui| {
+                    next |= ui.button(">").cursor_target().clicked();
+                });
+                should_close |= ui
+                    .button(t!(strings, "readable.close"))
+                    .cursor_target()
+                    .clicked();
+            });
+        });
+
+    if next && current + 1 < page_count {
+        open_pages.current += 1;
+    } else if previous && current > 0 {
+        open_pages.current -= 1;
+    }
+    if should_close {
+        if readable.collect && !collected_notes.0.contains(&readable.title) {
+            collected_notes.0.push(readable.title.clone());
+        }
+        commands.entity(entity).insert(AlreadyRead);
+        close(
+            &mut current_target,
+            &mut open_pages,
+            &mut freeze,
+            &mut cursor_grab,
+        );
+    }
+}
+
+fn close(
+    current_target: &mut CurrentReadTarget,
+    open_pages: &mut OpenPages,
+    freeze: &mut ActionsFrozen,
+    cursor_grab: &mut CursorGrabRequests,
+) {
+    current_target.0 = None;
+    *open_pages = default();
+    freeze.unfreeze();
+    cursor_grab.release();
+}
+
+fn split_pages(text: &str) -> Vec<String> {
+    let mut pages = vec![String::new()];
+    for line in text.lines() {
+        if line.trim() == PAGE_BREAK {
+            pages.push(String::new());
+        } else if let Some(page) = pages.last_mut() {
+            page.push_str(line);
+            page.push('\n');
+        }
+    }
+    pages.retain(|page| !page.trim().is_empty());
+    if pages.is_empty() {
+        pages.push(String::new());
+    }
+    pages
+}
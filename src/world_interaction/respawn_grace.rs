@@ -0,0 +1,222 @@
+use crate::{
+    file_system_interaction::config::GameConfig,
+    level_instantiation::{on_spawn::Player, portal::Travel},
+    movement::character_controller::HitboxOverlapEvent,
+    player_control::actions::ActionsFrozen,
+    world_interaction::hazard::IgnoredHazards,
+    GameState,
+};
+use bevy::prelude::*;
+
+/// Keeps the player from dying again right after arriving in a level, e.g. after loading a save next to lava.
+/// Arriving gives the player a [`GracePeriod`], tuned in the `respawn_grace` section of the game config:
+/// - [`DamageEvent`](crate::world_interaction::hazard::DamageEvent)s against the player are ignored, and its model blinks to show it.
+/// - Hazards the player arrives inside ignore it until it has left them once, see [`IgnoredHazards`].
+/// - NPCs only start following the player after a delay.
+///
+/// Hitting something ends the period early, since the player is clearly back in control.
+/// While the player is frozen, e.g. during a cutscene, the period is paused and the model is shown.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<GracePeriod>().add_systems(
+        Update,
+        (
+            start_grace_on_arrival,
+            end_grace_on_attack,
+            update_grace_periods,
+        )
+            .chain()
+            .run_if(in_state(GameState::Playing)),
+    );
+}
+
+/// On the player while it is protected after arriving. Removing it ends the protection, e.g. when the player dashes.
+#[derive(Debug, Clone, PartialEq, Component, Reflect)]
+#[reflect(Component)]
+pub(crate) struct GracePeriod {
+    /// How long damage is ignored, in seconds.
+    pub(crate) duration: f32,
+    /// How long NPCs ignore the player, in seconds.
+    pub(crate) aggro_delay: f32,
+    pub(crate) blink_interval: f32,
+    /// Seconds the player was not frozen since arriving.
+    elapsed: f32,
+}
+
+impl GracePeriod {
+    pub(crate) fn new(config: &GameConfig) -> Self {
+        let settings = &config.respawn_grace;
+        Self {
+            duration: settings.duration,
+            aggro_delay: settings.aggro_delay,
+            blink_interval: settings.blink_interval,
+            elapsed: 0.,
+        }
+    }
+
+    pub(crate) fn is_invulnerable(&self) -> bool {
+        self.elapsed < self.duration
+    }
+
+    /// Whether NPCs should not start following the player yet.
+    pub(crate) fn delays_aggro(&self) -> bool {
+        self.elapsed < self.aggro_delay
+    }
+
+    fn is_over(&self) -> bool {
+        !self.is_invulnerable() && !self.delays_aggro()
+    }
+
+    /// Whether the model is hidden at this point of the blinking.
+    fn is_blinked_out(&self) -> bool {
+        self.is_invulnerable()
+            && self.blink_interval > 0.
+            && (self.elapsed / self.blink_interval) as u32 % 2 == 1
+    }
+}
+
+fn start_grace_on_arrival(
+    mut commands: Commands,
+    travel: Res<Travel>,
+    config: Res<GameConfig>,
+    players: Query<Entity, With<Player>>,
+    mut was_arriving: Local<bool>,
+) {
+    let is_arriving = matches!(*travel, Travel::Arriving { .. });
+    let has_arrived = *was_arriving && !is_arriving;
+    *was_arriving = is_arriving;
+    if !has_arrived {
+        return;
+    }
+    for player in players.iter() {
+        commands
+            .entity(player)
+            .insert((GracePeriod::new(&config), IgnoredHazards::on_arrival()));
+    }
+}
+
+fn end_grace_on_attack(
+    mut commands: Commands,
+    mut hitbox_events: EventReader<HitboxOverlapEvent>,
+    players: Query<(), (With<Player>, With<GracePeriod>)>,
+) {
+    for event in hitbox_events.read() {
+        if players.contains(event.owner) {
+            commands.entity(event.owner).remove::<GracePeriod>();
+        }
+    }
+}
+
+fn update_grace_periods(
+    mut commands: Commands,
+    time: Res<Time>,
+    actions_frozen: Res<ActionsFrozen>,
+    mut players: Query<(Entity, &mut GracePeriod)>,
+    children: Query<&Children>,
+    mut meshes: Query<&mut Visibility, With<Handle<Mesh>>>,
+    mut ended: RemovedComponents<GracePeriod>,
+) {
+    let mut set_model_visible = |player: Entity, visible: bool| {
+        let visibility = if visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        for entity in children.iter_descendants(player) {
+            if let Ok(mut mesh_visibility) = meshes.get_mut(entity) {
+                mesh_visibility.set_if_neq(visibility);
+            }
+        }
+    };
+    // Also when the period was ended early
+    for player in ended.read() {
+        set_model_visible(player, true);
+    }
+    for (player, mut grace) in players.iter_mut() {
+        let is_frozen = actions_frozen.is_player_frozen(player);
+        if !is_frozen {
+            grace.elapsed += time.delta_seconds();
+        }
+        if grace.is_over() {
+            commands.entity(player).remove::<GracePeriod>();
+        }
+        set_model_visible(player, is_frozen || !grace.is_blinked_out());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        file_system_interaction::config::RespawnGrace,
+        testing::TestApp,
+        world_interaction::{
+            hazard::{self, DamageEvent},
+            nameplate::Health,
+        },
+    };
+
+    #[test]
+    fn grace_ignores_damage_and_restores_the_model_when_frozen() {
+        let mut app = TestApp::new();
+        app.add_plugins((plugin, hazard::plugin))
+            .init_resource::<Travel>()
+            .init_resource::<ActionsFrozen>()
+            .add_event::<HitboxOverlapEvent>();
+        let player = app.spawn_player(Vec3::new(0., 1., 0.));
+        let mut config = GameConfig::default();
+        config.respawn_grace = RespawnGrace {
+            duration: 1.,
+            aggro_delay: 0.5,
+            blink_interval: 0.1,
+        };
+        let model = app
+            .world_mut()
+            .spawn((SpatialBundle::default(), Handle::<Mesh>::default()))
+            .set_parent(player)
+            .id();
+        app.world_mut().entity_mut(player).insert((
+            GracePeriod::new(&config),
+            Health {
+                current: 100.,
+                max: 100.,
+            },
+        ));
+
+        let hit = DamageEvent {
+            target: player,
+            amount: 10.,
+            damage_type: "fire".to_string(),
+            source: None,
+        };
+        app.send_event(hit.clone());
+        let mut was_hidden = false;
+        for _ in 0..20 {
+            app.step(1);
+            was_hidden |= app.world().get::<Visibility>(model) == Some(&Visibility::Hidden);
+        }
+        assert!(was_hidden, "The model did not blink");
+        let health = app.world().get::<Health>(player);
+        assert_eq!(health.unwrap().current, 100.);
+
+        // A cutscene freezing the player shows the model until the period goes on
+        app.world_mut().resource_mut::<ActionsFrozen>().freeze();
+        app.step(30);
+        assert_eq!(
+            app.world().get::<Visibility>(model),
+            Some(&Visibility::Inherited)
+        );
+        assert!(app.world().get::<GracePeriod>(player).is_some());
+
+        app.world_mut().resource_mut::<ActionsFrozen>().unfreeze();
+        app.step(60);
+        assert!(app.world().get::<GracePeriod>(player).is_none());
+        assert_eq!(
+            app.world().get::<Visibility>(model),
+            Some(&Visibility::Inherited)
+        );
+        app.send_event(hit);
+        app.step(1);
+        let health = app.world().get::<Health>(player);
+        assert_eq!(health.unwrap().current, 90.);
+    }
+}
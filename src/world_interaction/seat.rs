@@ -0,0 +1,313 @@
+use crate::{
+    level_instantiation::on_spawn::{player, Player},
+    movement::character_controller::{
+        Depenetrate, GeneralMovementSystemSet, HeldAnimation, PlayOneShotAnimation, Walk,
+    },
+    player_control::{
+        actions::{DualAxisDataExt, PlayerAction},
+        camera::{CameraConstraints, IngameCamera},
+    },
+    util::math_trait_ext::{F32Ext, Vec3Ext},
+    world_interaction::interaction_sensor::{InteractionSensor, SensorShape},
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_gltf_blueprints::Animations;
+use bevy_tnua::TnuaToggle;
+use bevy_xpbd_3d::prelude::*;
+use leafwing_input_manager::{plugin::InputManagerSystem, prelude::ActionState};
+use serde::{Deserialize, Serialize};
+
+/// How close a character needs to get to the approach point before sitting down.
+const APPROACH_REACHED_DISTANCE: f32 = 0.2;
+/// When the approach point cannot be reached in this many seconds, the character sits down anyway.
+const APPROACH_TIMEOUT: f32 = 3.;
+/// How far the movement input needs to be pushed to stand up.
+const STAND_UP_INPUT_THRESHOLD: f32 = 0.5;
+/// A seated character that moved further than this from its seat was teleported away.
+const TELEPORT_DETECTION_DISTANCE: f32 = 0.5;
+
+/// Handles [`Seat`]s. Sitting down walks the character to the seat, plays a sit-down animation and
+/// locks the character in place, looping a sitting animation. The player stands up through
+/// [`PlayerAction::Interact`] or by moving.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Seat>()
+        .register_type::<SeatOccupant>()
+        .add_event::<SitDownRequest>()
+        .add_systems(
+            Update,
+            (
+                spawn_seats,
+                start_sitting,
+                release_invalid_seats,
+                request_standing_up.after(InputManagerSystem::ManualControl),
+                update_seated_characters,
+            )
+                .chain()
+                .before(GeneralMovementSystemSet)
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Something a character can sit on. All offsets are relative to the seat's transform.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct Seat {
+    /// Where the character walks to before sitting down.
+    pub(crate) approach_offset: Vec3,
+    /// Where the character's origin is while sitting.
+    pub(crate) sit_offset: Vec3,
+    /// Where the character is placed after standing up.
+    pub(crate) exit_offset: Vec3,
+    pub(crate) sit_down_animation: String,
+    pub(crate) sitting_animation: String,
+    pub(crate) stand_up_animation: String,
+    /// How far the camera may turn away from the direction the seat faces, in degrees.
+    pub(crate) camera_max_yaw_offset: f32,
+    pub(crate) camera_min_pitch: f32,
+    pub(crate) camera_max_pitch: f32,
+}
+
+impl Default for Seat {
+    fn default() -> Self {
+        Self {
+            approach_offset: Vec3::new(0., 0., -0.8),
+            sit_offset: Vec3::ZERO,
+            exit_offset: Vec3::new(0., 0., -0.8),
+            sit_down_animation: "sit_down".to_string(),
+            sitting_animation: "sitting".to_string(),
+            stand_up_animation: "stand_up".to_string(),
+            camera_max_yaw_offset: 70.,
+            camera_min_pitch: -40.,
+            camera_max_pitch: 30.,
+        }
+    }
+}
+
+/// The character currently using a [`Seat`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component, Reflect)]
+#[reflect(Component)]
+pub(crate) struct SeatOccupant(pub(crate) Entity);
+
+/// Makes a character walk to a seat and sit down. Ignored when the seat is already occupied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub(crate) struct SitDownRequest {
+    pub(crate) character: Entity,
+    pub(crate) seat: Entity,
+}
+
+/// Present on a character using a [`Seat`]. Its movement is controlled by the seat until it is removed.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub(crate) struct Seated {
+    seat: Entity,
+    phase: SeatPhase,
+    /// Where the character was placed last frame, used to notice teleports.
+    last_position: Option<Vec3>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SeatPhase {
+    Approaching { time_left: f32 },
+    Sitting,
+    StandingUp { remaining: Timer },
+}
+
+fn spawn_seats(
+    mut commands: Commands,
+    seats: Query<Entity, (Added<Seat>, Without<InteractionSensor>)>,
+) {
+    for entity in seats.iter() {
+        commands
+            .entity(entity)
+            .insert(InteractionSensor::new(SensorShape::Cylinder {
+                radius: 1.,
+                height: player::HEIGHT,
+            }));
+    }
+}
+
+fn start_sitting(
+    mut commands: Commands,
+    mut requests: EventReader<SitDownRequest>,
+    seats: Query<(), (With<Seat>, Without<SeatOccupant>)>,
+    already_seated: Query<(), With<Seated>>,
+) {
+    for request in requests.read() {
+        if !seats.contains(request.seat) || already_seated.contains(request.character) {
+            continue;
+        }
+        commands
+            .entity(request.seat)
+            .insert(SeatOccupant(request.character));
+        commands.entity(request.character).insert(Seated {
+            seat: request.seat,
+            phase: SeatPhase::Approaching {
+                time_left: APPROACH_TIMEOUT,
+            },
+            last_position: None,
+        });
+    }
+}
+
+/// Releases seats whose character is gone and characters whose seat is gone or who were teleported away.
+fn release_invalid_seats(
+    mut commands: Commands,
+    seats: Query<(Entity, &SeatOccupant)>,
+    characters: Query<(Entity, &Seated, &Transform, Has<Player>)>,
+    seat_exists: Query<(), With<Seat>>,
+    mut cameras: Query<&mut IngameCamera>,
+) {
+    for (seat, occupant) in seats.iter() {
+        if characters
+            .get(occupant.0)
+            .map_or(true, |(_, seated, ..)| seated.seat != seat)
+        {
+            commands.entity(seat).remove::<SeatOccupant>();
+        }
+    }
+    for (character, seated, transform, is_player) in characters.iter() {
+        let teleported = seated.last_position.is_some_and(|position| {
+            position.distance_squared(transform.translation) > TELEPORT_DETECTION_DISTANCE.squared()
+        });
+        if !seat_exists.contains(seated.seat) || teleported {
+            if seats.contains(seated.seat) {
+                commands.entity(seated.seat).remove::<SeatOccupant>();
+            }
+            release_character(&mut commands, character, is_player, &mut cameras);
+        }
+    }
+}
+
+fn request_standing_up(
+    mut characters: Query<(
+        Entity,
+        &mut Seated,
+        &ActionState<PlayerAction>,
+        Option<&Animations>,
+    )>,
+    seats: Query<&Seat>,
+    clips: Res<Assets<AnimationClip>>,
+    mut commands: Commands,
+    mut one_shot_events: EventWriter<PlayOneShotAnimation>,
+) {
+    for (entity, mut seated, actions, animations) in characters.iter_mut() {
+        if seated.phase != SeatPhase::Sitting {
+            continue;
+        }
+        let Ok(seat) = seats.get(seated.seat) else {
+            continue;
+        };
+        let is_moving = actions
+            .axis_pair(&PlayerAction::Move)
+            .and_then(|axis| axis.max_normalized())
+            .is_some_and(|movement| movement.length() > STAND_UP_INPUT_THRESHOLD);
+        if !(is_moving || actions.just_pressed(&PlayerAction::Interact)) {
+            continue;
+        }
+        let duration = animations
+            .and_then(|animations| animations.named_animations.get(&seat.stand_up_animation))
+            .and_then(|clip| clips.get(clip))
+            .map_or(0., |clip| clip.duration());
+        commands.entity(entity).remove::<HeldAnimation>();
+        one_shot_events.send(PlayOneShotAnimation {
+            entity,
+            animation: seat.stand_up_animation.clone(),
+        });
+        seated.phase = SeatPhase::StandingUp {
+            remaining: Timer::from_seconds(duration, TimerMode::Once),
+        };
+    }
+}
+
+fn update_seated_characters(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut characters: Query<(
+        Entity,
+        &mut Seated,
+        &mut Transform,
+        &mut Walk,
+        &mut LinearVelocity,
+        Has<Player>,
+    )>,
+    seats: Query<(&Seat, &GlobalTransform)>,
+    mut cameras: Query<&mut IngameCamera>,
+    mut one_shot_events: EventWriter<PlayOneShotAnimation>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut seated, mut transform, mut walk, mut velocity, is_player) in
+        characters.iter_mut()
+    {
+        let Ok((seat, seat_transform)) = seats.get(seated.seat) else {
+            continue;
+        };
+        let seat_transform = seat_transform.compute_transform();
+        let sit_position = seat_transform.transform_point(seat.sit_offset);
+        match &mut seated.phase {
+            SeatPhase::Approaching { time_left } => {
+                let approach = seat_transform.transform_point(seat.approach_offset);
+                let to_approach = (approach - transform.translation).horizontal();
+                *time_left -= dt;
+                if to_approach.length() > APPROACH_REACHED_DISTANCE && *time_left > 0. {
+                    walk.direction = Some(to_approach.clamp_length_max(1.));
+                    continue;
+                }
+                commands.entity(entity).insert((
+                    TnuaToggle::Disabled,
+                    RigidBody::Kinematic,
+                    HeldAnimation(seat.sitting_animation.clone()),
+                ));
+                one_shot_events.send(PlayOneShotAnimation {
+                    entity,
+                    animation: seat.sit_down_animation.clone(),
+                });
+                for mut camera in cameras.iter_mut().filter(|_| is_player) {
+                    camera.constraints = Some(CameraConstraints {
+                        yaw_center: CameraConstraints::yaw_towards(seat_transform.forward()),
+                        max_yaw_offset: seat.camera_max_yaw_offset,
+                        min_pitch: seat.camera_min_pitch,
+                        max_pitch: seat.camera_max_pitch,
+                    });
+                }
+                seated.phase = SeatPhase::Sitting;
+            }
+            SeatPhase::Sitting => {}
+            SeatPhase::StandingUp { remaining } => {
+                if remaining.tick(time.delta()).finished() {
+                    commands.entity(seated.seat).remove::<SeatOccupant>();
+                    transform.translation = seat_transform.transform_point(seat.exit_offset);
+                    release_character(&mut commands, entity, is_player, &mut cameras);
+                    continue;
+                }
+            }
+        }
+        transform.translation = sit_position;
+        transform.rotation = seat_transform.rotation;
+        velocity.0 = Vec3::ZERO;
+        walk.direction = None;
+        seated.last_position = Some(sit_position);
+    }
+}
+
+/// Gives control back to a character in place.
+fn release_character(
+    commands: &mut Commands,
+    character: Entity,
+    is_player: bool,
+    cameras: &mut Query<&mut IngameCamera>,
+) {
+    commands
+        .entity(character)
+        .remove::<(Seated, HeldAnimation)>()
+        .insert((
+            TnuaToggle::Enabled,
+            RigidBody::Dynamic,
+            Depenetrate::default(),
+        ));
+    if is_player {
+        for mut camera in cameras.iter_mut() {
+            camera.constraints = None;
+        }
+    }
+}
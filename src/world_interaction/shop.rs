@@ -0,0 +1,739 @@
+use crate::{
+    file_system_interaction::localization::{t, Strings},
+    level_instantiation::stable_id::StableId,
+    player_control::{
+        actions::{ActionsFrozen, UiAction, UiActions},
+        camera::CursorGrabRequests,
+        ui_layer::{UiLayer, UiLayers},
+        virtual_cursor::CursorTargetExt,
+    },
+    world_interaction::{
+        interaction_sensor::{InteractionSensor, SensorShape},
+        interaction_ui::request_interactions,
+        inventory::Inventory,
+        time_of_day::TimeOfDayEvent,
+    },
+    GameState,
+};
+use bevy::{prelude::*, utils::HashMap};
+use bevy_common_assets::ron::RonAssetPlugin;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+/// How long the reason a trade failed is shown, in seconds.
+const MESSAGE_DURATION: f32 = 2.5;
+const ERROR_COLOR: egui::Color32 = egui::Color32::from_rgb(220, 80, 60);
+
+/// Lets the player buy and sell items at [`Shop`]s. Interacting with a shop opens a trade window that lists
+/// the shop's stock next to the player's [`Inventory`]. Shops and items are defined in `assets/config/config.shops.ron`.
+/// Each shop entity keeps its own stock in [`ShopStates`] by its [`StableId`], which is stored in saves.
+/// At dawn, shops restock according to the [`Restock`] rule of each item.
+///
+/// The window is controlled with [`UiAction`]s: up and down select an item, left, right and tab switch between
+/// buying and selling, confirm trades the selected item and cancel closes the window.
+/// Trades of at least [`ShopTable::confirm_price`] ask for confirmation first.
+pub(super) fn plugin(app: &mut App) {
+    app.add_plugins(RonAssetPlugin::<ShopTable>::new(&["shops.ron"]))
+        .register_type::<Shop>()
+        .register_type::<CurrentShop>()
+        .register_type::<ShopStates>()
+        .init_resource::<CurrentShop>()
+        .init_resource::<ShopStates>()
+        .init_resource::<ShopWindow>()
+        .add_systems(
+            Update,
+            (
+                spawn_shops,
+                restock_shops,
+                display_shop
+                    .before(request_interactions)
+                    .run_if(|current: Res<CurrentShop>| current.0.is_some()),
+            )
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Interacting with this opens the trade window of the shop with this id in [`ShopTable::shops`].
+/// Several entities may use the same shop definition, each with its own stock.
+#[derive(Debug, Clone, PartialEq, Eq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct Shop(pub(crate) String);
+
+/// The [`Shop`] whose trade window is open.
+#[derive(Resource, Debug, Clone, Copy, Eq, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct CurrentShop(pub(crate) Option<Entity>);
+
+#[derive(Debug, Clone, PartialEq, Asset, Reflect, Serialize, Deserialize, Default)]
+pub(crate) struct ShopTable {
+    /// The id of the item that is paid with.
+    pub(crate) currency: String,
+    /// Trades of items that cost at least this much need to be confirmed. 0 never asks.
+    pub(crate) confirm_price: u32,
+    pub(crate) items: HashMap<String, ItemDefinition>,
+    pub(crate) shops: HashMap<String, ShopDefinition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+pub(crate) struct ItemDefinition {
+    /// Shown to the player. Translated when the string tables contain it.
+    pub(crate) name: String,
+    /// The price of the item in shops that do not set their own.
+    pub(crate) value: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+pub(crate) struct ShopDefinition {
+    /// Shown as the title of the trade window. Translated when the string tables contain it.
+    pub(crate) name: String,
+    /// What the shop sells, in the order it is listed.
+    pub(crate) stock: Vec<StockEntry>,
+    /// What the shop pays for an item, as a fraction of what it would sell it for.
+    #[serde(default = "default_sell_multiplier")]
+    pub(crate) sell_multiplier: f32,
+}
+
+fn default_sell_multiplier() -> f32 {
+    0.5
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+pub(crate) struct StockEntry {
+    pub(crate) item: String,
+    /// Overrides the [`ItemDefinition::value`] in this shop.
+    #[serde(default)]
+    pub(crate) price: Option<u32>,
+    /// How many the shop has before the player bought any.
+    pub(crate) quantity: u32,
+    #[serde(default)]
+    pub(crate) restock: Restock,
+}
+
+/// How a shop replenishes an item at dawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect, Serialize, Deserialize, Default)]
+pub(crate) enum Restock {
+    /// Once sold out, the item stays sold out.
+    #[default]
+    Never,
+    /// Back to the full quantity every day.
+    Daily,
+    /// This many more every day, up to the full quantity.
+    Gradual(u32),
+}
+
+impl ShopTable {
+    /// What the player pays for `item` in `shop`. `None` if the item has no price.
+    pub(crate) fn buy_price(&self, shop: &ShopDefinition, item: &str) -> Option<u32> {
+        shop.stock
+            .iter()
+            .find(|entry| entry.item == item)
+            .and_then(|entry| entry.price)
+            .or_else(|| self.items.get(item).map(|definition| definition.value))
+    }
+
+    /// What `shop` pays the player for `item`. `None` if it does not buy it.
+    pub(crate) fn sell_price(&self, shop: &ShopDefinition, item: &str) -> Option<u32> {
+        if item == self.currency {
+            return None;
+        }
+        let price = self.buy_price(shop, item)? as f32 * shop.sell_multiplier.max(0.);
+        Some(price.floor() as u32).filter(|&price| price > 0)
+    }
+
+    fn item_name<'a>(&'a self, item: &'a str) -> &'a str {
+        self.items
+            .get(item)
+            .map_or(item, |definition| definition.name.as_str())
+    }
+}
+
+/// The stock every opened [`Shop`] has left, by the shop's [`StableId`]. Stored in saves.
+#[derive(Debug, Clone, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct ShopStates(pub(crate) HashMap<StableId, ShopState>);
+
+#[derive(Debug, Clone, PartialEq, Eq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) struct ShopState {
+    /// The id of the [`ShopDefinition`].
+    pub(crate) shop: String,
+    /// How many of each item are left. Items missing here, e.g. because they were added to the definition
+    /// after the save was made, have their full quantity.
+    pub(crate) stock: HashMap<String, u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TradeError {
+    InsufficientFunds,
+    SoldOut,
+    /// The shop does not buy the item, or the player does not have it.
+    NotWanted,
+}
+
+impl TradeError {
+    fn message_key(self) -> &'static str {
+        match self {
+            Self::InsufficientFunds => "shop.insufficient_funds",
+            Self::SoldOut => "shop.sold_out",
+            Self::NotWanted => "shop.not_wanted",
+        }
+    }
+}
+
+impl ShopState {
+    pub(crate) fn new(shop: &str) -> Self {
+        Self {
+            shop: shop.to_string(),
+            stock: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn remaining(&self, entry: &StockEntry) -> u32 {
+        self.stock
+            .get(&entry.item)
+            .copied()
+            .unwrap_or(entry.quantity)
+    }
+
+    /// The price of buying one `item`, if the player can.
+    pub(crate) fn check_buy(
+        &self,
+        table: &ShopTable,
+        shop: &ShopDefinition,
+        inventory: &Inventory,
+        item: &str,
+    ) -> Result<u32, TradeError> {
+        let entry = shop
+            .stock
+            .iter()
+            .find(|entry| entry.item == item)
+            .ok_or(TradeError::SoldOut)?;
+        if self.remaining(entry) == 0 {
+            return Err(TradeError::SoldOut);
+        }
+        let price = table.buy_price(shop, item).ok_or(TradeError::NotWanted)?;
+        if inventory.count(&table.currency) < price {
+            return Err(TradeError::InsufficientFunds);
+        }
+        Ok(price)
+    }
+
+    /// What the player gets for selling one `item`, if the player can.
+    pub(crate) fn check_sell(
+        &self,
+        table: &ShopTable,
+        shop: &ShopDefinition,
+        inventory: &Inventory,
+        item: &str,
+    ) -> Result<u32, TradeError> {
+        if inventory.count(item) == 0 {
+            return Err(TradeError::NotWanted);
+        }
+        table.sell_price(shop, item).ok_or(TradeError::NotWanted)
+    }
+
+    /// Moves one `item` from the shop to the player and the price the other way.
+    pub(crate) fn buy(
+        &mut self,
+        table: &ShopTable,
+        shop: &ShopDefinition,
+        inventory: &mut Inventory,
+        item: &str,
+    ) -> Result<u32, TradeError> {
+        let price = self.check_buy(table, shop, inventory, item)?;
+        let Some(entry) = shop.stock.iter().find(|entry| entry.item == item) else {
+            return Err(TradeError::SoldOut);
+        };
+        let remaining = self.remaining(entry);
+        self.stock.insert(item.to_string(), remaining - 1);
+        inventory.remove(&table.currency, price);
+        inventory.add(item, 1);
+        Ok(price)
+    }
+
+    /// Moves one `item` from the player to the shop and the price the other way.
+    /// Items the shop sells go back into its stock.
+    pub(crate) fn sell(
+        &mut self,
+        table: &ShopTable,
+        shop: &ShopDefinition,
+        inventory: &mut Inventory,
+        item: &str,
+    ) -> Result<u32, TradeError> {
+        let price = self.check_sell(table, shop, inventory, item)?;
+        if let Some(entry) = shop.stock.iter().find(|entry| entry.item == item) {
+            let remaining = self.remaining(entry);
+            self.stock.insert(item.to_string(), remaining + 1);
+        }
+        inventory.remove(item, 1);
+        inventory.add(&table.currency, price);
+        Ok(price)
+    }
+
+    /// Applies a day's worth of [`Restock`]ing. Never removes what the player sold to the shop.
+    pub(crate) fn restock(&mut self, shop: &ShopDefinition) {
+        for entry in &shop.stock {
+            let remaining = self.remaining(entry);
+            let restocked = match entry.restock {
+                Restock::Never => remaining,
+                Restock::Daily => entry.quantity,
+                Restock::Gradual(amount) => remaining.saturating_add(amount).min(entry.quantity),
+            };
+            self.stock
+                .insert(entry.item.clone(), restocked.max(remaining));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum ShopTab {
+    #[default]
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Trade {
+    tab: ShopTab,
+    item: String,
+    price: u32,
+}
+
+#[derive(Debug, Default, Resource)]
+struct ShopWindow {
+    tab: ShopTab,
+    selected: usize,
+    /// A trade that waits for the player to confirm it.
+    pending: Option<Trade>,
+    /// Why the last trade failed, and for how many more seconds this is shown.
+    message: Option<(TradeError, f32)>,
+}
+
+/// One line in either list of the trade window.
+struct Row {
+    item: String,
+    name: String,
+    price: Option<u32>,
+    count: u32,
+}
+
+fn spawn_shops(
+    mut commands: Commands,
+    shops: Query<(Entity, Has<InteractionSensor>), Added<Shop>>,
+) {
+    for (entity, has_sensor) in shops.iter() {
+        if !has_sensor {
+            commands
+                .entity(entity)
+                .insert(InteractionSensor::new(SensorShape::Cylinder {
+                    radius: 1.5,
+                    height: 1.,
+                }));
+        }
+    }
+}
+
+fn restock_shops(
+    mut time_of_day_events: EventReader<TimeOfDayEvent>,
+    tables: Res<Assets<ShopTable>>,
+    mut states: ResMut<ShopStates>,
+) {
+    let days = time_of_day_events
+        .read()
+        .filter(|event| **event == TimeOfDayEvent::Dawn)
+        .count();
+    let Some(table) = tables.iter().next().map(|(_, table)| table) else {
+        return;
+    };
+    for _ in 0..days {
+        for state in states.0.values_mut() {
+            if let Some(shop) = table.shops.get(&state.shop) {
+                state.restock(shop);
+            }
+        }
+    }
+}
+
+fn display_shop(
+    time: Res<Time>,
+    mut current_shop: ResMut<CurrentShop>,
+    mut window: ResMut<ShopWindow>,
+    mut states: ResMut<ShopStates>,
+    mut inventory: ResMut<Inventory>,
+    mut freeze: ResMut<ActionsFrozen>,
+    mut cursor_grab: ResMut<CursorGrabRequests>,
+    mut egui_contexts: EguiContexts,
+    shops: Query<(&Shop, Option<&StableId>, Option<&Name>)>,
+    tables: Res<Assets<ShopTable>>,
+    mut ui_actions: UiActions,
+    mut ui_layers: ResMut<UiLayers>,
+    strings: Strings,
+) {
+    let Some(entity) = current_shop.0 else {
+        return;
+    };
+    let table = tables.iter().next().map(|(_, table)| table);
+    let shop = shops.get(entity).ok().and_then(|(shop, id, name)| {
+        let Some(definition) = table.and_then(|table| table.shops.get(&shop.0)) else {
+            error!("Shop \"{}\" is not defined in the shop table", shop.0);
+            return None;
+        };
+        let Some(&id) = id else {
+            error!("Shop {name:?} has no stable id, so its stock cannot be kept track of");
+            return None;
+        };
+        Some((shop, id, definition))
+    });
+    // The shop was despawned while open, or cannot be used
+    let (Some(table), Some((shop, id, definition))) = (table, shop) else {
+        close(
+            &mut current_shop,
+            &mut window,
+            &mut freeze,
+            &mut cursor_grab,
+        );
+        return;
+    };
+    let state = states
+        .0
+        .entry(id)
+        .or_insert_with(|| ShopState::new(&shop.0));
+
+    let layer = UiLayer::GameplayModal;
+    ui_layers.show(layer);
+    let window = window.as_mut();
+    if let Some((_, remaining)) = &mut window.message {
+        *remaining -= time.delta_seconds();
+    }
+    if window.message.is_some_and(|(_, remaining)| remaining <= 0.) {
+        window.message = None;
+    }
+
+    let stock_rows: Vec<_> = definition
+        .stock
+        .iter()
+        .map(|entry| Row {
+            item: entry.item.clone(),
+            name: strings.t(table.item_name(&entry.item)),
+            price: table.buy_price(definition, &entry.item),
+            count: state.remaining(entry),
+        })
+        .collect();
+    let inventory_rows: Vec<_> = inventory
+        .items()
+        .into_iter()
+        .filter(|(item, _)| *item != table.currency)
+        .map(|(item, count)| Row {
+            item: item.to_string(),
+            name: strings.t(table.item_name(item)),
+            price: table.sell_price(definition, item),
+            count,
+        })
+        .collect();
+
+    let mut trade_selected = false;
+    let mut confirm = false;
+    let mut cancel = false;
+    let mut should_close = false;
+    if window.pending.is_some() {
+        confirm |= ui_actions.consume(layer, UiAction::Confirm);
+        cancel |= ui_actions.consume(layer, UiAction::Cancel);
+    } else {
+        let switch_tab = ui_actions.consume(layer, UiAction::Left)
+            | ui_actions.consume(layer, UiAction::Right)
+            | ui_actions.consume(layer, UiAction::Tab);
+        if switch_tab {
+            window.tab = match window.tab {
+                ShopTab::Buy => ShopTab::Sell,
+                ShopTab::Sell => ShopTab::Buy,
+            };
+            window.selected = 0;
+        }
+        let row_count = match window.tab {
+            ShopTab::Buy => stock_rows.len(),
+            ShopTab::Sell => inventory_rows.len(),
+        };
+        if ui_actions.consume(layer, UiAction::Up) {
+            window.selected = window.selected.saturating_sub(1);
+        }
+        if ui_actions.consume(layer, UiAction::Down) {
+            window.selected += 1;
+        }
+        window.selected = window.selected.min(row_count.saturating_sub(1));
+        trade_selected |= ui_actions.consume(layer, UiAction::Confirm);
+        should_close |= ui_actions.consume(layer, UiAction::Cancel);
+    }
+
+    let ctx = egui_contexts.ctx_mut();
+    egui::Window::new(strings.t(&definition.name))
+        .collapsible(false)
+        .resizable(false)
+        .default_width(560.)
+        .order(layer.order())
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            let currency = strings.t(table.item_name(&table.currency));
+            let funds = inventory.count(&table.currency);
+            ui.label(t!(
+                strings,
+                "shop.funds",
+                currency = currency,
+                amount = funds
+            ));
+            ui.separator();
+            ui.add_enabled_ui(window.pending.is_none(), |ui| {
+                ui.columns(2, |columns| {
+                    let lists = [
+                        (ShopTab::Buy, "shop.stock", "shop.buy", &stock_rows),
+                        (
+                            ShopTab::Sell,
+                            "shop.inventory",
+                            "shop.sell",
+                            &inventory_rows,
+                        ),
+                    ];
+                    for (ui, (tab, heading, action, rows)) in columns.iter_mut().zip(lists) {
+                        ui.heading(strings.t(heading));
+                        if rows.is_empty() {
+                            ui.weak(t!(strings, "shop.empty"));
+                        }
+                        for (index, row) in rows.iter().enumerate() {
+                            let is_selected = window.tab == tab && window.selected == index;
+                            let price = row
+                                .price
+                                .map_or_else(|| "-".to_string(), |price| price.to_string());
+                            let label = format!("{}  x{}  ({price})", row.name, row.count);
+                            if ui
+                                .selectable_label(is_selected, label)
+                                .cursor_target()
+                                .clicked()
+                            {
+                                window.tab = tab;
+                                window.selected = index;
+                            }
+                        }
+                        ui.add_space(8.);
+                        if ui.button(strings.t(action)).cursor_target().clicked() {
+                            if window.tab != tab {
+                                window.tab = tab;
+                                window.selected = 0;
+                            }
+                            trade_selected = true;
+                        }
+                    }
+                });
+            });
+            if let Some(trade) = &window.pending {
+                ui.separator();
+                let name = strings.t(table.item_name(&trade.item));
+                let question = match trade.tab {
+                    ShopTab::Buy => {
+                        t!(
+                            strings,
+                            "shop.confirm_buy",
+                            item = name,
+                            price = trade.price
+                        )
+                    }
+                    ShopTab::Sell => {
+                        t!(
+                            strings,
+                            "shop.confirm_sell",
+                            item = name,
+                            price = trade.price
+                        )
+                    }
+                };
+                ui.label(question);
+                ui.horizontal(|ui| {
+                    confirm |= ui.button(t!(strings, "shop.yes")).cursor_target().clicked();
+                    cancel |= ui.button(t!(strings, "shop.no")).cursor_target().clicked();
+                });
+            }
+            if let Some((error, _)) = window.message {
+                ui.colored_label(ERROR_COLOR, strings.t(error.message_key()));
+            }
+            ui.separator();
+            ui.add_enabled_ui(window.pending.is_none(), |ui| {
+                should_close |= ui
+                    .button(t!(strings, "shop.close"))
+                    .cursor_target()
+                    .clicked();
+            });
+        });
+
+    let inventory = inventory.as_mut();
+    if let Some(trade) = window.pending.take() {
+        if confirm {
+            let result = match trade.tab {
+                ShopTab::Buy => state.buy(table, definition, inventory, &trade.item),
+                ShopTab::Sell => state.sell(table, definition, inventory, &trade.item),
+            };
+            window.message = result.err().map(|error| (error, MESSAGE_DURATION));
+        } else if !cancel {
+            window.pending = Some(trade);
+        }
+    } else if trade_selected {
+        let rows = match window.tab {
+            ShopTab::Buy => &stock_rows,
+            ShopTab::Sell => &inventory_rows,
+        };
+        if let Some(row) = rows.get(window.selected) {
+            let checked = match window.tab {
+                ShopTab::Buy => state.check_buy(table, definition, inventory, &row.item),
+                ShopTab::Sell => state.check_sell(table, definition, inventory, &row.item),
+            };
+            match checked {
+                Ok(price) if table.confirm_price > 0 && price >= table.confirm_price => {
+                    window.pending = Some(Trade {
+                        tab: window.tab,
+                        item: row.item.clone(),
+                        price,
+                    });
+                }
+                Ok(_) => {
+                    let result = match window.tab {
+                        ShopTab::Buy => state.buy(table, definition, inventory, &row.item),
+                        ShopTab::Sell => state.sell(table, definition, inventory, &row.item),
+                    };
+                    window.message = result.err().map(|error| (error, MESSAGE_DURATION));
+                }
+                Err(error) => window.message = Some((error, MESSAGE_DURATION)),
+            }
+        }
+    }
+    if should_close {
+        close(&mut current_shop, window, &mut freeze, &mut cursor_grab);
+    }
+}
+
+fn close(
+    current_shop: &mut CurrentShop,
+    window: &mut ShopWindow,
+    freeze: &mut ActionsFrozen,
+    cursor_grab: &mut CursorGrabRequests,
+) {
+    current_shop.0 = None;
+    *window = default();
+    freeze.unfreeze();
+    cursor_grab.release();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> ShopTable {
+        let item = |name: &str, value| ItemDefinition {
+            name: name.to_string(),
+            value,
+        };
+        let entry = |item: &str, price, quantity, restock| StockEntry {
+            item: item.to_string(),
+            price,
+            quantity,
+            restock,
+        };
+        ShopTable {
+            currency: "coin".to_string(),
+            confirm_price: 50,
+            items: [
+                ("coin".to_string(), item("Coin", 1)),
+                ("apple".to_string(), item("Apple", 2)),
+                ("rope".to_string(), item("Rope", 10)),
+                ("gem".to_string(), item("Gem", 40)),
+            ]
+            .into_iter()
+            .collect(),
+            shops: [(
+                "grocer".to_string(),
+                ShopDefinition {
+                    name: "Grocer".to_string(),
+                    stock: vec![
+                        entry("apple", None, 3, Restock::Daily),
+                        entry("rope", Some(12), 1, Restock::Never),
+                        entry("gem", None, 1, Restock::Gradual(1)),
+                    ],
+                    sell_multiplier: 0.5,
+                },
+            )]
+            .into_iter()
+            .collect(),
+        }
+    }
+
+    fn inventory(coins: u32) -> Inventory {
+        let mut inventory = Inventory::default();
+        inventory.add("coin", coins);
+        inventory
+    }
+
+    #[test]
+    fn buying_moves_items_and_money() {
+        let table = table();
+        let shop = &table.shops["grocer"];
+        let mut state = ShopState::new("grocer");
+        let mut inventory = inventory(15);
+
+        assert_eq!(state.buy(&table, shop, &mut inventory, "rope"), Ok(12));
+        assert_eq!(inventory.count("coin"), 3);
+        assert_eq!(inventory.count("rope"), 1);
+        assert_eq!(
+            state.buy(&table, shop, &mut inventory, "rope"),
+            Err(TradeError::SoldOut)
+        );
+        assert_eq!(
+            state.buy(&table, shop, &mut inventory, "gem"),
+            Err(TradeError::InsufficientFunds)
+        );
+        assert_eq!(inventory.count("coin"), 3, "A failed trade cost money");
+    }
+
+    #[test]
+    fn selling_uses_the_shops_multiplier() {
+        let table = table();
+        let shop = &table.shops["grocer"];
+        let mut state = ShopState::new("grocer");
+        let mut inventory = inventory(0);
+        inventory.add("rope", 1);
+        inventory.add("stick", 1);
+
+        assert_eq!(state.sell(&table, shop, &mut inventory, "rope"), Ok(6));
+        assert_eq!(inventory.count("coin"), 6);
+        assert_eq!(inventory.count("rope"), 0);
+        assert_eq!(state.remaining(&shop.stock[1]), 2);
+        assert_eq!(
+            state.sell(&table, shop, &mut inventory, "stick"),
+            Err(TradeError::NotWanted)
+        );
+        assert_eq!(
+            state.sell(&table, shop, &mut inventory, "coin"),
+            Err(TradeError::NotWanted)
+        );
+    }
+
+    #[test]
+    fn restocking_follows_each_items_rule() {
+        let table = table();
+        let shop = &table.shops["grocer"];
+        let mut state = ShopState::new("grocer");
+        let mut inventory = inventory(100);
+        for item in ["apple", "apple", "rope", "gem"] {
+            state.buy(&table, shop, &mut inventory, item).unwrap();
+        }
+
+        state.restock(shop);
+        let remaining: Vec<_> = shop
+            .stock
+            .iter()
+            .map(|entry| state.remaining(entry))
+            .collect();
+        assert_eq!(remaining, [3, 0, 1]);
+        state.restock(shop);
+        assert_eq!(
+            state.remaining(&shop.stock[2]),
+            1,
+            "Restocked past the quantity"
+        );
+    }
+}
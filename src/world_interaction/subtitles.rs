@@ -0,0 +1,103 @@
+use crate::{
+    file_system_interaction::config::GameConfig,
+    player_control::ui_layer::{UiLayer, UiLayers},
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_yarnspinner::events::PresentLineEvent;
+
+/// Yarn lines with this tag are barks, e.g. `Sheep: Baa! #bark`, and are shown as subtitles.
+const BARK_TAG: &str = "bark";
+
+/// Shows [`SubtitleEvent`]s at the bottom of the screen when subtitles are enabled in the accessibility settings.
+/// Their size and background opacity come from the settings as well.
+pub(super) fn plugin(app: &mut App) {
+    app.add_event::<SubtitleEvent>()
+        .init_resource::<ActiveSubtitles>()
+        .add_systems(
+            Update,
+            (subtitle_barks, queue_subtitles, display_subtitles)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Shows a line of speech that does not appear in the dialog box, e.g. a bark or a voice line.
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub(crate) struct SubtitleEvent {
+    pub(crate) speaker: Option<String>,
+    pub(crate) text: String,
+}
+
+#[derive(Debug, Default, Resource)]
+struct ActiveSubtitles(Vec<(SubtitleEvent, f32)>);
+
+fn subtitle_barks(
+    mut line_events: EventReader<PresentLineEvent>,
+    mut subtitle_events: EventWriter<SubtitleEvent>,
+) {
+    for event in line_events.read() {
+        let is_bark = event.line.metadata.iter().any(|tag| tag == BARK_TAG);
+        if is_bark {
+            subtitle_events.send(SubtitleEvent {
+                speaker: event.line.character_name().map(str::to_string),
+                text: event.line.text_without_character_name(),
+            });
+        }
+    }
+}
+
+fn queue_subtitles(
+    time: Res<Time>,
+    mut subtitle_events: EventReader<SubtitleEvent>,
+    mut subtitles: ResMut<ActiveSubtitles>,
+) {
+    for (_, elapsed) in subtitles.0.iter_mut() {
+        *elapsed += time.delta_seconds();
+    }
+    subtitles
+        .0
+        .extend(subtitle_events.read().map(|event| (event.clone(), 0.)));
+}
+
+fn display_subtitles(
+    mut subtitles: ResMut<ActiveSubtitles>,
+    config: Res<GameConfig>,
+    ui_layers: Res<UiLayers>,
+    mut egui_contexts: EguiContexts,
+) {
+    let settings = &config.accessibility;
+    // Uses the current reading speed, so that changing it applies to subtitles already on screen
+    subtitles
+        .0
+        .retain(|(subtitle, elapsed)| *elapsed < settings.reading_time(&subtitle.text));
+    // Unlike the rest of the HUD, speech stays readable during dialog
+    if !settings.subtitles || subtitles.0.is_empty() || ui_layers.is_open(UiLayer::SystemModal) {
+        return;
+    }
+    let opacity = (settings.subtitle_background_opacity.clamp(0., 1.) * 255.) as u8;
+    egui::Area::new("Subtitles")
+        .order(UiLayer::Hud.order())
+        .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0., -40.))
+        .interactable(false)
+        .show(egui_contexts.ctx_mut(), |ui| {
+            egui::Frame::none()
+                .fill(egui::Color32::from_black_alpha(opacity))
+                .rounding(4.)
+                .inner_margin(egui::Margin::symmetric(12., 6.))
+                .show(ui, |ui| {
+                    for (subtitle, _) in &subtitles.0 {
+                        let text = match &subtitle.speaker {
+                            Some(speaker) => format!("{speaker}: {}", subtitle.text),
+                            None => subtitle.text.clone(),
+                        };
+                        ui.label(
+                            egui::RichText::new(text)
+                                .size(settings.subtitle_size)
+                                .color(egui::Color32::WHITE),
+                        );
+                    }
+                });
+        });
+}
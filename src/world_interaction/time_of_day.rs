@@ -0,0 +1,97 @@
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The hour at which [`TimeOfDayEvent::Dawn`] is sent.
+const DAWN: f32 = 6.;
+/// The hour at which [`TimeOfDayEvent::Dusk`] is sent.
+const DUSK: f32 = 19.;
+
+/// Runs the in-game clock while playing and sends a [`TimeOfDayEvent`] at dawn and dusk.
+/// The clock follows virtual time, so it stops while the game is paused.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<TimeOfDay>()
+        .init_resource::<TimeOfDay>()
+        .add_event::<TimeOfDayEvent>()
+        .add_systems(
+            Update,
+            advance_time_of_day.run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// The in-game clock. Stored in saves.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect, Serialize, Deserialize)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct TimeOfDay {
+    /// Hours since midnight, between 0 and 24.
+    pub(crate) hour: f32,
+    /// Game minutes per real second. The default makes a day last 24 minutes.
+    pub(crate) speed: f32,
+    /// How many midnights have passed since the game was started fresh.
+    #[serde(default)]
+    pub(crate) day: u32,
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self {
+            hour: 12.,
+            speed: 1.,
+            day: 0,
+        }
+    }
+}
+
+impl TimeOfDay {
+    pub(crate) fn is_night(&self) -> bool {
+        !(DAWN..DUSK).contains(&self.hour)
+    }
+
+    /// How many game minutes pass in `seconds` of real time.
+    pub(crate) fn minutes_in(&self, seconds: f32) -> f32 {
+        seconds * self.speed
+    }
+
+    /// Game hours since the first midnight, for measuring how long ago something happened.
+    pub(crate) fn total_hours(&self) -> f32 {
+        self.day as f32 * 24. + self.hour
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub(crate) enum TimeOfDayEvent {
+    Dawn,
+    Dusk,
+}
+
+fn advance_time_of_day(
+    time: Res<Time>,
+    mut time_of_day: ResMut<TimeOfDay>,
+    mut time_of_day_events: EventWriter<TimeOfDayEvent>,
+) {
+    let minutes = time_of_day.minutes_in(time.delta_seconds());
+    if minutes <= 0. {
+        return;
+    }
+    let previous = time_of_day.hour;
+    let current = (previous + minutes / 60.).rem_euclid(24.);
+    time_of_day.hour = current;
+    if current < previous {
+        time_of_day.day += 1;
+    }
+    if has_passed(DAWN, previous, current) {
+        time_of_day_events.send(TimeOfDayEvent::Dawn);
+    }
+    if has_passed(DUSK, previous, current) {
+        time_of_day_events.send(TimeOfDayEvent::Dusk);
+    }
+}
+
+/// Whether the clock went past `hour` since the last frame, also across midnight.
+fn has_passed(hour: f32, previous: f32, current: f32) -> bool {
+    if current >= previous {
+        previous < hour && hour <= current
+    } else {
+        hour > previous || hour <= current
+    }
+}
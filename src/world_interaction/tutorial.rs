@@ -0,0 +1,308 @@
+use crate::{
+    file_system_interaction::{config::GameConfig, localization::Strings},
+    level_instantiation::on_spawn::Player,
+    movement::{character_controller::LeftGroundEvent, slow_motion::UnscaledTime},
+    player_control::{
+        actions::{glyphs::ActionGlyphs, PlayerAction},
+        ui_layer::{UiLayer, UiLayers},
+    },
+    world_interaction::{
+        dialog::YarnCommandsAppExt, interaction_ui::InteractionOpportunityEntered,
+    },
+    GameState,
+};
+use bevy::{prelude::*, utils::HashSet};
+use bevy_common_assets::ron::RonAssetPlugin;
+use bevy_egui::{egui, EguiContexts};
+use bevy_xpbd_3d::prelude::*;
+use bevy_yarnspinner::prelude::DialogueRunner;
+use leafwing_input_manager::prelude::{ActionState, InputMap};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// How long a toast takes to fade in and out, in seconds.
+const FADE_DURATION: f32 = 0.3;
+const DEFAULT_DURATION: f32 = 5.;
+
+/// Shows tutorial toasts in the top right corner, one at a time, when their [`TutorialTrigger`] fires for the first time.
+/// The steps are configured in `assets/config/config.tutorials.ron`.
+/// Gameplay code fires custom triggers by sending a [`TutorialTriggerEvent`], e.g. `TutorialTriggerEvent::custom("item_picked_up")`,
+/// and dialog does so with `<<tutorial item_picked_up>>`.
+pub(super) fn plugin(app: &mut App) {
+    app.add_plugins(RonAssetPlugin::<TutorialTable>::new(&["tutorials.ron"]))
+        .add_yarn_commands(register_tutorial_command)
+        .register_type::<CompletedTutorials>()
+        .add_event::<TutorialTriggerEvent>()
+        .init_resource::<CompletedTutorials>()
+        .init_resource::<TutorialQueue>()
+        .add_systems(
+            Update,
+            (
+                (trigger_airborne, trigger_interaction, trigger_areas),
+                queue_tutorials,
+                display_tutorial,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing).and_then(resource_exists::<GameConfig>)),
+        );
+}
+
+#[derive(Debug, Clone, PartialEq, Asset, Reflect, Serialize, Deserialize, Default)]
+pub(crate) struct TutorialTable {
+    pub(crate) steps: Vec<TutorialStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+pub(crate) struct TutorialStep {
+    /// Remembered in [`CompletedTutorials`] once shown.
+    pub(crate) id: String,
+    pub(crate) trigger: TutorialTrigger,
+    /// Localization key of the text.
+    pub(crate) text: String,
+    /// Shown as a button glyph in front of the text.
+    #[serde(default)]
+    pub(crate) action: Option<PlayerAction>,
+    #[serde(default)]
+    pub(crate) dismiss: TutorialDismiss,
+    /// Whether the step is shown only the first time its trigger fires.
+    #[serde(default = "default_once")]
+    pub(crate) once: bool,
+}
+
+fn default_once() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub(crate) enum TutorialTrigger {
+    /// The player leaves the ground.
+    Airborne,
+    /// The player can interact with something.
+    InteractionOpportunity,
+    /// The player enters a sensor with this [`Name`], or a sensor whose ancestor has it.
+    EnteredArea(String),
+    /// Sent by gameplay code through [`TutorialTriggerEvent::custom`].
+    Custom(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+pub(crate) enum TutorialDismiss {
+    /// In seconds.
+    After(f32),
+    /// When the player presses this.
+    Action(PlayerAction),
+}
+
+impl Default for TutorialDismiss {
+    fn default() -> Self {
+        Self::After(DEFAULT_DURATION)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub(crate) struct TutorialTriggerEvent(pub(crate) TutorialTrigger);
+
+impl TutorialTriggerEvent {
+    pub(crate) fn custom(name: impl Into<String>) -> Self {
+        Self(TutorialTrigger::Custom(name.into()))
+    }
+}
+
+/// The ids of once-only tutorial steps that were already shown. Stored in saves.
+#[derive(Debug, Clone, PartialEq, Eq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct CompletedTutorials(pub(crate) HashSet<String>);
+
+#[derive(Debug, Default, Resource)]
+struct TutorialQueue {
+    pending: VecDeque<TutorialStep>,
+    current: Option<Toast>,
+}
+
+#[derive(Debug, Clone)]
+struct Toast {
+    step: TutorialStep,
+    elapsed: f32,
+    /// Set once the toast starts fading out, counting down to zero.
+    fade_out: Option<f32>,
+}
+
+fn register_tutorial_command(dialogue_runner: &mut DialogueRunner) {
+    dialogue_runner
+        .commands_mut()
+        .add_command("tutorial", fire_custom_trigger);
+}
+
+fn fire_custom_trigger(
+    In(name): In<String>,
+    mut trigger_events: EventWriter<TutorialTriggerEvent>,
+) {
+    trigger_events.send(TutorialTriggerEvent::custom(name));
+}
+
+fn trigger_airborne(
+    mut left_ground_events: EventReader<LeftGroundEvent>,
+    players: Query<(), With<Player>>,
+    mut trigger_events: EventWriter<TutorialTriggerEvent>,
+) {
+    if left_ground_events
+        .read()
+        .any(|event| players.contains(event.entity))
+    {
+        trigger_events.send(TutorialTriggerEvent(TutorialTrigger::Airborne));
+    }
+}
+
+fn trigger_interaction(
+    mut entered_events: EventReader<InteractionOpportunityEntered>,
+    mut trigger_events: EventWriter<TutorialTriggerEvent>,
+) {
+    if entered_events.read().count() > 0 {
+        trigger_events.send(TutorialTriggerEvent(
+            TutorialTrigger::InteractionOpportunity,
+        ));
+    }
+}
+
+fn trigger_areas(
+    mut collision_events: EventReader<CollisionStarted>,
+    players: Query<(), With<Player>>,
+    sensors: Query<(), With<Sensor>>,
+    names: Query<&Name>,
+    parents: Query<&Parent>,
+    mut trigger_events: EventWriter<TutorialTriggerEvent>,
+) {
+    for CollisionStarted(entity1, entity2) in collision_events.read() {
+        let sensor = if players.contains(*entity1) {
+            *entity2
+        } else if players.contains(*entity2) {
+            *entity1
+        } else {
+            continue;
+        };
+        if !sensors.contains(sensor) {
+            continue;
+        }
+        let areas = std::iter::once(sensor)
+            .chain(parents.iter_ancestors(sensor))
+            .filter_map(|entity| names.get(entity).ok());
+        for name in areas {
+            trigger_events.send(TutorialTriggerEvent(TutorialTrigger::EnteredArea(
+                name.to_string(),
+            )));
+        }
+    }
+}
+
+fn queue_tutorials(
+    mut trigger_events: EventReader<TutorialTriggerEvent>,
+    tables: Res<Assets<TutorialTable>>,
+    config: Res<GameConfig>,
+    completed: Res<CompletedTutorials>,
+    mut queue: ResMut<TutorialQueue>,
+) {
+    if !config.tutorials.enabled {
+        trigger_events.clear();
+        queue.pending.clear();
+        queue.current = None;
+        return;
+    }
+    let Some(table) = tables.iter().next().map(|(_, table)| table) else {
+        return;
+    };
+    for TutorialTriggerEvent(trigger) in trigger_events.read() {
+        for step in table.steps.iter().filter(|step| step.trigger == *trigger) {
+            let is_shown = queue
+                .current
+                .as_ref()
+                .is_some_and(|toast| toast.step.id == step.id)
+                || queue.pending.iter().any(|pending| pending.id == step.id);
+            if is_shown || (step.once && completed.0.contains(&step.id)) {
+                continue;
+            }
+            queue.pending.push_back(step.clone());
+        }
+    }
+}
+
+fn display_tutorial(
+    time: UnscaledTime,
+    mut queue: ResMut<TutorialQueue>,
+    mut completed: ResMut<CompletedTutorials>,
+    players: Query<(&ActionState<PlayerAction>, &InputMap<PlayerAction>), With<Player>>,
+    glyphs: ActionGlyphs,
+    strings: Strings,
+    ui_layers: Res<UiLayers>,
+    mut egui_contexts: EguiContexts,
+) {
+    // Waits until the modal is closed, so that the toast is not missed
+    if !ui_layers.is_visible(UiLayer::Hud) {
+        return;
+    }
+    if queue.current.is_none() {
+        let Some(step) = queue.pending.pop_front() else {
+            return;
+        };
+        // Marked right away, so that the step also counts as seen when the game is saved while it shows
+        if step.once {
+            completed.0.insert(step.id.clone());
+        }
+        queue.current = Some(Toast {
+            step,
+            elapsed: 0.,
+            fade_out: None,
+        });
+    }
+    let Some(toast) = queue.current.as_mut() else {
+        return;
+    };
+    let dt = time.delta_seconds();
+    toast.elapsed += dt;
+    let player = players.iter().next();
+    let dismissed = match &toast.step.dismiss {
+        TutorialDismiss::After(duration) => toast.elapsed >= *duration,
+        TutorialDismiss::Action(action) => {
+            player.is_some_and(|(actions, _)| actions.just_pressed(action))
+        }
+    };
+    if dismissed && toast.fade_out.is_none() {
+        toast.fade_out = Some(FADE_DURATION);
+    }
+    let opacity = match toast.fade_out.as_mut() {
+        Some(remaining) => {
+            *remaining -= dt;
+            *remaining / FADE_DURATION
+        }
+        None => toast.elapsed / FADE_DURATION,
+    }
+    .clamp(0., 1.);
+    if toast.fade_out.is_some_and(|remaining| remaining <= 0.) {
+        queue.current = None;
+        return;
+    }
+
+    let text = strings.t(&toast.step.text);
+    let alpha = (opacity * 255.) as u8;
+    egui::Area::new("Tutorial")
+        .order(UiLayer::Hud.order())
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-20., 20.))
+        .interactable(false)
+        .show(egui_contexts.ctx_mut(), |ui| {
+            ui.visuals_mut().override_text_color = Some(egui::Color32::from_white_alpha(alpha));
+            egui::Frame::none()
+                .fill(egui::Color32::from_black_alpha((opacity * 180.) as u8))
+                .rounding(6.)
+                .inner_margin(egui::Margin::same(10.))
+                .show(ui, |ui| {
+                    ui.set_max_width(320.);
+                    match (&toast.step.action, player) {
+                        (Some(action), Some((_, input_map))) => {
+                            glyphs.prompt(ui, input_map, action, &text);
+                        }
+                        _ => {
+                            ui.label(text);
+                        }
+                    }
+                });
+        });
+}
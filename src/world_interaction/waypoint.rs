@@ -0,0 +1,324 @@
+use crate::{
+    file_system_interaction::config::GameConfig,
+    graphics::decals::{spawn_decal, Decal},
+    level_instantiation::{map::CurrentLevel, on_spawn::Player},
+    movement::physics::CollisionLayer,
+    player_control::{
+        actions::{ActionsFrozen, PlayerAction},
+        camera::IngameCamera,
+        ui_layer::{UiLayer, UiLayers},
+    },
+    shader::BeaconMaterial,
+    util::{criteria::is_globally_frozen, math_trait_ext::Vec3Ext},
+    GameState,
+};
+use bevy::{pbr::NotShadowCaster, prelude::*};
+use bevy_egui::{egui, EguiContexts};
+use bevy_kira_audio::prelude::{Audio, AudioSource, *};
+use bevy_xpbd_3d::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_2;
+
+/// How far away the player can place a waypoint by looking at a spot.
+const MAX_PLACEMENT_DISTANCE: f32 = 250.;
+const BEACON_RADIUS: f32 = 0.25;
+const BEACON_COLOR: Color = Color::rgba(1., 0.8, 0.2, 0.5);
+/// The diameter of the spot marking where a waypoint was placed, in meters.
+const MARKER_SIZE: f32 = 1.5;
+const MARKER_LIFETIME: f32 = 2.;
+const WAYPOINT_ICON: &str = "◆";
+const COMPASS_WIDTH: f32 = 400.;
+
+/// Lets the player mark a spot with a custom waypoint by pressing [`PlayerAction::PlaceWaypoint`] while looking at it.
+/// Other UI, e.g. a map or an objective list, can place it with a [`PlaceWaypointEvent`].
+/// There is only one custom waypoint. It shows as a beacon that is visible through walls and on the compass,
+/// and disappears once the player reaches it. Placing it briefly marks the spot on the ground. It only shows in the level it was placed in.
+///
+/// The compass at the top of the screen shows every [`MapMarker`] in front of the camera.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<CustomWaypoint>()
+        .register_type::<MapMarker>()
+        .init_resource::<CustomWaypoint>()
+        .add_event::<PlaceWaypointEvent>()
+        .add_systems(
+            Update,
+            (
+                place_waypoint_at_crosshair,
+                apply_place_waypoint_events,
+                clear_reached_waypoint,
+                sync_waypoint_beacon,
+                (display_beacon_distance, display_compass).run_if(not(is_globally_frozen)),
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing).and_then(resource_exists::<GameConfig>)),
+        );
+}
+
+/// The waypoint the player placed. Stored in saves.
+#[derive(Debug, Clone, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct CustomWaypoint(pub(crate) Option<Waypoint>);
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) struct Waypoint {
+    /// The [`CurrentLevel`] the waypoint was placed in.
+    pub(crate) level: String,
+    pub(crate) position: Vec3,
+}
+
+/// Replaces the custom waypoint with one at this position in the current level.
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub(crate) struct PlaceWaypointEvent {
+    pub(crate) position: Vec3,
+}
+
+/// Shows this entity on the compass.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component, Serialize, Deserialize)]
+pub(crate) struct MapMarker {
+    /// The text drawn on the compass, usually a single symbol.
+    pub(crate) icon: String,
+    pub(crate) color: Color,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+struct WaypointBeacon;
+
+fn place_waypoint_at_crosshair(
+    actions: Query<(Entity, &ActionState<PlayerAction>), With<Player>>,
+    actions_frozen: Res<ActionsFrozen>,
+    cameras: Query<&GlobalTransform, With<IngameCamera>>,
+    spatial_query: SpatialQuery,
+    mut place_events: EventWriter<PlaceWaypointEvent>,
+) {
+    if !actions.iter().any(|(player, actions)| {
+        !actions_frozen.is_player_frozen(player)
+            && actions.just_pressed(&PlayerAction::PlaceWaypoint)
+    }) {
+        return;
+    }
+    let Some(camera) = cameras.iter().next() else {
+        return;
+    };
+    let Ok(direction) = Direction3d::new(camera.forward()) else {
+        return;
+    };
+    let filter = SpatialQueryFilter::from_mask(CollisionLayer::Terrain.to_bits());
+    if let Some(hit) = spatial_query.cast_ray(
+        camera.translation(),
+        direction,
+        MAX_PLACEMENT_DISTANCE,
+        true,
+        filter,
+    ) {
+        place_events.send(PlaceWaypointEvent {
+            position: camera.translation() + *direction * hit.time_of_impact,
+        });
+    }
+}
+
+fn apply_place_waypoint_events(
+    mut commands: Commands,
+    mut place_events: EventReader<PlaceWaypointEvent>,
+    current_level: Res<CurrentLevel>,
+    mut waypoint: ResMut<CustomWaypoint>,
+) {
+    if let Some(event) = place_events.read().last() {
+        waypoint.0 = Some(Waypoint {
+            level: current_level.0.clone(),
+            position: event.position,
+        });
+        spawn_decal(
+            &mut commands,
+            Decal {
+                position: event.position,
+                size: Vec2::splat(MARKER_SIZE),
+                color: BEACON_COLOR.with_a(0.8),
+                unlit: true,
+                lifetime: MARKER_LIFETIME,
+                fade_duration: MARKER_LIFETIME / 2.,
+                ..default()
+            },
+        );
+    }
+}
+
+fn clear_reached_waypoint(
+    mut waypoint: ResMut<CustomWaypoint>,
+    current_level: Res<CurrentLevel>,
+    players: Query<&GlobalTransform, With<Player>>,
+    config: Res<GameConfig>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+) {
+    let Some(current) = waypoint.0.as_ref() else {
+        return;
+    };
+    if current.level != current_level.0 {
+        return;
+    }
+    let settings = &config.waypoints;
+    let reached = players.iter().any(|player| {
+        (player.translation() - current.position)
+            .horizontal()
+            .length_squared()
+            < settings.reach_radius * settings.reach_radius
+    });
+    if !reached {
+        return;
+    }
+    waypoint.0 = None;
+    if let Some(sound) = &settings.reached_sound {
+        audio.play(asset_server.load::<AudioSource>(sound.clone()));
+    }
+}
+
+fn sync_waypoint_beacon(
+    mut commands: Commands,
+    waypoint: Res<CustomWaypoint>,
+    current_level: Res<CurrentLevel>,
+    config: Res<GameConfig>,
+    mut beacons: Query<(&mut Transform, &mut Visibility), With<WaypointBeacon>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<BeaconMaterial>>,
+) {
+    let position = waypoint
+        .0
+        .as_ref()
+        .filter(|waypoint| waypoint.level == current_level.0)
+        .map(|waypoint| waypoint.position);
+    let height = config.waypoints.beacon_height;
+    let beacon_transform =
+        |position: Vec3| Transform::from_translation(position + Vec3::Y * height / 2.);
+    let Ok((mut transform, mut visibility)) = beacons.get_single_mut() else {
+        if let Some(position) = position {
+            commands.spawn((
+                Name::new("Waypoint Beacon"),
+                MaterialMeshBundle {
+                    mesh: meshes.add(Cylinder::new(BEACON_RADIUS, height)),
+                    material: materials.add(BeaconMaterial {
+                        color: BEACON_COLOR,
+                    }),
+                    transform: beacon_transform(position),
+                    ..default()
+                },
+                NotShadowCaster,
+                WaypointBeacon,
+                MapMarker {
+                    icon: WAYPOINT_ICON.to_string(),
+                    color: BEACON_COLOR.with_a(1.),
+                },
+            ));
+        }
+        return;
+    };
+    // Kept around while in another level, so that coming back shows it again
+    let new_visibility = match position {
+        Some(position) => {
+            let new_transform = beacon_transform(position);
+            if *transform != new_transform {
+                *transform = new_transform;
+            }
+            Visibility::Inherited
+        }
+        None => Visibility::Hidden,
+    };
+    if *visibility != new_visibility {
+        *visibility = new_visibility;
+    }
+}
+
+fn display_beacon_distance(
+    beacons: Query<&InheritedVisibility, With<WaypointBeacon>>,
+    players: Query<&GlobalTransform, With<Player>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<IngameCamera>>,
+    waypoint: Res<CustomWaypoint>,
+    ui_layers: Res<UiLayers>,
+    mut egui_contexts: EguiContexts,
+) {
+    if !ui_layers.is_visible(UiLayer::Hud) {
+        return;
+    }
+    let Some(waypoint) = waypoint.0.as_ref() else {
+        return;
+    };
+    let Some((camera, camera_transform)) = cameras.iter().next() else {
+        return;
+    };
+    let Some(player) = players.iter().next() else {
+        return;
+    };
+    for visibility in beacons.iter() {
+        if !visibility.get() {
+            continue;
+        }
+        // Placed at eye level above the waypoint instead of at the beacon's top, which is usually off-screen
+        let label_position = waypoint.position + Vec3::Y * 2.;
+        let Some(screen_position) = camera.world_to_viewport(camera_transform, label_position)
+        else {
+            continue;
+        };
+        let distance = player.translation().distance(waypoint.position);
+        egui::Area::new(egui::Id::new("Waypoint Distance"))
+            .order(UiLayer::Hud.order())
+            .fixed_pos(egui::pos2(screen_position.x, screen_position.y))
+            .pivot(egui::Align2::CENTER_BOTTOM)
+            .interactable(false)
+            .show(egui_contexts.ctx_mut(), |ui| {
+                ui.label(egui::RichText::new(format!("{distance:.0} m")).strong());
+            });
+    }
+}
+
+fn display_compass(
+    markers: Query<(&MapMarker, &GlobalTransform, &InheritedVisibility)>,
+    cameras: Query<&GlobalTransform, With<IngameCamera>>,
+    ui_layers: Res<UiLayers>,
+    mut egui_contexts: EguiContexts,
+) {
+    if !ui_layers.is_visible(UiLayer::Hud) {
+        return;
+    }
+    let Some(camera) = cameras.iter().next() else {
+        return;
+    };
+    let forward = camera.forward().horizontal().normalize_or_zero();
+    if forward == Vec3::ZERO {
+        return;
+    }
+    let ctx = egui_contexts.ctx_mut();
+    let top_center = ctx.screen_rect().center_top() + egui::vec2(0., 12.);
+    egui::Area::new(egui::Id::new("Compass"))
+        .order(UiLayer::Hud.order())
+        .fixed_pos(top_center)
+        .pivot(egui::Align2::CENTER_TOP)
+        .interactable(false)
+        .show(ctx, |ui| {
+            let (rect, _) =
+                ui.allocate_exact_size(egui::vec2(COMPASS_WIDTH, 20.), egui::Sense::hover());
+            let painter = ui.painter();
+            painter.rect_filled(rect, 4., egui::Color32::from_black_alpha(120));
+            for (marker, transform, visibility) in markers.iter() {
+                if !visibility.get() {
+                    continue;
+                }
+                let to_marker = (transform.translation() - camera.translation()).horizontal();
+                // Positive to the right of the camera
+                let angle = forward.xz().angle_between(to_marker.xz());
+                if !angle.is_finite() || angle.abs() > FRAC_PI_2 {
+                    continue;
+                }
+                let x = rect.center().x + angle / FRAC_PI_2 * rect.width() / 2.;
+                let [r, g, b, a] = marker.color.as_rgba_u8();
+                painter.text(
+                    egui::pos2(x, rect.center().y),
+                    egui::Align2::CENTER_CENTER,
+                    &marker.icon,
+                    egui::FontId::proportional(16.),
+                    egui::Color32::from_rgba_unmultiplied(r, g, b, a),
+                );
+            }
+        });
+}
@@ -0,0 +1,347 @@
+use crate::{
+    determinism::GameRng,
+    player_control::camera::IngameCamera,
+    world_interaction::{
+        dialog::{commands::DialogPosition, YarnCommandsAppExt},
+        time_of_day::TimeOfDay,
+    },
+    GameState,
+};
+use bevy::prelude::*;
+use bevy_yarnspinner::prelude::DialogueRunner;
+pub(crate) use effects::{AmbientWetness, SnowCover};
+use serde::{Deserialize, Serialize};
+
+mod effects;
+
+/// How quickly the [`WeatherExposure`] follows the camera going outdoors and indoors, per second.
+const EXPOSURE_RATE: f32 = 2.;
+
+/// Changes the [`Weather`] gradually, either at random or when dialog asks for it with
+/// `<<set_weather rain 0.7>>`, `<<set_weather snow 0.3>>` or `<<set_weather clear 0>>`.
+/// Changing from one kind of weather to another first lets the old one die down.
+/// The weather is global, but only shows in [`Outdoor`] levels and zones, see [`effects::plugin`].
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Weather>()
+        .register_type::<WeatherTransition>()
+        .register_type::<WeatherSettings>()
+        .register_type::<WeatherExposure>()
+        .register_type::<Outdoor>()
+        .init_resource::<Weather>()
+        .init_resource::<WeatherTransition>()
+        .init_resource::<WeatherSettings>()
+        .init_resource::<WeatherExposure>()
+        .add_yarn_commands(register_weather_command)
+        .add_plugins(effects::plugin)
+        .add_systems(
+            Update,
+            (change_weather_randomly, advance_weather, update_exposure)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// What the sky does right now. Stored in saves.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) enum Weather {
+    #[default]
+    Clear,
+    /// `intensity` is between 0 and 1.
+    Rain { intensity: f32 },
+    /// `intensity` is between 0 and 1.
+    Snow { intensity: f32 },
+}
+
+impl Weather {
+    pub(crate) fn intensity(self) -> f32 {
+        match self {
+            Self::Clear => 0.,
+            Self::Rain { intensity } | Self::Snow { intensity } => intensity,
+        }
+    }
+
+    /// The intensity of the rain, 0 when it does not rain.
+    pub(crate) fn rain(self) -> f32 {
+        match self {
+            Self::Rain { intensity } => intensity,
+            _ => 0.,
+        }
+    }
+
+    /// The intensity of the snowfall, 0 when it does not snow.
+    pub(crate) fn snow(self) -> f32 {
+        match self {
+            Self::Snow { intensity } => intensity,
+            _ => 0.,
+        }
+    }
+
+    /// The same kind of weather with another intensity. Weather without any intensity left is clear.
+    fn with_intensity(self, intensity: f32) -> Self {
+        let intensity = intensity.clamp(0., 1.);
+        match self {
+            _ if intensity <= 0. => Self::Clear,
+            Self::Clear => Self::Clear,
+            Self::Rain { .. } => Self::Rain { intensity },
+            Self::Snow { .. } => Self::Snow { intensity },
+        }
+    }
+
+    fn is_same_kind(self, other: Self) -> bool {
+        std::mem::discriminant(&self) == std::mem::discriminant(&other)
+    }
+
+    /// How much intensity has to change on the way to `target`, counting the old weather dying down.
+    fn distance_to(self, target: Self) -> f32 {
+        if self.is_same_kind(target) || self == Self::Clear {
+            (target.intensity() - self.intensity()).abs()
+        } else {
+            self.intensity() + target.intensity()
+        }
+    }
+
+    /// Changes the intensity by at most `max_step` towards `target`.
+    fn step_towards(self, target: Self, max_step: f32) -> Self {
+        if self.distance_to(target) <= max_step {
+            target
+        } else if self.is_same_kind(target) || self == Self::Clear {
+            let from = if self.is_same_kind(target) {
+                self.intensity()
+            } else {
+                0.
+            };
+            let step = (target.intensity() - from).clamp(-max_step, max_step);
+            target.with_intensity(from + step)
+        } else {
+            self.with_intensity(self.intensity() - max_step)
+        }
+    }
+}
+
+/// Where the [`Weather`] is going. Stored in saves.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect, Serialize, Deserialize)]
+#[reflect(Resource, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct WeatherTransition {
+    pub(crate) target: Weather,
+    /// How much the intensity changes per second.
+    rate: f32,
+    /// Game minutes until the weather changes at random.
+    minutes_until_change: f32,
+    /// How many times the weather changed at random. Seeds the next change.
+    changes: u32,
+}
+
+impl Default for WeatherTransition {
+    fn default() -> Self {
+        Self {
+            target: Weather::Clear,
+            rate: 0.,
+            minutes_until_change: 120.,
+            changes: 0,
+        }
+    }
+}
+
+impl WeatherTransition {
+    /// Starts changing `current` to `target`, taking `seconds` to get there.
+    fn start(&mut self, current: Weather, target: Weather, seconds: f32) {
+        self.target = target;
+        self.rate = current.distance_to(target) / seconds.max(1e-3);
+    }
+}
+
+/// Tuning for how the weather changes.
+#[derive(Debug, Clone, PartialEq, Resource, Reflect, Serialize, Deserialize)]
+#[reflect(Resource, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct WeatherSettings {
+    /// Whether the weather changes by itself.
+    pub(crate) random: bool,
+    /// Each change takes between these many seconds.
+    pub(crate) min_transition: f32,
+    pub(crate) max_transition: f32,
+    /// Each kind of weather lasts between these many game minutes before the next random change.
+    /// Weather set by dialog also lasts at least the minimum.
+    pub(crate) min_duration: f32,
+    pub(crate) max_duration: f32,
+    /// How likely a random change is to bring rain and snow. It clears up otherwise.
+    pub(crate) rain_chance: f32,
+    pub(crate) snow_chance: f32,
+}
+
+impl Default for WeatherSettings {
+    fn default() -> Self {
+        Self {
+            random: true,
+            min_transition: 30.,
+            max_transition: 60.,
+            min_duration: 120.,
+            max_duration: 480.,
+            rain_chance: 0.35,
+            snow_chance: 0.1,
+        }
+    }
+}
+
+/// Marks a level or a part of it as being outdoors. Everything below an entity with this component is exposed to the weather.
+/// With `half_extents`, the camera counts as outdoors only within the box around the entity's origin.
+/// Without, it counts as outdoors anywhere in the level.
+/// Zones may be rotated around the Y axis, but not tilted.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Outdoor {
+    pub(crate) half_extents: Option<Vec3>,
+}
+
+/// Between 0 while the camera is indoors and 1 while it is [`Outdoor`], smoothed so that rain does not pop in and out at doors.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect, Default)]
+#[reflect(Resource)]
+pub(crate) struct WeatherExposure(pub(crate) f32);
+
+fn register_weather_command(dialogue_runner: &mut DialogueRunner) {
+    dialogue_runner
+        .commands_mut()
+        .add_command("set_weather", set_weather);
+}
+
+fn set_weather(
+    In((kind, intensity)): In<(String, f32)>,
+    weather: Res<Weather>,
+    mut transition: ResMut<WeatherTransition>,
+    settings: Res<WeatherSettings>,
+    game_rng: Res<GameRng>,
+    position: Res<DialogPosition>,
+) {
+    let intensity = intensity.clamp(0., 1.);
+    let target = match kind.as_str() {
+        "clear" => Weather::Clear,
+        "rain" => Weather::Rain { intensity },
+        "snow" => Weather::Snow { intensity },
+        _ => {
+            error!(
+                "<<set_weather>> in {}: expected \"clear\", \"rain\" or \"snow\", but got \"{kind}\"",
+                *position
+            );
+            return;
+        }
+    };
+    let mut rng = game_rng.fork(&format!("scripted weather {}", transition.changes));
+    let seconds = rng.range_f32(transition_range(&settings));
+    transition.start(*weather, target.with_intensity(intensity), seconds);
+    transition.minutes_until_change = settings.min_duration;
+}
+
+fn transition_range(settings: &WeatherSettings) -> std::ops::Range<f32> {
+    let min = settings.min_transition.max(0.);
+    min..settings.max_transition.max(min + 1e-3)
+}
+
+fn change_weather_randomly(
+    time: Res<Time>,
+    time_of_day: Res<TimeOfDay>,
+    settings: Res<WeatherSettings>,
+    game_rng: Res<GameRng>,
+    weather: Res<Weather>,
+    mut transition: ResMut<WeatherTransition>,
+) {
+    if !settings.random {
+        return;
+    }
+    let minutes = time_of_day.minutes_in(time.delta_seconds());
+    if minutes <= 0. {
+        return;
+    }
+    transition.minutes_until_change -= minutes;
+    if transition.minutes_until_change > 0. {
+        return;
+    }
+    let mut rng = game_rng.fork(&format!("weather {}", transition.changes));
+    transition.changes += 1;
+    let roll = rng.f32();
+    let intensity = rng.range_f32(0.3..1.);
+    let target = if roll < settings.rain_chance {
+        Weather::Rain { intensity }
+    } else if roll < settings.rain_chance + settings.snow_chance {
+        Weather::Snow { intensity }
+    } else {
+        Weather::Clear
+    };
+    let seconds = rng.range_f32(transition_range(&settings));
+    transition.start(*weather, target, seconds);
+    let min_duration = settings.min_duration.max(1.);
+    transition.minutes_until_change =
+        rng.range_f32(min_duration..settings.max_duration.max(min_duration + 1e-3));
+}
+
+fn advance_weather(
+    time: Res<Time>,
+    transition: Res<WeatherTransition>,
+    mut weather: ResMut<Weather>,
+) {
+    if *weather == transition.target {
+        return;
+    }
+    let next = weather.step_towards(transition.target, transition.rate * time.delta_seconds());
+    if *weather != next {
+        *weather = next;
+    }
+}
+
+fn update_exposure(
+    time: Res<Time>,
+    cameras: Query<&GlobalTransform, With<IngameCamera>>,
+    zones: Query<(&Outdoor, &GlobalTransform)>,
+    mut exposure: ResMut<WeatherExposure>,
+) {
+    let Some(camera) = cameras.iter().next().map(GlobalTransform::translation) else {
+        return;
+    };
+    let is_outdoors = zones.iter().any(|(outdoor, transform)| {
+        outdoor.half_extents.map_or(true, |half_extents| {
+            let local = transform.affine().inverse().transform_point3(camera);
+            local.abs().cmple(half_extents).all()
+        })
+    });
+    let target = if is_outdoors { 1. } else { 0. };
+    let max_step = EXPOSURE_RATE * time.delta_seconds();
+    let level = exposure.0 + (target - exposure.0).clamp(-max_step, max_step);
+    if exposure.0 != level {
+        exposure.0 = level;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rain_dies_down_before_snow_starts() {
+        let rain = Weather::Rain { intensity: 0.5 };
+        let snow = Weather::Snow { intensity: 0.75 };
+        assert_eq!(rain.distance_to(snow), 1.25);
+
+        let weather = rain.step_towards(snow, 0.25);
+        assert_eq!(weather, Weather::Rain { intensity: 0.25 });
+        let weather = weather.step_towards(snow, 0.25);
+        assert_eq!(weather, Weather::Clear);
+        let weather = weather.step_towards(snow, 0.25);
+        assert_eq!(weather, Weather::Snow { intensity: 0.25 });
+    }
+
+    #[test]
+    fn weather_settles_on_target() {
+        let target = Weather::Rain { intensity: 0.7 };
+        let mut weather = Weather::Rain { intensity: 0.2 };
+        for _ in 0..10 {
+            weather = weather.step_towards(target, 0.1);
+        }
+        assert_eq!(weather, target);
+        for _ in 0..10 {
+            weather = weather.step_towards(Weather::Clear, 0.1);
+        }
+        assert_eq!(weather, Weather::Clear);
+    }
+}
@@ -0,0 +1,400 @@
+use crate::{
+    determinism::{GameRng, RngStream},
+    level_instantiation::on_spawn::{GroundSurface, SurfaceGrip},
+    movement::water::WaterVolume,
+    particles,
+    player_control::camera::IngameCamera,
+    world_interaction::weather::{Outdoor, Weather, WeatherExposure},
+    GameState,
+};
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    utils::HashMap,
+};
+use bevy_hanabi::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Precipitation particles are only emitted above this intensity.
+const MIN_PARTICLE_INTENSITY: f32 = 0.05;
+/// Wet outdoor surfaces keep this fraction of their friction and grip in the heaviest rain.
+const WET_GRIP: f32 = 0.6;
+/// Ripples appear on water within this many meters of the camera.
+const RIPPLE_RANGE: f32 = 15.;
+/// Ripples per second in the heaviest rain.
+const RIPPLE_RATE: f32 = 30.;
+const MAX_RIPPLES: usize = 64;
+/// In seconds.
+const RIPPLE_LIFETIME: f32 = 0.8;
+/// The radius of a ripple when it fades out, in meters.
+const RIPPLE_RADIUS: f32 = 0.4;
+/// Keeps ripples from flickering with the water surface.
+const RIPPLE_OFFSET: f32 = 0.01;
+/// Seconds of the heaviest snowfall until outdoor objects are fully covered.
+const SNOW_ACCUMULATION_TIME: f32 = 120.;
+/// Seconds until a full snow cover has melted while it does not snow. Rain melts it twice as fast.
+const SNOW_MELT_TIME: f32 = 300.;
+/// How far materials are tinted towards [`SNOW_COLOR`] under a full snow cover.
+const MAX_SNOW_TINT: f32 = 0.7;
+/// The tint of the materials is only updated once it changed by at least this much.
+const MIN_TINT_CHANGE: f32 = 0.01;
+const SNOW_COLOR: Color = Color::rgb(0.95, 0.96, 1.);
+
+/// Makes the [`Weather`] visible and felt while the camera is [`Outdoor`]s:
+/// - Rain and snow fall around the camera.
+/// - Rain makes ripples on nearby [`WaterVolume`]s and raises the [`AmbientWetness`].
+/// - Rain makes outdoor [`GroundSurface`]s slippery, lowering both the physics friction and the [`SurfaceGrip`] of their colliders.
+/// - Snow builds up a [`SnowCover`] that tints the materials of everything outdoors white, and melts again afterwards.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<AmbientWetness>()
+        .register_type::<SnowCover>()
+        .init_resource::<AmbientWetness>()
+        .init_resource::<SnowCover>()
+        .init_resource::<Ripples>()
+        .init_resource::<SnowMaterials>()
+        .add_systems(
+            Update,
+            (
+                spawn_precipitation,
+                play_precipitation,
+                spawn_ripples,
+                update_ripples,
+                update_ambient_wetness,
+                (init_wet_surfaces, update_wet_surfaces).chain(),
+                (
+                    accumulate_snow,
+                    collect_snow_materials,
+                    update_snow_materials,
+                )
+                    .chain(),
+            )
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// How wet the soundscape should sound, between 0 and 1. Follows the rain while the camera is outdoors.
+/// Like the [`ReverbSend`](crate::file_system_interaction::spatial_audio::ReverbSend), bevy_kira_audio does not expose kira's filters,
+/// so for now this is only the input for mixing ambient audio, not the mix itself.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect, Default)]
+#[reflect(Resource)]
+pub(crate) struct AmbientWetness(pub(crate) f32);
+
+/// How much snow lies on everything outdoors, between 0 and 1. Stored in saves.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct SnowCover(pub(crate) f32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+enum PrecipitationParticle {
+    Rain,
+    Snow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct Ripple {
+    age: f32,
+}
+
+#[derive(Debug, Default, Resource)]
+struct Ripples {
+    mesh: Option<Handle<Mesh>>,
+    count: usize,
+    /// Fractional ripples carried over to the next frame.
+    pending: f32,
+    rng: Option<RngStream>,
+}
+
+/// On the colliders of outdoor [`GroundSurface`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct WetSurface {
+    dry_friction: Friction,
+    dry_grip: f32,
+}
+
+/// The outdoor copies of materials, by the material they were copied from, with the color of the original.
+/// Copying keeps the snow off indoor objects that share a material with outdoor ones.
+#[derive(Debug, Default, Resource)]
+struct SnowMaterials {
+    copies: HashMap<AssetId<StandardMaterial>, (Handle<StandardMaterial>, Color)>,
+    /// The tint the copies currently have.
+    applied_tint: f32,
+}
+
+fn spawn_precipitation(
+    mut commands: Commands,
+    cameras: Query<Entity, Added<IngameCamera>>,
+    mut effects: ResMut<Assets<EffectAsset>>,
+) {
+    for camera in cameras.iter() {
+        commands.entity(camera).with_children(|parent| {
+            parent.spawn((
+                particles::create_rain_particle_bundle(&mut effects),
+                PrecipitationParticle::Rain,
+            ));
+            parent.spawn((
+                particles::create_snow_particle_bundle(&mut effects),
+                PrecipitationParticle::Snow,
+            ));
+        });
+    }
+}
+
+fn play_precipitation(
+    weather: Res<Weather>,
+    exposure: Res<WeatherExposure>,
+    mut spawners: Query<(&PrecipitationParticle, &mut EffectSpawner)>,
+) {
+    for (particle, mut spawner) in spawners.iter_mut() {
+        let intensity = match particle {
+            PrecipitationParticle::Rain => weather.rain(),
+            PrecipitationParticle::Snow => weather.snow(),
+        };
+        let active = intensity * exposure.0 > MIN_PARTICLE_INTENSITY;
+        if spawner.is_active() != active {
+            spawner.set_active(active);
+        }
+    }
+}
+
+fn spawn_ripples(
+    mut commands: Commands,
+    time: Res<Time>,
+    weather: Res<Weather>,
+    exposure: Res<WeatherExposure>,
+    game_rng: Res<GameRng>,
+    cameras: Query<&GlobalTransform, With<IngameCamera>>,
+    volumes: Query<(&WaterVolume, &GlobalTransform)>,
+    mut ripples: ResMut<Ripples>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let intensity = weather.rain() * exposure.0;
+    let Some(camera) = cameras.iter().next().map(GlobalTransform::translation) else {
+        return;
+    };
+    if intensity <= 0. || volumes.is_empty() {
+        ripples.pending = 0.;
+        return;
+    }
+    ripples.pending += RIPPLE_RATE * intensity * time.delta_seconds();
+    let mesh = ripples
+        .mesh
+        .get_or_insert_with(|| meshes.add(Circle::new(1.)))
+        .clone();
+    let mut rng = ripples
+        .rng
+        .take()
+        .unwrap_or_else(|| game_rng.fork("rain ripples"));
+    while ripples.pending >= 1. {
+        ripples.pending -= 1.;
+        if ripples.count >= MAX_RIPPLES {
+            continue;
+        }
+        // Uniformly distributed over the disk around the camera
+        let offset = rng.horizontal_direction() * RIPPLE_RANGE * rng.f32().sqrt();
+        let point = camera + offset;
+        let Some(surface) = volumes.iter().find_map(|(volume, transform)| {
+            let local = transform.affine().inverse().transform_point3(point);
+            let extents = volume.half_extents;
+            let inside = local.x.abs() <= extents.x && local.z.abs() <= extents.z;
+            inside.then(|| {
+                let top = transform.transform_point(Vec3::Y * extents.y).y;
+                Vec3::new(point.x, top + RIPPLE_OFFSET, point.z)
+            })
+        }) else {
+            continue;
+        };
+        let material = materials.add(StandardMaterial {
+            base_color: Color::rgba(0.9, 0.95, 1., 0.),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        });
+        commands.spawn((
+            Name::new("Rain ripple"),
+            Ripple { age: 0. },
+            PbrBundle {
+                mesh: mesh.clone(),
+                material,
+                transform: Transform::from_translation(surface)
+                    .with_rotation(Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2))
+                    .with_scale(Vec3::ZERO),
+                ..default()
+            },
+            NotShadowCaster,
+            NotShadowReceiver,
+        ));
+        ripples.count += 1;
+    }
+    ripples.rng = Some(rng);
+}
+
+fn update_ripples(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut ripples: ResMut<Ripples>,
+    mut ripple_query: Query<(
+        Entity,
+        &mut Ripple,
+        &mut Transform,
+        &Handle<StandardMaterial>,
+    )>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut ripple, mut transform, material) in ripple_query.iter_mut() {
+        ripple.age += dt;
+        let progress = ripple.age / RIPPLE_LIFETIME;
+        if progress >= 1. {
+            commands.entity(entity).despawn_recursive();
+            ripples.count = ripples.count.saturating_sub(1);
+            continue;
+        }
+        transform.scale = Vec3::splat(RIPPLE_RADIUS * progress);
+        if let Some(material) = materials.get_mut(material) {
+            material.base_color.set_a(0.4 * (1. - progress));
+        }
+    }
+}
+
+fn update_ambient_wetness(
+    weather: Res<Weather>,
+    exposure: Res<WeatherExposure>,
+    mut wetness: ResMut<AmbientWetness>,
+) {
+    let target = weather.rain() * exposure.0;
+    if wetness.0 != target {
+        wetness.0 = target;
+    }
+}
+
+/// Colliders may spawn some frames after their surface, e.g. in blueprints, so they are picked up as they appear.
+fn init_wet_surfaces(
+    mut commands: Commands,
+    colliders: Query<(Entity, Option<&Friction>, Option<&SurfaceGrip>), Added<Collider>>,
+    parents: Query<&Parent>,
+    surfaces: Query<(), With<GroundSurface>>,
+    outdoors: Query<(), With<Outdoor>>,
+) {
+    for (entity, friction, grip) in colliders.iter() {
+        let ancestors: Vec<_> = std::iter::once(entity)
+            .chain(parents.iter_ancestors(entity))
+            .collect();
+        let is_surface = ancestors.iter().any(|&entity| surfaces.contains(entity));
+        let is_outdoor = ancestors.iter().any(|&entity| outdoors.contains(entity));
+        if !is_surface || !is_outdoor {
+            continue;
+        }
+        let friction = friction.copied().unwrap_or_default();
+        let grip = grip.copied().unwrap_or_default();
+        commands.entity(entity).insert((
+            WetSurface {
+                dry_friction: friction,
+                dry_grip: grip.0,
+            },
+            friction,
+            grip,
+        ));
+    }
+}
+
+fn update_wet_surfaces(
+    weather: Res<Weather>,
+    mut surfaces: Query<(Ref<WetSurface>, &mut Friction, &mut SurfaceGrip)>,
+) {
+    let grip_factor = 1. - (1. - WET_GRIP) * weather.rain();
+    for (surface, mut friction, mut grip) in surfaces.iter_mut() {
+        if !weather.is_changed() && !surface.is_added() {
+            continue;
+        }
+        friction.dynamic_coefficient = surface.dry_friction.dynamic_coefficient * grip_factor;
+        friction.static_coefficient = surface.dry_friction.static_coefficient * grip_factor;
+        grip.0 = surface.dry_grip * grip_factor;
+    }
+}
+
+fn accumulate_snow(time: Res<Time>, weather: Res<Weather>, mut snow_cover: ResMut<SnowCover>) {
+    let dt = time.delta_seconds();
+    let change = if weather.snow() > 0. {
+        weather.snow() * dt / SNOW_ACCUMULATION_TIME
+    } else {
+        let melt_speed = if weather.rain() > 0. { 2. } else { 1. };
+        -melt_speed * dt / SNOW_MELT_TIME
+    };
+    let cover = (snow_cover.0 + change).clamp(0., 1.);
+    if snow_cover.0 != cover {
+        snow_cover.0 = cover;
+    }
+}
+
+/// Meshes may spawn some frames after their [`Outdoor`] ancestor, e.g. in blueprints, so they are picked up as they appear.
+fn collect_snow_materials(
+    mut commands: Commands,
+    meshes: Query<(Entity, &Handle<StandardMaterial>), Added<Handle<StandardMaterial>>>,
+    parents: Query<&Parent>,
+    outdoors: Query<(), With<Outdoor>>,
+    mut snow_materials: ResMut<SnowMaterials>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, handle) in meshes.iter() {
+        let is_outdoor = parents
+            .iter_ancestors(entity)
+            .any(|entity| outdoors.contains(entity));
+        let id = handle.id();
+        let is_copy = || {
+            snow_materials
+                .copies
+                .values()
+                .any(|(copy, _)| copy.id() == id)
+        };
+        if !is_outdoor || is_copy() {
+            continue;
+        }
+        let copy = match snow_materials.copies.get(&id) {
+            Some((copy, _)) => copy.clone(),
+            None => {
+                let Some(material) = materials.get(handle) else {
+                    continue;
+                };
+                let color = material.base_color;
+                let copy = materials.add(StandardMaterial {
+                    base_color: snowy(color, snow_materials.applied_tint),
+                    ..material.clone()
+                });
+                snow_materials.copies.insert(id, (copy.clone(), color));
+                copy
+            }
+        };
+        commands.entity(entity).insert(copy);
+    }
+}
+
+/// Only touches the materials once the tint changed noticeably, since every change uploads them to the GPU again.
+fn update_snow_materials(
+    snow_cover: Res<SnowCover>,
+    mut snow_materials: ResMut<SnowMaterials>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let tint = snow_cover.0 * MAX_SNOW_TINT;
+    let is_settled = tint == snow_materials.applied_tint;
+    let is_small_change = (tint - snow_materials.applied_tint).abs() < MIN_TINT_CHANGE;
+    if is_settled || (is_small_change && tint != 0.) {
+        return;
+    }
+    #[cfg(feature = "tracing")]
+    let _span = info_span!("update_snow_materials").entered();
+    snow_materials.applied_tint = tint;
+    for (copy, color) in snow_materials.copies.values() {
+        if let Some(material) = materials.get_mut(copy) {
+            material.base_color = snowy(*color, tint);
+        }
+    }
+}
+
+fn snowy(color: Color, tint: f32) -> Color {
+    let original = Vec4::from(color.as_rgba_f32());
+    let mut tinted = original.lerp(Vec4::from(SNOW_COLOR.as_rgba_f32()), tint);
+    tinted.w = original.w;
+    Color::rgba_from_array(tinted)
+}
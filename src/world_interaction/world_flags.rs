@@ -0,0 +1,502 @@
+use crate::{
+    level_instantiation::{
+        on_spawn::Player,
+        spawn_queue::SpawnRequest,
+        stable_id::{StableId, StableIdRegistry},
+    },
+    world_interaction::dialog::{commands::DialogPosition, YarnCommandsAppExt},
+    GameState,
+};
+use bevy::{prelude::*, utils::HashMap};
+use bevy_xpbd_3d::prelude::*;
+use bevy_yarnspinner::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+
+/// Keeps track of [`WorldFlags`], named values that dialog choices and triggers leave behind for gameplay code to react to.
+/// Dialog sets and reads them:
+/// - `<<set_flag bridge_repaired true>>` sets a flag to `true`, `false`, a whole number or a decimal number.
+/// - `<<clear_flag bridge_repaired>>` removes a flag.
+/// - `flag("bridge_repaired")` is true if the flag is `true` or a non-zero number, e.g. `<<if flag("bridge_repaired")>>`.
+/// - `flag_number("bridges_repaired")` is the flag's number, 0 if it is not set.
+///
+/// Levels use [`FlagCondition`]s to gate what exists and what can be used:
+/// - [`FlagGatedSpawn`] spawns a blueprint while its condition is met and despawns it again when it stops being met.
+/// - [`RequiresFlags`] makes an interactable only usable while its condition is met.
+/// - [`SetFlagsOnEnter`] sets flags when the player enters a sensor.
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<WorldFlags>()
+        .register_type::<FlagValue>()
+        .register_type::<FlagCondition>()
+        .register_type::<FlagGatedSpawn>()
+        .register_type::<RequiresFlags>()
+        .register_type::<SetFlagsOnEnter>()
+        .init_resource::<WorldFlags>()
+        .init_resource::<YarnFlags>()
+        .add_event::<WorldFlagChanged>()
+        .add_yarn_commands(register_flag_commands)
+        .add_systems(
+            Update,
+            (
+                register_flag_functions,
+                set_flags_on_enter,
+                mirror_flags_to_yarn,
+                send_flag_change_events,
+                update_flag_gated_spawns,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+/// Named values set by dialog choices and triggers. Stored in saves.
+#[derive(Debug, Clone, PartialEq, Resource, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Resource, Serialize, Deserialize)]
+pub(crate) struct WorldFlags(HashMap<String, FlagValue>);
+
+impl WorldFlags {
+    pub(crate) fn get(&self, flag: &str) -> Option<FlagValue> {
+        self.0.get(flag).copied()
+    }
+
+    /// Whether the flag is `true` or a non-zero number.
+    pub(crate) fn is_set(&self, flag: &str) -> bool {
+        self.get(flag).is_some_and(FlagValue::is_truthy)
+    }
+
+    /// The flag's number, 0 if it is not set.
+    pub(crate) fn number(&self, flag: &str) -> f32 {
+        self.get(flag).map_or(0., FlagValue::as_number)
+    }
+
+    pub(crate) fn set(&mut self, flag: impl Into<String>, value: FlagValue) {
+        self.0.insert(flag.into(), value);
+    }
+
+    pub(crate) fn clear(&mut self, flag: &str) {
+        self.0.remove(flag);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) enum FlagValue {
+    Bool(bool),
+    Int(i32),
+    Float(f32),
+}
+
+impl FlagValue {
+    /// Parses `true`, `false`, whole numbers and decimal numbers.
+    pub(crate) fn parse(text: &str) -> Option<Self> {
+        match text.trim() {
+            "true" => Some(Self::Bool(true)),
+            "false" => Some(Self::Bool(false)),
+            text => text
+                .parse()
+                .map(Self::Int)
+                .or_else(|_| text.parse().map(Self::Float))
+                .ok(),
+        }
+    }
+
+    pub(crate) fn is_truthy(self) -> bool {
+        match self {
+            Self::Bool(value) => value,
+            Self::Int(value) => value != 0,
+            Self::Float(value) => value != 0.,
+        }
+    }
+
+    /// `true` counts as 1 and `false` as 0.
+    pub(crate) fn as_number(self) -> f32 {
+        match self {
+            Self::Bool(value) => f32::from(u8::from(value)),
+            Self::Int(value) => value as f32,
+            Self::Float(value) => value,
+        }
+    }
+}
+
+/// Sent when a flag is set to a different value or cleared, whatever changed it.
+#[derive(Debug, Clone, PartialEq, Event)]
+pub(crate) struct WorldFlagChanged {
+    pub(crate) flag: String,
+    /// `None` when the flag was cleared.
+    pub(crate) value: Option<FlagValue>,
+    pub(crate) previous: Option<FlagValue>,
+}
+
+/// Met when all tests in `all` pass and, if there are any, at least one test in `any` does.
+/// The default is always met.
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct FlagCondition {
+    pub(crate) all: Vec<FlagTest>,
+    pub(crate) any: Vec<FlagTest>,
+}
+
+impl FlagCondition {
+    pub(crate) fn is_met(&self, flags: &WorldFlags) -> bool {
+        self.all.iter().all(|test| test.passes(flags))
+            && (self.any.is_empty() || self.any.iter().any(|test| test.passes(flags)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Reflect, Serialize, Deserialize)]
+#[reflect(Serialize, Deserialize)]
+pub(crate) enum FlagTest {
+    /// The flag is `true` or a non-zero number.
+    IsSet(String),
+    /// The flag is missing, `false` or zero.
+    IsNotSet(String),
+    Equals(String, FlagValue),
+    /// The flag's number is at least this.
+    AtLeast(String, f32),
+    /// The flag's number is less than this.
+    Below(String, f32),
+}
+
+impl FlagTest {
+    fn passes(&self, flags: &WorldFlags) -> bool {
+        match self {
+            Self::IsSet(flag) => flags.is_set(flag),
+            Self::IsNotSet(flag) => !flags.is_set(flag),
+            Self::Equals(flag, value) => flags.get(flag) == Some(*value),
+            Self::AtLeast(flag, value) => flags.number(flag) >= *value,
+            Self::Below(flag, value) => flags.number(flag) < *value,
+        }
+    }
+}
+
+/// Spawns `blueprint` at this entity while the `condition` is met and despawns it when it stops being met,
+/// including when the flags change mid-session. The spawned object gets a [`StableId`] derived from this entity's.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct FlagGatedSpawn {
+    pub(crate) condition: FlagCondition,
+    pub(crate) blueprint: String,
+    /// Defaults to the blueprint's name.
+    pub(crate) name: Option<String>,
+}
+
+/// Put this on an interactable to make it usable only while the condition is met.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+pub(crate) struct RequiresFlags(pub(crate) FlagCondition);
+
+/// Sets these flags when the player enters a sensor on this entity or below it.
+#[derive(Debug, Clone, PartialEq, Component, Reflect, Serialize, Deserialize, Default)]
+#[reflect(Component, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct SetFlagsOnEnter {
+    pub(crate) flags: HashMap<String, FlagValue>,
+}
+
+/// A copy of the [`WorldFlags`] for the yarn functions, which cannot access the world.
+#[derive(Debug, Clone, Default, Resource)]
+struct YarnFlags(Arc<RwLock<WorldFlags>>);
+
+impl YarnFlags {
+    fn update(&self, flags: &WorldFlags) {
+        match self.0.write() {
+            Ok(mut copy) => copy.clone_from(flags),
+            Err(error) => error!("Failed to update the flags for yarn: {error}"),
+        }
+    }
+}
+
+/// Remembers which spawns a [`FlagGatedSpawn`] requested.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+struct GatedSpawnState {
+    id: StableId,
+    requested: bool,
+}
+
+fn register_flag_commands(dialogue_runner: &mut DialogueRunner) {
+    dialogue_runner
+        .commands_mut()
+        .add_command("set_flag", set_flag)
+        .add_command("clear_flag", clear_flag);
+}
+
+fn set_flag(
+    In((flag, value)): In<(String, String)>,
+    mut flags: ResMut<WorldFlags>,
+    yarn_flags: Res<YarnFlags>,
+    position: Res<DialogPosition>,
+) {
+    let Some(value) = FlagValue::parse(&value) else {
+        error!(
+            "<<set_flag>> in {}: expected true, false or a number for \"{flag}\", but got \"{value}\"",
+            *position
+        );
+        return;
+    };
+    flags.set(flag, value);
+    // The rest of the node may already read the flag in this frame
+    yarn_flags.update(&flags);
+}
+
+fn clear_flag(In(flag): In<String>, mut flags: ResMut<WorldFlags>, yarn_flags: Res<YarnFlags>) {
+    flags.clear(&flag);
+    yarn_flags.update(&flags);
+}
+
+fn register_flag_functions(
+    mut dialogue_runners: Query<&mut DialogueRunner, Added<DialogueRunner>>,
+    yarn_flags: Res<YarnFlags>,
+) {
+    for mut dialogue_runner in dialogue_runners.iter_mut() {
+        let is_set = yarn_flags.0.clone();
+        let number = yarn_flags.0.clone();
+        dialogue_runner
+            .library_mut()
+            .add_function("flag", move |flag: String| {
+                is_set.read().is_ok_and(|flags| flags.is_set(&flag))
+            })
+            .add_function("flag_number", move |flag: String| {
+                number.read().map_or(0., |flags| flags.number(&flag))
+            });
+    }
+}
+
+fn mirror_flags_to_yarn(flags: Res<WorldFlags>, yarn_flags: Res<YarnFlags>) {
+    if flags.is_changed() {
+        yarn_flags.update(&flags);
+    }
+}
+
+fn set_flags_on_enter(
+    mut collision_events: EventReader<CollisionStarted>,
+    players: Query<(), With<Player>>,
+    sensors: Query<(), With<Sensor>>,
+    triggers: Query<&SetFlagsOnEnter>,
+    parents: Query<&Parent>,
+    mut flags: ResMut<WorldFlags>,
+) {
+    for CollisionStarted(entity1, entity2) in collision_events.read() {
+        let sensor = if players.contains(*entity1) {
+            *entity2
+        } else if players.contains(*entity2) {
+            *entity1
+        } else {
+            continue;
+        };
+        if !sensors.contains(sensor) {
+            continue;
+        }
+        let Some(trigger) = std::iter::once(sensor)
+            .chain(parents.iter_ancestors(sensor))
+            .find_map(|entity| triggers.get(entity).ok())
+        else {
+            continue;
+        };
+        for (flag, value) in &trigger.flags {
+            if flags.get(flag) != Some(*value) {
+                flags.set(flag.clone(), *value);
+            }
+        }
+    }
+}
+
+fn send_flag_change_events(
+    flags: Res<WorldFlags>,
+    mut previous: Local<WorldFlags>,
+    mut change_events: EventWriter<WorldFlagChanged>,
+) {
+    if !flags.is_changed() || *flags == *previous {
+        return;
+    }
+    for (flag, &value) in flags.0.iter() {
+        let old = previous.get(flag);
+        if old != Some(value) {
+            change_events.send(WorldFlagChanged {
+                flag: flag.clone(),
+                value: Some(value),
+                previous: old,
+            });
+        }
+    }
+    for (flag, &value) in previous.0.iter() {
+        if flags.get(flag).is_none() {
+            change_events.send(WorldFlagChanged {
+                flag: flag.clone(),
+                value: None,
+                previous: Some(value),
+            });
+        }
+    }
+    previous.clone_from(&flags);
+}
+
+fn update_flag_gated_spawns(
+    mut commands: Commands,
+    flags: Res<WorldFlags>,
+    mut gates: Query<(
+        Entity,
+        &FlagGatedSpawn,
+        &GlobalTransform,
+        Option<&StableId>,
+        Option<&mut GatedSpawnState>,
+    )>,
+    registry: Res<StableIdRegistry>,
+    mut removed: RemovedComponents<FlagGatedSpawn>,
+    mut spawn_requests: EventWriter<SpawnRequest>,
+    mut spawned_ids: Local<HashMap<Entity, StableId>>,
+    mut orphaned_ids: Local<Vec<StableId>>,
+) {
+    let despawn = |commands: &mut Commands, id: StableId| {
+        let Some(entity) = registry.get(id) else {
+            // Not spawned yet, so try again next frame
+            return false;
+        };
+        if let Some(entity) = commands.get_entity(entity) {
+            entity.despawn_recursive();
+        }
+        true
+    };
+    // What a gate spawned goes away with the gate, e.g. when leaving the level, even if it is still queued
+    orphaned_ids.extend(removed.read().filter_map(|gate| spawned_ids.remove(&gate)));
+    orphaned_ids.retain(|&id| !despawn(&mut commands, id));
+
+    for (entity, gate, transform, gate_id, state) in gates.iter_mut() {
+        let Some(mut state) = state else {
+            // Level entities get their id at the end of the frame they spawn in
+            let Some(gate_id) = gate_id else {
+                continue;
+            };
+            commands.entity(entity).insert(GatedSpawnState {
+                id: StableId::derive(&gate_id.0.to_string(), "flag gated spawn"),
+                requested: false,
+            });
+            continue;
+        };
+        let should_exist = gate.condition.is_met(&flags);
+        if should_exist && !state.requested {
+            let mut request = SpawnRequest::new(&gate.blueprint, transform.compute_transform());
+            request.name.clone_from(&gate.name);
+            request.id = Some(state.id);
+            spawn_requests.send(request);
+            state.requested = true;
+            spawned_ids.insert(entity, state.id);
+        } else if !should_exist && state.requested && despawn(&mut commands, state.id) {
+            state.requested = false;
+            spawned_ids.remove(&entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        determinism::GameRng,
+        level_instantiation::{
+            spawn_queue::{self, SpawnBudget},
+            stable_id,
+        },
+        testing::TestApp,
+    };
+    use bevy_gltf_blueprints::BlueprintName;
+
+    /// A gate for a bridge that only exists while `bridge_repaired` is set.
+    /// Nothing is spawned from the queue until [`set_spawning`] allows it.
+    fn app_with_gate() -> (TestApp, Entity) {
+        let mut app = TestApp::new();
+        app.add_plugins((plugin, spawn_queue::plugin, stable_id::plugin))
+            .insert_resource(GameRng::new(0));
+        set_spawning(&mut app, false);
+        let gate = app
+            .world_mut()
+            .spawn((
+                FlagGatedSpawn {
+                    condition: FlagCondition {
+                        all: vec![FlagTest::IsSet("bridge_repaired".to_string())],
+                        any: Vec::new(),
+                    },
+                    blueprint: "Bridge".to_string(),
+                    name: None,
+                },
+                StableId::derive("levels/river.glb", "Bridge Gate"),
+                TransformBundle::default(),
+            ))
+            .id();
+        app.step(2);
+        (app, gate)
+    }
+
+    fn set_spawning(app: &mut TestApp, enabled: bool) {
+        app.insert_resource(SpawnBudget {
+            max_per_frame: if enabled { 64 } else { 0 },
+            ..default()
+        });
+    }
+
+    fn set_bridge_repaired(app: &mut TestApp, repaired: bool) {
+        let mut flags = app.world_mut().resource_mut::<WorldFlags>();
+        if repaired {
+            flags.set("bridge_repaired", FlagValue::Bool(true));
+        } else {
+            flags.clear("bridge_repaired");
+        }
+    }
+
+    fn bridges(app: &mut TestApp) -> usize {
+        app.world_mut()
+            .query_filtered::<(), With<BlueprintName>>()
+            .iter(app.world())
+            .count()
+    }
+
+    #[test]
+    fn spawns_that_stop_being_met_while_queued_are_despawned_once_spawned() {
+        let (mut app, _gate) = app_with_gate();
+        set_bridge_repaired(&mut app, true);
+        app.step(2);
+        assert_eq!(bridges(&mut app), 0, "The spawn should still be queued");
+
+        set_bridge_repaired(&mut app, false);
+        app.step(2);
+        set_spawning(&mut app, true);
+        app.step(4);
+        assert_eq!(bridges(&mut app), 0);
+
+        // The gate still works afterwards
+        set_bridge_repaired(&mut app, true);
+        app.step(4);
+        assert_eq!(bridges(&mut app), 1);
+    }
+
+    #[test]
+    fn spawns_of_removed_gates_are_despawned_once_spawned() {
+        let (mut app, gate) = app_with_gate();
+        set_bridge_repaired(&mut app, true);
+        app.step(2);
+        app.world_mut().despawn(gate);
+        app.step(2);
+        set_spawning(&mut app, true);
+        app.step(4);
+        assert_eq!(bridges(&mut app), 0);
+    }
+
+    #[test]
+    fn conditions_check_flags() {
+        let mut flags = WorldFlags::default();
+        let condition: FlagCondition = ron::from_str(
+            "(all: [IsSet(\"bridge_repaired\")], any: [AtLeast(\"planks\", 3.0), Equals(\"mood\", Int(2))])",
+        )
+        .unwrap();
+        assert!(!condition.is_met(&flags));
+        flags.set("bridge_repaired", FlagValue::Bool(true));
+        assert!(!condition.is_met(&flags));
+        flags.set("mood", FlagValue::parse("2").unwrap());
+        assert!(condition.is_met(&flags));
+        flags.clear("mood");
+        flags.set("planks", FlagValue::parse("3.5").unwrap());
+        assert!(condition.is_met(&flags));
+        assert!(FlagCondition::default().is_met(&WorldFlags::default()));
+    }
+}